@@ -0,0 +1,119 @@
+//! Per-request work the data plane does in process, timed on its own.
+//!
+//! Run with `cargo bench -p hyperinfer-bench`.  Redis round trips are not
+//! included; `hyperinfer-bench` measures the whole path.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use hyperinfer_client::{ExactMatchCache, KeyPolicies, Router};
+use hyperinfer_core::types::{ChatMessage, MessageRole, RoutingRule, UsageRecord};
+use hyperinfer_core::{tokenizer, ChatRequest, Config, RateLimiter, RpmWindow};
+use std::collections::HashMap;
+use std::hint::black_box;
+
+fn request() -> ChatRequest {
+    ChatRequest {
+        model: "fast".to_string(),
+        messages: vec![
+            ChatMessage {
+                role: MessageRole::System,
+                content: "You are a helpful assistant.".to_string(),
+            },
+            ChatMessage {
+                role: MessageRole::User,
+                content: "Summarize the following support ticket in two sentences. ".repeat(20),
+            },
+        ],
+        max_tokens: Some(256),
+        ..Default::default()
+    }
+}
+
+fn config() -> Config {
+    Config {
+        api_keys: HashMap::from([
+            ("openai".to_string(), "sk-bench".to_string()),
+            ("anthropic".to_string(), "sk-bench".to_string()),
+        ]),
+        routing_rules: vec![RoutingRule {
+            name: "fast".to_string(),
+            priority: 1,
+            fallback_models: vec!["gpt-4o-mini".to_string(), "claude-3-5-haiku".to_string()],
+        }],
+        model_aliases: HashMap::from([("fast".to_string(), "gpt-4o-mini".to_string())]),
+        ..Default::default()
+    }
+}
+
+fn routing(c: &mut Criterion) {
+    let config = config();
+    let router =
+        Router::new(config.routing_rules.clone()).with_aliases(config.model_aliases.clone());
+    c.bench_function("route/resolve_alias", |b| {
+        b.iter(|| router.resolve(None, black_box("fast"), &config))
+    });
+    c.bench_function("route/resolve_model", |b| {
+        b.iter(|| router.resolve(None, black_box("claude-3-5-haiku"), &config))
+    });
+}
+
+fn rate_limiting(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let limiter = RateLimiter::local();
+    c.bench_function("rate_limit/rpm_fixed", |b| {
+        b.to_async(&runtime).iter(|| async {
+            limiter
+                .check_rpm_window(black_box("bench-key"), u64::MAX / 2, RpmWindow::Fixed)
+                .await
+                .unwrap()
+        })
+    });
+    c.bench_function("rate_limit/tpm", |b| {
+        b.to_async(&runtime).iter(|| async {
+            limiter
+                .check_tpm(black_box("bench-key"), u64::MAX / 2, 300)
+                .await
+                .unwrap()
+        })
+    });
+}
+
+fn request_handling(c: &mut Criterion) {
+    let request = request();
+    c.bench_function("request/validate", |b| {
+        b.iter(|| black_box(&request).validate().unwrap())
+    });
+    c.bench_function("request/estimate_tokens", |b| {
+        b.iter(|| tokenizer::estimate_request_tokens(black_box(&request)))
+    });
+    c.bench_function("request/hash_api_key", |b| {
+        b.iter(|| KeyPolicies::hash_key(black_box("sk-team-0123456789abcdef")))
+    });
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    // Never connects: only the key derivation is timed.
+    let cache =
+        runtime.block_on(async { ExactMatchCache::new_lazy("redis://127.0.0.1:6379", "bench") });
+    c.bench_function("request/cache_key", |b| {
+        b.iter(|| cache.cache_key(black_box(&request)))
+    });
+}
+
+fn telemetry(c: &mut Criterion) {
+    let record = UsageRecord {
+        key: "vk:0123456789".to_string(),
+        model: "gpt-4o-mini".to_string(),
+        input_tokens: 312,
+        output_tokens: 48,
+        response_time_ms: 420,
+        timestamp: 1_700_000_000_000,
+        provider: Some("openai".to_string()),
+        metadata: HashMap::from([("feature".to_string(), "summaries".to_string())]),
+        ..Default::default()
+    };
+    c.bench_function("telemetry/serialize_usage", |b| {
+        b.iter(|| serde_json::to_string(black_box(&record)).unwrap())
+    });
+}
+
+criterion_group!(benches, routing, rate_limiting, request_handling, telemetry);
+criterion_main!(benches);
@@ -0,0 +1,197 @@
+//! Load testing support for the HyperInfer data plane.
+//!
+//! The `hyperinfer-bench` binary drives a real [`HyperInferClient`] against
+//! a [`MockProvider`] that answers after a fixed delay, so every millisecond
+//! beyond that delay is overhead the gateway added: key resolution, rate
+//! limiting, routing, caching and telemetry.  The criterion benchmarks in
+//! `benches/` time the in-process pieces of that path on their own.
+//!
+//! [`HyperInferClient`]: hyperinfer_client::HyperInferClient
+
+use async_trait::async_trait;
+use futures::Stream;
+use hyperinfer_client::ProviderTransport;
+use hyperinfer_core::types::{ChatMessage, Choice, MessageRole, Usage};
+use hyperinfer_core::{ChatChunk, ChatRequest, ChatResponse, HyperInferError, Provider};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A provider that answers every request after `latency`, recording how long
+/// each call actually spent inside it.
+pub struct MockProvider {
+    latency: Duration,
+    /// Time spent in the provider per request, keyed by the content of the
+    /// request's last message, which the load generator makes unique.
+    provider_times: Mutex<HashMap<String, Duration>>,
+}
+
+impl MockProvider {
+    pub fn new(latency: Duration) -> Self {
+        Self {
+            latency,
+            provider_times: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Time the provider spent on the request whose last message was
+    /// `content`, forgetting it.
+    pub fn take_provider_time(&self, content: &str) -> Option<Duration> {
+        self.provider_times
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(content)
+    }
+
+    fn response(model: &str) -> ChatResponse {
+        ChatResponse {
+            id: "bench".to_string(),
+            model: model.to_string(),
+            choices: vec![Choice {
+                index: 0,
+                message: ChatMessage {
+                    role: MessageRole::Assistant,
+                    content: "ok".to_string(),
+                },
+                finish_reason: Some("stop".to_string()),
+                thinking: None,
+            }],
+            usage: Usage {
+                input_tokens: 12,
+                output_tokens: 1,
+                thinking_tokens: 0,
+            },
+            ..Default::default()
+        }
+    }
+}
+
+#[async_trait]
+impl ProviderTransport for MockProvider {
+    async fn call_chat(
+        &self,
+        _provider: &Provider,
+        model: &str,
+        _api_key: &str,
+        request: &ChatRequest,
+    ) -> Result<ChatResponse, HyperInferError> {
+        let start = Instant::now();
+        tokio::time::sleep(self.latency).await;
+        if let Some(message) = request.messages.last() {
+            self.provider_times
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(message.content.clone(), start.elapsed());
+        }
+        Ok(Self::response(model))
+    }
+
+    fn call_stream(
+        &self,
+        _provider: &Provider,
+        model: &str,
+        _api_key: &str,
+        _request: &ChatRequest,
+    ) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send + 'static>> {
+        let latency = self.latency;
+        let chunk = ChatChunk {
+            id: "bench".to_string(),
+            model: model.to_string(),
+            delta: "ok".to_string(),
+            finish_reason: Some("stop".to_string()),
+            ..Default::default()
+        };
+        Box::pin(futures::stream::once(async move {
+            tokio::time::sleep(latency).await;
+            Ok(chunk)
+        }))
+    }
+}
+
+/// Percentiles of a set of durations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencySummary {
+    pub count: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LatencySummary {
+    /// Summarize `samples`, or `None` when there are none.
+    pub fn from_samples(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        // Nearest-rank percentile.
+        let percentile = |p: f64| {
+            let rank = ((p / 100.0) * samples.len() as f64).ceil() as usize;
+            samples[rank.clamp(1, samples.len()) - 1]
+        };
+        Some(Self {
+            count: samples.len(),
+            p50: percentile(50.0),
+            p95: percentile(95.0),
+            p99: percentile(99.0),
+            max: samples[samples.len() - 1],
+        })
+    }
+}
+
+impl std::fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        write!(
+            f,
+            "p50 {:.2} ms  p95 {:.2} ms  p99 {:.2} ms  max {:.2} ms",
+            ms(self.p50),
+            ms(self.p95),
+            ms(self.p99),
+            ms(self.max)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_summary_percentiles() {
+        let samples = (1..=100).map(Duration::from_millis).collect();
+        let summary = LatencySummary::from_samples(samples).unwrap();
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.p50, Duration::from_millis(50));
+        assert_eq!(summary.p95, Duration::from_millis(95));
+        assert_eq!(summary.p99, Duration::from_millis(99));
+        assert_eq!(summary.max, Duration::from_millis(100));
+
+        let one = LatencySummary::from_samples(vec![Duration::from_millis(7)]).unwrap();
+        assert_eq!(one.p99, Duration::from_millis(7));
+        assert!(LatencySummary::from_samples(Vec::new()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_mock_provider_records_time_spent() {
+        let provider = MockProvider::new(Duration::from_millis(5));
+        let request = ChatRequest {
+            model: "gpt-4o-mini".to_string(),
+            messages: vec![ChatMessage {
+                role: MessageRole::User,
+                content: "request 1".to_string(),
+            }],
+            ..Default::default()
+        };
+        let response = provider
+            .call_chat(&Provider::OpenAI, "gpt-4o-mini", "key", &request)
+            .await
+            .unwrap();
+        assert_eq!(response.choices[0].message.content, "ok");
+        let spent = provider.take_provider_time("request 1").unwrap();
+        assert!(spent >= Duration::from_millis(5));
+        assert!(provider.take_provider_time("request 1").is_none());
+    }
+}
@@ -0,0 +1,210 @@
+//! Open-loop load test of the data plane.
+//!
+//! Sends chat requests at a fixed rate through a real client, backed by
+//! Redis, to a mock provider that answers after a fixed delay, and reports
+//! the latency the gateway added on top of the provider.  Exits non-zero
+//! when the p99 overhead is over `--max-p99-overhead-ms`, so it can gate CI
+//! or a release.
+
+use clap::Parser;
+use hyperinfer_bench::{LatencySummary, MockProvider};
+use hyperinfer_client::HyperInferClient;
+use hyperinfer_core::types::{ChatMessage, MessageRole};
+use hyperinfer_core::{ChatRequest, Config};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Semaphore};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "hyperinfer-bench",
+    about = "Measure the latency HyperInfer adds on top of the provider"
+)]
+struct Args {
+    /// Redis used for rate limiting, caching and telemetry.
+    #[arg(long, env = "REDIS_URL", default_value = "redis://127.0.0.1:6379")]
+    redis_url: String,
+    /// Requests sent per second.
+    #[arg(long, default_value_t = 200)]
+    rps: u32,
+    /// Seconds to send requests for.
+    #[arg(long, default_value_t = 30)]
+    duration_secs: u64,
+    /// Seconds of unrecorded traffic first, to fill connection pools.
+    #[arg(long, default_value_t = 2)]
+    warmup_secs: u64,
+    /// How long the mock provider takes to answer.
+    #[arg(long, default_value_t = 50)]
+    provider_latency_ms: u64,
+    /// Most requests in flight; requests beyond it are skipped, not queued.
+    #[arg(long, default_value_t = 4096)]
+    concurrency: usize,
+    /// API keys to spread requests over.  Each key gets the default 60
+    /// requests per minute, so the default is twice `--rps`.
+    #[arg(long)]
+    keys: Option<usize>,
+    /// Model requested.
+    #[arg(long, default_value = "gpt-4o-mini")]
+    model: String,
+    /// Fail when the p99 overhead is above this.
+    #[arg(long, default_value_t = 5.0)]
+    max_p99_overhead_ms: f64,
+}
+
+struct Sample {
+    total: Duration,
+    provider: Option<Duration>,
+    error: Option<String>,
+}
+
+fn request(model: &str, id: u64) -> ChatRequest {
+    ChatRequest {
+        model: model.to_string(),
+        messages: vec![
+            ChatMessage {
+                role: MessageRole::System,
+                content: "You are a load test.".to_string(),
+            },
+            ChatMessage {
+                role: MessageRole::User,
+                // Unique, so the response cache never answers.
+                content: format!("bench request {}", id),
+            },
+        ],
+        max_tokens: Some(16),
+        ..Default::default()
+    }
+}
+
+/// Send `count` requests at `rps`, numbering them from `first_id`.
+async fn run(
+    client: Arc<HyperInferClient>,
+    provider: Arc<MockProvider>,
+    args: &Args,
+    first_id: u64,
+    count: u64,
+) -> (Vec<Sample>, u64) {
+    let keys = args.keys.unwrap_or(args.rps as usize * 2).max(1) as u64;
+    let in_flight = Arc::new(Semaphore::new(args.concurrency));
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / args.rps as f64));
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Burst);
+    let mut skipped = 0;
+
+    for id in first_id..first_id + count {
+        ticker.tick().await;
+        let Ok(permit) = in_flight.clone().try_acquire_owned() else {
+            skipped += 1;
+            continue;
+        };
+        let (client, provider, tx) = (client.clone(), provider.clone(), tx.clone());
+        let request = request(&args.model, id);
+        let key = format!("bench-key-{}", id % keys);
+        tokio::spawn(async move {
+            let content = request.messages[1].content.clone();
+            let start = Instant::now();
+            let result = client.chat(&key, request).await;
+            let total = start.elapsed();
+            drop(permit);
+            let _ = tx.send(Sample {
+                total,
+                provider: provider.take_provider_time(&content),
+                error: result.err().map(|e| e.to_string()),
+            });
+        });
+    }
+    drop(tx);
+
+    let mut samples = Vec::new();
+    while let Some(sample) = rx.recv().await {
+        samples.push(sample);
+    }
+    (samples, skipped)
+}
+
+fn report(samples: &[Sample], skipped: u64, elapsed: Duration) -> Option<LatencySummary> {
+    let errors: Vec<&str> = samples.iter().filter_map(|s| s.error.as_deref()).collect();
+    println!(
+        "requests: {} sent, {} ok, {} failed, {} skipped over the concurrency limit",
+        samples.len(),
+        samples.len() - errors.len(),
+        errors.len(),
+        skipped
+    );
+    if let Some(error) = errors.first() {
+        println!("first error: {}", error);
+    }
+    println!(
+        "throughput: {:.1} req/s",
+        samples.len() as f64 / elapsed.as_secs_f64()
+    );
+
+    let ok = || samples.iter().filter(|s| s.error.is_none());
+    if let Some(total) = LatencySummary::from_samples(ok().map(|s| s.total).collect()) {
+        println!("total latency:    {}", total);
+    }
+    let overhead = LatencySummary::from_samples(
+        ok().filter_map(|s| Some(s.total.saturating_sub(s.provider?)))
+            .collect(),
+    );
+    if let Some(overhead) = &overhead {
+        println!("gateway overhead: {}", overhead);
+    }
+    overhead
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    if args.rps == 0 {
+        return Err("--rps must be positive".into());
+    }
+
+    let config = Config {
+        api_keys: HashMap::from([
+            ("openai".to_string(), "sk-bench".to_string()),
+            ("anthropic".to_string(), "sk-bench".to_string()),
+        ]),
+        ..Default::default()
+    };
+    let provider = Arc::new(MockProvider::new(Duration::from_millis(
+        args.provider_latency_ms,
+    )));
+    let client = Arc::new(
+        HyperInferClient::new(&args.redis_url, config)
+            .await?
+            .with_transport(provider.clone()),
+    );
+
+    let warmup = args.warmup_secs * args.rps as u64;
+    if warmup > 0 {
+        println!("warming up for {} s", args.warmup_secs);
+        run(client.clone(), provider.clone(), &args, 0, warmup).await;
+    }
+
+    println!(
+        "sending {} req/s for {} s to a provider answering in {} ms",
+        args.rps, args.duration_secs, args.provider_latency_ms
+    );
+    let start = Instant::now();
+    let (samples, skipped) = run(
+        client,
+        provider,
+        &args,
+        warmup,
+        args.duration_secs * args.rps as u64,
+    )
+    .await;
+    let overhead = report(&samples, skipped, start.elapsed());
+
+    let p99_ms = overhead.map_or(f64::INFINITY, |o| o.p99.as_secs_f64() * 1000.0);
+    if p99_ms > args.max_p99_overhead_ms {
+        eprintln!(
+            "p99 overhead {:.2} ms is over the {:.2} ms budget",
+            p99_ms, args.max_p99_overhead_ms
+        );
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,10 @@
+//! Command-line tools for HyperInfer configs.
+//!
+//! `hyperinfer-cli routes test` checks a candidate [`Config`] against a
+//! file of golden routing cases before it is published, so a change to
+//! aliases, routing rules or provider drains that sends traffic somewhere
+//! unexpected fails CI instead of production; see [`routes`].
+//!
+//! [`Config`]: hyperinfer_core::Config
+
+pub mod routes;
@@ -0,0 +1,117 @@
+//! Command-line tools for HyperInfer configs.
+//!
+//! `hyperinfer-cli routes test --config candidate.json --cases routes.json`
+//! resolves every golden case against the candidate config and exits
+//! non-zero, listing each case with where it went instead, when any routes
+//! somewhere other than expected.  `--update` rewrites the cases file with
+//! the candidate's routes, to accept an intended change.
+
+use clap::{Parser, Subcommand};
+use hyperinfer_cli::routes;
+use hyperinfer_core::Config;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "hyperinfer-cli",
+    about = "Command-line tools for HyperInfer configs"
+)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Routing config checks.
+    Routes {
+        #[command(subcommand)]
+        command: RoutesCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum RoutesCommand {
+    /// Check a candidate config against golden routing cases.
+    Test {
+        /// The candidate config, as JSON in the form `/v1/config/sync`
+        /// serves it.
+        #[arg(long)]
+        config: PathBuf,
+        /// The golden cases, as a JSON array.
+        #[arg(long)]
+        cases: PathBuf,
+        /// Rewrite the cases with the candidate's routes instead of
+        /// checking them.
+        #[arg(long)]
+        update: bool,
+    },
+}
+
+fn describe(route: &Option<routes::ExpectedRoute>) -> String {
+    match route {
+        Some(route) => format!("{}/{}", route.provider, route.target_model),
+        None => "no route".to_string(),
+    }
+}
+
+fn routes_test(
+    config: PathBuf,
+    cases_path: PathBuf,
+    update: bool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let config: Config = serde_json::from_str(&std::fs::read_to_string(&config)?)
+        .map_err(|e| format!("{}: {}", config.display(), e))?;
+    let cases = routes::parse_cases(&std::fs::read_to_string(&cases_path)?)
+        .map_err(|e| format!("{}: {}", cases_path.display(), e))?;
+
+    if update {
+        let updated = routes::update(config, &cases);
+        std::fs::write(&cases_path, serde_json::to_string_pretty(&updated)? + "\n")?;
+        println!(
+            "updated {} cases in {}",
+            updated.len(),
+            cases_path.display()
+        );
+        return Ok(true);
+    }
+
+    let mismatches = routes::check(config, &cases);
+    for mismatch in &mismatches {
+        println!(
+            "FAIL {} (team {}): expected {}, got {}",
+            mismatch.case.model,
+            mismatch.case.team.as_deref().unwrap_or("none"),
+            describe(&mismatch.case.expected),
+            describe(&mismatch.actual)
+        );
+        for step in &mismatch.steps {
+            println!("    {}", serde_json::to_string(step)?);
+        }
+    }
+    println!(
+        "{} cases, {} passed, {} failed",
+        cases.len(),
+        cases.len() - mismatches.len(),
+        mismatches.len()
+    );
+    Ok(mismatches.is_empty())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let passed = match args.command {
+        Command::Routes {
+            command:
+                RoutesCommand::Test {
+                    config,
+                    cases,
+                    update,
+                },
+        } => routes_test(config, cases, update)?,
+    };
+    if !passed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,135 @@
+//! Golden routing cases.
+//!
+//! A cases file is a JSON array of requests, each a model name and
+//! optionally a team, with the provider and target model it is expected
+//! to reach:
+//!
+//! ```json
+//! [
+//!   {"model": "fast", "team": "team-1", "expected": {"provider": "openai", "target_model": "gpt-4o-mini"}},
+//!   {"model": "retired-model", "expected": null}
+//! ]
+//! ```
+//!
+//! `"expected": null` means the model must not resolve at all.  Cases are
+//! resolved with the same [`RouterSnapshot`] clients build from a config,
+//! so they see aliases, routing rules, drains and data regions the way the
+//! data plane does.
+
+use hyperinfer_client::{RouteStep, RouterSnapshot};
+use hyperinfer_core::{Config, Provider};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpectedRoute {
+    pub provider: Provider,
+    pub target_model: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteCase {
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<String>,
+    /// `None` when the model is expected not to resolve.
+    pub expected: Option<ExpectedRoute>,
+}
+
+/// A case whose route differs from the expected one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub case: RouteCase,
+    pub actual: Option<ExpectedRoute>,
+    /// How the route was resolved, for working out what changed.
+    pub steps: Vec<RouteStep>,
+}
+
+pub fn parse_cases(json: &str) -> Result<Vec<RouteCase>, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+/// Where `case` routes under `snapshot`.
+fn route(snapshot: &RouterSnapshot, case: &RouteCase) -> (Option<ExpectedRoute>, Vec<RouteStep>) {
+    let explanation = snapshot
+        .router
+        .explain(case.team.as_deref(), &case.model, &snapshot.config);
+    let actual = explanation.resolved.map(|resolved| ExpectedRoute {
+        provider: resolved.provider,
+        target_model: resolved.model,
+    });
+    (actual, explanation.steps)
+}
+
+/// Resolve every case against `config`, returning those that did not
+/// route as expected, in file order.
+pub fn check(config: Config, cases: &[RouteCase]) -> Vec<Mismatch> {
+    let snapshot = RouterSnapshot::new(config);
+    cases
+        .iter()
+        .filter_map(|case| {
+            let (actual, steps) = route(&snapshot, case);
+            (actual != case.expected).then(|| Mismatch {
+                case: case.clone(),
+                actual,
+                steps,
+            })
+        })
+        .collect()
+}
+
+/// `cases` with their expectations replaced by where they route under
+/// `config`, for accepting an intended routing change.
+pub fn update(config: Config, cases: &[RouteCase]) -> Vec<RouteCase> {
+    let snapshot = RouterSnapshot::new(config);
+    cases
+        .iter()
+        .map(|case| RouteCase {
+            expected: route(&snapshot, case).0,
+            ..case.clone()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config() -> Config {
+        let mut config = Config {
+            model_aliases: HashMap::from([("fast".to_string(), "openai/gpt-4o-mini".to_string())]),
+            ..Default::default()
+        };
+        config.team_model_aliases.insert(
+            "team-1".to_string(),
+            HashMap::from([("fast".to_string(), "anthropic/claude-3-haiku".to_string())]),
+        );
+        config
+    }
+
+    #[test]
+    fn test_check_reports_only_changed_routes() {
+        let cases = parse_cases(
+            r#"[
+                {"model": "fast", "expected": {"provider": "openai", "target_model": "gpt-4o-mini"}},
+                {"model": "fast", "team": "team-1", "expected": {"provider": "openai", "target_model": "gpt-4o-mini"}},
+                {"model": "unknown-model", "expected": null}
+            ]"#,
+        )
+        .unwrap();
+
+        let mismatches = check(config(), &cases);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].case.team.as_deref(), Some("team-1"));
+        assert_eq!(
+            mismatches[0].actual,
+            Some(ExpectedRoute {
+                provider: Provider::Anthropic,
+                target_model: "claude-3-haiku".to_string(),
+            })
+        );
+
+        let updated = update(config(), &cases);
+        assert!(check(config(), &updated).is_empty());
+    }
+}
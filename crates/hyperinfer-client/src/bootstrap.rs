@@ -0,0 +1,323 @@
+//! Booting a data plane from the control plane.
+//!
+//! [`HyperInferClient::from_control_plane`](crate::HyperInferClient::from_control_plane)
+//! fetches its config from the control plane's [`CONFIG_SYNC_PATH`] over
+//! HTTP instead of taking it from the caller, so a data plane needs nothing
+//! but the control plane's address to start.  The fetch is retried with
+//! exponential backoff while the control plane is unreachable or answers
+//! with a server error; a client error such as a rejected admin token fails
+//! at once, since retrying cannot fix it.
+//!
+//! Provider API keys are never serialized into the config, so the synced
+//! config carries none; the data plane supplies its own and keeps them
+//! across config updates.
+//!
+//! Once running, the client reports the config version it holds to Redis
+//! every few seconds under its [`instance_id`], as every client on Redis
+//! does (see [`heartbeat`](crate::heartbeat)), which the control plane's
+//! `/v1/config/fleet` uses to show whether a push has propagated.
+
+use hyperinfer_core::{Config, HyperInferError};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Control-plane endpoint serving the current config.
+pub const CONFIG_SYNC_PATH: &str = "/v1/config/sync";
+
+/// Fetch attempts made before giving up.
+pub const DEFAULT_ATTEMPTS: u32 = 5;
+
+/// Wait before the first retry; doubled after each failure.
+pub const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Longest wait between retries.
+pub const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Timeout for each fetch.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Environment variable naming this data-plane instance in fleet heartbeats.
+pub const INSTANCE_ID_ENV: &str = "HYPERINFER_INSTANCE_ID";
+
+/// This instance's id: [`INSTANCE_ID_ENV`] if set, otherwise a random one.
+pub fn instance_id() -> String {
+    std::env::var(INSTANCE_ID_ENV)
+        .ok()
+        .filter(|id| !id.trim().is_empty())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// Whether a failed fetch is worth retrying.
+fn retryable(error: &HyperInferError) -> bool {
+    match error {
+        HyperInferError::ApiError { kind, .. } => kind.is_retryable(),
+        HyperInferError::Http(_) => true,
+        _ => false,
+    }
+}
+
+async fn fetch_once(
+    http: &reqwest::Client,
+    url: &str,
+    admin_token: &str,
+) -> Result<Config, HyperInferError> {
+    let response = http.get(url).bearer_auth(admin_token).send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let message = response.text().await.unwrap_or_default();
+        return Err(HyperInferError::api_error(status.as_u16(), message));
+    }
+    Ok(response.json().await?)
+}
+
+/// Fetch the config from the control plane at `base_url`, making up to
+/// `attempts` tries.  Returns the last error if none succeeds.
+pub async fn fetch_config(
+    http: &reqwest::Client,
+    base_url: &str,
+    admin_token: &str,
+    attempts: u32,
+    initial_backoff: Duration,
+) -> Result<Config, HyperInferError> {
+    let url = format!("{}{}", base_url.trim_end_matches('/'), CONFIG_SYNC_PATH);
+    let mut backoff = initial_backoff;
+    let mut attempt = 1;
+    loop {
+        match fetch_once(http, &url, admin_token).await {
+            Ok(config) => return Ok(config),
+            Err(e) if attempt < attempts && retryable(&e) => {
+                tracing::warn!(
+                    "Config fetch from {} failed (attempt {}/{}): {}; retrying in {:?}",
+                    url,
+                    attempt,
+                    attempts,
+                    e,
+                    backoff
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Aborts the config subscription and heartbeat tasks on drop.
+pub struct ConfigSubscription(Vec<JoinHandle<()>>);
+
+impl ConfigSubscription {
+    pub fn new(handles: Vec<JoinHandle<()>>) -> Self {
+        Self(handles)
+    }
+}
+
+impl Drop for ConfigSubscription {
+    fn drop(&mut self) {
+        for handle in &self.0 {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve `responses` in order, one per connection, and count requests.
+    async fn serve(responses: Vec<(u16, String)>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            for (status, body) in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8_lossy(&request).to_lowercase();
+                assert!(request.starts_with("get /v1/config/sync "));
+                assert!(request.contains("authorization: bearer admin-token"));
+                counter.fetch_add(1, Ordering::SeqCst);
+                let reply = format!(
+                    "HTTP/1.1 {} X\r\ncontent-type: application/json\r\n\
+                     content-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                socket.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+        (url, hits)
+    }
+
+    fn config_json() -> String {
+        serde_json::to_string(&Config {
+            default_provider: Some(hyperinfer_core::Provider::Anthropic),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fetch_config_retries_server_errors() {
+        let (url, hits) = serve(vec![
+            (503, "starting".to_string()),
+            (502, "bad gateway".to_string()),
+            (200, config_json()),
+        ])
+        .await;
+        let config = fetch_config(
+            &reqwest::Client::new(),
+            &format!("{}/", url),
+            "admin-token",
+            5,
+            Duration::from_millis(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            config.default_provider,
+            Some(hyperinfer_core::Provider::Anthropic)
+        );
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    /// Answers chat calls with the API key it was given as the id.
+    #[cfg(feature = "redis")]
+    struct KeyEchoTransport;
+
+    #[cfg(feature = "redis")]
+    #[async_trait::async_trait]
+    impl crate::ProviderTransport for KeyEchoTransport {
+        async fn call_chat(
+            &self,
+            _provider: &hyperinfer_core::Provider,
+            model: &str,
+            api_key: &str,
+            _request: &hyperinfer_core::ChatRequest,
+        ) -> Result<hyperinfer_core::ChatResponse, HyperInferError> {
+            Ok(hyperinfer_core::ChatResponse {
+                id: api_key.to_string(),
+                model: model.to_string(),
+                ..Default::default()
+            })
+        }
+
+        fn call_stream(
+            &self,
+            _provider: &hyperinfer_core::Provider,
+            _model: &str,
+            _api_key: &str,
+            _request: &hyperinfer_core::ChatRequest,
+        ) -> std::pin::Pin<
+            Box<
+                dyn futures::Stream<Item = Result<hyperinfer_core::ChatChunk, HyperInferError>>
+                    + Send
+                    + 'static,
+            >,
+        > {
+            Box::pin(futures::stream::empty())
+        }
+    }
+
+    #[cfg(feature = "redis")]
+    #[tokio::test]
+    async fn test_client_boots_while_redis_is_unreachable() {
+        let (url, _hits) = serve(vec![(200, config_json())]).await;
+        let client = crate::HyperInferClient::from_control_plane(
+            &url,
+            "admin-token",
+            HashMap::new(),
+            "redis://127.0.0.1:1",
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            client.snapshot.load().config.default_provider,
+            Some(hyperinfer_core::Provider::Anthropic)
+        );
+    }
+
+    #[cfg(feature = "redis")]
+    #[tokio::test]
+    async fn test_booted_client_chats_with_local_provider_keys() {
+        let (url, _hits) = serve(vec![(200, config_json())]).await;
+        let api_keys = HashMap::from([("openai".to_string(), "sk-local".to_string())]);
+        let mut client = crate::HyperInferClient::from_control_plane(
+            &url,
+            "admin-token",
+            api_keys,
+            "redis://127.0.0.1:1",
+        )
+        .await
+        .unwrap()
+        .with_transport(Arc::new(KeyEchoTransport));
+        // Redis is unreachable here, so keep rate limits in the process.
+        client.rate_limiter = hyperinfer_core::rate_limiting::RateLimiter::local();
+        let request = || hyperinfer_core::ChatRequest {
+            model: "gpt-4".to_string(),
+            messages: vec![hyperinfer_core::ChatMessage {
+                role: hyperinfer_core::MessageRole::User,
+                content: "hi".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        let response = client.chat("user-1", request()).await.unwrap();
+        assert_eq!(response.id, "sk-local");
+
+        // A config push arrives without keys, which are never serialized.
+        let mut pushed: Config = serde_json::from_str(&config_json()).unwrap();
+        pushed.version = 2;
+        assert!(client.snapshot.apply_update(pushed));
+        let response = client.chat("user-1", request()).await.unwrap();
+        assert_eq!(response.id, "sk-local");
+    }
+
+    #[tokio::test]
+    async fn test_fetch_config_does_not_retry_client_errors() {
+        let (url, hits) = serve(vec![(401, "Unauthorized".to_string())]).await;
+        let err = fetch_config(
+            &reqwest::Client::new(),
+            &url,
+            "admin-token",
+            5,
+            Duration::from_millis(1),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, HyperInferError::ApiError { status: 401, .. }));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_config_gives_up_after_attempts() {
+        let (url, hits) = serve(vec![
+            (500, "down".to_string()),
+            (500, "still down".to_string()),
+        ])
+        .await;
+        let err = fetch_config(
+            &reqwest::Client::new(),
+            &url,
+            "admin-token",
+            2,
+            Duration::from_millis(1),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, HyperInferError::ApiError { status: 500, .. }));
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+}
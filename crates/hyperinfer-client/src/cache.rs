@@ -10,13 +10,21 @@
 //!
 //! The cache gracefully degrades: if Redis is unavailable all `get`/`set`
 //! calls return `None`/`Ok(())` without surfacing errors to the caller.
+//! Without the `redis` feature it never holds anything.
 
 use hyperinfer_core::{ChatRequest, ChatResponse};
+#[cfg(feature = "redis")]
+use hyperinfer_core::{RedisHandle, RedisOptions};
+#[cfg(feature = "redis")]
 use redis::{aio::ConnectionManager, AsyncCommands};
 use sha2::{Digest, Sha256};
+#[cfg(feature = "redis")]
 use std::sync::Arc;
+#[cfg(feature = "redis")]
 use tokio::sync::Mutex;
-use tracing::{debug, warn};
+#[cfg(feature = "redis")]
+use tracing::debug;
+use tracing::warn;
 
 /// Default TTL for cached responses (5 minutes).
 pub const DEFAULT_TTL_SECS: u64 = 300;
@@ -24,6 +32,7 @@ pub const DEFAULT_TTL_SECS: u64 = 300;
 /// Exact-match Redis cache for [`ChatResponse`] values.
 #[derive(Clone)]
 pub struct ExactMatchCache {
+    #[cfg(feature = "redis")]
     conn: Option<Arc<Mutex<ConnectionManager>>>,
     ttl_secs: u64,
     /// Namespace for cache keys to avoid cross-client collisions.
@@ -31,10 +40,21 @@ pub struct ExactMatchCache {
 }
 
 impl ExactMatchCache {
+    /// A cache that never holds anything.
+    pub fn disabled(namespace: &str) -> Self {
+        Self {
+            #[cfg(feature = "redis")]
+            conn: None,
+            ttl_secs: DEFAULT_TTL_SECS,
+            namespace: namespace.to_string(),
+        }
+    }
+
     /// Connect to Redis at `redis_url`.  On failure the cache is disabled and
     /// all operations become no-ops.
+    #[cfg(feature = "redis")]
     pub async fn new(redis_url: &str, namespace: &str) -> Self {
-        match redis::Client::open(redis_url) {
+        match RedisOptions::new(redis_url).client() {
             Ok(client) => match ConnectionManager::new(client).await {
                 Ok(mgr) => {
                     debug!("ExactMatchCache: connected to Redis");
@@ -67,6 +87,37 @@ impl ExactMatchCache {
         }
     }
 
+    /// Like [`ExactMatchCache::new`], but connects on first use, so the
+    /// cache starts working once an unreachable Redis comes back.
+    #[cfg(feature = "redis")]
+    pub fn new_lazy(redis_url: &str, namespace: &str) -> Self {
+        let conn = match RedisOptions::new(redis_url)
+            .client()
+            .and_then(hyperinfer_core::redis::lazy_connection_manager)
+        {
+            Ok(mgr) => Some(Arc::new(Mutex::new(mgr))),
+            Err(e) => {
+                warn!("ExactMatchCache: invalid Redis URL: {}; cache disabled", e);
+                None
+            }
+        };
+        Self {
+            conn,
+            ttl_secs: DEFAULT_TTL_SECS,
+            namespace: namespace.to_string(),
+        }
+    }
+
+    /// A cache on the shared connection of `redis`.
+    #[cfg(feature = "redis")]
+    pub fn with_redis(redis: &RedisHandle, namespace: &str) -> Self {
+        Self {
+            conn: Some(Arc::new(Mutex::new(redis.connection()))),
+            ttl_secs: DEFAULT_TTL_SECS,
+            namespace: namespace.to_string(),
+        }
+    }
+
     /// Override the cache TTL.  Returns `self` for chaining.
     pub fn with_ttl(mut self, secs: u64) -> Self {
         self.ttl_secs = secs;
@@ -75,9 +126,11 @@ impl ExactMatchCache {
 
     /// Compute the cache key for `request`.
     pub fn cache_key(&self, request: &ChatRequest) -> Option<String> {
-        // Clone and normalize to ignore streaming preference
+        // Clone and normalize to ignore streaming preference and
+        // attribution tags, which do not affect the response.
         let mut normalized_request = request.clone();
         normalized_request.stream = None;
+        normalized_request.metadata.clear();
 
         match serde_json::to_string(&normalized_request) {
             Ok(json) => {
@@ -96,6 +149,15 @@ impl ExactMatchCache {
     /// Attempt to retrieve a cached [`ChatResponse`] for `request`.
     ///
     /// Returns `None` on cache miss, Redis error, or deserialisation failure.
+    #[cfg(not(feature = "redis"))]
+    pub async fn get(&self, _request: &ChatRequest) -> Option<ChatResponse> {
+        None
+    }
+
+    /// Attempt to retrieve a cached [`ChatResponse`] for `request`.
+    ///
+    /// Returns `None` on cache miss, Redis error, or deserialisation failure.
+    #[cfg(feature = "redis")]
     pub async fn get(&self, request: &ChatRequest) -> Option<ChatResponse> {
         let conn = self.conn.as_ref()?;
         let key = self.cache_key(request)?;
@@ -120,6 +182,13 @@ impl ExactMatchCache {
     /// Store `response` in the cache under the key derived from `request`.
     ///
     /// Silently ignores serialisation and Redis errors.
+    #[cfg(not(feature = "redis"))]
+    pub async fn set(&self, _request: &ChatRequest, _response: &ChatResponse) {}
+
+    /// Store `response` in the cache under the key derived from `request`.
+    ///
+    /// Silently ignores serialisation and Redis errors.
+    #[cfg(feature = "redis")]
     pub async fn set(&self, request: &ChatRequest, response: &ChatResponse) {
         let conn = match self.conn.as_ref() {
             Some(c) => c,
@@ -152,7 +221,7 @@ impl ExactMatchCache {
 
 // ── Tests ─────────────────────────────────────────────────────────────────────
 
-#[cfg(test)]
+#[cfg(all(test, feature = "redis"))]
 mod tests {
     use super::*;
     use hyperinfer_core::{
@@ -171,6 +240,15 @@ mod tests {
             temperature: None,
             stream: None,
             stop: None,
+            metadata: std::collections::HashMap::new(),
+            response_format: None,
+            compression: None,
+            reasoning_effort: None,
+            thinking: None,
+            dry_run: false,
+            context_overflow: None,
+            routing: None,
+            data_region: None,
         }
     }
 
@@ -185,11 +263,15 @@ mod tests {
                 },
                 finish_reason: Some("stop".to_string()),
                 index: 0,
+                thinking: None,
             }],
             usage: Usage {
                 input_tokens: 5,
                 output_tokens: 10,
+                thinking_tokens: 0,
             },
+            metadata: std::collections::HashMap::new(),
+            dry_run: None,
         }
     }
 
@@ -237,6 +319,20 @@ mod tests {
         assert_ne!(cache.cache_key(&r1), cache.cache_key(&r2));
     }
 
+    #[test]
+    fn test_cache_key_ignores_metadata() {
+        let cache = ExactMatchCache {
+            conn: None,
+            ttl_secs: DEFAULT_TTL_SECS,
+            namespace: "test-ns".to_string(),
+        };
+        let r1 = sample_request("gpt-4");
+        let mut r2 = sample_request("gpt-4");
+        r2.metadata
+            .insert("customer_id".to_string(), "cust-42".to_string());
+        assert_eq!(cache.cache_key(&r1), cache.cache_key(&r2));
+    }
+
     #[test]
     fn test_cache_key_ignores_stream() {
         let cache = ExactMatchCache {
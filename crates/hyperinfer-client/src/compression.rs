@@ -0,0 +1,240 @@
+//! Prompt compression.
+//!
+//! Requests with `ChatRequest::compression` set have their long messages —
+//! typically retrieved documents in a RAG prompt — shortened before dispatch.
+//! Compression is extractive and runs locally: whitespace is collapsed,
+//! repeated sentences (overlapping retrieval chunks) are removed, and then
+//! the sentences sharing the fewest words with the latest user message are
+//! dropped until the message is down to `ratio` of its original estimate.
+//! The first and last sentence of every message are kept, so instructions
+//! and a trailing question survive.
+//!
+//! The estimated prompt size before and after is returned as
+//! [`CompressionStats`] and recorded with the request's usage.
+
+use hyperinfer_core::{tokenizer, ChatRequest, CompressionOptions, CompressionStats, MessageRole};
+use std::collections::HashSet;
+
+/// Words too common to say anything about relevance.
+const STOPWORDS: &[&str] = &[
+    "about", "also", "and", "are", "but", "can", "did", "does", "for", "from", "had", "has",
+    "have", "her", "his", "how", "its", "not", "our", "she", "that", "the", "their", "them",
+    "then", "there", "these", "they", "this", "was", "were", "what", "when", "where", "which",
+    "who", "why", "will", "with", "would", "you", "your",
+];
+
+fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= 3)
+        .map(str::to_lowercase)
+        .filter(|w| !STOPWORDS.contains(&w.as_str()))
+}
+
+/// Split `text` into sentences, each keeping its terminator.
+fn sentences(text: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let boundary = c == '\n'
+            || (matches!(c, '.' | '!' | '?')
+                && chars.peek().is_none_or(|(_, next)| next.is_whitespace()));
+        if boundary {
+            let end = i + c.len_utf8();
+            let sentence = text[start..end].trim();
+            if !sentence.is_empty() {
+                out.push(sentence);
+            }
+            start = end;
+        }
+    }
+    let rest = text[start..].trim();
+    if !rest.is_empty() {
+        out.push(rest);
+    }
+    out
+}
+
+/// Compress `text` to about `ratio` of its estimated tokens, preferring
+/// sentences that share words with `query`.
+pub fn compress_text(text: &str, ratio: f64, query: &HashSet<String>) -> String {
+    let original = tokenizer::estimate_text_tokens(text);
+    let target = (original as f64 * ratio).ceil() as u32;
+
+    // Distinct sentences in order; whitespace inside them collapsed.
+    let mut seen = HashSet::new();
+    let sentences: Vec<String> = sentences(text)
+        .into_iter()
+        .map(|s| s.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|s| seen.insert(s.to_lowercase()))
+        .collect();
+    let cost = |s: &String| tokenizer::estimate_text_tokens(s) + 1;
+    let total: u32 = sentences.iter().map(cost).sum();
+    if total <= target || sentences.len() <= 2 {
+        return sentences.join(" ");
+    }
+
+    let last = sentences.len() - 1;
+    let mut ranked: Vec<(usize, f64)> = sentences
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let score = if i == 0 || i == last {
+                f64::INFINITY
+            } else {
+                let words: Vec<String> = terms(s).collect();
+                let hits = words.iter().filter(|w| query.contains(*w)).count();
+                hits as f64 / (words.len() as f64 + 1.0).sqrt()
+            };
+            (i, score)
+        })
+        .collect();
+    // Most relevant first; earlier sentences win ties.
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut keep = vec![false; sentences.len()];
+    let mut used = 0;
+    for (i, score) in ranked {
+        let c = cost(&sentences[i]);
+        if score.is_infinite() || used + c <= target {
+            keep[i] = true;
+            used += c;
+        }
+    }
+    sentences
+        .iter()
+        .zip(keep)
+        .filter_map(|(s, keep)| keep.then_some(s.as_str()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Compress the messages of `request` at or above
+/// `options.min_message_tokens`.  Returns `None` when nothing qualified.
+pub fn compress_request(
+    request: &mut ChatRequest,
+    options: &CompressionOptions,
+) -> Option<CompressionStats> {
+    let original_tokens = tokenizer::estimate_request_tokens(request);
+    let query: HashSet<String> = request
+        .messages
+        .iter()
+        .rev()
+        .find(|m| m.role == MessageRole::User)
+        .map(|m| terms(&m.content).collect())
+        .unwrap_or_default();
+    let mut compressed_any = false;
+    for message in &mut request.messages {
+        if tokenizer::estimate_text_tokens(&message.content) < options.min_message_tokens {
+            continue;
+        }
+        message.content = compress_text(&message.content, options.ratio, &query);
+        compressed_any = true;
+    }
+    compressed_any.then(|| CompressionStats {
+        original_tokens,
+        compressed_tokens: tokenizer::estimate_request_tokens(request),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyperinfer_core::ChatMessage;
+
+    fn query(text: &str) -> HashSet<String> {
+        terms(text).collect()
+    }
+
+    #[test]
+    fn test_sentences() {
+        assert_eq!(
+            sentences("First one. Second one!\nThird  line\nv1.2 is out? yes"),
+            vec![
+                "First one.",
+                "Second one!",
+                "Third  line",
+                "v1.2 is out?",
+                "yes"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_short_text_only_normalized() {
+        let text = "Paris is the capital.   It is   in France.";
+        assert_eq!(
+            compress_text(text, 0.1, &HashSet::new()),
+            "Paris is the capital. It is in France."
+        );
+    }
+
+    #[test]
+    fn test_duplicate_sentences_removed() {
+        let text = "Intro. The tower is 330 metres tall. The tower is 330 metres tall. End.";
+        assert_eq!(
+            compress_text(text, 1.0, &HashSet::new()),
+            "Intro. The tower is 330 metres tall. End."
+        );
+    }
+
+    #[test]
+    fn test_keeps_relevant_sentences() {
+        let filler = "Unrelated remarks about weather patterns in distant mountains.";
+        let text = format!(
+            "Document start. {f} {f2} The Eiffel Tower is 330 metres tall. {f3} Document end.",
+            f = filler,
+            f2 = "Many tourists enjoy croissants and coffee every morning.",
+            f3 = "Trains connect several regional cities each afternoon.",
+        );
+        let compressed = compress_text(&text, 0.5, &query("How tall is the Eiffel Tower?"));
+        assert!(compressed.starts_with("Document start."));
+        assert!(compressed.ends_with("Document end."));
+        assert!(compressed.contains("330 metres"));
+        assert!(!compressed.contains("weather"));
+        assert!(
+            tokenizer::estimate_text_tokens(&compressed)
+                <= (tokenizer::estimate_text_tokens(&text) as f64 * 0.5).ceil() as u32 + 2
+        );
+    }
+
+    #[test]
+    fn test_compress_request_skips_short_messages() {
+        let context: String = (0..100)
+            .map(|i| format!("Fact number {} about topic {}.", i, i % 7))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut request = ChatRequest {
+            model: "gpt-4o".to_string(),
+            messages: vec![
+                ChatMessage {
+                    role: MessageRole::System,
+                    content: context.clone(),
+                },
+                ChatMessage {
+                    role: MessageRole::User,
+                    content: "What about topic 3?".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+        let options = CompressionOptions {
+            ratio: 0.3,
+            min_message_tokens: 100,
+        };
+        let stats = compress_request(&mut request, &options).unwrap();
+        assert!(stats.compressed_tokens < stats.original_tokens);
+        assert!(stats.saved_tokens() > 0);
+        assert!(request.messages[0].content.len() < context.len());
+        assert_eq!(request.messages[1].content, "What about topic 3?");
+
+        let mut short = ChatRequest {
+            messages: vec![ChatMessage {
+                role: MessageRole::User,
+                content: "hi".to_string(),
+            }],
+            ..Default::default()
+        };
+        assert_eq!(compress_request(&mut short, &options), None);
+    }
+}
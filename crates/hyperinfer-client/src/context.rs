@@ -0,0 +1,423 @@
+//! Context-window management.
+//!
+//! With `Config::context` set, a request whose estimated prompt would not
+//! fit the resolved model's context window, less its output budget, is
+//! shortened before it is sent (see [`ContextStrategy`]).  System messages
+//! and the latest message are always kept, and a conversation never starts
+//! with an assistant turn after trimming.
+//!
+//! Estimates come from [`tokenizer`] and err high, so a trimmed request
+//! leaves some headroom.  A request that still does not fit once nothing
+//! more can be dropped is sent anyway and left to the provider to reject.
+//! The summary call of [`ContextStrategy::Summarize`] is made with the
+//! caller's request but is not rate limited or recorded separately.
+
+use hyperinfer_core::{
+    tokenizer, ChatMessage, ChatRequest, ContextStrategy, HyperInferError, MessageRole,
+};
+use hyperinfer_providers::LlmProvider;
+use std::sync::Arc;
+
+/// Output budget of a summary call; also reserved in the prompt for the
+/// summary it produces.
+pub const SUMMARY_MAX_TOKENS: u32 = 512;
+
+const SUMMARY_PROMPT: &str = "Summarize the following conversation in a few sentences. \
+Keep names, numbers, decisions and open questions; leave out pleasantries.";
+
+/// The model that writes summaries for [`ContextStrategy::Summarize`].
+#[derive(Clone)]
+pub struct Summarizer {
+    pub provider: Arc<dyn LlmProvider>,
+    pub model: String,
+    pub api_key: String,
+    /// Data region of the conversation being summarized.
+    pub data_region: Option<String>,
+}
+
+/// Tokens left for the prompt in a `window`-token context once
+/// `max_tokens` are set aside for the reply.
+pub fn input_budget(window: u32, max_tokens: Option<u32>) -> u32 {
+    window.saturating_sub(max_tokens.unwrap_or(0))
+}
+
+/// Window to trim `request` to after the provider rejected it as too long
+/// for `model_window` (`None` when unknown).  The estimate evidently ran
+/// low, so the prompt is cut by a quarter, and to at most four fifths of the
+/// model's window.
+pub fn recovery_window(request: &ChatRequest, model_window: Option<u32>) -> u32 {
+    let prompt = tokenizer::estimate_request_tokens(request);
+    let shrunk = prompt / 4 * 3 + request.max_tokens.unwrap_or(0);
+    model_window.map_or(shrunk, |window| shrunk.min(window / 5 * 4))
+}
+
+/// Indices of the messages that may be removed, oldest first: everything
+/// but system messages and the last message.
+fn droppable(messages: &[ChatMessage]) -> Vec<usize> {
+    let last = messages.len().saturating_sub(1);
+    (0..last)
+        .filter(|&i| messages[i].role != MessageRole::System)
+        .collect()
+}
+
+/// Remove the messages at `indices` (ascending) and return them in order.
+fn remove_at(messages: &mut Vec<ChatMessage>, indices: &[usize]) -> Vec<ChatMessage> {
+    let mut removed = Vec::with_capacity(indices.len());
+    for &i in indices.iter().rev() {
+        removed.push(messages.remove(i));
+    }
+    removed.reverse();
+    removed
+}
+
+/// Drop the oldest droppable messages until the request's estimate is
+/// within `budget`, then any assistant turns left at the start of the
+/// conversation.  Returns the removed messages, oldest first.
+pub fn drop_oldest(request: &mut ChatRequest, budget: u32) -> Vec<ChatMessage> {
+    let mut excess = tokenizer::estimate_request_tokens(request).saturating_sub(budget);
+    let mut drop = Vec::new();
+    let mut candidates = droppable(&request.messages).into_iter().peekable();
+    while excess > 0 {
+        let Some(i) = candidates.next() else { break };
+        excess = excess.saturating_sub(tokenizer::estimate_message_tokens(&request.messages[i]));
+        drop.push(i);
+    }
+    // The first kept turn must come from the user.
+    while let Some(&i) = candidates.peek() {
+        if drop.is_empty() || request.messages[i].role != MessageRole::Assistant {
+            break;
+        }
+        drop.push(i);
+        candidates.next();
+    }
+    remove_at(&mut request.messages, &drop)
+}
+
+/// Keep at most `max_messages` non-system messages (never fewer than the
+/// latest one).  Returns the removed messages, oldest first.
+pub fn sliding_window(request: &mut ChatRequest, max_messages: usize) -> Vec<ChatMessage> {
+    let candidates = droppable(&request.messages);
+    // The last message is not a candidate but counts against the window.
+    let keep = max_messages.saturating_sub(1);
+    let drop_count = candidates.len().saturating_sub(keep);
+    let mut drop: Vec<usize> = candidates[..drop_count].to_vec();
+    for &i in &candidates[drop_count..] {
+        if drop.is_empty() || request.messages[i].role != MessageRole::Assistant {
+            break;
+        }
+        drop.push(i);
+    }
+    remove_at(&mut request.messages, &drop)
+}
+
+/// Ask `summarizer` to summarize `messages`.
+pub async fn summarize(
+    summarizer: &Summarizer,
+    messages: &[ChatMessage],
+) -> Result<String, HyperInferError> {
+    let transcript = messages
+        .iter()
+        .map(|m| {
+            let role = match m.role {
+                MessageRole::System => "system",
+                MessageRole::User => "user",
+                MessageRole::Assistant => "assistant",
+            };
+            format!("{}: {}", role, m.content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let request = ChatRequest {
+        model: summarizer.model.clone(),
+        messages: vec![
+            ChatMessage {
+                role: MessageRole::System,
+                content: SUMMARY_PROMPT.to_string(),
+            },
+            ChatMessage {
+                role: MessageRole::User,
+                content: transcript,
+            },
+        ],
+        max_tokens: Some(SUMMARY_MAX_TOKENS),
+        data_region: summarizer.data_region.clone(),
+        ..Default::default()
+    };
+    let response = summarizer
+        .provider
+        .chat(&request, &summarizer.api_key)
+        .await?;
+    Ok(response
+        .choices
+        .into_iter()
+        .next()
+        .map(|c| c.message.content)
+        .unwrap_or_default())
+}
+
+/// Shorten `request` to fit a `window`-token context with `strategy`.
+/// Returns how many of the original messages were removed.
+pub async fn fit_to_window(
+    request: &mut ChatRequest,
+    window: u32,
+    strategy: &ContextStrategy,
+    summarizer: Option<&Summarizer>,
+) -> usize {
+    let budget = input_budget(window, request.max_tokens);
+    if tokenizer::estimate_request_tokens(request) <= budget {
+        return 0;
+    }
+    let removed = match (strategy, summarizer) {
+        (ContextStrategy::DropOldest, _) | (ContextStrategy::Summarize { .. }, None) => {
+            drop_oldest(request, budget).len()
+        }
+        (ContextStrategy::SlidingWindow { max_messages }, _) => {
+            sliding_window(request, *max_messages).len() + drop_oldest(request, budget).len()
+        }
+        (ContextStrategy::Summarize { .. }, Some(summarizer)) => {
+            let reserved = SUMMARY_MAX_TOKENS + tokenizer::MESSAGE_OVERHEAD_TOKENS;
+            let dropped = drop_oldest(request, budget.saturating_sub(reserved));
+            if !dropped.is_empty() {
+                match summarize(summarizer, &dropped).await {
+                    Ok(summary) if !summary.trim().is_empty() => {
+                        // After the leading system messages, where the
+                        // dropped turns used to start.
+                        let at = request
+                            .messages
+                            .iter()
+                            .position(|m| m.role != MessageRole::System)
+                            .unwrap_or(request.messages.len());
+                        request.messages.insert(
+                            at,
+                            ChatMessage {
+                                role: MessageRole::System,
+                                content: format!(
+                                    "Summary of the earlier conversation: {}",
+                                    summary
+                                ),
+                            },
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!(
+                        "Summarizing trimmed context with {} failed, dropping it instead: {}",
+                        summarizer.model,
+                        e
+                    ),
+                }
+            }
+            dropped.len()
+        }
+    };
+    let estimate = tokenizer::estimate_request_tokens(request);
+    if estimate > budget {
+        tracing::warn!(
+            "Request for {} still needs ~{} prompt tokens after trimming, over the {} available",
+            request.model,
+            estimate,
+            budget
+        );
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use futures::Stream;
+    use hyperinfer_core::{ChatChunk, ChatResponse, Choice};
+    use std::pin::Pin;
+    use std::sync::Mutex;
+
+    fn message(role: MessageRole, content: &str) -> ChatMessage {
+        ChatMessage {
+            role,
+            content: content.to_string(),
+        }
+    }
+
+    /// A system prompt and `turns` user/assistant pairs of ~25 tokens each,
+    /// ending with a user question.
+    fn conversation(turns: usize) -> ChatRequest {
+        let mut messages = vec![message(MessageRole::System, "You are helpful.")];
+        for i in 0..turns {
+            messages.push(message(
+                MessageRole::User,
+                &format!("question {} {}", i, "x".repeat(80)),
+            ));
+            messages.push(message(
+                MessageRole::Assistant,
+                &format!("answer {} {}", i, "y".repeat(80)),
+            ));
+        }
+        messages.push(message(MessageRole::User, "latest question"));
+        ChatRequest {
+            model: "gpt-4".to_string(),
+            messages,
+            ..Default::default()
+        }
+    }
+
+    fn contents(request: &ChatRequest) -> Vec<&str> {
+        request
+            .messages
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_input_budget() {
+        assert_eq!(input_budget(8192, Some(1000)), 7192);
+        assert_eq!(input_budget(8192, None), 8192);
+        assert_eq!(input_budget(100, Some(1000)), 0);
+    }
+
+    #[test]
+    fn test_recovery_window() {
+        let mut request = conversation(6);
+        request.max_tokens = Some(100);
+        let prompt = tokenizer::estimate_request_tokens(&request);
+        assert_eq!(recovery_window(&request, None), prompt / 4 * 3 + 100);
+        assert_eq!(recovery_window(&request, Some(200)), 160);
+        assert!(recovery_window(&request, Some(1_000_000)) < prompt + 100);
+    }
+
+    #[test]
+    fn test_drop_oldest_keeps_system_and_latest() {
+        let mut request = conversation(5);
+        let full = tokenizer::estimate_request_tokens(&request);
+        let removed = drop_oldest(&mut request, full - 40);
+        // Two messages are needed to free 40 tokens; the turn then starts
+        // with a user message.
+        assert_eq!(removed.len(), 2);
+        assert!(removed[0].content.starts_with("question 0"));
+        assert_eq!(request.messages[0].role, MessageRole::System);
+        assert!(request.messages[1].content.starts_with("question 1"));
+        assert_eq!(request.messages.last().unwrap().content, "latest question");
+    }
+
+    #[test]
+    fn test_drop_oldest_never_starts_with_assistant() {
+        let mut request = conversation(3);
+        let full = tokenizer::estimate_request_tokens(&request);
+        let removed = drop_oldest(&mut request, full - 10);
+        assert_eq!(removed.len(), 2);
+        assert_eq!(request.messages[1].role, MessageRole::User);
+    }
+
+    #[test]
+    fn test_drop_oldest_stops_at_latest_message() {
+        let mut request = conversation(3);
+        drop_oldest(&mut request, 0);
+        assert_eq!(
+            contents(&request),
+            vec!["You are helpful.", "latest question"]
+        );
+    }
+
+    #[test]
+    fn test_sliding_window() {
+        let mut request = conversation(5);
+        let removed = sliding_window(&mut request, 3);
+        assert_eq!(removed.len(), 8);
+        assert_eq!(request.messages.len(), 4);
+        assert!(request.messages[1].content.starts_with("question 4"));
+        assert!(request.messages[2].content.starts_with("answer 4"));
+
+        // An even window would start on an assistant turn, which is dropped.
+        let mut request = conversation(5);
+        sliding_window(&mut request, 2);
+        assert_eq!(
+            contents(&request),
+            vec!["You are helpful.", "latest question"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fit_to_window_leaves_fitting_request_alone() {
+        let mut request = conversation(2);
+        let removed = fit_to_window(&mut request, 8192, &ContextStrategy::DropOldest, None).await;
+        assert_eq!(removed, 0);
+        assert_eq!(request, conversation(2));
+    }
+
+    #[tokio::test]
+    async fn test_fit_to_window_reserves_output_budget() {
+        let mut request = conversation(10);
+        let full = tokenizer::estimate_request_tokens(&request);
+        request.max_tokens = Some(100);
+        let removed =
+            fit_to_window(&mut request, full + 50, &ContextStrategy::DropOldest, None).await;
+        assert!(removed > 0);
+        assert!(tokenizer::estimate_request_tokens(&request) <= full - 50);
+    }
+
+    /// Replies with a fixed summary and records the transcript it was sent.
+    #[derive(Clone)]
+    struct FixedSummary(Arc<Mutex<Vec<ChatRequest>>>);
+
+    #[async_trait]
+    impl LlmProvider for FixedSummary {
+        fn name(&self) -> &str {
+            "summary"
+        }
+
+        async fn chat(
+            &self,
+            request: &ChatRequest,
+            _api_key: &str,
+        ) -> Result<ChatResponse, HyperInferError> {
+            self.0.lock().unwrap().push(request.clone());
+            Ok(ChatResponse {
+                choices: vec![Choice {
+                    index: 0,
+                    message: message(MessageRole::Assistant, "they asked five questions"),
+                    finish_reason: Some("stop".to_string()),
+                    thinking: None,
+                }],
+                ..Default::default()
+            })
+        }
+
+        fn stream(
+            &self,
+            _request: &ChatRequest,
+            _api_key: &str,
+        ) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send + 'static>>
+        {
+            Box::pin(futures::stream::empty())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_summarize_replaces_dropped_messages() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let summarizer = Summarizer {
+            provider: Arc::new(FixedSummary(calls.clone())),
+            model: "gpt-4o-mini".to_string(),
+            api_key: "key".to_string(),
+            data_region: None,
+        };
+        let mut request = conversation(20);
+        let full = tokenizer::estimate_request_tokens(&request);
+        let strategy = ContextStrategy::Summarize {
+            model: "gpt-4o-mini".to_string(),
+        };
+        let removed = fit_to_window(&mut request, full - 100, &strategy, Some(&summarizer)).await;
+        assert!(removed > 0);
+        assert_eq!(request.messages[0].content, "You are helpful.");
+        assert_eq!(
+            request.messages[1].content,
+            "Summary of the earlier conversation: they asked five questions"
+        );
+        assert_eq!(request.messages[2].role, MessageRole::User);
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].model, "gpt-4o-mini");
+        assert_eq!(calls[0].max_tokens, Some(SUMMARY_MAX_TOKENS));
+        assert!(calls[0].messages[1].content.starts_with("user: question 0"));
+    }
+}
@@ -0,0 +1,131 @@
+//! Server-side conversation history.
+//!
+//! The control plane can keep chat history for clients that do not want to
+//! store it themselves (with `CONVERSATIONS_ENABLED`).  With a
+//! [`ConversationStore`] attached through
+//! [`HyperInferClient::with_conversation_store`](crate::HyperInferClient::with_conversation_store),
+//! [`HyperInferClient::chat_in_conversation`](crate::HyperInferClient::chat_in_conversation)
+//! loads a conversation's history before dispatch and appends the new turn
+//! once the provider has answered.
+
+use hyperinfer_core::{
+    ChatMessage, ChatRequest, ConversationMessage, HyperInferError, MessageRole,
+};
+
+/// Client for the control plane's conversation store.
+#[derive(Clone)]
+pub struct ConversationStore {
+    http: reqwest::Client,
+    base_url: String,
+    admin_token: String,
+}
+
+impl ConversationStore {
+    pub fn new(base_url: &str, admin_token: &str) -> Result<Self, HyperInferError> {
+        let http = reqwest::Client::builder()
+            .timeout(crate::bootstrap::REQUEST_TIMEOUT)
+            .build()
+            .map_err(HyperInferError::Http)?;
+        Ok(Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            admin_token: admin_token.to_string(),
+        })
+    }
+
+    /// Control-plane endpoint for a conversation's messages.
+    fn url(&self, conversation_id: &str) -> String {
+        format!(
+            "{}/v1/conversations/{}/messages",
+            self.base_url, conversation_id
+        )
+    }
+
+    /// The conversation's messages, oldest first.
+    pub async fn history(
+        &self,
+        conversation_id: &str,
+    ) -> Result<Vec<ChatMessage>, HyperInferError> {
+        let response = self
+            .http
+            .get(self.url(conversation_id))
+            .bearer_auth(&self.admin_token)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(HyperInferError::api_error(status.as_u16(), message));
+        }
+        let messages: Vec<ConversationMessage> = response.json().await?;
+        Ok(messages.into_iter().map(ChatMessage::from).collect())
+    }
+
+    /// Add `messages` to the end of the conversation.
+    pub async fn append(
+        &self,
+        conversation_id: &str,
+        messages: &[ChatMessage],
+    ) -> Result<(), HyperInferError> {
+        let response = self
+            .http
+            .post(self.url(conversation_id))
+            .bearer_auth(&self.admin_token)
+            .json(&serde_json::json!({ "messages": messages }))
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(HyperInferError::api_error(status.as_u16(), message));
+        }
+        Ok(())
+    }
+}
+
+/// Put `history` into `request` after its system messages, so the
+/// caller's instructions still lead and its new messages follow.
+pub(crate) fn with_history(request: &mut ChatRequest, history: Vec<ChatMessage>) {
+    let system = request
+        .messages
+        .iter()
+        .take_while(|message| message.role == MessageRole::System)
+        .count();
+    request.messages.splice(system..system, history);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: MessageRole, content: &str) -> ChatMessage {
+        ChatMessage {
+            role,
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_history_goes_between_system_and_new_messages() {
+        let mut request = ChatRequest {
+            messages: vec![
+                message(MessageRole::System, "be brief"),
+                message(MessageRole::User, "and then?"),
+            ],
+            ..Default::default()
+        };
+        with_history(
+            &mut request,
+            vec![
+                message(MessageRole::User, "hi"),
+                message(MessageRole::Assistant, "hello"),
+            ],
+        );
+        let contents: Vec<_> = request
+            .messages
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(contents, ["be brief", "hi", "hello", "and then?"]);
+    }
+}
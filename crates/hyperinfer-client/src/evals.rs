@@ -0,0 +1,66 @@
+//! Running eval datasets through the data plane.
+//!
+//! [`HyperInferClient::run_eval`](crate::HyperInferClient::run_eval) sends
+//! each item of a dataset to a model through the normal chat path, scores
+//! the answers and returns a run ready to submit.  [`EvalStore`] fetches
+//! datasets from the control plane and submits runs back, where runs of
+//! the same dataset against different models can be compared.
+
+use hyperinfer_core::{EvalDataset, EvalRun, HyperInferError, NewEvalRun};
+
+/// Client for the control plane's eval endpoints.
+#[derive(Clone)]
+pub struct EvalStore {
+    http: reqwest::Client,
+    base_url: String,
+    admin_token: String,
+}
+
+impl EvalStore {
+    pub fn new(base_url: &str, admin_token: &str) -> Result<Self, HyperInferError> {
+        let http = reqwest::Client::builder()
+            .timeout(crate::bootstrap::REQUEST_TIMEOUT)
+            .build()
+            .map_err(HyperInferError::Http)?;
+        Ok(Self {
+            http,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            admin_token: admin_token.to_string(),
+        })
+    }
+
+    pub async fn dataset(&self, dataset_id: &str) -> Result<EvalDataset, HyperInferError> {
+        let response = self
+            .http
+            .get(format!(
+                "{}/v1/evals/datasets/{}",
+                self.base_url, dataset_id
+            ))
+            .bearer_auth(&self.admin_token)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(HyperInferError::api_error(status.as_u16(), message));
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Store `run`; the control plane answers with its aggregate scores.
+    pub async fn submit(&self, run: &NewEvalRun) -> Result<EvalRun, HyperInferError> {
+        let response = self
+            .http
+            .post(format!("{}/v1/evals/runs", self.base_url))
+            .bearer_auth(&self.admin_token)
+            .json(run)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(HyperInferError::api_error(status.as_u16(), message));
+        }
+        Ok(response.json().await?)
+    }
+}
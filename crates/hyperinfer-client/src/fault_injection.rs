@@ -0,0 +1,338 @@
+//! Fault injection for provider calls.
+//!
+//! Built only with the `fault-injection` feature, for staging environments
+//! where retries, fallbacks and provider drains need exercising against
+//! realistic failures.  A [`FaultInjector`] attached to an [`HttpCaller`]
+//! rolls, for every provider call, whether to add latency and whether to
+//! fail it with a 429, a 5xx or a connection reset instead of (or, for
+//! resets of streams, partway through) the real response.  The client's
+//! built-in providers are all called through its `HttpCaller`, which takes
+//! the injector from [`TransportConfig::faults`]; providers registered from
+//! outside, such as Python ones, are not touched.
+//!
+//! Faults are configured per provider name, with `"*"` covering providers
+//! without an entry of their own.  [`TransportConfig::from_env`] reads them
+//! as JSON from `HYPERINFER_FAULTS`:
+//!
+//! ```json
+//! {"openai": {"rate_limit_probability": 0.05, "latency_probability": 0.2, "latency_ms": 3000},
+//!  "*": {"server_error_probability": 0.01}}
+//! ```
+//!
+//! [`HttpCaller`]: crate::HttpCaller
+//! [`TransportConfig::faults`]: crate::TransportConfig::faults
+//! [`TransportConfig::from_env`]: crate::TransportConfig::from_env
+
+use futures::{Stream, StreamExt};
+use hyperinfer_core::types::Provider;
+use hyperinfer_core::{ChatChunk, HyperInferError};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// Environment variable holding the fault configuration.
+pub const FAULTS_ENV: &str = "HYPERINFER_FAULTS";
+
+/// Statuses injected server errors pick from.
+const SERVER_ERROR_STATUSES: [u16; 3] = [500, 502, 503];
+
+/// Fault probabilities for one provider.  The three failure probabilities
+/// are exclusive outcomes of one roll, so they may sum to at most 1.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FaultConfig {
+    /// Chance of delaying a call by `latency_ms` before it is sent.
+    pub latency_probability: f64,
+    pub latency_ms: u64,
+    /// Chance of failing with a 429.
+    pub rate_limit_probability: f64,
+    /// Chance of failing with a 500, 502 or 503.
+    pub server_error_probability: f64,
+    /// Chance of the connection being reset: before the response for chat
+    /// calls, after the first chunk for streams.
+    pub reset_probability: f64,
+}
+
+impl FaultConfig {
+    fn validate(&self, provider: &str) -> Result<(), String> {
+        let probabilities = [
+            self.latency_probability,
+            self.rate_limit_probability,
+            self.server_error_probability,
+            self.reset_probability,
+        ];
+        if probabilities.iter().any(|p| !(0.0..=1.0).contains(p)) {
+            return Err(format!(
+                "fault probabilities for '{}' must be between 0 and 1",
+                provider
+            ));
+        }
+        if self.rate_limit_probability + self.server_error_probability + self.reset_probability
+            > 1.0
+        {
+            return Err(format!(
+                "failure probabilities for '{}' add up to more than 1",
+                provider
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A failure chosen for one call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    RateLimited,
+    ServerError(u16),
+    ConnectionReset,
+}
+
+impl Fault {
+    pub fn to_error(self, provider: &Provider) -> HyperInferError {
+        match self {
+            Fault::RateLimited => HyperInferError::api_error(
+                429,
+                format!("injected fault: {} rate limited the request", provider),
+            ),
+            Fault::ServerError(status) => HyperInferError::api_error(
+                status,
+                format!("injected fault: {} server error", provider),
+            ),
+            Fault::ConnectionReset => HyperInferError::Config(std::io::Error::new(
+                std::io::ErrorKind::ConnectionReset,
+                format!("injected fault: connection to {} reset", provider),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FaultInjector {
+    providers: HashMap<String, FaultConfig>,
+}
+
+impl FaultInjector {
+    pub fn new(providers: HashMap<String, FaultConfig>) -> Result<Self, HyperInferError> {
+        for (provider, config) in &providers {
+            config.validate(provider).map_err(|message| {
+                HyperInferError::Config(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    message,
+                ))
+            })?;
+        }
+        Ok(Self { providers })
+    }
+
+    /// The injector configured by [`FAULTS_ENV`], or `None` when it is
+    /// unset.
+    pub fn from_env() -> Result<Option<Self>, HyperInferError> {
+        match std::env::var(FAULTS_ENV) {
+            Ok(json) if !json.trim().is_empty() => Self::from_json(&json).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// The injector configured by `json`, in the format of [`FAULTS_ENV`].
+    pub fn from_json(json: &str) -> Result<Self, HyperInferError> {
+        let providers = serde_json::from_str(json).map_err(|e| {
+            HyperInferError::Config(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid {}: {}", FAULTS_ENV, e),
+            ))
+        })?;
+        Self::new(providers)
+    }
+
+    fn config_for(&self, provider: &Provider) -> Option<&FaultConfig> {
+        self.providers
+            .get(&provider.to_string())
+            .or_else(|| self.providers.get("*"))
+    }
+
+    /// Latency to add to a call to `provider`, if any.
+    pub fn delay(&self, provider: &Provider) -> Option<Duration> {
+        let config = self.config_for(provider)?;
+        (config.latency_ms > 0 && fastrand::f64() < config.latency_probability)
+            .then(|| Duration::from_millis(config.latency_ms))
+    }
+
+    /// The failure to inject into a call to `provider`, if any.
+    pub fn fault(&self, provider: &Provider) -> Option<Fault> {
+        let config = self.config_for(provider)?;
+        let roll = fastrand::f64();
+        let mut threshold = config.rate_limit_probability;
+        if roll < threshold {
+            return Some(Fault::RateLimited);
+        }
+        threshold += config.server_error_probability;
+        if roll < threshold {
+            let status = SERVER_ERROR_STATUSES[fastrand::usize(..SERVER_ERROR_STATUSES.len())];
+            return Some(Fault::ServerError(status));
+        }
+        threshold += config.reset_probability;
+        (roll < threshold).then_some(Fault::ConnectionReset)
+    }
+
+    /// Apply latency and failures before a non-streaming call.
+    pub async fn before_call(&self, provider: &Provider) -> Result<(), HyperInferError> {
+        if let Some(delay) = self.delay(provider) {
+            tokio::time::sleep(delay).await;
+        }
+        match self.fault(provider) {
+            Some(fault) => Err(fault.to_error(provider)),
+            None => Ok(()),
+        }
+    }
+
+    /// Apply latency and failures to a stream from `provider`.
+    pub fn wrap_stream(
+        &self,
+        provider: &Provider,
+        mut upstream: Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send>>,
+    ) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send>> {
+        let delay = self.delay(provider);
+        let fault = self.fault(provider);
+        let provider = provider.clone();
+        Box::pin(async_stream::stream! {
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            match fault {
+                Some(Fault::ConnectionReset) => {
+                    if let Some(first) = upstream.next().await {
+                        yield first;
+                    }
+                    yield Err(Fault::ConnectionReset.to_error(&provider));
+                }
+                Some(fault) => yield Err(fault.to_error(&provider)),
+                None => {
+                    while let Some(item) = upstream.next().await {
+                        yield item;
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn injector(provider: &str, config: FaultConfig) -> FaultInjector {
+        FaultInjector::new(HashMap::from([(provider.to_string(), config)])).unwrap()
+    }
+
+    fn chunks(n: usize) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send>> {
+        Box::pin(futures::stream::iter((0..n).map(|i| {
+            Ok(ChatChunk {
+                delta: i.to_string(),
+                ..Default::default()
+            })
+        })))
+    }
+
+    #[test]
+    fn test_certain_and_impossible_faults() {
+        let always = injector(
+            "openai",
+            FaultConfig {
+                rate_limit_probability: 1.0,
+                ..Default::default()
+            },
+        );
+        assert_eq!(always.fault(&Provider::OpenAI), Some(Fault::RateLimited));
+        // Other providers are untouched without a "*" entry.
+        assert_eq!(always.fault(&Provider::Anthropic), None);
+
+        let never = injector("*", FaultConfig::default());
+        assert!((0..100).all(|_| never.fault(&Provider::OpenAI).is_none()));
+        assert_eq!(never.delay(&Provider::OpenAI), None);
+
+        let errors = injector(
+            "*",
+            FaultConfig {
+                server_error_probability: 1.0,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            errors.fault(&Provider::Anthropic),
+            Some(Fault::ServerError(500 | 502 | 503))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_probabilities_are_rejected() {
+        let too_likely = FaultConfig {
+            rate_limit_probability: 0.6,
+            reset_probability: 0.6,
+            ..Default::default()
+        };
+        assert!(FaultInjector::new(HashMap::from([("openai".to_string(), too_likely)])).is_err());
+        let negative = FaultConfig {
+            latency_probability: -0.1,
+            ..Default::default()
+        };
+        assert!(FaultInjector::new(HashMap::from([("openai".to_string(), negative)])).is_err());
+    }
+
+    #[test]
+    fn test_config_parses_from_json() {
+        let providers: HashMap<String, FaultConfig> =
+            serde_json::from_str(r#"{"openai": {"latency_probability": 0.5, "latency_ms": 250}}"#)
+                .unwrap();
+        assert_eq!(providers["openai"].latency_ms, 250);
+        assert!(serde_json::from_str::<HashMap<String, FaultConfig>>(
+            r#"{"openai": {"latency": 1}}"#
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_streams_are_cut_or_failed() {
+        let reset = injector(
+            "openai",
+            FaultConfig {
+                reset_probability: 1.0,
+                ..Default::default()
+            },
+        );
+        let items: Vec<_> = reset
+            .wrap_stream(&Provider::OpenAI, chunks(3))
+            .collect()
+            .await;
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].as_ref().unwrap().delta, "0");
+        assert!(matches!(
+            &items[1],
+            Err(HyperInferError::Config(e)) if e.kind() == std::io::ErrorKind::ConnectionReset
+        ));
+
+        let limited = injector(
+            "openai",
+            FaultConfig {
+                rate_limit_probability: 1.0,
+                latency_probability: 1.0,
+                latency_ms: 1,
+                ..Default::default()
+            },
+        );
+        let items: Vec<_> = limited
+            .wrap_stream(&Provider::OpenAI, chunks(3))
+            .collect()
+            .await;
+        assert!(matches!(
+            &items[..],
+            [Err(HyperInferError::ApiError { status: 429, .. })]
+        ));
+
+        let untouched: Vec<_> = limited
+            .wrap_stream(&Provider::Anthropic, chunks(3))
+            .collect()
+            .await;
+        assert_eq!(untouched.len(), 3);
+    }
+}
@@ -0,0 +1,130 @@
+//! Liveness reports from this client to the control plane.
+//!
+//! Every client on Redis reports a heartbeat under its instance id every
+//! [`HEARTBEAT_INTERVAL`](hyperinfer_core::redis::HEARTBEAT_INTERVAL): the
+//! service embedding it, the client and config versions it runs, its request
+//! rate and whether its telemetry is getting through.  The control plane
+//! lists them at `/v1/fleet/instances`.
+
+use crate::shutdown::InFlight;
+use crate::snapshot::SharedSnapshot;
+use crate::telemetry::Telemetry;
+use async_trait::async_trait;
+use hyperinfer_core::{HeartbeatSource, InstanceHeartbeat};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Environment variable naming the service that embeds the client.
+pub const SERVICE_NAME_ENV: &str = "HYPERINFER_SERVICE_NAME";
+
+/// The service this process reports as: [`SERVICE_NAME_ENV`], or the
+/// OpenTelemetry `OTEL_SERVICE_NAME` it already exports traces under.
+pub fn service_name() -> Option<String> {
+    [SERVICE_NAME_ENV, "OTEL_SERVICE_NAME"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|service| !service.trim().is_empty())
+}
+
+/// What a client reports about itself.
+pub(crate) struct InstanceStatus {
+    instance_id: String,
+    service: Option<String>,
+    snapshot: Arc<SharedSnapshot>,
+    in_flight: InFlight,
+    telemetry: Telemetry,
+    /// When the last heartbeat was taken and the requests started by then,
+    /// to work out the rate since.
+    last: Mutex<(Instant, u64)>,
+}
+
+impl InstanceStatus {
+    pub(crate) fn new(
+        instance_id: String,
+        snapshot: Arc<SharedSnapshot>,
+        in_flight: InFlight,
+        telemetry: Telemetry,
+    ) -> Self {
+        let started = in_flight.started();
+        Self {
+            instance_id,
+            service: service_name(),
+            snapshot,
+            in_flight,
+            telemetry,
+            last: Mutex::new((Instant::now(), started)),
+        }
+    }
+
+    /// Requests per second since the previous call.
+    fn qps(&self, now: Instant) -> f64 {
+        let started = self.in_flight.started();
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = now.duration_since(last.0).as_secs_f64();
+        let requests = started.saturating_sub(last.1);
+        *last = (now, started);
+        if elapsed > 0.0 {
+            requests as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+}
+
+#[async_trait]
+impl HeartbeatSource for InstanceStatus {
+    async fn heartbeat(&self) -> InstanceHeartbeat {
+        InstanceHeartbeat {
+            instance_id: self.instance_id.clone(),
+            config_version: self.snapshot.load().config.version,
+            service: self.service.clone(),
+            client_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            qps: self.qps(Instant::now()),
+            in_flight: self.in_flight.len() as u64,
+            telemetry_connected: Some(self.telemetry.is_connected()),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyperinfer_core::Config;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_heartbeat_reports_rate_since_the_last_one() {
+        let snapshot = Arc::new(SharedSnapshot::new(Config {
+            version: 7,
+            ..Default::default()
+        }));
+        let in_flight = InFlight::default();
+        let status = InstanceStatus::new(
+            "dp-1".to_string(),
+            snapshot,
+            in_flight.clone(),
+            Telemetry::disabled(),
+        );
+        let start = status.last.lock().unwrap().0;
+
+        let running = in_flight.enter().unwrap();
+        for _ in 0..19 {
+            drop(in_flight.enter());
+        }
+        assert_eq!(status.qps(start + Duration::from_secs(10)), 2.0);
+        // Nothing new since.
+        assert_eq!(status.qps(start + Duration::from_secs(20)), 0.0);
+
+        let heartbeat = status.heartbeat().await;
+        assert_eq!(heartbeat.instance_id, "dp-1");
+        assert_eq!(heartbeat.config_version, 7);
+        assert_eq!(heartbeat.in_flight, 1);
+        assert_eq!(heartbeat.telemetry_connected, Some(false));
+        assert_eq!(
+            heartbeat.client_version.as_deref(),
+            Some(env!("CARGO_PKG_VERSION"))
+        );
+        drop(running);
+    }
+}
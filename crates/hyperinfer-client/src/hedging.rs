@@ -0,0 +1,175 @@
+//! Hedged requests.
+//!
+//! With `Config::hedging` set, a request whose primary provider has not
+//! answered within `after_ms` (for streams: has not produced its first
+//! chunk) is also sent to a fallback, and the first success wins.  The
+//! losing call is dropped, which aborts its HTTP request.
+//!
+//! Hedging must not double-charge the caller: the request is counted against
+//! the rate limit once, before either call is made, and tokens and telemetry
+//! are only recorded for the winner.  `HedgingConfig::max_output_tokens`
+//! bounds what the cancelled duplicate can cost at the provider.
+
+use futures::{Stream, StreamExt};
+use hyperinfer_core::{ChatChunk, ChatRequest, HyperInferError};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+pub type ChunkStream = Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send>>;
+
+/// The hedge call to make for a request, if its primary is slow.
+#[derive(Debug, Clone)]
+pub struct HedgePlan {
+    pub after: Duration,
+    pub model: String,
+    pub provider_name: String,
+    pub api_key: String,
+    /// The original request, rewritten for the hedge model.
+    pub request: ChatRequest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Winner {
+    Primary,
+    Hedge,
+}
+
+/// Run `primary`; if it has not finished after `after`, start `hedge` as
+/// well and return the first success.  A failure only wins once the other
+/// call has failed too, in which case the primary's error is returned.
+/// Without a hedge this is just `primary.await`.
+pub async fn race<T, E, P, H>(primary: P, hedge: Option<(Duration, H)>) -> (Result<T, E>, Winner)
+where
+    P: Future<Output = Result<T, E>>,
+    H: Future<Output = Result<T, E>>,
+{
+    let Some((after, hedge)) = hedge else {
+        return (primary.await, Winner::Primary);
+    };
+    tokio::pin!(primary);
+    tokio::select! {
+        result = &mut primary => return (result, Winner::Primary),
+        _ = tokio::time::sleep(after) => {}
+    }
+    tracing::debug!(
+        "Primary provider slow after {:?}, sending hedge request",
+        after
+    );
+    tokio::pin!(hedge);
+    tokio::select! {
+        result = &mut primary => match result {
+            Ok(value) => (Ok(value), Winner::Primary),
+            Err(e) => match hedge.await {
+                Ok(value) => (Ok(value), Winner::Hedge),
+                Err(_) => (Err(e), Winner::Primary),
+            },
+        },
+        result = &mut hedge => match result {
+            Ok(value) => (Ok(value), Winner::Hedge),
+            Err(_) => (primary.await, Winner::Primary),
+        },
+    }
+}
+
+/// Wait for the first chunk of `stream`, so a stream can take part in a
+/// [`race`].  Fails if the first item is an error; otherwise returns the
+/// stream with that chunk put back in front.
+pub async fn first_chunk(mut stream: ChunkStream) -> Result<ChunkStream, HyperInferError> {
+    match stream.next().await {
+        Some(Err(e)) => Err(e),
+        Some(Ok(chunk)) => Ok(Box::pin(
+            futures::stream::once(async move { Ok(chunk) }).chain(stream),
+        )),
+        None => Ok(stream),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    async fn after(
+        ms: u64,
+        result: Result<&'static str, &'static str>,
+    ) -> Result<&'static str, &'static str> {
+        tokio::time::sleep(Duration::from_millis(ms)).await;
+        result
+    }
+
+    #[tokio::test]
+    async fn test_fast_primary_never_starts_hedge() {
+        let started = Arc::new(AtomicBool::new(false));
+        let hedge = {
+            let started = started.clone();
+            async move {
+                started.store(true, Ordering::SeqCst);
+                Ok("hedge")
+            }
+        };
+        let (result, winner) = race(
+            after(5, Ok("primary")),
+            Some((Duration::from_millis(200), hedge)),
+        )
+        .await;
+        assert_eq!(result, Ok("primary"));
+        assert_eq!(winner, Winner::Primary);
+        assert!(!started.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_slow_primary_loses_to_hedge() {
+        let (result, winner) = race(
+            after(1000, Ok("primary")),
+            Some((Duration::from_millis(10), after(10, Ok("hedge")))),
+        )
+        .await;
+        assert_eq!(result, Ok("hedge"));
+        assert_eq!(winner, Winner::Hedge);
+    }
+
+    #[tokio::test]
+    async fn test_failed_hedge_waits_for_primary() {
+        let (result, winner) = race(
+            after(50, Ok("primary")),
+            Some((Duration::from_millis(10), after(0, Err("hedge failed")))),
+        )
+        .await;
+        assert_eq!(result, Ok("primary"));
+        assert_eq!(winner, Winner::Primary);
+    }
+
+    #[tokio::test]
+    async fn test_both_fail_returns_primary_error() {
+        let (result, winner) = race(
+            after(30, Err("primary failed")),
+            Some((Duration::from_millis(10), after(0, Err("hedge failed")))),
+        )
+        .await;
+        assert_eq!(result, Err("primary failed"));
+        assert_eq!(winner, Winner::Primary);
+    }
+
+    #[tokio::test]
+    async fn test_first_chunk_keeps_chunk() {
+        let chunk = |delta: &str| ChatChunk {
+            delta: delta.to_string(),
+            ..Default::default()
+        };
+        let stream: ChunkStream =
+            Box::pin(futures::stream::iter(vec![Ok(chunk("a")), Ok(chunk("b"))]));
+        let stream = first_chunk(stream).await.unwrap();
+        let deltas: Vec<_> = stream.map(|c| c.unwrap().delta).collect().await;
+        assert_eq!(deltas, vec!["a", "b"]);
+
+        let failing: ChunkStream = Box::pin(futures::stream::iter(vec![Err(
+            HyperInferError::StreamParse {
+                message: "bad".to_string(),
+                raw: String::new(),
+            },
+        )]));
+        assert!(first_chunk(failing).await.is_err());
+    }
+}
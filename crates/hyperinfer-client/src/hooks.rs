@@ -0,0 +1,59 @@
+//! Callbacks around chat calls.
+//!
+//! A [`ChatHooks`] attached with
+//! [`HyperInferClient::with_hooks`](crate::HyperInferClient::with_hooks)
+//! hears about every `chat()` call: once before it is handled, and once
+//! with its response or error.  Hooks are for logging and cost tracking;
+//! they see the request as the caller sent it and cannot change it.
+//!
+//! Hooks run inline, so a slow hook slows the call down.  Streams report
+//! `on_request`, and `on_error` if the stream cannot be started; their
+//! chunks are not reported.
+
+use async_trait::async_trait;
+use hyperinfer_core::{ChatRequest, ChatResponse};
+use serde::Serialize;
+
+/// A call about to be handled.
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestEvent {
+    /// Id shared by the events of one call.
+    pub request_id: String,
+    /// SHA-256 hex digest of the caller's key; the key itself is left out.
+    pub key_hash: String,
+    pub request: ChatRequest,
+    pub stream: bool,
+}
+
+/// A call that succeeded.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResponseEvent {
+    pub request_id: String,
+    pub key_hash: String,
+    pub request: ChatRequest,
+    pub response: ChatResponse,
+    pub latency_ms: u64,
+    /// Cost of the call at the configured prices; 0 for unpriced models.
+    pub cost_cents: f64,
+}
+
+/// A call that failed.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorEvent {
+    pub request_id: String,
+    pub key_hash: String,
+    pub request: ChatRequest,
+    pub error: String,
+    pub latency_ms: u64,
+    pub stream: bool,
+}
+
+/// Callbacks for chat calls.  Every method does nothing by default.
+#[async_trait]
+pub trait ChatHooks: Send + Sync {
+    async fn on_request(&self, _event: &RequestEvent) {}
+
+    async fn on_response(&self, _event: &ResponseEvent) {}
+
+    async fn on_error(&self, _event: &ErrorEvent) {}
+}
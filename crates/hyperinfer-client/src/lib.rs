@@ -1,30 +1,71 @@
 //! HyperInfer Client Library - Data Plane
 
+pub mod bootstrap;
 pub mod cache;
+pub mod compression;
+pub mod context;
+pub mod conversations;
+pub mod evals;
+#[cfg(feature = "fault-injection")]
+pub mod fault_injection;
+#[cfg(feature = "redis")]
+pub mod heartbeat;
+pub mod hedging;
+pub mod hooks;
 pub mod http_client;
 pub mod mirroring;
-pub mod router;
+pub mod policy;
+pub mod recording;
+mod regions;
+mod shutdown;
+pub mod single_flight;
+pub mod snapshot;
+pub mod state;
+mod stream_usage;
 pub mod telemetry;
 pub mod telemetry_otlp;
+mod telemetry_queue;
 mod util;
+pub mod validation;
 
 pub use cache::ExactMatchCache;
-pub use http_client::HttpCaller;
+pub use conversations::ConversationStore;
+pub use evals::EvalStore;
+pub use hooks::{ChatHooks, ErrorEvent, RequestEvent, ResponseEvent};
+pub use http_client::{
+    EgressConfig, HttpCaller, OversizedResponse, ProviderTransport, TransportConfig,
+};
+pub use hyperinfer_core::router::{AliasScope, ResolvedRoute, RouteExplanation, RouteStep, Router};
+pub use hyperinfer_core::{aliases, cascade, experiments, router};
 pub use mirroring::{MirrorConfig, MirrorHandle};
-pub use router::Router;
-pub use telemetry::Telemetry;
+pub use policy::{KeyPolicies, KeyPolicy};
+pub use recording::{Cassette, RecordingTransport, ReplayTransport};
+pub use shutdown::ShutdownReport;
+pub use single_flight::SingleFlight;
+pub use snapshot::{RouterSnapshot, SharedSnapshot};
+pub use state::StateStore;
+pub use telemetry::{Telemetry, TelemetryBatching, TelemetryHealth};
 pub use telemetry_otlp::{
-    init_langfuse_telemetry, init_telemetry, init_telemetry_with_headers, set_gen_ai_attributes,
-    set_gen_ai_response, set_gen_ai_usage, shutdown_telemetry,
+    init_langfuse_telemetry, init_metrics_with_headers, init_observability,
+    init_observability_with_headers, init_telemetry, init_telemetry_with_headers,
+    set_gen_ai_attributes, set_gen_ai_response, set_gen_ai_usage, shutdown_telemetry, GenAiMetrics,
 };
+pub use telemetry_queue::OverflowPolicy;
 
 use futures::Stream;
 use hyperinfer_core::{
-    rate_limiting::RateLimiter, ChatChunk, ChatRequest, ChatResponse, Config, HyperInferError,
+    pricing::CostEstimate, rate_limiting::RateLimiter, Cascade, CascadeVerifier, ChatChunk,
+    ChatMessage, ChatRequest, ChatResponse, CompressionStats, Config, ContextOverflow,
+    DryRunReport, EvalDataset, EvalItemResult, HyperInferError, KeyLimits, MessageRole, NewEvalRun,
+    OutputTransform, ParamClamps, Provider, ProviderErrorKind, RoutingOverride, TeamFeature, Usage,
+    VirtualKey,
 };
-use hyperinfer_providers::ProviderRegistry;
+#[cfg(feature = "redis")]
+use hyperinfer_core::{redis::ConfigManager, RedisHandle};
+use hyperinfer_providers::{LlmProvider, ProviderRegistry};
+use std::collections::HashMap;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
 use tokio::sync::RwLock;
 use tracing::Instrument as _;
@@ -33,26 +74,46 @@ use tracing::Instrument as _;
 /// `chat()` once the stream terminates (naturally or via an error):
 ///
 /// - Fires Redis telemetry off the critical path via `tokio::spawn`.
-/// - Records output-token usage in the rate-limiter bucket.
-/// - Sets OTel span usage / response attributes.
+/// - Records token usage in the rate-limiter bucket.
+/// - Sets OTel span usage / response attributes and records GenAI metrics.
+///
+/// Usage is estimated as chunks arrive and charged to the rate limiter every
+/// [`stream_usage::CHARGE_INTERVAL_TOKENS`], so long streams count against
+/// limits while they run; at the end the charge is reconciled with the
+/// provider's reported usage, or the estimate if it reported none.
 ///
-/// The accounting is triggered exactly once, either when a `[DONE]`-equivalent
-/// chunk with a `finish_reason` is seen **or** when the stream signals
-/// `Poll::Ready(None)`.
+/// The accounting is triggered exactly once: when the stream ends or fails,
+/// or when it is dropped.  It is not triggered by the chunk carrying a
+/// `finish_reason`, since OpenAI reports usage on a chunk after it.
 struct AccountedStream {
     inner: Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send>>,
     telemetry: Telemetry,
     rate_limiter: RateLimiter,
     key: String,
+    /// Identity rate limits are charged to (see `HyperInferClient::limit_key`).
+    limit_key: String,
+    /// Attribution tags from the request.
+    metadata: HashMap<String, String>,
     model: String,
+    provider: String,
     start: std::time::Instant,
-    /// Accumulated token counts from the stream's usage chunk (if any).
-    input_tokens: u32,
-    output_tokens: u32,
+    /// Set when the provider stream yielded an error; recorded as a failed
+    /// request instead of a usage record.
+    error: Option<String>,
+    /// `error.type` metric attribute for `error`.
+    error_type: Option<String>,
+    /// Prompt size before and after compression, if it was compressed.
+    compression: Option<CompressionStats>,
+    /// Reported or estimated token usage so far.
+    usage: stream_usage::StreamUsage,
+    /// Tokens already charged to the rate limiter while streaming.
+    charged: u64,
     /// Guards against running the accounting block more than once.
     accounted: bool,
     /// OTel span that lives for the full stream lifetime.
     span: tracing::Span,
+    /// Holds off client shutdown until the stream's usage is buffered.
+    in_flight: Option<shutdown::InFlightGuard>,
 }
 
 impl AccountedStream {
@@ -64,32 +125,101 @@ impl AccountedStream {
         self.accounted = true;
 
         let elapsed = self.start.elapsed().as_millis() as u64;
-        let input_tokens = self.input_tokens;
-        let output_tokens = self.output_tokens;
+        let usage = self.usage.reconcile();
+        let (input_tokens, output_tokens) = (usage.input_tokens, usage.output_tokens);
+        if self.usage.is_estimated() && self.error.is_none() {
+            tracing::debug!(
+                "{} reported no usage for a stream; recording estimated usage",
+                self.provider
+            );
+        }
 
         let _enter = self.span.clone().entered();
         crate::telemetry_otlp::set_gen_ai_usage(&self.span, input_tokens, output_tokens);
+        crate::telemetry_otlp::record_gen_ai_duration(
+            &self.provider,
+            &self.model,
+            "chat_stream",
+            self.start.elapsed(),
+            self.error_type.as_deref(),
+        );
+        if self.error.is_none() {
+            crate::telemetry_otlp::record_gen_ai_token_usage(
+                &self.provider,
+                &self.model,
+                "chat_stream",
+                input_tokens,
+                output_tokens,
+            );
+        }
 
         // Telemetry write is off the critical path.
         let telemetry = self.telemetry.clone();
         let key = self.key.clone();
         let model = self.model.clone();
+        let provider = self.provider.clone();
+        let error = self.error.clone();
+        let metadata = std::mem::take(&mut self.metadata);
+        let compression = self.compression;
+        let in_flight = self.in_flight.take();
         tokio::spawn(async move {
-            if let Err(e) = telemetry
-                .record_with_tokens(&key, &model, input_tokens, output_tokens, elapsed)
-                .await
-            {
+            let _in_flight = in_flight;
+            let result = match error {
+                Some(error) => {
+                    telemetry
+                        .record_error(&key, &model, &provider, &error, elapsed)
+                        .await
+                }
+                None => {
+                    telemetry
+                        .record_with_compression(
+                            &key,
+                            &model,
+                            input_tokens,
+                            output_tokens,
+                            elapsed,
+                            &metadata,
+                            compression,
+                        )
+                        .await
+                }
+            };
+            if let Err(e) = result {
                 tracing::warn!(error = %e, "stream telemetry record failed");
             }
         });
 
-        // Rate-limiter token-bucket update is lightweight and synchronous-ish;
-        // run it in a spawn to avoid blocking the poll path.
+        // Settle the rate-limiter charge: the rest of the usage, or a refund
+        // if the estimates charged while streaming came out high.  Run in a
+        // spawn to avoid blocking the poll path.
         let rate_limiter = self.rate_limiter.clone();
-        let key2 = self.key.clone();
-        let total = (input_tokens + output_tokens) as u64;
+        let key2 = self.limit_key.clone();
+        let total = u64::from(input_tokens) + u64::from(output_tokens);
+        let charged = self.charged;
         tokio::spawn(async move {
-            let _ = rate_limiter.record_usage(&key2, total).await;
+            let _ = rate_limiter
+                .record_usage(&key2, total.saturating_sub(charged))
+                .await;
+            if charged > total {
+                let _ = rate_limiter
+                    .adjust_token_usage(&key2, -((charged - total) as i64))
+                    .await;
+            }
+        });
+    }
+
+    /// Charge the rate limiter for usage estimated so far once it is
+    /// [`stream_usage::CHARGE_INTERVAL_TOKENS`] ahead of what was charged.
+    fn charge_progress(&mut self) {
+        let due = self.usage.total().saturating_sub(self.charged);
+        if due < stream_usage::CHARGE_INTERVAL_TOKENS {
+            return;
+        }
+        self.charged += due;
+        let rate_limiter = self.rate_limiter.clone();
+        let key = self.limit_key.clone();
+        tokio::spawn(async move {
+            let _ = rate_limiter.adjust_token_usage(&key, due as i64).await;
         });
     }
 }
@@ -109,19 +239,15 @@ impl Stream for AccountedStream {
         let _enter = self.span.clone().entered();
         match self.inner.as_mut().poll_next(cx) {
             Poll::Ready(Some(Ok(chunk))) => {
-                // Capture usage from whatever chunk carries it (typically the last).
-                if let Some(ref u) = chunk.usage {
-                    self.input_tokens = u.input_tokens;
-                    self.output_tokens = u.output_tokens;
-                }
-                // If this chunk has a finish_reason the stream is done; account now
-                // so the span attributes are set while the span is still open.
-                if chunk.finish_reason.is_some() {
-                    self.account();
+                self.usage.observe(&chunk);
+                if !self.accounted {
+                    self.charge_progress();
                 }
                 Poll::Ready(Some(Ok(chunk)))
             }
             Poll::Ready(Some(Err(e))) => {
+                self.error = Some(e.to_string());
+                self.error_type = Some(crate::telemetry_otlp::gen_ai_error_type(&e));
                 self.account();
                 Poll::Ready(Some(Err(e)))
             }
@@ -134,48 +260,580 @@ impl Stream for AccountedStream {
     }
 }
 
+/// A registry serving the built-in providers through `transport`, so every
+/// call to them shares its connection pool and settings.
+fn transport_registry(transport: &Arc<dyn ProviderTransport>) -> ProviderRegistry {
+    let registry = ProviderRegistry::new();
+    for provider in [Provider::OpenAI, Provider::Anthropic] {
+        registry.register(http_client::TransportProvider::new(
+            provider,
+            transport.clone(),
+        ));
+    }
+    registry
+}
+
+/// The [`HttpCaller`] for `config`'s providers: pooled and routed out as
+/// `transport` says, with their headers, base URLs and regions.
+fn http_caller(
+    config: &Config,
+    transport: &TransportConfig,
+) -> Result<HttpCaller, HyperInferError> {
+    Ok(HttpCaller::with_config(transport)?
+        .with_provider_headers(&config.provider_headers)?
+        .with_base_urls(&config.provider_base_urls)
+        .with_regions(&config.provider_regions))
+}
+
+/// Reject `model` if the caller's virtual key is restricted to other models.
+fn check_model_allowed(identity: Option<&VirtualKey>, model: &str) -> Result<(), HyperInferError> {
+    match identity {
+        Some(vk) if !vk.allows_model(model) => Err(HyperInferError::Forbidden(format!(
+            "API key is not allowed to use model '{}'",
+            model
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// The error for a request whose `model` could not be routed for
+/// `team_id`: a residency error when only the team's data region stood in
+/// the way, a missing deployment when the request's route tag did, an
+/// unknown model otherwise.
+fn unroutable(
+    router: &Router,
+    config: &Config,
+    team_id: Option<&str>,
+    model: &str,
+    routing: Option<&RoutingOverride>,
+) -> HyperInferError {
+    let data_region = config.data_region(team_id);
+    if data_region.is_some() || routing.is_some() {
+        let explanation = router.explain_with(team_id, model, config, routing);
+        for step in &explanation.steps {
+            match (step, data_region) {
+                (RouteStep::OutsideDataRegion { .. }, Some(data_region)) => {
+                    return HyperInferError::Forbidden(format!(
+                        "Model '{}' has no provider endpoint in data region '{}', which this team's traffic is restricted to",
+                        model, data_region
+                    ));
+                }
+                (RouteStep::NoTaggedEndpoint { route_tag, .. }, _) => {
+                    return HyperInferError::Config(std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!(
+                            "Model '{}' has no provider endpoint tagged '{}'",
+                            model, route_tag
+                        ),
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+    HyperInferError::Config(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!(
+            "Unknown model: '{}'. No routing rule or alias found.",
+            model
+        ),
+    ))
+}
+
+/// Where and how to resend a request its model rejected as too long.
+struct ContextRetry {
+    model: String,
+    provider_name: String,
+    provider: Arc<dyn LlmProvider>,
+    api_key: String,
+    request: ChatRequest,
+}
+
+/// The error for a request the rate limiter turned away, carrying the
+/// limiter's retry time (0 meaning unknown).
+fn rate_limit_exceeded(retry_after_ms: u64) -> HyperInferError {
+    let (message, retry_after_ms) = match retry_after_ms {
+        0 => ("Rate limit exceeded".to_string(), None),
+        ms => (
+            format!("Rate limit exceeded; retry after {} ms", ms),
+            Some(ms),
+        ),
+    };
+    HyperInferError::RateLimit {
+        message,
+        retry_after_ms,
+    }
+}
+
 pub struct HyperInferClient {
-    config: Arc<RwLock<Config>>,
-    http_caller: Arc<HttpCaller>,
-    router: Arc<Router>,
+    /// Config and router, swapped whole on updates so requests never lock.
+    snapshot: Arc<SharedSnapshot>,
+    transport: Arc<dyn ProviderTransport>,
     rate_limiter: RateLimiter,
     telemetry: Telemetry,
     cache: ExactMatchCache,
     mirror: MirrorHandle,
     provider_registry: Arc<RwLock<Arc<ProviderRegistry>>>,
+    policies: KeyPolicies,
+    /// Per-key runtime state, shared through Redis when connected.
+    state: StateStore,
+    /// Taken and dropped, ending its pub/sub connection, on shutdown.
+    #[cfg(feature = "redis")]
+    policy_subscription: Mutex<Option<policy::PolicySubscription>>,
+    /// Fleet heartbeats, and for clients booted from the control plane,
+    /// config updates over pub/sub.
+    config_subscription: Mutex<Option<bootstrap::ConfigSubscription>>,
+    instance_id: String,
+    single_flight: SingleFlight,
+    /// Requests and streams running, and whether new ones are turned away.
+    in_flight: shutdown::InFlight,
+    /// History for `chat_in_conversation`, if attached.
+    conversations: Option<ConversationStore>,
+    /// Callbacks around chat calls, if attached.
+    hooks: Option<Arc<dyn ChatHooks>>,
 }
 
 impl HyperInferClient {
+    /// A client that only routes and calls providers: rate limits are kept
+    /// in this process, and there is no telemetry, response cache or
+    /// control-plane policy feed.
+    pub fn standalone(config: Config) -> Result<Self, HyperInferError> {
+        Self::assemble(
+            config,
+            RateLimiter::local(),
+            Telemetry::disabled(),
+            ExactMatchCache::disabled("default"),
+            StateStore::in_memory(),
+        )
+    }
+
+    #[cfg(feature = "redis")]
     pub async fn new(redis_url: &str, config: Config) -> Result<Self, HyperInferError> {
-        let http_caller = Arc::new(HttpCaller::new().map_err(HyperInferError::Http)?);
-        let router = Arc::new(
-            Router::new(config.routing_rules.clone())
-                .with_aliases(config.model_aliases.clone())
-                .with_default_provider(config.default_provider.clone()),
-        );
-        let rate_limiter = RateLimiter::new(Some(redis_url))
+        let redis = RedisHandle::connect(redis_url)
             .await
             .map_err(|e| HyperInferError::Config(std::io::Error::other(e.to_string())))?;
-        let telemetry = Telemetry::new(redis_url)
+        Self::with_redis(&redis, config).await
+    }
+
+    /// Like [`HyperInferClient::new`], on an existing Redis handle.  The
+    /// rate limiter, telemetry, cache, policy updates and fleet heartbeats
+    /// all share its connection; pub/sub opens one more from its client.
+    #[cfg(feature = "redis")]
+    pub async fn with_redis(redis: &RedisHandle, config: Config) -> Result<Self, HyperInferError> {
+        let rate_limiter = RateLimiter::with_redis(redis);
+        rate_limiter.preload_scripts().await;
+        let mut client = Self::assemble(
+            config,
+            rate_limiter,
+            Telemetry::with_redis(redis),
+            ExactMatchCache::with_redis(redis, "default"),
+            StateStore::with_redis(redis),
+        )?;
+        let manager = ConfigManager::with_redis(redis);
+        let policy_subscription = client.policies.subscribe_with(&manager).await?;
+        client.policy_subscription = Mutex::new(Some(policy_subscription));
+        let heartbeat = client.spawn_heartbeat(&manager);
+        client.config_subscription =
+            Mutex::new(Some(bootstrap::ConfigSubscription::new(vec![heartbeat])));
+        Ok(client)
+    }
+
+    /// Report this instance to the control plane's fleet view until the
+    /// returned task is aborted.
+    #[cfg(feature = "redis")]
+    fn spawn_heartbeat(&self, manager: &ConfigManager) -> tokio::task::JoinHandle<()> {
+        manager.spawn_heartbeat(Arc::new(heartbeat::InstanceStatus::new(
+            self.instance_id.clone(),
+            self.snapshot.clone(),
+            self.in_flight.clone(),
+            self.telemetry.clone(),
+        )))
+    }
+
+    /// Boot from the control plane at `base_url` rather than a local config.
+    ///
+    /// The config is fetched from `/v1/config/sync` with `admin_token`,
+    /// retrying with backoff, and then kept current over Redis pub/sub.
+    /// Provider keys never leave the control plane, so they are passed here
+    /// as `api_keys` (provider name to key) and kept across updates.  The
+    /// version in use is reported to the control plane's fleet status under
+    /// [`instance_id`](Self::instance_id).
+    /// Redis connections are made on first use, so the client starts even
+    /// while Redis is unreachable; until it is back, rate-limit checks fail
+    /// and telemetry and caching are skipped.
+    #[cfg(feature = "redis")]
+    pub async fn from_control_plane(
+        base_url: &str,
+        admin_token: &str,
+        api_keys: HashMap<String, String>,
+        redis_url: &str,
+    ) -> Result<Self, HyperInferError> {
+        let http = reqwest::Client::builder()
+            .timeout(bootstrap::REQUEST_TIMEOUT)
+            .build()
+            .map_err(HyperInferError::Http)?;
+        let mut config = bootstrap::fetch_config(
+            &http,
+            base_url,
+            admin_token,
+            bootstrap::DEFAULT_ATTEMPTS,
+            bootstrap::INITIAL_BACKOFF,
+        )
+        .await?;
+        config.api_keys = api_keys;
+
+        let redis = RedisHandle::lazy(redis_url)
+            .map_err(|e| HyperInferError::Config(std::io::Error::other(e.to_string())))?;
+        let manager = ConfigManager::with_redis(&redis);
+        let mut client = Self::assemble(
+            config,
+            RateLimiter::with_redis(&redis),
+            Telemetry::with_redis(&redis),
+            ExactMatchCache::with_redis(&redis, "default"),
+            StateStore::with_redis(&redis),
+        )?;
+        let policy_subscription = client.policies.subscribe_with(&manager).await?;
+        client.policy_subscription = Mutex::new(Some(policy_subscription));
+        let handle = manager
+            .subscribe_to_config_updates(client.snapshot.clone())
             .await
             .map_err(|e| HyperInferError::Config(std::io::Error::other(e.to_string())))?;
-        let cache = ExactMatchCache::new(redis_url, "default").await;
-        let mirror: MirrorHandle = Arc::new(RwLock::new(None));
-        let config = Arc::new(RwLock::new(config));
+        let heartbeat = client.spawn_heartbeat(&manager);
+        client.config_subscription = Mutex::new(Some(bootstrap::ConfigSubscription::new(vec![
+            handle, heartbeat,
+        ])));
+        Ok(client)
+    }
 
-        let provider_registry_inner = Arc::new(ProviderRegistry::new());
-        hyperinfer_providers::init_default_registry(&provider_registry_inner);
-        let provider_registry = Arc::new(RwLock::new(provider_registry_inner));
+    fn assemble(
+        config: Config,
+        rate_limiter: RateLimiter,
+        telemetry: Telemetry,
+        cache: ExactMatchCache,
+        state: StateStore,
+    ) -> Result<Self, HyperInferError> {
+        let caller = http_caller(&config, &TransportConfig::from_env()?)?;
+        let transport: Arc<dyn ProviderTransport> = Arc::new(caller);
+        let mirror: MirrorHandle = Arc::new(RwLock::new(None));
+        let provider_registry = Arc::new(RwLock::new(Arc::new(transport_registry(&transport))));
 
         Ok(Self {
-            config,
-            http_caller,
-            router,
+            snapshot: Arc::new(SharedSnapshot::new(config)),
+            transport,
             rate_limiter,
             telemetry,
             cache,
             mirror,
             provider_registry,
+            policies: KeyPolicies::with_store(&state),
+            state,
+            #[cfg(feature = "redis")]
+            policy_subscription: Mutex::default(),
+            config_subscription: Mutex::default(),
+            instance_id: bootstrap::instance_id(),
+            single_flight: SingleFlight::default(),
+            in_flight: shutdown::InFlight::default(),
+            conversations: None,
+            hooks: None,
+        })
+    }
+
+    /// Id this instance reports fleet heartbeats under.
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    /// Whether usage records are reaching Redis, and how many were lost or
+    /// dropped on the way.
+    pub fn telemetry_health(&self) -> TelemetryHealth {
+        self.telemetry.health()
+    }
+
+    /// Stop taking requests, wait for running ones, streams included, to
+    /// finish, and write out buffered telemetry, all within `timeout`.
+    /// Policy and config subscriptions are ended, closing their pub/sub
+    /// connections; the shared Redis connection closes once the client is
+    /// dropped.
+    ///
+    /// Call before the process exits: records still buffered when the
+    /// runtime stops are lost.  Requests made from here on fail with
+    /// [`HyperInferError::ShuttingDown`].  Calling it again only waits for
+    /// what the first call left running.
+    pub async fn shutdown(&self, timeout: std::time::Duration) -> ShutdownReport {
+        let deadline = tokio::time::Instant::now() + timeout;
+        self.in_flight.close();
+        let drained = tokio::time::timeout_at(deadline, self.in_flight.wait_idle())
+            .await
+            .is_ok();
+        let abandoned_requests = if drained { 0 } else { self.in_flight.len() };
+        if abandoned_requests > 0 {
+            tracing::warn!(
+                "Shutting down with {} requests still running",
+                abandoned_requests
+            );
+        }
+        #[cfg(feature = "redis")]
+        drop(
+            self.policy_subscription
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take(),
+        );
+        drop(
+            self.config_subscription
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take(),
+        );
+        let telemetry_flushed = self.telemetry.shutdown(deadline).await;
+        ShutdownReport {
+            abandoned_requests,
+            telemetry_flushed,
+        }
+    }
+
+    /// Whether [`HyperInferClient::shutdown`] has been called.
+    pub fn is_shutting_down(&self) -> bool {
+        self.in_flight.is_closing()
+    }
+
+    /// Count a request as running until the guard drops, or refuse it once
+    /// shutting down.
+    fn enter(&self) -> Result<shutdown::InFlightGuard, HyperInferError> {
+        self.in_flight.enter().ok_or(HyperInferError::ShuttingDown)
+    }
+
+    /// Spread usage records over `shards` telemetry streams; see
+    /// [`Telemetry::with_shards`].  Must match the control plane's
+    /// `TELEMETRY_SHARDS`.
+    pub fn with_telemetry_shards(mut self, shards: u32) -> Self {
+        self.telemetry = self.telemetry.with_shards(shards);
+        self
+    }
+
+    /// Keep chat history in the control plane's conversation store; see
+    /// [`HyperInferClient::chat_in_conversation`].
+    pub fn with_conversation_store(mut self, store: ConversationStore) -> Self {
+        self.conversations = Some(store);
+        self
+    }
+
+    /// Call `hooks` around every chat call; see [`hooks`].
+    pub fn with_hooks(mut self, hooks: Arc<dyn ChatHooks>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// Replace the wire transport used for provider calls.
+    ///
+    /// The built-in `openai` and `anthropic` registry entries are rebuilt on
+    /// top of `transport`, and traffic mirroring uses it as well, so a fake
+    /// transport lets `chat()` / `chat_stream()` run end-to-end without real
+    /// provider credentials.  Custom transports are responsible for applying
+    /// `Config::provider_headers`, `Config::provider_base_urls` and
+    /// `Config::provider_regions` themselves (see
+    /// [`HttpCaller::with_provider_headers`], [`HttpCaller::with_base_urls`]
+    /// and [`HttpCaller::with_regions`]).
+    pub fn with_transport(mut self, transport: Arc<dyn ProviderTransport>) -> Self {
+        self.provider_registry = Arc::new(RwLock::new(Arc::new(transport_registry(&transport))));
+        self.transport = transport;
+        self
+    }
+
+    /// Call providers with connection pooling, timeouts and keep-alive set
+    /// by `transport` instead of [`TransportConfig::from_env`], which the
+    /// constructors use.  Headers, base URLs and regions still come from
+    /// the config.
+    pub fn with_transport_config(
+        self,
+        transport: &TransportConfig,
+    ) -> Result<Self, HyperInferError> {
+        let caller = http_caller(&self.snapshot.load().config, transport)?;
+        Ok(self.with_transport(Arc::new(caller)))
+    }
+
+    /// Pre-establish connections to every provider host through the
+    /// transport `chat()` and `chat_stream()` call providers with.
+    ///
+    /// Call once at startup to take the TLS handshake off the first user
+    /// request.  Returns the number of provider hosts reached.
+    pub async fn warm_up(&self) -> usize {
+        self.transport.warm_up().await
+    }
+
+    /// Where a request for `model` would go now, after aliases and routing
+    /// rules.  Team aliases and allow-lists are not applied, since no key is
+    /// given; use [`dry_run`](Self::dry_run) for a key's view.
+    pub fn resolve(&self, model: &str) -> Result<ResolvedRoute, HyperInferError> {
+        let RouterSnapshot { config, router } = &*self.snapshot.load();
+        router
+            .resolve_with(None, model, config, None)
+            .map(|(model, provider)| ResolvedRoute { model, provider })
+            .ok_or_else(|| unroutable(router, config, None, model, None))
+    }
+
+    /// The limits in effect for `key`: its own quota, its organization's,
+    /// its virtual key's allow-list and budget, and any throttle or
+    /// revocation from the control plane.  Read from the local config and
+    /// policies only; use [`dry_run`](Self::dry_run) to see usage against
+    /// the limits.
+    pub fn limits(&self, key: &str) -> Result<KeyLimits, HyperInferError> {
+        let config = &self.snapshot.load().config;
+        let virtual_key = match config.virtual_key(&KeyPolicies::hash_key(key)) {
+            Some(virtual_key) => {
+                virtual_key.check_usable(chrono::Utc::now())?;
+                Some(virtual_key)
+            }
+            None => None,
+        };
+        let organization = virtual_key.and_then(|vk| config.organization_quota(&vk.team_id));
+        let policy = self.policies.get(key);
+        Ok(KeyLimits {
+            team_id: virtual_key.map(|vk| vk.team_id.clone()),
+            quota: config.quotas.get(key).cloned(),
+            organization_id: organization.map(|(org_id, _)| org_id.to_string()),
+            organization_quota: organization.map(|(_, quota)| quota.clone()),
+            allowed_models: virtual_key
+                .map(|vk| vk.allowed_models.clone())
+                .unwrap_or_default(),
+            budget_cents: virtual_key.and_then(|vk| vk.budget_cents),
+            throttled_rpm: match policy {
+                Some(KeyPolicy::Throttled { rpm }) => Some(rpm),
+                _ => None,
+            },
+            revoked: matches!(policy, Some(KeyPolicy::Revoked { .. })),
+        })
+    }
+
+    /// Estimate what `request` will cost before sending it.
+    ///
+    /// The model is resolved through aliases and routing like `chat()`.
+    /// Input tokens are estimated locally; the output side is priced at the
+    /// request's `max_tokens` (or the configured / model maximum), so
+    /// `max_total_cost_cents` is an upper bound.  Prices synced from the
+    /// control plane take precedence over the built-in table.  Fails if the
+    /// model has no known price.
+    pub async fn estimate_cost(
+        &self,
+        request: &ChatRequest,
+    ) -> Result<CostEstimate, HyperInferError> {
+        let RouterSnapshot { config, router } = &*self.snapshot.load();
+        let (model, _) = router
+            .resolve_with(None, &request.model, config, request.routing.as_ref())
+            .ok_or_else(|| {
+                HyperInferError::Config(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!(
+                        "Unknown model: '{}'. No routing rule or alias found.",
+                        request.model
+                    ),
+                ))
+            })?;
+        config.estimate_cost(&model, request).ok_or_else(|| {
+            HyperInferError::Config(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("No price known for model '{}'", model),
+            ))
+        })
+    }
+
+    /// Run `request` through everything `chat()` does before calling the
+    /// provider — validation, key policy, routing, model allow-lists and
+    /// `max_tokens` checks — and report where it would have gone and what it
+    /// would have cost.  Rate limits are read, not consumed: the report says
+    /// whether the key's default limits have room now for one more request
+    /// and its estimated input plus maximum output tokens.  Nothing is
+    /// cached, recorded or sent.
+    pub async fn dry_run(
+        &self,
+        key: &str,
+        request: &ChatRequest,
+    ) -> Result<DryRunReport, HyperInferError> {
+        request.validate()?;
+        validation::OutputValidator::for_format(request.response_format.as_ref())?;
+        self.policies.load(key).await;
+        if let Some(KeyPolicy::Revoked { reason }) = self.policies.get(key) {
+            return Err(HyperInferError::KeySuspended(
+                reason.unwrap_or_else(|| "revoked by control plane".to_string()),
+            ));
+        }
+        let identity = self.resolve_key(key).await?;
+        self.check_routing_allowed(identity.as_ref(), request)?;
+        let mut request = request.clone();
+        self.clamp_params(&mut request, identity.as_ref())?;
+        self.assign_experiment(&mut request, identity.as_ref());
+        let request = &request;
+        let limit_key = Self::limit_key(key, identity.as_ref());
+
+        let snapshot = self.snapshot.load();
+        let RouterSnapshot { config, router } = &*snapshot;
+        let (model, provider) = router
+            .resolve_with(
+                identity.as_ref().map(|vk| vk.team_id.as_str()),
+                &request.model,
+                config,
+                request.routing.as_ref(),
+            )
+            .ok_or_else(|| {
+                unroutable(
+                    router,
+                    config,
+                    identity.as_ref().map(|vk| vk.team_id.as_str()),
+                    &request.model,
+                    request.routing.as_ref(),
+                )
+            })?;
+        check_model_allowed(identity.as_ref(), &model)?;
+        let provider_name = provider.to_string();
+        if !config.api_keys.contains_key(&provider_name) {
+            return Err(HyperInferError::Config(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("API key not found for provider: {:?}", provider),
+            )));
+        }
+        if self
+            .provider_registry
+            .read()
+            .await
+            .get(&provider_name)
+            .is_none()
+        {
+            return Err(HyperInferError::Config(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Provider '{}' not found in registry", provider_name),
+            )));
+        }
+        let mut resolved_request = request.clone();
+        resolved_request.max_tokens = request
+            .max_tokens
+            .or_else(|| config.default_max_tokens(&model));
+        resolved_request.validate_max_tokens(&model)?;
+        let hedge = Self::hedge_plan(
+            &snapshot,
+            identity.as_ref(),
+            request,
+            &(model.clone(), provider),
+        );
+
+        let rate_limit = self
+            .rate_limiter
+            .get_usage(&limit_key)
+            .await
+            .map_err(|e| HyperInferError::rate_limit(e.to_string()))?;
+        let cost = config.estimate_cost(&model, request);
+        let tokens = u64::from(hyperinfer_core::tokenizer::estimate_request_tokens(request))
+            + u64::from(config.max_output_tokens(&model, request));
+
+        Ok(DryRunReport {
+            model,
+            provider: provider_name,
+            hedge_model: hedge.as_ref().map(|plan| plan.model.clone()),
+            hedge_provider: hedge.map(|plan| plan.provider_name),
+            max_tokens: resolved_request.max_tokens,
+            rate_limit_allowed: rate_limit.requests_remaining > 0
+                && rate_limit.tokens_remaining >= tokens,
+            rate_limit,
+            cost,
         })
     }
 
@@ -185,61 +843,768 @@ impl HyperInferClient {
         *guard = cfg;
     }
 
+    /// Per-key policies received from the control plane.
+    pub fn key_policies(&self) -> &KeyPolicies {
+        &self.policies
+    }
+
+    /// Namespaced runtime state, shared with other instances through Redis
+    /// when the client has it.  Take a namespace with
+    /// [`StateStore::scoped`] before storing anything.
+    pub fn state_store(&self) -> &StateStore {
+        &self.state
+    }
+
+    /// Reject or throttle `key` according to any control-plane policy.
+    async fn enforce_key_policy(&self, key: &str, model: &str) -> Result<(), HyperInferError> {
+        self.policies.load(key).await;
+        match self.policies.get(key) {
+            None => Ok(()),
+            Some(KeyPolicy::Revoked { reason }) => Err(HyperInferError::KeySuspended(
+                reason.unwrap_or_else(|| "revoked by control plane".to_string()),
+            )),
+            Some(KeyPolicy::Throttled { rpm }) => {
+                let (allowed, retry_after_ms) = self
+                    .rate_limiter
+                    .check_rpm_retry(&format!("policy:{}", key), rpm)
+                    .await
+                    .map_err(|e| HyperInferError::rate_limit(e.to_string()))?;
+                if allowed {
+                    Ok(())
+                } else {
+                    let reason = format!("Key throttled to {} requests per minute", rpm);
+                    self.telemetry.record_rejection(key, model, &reason);
+                    Err(HyperInferError::RateLimit {
+                        message: reason,
+                        retry_after_ms: (retry_after_ms > 0).then_some(retry_after_ms),
+                    })
+                }
+            }
+        }
+    }
+
+    /// Count the request against the RPM limit of any quota configured for
+    /// `key`, with the quota's window algorithm.
+    async fn enforce_quota(
+        &self,
+        key: &str,
+        limit_key: &str,
+        model: &str,
+    ) -> Result<(), HyperInferError> {
+        let quota = {
+            let config = &self.snapshot.load().config;
+            config
+                .quotas
+                .get(key)
+                .and_then(|q| Some((q.max_requests_per_minute?, q.rpm_window)))
+        };
+        let Some((rpm, window)) = quota else {
+            return Ok(());
+        };
+        let (allowed, retry_after_ms) = self
+            .rate_limiter
+            .check_rpm_window(&format!("quota:{}", limit_key), rpm, window)
+            .await
+            .map_err(|e| HyperInferError::rate_limit(e.to_string()))?;
+        if allowed {
+            Ok(())
+        } else {
+            self.telemetry
+                .record_rejection(key, model, "Quota RPM limit exceeded");
+            Err(rate_limit_exceeded(retry_after_ms))
+        }
+    }
+
+    /// Count the request against the shared limits of the organization the
+    /// key's team belongs to, on top of the key's and team's own.  Tokens
+    /// are charged up front from an estimate of the prompt.
+    async fn enforce_organization_quota(
+        &self,
+        key: &str,
+        identity: Option<&VirtualKey>,
+        request: &ChatRequest,
+    ) -> Result<(), HyperInferError> {
+        let Some(identity) = identity else {
+            return Ok(());
+        };
+        let quota = {
+            let config = &self.snapshot.load().config;
+            config
+                .organization_quota(&identity.team_id)
+                .map(|(org_id, quota)| (format!("org:{}", org_id), quota.clone()))
+        };
+        let Some((limit_key, quota)) = quota else {
+            return Ok(());
+        };
+        if let Some(rpm) = quota.max_requests_per_minute {
+            let (allowed, retry_after_ms) = self
+                .rate_limiter
+                .check_rpm_window(&limit_key, rpm, quota.rpm_window)
+                .await
+                .map_err(|e| HyperInferError::rate_limit(e.to_string()))?;
+            if !allowed {
+                self.telemetry.record_rejection(
+                    key,
+                    &request.model,
+                    "Organization RPM limit exceeded",
+                );
+                return Err(rate_limit_exceeded(retry_after_ms));
+            }
+        }
+        if let Some(tpm) = quota.max_tokens_per_minute {
+            let tokens = hyperinfer_core::tokenizer::estimate_request_tokens(request) as u64;
+            let (allowed, retry_after_ms) = self
+                .rate_limiter
+                .check_tpm(&limit_key, tpm, tokens)
+                .await
+                .map_err(|e| HyperInferError::rate_limit(e.to_string()))?;
+            if !allowed {
+                self.telemetry.record_rejection(
+                    key,
+                    &request.model,
+                    "Organization TPM limit exceeded",
+                );
+                return Err(rate_limit_exceeded(retry_after_ms));
+            }
+        }
+        Ok(())
+    }
+
+    /// Look up the virtual key the control plane issued for the raw `key`.
+    ///
+    /// Returns `Ok(None)` for keys the control plane does not know about,
+    /// which are served as before.  Disabled or expired virtual keys are
+    /// rejected with [`HyperInferError::Forbidden`].
+    pub async fn resolve_key(&self, key: &str) -> Result<Option<VirtualKey>, HyperInferError> {
+        let config = &self.snapshot.load().config;
+        let Some(virtual_key) = config.virtual_key(&KeyPolicies::hash_key(key)) else {
+            return Ok(None);
+        };
+        virtual_key.check_usable(chrono::Utc::now())?;
+        Ok(Some(virtual_key.clone()))
+    }
+
+    /// Hedge call for `request`, whose primary route is `primary`, when
+    /// hedging is configured, a fallback is available and both sides stay
+    /// within the hedging output cap.
+    fn hedge_plan(
+        snapshot: &RouterSnapshot,
+        identity: Option<&VirtualKey>,
+        request: &ChatRequest,
+        primary: &(String, Provider),
+    ) -> Option<hedging::HedgePlan> {
+        if request.routing.is_some() {
+            return None;
+        }
+        let config = &snapshot.config;
+        let hedging = config.hedging.as_ref()?;
+        let primary_max_tokens = request
+            .max_tokens
+            .or_else(|| config.default_max_tokens(&primary.0));
+        if !hedging.applies_to(primary_max_tokens) {
+            return None;
+        }
+        let (model, provider) = snapshot.router.hedge_target(
+            identity.map(|vk| vk.team_id.as_str()),
+            &request.model,
+            primary,
+            config,
+        )?;
+        check_model_allowed(identity, &model).ok()?;
+        let provider_name = provider.to_string();
+        let api_key = config.api_keys.get(&provider_name)?.clone();
+
+        let mut hedge_request = request.clone();
+        hedge_request.max_tokens = request
+            .max_tokens
+            .or_else(|| config.default_max_tokens(&model));
+        if !hedging.applies_to(hedge_request.max_tokens)
+            || hedge_request.validate_max_tokens(&model).is_err()
+        {
+            return None;
+        }
+        hedge_request.model = model.clone();
+        Some(hedging::HedgePlan {
+            after: std::time::Duration::from_millis(hedging.after_ms),
+            model,
+            provider_name,
+            api_key,
+            request: hedge_request,
+        })
+    }
+
+    /// Second attempt at `request` after `sent`, its resolved form, was
+    /// rejected by `primary` as too long, as `request.context_overflow`
+    /// asks.  `None` when there is nothing to trim or no larger model.
+    async fn context_overflow_retry(
+        &self,
+        identity: Option<&VirtualKey>,
+        request: &ChatRequest,
+        sent: &ChatRequest,
+        primary: &(String, Provider),
+        primary_call: (&Arc<dyn LlmProvider>, &str),
+    ) -> Option<ContextRetry> {
+        match request.context_overflow? {
+            ContextOverflow::Trim => {
+                let window = {
+                    let config = &self.snapshot.load().config;
+                    context::recovery_window(sent, config.context_window(&primary.0))
+                };
+                let mut retry = sent.clone();
+                if self.trim_to_window(&mut retry, &primary.0, window).await == 0 {
+                    return None;
+                }
+                Some(ContextRetry {
+                    model: primary.0.clone(),
+                    provider_name: primary.1.to_string(),
+                    provider: primary_call.0.clone(),
+                    api_key: primary_call.1.to_string(),
+                    request: retry,
+                })
+            }
+            ContextOverflow::LongerContextModel if request.routing.is_none() => {
+                let (model, provider_name, api_key, max_tokens) = {
+                    let snapshot = self.snapshot.load();
+                    let config = &snapshot.config;
+                    let (model, provider) = snapshot.router.longer_context_target(
+                        identity.map(|vk| vk.team_id.as_str()),
+                        &request.model,
+                        primary,
+                        config,
+                    )?;
+                    check_model_allowed(identity, &model).ok()?;
+                    let provider_name = provider.to_string();
+                    let api_key = config.api_keys.get(&provider_name)?.clone();
+                    let max_tokens = request
+                        .max_tokens
+                        .or_else(|| config.default_max_tokens(&model));
+                    (model, provider_name, api_key, max_tokens)
+                };
+                let provider = self.provider_registry.read().await.get(&provider_name)?;
+                let mut retry = request.clone();
+                retry.max_tokens = max_tokens;
+                retry.validate_max_tokens(&model).ok()?;
+                retry.model = model.clone();
+                self.prepare_request(&mut retry, &model).await;
+                Some(ContextRetry {
+                    model,
+                    provider_name,
+                    provider,
+                    api_key,
+                    request: retry,
+                })
+            }
+            // A routing override picks the model itself.
+            ContextOverflow::LongerContextModel => None,
+        }
+    }
+
+    /// Compress and trim `request`, already resolved to `model`, as its
+    /// options and the config ask.  Returns the compression savings.
+    async fn prepare_request(
+        &self,
+        request: &mut ChatRequest,
+        model: &str,
+    ) -> Option<CompressionStats> {
+        let stats = request
+            .compression
+            .clone()
+            .and_then(|options| compression::compress_request(request, &options));
+        self.fit_context(request, model).await;
+        stats
+    }
+
+    /// Shorten `request`, already resolved to `model`, to fit the model's
+    /// context window when context management is configured.
+    async fn fit_context(&self, request: &mut ChatRequest, model: &str) {
+        let window = {
+            let config = &self.snapshot.load().config;
+            config
+                .context
+                .as_ref()
+                .and_then(|context| context.context_window(model))
+        };
+        if let Some(window) = window {
+            self.trim_to_window(request, model, window).await;
+        }
+    }
+
+    /// Shorten `request`, already resolved to `model`, to fit a
+    /// `window`-token context with the configured strategy, or by dropping
+    /// the oldest messages without one.  Returns how many were removed.
+    async fn trim_to_window(&self, request: &mut ChatRequest, model: &str, window: u32) -> usize {
+        let (strategy, summary_route) = {
+            let RouterSnapshot { config, router } = &*self.snapshot.load();
+            let strategy = config
+                .context
+                .as_ref()
+                .map(|context| context.strategy.clone())
+                .unwrap_or_default();
+            // The summary model reads the conversation, so it must be in
+            // the conversation's data region too.
+            let data_region = request.data_region.as_deref();
+            let summary_route = match &strategy {
+                hyperinfer_core::ContextStrategy::Summarize { model } => router
+                    .resolve(None, model, config)
+                    .filter(|(_, provider)| {
+                        data_region.is_none_or(|region| config.serves_region(provider, region))
+                    })
+                    .and_then(|(model, provider)| {
+                        let api_key = config.api_keys.get(&provider.to_string())?.clone();
+                        Some((model, provider.to_string(), api_key))
+                    }),
+                _ => None,
+            };
+            (strategy, summary_route)
+        };
+        let summarizer = match summary_route {
+            Some((model, provider_name, api_key)) => {
+                let registry = self.provider_registry.read().await;
+                registry
+                    .get(&provider_name)
+                    .map(|provider| context::Summarizer {
+                        provider,
+                        model,
+                        api_key,
+                        data_region: request.data_region.clone(),
+                    })
+            }
+            None => None,
+        };
+        let removed = context::fit_to_window(request, window, &strategy, summarizer.as_ref()).await;
+        if removed > 0 {
+            tracing::debug!(
+                "Trimmed {} messages to fit the {}-token context of {}",
+                removed,
+                window,
+                model
+            );
+        }
+        removed
+    }
+
+    /// Pin `request` to the data region of the caller's team, if it has one,
+    /// so every provider call made for it stays in the region.
+    fn pin_data_region(&self, request: &mut ChatRequest, identity: Option<&VirtualKey>) {
+        let config = &self.snapshot.load().config;
+        if let Some(region) = config.data_region(identity.map(|vk| vk.team_id.as_str())) {
+            request.data_region = Some(region.to_string());
+        }
+    }
+
+    /// Lower `request`'s sampling parameters to the bounds set for the
+    /// caller's team, noting in its usage metadata which were lowered.
+    fn clamp_params(
+        &self,
+        request: &mut ChatRequest,
+        identity: Option<&VirtualKey>,
+    ) -> Result<(), HyperInferError> {
+        let team_id = identity.map(|vk| vk.team_id.as_str());
+        let Some(clamps) = self.snapshot.load().config.param_clamps(team_id).copied() else {
+            return Ok(());
+        };
+        let clamped = clamps.apply(request);
+        if clamped.is_empty() {
+            return Ok(());
+        }
+        tracing::debug!(
+            model = %request.model,
+            "Clamped {} to the team's bounds",
+            clamped.join(", ")
+        );
+        request
+            .metadata
+            .insert(ParamClamps::METADATA_KEY.to_string(), clamped.join(","));
+        // A thinking budget may no longer fit in the lowered max_tokens.
+        request.validate()
+    }
+
+    /// Send `request` to an arm of the experiment its model is under, if
+    /// any, recording the assignment in its usage metadata.
+    fn assign_experiment(&self, request: &mut ChatRequest, identity: Option<&VirtualKey>) {
+        let snapshot = self.snapshot.load();
+        let team_id = identity.map(|vk| vk.team_id.as_str());
+        let Some(experiment) = snapshot.config.experiment(&request.model, team_id) else {
+            return;
+        };
+        let Some(arm) = experiment.choose(fastrand::f64(), fastrand::usize(..)) else {
+            return;
+        };
+        tracing::debug!(experiment = %request.model, arm = %arm.model, "Assigned experiment arm");
+        request.metadata.insert(
+            experiments::EXPERIMENT_METADATA_KEY.to_string(),
+            request.model.clone(),
+        );
+        request
+            .metadata
+            .insert(experiments::ARM_METADATA_KEY.to_string(), arm.model.clone());
+        request.model = arm.model.clone();
+    }
+
+    /// Whether `feature` is on for the team of `identity`; keys the control
+    /// plane does not know get the defaults.
+    fn feature_enabled(&self, identity: Option<&VirtualKey>, feature: TeamFeature) -> bool {
+        self.snapshot
+            .load()
+            .config
+            .feature_enabled(identity.map(|vk| vk.team_id.as_str()), feature)
+    }
+
+    /// Refuse `request`'s routing override if the caller's team may not
+    /// use one.
+    fn check_routing_allowed(
+        &self,
+        identity: Option<&VirtualKey>,
+        request: &ChatRequest,
+    ) -> Result<(), HyperInferError> {
+        if request.routing.is_some()
+            && !self.feature_enabled(identity, TeamFeature::EnableRoutingOverrides)
+        {
+            return Err(HyperInferError::Forbidden(
+                "Routing overrides are switched off for this team".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Identity that rate limits hang off: the virtual key id when the key is
+    /// known to the control plane, otherwise the raw key string.
+    fn limit_key(key: &str, identity: Option<&VirtualKey>) -> String {
+        identity.map_or_else(|| key.to_string(), |vk| format!("vk:{}", vk.id))
+    }
+
     pub async fn inject_provider_registry(&self, external_registry: Arc<ProviderRegistry>) {
         let mut guard = self.provider_registry.write().await;
         *guard = external_registry;
     }
 
+    /// Send `request` to the model it resolves to.  Requests for a model
+    /// under a [`Cascade`] try the cascade's cheap model first; dry runs
+    /// report the cheap model's route.  Attached [`ChatHooks`] hear about
+    /// the call before and after.
     pub async fn chat(
         &self,
         key: &str,
         request: ChatRequest,
     ) -> Result<ChatResponse, HyperInferError> {
+        let Some(hooks) = &self.hooks else {
+            return self.dispatch_chat(key, request).await;
+        };
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let key_hash = KeyPolicies::hash_key(key);
+        hooks
+            .on_request(&RequestEvent {
+                request_id: request_id.clone(),
+                key_hash: key_hash.clone(),
+                request: request.clone(),
+                stream: false,
+            })
+            .await;
+        let started = std::time::Instant::now();
+        let result = self.dispatch_chat(key, request.clone()).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+        match &result {
+            Ok(response) => {
+                let cost_cents = self.cost_cents(&response.model, &response.usage);
+                hooks
+                    .on_response(&ResponseEvent {
+                        request_id,
+                        key_hash,
+                        request,
+                        response: response.clone(),
+                        latency_ms,
+                        cost_cents,
+                    })
+                    .await;
+            }
+            Err(e) => {
+                hooks
+                    .on_error(&ErrorEvent {
+                        request_id,
+                        key_hash,
+                        request,
+                        error: e.to_string(),
+                        latency_ms,
+                        stream: false,
+                    })
+                    .await;
+            }
+        }
+        result
+    }
+
+    async fn dispatch_chat(
+        &self,
+        key: &str,
+        request: ChatRequest,
+    ) -> Result<ChatResponse, HyperInferError> {
+        let cascade = self
+            .snapshot
+            .load()
+            .config
+            .cascades
+            .get(&request.model)
+            .cloned();
+        match cascade {
+            Some(cascade) if request.dry_run => {
+                let request = ChatRequest {
+                    model: cascade.cheap_model,
+                    ..request
+                };
+                self.chat_direct(key, request).await
+            }
+            Some(cascade) => self.chat_cascade(key, request, &cascade).await,
+            None => self.chat_direct(key, request).await,
+        }
+    }
+
+    /// Send `request` to `cascade`'s cheap model, keeping the answer if it
+    /// passes every verifier and otherwise sending the request again to
+    /// the expensive model.  A failed cheap call escalates too.  Every
+    /// attempt, judge calls included, is recorded in usage tagged with its
+    /// step; the outcome and the saving over calling the expensive model
+    /// directly go to the `hyperinfer.cascade.*` metrics and the response
+    /// metadata.
+    async fn chat_cascade(
+        &self,
+        key: &str,
+        request: ChatRequest,
+        cascade: &Cascade,
+    ) -> Result<ChatResponse, HyperInferError> {
+        let name = request.model.clone();
+        let tagged = |mut attempt: ChatRequest, step: &str| {
+            attempt
+                .metadata
+                .insert(cascade::CASCADE_METADATA_KEY.to_string(), name.clone());
+            attempt
+                .metadata
+                .insert(cascade::STEP_METADATA_KEY.to_string(), step.to_string());
+            attempt
+        };
+        let cheap_request = ChatRequest {
+            model: cascade.cheap_model.clone(),
+            ..request.clone()
+        };
+        // Cents spent on attempts that did not produce the final answer, or
+        // on the whole cascade when the cheap answer is kept.
+        let mut spent = 0.0;
+        match self.chat_direct(key, tagged(cheap_request, "cheap")).await {
+            Ok(mut response) => {
+                spent += self.cost_cents(&response.model, &response.usage);
+                let answer = response
+                    .choices
+                    .first()
+                    .map(|choice| choice.message.content.clone())
+                    .unwrap_or_default();
+                let mut accepted = true;
+                for verifier in &cascade.verifiers {
+                    let passed = match verifier.check(&answer) {
+                        Some(passed) => passed,
+                        None => {
+                            let Some(judge) = verifier.judge_request(&request, &answer) else {
+                                continue;
+                            };
+                            match self.chat_direct(key, tagged(judge, "judge")).await {
+                                Ok(reply) => {
+                                    spent += self.cost_cents(&reply.model, &reply.usage);
+                                    reply.choices.first().is_some_and(|choice| {
+                                        CascadeVerifier::judge_passed(&choice.message.content)
+                                    })
+                                }
+                                Err(e) => {
+                                    tracing::warn!(cascade = %name, error = %e, "Cascade judge failed");
+                                    false
+                                }
+                            }
+                        }
+                    };
+                    if !passed {
+                        accepted = false;
+                        break;
+                    }
+                }
+                if accepted {
+                    let expensive_model = self.resolved_model(key, &cascade.expensive_model).await;
+                    let saved = self.cost_cents(&expensive_model, &response.usage) - spent;
+                    crate::telemetry_otlp::record_cascade(&name, "accepted", saved);
+                    Self::tag_cascade_outcome(&mut response, "cheap", saved);
+                    return Ok(response);
+                }
+                tracing::debug!(cascade = %name, "Cheap answer failed verification; escalating");
+            }
+            Err(e) => {
+                tracing::warn!(cascade = %name, error = %e, "Cheap model failed; escalating");
+            }
+        }
+        let expensive_request = ChatRequest {
+            model: cascade.expensive_model.clone(),
+            ..request
+        };
+        let mut response = self
+            .chat_direct(key, tagged(expensive_request, "escalated"))
+            .await?;
+        crate::telemetry_otlp::record_cascade(&name, "escalated", -spent);
+        Self::tag_cascade_outcome(&mut response, "escalated", -spent);
+        Ok(response)
+    }
+
+    fn tag_cascade_outcome(response: &mut ChatResponse, step: &str, saved_cents: f64) {
+        response
+            .metadata
+            .insert(cascade::STEP_METADATA_KEY.to_string(), step.to_string());
+        response.metadata.insert(
+            cascade::SAVED_METADATA_KEY.to_string(),
+            format!("{:.4}", saved_cents),
+        );
+    }
+
+    /// Cost in cents of `usage` on `model` at the configured prices.
+    fn cost_cents(&self, model: &str, usage: &Usage) -> f64 {
+        hyperinfer_core::pricing::configured_cost_cents(
+            &self.snapshot.load().config.model_prices,
+            model,
+            chrono::Utc::now(),
+            u64::from(usage.input_tokens),
+            u64::from(usage.output_tokens),
+        )
+    }
+
+    /// The model `model` resolves to for `key`, or `model` itself when it
+    /// does not resolve.
+    async fn resolved_model(&self, key: &str, model: &str) -> String {
+        let identity = self.resolve_key(key).await.ok().flatten();
+        let snapshot = self.snapshot.load();
+        snapshot
+            .router
+            .resolve(
+                identity.as_ref().map(|vk| vk.team_id.as_str()),
+                model,
+                &snapshot.config,
+            )
+            .map_or_else(|| model.to_string(), |(model, _)| model)
+    }
+
+    async fn chat_direct(
+        &self,
+        key: &str,
+        mut request: ChatRequest,
+    ) -> Result<ChatResponse, HyperInferError> {
+        let _in_flight = self.enter()?;
+        if request.dry_run {
+            let report = self.dry_run(key, &request).await?;
+            return Ok(ChatResponse {
+                model: report.model.clone(),
+                dry_run: Some(report),
+                ..Default::default()
+            });
+        }
         request.validate()?;
+        let validator = validation::OutputValidator::for_format(request.response_format.as_ref())?;
+        self.enforce_key_policy(key, &request.model).await?;
+        let identity = self.resolve_key(key).await?;
+        self.check_routing_allowed(identity.as_ref(), &request)?;
+        if let Some(vk) = &identity {
+            self.telemetry.record_key_use(&vk.id);
+        }
+        self.pin_data_region(&mut request, identity.as_ref());
+        self.clamp_params(&mut request, identity.as_ref())?;
+        self.assign_experiment(&mut request, identity.as_ref());
+        let limit_key = Self::limit_key(key, identity.as_ref());
+        // Responses are cached and shared as the provider sent them; the
+        // team's cleanup runs on each copy handed back.
+        let postprocess = {
+            let transforms = self
+                .snapshot
+                .load()
+                .config
+                .output_transforms(identity.as_ref().map(|vk| vk.team_id.as_str()))
+                .to_vec();
+            let stop = request.stop.clone().unwrap_or_default();
+            move |mut response: ChatResponse| {
+                OutputTransform::apply_all(&transforms, &stop, &mut response);
+                response
+            }
+        };
 
-        // 0. Exact-match cache lookup (before rate-limiting to avoid wasting quota).
-        if let Some(cached) = self.cache.get(&request).await {
-            return Ok(cached);
+        // 0. Exact-match cache lookup (before rate-limiting to avoid wasting
+        //    quota), unless the team has the cache switched off.
+        let use_cache = self.feature_enabled(identity.as_ref(), TeamFeature::EnableCache);
+        if use_cache {
+            if let Some(cached) = self.cache.get(&request).await {
+                return Ok(postprocess(cached));
+            }
         }
 
+        // Identical requests under the same key share one call while it is
+        // in flight, when the caller's team has single-flight enabled.
+        let flight_key = {
+            let config = &self.snapshot.load().config;
+            let team_id = identity.as_ref().map(|vk| vk.team_id.as_str());
+            config
+                .single_flight
+                .as_ref()
+                .filter(|single_flight| single_flight.enabled_for(team_id))
+                .and_then(|_| self.cache.cache_key(&request))
+                .map(|request_key| format!("{}:{}", limit_key, request_key))
+        };
+
         // Create a root OTel span following the GenAI Semantic Conventions.
         // We use `.instrument(span)` on the inner async block so the span is
         // properly propagated across every `.await` point (using `span.enter()`
         // in an async function is unsafe — the guard can survive suspension).
+        // Rate limiting, routing, the provider call and the telemetry write
+        // each get a child span so traces show where the latency goes.
         let span = tracing::info_span!(
             "gen_ai.chat",
             gen_ai.operation.name = "chat",
             gen_ai.request.model = %request.model,
         );
 
-        async move {
+        let call = async move {
             let start = std::time::Instant::now();
 
             // 1. Check rate limit
-            let allowed = self.rate_limiter.is_allowed(key, 1).await;
-            if let Err(e) = allowed {
-                return Err(HyperInferError::RateLimit(e.to_string()));
-            }
-            if !allowed.unwrap() {
-                return Err(HyperInferError::RateLimit(
-                    "Rate limit exceeded".to_string(),
-                ));
+            let allowed = self
+                .rate_limiter
+                .is_allowed(&limit_key, 1)
+                .instrument(tracing::info_span!("gen_ai.rate_limit"))
+                .await;
+            let (allowed, retry_after_ms) =
+                allowed.map_err(|e| HyperInferError::rate_limit(e.to_string()))?;
+            if !allowed {
+                self.telemetry
+                    .record_rejection(key, &request.model, "Rate limit exceeded");
+                return Err(rate_limit_exceeded(retry_after_ms));
             }
+            self.enforce_quota(key, &limit_key, &request.model).await?;
+            self.enforce_organization_quota(key, identity.as_ref(), &request)
+                .await?;
 
             // 2. Resolve model alias
-            let (model, provider, api_key, config_snapshot) = {
-                let config = self.config.read().await;
-                let resolved = self.router.resolve(&request.model, &config);
+            let route_span = tracing::info_span!(
+                "gen_ai.route",
+                gen_ai.request.model = %request.model,
+            );
+            let (mut model, provider, mut api_key, hedge_plan, snapshot) = async {
+                let snapshot = self.snapshot.load();
+                let config = &snapshot.config;
+                let resolved = snapshot.router.resolve_with(
+                    identity.as_ref().map(|vk| vk.team_id.as_str()),
+                    &request.model,
+                    config,
+                    request.routing.as_ref(),
+                );
 
                 let (model, provider) = resolved.ok_or_else(|| {
-                    HyperInferError::Config(std::io::Error::new(
-                        std::io::ErrorKind::NotFound,
-                        format!(
-                            "Unknown model: '{}'. No routing rule or alias found.",
-                            request.model
-                        ),
-                    ))
+                    unroutable(
+                        &snapshot.router,
+                        config,
+                        identity.as_ref().map(|vk| vk.team_id.as_str()),
+                        &request.model,
+                        request.routing.as_ref(),
+                    )
                 })?;
+                check_model_allowed(identity.as_ref(), &model)?;
 
                 let api_key = config
                     .api_keys
@@ -252,20 +1617,26 @@ impl HyperInferClient {
                         ))
                     })?;
 
-                (model, provider, api_key, Arc::new(config.clone()))
-            };
+                let hedge_plan = Self::hedge_plan(
+                    &snapshot,
+                    identity.as_ref(),
+                    &request,
+                    &(model.clone(), provider.clone()),
+                );
 
-            // Enrich span with the resolved provider and final model name.
-            let provider_name = provider.to_string();
-            crate::telemetry_otlp::set_gen_ai_attributes(
-                &tracing::Span::current(),
-                &provider_name,
-                &model,
-                "chat",
-            );
+                Ok::<_, HyperInferError>((model, provider, api_key, hedge_plan, snapshot))
+            }
+            .instrument(route_span.clone())
+            .await?;
+
+            // Enrich spans with the resolved provider and final model name.
+            let mut provider_name = provider.to_string();
+            for span in [&tracing::Span::current(), &route_span] {
+                crate::telemetry_otlp::set_gen_ai_attributes(span, &provider_name, &model, "chat");
+            }
 
             // 3. Execute HTTP call via provider registry
-            let llm_provider = {
+            let mut llm_provider = {
                 let registry = self.provider_registry.read().await;
                 registry.get(&provider_name).ok_or_else(|| {
                     HyperInferError::Config(std::io::Error::new(
@@ -276,66 +1647,237 @@ impl HyperInferClient {
             };
 
             let mut resolved_request = request.clone();
+            resolved_request.max_tokens = request
+                .max_tokens
+                .or_else(|| snapshot.config.default_max_tokens(&model));
+            resolved_request.validate_max_tokens(&model)?;
             resolved_request.model = model.clone();
-            let response = llm_provider.chat(&resolved_request, &api_key).await?;
+            let compression = self.prepare_request(&mut resolved_request, &model).await;
+            let mut hedge_plan = hedge_plan;
+            if let Some(plan) = hedge_plan.as_mut() {
+                self.prepare_request(&mut plan.request, &plan.model).await;
+            }
+            if let Some(stats) = compression {
+                crate::telemetry_otlp::set_compression_attributes(&tracing::Span::current(), stats);
+            }
+            let provider_span = tracing::info_span!("gen_ai.provider_call");
+            crate::telemetry_otlp::set_gen_ai_attributes(
+                &provider_span,
+                &provider_name,
+                &model,
+                "chat",
+            );
+            let hedge = match hedge_plan {
+                Some(plan) => {
+                    let registry = self.provider_registry.read().await;
+                    registry
+                        .get(&plan.provider_name)
+                        .map(|hedge_provider| (plan, hedge_provider))
+                }
+                None => None,
+            };
+            let hedge_call = hedge.as_ref().map(|(plan, hedge_provider)| {
+                let hedge_span = tracing::info_span!("gen_ai.provider_call", hedge = true);
+                crate::telemetry_otlp::set_gen_ai_attributes(
+                    &hedge_span,
+                    &plan.provider_name,
+                    &plan.model,
+                    "chat",
+                );
+                (
+                    plan.after,
+                    hedge_provider
+                        .chat(&plan.request, &plan.api_key)
+                        .instrument(hedge_span),
+                )
+            });
+            let (result, mut winner) = hedging::race(
+                llm_provider
+                    .chat(&resolved_request, &api_key)
+                    .instrument(provider_span.clone()),
+                hedge_call,
+            )
+            .await;
+            // Retry once, trimmed or on a larger model, when the prompt did
+            // not fit and the request asked for that.
+            let result = match result {
+                Err(e) if e.provider_error_kind() == Some(ProviderErrorKind::ContextLength) => {
+                    let retry = self
+                        .context_overflow_retry(
+                            identity.as_ref(),
+                            &request,
+                            &resolved_request,
+                            &(model.clone(), provider.clone()),
+                            (&llm_provider, &api_key),
+                        )
+                        .await;
+                    match retry {
+                        Some(retry) => {
+                            tracing::info!(
+                                "{}/{} rejected the prompt as too long; retrying with {} messages on {}/{}",
+                                provider_name,
+                                model,
+                                retry.request.messages.len(),
+                                retry.provider_name,
+                                retry.model
+                            );
+                            winner = hedging::Winner::Primary;
+                            model = retry.model;
+                            provider_name = retry.provider_name;
+                            llm_provider = retry.provider;
+                            api_key = retry.api_key;
+                            resolved_request = retry.request;
+                            for span in [&tracing::Span::current(), &provider_span] {
+                                crate::telemetry_otlp::set_gen_ai_attributes(
+                                    span,
+                                    &provider_name,
+                                    &model,
+                                    "chat",
+                                );
+                            }
+                            llm_provider
+                                .chat(&resolved_request, &api_key)
+                                .instrument(provider_span.clone())
+                                .await
+                        }
+                        None => Err(e),
+                    }
+                }
+                result => result,
+            };
+            let response = match result {
+                Ok(response) => response,
+                Err(e) => {
+                    self.record_error(key, &model, &provider_name, &e, start);
+                    return Err(e);
+                }
+            };
+            if let (hedging::Winner::Hedge, Some((plan, _))) = (winner, &hedge) {
+                tracing::info!(
+                    "Hedge request to {}/{} answered before {}/{}",
+                    plan.provider_name,
+                    plan.model,
+                    provider_name,
+                    model
+                );
+                model = plan.model.clone();
+                provider_name = plan.provider_name.clone();
+                for span in [&tracing::Span::current(), &provider_span] {
+                    crate::telemetry_otlp::set_gen_ai_attributes(
+                        span,
+                        &provider_name,
+                        &model,
+                        "chat",
+                    );
+                }
+            }
 
-            // 4. Record OTel usage and response attributes on the span.
+            // Re-ask the winning provider while a `json_schema` response
+            // does not conform; usage below covers every attempt.
+            let response = match &validator {
+                Some(validator) => {
+                    let (provider, request, api_key) = match (winner, &hedge) {
+                        (hedging::Winner::Hedge, Some((plan, hedge_provider))) => {
+                            (hedge_provider, &plan.request, &plan.api_key)
+                        }
+                        _ => (&llm_provider, &resolved_request, &api_key),
+                    };
+                    validation::reask_until_valid(
+                        validator,
+                        provider.as_ref(),
+                        request,
+                        api_key,
+                        response,
+                        validation::MAX_REASKS,
+                    )
+                    .instrument(tracing::info_span!("gen_ai.validation"))
+                    .await
+                }
+                None => response,
+            };
+
+            // 4. Record OTel usage and response attributes on the spans.
             let elapsed = start.elapsed().as_millis() as u64;
             let input_tokens = response.usage.input_tokens;
             let output_tokens = response.usage.output_tokens;
 
-            crate::telemetry_otlp::set_gen_ai_usage(
-                &tracing::Span::current(),
+            for span in [&tracing::Span::current(), &provider_span] {
+                crate::telemetry_otlp::set_gen_ai_usage(span, input_tokens, output_tokens);
+            }
+            crate::telemetry_otlp::record_gen_ai_token_usage(
+                &provider_name,
+                &model,
+                "chat",
                 input_tokens,
                 output_tokens,
             );
+            crate::telemetry_otlp::record_gen_ai_duration(
+                &provider_name,
+                &model,
+                "chat",
+                start.elapsed(),
+                None,
+            );
 
             let finish_reason = response
                 .choices
                 .first()
                 .and_then(|c| c.finish_reason.as_deref())
                 .unwrap_or("unknown");
-            crate::telemetry_otlp::set_gen_ai_response(
-                &tracing::Span::current(),
-                &response.id,
-                finish_reason,
-            );
+            for span in [&tracing::Span::current(), &provider_span] {
+                crate::telemetry_otlp::set_gen_ai_response(span, &response.id, finish_reason);
+            }
+            drop(provider_span);
 
-            // Store successful response in exact-match cache.
-            self.cache.set(&request, &response).await;
-
-            // Record async Redis telemetry off the critical path.
-            let telemetry = self.telemetry.clone();
-            let key_owned = key.to_string();
-            let model_owned = model.clone();
-            tokio::spawn(async move {
-                if let Err(e) = telemetry
-                    .record_with_tokens(
-                        &key_owned,
-                        &model_owned,
-                        input_tokens,
-                        output_tokens,
-                        elapsed,
-                    )
-                    .await
-                {
-                    tracing::warn!(error = %e, "telemetry record failed");
-                }
-            });
+            // Store successful response in exact-match cache.  Responses
+            // that never passed validation are not worth replaying.
+            if use_cache
+                && !response
+                    .metadata
+                    .contains_key(validation::ERROR_METADATA_KEY)
+            {
+                self.cache.set(&request, &response).await;
+            }
+
+            // Buffer the usage record; telemetry writes it to Redis in
+            // batches off the critical path.
+            let telemetry_span = tracing::info_span!("gen_ai.telemetry");
+            crate::telemetry_otlp::set_gen_ai_attributes(
+                &telemetry_span,
+                &provider_name,
+                &model,
+                "chat",
+            );
+            if let Err(e) = self
+                .telemetry
+                .record_with_compression(
+                    key,
+                    &model,
+                    input_tokens,
+                    output_tokens,
+                    elapsed,
+                    &request.metadata,
+                    compression,
+                )
+                .instrument(telemetry_span)
+                .await
+            {
+                tracing::warn!(error = %e, "telemetry record failed");
+            }
 
             // Record usage for rate-limiter token bucket.
             let total_tokens = response.usage.input_tokens + response.usage.output_tokens;
             let _ = self
                 .rate_limiter
-                .record_usage(key, total_tokens as u64)
+                .record_usage(&limit_key, total_tokens as u64)
                 .await;
 
             // 5. Fire-and-forget traffic mirror (if configured).
             mirroring::maybe_mirror(
                 self.mirror.clone(),
-                self.http_caller.clone(),
-                self.router.clone(),
-                config_snapshot,
+                self.transport.clone(),
+                snapshot.router.clone(),
+                snapshot.config.clone(),
                 key.to_string(),
                 request,
             );
@@ -343,8 +1885,130 @@ impl HyperInferClient {
             // 6. Return response
             Ok(response)
         }
-        .instrument(span)
-        .await
+        .instrument(span);
+
+        let response = match flight_key {
+            Some(flight_key) => self.single_flight.run(flight_key, call).await,
+            None => call.await,
+        };
+        response.map(postprocess)
+    }
+
+    /// `chat()` as the next turn of a stored conversation: its history is
+    /// loaded from the conversation store and sent ahead of `request`'s
+    /// messages (after any system messages), and once the provider has
+    /// answered, the new messages and the reply are appended to it.  A
+    /// failed append is logged; the response is returned regardless.
+    pub async fn chat_in_conversation(
+        &self,
+        key: &str,
+        conversation_id: &str,
+        mut request: ChatRequest,
+    ) -> Result<ChatResponse, HyperInferError> {
+        let store = self.conversations.as_ref().ok_or_else(|| {
+            HyperInferError::Config(std::io::Error::other(
+                "No conversation store attached; see with_conversation_store",
+            ))
+        })?;
+        let history = store.history(conversation_id).await?;
+        let mut turn: Vec<ChatMessage> = request
+            .messages
+            .iter()
+            .filter(|message| message.role != MessageRole::System)
+            .cloned()
+            .collect();
+        conversations::with_history(&mut request, history);
+
+        let response = self.chat(key, request).await?;
+        if response.dry_run.is_none() {
+            turn.extend(
+                response
+                    .choices
+                    .first()
+                    .map(|choice| choice.message.clone()),
+            );
+            if let Err(e) = store.append(conversation_id, &turn).await {
+                tracing::warn!(
+                    conversation_id,
+                    error = %e,
+                    "Failed to append to conversation"
+                );
+            }
+        }
+        Ok(response)
+    }
+
+    /// Run every item of `dataset` against `model` through [`chat`], one
+    /// at a time, and score the answers.  Items whose call fails are
+    /// recorded as failed rather than ending the run.  Submit the result
+    /// with [`EvalStore::submit`]; run once per model to compare models.
+    ///
+    /// [`chat`]: HyperInferClient::chat
+    pub async fn run_eval(&self, key: &str, dataset: &EvalDataset, model: &str) -> NewEvalRun {
+        let mut results = Vec::with_capacity(dataset.items.len());
+        for (index, item) in dataset.items.iter().enumerate() {
+            let request = ChatRequest {
+                model: model.to_string(),
+                messages: item.messages.clone(),
+                ..Default::default()
+            };
+            let started = std::time::Instant::now();
+            let outcome = self.chat(key, request).await;
+            let latency_ms = started.elapsed().as_millis() as u64;
+            results.push(match outcome {
+                Ok(response) => {
+                    let answer = response
+                        .choices
+                        .first()
+                        .map(|choice| choice.message.content.clone())
+                        .unwrap_or_default();
+                    let mut result = EvalItemResult::scored(index as u32, item, answer, latency_ms);
+                    result.input_tokens = response.usage.input_tokens;
+                    result.output_tokens = response.usage.output_tokens;
+                    result
+                }
+                Err(e) => EvalItemResult::failed(index as u32, e.to_string(), latency_ms),
+            });
+        }
+        NewEvalRun {
+            dataset_id: dataset.id.clone(),
+            model: model.to_string(),
+            results,
+        }
+    }
+
+    /// Record a failed provider call off the critical path.
+    fn record_error(
+        &self,
+        key: &str,
+        model: &str,
+        provider: &str,
+        error: &HyperInferError,
+        start: std::time::Instant,
+    ) {
+        crate::telemetry_otlp::record_gen_ai_duration(
+            provider,
+            model,
+            "chat",
+            start.elapsed(),
+            Some(&crate::telemetry_otlp::gen_ai_error_type(error)),
+        );
+        let telemetry = self.telemetry.clone();
+        let key = key.to_string();
+        let model = model.to_string();
+        let provider = provider.to_string();
+        let error = error.to_string();
+        let elapsed = start.elapsed().as_millis() as u64;
+        let in_flight = self.in_flight.track();
+        tokio::spawn(async move {
+            let _in_flight = in_flight;
+            if let Err(e) = telemetry
+                .record_error(&key, &model, &provider, &error, elapsed)
+                .await
+            {
+                tracing::warn!(error = %e, "error telemetry record failed");
+            }
+        });
     }
 
     /// Stream token chunks for a chat request.
@@ -354,6 +2018,8 @@ impl HyperInferClient {
     /// in the stream has a non-`None` `finish_reason` and may carry `usage`.
     ///
     /// Rate-limiting and routing follow the same logic as `chat()`.
+    /// Attached [`ChatHooks`] hear about the request, and about the error
+    /// if the stream cannot be started.
     pub async fn chat_stream(
         &self,
         key: &str,
@@ -362,33 +2028,102 @@ impl HyperInferClient {
         Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send>>,
         HyperInferError,
     > {
-        request.validate()?;
+        let Some(hooks) = &self.hooks else {
+            return self.start_stream(key, request).await;
+        };
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let key_hash = KeyPolicies::hash_key(key);
+        hooks
+            .on_request(&RequestEvent {
+                request_id: request_id.clone(),
+                key_hash: key_hash.clone(),
+                request: request.clone(),
+                stream: true,
+            })
+            .await;
+        let started = std::time::Instant::now();
+        let result = self.start_stream(key, request.clone()).await;
+        if let Err(e) = &result {
+            hooks
+                .on_error(&ErrorEvent {
+                    request_id,
+                    key_hash,
+                    request,
+                    error: e.to_string(),
+                    latency_ms: started.elapsed().as_millis() as u64,
+                    stream: true,
+                })
+                .await;
+        }
+        result
+    }
 
-        // 1. Rate limit check (same as non-streaming path).
-        let allowed = self.rate_limiter.is_allowed(key, 1).await;
-        if let Err(e) = allowed {
-            return Err(HyperInferError::RateLimit(e.to_string()));
+    async fn start_stream(
+        &self,
+        key: &str,
+        mut request: ChatRequest,
+    ) -> Result<
+        Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send>>,
+        HyperInferError,
+    > {
+        let in_flight = self.enter()?;
+        request.validate()?;
+        if request.dry_run {
+            return Err(HyperInferError::Config(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "dry_run is not supported for streaming; use chat() or dry_run()",
+            )));
         }
-        if !allowed.unwrap() {
-            return Err(HyperInferError::RateLimit(
-                "Rate limit exceeded".to_string(),
+        self.enforce_key_policy(key, &request.model).await?;
+        let identity = self.resolve_key(key).await?;
+        if !self.feature_enabled(identity.as_ref(), TeamFeature::EnableStreaming) {
+            return Err(HyperInferError::Forbidden(
+                "Streaming is switched off for this team".to_string(),
             ));
         }
+        self.check_routing_allowed(identity.as_ref(), &request)?;
+        if let Some(vk) = &identity {
+            self.telemetry.record_key_use(&vk.id);
+        }
+        self.pin_data_region(&mut request, identity.as_ref());
+        self.clamp_params(&mut request, identity.as_ref())?;
+        self.assign_experiment(&mut request, identity.as_ref());
+        let limit_key = Self::limit_key(key, identity.as_ref());
 
-        // 2. Resolve model / provider / api key.
-        let (model, provider_name, api_key) = {
-            let config = self.config.read().await;
-            let resolved = self.router.resolve(&request.model, &config);
+        // 1. Rate limit check (same as non-streaming path).
+        let allowed = self.rate_limiter.is_allowed(&limit_key, 1).await;
+        let (allowed, retry_after_ms) =
+            allowed.map_err(|e| HyperInferError::rate_limit(e.to_string()))?;
+        if !allowed {
+            self.telemetry
+                .record_rejection(key, &request.model, "Rate limit exceeded");
+            return Err(rate_limit_exceeded(retry_after_ms));
+        }
+        self.enforce_quota(key, &limit_key, &request.model).await?;
+        self.enforce_organization_quota(key, identity.as_ref(), &request)
+            .await?;
+
+        // 2. Resolve model / provider / api key / output budget.
+        let (model, provider_name, api_key, max_tokens, hedge_plan) = {
+            let snapshot = self.snapshot.load();
+            let config = &snapshot.config;
+            let resolved = snapshot.router.resolve_with(
+                identity.as_ref().map(|vk| vk.team_id.as_str()),
+                &request.model,
+                config,
+                request.routing.as_ref(),
+            );
 
             let (model, provider) = resolved.ok_or_else(|| {
-                HyperInferError::Config(std::io::Error::new(
-                    std::io::ErrorKind::NotFound,
-                    format!(
-                        "Unknown model: '{}'. No routing rule or alias found.",
-                        request.model
-                    ),
-                ))
+                unroutable(
+                    &snapshot.router,
+                    config,
+                    identity.as_ref().map(|vk| vk.team_id.as_str()),
+                    &request.model,
+                    request.routing.as_ref(),
+                )
             })?;
+            check_model_allowed(identity.as_ref(), &model)?;
 
             let provider_name = provider.to_string();
             let api_key = config
@@ -402,7 +2137,16 @@ impl HyperInferClient {
                     ))
                 })?;
 
-            (model, provider_name, api_key)
+            let max_tokens = request
+                .max_tokens
+                .or_else(|| config.default_max_tokens(&model));
+            let hedge_plan = Self::hedge_plan(
+                &snapshot,
+                identity.as_ref(),
+                &request,
+                &(model.clone(), provider),
+            );
+            (model, provider_name, api_key, max_tokens, hedge_plan)
         };
 
         // 3. Get streaming provider from registry (already checks supports_streaming)
@@ -420,10 +2164,70 @@ impl HyperInferClient {
         };
 
         let mut resolved_request = request.clone();
+        resolved_request.max_tokens = max_tokens;
+        resolved_request.validate_max_tokens(&model)?;
         resolved_request.model = model.clone();
-        let provider_stream: Pin<
-            Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send>,
-        > = streaming_provider.into_stream(&resolved_request, &api_key);
+        let compression = self.prepare_request(&mut resolved_request, &model).await;
+        let mut hedge_plan = hedge_plan;
+        if let Some(plan) = hedge_plan.as_mut() {
+            self.prepare_request(&mut plan.request, &plan.model).await;
+        }
+        let start = std::time::Instant::now();
+        let provider_stream: hedging::ChunkStream =
+            streaming_provider.into_stream(&resolved_request, &api_key);
+
+        // With hedging, the primary has until `after` to produce its first
+        // chunk before the hedge stream is opened; the loser is dropped.
+        let hedge = match hedge_plan {
+            Some(plan) => {
+                let registry = self.provider_registry.read().await;
+                registry
+                    .get_streaming(&plan.provider_name)
+                    .map(|hedge_provider| (plan, hedge_provider))
+            }
+            None => None,
+        };
+        let (provider_stream, model, provider_name, usage) = match hedge {
+            Some((plan, hedge_provider)) => {
+                let hedge_stream = hedge_provider.into_stream(&plan.request, &plan.api_key);
+                let (result, winner) = hedging::race(
+                    hedging::first_chunk(provider_stream),
+                    Some((plan.after, hedging::first_chunk(hedge_stream))),
+                )
+                .await;
+                // A failure is surfaced through the stream so it is
+                // accounted like any other stream error.
+                let stream: hedging::ChunkStream = match result {
+                    Ok(stream) => stream,
+                    Err(e) => Box::pin(futures::stream::once(async move { Err(e) })),
+                };
+                match winner {
+                    hedging::Winner::Primary => (
+                        stream,
+                        model,
+                        provider_name,
+                        stream_usage::StreamUsage::new(&resolved_request),
+                    ),
+                    hedging::Winner::Hedge => {
+                        tracing::info!(
+                            "Hedge stream from {}/{} started before {}/{}",
+                            plan.provider_name,
+                            plan.model,
+                            provider_name,
+                            model
+                        );
+                        let usage = stream_usage::StreamUsage::new(&plan.request);
+                        (stream, plan.model, plan.provider_name, usage)
+                    }
+                }
+            }
+            None => (
+                provider_stream,
+                model,
+                provider_name,
+                stream_usage::StreamUsage::new(&resolved_request),
+            ),
+        };
         // Note: streaming responses are not cached — the stream is consumed
         // incrementally by the caller so we cannot inspect it here.
 
@@ -435,6 +2239,9 @@ impl HyperInferClient {
             gen_ai.request.model = %request.model,
         );
         crate::telemetry_otlp::set_gen_ai_attributes(&span, &provider_name, &model, "chat_stream");
+        if let Some(stats) = compression {
+            crate::telemetry_otlp::set_compression_attributes(&span, stats);
+        }
 
         // 5. Wrap the provider stream so usage/telemetry are recorded on
         //    termination — the same accounting chat() performs, but deferred
@@ -446,12 +2253,19 @@ impl HyperInferClient {
             telemetry: self.telemetry.clone(),
             rate_limiter: self.rate_limiter.clone(),
             key: key.to_string(),
+            limit_key,
+            metadata: request.metadata,
             model,
-            start: std::time::Instant::now(),
-            input_tokens: 0,
-            output_tokens: 0,
+            provider: provider_name,
+            start,
+            error: None,
+            error_type: None,
+            compression,
+            usage,
+            charged: 0,
             accounted: false,
             span,
+            in_flight: Some(in_flight),
         };
 
         Ok(Box::pin(stream))
@@ -19,8 +19,8 @@
 //! Thread-safety: the config is stored behind an `Arc<RwLock<…>>` so it can
 //! be hot-swapped at runtime without restarting the client.
 
+use crate::http_client::ProviderTransport;
 use crate::util::rand_f64;
-use crate::HttpCaller;
 use crate::Router;
 use hyperinfer_core::{types::Provider, ChatRequest, Config};
 use std::sync::{Arc, OnceLock};
@@ -65,7 +65,7 @@ pub type MirrorHandle = Arc<RwLock<Option<MirrorConfig>>>;
 /// The function returns immediately; the mirror call runs concurrently.
 pub fn maybe_mirror(
     mirror_handle: MirrorHandle,
-    http_caller: Arc<HttpCaller>,
+    transport: Arc<dyn ProviderTransport>,
     router: Arc<Router>,
     config_snapshot: Arc<Config>,
     _key: String,
@@ -97,7 +97,7 @@ pub fn maybe_mirror(
     request.model = mirror_cfg.model.clone();
 
     // Resolve provider for the mirror model — bail out early if not resolvable.
-    let resolved = router.resolve(&request.model, &config_snapshot);
+    let resolved = router.resolve(None, &request.model, &config_snapshot);
     let (model, provider) = match resolved {
         Some(r) => r,
         None => {
@@ -138,11 +138,9 @@ pub fn maybe_mirror(
     tokio::spawn(async move {
         let _permit = permit;
 
-        let result = match provider {
-            Provider::OpenAI => http_caller.call_openai(&model, &api_key, &request).await,
-            Provider::Anthropic => http_caller.call_anthropic(&model, &api_key, &request).await,
-            _ => unreachable!(),
-        };
+        let result = transport
+            .call_chat(&provider, &model, &api_key, &request)
+            .await;
 
         match result {
             Ok(resp) => {
@@ -181,6 +179,7 @@ mod tests {
             quotas: HashMap::new(),
             model_aliases: HashMap::new(),
             default_provider: None,
+            ..Default::default()
         }
     }
 
@@ -202,7 +201,7 @@ mod tests {
             model: "gpt-4o".to_string(),
             sample_rate: 0.0,
         })));
-        let http = Arc::new(HttpCaller::new().unwrap());
+        let http: Arc<dyn ProviderTransport> = Arc::new(crate::HttpCaller::new().unwrap());
         let router = Arc::new(Router::new(vec![]));
         let config = Arc::new(empty_config());
 
@@ -216,6 +215,15 @@ mod tests {
             temperature: None,
             stream: None,
             stop: None,
+            metadata: HashMap::new(),
+            response_format: None,
+            compression: None,
+            reasoning_effort: None,
+            thinking: None,
+            dry_run: false,
+            context_overflow: None,
+            routing: None,
+            data_region: None,
         };
 
         maybe_mirror(handle, http, router, config, "key".to_string(), request);
@@ -227,7 +235,7 @@ mod tests {
     async fn test_maybe_mirror_none_config_no_panic() {
         // With None config nothing should happen.
         let handle: MirrorHandle = Arc::new(RwLock::new(None));
-        let http = Arc::new(HttpCaller::new().unwrap());
+        let http: Arc<dyn ProviderTransport> = Arc::new(crate::HttpCaller::new().unwrap());
         let router = Arc::new(Router::new(vec![]));
         let config = Arc::new(empty_config());
 
@@ -241,6 +249,15 @@ mod tests {
             temperature: None,
             stream: None,
             stop: None,
+            metadata: HashMap::new(),
+            response_format: None,
+            compression: None,
+            reasoning_effort: None,
+            thinking: None,
+            dry_run: false,
+            context_overflow: None,
+            routing: None,
+            data_region: None,
         };
 
         maybe_mirror(handle, http, router, config, "key".to_string(), request);
@@ -254,7 +271,7 @@ mod tests {
             model: "unknown-llm-xyz".to_string(),
             sample_rate: 1.0,
         })));
-        let http = Arc::new(HttpCaller::new().unwrap());
+        let http: Arc<dyn ProviderTransport> = Arc::new(crate::HttpCaller::new().unwrap());
         let router = Arc::new(Router::new(vec![]));
         let config = Arc::new(empty_config());
 
@@ -268,6 +285,15 @@ mod tests {
             temperature: None,
             stream: None,
             stop: None,
+            metadata: HashMap::new(),
+            response_format: None,
+            compression: None,
+            reasoning_effort: None,
+            thinking: None,
+            dry_run: false,
+            context_overflow: None,
+            routing: None,
+            data_region: None,
         };
 
         maybe_mirror(handle, http, router, config, "key".to_string(), request);
@@ -298,7 +324,7 @@ mod tests {
             model: "gpt-4o".to_string(),
             sample_rate: 1.0,
         })));
-        let http = Arc::new(HttpCaller::new().unwrap());
+        let http: Arc<dyn ProviderTransport> = Arc::new(crate::HttpCaller::new().unwrap());
         let router = Arc::new(Router::new(vec![]));
         let config = Arc::new(empty_config());
 
@@ -312,6 +338,15 @@ mod tests {
             temperature: None,
             stream: None,
             stop: None,
+            metadata: HashMap::new(),
+            response_format: None,
+            compression: None,
+            reasoning_effort: None,
+            thinking: None,
+            dry_run: false,
+            context_overflow: None,
+            routing: None,
+            data_region: None,
         };
 
         maybe_mirror(handle, http, router, config, "key".to_string(), request);
@@ -0,0 +1,279 @@
+//! Per-key policies pushed by the control plane.
+//!
+//! The server publishes [`PolicyUpdate`]s on the Redis policy channel when a
+//! team's budget auto-action kicks in.  [`KeyPolicies`] keeps the latest
+//! policy for each key (by SHA-256 hash, since the server never sees raw
+//! keys) so `chat()` / `chat_stream()` can reject or throttle requests.
+//!
+//! Pub/sub only reaches clients that are running when an update goes out,
+//! so updates are also written to the shared [`StateStore`] under the
+//! `policies` namespace.  A client checks the store the first time it sees
+//! a key, and picks up policies set before it started.
+
+use crate::state::StateStore;
+#[cfg(feature = "redis")]
+use hyperinfer_core::redis::{ConfigManager, PolicyAction, PolicyUpdate};
+#[cfg(feature = "redis")]
+use hyperinfer_core::HyperInferError;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+#[cfg(feature = "redis")]
+use tokio::task::JoinHandle;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum KeyPolicy {
+    /// Cap the key at `rpm` requests per minute.
+    Throttled { rpm: u64 },
+    /// Reject every request for the key.
+    Revoked { reason: Option<String> },
+}
+
+/// Latest policy per key hash.  Cheap to clone; clones share state.
+#[derive(Clone, Default)]
+pub struct KeyPolicies {
+    inner: Arc<RwLock<HashMap<String, KeyPolicy>>>,
+    store: StateStore,
+    /// Key hashes already looked up in `store`.
+    loaded: Arc<RwLock<HashSet<String>>>,
+}
+
+impl KeyPolicies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Policies that also persist to and load from `store`.
+    pub fn with_store(store: &StateStore) -> Self {
+        Self {
+            store: store.scoped("policies"),
+            ..Self::default()
+        }
+    }
+
+    /// SHA-256 hex digest of `key`, matching `api_keys.key_hash` on the server.
+    pub fn hash_key(key: &str) -> String {
+        hex::encode(Sha256::digest(key.as_bytes()))
+    }
+
+    /// Apply an update received from the control plane.
+    #[cfg(feature = "redis")]
+    pub fn apply(&self, update: PolicyUpdate) {
+        let reason = update.reason.as_deref().unwrap_or("no reason given");
+        let mut policies = match self.inner.write() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match update.action {
+            PolicyAction::Revoke => {
+                tracing::warn!("API key revoked by control plane: {}", reason);
+                policies.insert(
+                    update.key,
+                    KeyPolicy::Revoked {
+                        reason: update.reason,
+                    },
+                );
+            }
+            PolicyAction::Throttle => match update.rpm_limit {
+                Some(rpm) => {
+                    tracing::warn!(
+                        "API key throttled to {} requests per minute: {}",
+                        rpm,
+                        reason
+                    );
+                    policies.insert(update.key, KeyPolicy::Throttled { rpm });
+                }
+                None => tracing::warn!("Ignoring throttle policy update without rpm_limit"),
+            },
+            PolicyAction::Restore => {
+                if policies.remove(&update.key).is_some() {
+                    tracing::info!("API key restored by control plane: {}", reason);
+                }
+            }
+            PolicyAction::Warn => {
+                tracing::warn!("API key reached its soft budget limit: {}", reason);
+            }
+            PolicyAction::Update => {
+                tracing::debug!("Ignoring generic policy update: {}", reason);
+            }
+        }
+    }
+
+    /// Write `update` to the store for clients that start later.
+    #[cfg(feature = "redis")]
+    pub async fn persist(&self, update: &PolicyUpdate) {
+        match (&update.action, update.rpm_limit) {
+            (PolicyAction::Revoke, _) => {
+                let policy = KeyPolicy::Revoked {
+                    reason: update.reason.clone(),
+                };
+                self.store.set(&update.key, &policy, None).await;
+            }
+            (PolicyAction::Throttle, Some(rpm)) => {
+                let policy = KeyPolicy::Throttled { rpm };
+                self.store.set(&update.key, &policy, None).await;
+            }
+            (PolicyAction::Restore, _) => self.store.delete(&update.key).await,
+            _ => {}
+        }
+    }
+
+    /// Look up the raw `key` in the store, once per key, unless an update
+    /// for it has already arrived.  Later changes come through `apply`.
+    pub async fn load(&self, key: &str) {
+        let hash = Self::hash_key(key);
+        {
+            let mut loaded = match self.loaded.write() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            if !loaded.insert(hash.clone()) {
+                return;
+            }
+        }
+        if let Some(policy) = self.store.get::<KeyPolicy>(&hash).await {
+            let mut policies = match self.inner.write() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            policies.entry(hash).or_insert(policy);
+        }
+    }
+
+    /// Policy currently in effect for the raw `key`, if any.
+    pub fn get(&self, key: &str) -> Option<KeyPolicy> {
+        let policies = match self.inner.read() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        policies.get(&Self::hash_key(key)).cloned()
+    }
+
+    /// Subscribe to the control-plane policy channel.  The subscription is
+    /// cancelled when the returned guard is dropped.
+    #[cfg(feature = "redis")]
+    pub async fn subscribe(&self, redis_url: &str) -> Result<PolicySubscription, HyperInferError> {
+        let manager = ConfigManager::new(redis_url)
+            .await
+            .map_err(|e| HyperInferError::Config(std::io::Error::other(e.to_string())))?;
+        self.subscribe_with(&manager).await
+    }
+
+    /// Apply policy updates received through `manager`.
+    #[cfg(feature = "redis")]
+    pub async fn subscribe_with(
+        &self,
+        manager: &ConfigManager,
+    ) -> Result<PolicySubscription, HyperInferError> {
+        let policies = self.clone();
+        let handle = manager
+            .subscribe_to_policy_updates(move |update| {
+                policies.apply(update.clone());
+                let policies = policies.clone();
+                tokio::spawn(async move { policies.persist(&update).await });
+            })
+            .await
+            .map_err(|e| HyperInferError::Config(std::io::Error::other(e.to_string())))?;
+        Ok(PolicySubscription(handle))
+    }
+}
+
+/// Aborts the policy subscription task on drop.
+#[cfg(feature = "redis")]
+pub struct PolicySubscription(JoinHandle<()>);
+
+#[cfg(feature = "redis")]
+impl Drop for PolicySubscription {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(all(test, feature = "redis"))]
+mod tests {
+    use super::*;
+
+    fn update(key: &str, action: PolicyAction, rpm_limit: Option<u64>) -> PolicyUpdate {
+        PolicyUpdate {
+            key: KeyPolicies::hash_key(key),
+            action,
+            reason: Some("Team budget exhausted".to_string()),
+            rpm_limit,
+        }
+    }
+
+    #[test]
+    fn test_revoke_and_restore() {
+        let policies = KeyPolicies::new();
+        policies.apply(update("sk-team", PolicyAction::Revoke, None));
+        assert_eq!(
+            policies.get("sk-team"),
+            Some(KeyPolicy::Revoked {
+                reason: Some("Team budget exhausted".to_string())
+            })
+        );
+        assert_eq!(policies.get("sk-other"), None);
+
+        policies.apply(update("sk-team", PolicyAction::Restore, None));
+        assert_eq!(policies.get("sk-team"), None);
+    }
+
+    #[test]
+    fn test_throttle() {
+        let policies = KeyPolicies::new();
+        policies.apply(update("sk-team", PolicyAction::Throttle, Some(5)));
+        assert_eq!(
+            policies.get("sk-team"),
+            Some(KeyPolicy::Throttled { rpm: 5 })
+        );
+    }
+
+    #[test]
+    fn test_throttle_without_limit_is_ignored() {
+        let policies = KeyPolicies::new();
+        policies.apply(update("sk-team", PolicyAction::Throttle, None));
+        assert_eq!(policies.get("sk-team"), None);
+    }
+
+    #[test]
+    fn test_warn_does_not_restrict() {
+        let policies = KeyPolicies::new();
+        policies.apply(update("sk-team", PolicyAction::Warn, None));
+        assert_eq!(policies.get("sk-team"), None);
+    }
+
+    #[tokio::test]
+    async fn test_persisted_policy_is_loaded_by_a_later_client() {
+        let store = StateStore::in_memory();
+        let before = KeyPolicies::with_store(&store);
+        before
+            .persist(&update("sk-team", PolicyAction::Revoke, None))
+            .await;
+
+        let after = KeyPolicies::with_store(&store);
+        after.load("sk-team").await;
+        assert_eq!(
+            after.get("sk-team"),
+            Some(KeyPolicy::Revoked {
+                reason: Some("Team budget exhausted".to_string())
+            })
+        );
+
+        before
+            .persist(&update("sk-team", PolicyAction::Restore, None))
+            .await;
+        let fresh = KeyPolicies::with_store(&store);
+        fresh.load("sk-team").await;
+        assert_eq!(fresh.get("sk-team"), None);
+    }
+
+    #[test]
+    fn test_hash_key_is_sha256_hex() {
+        assert_eq!(
+            KeyPolicies::hash_key("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,423 @@
+//! Record and replay provider interactions.
+//!
+//! [`RecordingTransport`] wraps a real transport and keeps every request it
+//! forwards together with the response, stream chunks or error that came
+//! back.  [`RecordingTransport::save`] writes them to a JSON cassette, which
+//! [`ReplayTransport`] serves back in tests, so the full `chat()` pipeline
+//! can be exercised deterministically and without provider credentials.
+//!
+//! Cassettes are meant to be checked in, so they are sanitized on the way
+//! out: provider API keys are never stored (and are scrubbed from any text
+//! that echoes them), and client-side request options that never reach the
+//! provider — metadata tags, compression settings — are dropped.
+
+use crate::http_client::ProviderTransport;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use hyperinfer_core::types::Provider;
+use hyperinfer_core::{ChatChunk, ChatRequest, ChatResponse, HyperInferError};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+/// Replaces API keys found in recorded text.
+const REDACTED: &str = "[REDACTED]";
+
+/// A provider error as recorded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedError {
+    /// HTTP status for API errors, `None` for transport failures.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub message: String,
+}
+
+impl RecordedError {
+    fn new(error: &HyperInferError, api_key: &str) -> Self {
+        match error {
+            HyperInferError::ApiError {
+                status, message, ..
+            } => Self {
+                status: Some(*status),
+                message: redact(message, api_key),
+            },
+            other => Self {
+                status: None,
+                message: redact(&other.to_string(), api_key),
+            },
+        }
+    }
+
+    fn to_error(&self) -> HyperInferError {
+        match self.status {
+            Some(status) => HyperInferError::api_error(status, self.message.clone()),
+            None => HyperInferError::Config(std::io::Error::other(self.message.clone())),
+        }
+    }
+}
+
+/// One request and what the provider answered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub provider: Provider,
+    pub model: String,
+    pub request: ChatRequest,
+    /// Set for non-streaming calls that succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<ChatResponse>,
+    /// Set for streaming calls: every chunk received, in order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<Vec<ChatChunk>>,
+    /// The error the call failed with, after any `chunks`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RecordedError>,
+}
+
+/// A set of recorded interactions, as stored on disk.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, HyperInferError> {
+        let json = std::fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| {
+            HyperInferError::Config(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), HyperInferError> {
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, json + "\n")?;
+        Ok(())
+    }
+}
+
+fn redact(text: &str, api_key: &str) -> String {
+    if api_key.is_empty() {
+        text.to_string()
+    } else {
+        text.replace(api_key, REDACTED)
+    }
+}
+
+/// `request` as stored and matched: without the options the client handles
+/// itself, and with `api_key` scrubbed from message text.
+fn sanitize(request: &ChatRequest, api_key: &str) -> ChatRequest {
+    let mut request = request.clone();
+    request.metadata.clear();
+    request.compression = None;
+    request.dry_run = false;
+    for message in &mut request.messages {
+        message.content = redact(&message.content, api_key);
+    }
+    request
+}
+
+/// A transport that forwards to another and records every interaction.
+pub struct RecordingTransport {
+    inner: Arc<dyn ProviderTransport>,
+    interactions: Arc<Mutex<Vec<Interaction>>>,
+}
+
+impl RecordingTransport {
+    pub fn new(inner: Arc<dyn ProviderTransport>) -> Self {
+        Self {
+            inner,
+            interactions: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Everything recorded so far.  Streams are recorded once they end.
+    pub fn cassette(&self) -> Cassette {
+        Cassette {
+            interactions: self
+                .interactions
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        }
+    }
+
+    /// Write everything recorded so far to `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), HyperInferError> {
+        self.cassette().save(path)
+    }
+}
+
+#[async_trait]
+impl ProviderTransport for RecordingTransport {
+    async fn call_chat(
+        &self,
+        provider: &Provider,
+        model: &str,
+        api_key: &str,
+        request: &ChatRequest,
+    ) -> Result<ChatResponse, HyperInferError> {
+        let result = self
+            .inner
+            .call_chat(provider, model, api_key, request)
+            .await;
+        let mut interaction = Interaction {
+            provider: provider.clone(),
+            model: model.to_string(),
+            request: sanitize(request, api_key),
+            response: None,
+            chunks: None,
+            error: None,
+        };
+        match &result {
+            Ok(response) => interaction.response = Some(response.clone()),
+            Err(e) => interaction.error = Some(RecordedError::new(e, api_key)),
+        }
+        self.interactions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(interaction);
+        result
+    }
+
+    fn call_stream(
+        &self,
+        provider: &Provider,
+        model: &str,
+        api_key: &str,
+        request: &ChatRequest,
+    ) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send + 'static>> {
+        let mut upstream = self.inner.call_stream(provider, model, api_key, request);
+        let mut interaction = Interaction {
+            provider: provider.clone(),
+            model: model.to_string(),
+            request: sanitize(request, api_key),
+            response: None,
+            chunks: Some(Vec::new()),
+            error: None,
+        };
+        let api_key = api_key.to_string();
+        let interactions = self.interactions.clone();
+        Box::pin(async_stream::stream! {
+            while let Some(item) = upstream.next().await {
+                match &item {
+                    Ok(chunk) => interaction.chunks.get_or_insert_with(Vec::new).push(chunk.clone()),
+                    Err(e) => interaction.error = Some(RecordedError::new(e, &api_key)),
+                }
+                yield item;
+            }
+            interactions
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(interaction);
+        })
+    }
+
+    async fn warm_up(&self) -> usize {
+        self.inner.warm_up().await
+    }
+}
+
+/// A transport answering from a [`Cassette`].
+///
+/// A call is answered by the first unused interaction recorded for the same
+/// provider, model and (sanitized) request; once those are used up, the
+/// last of them answers again.  Calls with no recorded match fail with a
+/// `NotFound` configuration error.
+pub struct ReplayTransport {
+    interactions: Vec<Interaction>,
+    used: Mutex<Vec<bool>>,
+}
+
+impl ReplayTransport {
+    pub fn new(cassette: Cassette) -> Self {
+        let used = vec![false; cassette.interactions.len()];
+        Self {
+            interactions: cassette.interactions,
+            used: Mutex::new(used),
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, HyperInferError> {
+        Ok(Self::new(Cassette::load(path)?))
+    }
+
+    fn find(
+        &self,
+        provider: &Provider,
+        model: &str,
+        api_key: &str,
+        request: &ChatRequest,
+    ) -> Result<&Interaction, HyperInferError> {
+        let request = sanitize(request, api_key);
+        let matching: Vec<usize> = self
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| &i.provider == provider && i.model == model && i.request == request)
+            .map(|(index, _)| index)
+            .collect();
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        let index = matching
+            .iter()
+            .copied()
+            .find(|&index| !used[index])
+            .or_else(|| matching.last().copied())
+            .ok_or_else(|| {
+                HyperInferError::Config(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("No recorded interaction for {} model '{}'", provider, model),
+                ))
+            })?;
+        used[index] = true;
+        Ok(&self.interactions[index])
+    }
+}
+
+#[async_trait]
+impl ProviderTransport for ReplayTransport {
+    async fn call_chat(
+        &self,
+        provider: &Provider,
+        model: &str,
+        api_key: &str,
+        request: &ChatRequest,
+    ) -> Result<ChatResponse, HyperInferError> {
+        let interaction = self.find(provider, model, api_key, request)?;
+        match (&interaction.response, &interaction.error) {
+            (Some(response), _) => Ok(response.clone()),
+            (None, Some(error)) => Err(error.to_error()),
+            (None, None) => Err(HyperInferError::Config(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Recorded {} call to '{}' has no response", provider, model),
+            ))),
+        }
+    }
+
+    fn call_stream(
+        &self,
+        provider: &Provider,
+        model: &str,
+        api_key: &str,
+        request: &ChatRequest,
+    ) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send + 'static>> {
+        let items: Vec<Result<ChatChunk, HyperInferError>> =
+            match self.find(provider, model, api_key, request) {
+                Ok(interaction) => interaction
+                    .chunks
+                    .iter()
+                    .flatten()
+                    .cloned()
+                    .map(Ok)
+                    .chain(interaction.error.iter().map(|e| Err(e.to_error())))
+                    .collect(),
+                Err(e) => vec![Err(e)],
+            };
+        Box::pin(futures::stream::iter(items))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyperinfer_core::types::{ChatMessage, MessageRole};
+
+    /// Answers chat calls with the request's first message as the id, and
+    /// streams with one chunk and an error echoing the API key.
+    struct EchoTransport;
+
+    #[async_trait]
+    impl ProviderTransport for EchoTransport {
+        async fn call_chat(
+            &self,
+            _provider: &Provider,
+            model: &str,
+            _api_key: &str,
+            request: &ChatRequest,
+        ) -> Result<ChatResponse, HyperInferError> {
+            Ok(ChatResponse {
+                id: request.messages[0].content.clone(),
+                model: model.to_string(),
+                ..Default::default()
+            })
+        }
+
+        fn call_stream(
+            &self,
+            _provider: &Provider,
+            model: &str,
+            api_key: &str,
+            _request: &ChatRequest,
+        ) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send + 'static>>
+        {
+            let chunk = ChatChunk {
+                model: model.to_string(),
+                delta: "hel".to_string(),
+                ..Default::default()
+            };
+            let error = HyperInferError::api_error(401, format!("bad key {}", api_key));
+            Box::pin(futures::stream::iter(vec![Ok(chunk), Err(error)]))
+        }
+    }
+
+    fn request(content: &str) -> ChatRequest {
+        ChatRequest {
+            model: "gpt-4".to_string(),
+            messages: vec![ChatMessage {
+                role: MessageRole::User,
+                content: content.to_string(),
+            }],
+            metadata: [("customer".to_string(), "acme".to_string())].into(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recorded_calls_replay() {
+        let recorder = RecordingTransport::new(Arc::new(EchoTransport));
+        let openai = Provider::OpenAI;
+        recorder
+            .call_chat(&openai, "gpt-4", "sk-secret", &request("one"))
+            .await
+            .unwrap();
+        let streamed: Vec<_> = recorder
+            .call_stream(&openai, "gpt-4", "sk-secret", &request("two"))
+            .collect()
+            .await;
+        assert_eq!(streamed.len(), 2);
+
+        let cassette = recorder.cassette();
+        assert_eq!(cassette.interactions.len(), 2);
+        let json = serde_json::to_string(&cassette).unwrap();
+        assert!(!json.contains("sk-secret"));
+        assert!(!json.contains("acme"));
+
+        // Replay matches on the request, whatever key and tags it carries.
+        let replay = ReplayTransport::new(cassette);
+        let response = replay
+            .call_chat(&openai, "gpt-4", "sk-other", &request("one"))
+            .await
+            .unwrap();
+        assert_eq!(response.id, "one");
+        let replayed: Vec<_> = replay
+            .call_stream(&openai, "gpt-4", "sk-other", &request("two"))
+            .collect()
+            .await;
+        assert_eq!(replayed[0].as_ref().unwrap().delta, "hel");
+        assert!(matches!(
+            &replayed[1],
+            Err(HyperInferError::ApiError { status: 401, message, .. }) if message == "bad key [REDACTED]"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unrecorded_request_is_not_found() {
+        let replay = ReplayTransport::new(Cassette::default());
+        let err = replay
+            .call_chat(&Provider::OpenAI, "gpt-4", "key", &request("hi"))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, HyperInferError::Config(e) if e.kind() == std::io::ErrorKind::NotFound)
+        );
+    }
+}
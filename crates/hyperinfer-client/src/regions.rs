@@ -0,0 +1,428 @@
+//! Failover between regional endpoints of one provider.
+//!
+//! A [`RegionPool`] tracks the health and response time of each endpoint
+//! configured in [`ProviderRegions`] and hands out the order to try them in
+//! for each call.  Health is local to this process: every client instance
+//! learns on its own which regions are failing.  Calls pinned to a data
+//! region, or to a route tag, only ever go to endpoints in it.
+
+use futures::{Stream, StreamExt};
+use hyperinfer_core::{
+    ChatChunk, ChatRequest, HyperInferError, ProviderRegions, RegionSelection, RegionalEndpoint,
+};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+type ChunkStream = Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send>>;
+
+/// Weight of the newest sample in the response-time average.
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+#[derive(Debug, Default)]
+struct Health {
+    consecutive_failures: u32,
+    /// Set once `consecutive_failures` reaches the threshold.
+    out_until: Option<Instant>,
+    /// Smoothed response time of successful calls.
+    latency_ms: Option<f64>,
+}
+
+struct Endpoint {
+    /// As configured, with the base URL's trailing slash removed.
+    spec: RegionalEndpoint,
+    health: Mutex<Health>,
+}
+
+impl Endpoint {
+    fn health(&self) -> std::sync::MutexGuard<'_, Health> {
+        self.health.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Which endpoints a call may go to.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Placement<'a> {
+    /// The caller's data region.
+    pub(crate) data_region: Option<&'a str>,
+    /// The region or tag of the deployment the request pinned itself to.
+    pub(crate) route_tag: Option<&'a str>,
+}
+
+impl<'a> Placement<'a> {
+    pub(crate) fn of(request: &'a ChatRequest) -> Self {
+        Self {
+            data_region: request.data_region.as_deref(),
+            route_tag: request
+                .routing
+                .as_ref()
+                .and_then(|routing| routing.route_tag.as_deref()),
+        }
+    }
+
+    /// Whether any endpoint at all will do.
+    pub(crate) fn is_any(&self) -> bool {
+        self.data_region.is_none() && self.route_tag.is_none()
+    }
+
+    fn admits(&self, endpoint: &RegionalEndpoint) -> bool {
+        [self.data_region, self.route_tag]
+            .into_iter()
+            .flatten()
+            .all(|region| endpoint.in_region(region))
+    }
+
+    /// The error for a call that no endpoint is placed for: a residency
+    /// error for a data region, an unknown deployment for a route tag.
+    pub(crate) fn no_endpoint(&self) -> HyperInferError {
+        match (self.data_region, self.route_tag) {
+            (data_region, Some(tag)) => HyperInferError::Config(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                match data_region {
+                    Some(region) => format!(
+                        "No provider endpoint tagged '{}' in data region '{}'",
+                        tag, region
+                    ),
+                    None => format!("No provider endpoint tagged '{}'", tag),
+                },
+            )),
+            (data_region, None) => HyperInferError::Forbidden(format!(
+                "No provider endpoint in data region '{}'",
+                data_region.unwrap_or_default()
+            )),
+        }
+    }
+}
+
+/// Whether `error` says something about the endpoint rather than the
+/// request, so another region may do better.
+fn fails_over(error: &HyperInferError) -> bool {
+    match error {
+        HyperInferError::Http(_) => true,
+        _ => error
+            .provider_error_kind()
+            .is_some_and(|kind| kind.is_retryable()),
+    }
+}
+
+pub(crate) struct RegionPool {
+    endpoints: Vec<Endpoint>,
+    selection: RegionSelection,
+    failure_threshold: u32,
+    cooldown: Duration,
+}
+
+impl RegionPool {
+    /// `None` when `regions` lists no endpoints.
+    pub(crate) fn new(regions: &ProviderRegions) -> Option<Self> {
+        if regions.endpoints.is_empty() {
+            return None;
+        }
+        let endpoints = regions
+            .endpoints
+            .iter()
+            .map(|spec| Endpoint {
+                spec: RegionalEndpoint {
+                    base_url: spec.base_url.trim_end_matches('/').to_string(),
+                    ..spec.clone()
+                },
+                health: Mutex::new(Health::default()),
+            })
+            .collect();
+        Some(Self {
+            endpoints,
+            selection: regions.selection,
+            failure_threshold: regions.failure_threshold.max(1),
+            cooldown: Duration::from_secs(regions.cooldown_secs),
+        })
+    }
+
+    pub(crate) fn base_urls(&self) -> impl Iterator<Item = &str> {
+        self.endpoints.iter().map(|e| e.spec.base_url.as_str())
+    }
+
+    /// Endpoint indices in the order to try them: those in rotation by the
+    /// selection policy, then those left out, soonest back first.  Only
+    /// endpoints `placement` admits are candidates.
+    fn candidates(&self, placement: Placement) -> Vec<usize> {
+        let now = Instant::now();
+        let mut available = Vec::new();
+        let mut out = Vec::new();
+        for (i, endpoint) in self.endpoints.iter().enumerate() {
+            if !placement.admits(&endpoint.spec) {
+                continue;
+            }
+            let health = endpoint.health();
+            match health.out_until {
+                Some(until) if until > now => out.push((until, i)),
+                _ => available.push((health.latency_ms.unwrap_or(0.0), i)),
+            }
+        }
+        if self.selection == RegionSelection::LowestLatency {
+            available.sort_by(|a, b| a.0.total_cmp(&b.0));
+        }
+        out.sort();
+        available
+            .into_iter()
+            .map(|(_, i)| i)
+            .chain(out.into_iter().map(|(_, i)| i))
+            .collect()
+    }
+
+    fn record_success(&self, i: usize, elapsed: Duration) {
+        let mut health = self.endpoints[i].health();
+        health.consecutive_failures = 0;
+        health.out_until = None;
+        let sample = elapsed.as_secs_f64() * 1000.0;
+        health.latency_ms = Some(match health.latency_ms {
+            Some(avg) => avg + LATENCY_SMOOTHING * (sample - avg),
+            None => sample,
+        });
+    }
+
+    /// The endpoint answered, but with an error about the request.
+    fn record_reachable(&self, i: usize) {
+        let mut health = self.endpoints[i].health();
+        health.consecutive_failures = 0;
+        health.out_until = None;
+    }
+
+    fn record_failure(&self, i: usize, error: &HyperInferError) {
+        let endpoint = &self.endpoints[i];
+        let mut health = endpoint.health();
+        health.consecutive_failures += 1;
+        if health.consecutive_failures >= self.failure_threshold {
+            health.out_until = Some(Instant::now() + self.cooldown);
+        }
+        tracing::warn!(
+            region = %endpoint.spec.region,
+            failures = health.consecutive_failures,
+            "Provider endpoint failed, trying the next region: {}",
+            error
+        );
+    }
+
+    fn record(&self, i: usize, start: Instant, error: Option<&HyperInferError>) {
+        match error {
+            None => self.record_success(i, start.elapsed()),
+            Some(e) if fails_over(e) => self.record_failure(i, e),
+            Some(_) => self.record_reachable(i),
+        }
+    }
+
+    /// Run `call` against the base URL of each endpoint `placement` admits
+    /// in turn until one does not fail over.
+    pub(crate) async fn call<T, F, Fut>(
+        &self,
+        placement: Placement<'_>,
+        call: F,
+    ) -> Result<T, HyperInferError>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<T, HyperInferError>>,
+    {
+        let mut last_error = None;
+        for i in self.candidates(placement) {
+            let start = Instant::now();
+            let result = call(self.endpoints[i].spec.base_url.clone()).await;
+            self.record(i, start, result.as_ref().err());
+            match result {
+                Err(e) if fails_over(&e) => last_error = Some(e),
+                result => return result,
+            }
+        }
+        Err(last_error.unwrap_or_else(|| placement.no_endpoint()))
+    }
+
+    /// Open the stream from `open` against each endpoint `placement` admits
+    /// in turn until one produces a first item that does not fail over.  Errors after the
+    /// first item are passed through: the response has already started.
+    ///
+    /// `open` is called for every endpoint up front; the streams it returns
+    /// must not connect until polled.
+    pub(crate) fn stream(
+        self: Arc<Self>,
+        placement: Placement<'_>,
+        mut open: impl FnMut(&str) -> ChunkStream,
+    ) -> ChunkStream {
+        let attempts: Vec<_> = self
+            .candidates(placement)
+            .into_iter()
+            .map(|i| (i, open(&self.endpoints[i].spec.base_url)))
+            .collect();
+        if attempts.is_empty() {
+            let error = placement.no_endpoint();
+            return Box::pin(futures::stream::once(async { Err(error) }));
+        }
+        Box::pin(async_stream::stream! {
+            let mut last_error = None;
+            for (i, mut stream) in attempts {
+                let start = Instant::now();
+                let first = stream.next().await;
+                self.record(i, start, first.as_ref().and_then(|item| item.as_ref().err()));
+                match first {
+                    Some(Err(e)) if fails_over(&e) => last_error = Some(e),
+                    Some(item) => {
+                        yield item;
+                        while let Some(item) = stream.next().await {
+                            yield item;
+                        }
+                        return;
+                    }
+                    None => return,
+                }
+            }
+            if let Some(e) = last_error {
+                yield Err(e);
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(selection: RegionSelection) -> RegionPool {
+        let endpoint = |region: &str| RegionalEndpoint {
+            region: region.to_string(),
+            base_url: format!("https://{}.example.com/", region),
+            tags: vec!["us".to_string()],
+        };
+        RegionPool::new(&ProviderRegions {
+            endpoints: vec![endpoint("eastus"), endpoint("westus")],
+            selection,
+            failure_threshold: 2,
+            cooldown_secs: 60,
+        })
+        .unwrap()
+    }
+
+    fn overloaded() -> HyperInferError {
+        HyperInferError::api_error(503, "overloaded")
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_and_takes_endpoint_out() {
+        let pool = pool(RegionSelection::Priority);
+        let calls = Mutex::new(Vec::new());
+        let call = |base_url: String| {
+            calls.lock().unwrap().push(base_url.clone());
+            async move {
+                if base_url.contains("eastus") {
+                    Err(overloaded())
+                } else {
+                    Ok(base_url)
+                }
+            }
+        };
+
+        for _ in 0..3 {
+            assert_eq!(
+                pool.call(Placement::default(), call).await.unwrap(),
+                "https://westus.example.com"
+            );
+        }
+        // eastus is left out after its second failure.
+        assert_eq!(
+            calls.lock().unwrap().len(),
+            2 + 2 + 1,
+            "{:?}",
+            calls.lock().unwrap()
+        );
+        assert_eq!(pool.candidates(Placement::default()), vec![1, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_request_errors_do_not_fail_over() {
+        let pool = pool(RegionSelection::Priority);
+        let err = pool
+            .call(Placement::default(), |_| async {
+                Err::<(), _>(HyperInferError::api_error(400, "bad request"))
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.provider_error_kind(),
+            Some(hyperinfer_core::ProviderErrorKind::InvalidRequest)
+        );
+        assert_eq!(pool.endpoints[0].health().consecutive_failures, 0);
+    }
+
+    #[test]
+    fn test_lowest_latency_prefers_fastest_measured() {
+        let pool = pool(RegionSelection::LowestLatency);
+        pool.record_success(0, Duration::from_millis(300));
+        pool.record_success(1, Duration::from_millis(80));
+        assert_eq!(pool.candidates(Placement::default()), vec![1, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_fails_over_before_first_chunk() {
+        let pool = Arc::new(pool(RegionSelection::Priority));
+        let stream = pool.stream(Placement::default(), |base_url| -> ChunkStream {
+            let item = if base_url.contains("eastus") {
+                Err(overloaded())
+            } else {
+                Ok(ChatChunk {
+                    delta: base_url.to_string(),
+                    ..Default::default()
+                })
+            };
+            Box::pin(futures::stream::iter(vec![item]))
+        });
+        let items: Vec<_> = stream.collect().await;
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            items[0].as_ref().unwrap().delta,
+            "https://westus.example.com"
+        );
+    }
+
+    fn in_region(data_region: &str) -> Placement<'_> {
+        Placement {
+            data_region: Some(data_region),
+            route_tag: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_data_region_limits_candidates() {
+        let pool = pool(RegionSelection::Priority);
+        assert_eq!(pool.candidates(in_region("westus")), vec![1]);
+        assert_eq!(pool.candidates(in_region("us")), vec![0, 1]);
+
+        let err = pool
+            .call(in_region("eu"), |base_url| async move { Ok(base_url) })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, HyperInferError::Forbidden(_)), "{:?}", err);
+        let items: Vec<_> = Arc::new(pool)
+            .stream(in_region("eu"), |_| unreachable!())
+            .collect()
+            .await;
+        assert!(matches!(items[..], [Err(HyperInferError::Forbidden(_))]));
+    }
+
+    #[tokio::test]
+    async fn test_route_tag_pins_the_deployment() {
+        let pool = pool(RegionSelection::Priority);
+        let pinned = |route_tag| Placement {
+            data_region: Some("us"),
+            route_tag: Some(route_tag),
+        };
+        assert_eq!(pool.candidates(pinned("westus")), vec![1]);
+        // Even while it is out of rotation.
+        pool.record_failure(1, &overloaded());
+        pool.record_failure(1, &overloaded());
+        assert_eq!(pool.candidates(pinned("westus")), vec![1]);
+
+        let err = pool
+            .call(pinned("canary"), |base_url| async move { Ok(base_url) })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, HyperInferError::Config(_)), "{:?}", err);
+        assert!(err.to_string().contains("tagged 'canary'"), "{}", err);
+    }
+}
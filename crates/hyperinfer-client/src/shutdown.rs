@@ -0,0 +1,141 @@
+//! Draining a client before the process exits.
+//!
+//! Requests and the telemetry they leave behind run on spawned tasks that a
+//! runtime shutting down simply drops.  [`InFlight`] counts that work so
+//! [`HyperInferClient::shutdown`](crate::HyperInferClient::shutdown) can
+//! refuse new requests and wait for the rest.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// What [`HyperInferClient::shutdown`](crate::HyperInferClient::shutdown)
+/// got done before its deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Requests, streams included, still running at the deadline.
+    pub abandoned_requests: usize,
+    /// Whether the telemetry buffer was written out, or its writes failed
+    /// for good, before the deadline.  Records still buffered when it is
+    /// false are lost.
+    pub telemetry_flushed: bool,
+}
+
+impl ShutdownReport {
+    /// Whether everything finished in time.
+    pub fn is_clean(&self) -> bool {
+        self.abandoned_requests == 0 && self.telemetry_flushed
+    }
+}
+
+#[derive(Default)]
+struct State {
+    closing: AtomicBool,
+    count: AtomicUsize,
+    /// Work let in by [`InFlight::enter`] so far.
+    started: AtomicU64,
+    idle: Notify,
+}
+
+/// Counts running work; clones share the count.
+#[derive(Clone, Default)]
+pub(crate) struct InFlight(Arc<State>);
+
+/// One piece of work counted by an [`InFlight`], until dropped.
+pub(crate) struct InFlightGuard(Arc<State>);
+
+impl InFlight {
+    /// Count new work, unless [`InFlight::close`] was called.
+    pub(crate) fn enter(&self) -> Option<InFlightGuard> {
+        let guard = self.track();
+        // Counted before checking, so `wait_idle` after `close` cannot miss
+        // work that got in.
+        if self.0.closing.load(Ordering::SeqCst) {
+            return None;
+        }
+        self.0.started.fetch_add(1, Ordering::Relaxed);
+        Some(guard)
+    }
+
+    /// Count work even while closing, for what running work hands off to
+    /// a task of its own.
+    pub(crate) fn track(&self) -> InFlightGuard {
+        self.0.count.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(self.0.clone())
+    }
+
+    /// Turn away new work from [`InFlight::enter`].
+    pub(crate) fn close(&self) {
+        self.0.closing.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_closing(&self) -> bool {
+        self.0.closing.load(Ordering::SeqCst)
+    }
+
+    /// Work still running.
+    pub(crate) fn len(&self) -> usize {
+        self.0.count.load(Ordering::SeqCst)
+    }
+
+    /// Work let in by [`InFlight::enter`] since it was created.
+    #[cfg_attr(not(feature = "redis"), allow(dead_code))]
+    pub(crate) fn started(&self) -> u64 {
+        self.0.started.load(Ordering::Relaxed)
+    }
+
+    /// Wait until no work is running.
+    pub(crate) async fn wait_idle(&self) {
+        loop {
+            let idle = self.0.idle.notified();
+            tokio::pin!(idle);
+            // Registered before checking, so the last guard dropping in
+            // between still wakes us.
+            idle.as_mut().enable();
+            if self.len() == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_close_turns_away_new_work_and_waits_for_running_work() {
+        let in_flight = InFlight::default();
+        let running = in_flight.enter().unwrap();
+        in_flight.close();
+        assert!(in_flight.is_closing());
+        assert!(in_flight.enter().is_none());
+        assert_eq!(in_flight.started(), 1);
+        // Handed-off work is still counted.
+        let handed_off = in_flight.track();
+        assert_eq!(in_flight.len(), 2);
+
+        let waiter = tokio::spawn({
+            let in_flight = in_flight.clone();
+            async move { in_flight.wait_idle().await }
+        });
+        drop(running);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+        drop(handed_off);
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("idle once the last guard is dropped")
+            .unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+//! Single-flight coalescing of identical concurrent requests.
+//!
+//! With `Config::single_flight` enabled for the caller's team, the first
+//! `chat()` call for a request leads: it is rate limited, sent to the
+//! provider and recorded as usual.  Identical requests under the same key
+//! that arrive while it is in flight follow: they wait for the leader's
+//! response and return a copy of it, marked with [`COALESCED_METADATA_KEY`].
+//! Like cache hits, followers are not counted against rate limits and
+//! record no usage, since no tokens were spent on them.
+//!
+//! Only successes are shared.  If the leader fails or is cancelled, each
+//! follower makes its own call, so one bad response cannot fail the whole
+//! herd.  Streams are never coalesced.
+
+use hyperinfer_core::{ChatResponse, HyperInferError};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+
+/// Response metadata key set on responses shared from another call.
+pub const COALESCED_METADATA_KEY: &str = "coalesced";
+
+type Slot = watch::Receiver<Option<ChatResponse>>;
+
+/// In-flight leader calls, keyed by caller and request.
+#[derive(Clone, Default)]
+pub struct SingleFlight {
+    in_flight: Arc<Mutex<HashMap<String, Slot>>>,
+}
+
+/// Removes the leader's entry however its call ends.
+struct Lead<'a> {
+    flights: &'a SingleFlight,
+    key: String,
+    tx: watch::Sender<Option<ChatResponse>>,
+}
+
+impl Drop for Lead<'_> {
+    fn drop(&mut self) {
+        self.flights.lock().remove(&self.key);
+    }
+}
+
+impl SingleFlight {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Slot>> {
+        self.in_flight.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Number of leader calls currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Run `call` unless a call for `key` is already in flight, in which
+    /// case wait for and return its response.
+    pub async fn run<F>(&self, key: String, call: F) -> Result<ChatResponse, HyperInferError>
+    where
+        F: Future<Output = Result<ChatResponse, HyperInferError>>,
+    {
+        let (tx, mut slot) = {
+            let mut in_flight = self.lock();
+            match in_flight.get(&key) {
+                Some(slot) => (None, slot.clone()),
+                None => {
+                    let (tx, rx) = watch::channel(None);
+                    in_flight.insert(key.clone(), rx.clone());
+                    (Some(tx), rx)
+                }
+            }
+        };
+
+        let Some(tx) = tx else {
+            // Clone out of the channel so its lock is not held across an
+            // await below.
+            let shared = slot.wait_for(Option::is_some).await.map(|r| r.clone());
+            return match shared {
+                Ok(Some(mut response)) => {
+                    response
+                        .metadata
+                        .insert(COALESCED_METADATA_KEY.to_string(), "true".to_string());
+                    Ok(response)
+                }
+                _ => {
+                    tracing::debug!("Coalesced request's leader failed; calling the provider");
+                    call.await
+                }
+            };
+        };
+
+        let lead = Lead {
+            flights: self,
+            key,
+            tx,
+        };
+        let result = call.await;
+        if let Ok(response) = &result {
+            lead.tx.send_replace(Some(response.clone()));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn response(content: &str) -> ChatResponse {
+        ChatResponse {
+            id: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn slow_call(
+        calls: &AtomicUsize,
+        result: Result<ChatResponse, HyperInferError>,
+    ) -> Result<ChatResponse, HyperInferError> {
+        calls.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        result
+    }
+
+    #[tokio::test]
+    async fn test_identical_requests_share_one_call() {
+        let flights = SingleFlight::default();
+        let calls = AtomicUsize::new(0);
+        let (a, b, c) = tokio::join!(
+            flights.run("k".to_string(), slow_call(&calls, Ok(response("r1")))),
+            flights.run("k".to_string(), slow_call(&calls, Ok(response("r2")))),
+            flights.run("k".to_string(), slow_call(&calls, Ok(response("r3")))),
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let (a, b, c) = (a.unwrap(), b.unwrap(), c.unwrap());
+        assert_eq!(
+            (a.id.as_str(), b.id.as_str(), c.id.as_str()),
+            ("r1", "r1", "r1")
+        );
+        assert!(!a.metadata.contains_key(COALESCED_METADATA_KEY));
+        assert_eq!(b.metadata[COALESCED_METADATA_KEY], "true");
+        assert_eq!(flights.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_different_keys_are_not_coalesced() {
+        let flights = SingleFlight::default();
+        let calls = AtomicUsize::new(0);
+        let (a, b) = tokio::join!(
+            flights.run("k1".to_string(), slow_call(&calls, Ok(response("r1")))),
+            flights.run("k2".to_string(), slow_call(&calls, Ok(response("r2")))),
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(a.unwrap().id, "r1");
+        assert_eq!(b.unwrap().id, "r2");
+    }
+
+    #[tokio::test]
+    async fn test_followers_call_provider_when_leader_fails() {
+        let flights = SingleFlight::default();
+        let calls = AtomicUsize::new(0);
+        let failure = Err(HyperInferError::api_error(500, "boom".to_string()));
+        let (a, b) = tokio::join!(
+            flights.run("k".to_string(), slow_call(&calls, failure)),
+            flights.run("k".to_string(), slow_call(&calls, Ok(response("r2")))),
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(a.is_err());
+        let b = b.unwrap();
+        assert_eq!(b.id, "r2");
+        assert!(!b.metadata.contains_key(COALESCED_METADATA_KEY));
+        assert_eq!(flights.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_leader_releases_key() {
+        let flights = SingleFlight::default();
+        let calls = AtomicUsize::new(0);
+        let leader = flights.run("k".to_string(), slow_call(&calls, Ok(response("r1"))));
+        assert!(tokio::time::timeout(Duration::from_millis(5), leader)
+            .await
+            .is_err());
+        assert_eq!(flights.in_flight(), 0);
+    }
+}
@@ -0,0 +1,158 @@
+//! Lock-free config for the request path.
+//!
+//! Each config version is compiled once into a [`RouterSnapshot`]: the
+//! config itself plus a [`Router`] with its routing rules sorted and its
+//! global and team alias tables parsed.  [`SharedSnapshot`] publishes the
+//! current one through an [`ArcSwap`], so a request loads it without taking
+//! a lock and keeps using the same version to the end, even if an update
+//! lands halfway through.
+
+use crate::router::Router;
+use arc_swap::ArcSwap;
+#[cfg(feature = "redis")]
+use async_trait::async_trait;
+#[cfg(feature = "redis")]
+use hyperinfer_core::redis::ConfigTarget;
+use hyperinfer_core::Config;
+use std::sync::{Arc, Mutex};
+
+/// One config version and the router compiled from it.
+pub struct RouterSnapshot {
+    pub config: Arc<Config>,
+    pub router: Arc<Router>,
+}
+
+impl RouterSnapshot {
+    pub fn new(config: Config) -> Self {
+        let router = Router::new(config.routing_rules.clone())
+            .with_aliases(config.model_aliases.clone())
+            .with_team_aliases(&config.team_model_aliases)
+            .with_default_provider(config.default_provider.clone());
+        Self {
+            config: Arc::new(config),
+            router: Arc::new(router),
+        }
+    }
+}
+
+/// The current [`RouterSnapshot`], replaced whole on every config update.
+pub struct SharedSnapshot {
+    current: ArcSwap<RouterSnapshot>,
+    /// Serializes updates so an older version never replaces a newer one.
+    update: Mutex<()>,
+}
+
+impl SharedSnapshot {
+    pub fn new(config: Config) -> Self {
+        Self {
+            current: ArcSwap::from_pointee(RouterSnapshot::new(config)),
+            update: Mutex::new(()),
+        }
+    }
+
+    /// The current snapshot.
+    pub fn load(&self) -> Arc<RouterSnapshot> {
+        self.current.load_full()
+    }
+
+    /// Compile `update` and make it current unless it is older than the
+    /// current config.  Returns whether it was applied.
+    ///
+    /// Provider keys are never serialized, so updates over pub/sub arrive
+    /// without them; the current keys are kept for every provider the
+    /// update has no key for.
+    pub fn apply_update(&self, mut update: Config) -> bool {
+        let _guard = self.update.lock().unwrap_or_else(|e| e.into_inner());
+        let current = self.current.load();
+        if update.version != 0 && update.version <= current.config.version {
+            return false;
+        }
+        for (provider, key) in &current.config.api_keys {
+            update
+                .api_keys
+                .entry(provider.clone())
+                .or_insert_with(|| key.clone());
+        }
+        self.current.store(Arc::new(RouterSnapshot::new(update)));
+        true
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl ConfigTarget for SharedSnapshot {
+    async fn config_version(&self) -> u64 {
+        self.current.load().config.version
+    }
+
+    async fn apply_config(&self, update: Config) -> bool {
+        self.apply_update(update)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyperinfer_core::Provider;
+    use std::collections::HashMap;
+
+    fn config(version: u64, alias_target: &str) -> Config {
+        Config {
+            version,
+            model_aliases: HashMap::from([("fast".to_string(), alias_target.to_string())]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_update_recompiles_router() {
+        let shared = SharedSnapshot::new(config(1, "openai/gpt-4o-mini"));
+        let before = shared.load();
+
+        assert!(shared.apply_update(config(2, "anthropic/claude-3-5-haiku")));
+        let after = shared.load();
+        assert_eq!(after.config.version, 2);
+        assert_eq!(
+            after.router.resolve(None, "fast", &after.config),
+            Some(("claude-3-5-haiku".to_string(), Provider::Anthropic))
+        );
+        // Requests already holding the old snapshot keep routing with it.
+        assert_eq!(
+            before.router.resolve(None, "fast", &before.config),
+            Some(("gpt-4o-mini".to_string(), Provider::OpenAI))
+        );
+    }
+
+    #[test]
+    fn test_update_keeps_provider_keys() {
+        let mut initial = config(1, "openai/gpt-4o-mini");
+        initial.api_keys = HashMap::from([("openai".to_string(), "sk-local".to_string())]);
+        let shared = SharedSnapshot::new(initial);
+
+        // As received over pub/sub: `api_keys` is never serialized.
+        let update: Config =
+            serde_json::from_str(&serde_json::to_string(&config(2, "openai/gpt-4o")).unwrap())
+                .unwrap();
+        assert!(shared.apply_update(update));
+        assert_eq!(
+            shared
+                .load()
+                .config
+                .api_keys
+                .get("openai")
+                .map(String::as_str),
+            Some("sk-local")
+        );
+    }
+
+    #[test]
+    fn test_stale_update_is_ignored() {
+        let shared = SharedSnapshot::new(config(5, "openai/gpt-4o-mini"));
+        assert!(!shared.apply_update(config(4, "anthropic/claude-3-5-haiku")));
+        assert!(!shared.apply_update(config(5, "anthropic/claude-3-5-haiku")));
+        assert_eq!(shared.load().config.version, 5);
+        // Unversioned configs always apply, as with `Config::apply_update`.
+        assert!(shared.apply_update(config(0, "anthropic/claude-3-5-haiku")));
+        assert_eq!(shared.load().config.version, 0);
+    }
+}
@@ -0,0 +1,297 @@
+//! Namespaced key-value store for per-key runtime state.
+//!
+//! Features that keep small pieces of state per key or per caller —
+//! control-plane policies, breaker state, session pins, in-flight markers —
+//! go through a [`StateStore`] instead of building Redis keys themselves.
+//! Each feature takes its own namespace with [`StateStore::scoped`], and
+//! entries live under `hyperinfer:state:{namespace}:{key}`, so features
+//! cannot collide and their keys are easy to find and flush.
+//!
+//! Values are stored as JSON, optionally with a TTL.  Every write is also
+//! kept in this process, and reads fall back to that copy when Redis
+//! errors, so state degrades to per-instance rather than disappearing while
+//! Redis is unreachable.  Without Redis (a standalone client, or without
+//! the `redis` feature) the in-process copy is all there is.
+
+#[cfg(feature = "redis")]
+use hyperinfer_core::RedisHandle;
+#[cfg(feature = "redis")]
+use redis::{aio::ConnectionManager, AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+#[cfg(feature = "redis")]
+use tokio::sync::Mutex as AsyncMutex;
+use tracing::warn;
+
+/// Prefix of every key the store writes.
+pub const STATE_KEY_PREFIX: &str = "hyperinfer:state:";
+
+/// Raw JSON value and when it expires.
+type Entry = (String, Option<Instant>);
+
+/// In-process copy of the store's entries, by full key.
+#[derive(Clone, Default)]
+struct Memory {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl Memory {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        let mut entries = self.lock();
+        match entries.get(key) {
+            Some((_, Some(expires_at))) if *expires_at <= Instant::now() => {
+                entries.remove(key);
+                None
+            }
+            Some((raw, _)) => Some(raw.clone()),
+            None => None,
+        }
+    }
+
+    fn set(&self, key: &str, raw: String, ttl: Option<Duration>) {
+        let now = Instant::now();
+        let mut entries = self.lock();
+        entries.retain(|_, (_, expires_at)| expires_at.is_none_or(|at| at > now));
+        entries.insert(key.to_string(), (raw, ttl.map(|ttl| now + ttl)));
+    }
+
+    fn set_nx(&self, key: &str, raw: String, ttl: Option<Duration>) -> bool {
+        let now = Instant::now();
+        let mut entries = self.lock();
+        entries.retain(|_, (_, expires_at)| expires_at.is_none_or(|at| at > now));
+        if entries.contains_key(key) {
+            return false;
+        }
+        entries.insert(key.to_string(), (raw, ttl.map(|ttl| now + ttl)));
+        true
+    }
+
+    fn delete(&self, key: &str) {
+        self.lock().remove(key);
+    }
+}
+
+/// Namespaced JSON store over Redis, with an in-process fallback.  Cheap to
+/// clone; clones and scopes share the same entries.
+#[derive(Clone)]
+pub struct StateStore {
+    #[cfg(feature = "redis")]
+    conn: Option<Arc<AsyncMutex<ConnectionManager>>>,
+    memory: Memory,
+    namespace: String,
+}
+
+impl Default for StateStore {
+    fn default() -> Self {
+        Self::in_memory()
+    }
+}
+
+impl StateStore {
+    /// A store kept only in this process, under the `default` namespace.
+    pub fn in_memory() -> Self {
+        Self {
+            #[cfg(feature = "redis")]
+            conn: None,
+            memory: Memory::default(),
+            namespace: "default".to_string(),
+        }
+    }
+
+    /// A store on the shared connection of `redis`, under the `default`
+    /// namespace.
+    #[cfg(feature = "redis")]
+    pub fn with_redis(redis: &RedisHandle) -> Self {
+        Self {
+            conn: Some(Arc::new(AsyncMutex::new(redis.connection()))),
+            ..Self::in_memory()
+        }
+    }
+
+    /// The same store under `namespace`.
+    pub fn scoped(&self, namespace: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            ..self.clone()
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Whether entries are shared through Redis rather than kept only in
+    /// this process.
+    pub fn is_shared(&self) -> bool {
+        #[cfg(feature = "redis")]
+        return self.conn.is_some();
+        #[cfg(not(feature = "redis"))]
+        false
+    }
+
+    /// Full Redis key for `key` in this namespace.
+    pub fn state_key(&self, key: &str) -> String {
+        format!("{}{}:{}", STATE_KEY_PREFIX, self.namespace, key)
+    }
+
+    /// The value under `key`, if present, unexpired and of type `T`.
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let key = self.state_key(key);
+        let raw = match self.get_shared(&key).await {
+            Some(Ok(raw)) => raw,
+            Some(Err(())) | None => self.memory.get(&key),
+        }?;
+        match serde_json::from_str(&raw) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                warn!("State deserialisation error for {}: {}", key, e);
+                None
+            }
+        }
+    }
+
+    /// Store `value` under `key`, expiring after `ttl` if given.
+    pub async fn set<T: Serialize>(&self, key: &str, value: &T, ttl: Option<Duration>) {
+        let key = self.state_key(key);
+        let Some(raw) = Self::encode(&key, value) else {
+            return;
+        };
+        self.memory.set(&key, raw.clone(), ttl);
+        self.set_shared(&key, &raw, ttl, false).await;
+    }
+
+    /// Store `value` under `key` only if nothing is there yet.  Returns
+    /// whether it was stored, so callers can use an entry as a marker that
+    /// one of them holds.
+    pub async fn set_nx<T: Serialize>(&self, key: &str, value: &T, ttl: Option<Duration>) -> bool {
+        let key = self.state_key(key);
+        let Some(raw) = Self::encode(&key, value) else {
+            return false;
+        };
+        match self.set_shared(&key, &raw, ttl, true).await {
+            Some(stored) => {
+                if stored {
+                    self.memory.set(&key, raw, ttl);
+                }
+                stored
+            }
+            None => self.memory.set_nx(&key, raw, ttl),
+        }
+    }
+
+    /// Remove the entry under `key`.
+    pub async fn delete(&self, key: &str) {
+        let key = self.state_key(key);
+        self.memory.delete(&key);
+        #[cfg(feature = "redis")]
+        if let Some(conn) = &self.conn {
+            let result: redis::RedisResult<()> = conn.lock().await.del(&key).await;
+            if let Err(e) = result {
+                warn!("State delete error for {}: {}", key, e);
+            }
+        }
+    }
+
+    fn encode<T: Serialize>(key: &str, value: &T) -> Option<String> {
+        serde_json::to_string(value)
+            .map_err(|e| warn!("State serialisation error for {}: {}", key, e))
+            .ok()
+    }
+
+    /// The raw value from Redis: `None` without Redis, `Err` if it failed.
+    #[cfg(feature = "redis")]
+    async fn get_shared(&self, key: &str) -> Option<Result<Option<String>, ()>> {
+        let conn = self.conn.as_ref()?;
+        let result: redis::RedisResult<Option<String>> = conn.lock().await.get(key).await;
+        Some(result.map_err(|e| warn!("State read error for {}: {}", key, e)))
+    }
+
+    #[cfg(not(feature = "redis"))]
+    async fn get_shared(&self, _key: &str) -> Option<Result<Option<String>, ()>> {
+        None
+    }
+
+    /// Write to Redis.  Returns whether the value was stored, or `None`
+    /// without Redis or if it failed.
+    #[cfg(feature = "redis")]
+    async fn set_shared(
+        &self,
+        key: &str,
+        raw: &str,
+        ttl: Option<Duration>,
+        only_if_absent: bool,
+    ) -> Option<bool> {
+        let conn = self.conn.as_ref()?;
+        let mut options = SetOptions::default();
+        if only_if_absent {
+            options = options.conditional_set(ExistenceCheck::NX);
+        }
+        if let Some(ttl) = ttl {
+            options = options.with_expiration(SetExpiry::PX(ttl.as_millis().max(1) as u64));
+        }
+        let result: redis::RedisResult<Option<String>> =
+            conn.lock().await.set_options(key, raw, options).await;
+        match result {
+            Ok(reply) => Some(reply.is_some()),
+            Err(e) => {
+                warn!("State write error for {}: {}", key, e);
+                None
+            }
+        }
+    }
+
+    #[cfg(not(feature = "redis"))]
+    async fn set_shared(
+        &self,
+        _key: &str,
+        _raw: &str,
+        _ttl: Option<Duration>,
+        _only_if_absent: bool,
+    ) -> Option<bool> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_namespaces_do_not_collide() {
+        let store = StateStore::in_memory();
+        let breakers = store.scoped("breakers");
+        let sessions = store.scoped("sessions");
+        breakers.set("gpt-4", &"open", None).await;
+        assert_eq!(
+            breakers.get::<String>("gpt-4").await.as_deref(),
+            Some("open")
+        );
+        assert_eq!(sessions.get::<String>("gpt-4").await, None);
+        assert_eq!(
+            sessions.state_key("user-1"),
+            "hyperinfer:state:sessions:user-1"
+        );
+
+        breakers.delete("gpt-4").await;
+        assert_eq!(breakers.get::<String>("gpt-4").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_set_nx_holds_until_expiry() {
+        let markers = StateStore::in_memory().scoped("markers");
+        let ttl = Some(Duration::from_millis(20));
+        assert!(markers.set_nx("job", &1, ttl).await);
+        assert!(!markers.set_nx("job", &2, ttl).await);
+        assert_eq!(markers.get::<u32>("job").await, Some(1));
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(markers.get::<u32>("job").await, None);
+        assert!(markers.set_nx("job", &3, ttl).await);
+    }
+}
@@ -0,0 +1,133 @@
+//! Token usage of a streamed response.
+//!
+//! Providers report usage on the last chunk of a stream, sometimes in
+//! pieces across several chunks, and some not at all.  [`StreamUsage`]
+//! keeps a running estimate from the prompt and the text streamed so far,
+//! so usage can be charged while the stream is still going, and settles on
+//! the provider's numbers wherever it reported them.
+
+use hyperinfer_core::tokenizer::{self, TextTokenCounter};
+use hyperinfer_core::types::Usage;
+use hyperinfer_core::{ChatChunk, ChatRequest};
+
+/// Estimated tokens a stream is allowed to get ahead of what has been
+/// charged to the rate limiter before the difference is charged.
+pub(crate) const CHARGE_INTERVAL_TOKENS: u64 = 256;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StreamUsage {
+    estimated_input: u32,
+    output: TextTokenCounter,
+    /// Largest counts the provider reported, per field.
+    reported: Usage,
+}
+
+impl StreamUsage {
+    /// Usage of a stream answering `request`, as sent to the provider.
+    pub(crate) fn new(request: &ChatRequest) -> Self {
+        Self {
+            estimated_input: tokenizer::estimate_request_tokens(request),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn observe(&mut self, chunk: &ChatChunk) {
+        self.output.push(&chunk.delta);
+        if let Some(thinking) = &chunk.thinking {
+            self.output.push(thinking);
+        }
+        if let Some(usage) = &chunk.usage {
+            self.reported.input_tokens = self.reported.input_tokens.max(usage.input_tokens);
+            self.reported.output_tokens = self.reported.output_tokens.max(usage.output_tokens);
+            self.reported.thinking_tokens =
+                self.reported.thinking_tokens.max(usage.thinking_tokens);
+        }
+    }
+
+    /// Best current count of input plus output tokens.
+    pub(crate) fn total(&self) -> u64 {
+        let usage = self.reconcile();
+        u64::from(usage.input_tokens) + u64::from(usage.output_tokens)
+    }
+
+    /// Whether any of [`reconcile`](Self::reconcile)'s counts are estimates.
+    pub(crate) fn is_estimated(&self) -> bool {
+        self.reported.input_tokens == 0 || self.reported.output_tokens == 0
+    }
+
+    /// Final usage: the provider's counts where it reported them, the
+    /// estimates otherwise.
+    pub(crate) fn reconcile(&self) -> Usage {
+        let reported_or = |reported: u32, estimate: u32| match reported {
+            0 => estimate,
+            reported => reported,
+        };
+        Usage {
+            input_tokens: reported_or(self.reported.input_tokens, self.estimated_input),
+            output_tokens: reported_or(self.reported.output_tokens, self.output.tokens()),
+            thinking_tokens: self.reported.thinking_tokens,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyperinfer_core::types::{ChatMessage, MessageRole};
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            model: "gpt-4o-mini".to_string(),
+            messages: vec![ChatMessage {
+                role: MessageRole::User,
+                content: "Tell me a story about a lighthouse.".to_string(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn chunk(delta: &str, usage: Option<(u32, u32)>) -> ChatChunk {
+        ChatChunk {
+            delta: delta.to_string(),
+            usage: usage.map(|(input_tokens, output_tokens)| Usage {
+                input_tokens,
+                output_tokens,
+                thinking_tokens: 0,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_estimates_without_reported_usage() {
+        let mut usage = StreamUsage::new(&request());
+        for delta in ["Once upon", " a time, ", "there was a lighthouse."] {
+            usage.observe(&chunk(delta, None));
+        }
+        let reconciled = usage.reconcile();
+        assert_eq!(
+            reconciled.input_tokens,
+            tokenizer::estimate_request_tokens(&request())
+        );
+        assert_eq!(
+            reconciled.output_tokens,
+            tokenizer::estimate_text_tokens("Once upon a time, there was a lighthouse.")
+        );
+        assert!(usage.is_estimated());
+    }
+
+    #[test]
+    fn test_reported_usage_wins() {
+        let mut usage = StreamUsage::new(&request());
+        usage.observe(&chunk("Once upon a time", None));
+        // Anthropic reports input on the first event and output on the last.
+        usage.observe(&chunk("", Some((42, 0))));
+        assert_eq!(usage.reconcile().input_tokens, 42);
+        assert!(usage.is_estimated());
+
+        usage.observe(&chunk("", Some((0, 7))));
+        assert_eq!(usage.reconcile().output_tokens, 7);
+        assert_eq!(usage.total(), 49);
+        assert!(!usage.is_estimated());
+    }
+}
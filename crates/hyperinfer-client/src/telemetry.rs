@@ -1,13 +1,130 @@
+use crate::shutdown::InFlight;
+use crate::telemetry_queue::{OverflowPolicy, TelemetryQueue};
 use hex;
+use hyperinfer_core::CompressionStats;
+#[cfg(feature = "redis")]
+use hyperinfer_core::{
+    redis::{RateLimitRejection, EVENTS_CHANNEL, KEY_LAST_USED_KEY},
+    telemetry_consumer::{shard_for_key, shard_stream_key},
+    RedisHandle, RedisOptions,
+};
 use sha2::{Digest, Sha256};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+#[cfg(feature = "redis")]
+use std::time::Instant;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 const DEFAULT_STREAM_KEY: &str = "hyperinfer:telemetry";
 
+/// Least time between two writes of the same key's last use.
+pub const KEY_USE_WRITE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Pause after the first failed write before trying the next batch,
+/// doubling with each further failure up to [`MAX_WRITE_BACKOFF`].
+#[cfg(feature = "redis")]
+const MIN_WRITE_BACKOFF: Duration = Duration::from_millis(100);
+#[cfg(feature = "redis")]
+const MAX_WRITE_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Fields of one telemetry stream entry.
+type Entry = Vec<(&'static str, String)>;
+
+/// How records are buffered and written to the telemetry stream.
+///
+/// Records go into a bounded in-memory buffer and a background task writes
+/// them in batches, one pipelined round trip of XADDs per batch.  A batch is
+/// written once it holds `max_batch` records or `flush_interval` after its
+/// first record, whichever comes first.  What happens to records arriving
+/// while the buffer is full is up to `overflow`; by default they are dropped
+/// and counted rather than slowing requests down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TelemetryBatching {
+    pub capacity: usize,
+    pub max_batch: usize,
+    pub flush_interval: Duration,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for TelemetryBatching {
+    fn default() -> Self {
+        Self {
+            capacity: 10_000,
+            max_batch: 100,
+            flush_interval: Duration::from_millis(50),
+            overflow: OverflowPolicy::default(),
+        }
+    }
+}
+
+/// State of the telemetry writer's Redis connection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TelemetryHealth {
+    /// Whether the last write succeeded, or before any write, whether the
+    /// initial connection did.
+    pub connected: bool,
+    /// Error of the last failed write or connection attempt.
+    pub last_error: Option<String>,
+    /// Failed writes since the last successful one.
+    pub consecutive_failures: u32,
+    /// Records lost in failed writes so far.
+    pub lost: u64,
+    /// Records dropped under the overflow policy so far.
+    pub dropped: u64,
+    /// Records waiting in the buffer to be written.
+    pub queue_depth: usize,
+}
+
+/// Usage records, rejections and key uses, written to Redis for the control
+/// plane.  Without the `redis` feature, or with no connection, nothing is
+/// written.
 #[derive(Clone)]
 pub struct Telemetry {
+    #[cfg(feature = "redis")]
     manager: Option<redis::aio::ConnectionManager>,
+    /// Updated by the flusher after every write; the buffer fields are
+    /// filled in by [`Telemetry::health`].
+    health: Arc<Mutex<TelemetryHealth>>,
     stream_key: String,
+    /// Streams the telemetry stream is split into; see
+    /// [`Telemetry::with_shards`].
+    shards: u32,
+    /// When each API key's use was last written, by key id.
+    #[cfg(feature = "redis")]
+    key_uses: Arc<Mutex<HashMap<String, Instant>>>,
+    batching: TelemetryBatching,
+    /// The buffer, created with its flusher on the first record.
+    buffer: Arc<OnceLock<Buffer>>,
+    /// Rejection and key-use writes still running.
+    tasks: InFlight,
+}
+
+/// Closes the buffer, stopping its flusher, once the last clone of the
+/// [`Telemetry`] that created it is gone.
+#[cfg_attr(not(feature = "redis"), allow(dead_code))]
+struct Buffer {
+    queue: Arc<TelemetryQueue<Entry>>,
+    /// Taken by [`Telemetry::shutdown`] to wait for the last write.
+    flusher: Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
+#[cfg_attr(not(feature = "redis"), allow(dead_code))]
+impl Buffer {
+    fn new(
+        queue: Arc<TelemetryQueue<Entry>>,
+        flusher: Option<tokio::task::JoinHandle<()>>,
+    ) -> Self {
+        Self {
+            queue,
+            flusher: Mutex::new(flusher),
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        self.queue.close();
+    }
 }
 
 impl Telemetry {
@@ -24,34 +141,173 @@ impl Telemetry {
             hex_hash
         }
     }
+
+    /// Telemetry that writes nothing.
+    pub fn disabled() -> Self {
+        Self {
+            #[cfg(feature = "redis")]
+            manager: None,
+            health: Arc::default(),
+            stream_key: DEFAULT_STREAM_KEY.to_string(),
+            shards: 1,
+            #[cfg(feature = "redis")]
+            key_uses: Arc::default(),
+            batching: TelemetryBatching::default(),
+            buffer: Arc::default(),
+            tasks: InFlight::default(),
+        }
+    }
+
+    /// Whether records have a Redis connection to go to.
+    fn writes_to_redis(&self) -> bool {
+        #[cfg(feature = "redis")]
+        return self.manager.is_some();
+        #[cfg(not(feature = "redis"))]
+        false
+    }
+
+    /// Telemetry connected to Redis up front.  If the connection fails,
+    /// records are written once Redis becomes reachable, as with
+    /// [`Telemetry::new_lazy`]; [`Telemetry::health`] reports the outage.
+    #[cfg(feature = "redis")]
     pub async fn new(redis_url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let manager = match redis::Client::open(redis_url) {
-            Ok(client) => match redis::aio::ConnectionManager::new(client).await {
-                Ok(m) => Some(m),
+        let (manager, health) = match RedisOptions::new(redis_url).client() {
+            Ok(client) => match redis::aio::ConnectionManager::new(client.clone()).await {
+                Ok(m) => (
+                    Some(m),
+                    TelemetryHealth {
+                        connected: true,
+                        ..Default::default()
+                    },
+                ),
                 Err(e) => {
-                    tracing::warn!("Failed to create Redis connection manager: {}", e);
-                    None
+                    tracing::warn!(
+                        "Failed to connect telemetry to Redis, records are dropped until it is reachable: {}",
+                        e
+                    );
+                    let health = TelemetryHealth {
+                        last_error: Some(e.to_string()),
+                        ..Default::default()
+                    };
+                    (Self::lazy_manager(client), health)
                 }
             },
-            Err(e) => {
-                tracing::warn!("Invalid Redis URL for telemetry: {}", e);
-                None
-            }
+            Err(e) => Self::invalid_url(e),
         };
+        Ok(Self::with_manager(manager, health))
+    }
+
+    /// Telemetry whose Redis connection is made on first use, so records
+    /// resume once an unreachable Redis comes back.
+    #[cfg(feature = "redis")]
+    pub fn new_lazy(redis_url: &str) -> Self {
+        let (manager, health) = match RedisOptions::new(redis_url).client() {
+            Ok(client) => (Self::lazy_manager(client), TelemetryHealth::default()),
+            Err(e) => Self::invalid_url(e),
+        };
+        Self::with_manager(manager, health)
+    }
+
+    /// Telemetry on the shared connection of `redis`.  Like
+    /// [`Telemetry::new_lazy`], it reports itself connected only once a
+    /// write has succeeded.
+    #[cfg(feature = "redis")]
+    pub fn with_redis(redis: &RedisHandle) -> Self {
+        Self::with_manager(Some(redis.connection()), TelemetryHealth::default())
+    }
+
+    #[cfg(feature = "redis")]
+    fn lazy_manager(client: redis::Client) -> Option<redis::aio::ConnectionManager> {
+        hyperinfer_core::redis::lazy_connection_manager(client)
+            .inspect_err(|e| tracing::warn!("Invalid Redis config for telemetry: {}", e))
+            .ok()
+    }
+
+    #[cfg(feature = "redis")]
+    fn invalid_url(
+        e: redis::RedisError,
+    ) -> (Option<redis::aio::ConnectionManager>, TelemetryHealth) {
+        tracing::warn!(
+            "Invalid Redis URL for telemetry, records are dropped: {}",
+            e
+        );
+        let health = TelemetryHealth {
+            last_error: Some(e.to_string()),
+            ..Default::default()
+        };
+        (None, health)
+    }
 
-        Ok(Self {
+    #[cfg(feature = "redis")]
+    fn with_manager(
+        manager: Option<redis::aio::ConnectionManager>,
+        health: TelemetryHealth,
+    ) -> Self {
+        Self {
             manager,
-            stream_key: DEFAULT_STREAM_KEY.to_string(),
-        })
+            health: Arc::new(Mutex::new(health)),
+            ..Self::disabled()
+        }
     }
 
     pub fn with_stream_key(mut self, stream_key: &str) -> Self {
         if !stream_key.trim().is_empty() {
             self.stream_key = stream_key.to_string();
+            self.buffer = Arc::default();
         }
         self
     }
 
+    /// Spread records over `shards` streams, `stream_key:0` to
+    /// `stream_key:{shards - 1}`, by a hash of the API key, so that no single
+    /// stream limits ingestion.  The control plane's consumer must be set to
+    /// the same count (`TELEMETRY_SHARDS`).
+    pub fn with_shards(mut self, shards: u32) -> Self {
+        self.shards = shards.max(1);
+        self.buffer = Arc::default();
+        self
+    }
+
+    pub fn with_batching(mut self, batching: TelemetryBatching) -> Self {
+        self.batching = TelemetryBatching {
+            capacity: batching.capacity.max(1),
+            max_batch: batching.max_batch.max(1),
+            ..batching
+        };
+        self.buffer = Arc::default();
+        self
+    }
+
+    /// Records waiting in the buffer to be written.
+    pub fn queue_depth(&self) -> usize {
+        self.buffer.get().map_or(0, |buffer| buffer.queue.len())
+    }
+
+    /// Records dropped so far under the overflow policy.
+    pub fn dropped(&self) -> u64 {
+        self.buffer.get().map_or(0, |buffer| buffer.queue.dropped())
+    }
+
+    /// Whether records are reaching Redis: the last write succeeded, or
+    /// with nothing written yet, the initial connection did.  A lazily
+    /// connected telemetry reports `false` until its first write.
+    pub fn is_connected(&self) -> bool {
+        Self::lock_health(&self.health).connected
+    }
+
+    /// Snapshot of the Redis connection and the buffer.
+    pub fn health(&self) -> TelemetryHealth {
+        TelemetryHealth {
+            dropped: self.dropped(),
+            queue_depth: self.queue_depth(),
+            ..Self::lock_health(&self.health).clone()
+        }
+    }
+
+    fn lock_health(health: &Mutex<TelemetryHealth>) -> std::sync::MutexGuard<'_, TelemetryHealth> {
+        health.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
     pub async fn record(
         &self,
         key: &str,
@@ -73,52 +329,391 @@ impl Telemetry {
         output_tokens: u32,
         response_time_ms: u64,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64;
-
-        if let Some(ref manager) = self.manager {
-            let stream_key = self.stream_key.clone();
-            let key_clone = key.to_string();
-            let model_clone = model.to_string();
-            let mut manager = manager.clone();
-
-            tokio::spawn(async move {
-                let result: Result<(), redis::RedisError> = redis::cmd("XADD")
-                    .arg(&stream_key)
-                    .arg("*")
-                    .arg("key")
-                    .arg(&key_clone)
-                    .arg("model")
-                    .arg(&model_clone)
-                    .arg("input_tokens")
-                    .arg(input_tokens.to_string())
-                    .arg("output_tokens")
-                    .arg(output_tokens.to_string())
-                    .arg("response_time_ms")
-                    .arg(response_time_ms.to_string())
-                    .arg("timestamp")
-                    .arg(timestamp.to_string())
-                    .query_async(&mut manager)
-                    .await;
-
-                if let Err(e) = result {
-                    tracing::error!("Failed to push telemetry to Redis stream: {:?}", e);
-                }
-            });
-        } else {
+        self.record_with_metadata(
+            key,
+            model,
+            input_tokens,
+            output_tokens,
+            response_time_ms,
+            &HashMap::new(),
+        )
+        .await
+    }
+
+    /// Like [`record_with_tokens`](Self::record_with_tokens), tagged with the
+    /// request's attribution `metadata`.
+    pub async fn record_with_metadata(
+        &self,
+        key: &str,
+        model: &str,
+        input_tokens: u32,
+        output_tokens: u32,
+        response_time_ms: u64,
+        metadata: &HashMap<String, String>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.record_with_compression(
+            key,
+            model,
+            input_tokens,
+            output_tokens,
+            response_time_ms,
+            metadata,
+            None,
+        )
+        .await
+    }
+
+    /// Like [`record_with_metadata`](Self::record_with_metadata), with the
+    /// prompt size before and after compression when the prompt was
+    /// compressed.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn record_with_compression(
+        &self,
+        key: &str,
+        model: &str,
+        input_tokens: u32,
+        output_tokens: u32,
+        response_time_ms: u64,
+        metadata: &HashMap<String, String>,
+        compression: Option<CompressionStats>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if !self.writes_to_redis() {
             tracing::debug!(
                 "Telemetry skipped (Redis unavailable): key_id={}, model={}, input_tokens={}, output_tokens={}, response_time_ms={}",
                 Self::key_id(key), model, input_tokens, output_tokens, response_time_ms
             );
         }
 
+        let mut fields = vec![
+            ("key", key.to_string()),
+            ("model", model.to_string()),
+            ("input_tokens", input_tokens.to_string()),
+            ("output_tokens", output_tokens.to_string()),
+            ("response_time_ms", response_time_ms.to_string()),
+            ("timestamp", Self::now_ms().to_string()),
+        ];
+        if !metadata.is_empty() {
+            fields.push(("metadata", serde_json::to_string(metadata)?));
+        }
+        if let Some(stats) = compression {
+            fields.push((
+                "compression_original_tokens",
+                stats.original_tokens.to_string(),
+            ));
+            fields.push((
+                "compression_compressed_tokens",
+                stats.compressed_tokens.to_string(),
+            ));
+        }
+        self.push(fields).await;
+
         Ok(())
     }
+
+    /// Record a failed request.  The control plane counts these for
+    /// error-rate and provider-downtime alerting.
+    pub async fn record_error(
+        &self,
+        key: &str,
+        model: &str,
+        provider: &str,
+        error: &str,
+        response_time_ms: u64,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if !self.writes_to_redis() {
+            tracing::debug!(
+                "Telemetry skipped (Redis unavailable): key_id={}, model={}, provider={}, error={}",
+                Self::key_id(key),
+                model,
+                provider,
+                error
+            );
+        }
+
+        self.push(vec![
+            ("key", key.to_string()),
+            ("model", model.to_string()),
+            ("input_tokens", "0".to_string()),
+            ("output_tokens", "0".to_string()),
+            ("response_time_ms", response_time_ms.to_string()),
+            ("timestamp", Self::now_ms().to_string()),
+            ("provider", provider.to_string()),
+            ("error", error.to_string()),
+        ])
+        .await;
+
+        Ok(())
+    }
+
+    /// Announce a request rejected by rate limiting on the events channel,
+    /// for live dashboards.  Not persisted; nothing is published while Redis
+    /// is unavailable.
+    #[cfg_attr(not(feature = "redis"), allow(unused_variables))]
+    pub fn record_rejection(&self, key: &str, model: &str, reason: &str) {
+        #[cfg(feature = "redis")]
+        self.publish_rejection(key, model, reason);
+    }
+
+    #[cfg(feature = "redis")]
+    fn publish_rejection(&self, key: &str, model: &str, reason: &str) {
+        let Some(ref manager) = self.manager else {
+            return;
+        };
+        let rejection = RateLimitRejection {
+            key_hash: hex::encode(Sha256::digest(key.as_bytes())),
+            model: model.to_string(),
+            reason: reason.to_string(),
+            timestamp: Self::now_ms(),
+        };
+        let mut manager = manager.clone();
+        let task = self.tasks.track();
+
+        tokio::spawn(async move {
+            let _task = task;
+            let payload = match serde_json::to_string(&rejection) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    tracing::error!("Failed to serialize rate-limit rejection: {:?}", e);
+                    return;
+                }
+            };
+            let result: Result<(), redis::RedisError> = redis::cmd("PUBLISH")
+                .arg(EVENTS_CHANNEL)
+                .arg(payload)
+                .query_async(&mut manager)
+                .await;
+            if let Err(e) = result {
+                tracing::warn!("Failed to publish rate-limit rejection: {:?}", e);
+            }
+        });
+    }
+
+    /// Whether a use of `key_id` at `now` is due to be written, marking it
+    /// written if so.
+    #[cfg(feature = "redis")]
+    fn key_use_due(&self, key_id: &str, now: Instant) -> bool {
+        let mut key_uses = self.key_uses.lock().unwrap_or_else(|e| e.into_inner());
+        match key_uses.get(key_id) {
+            Some(last) if now.duration_since(*last) < KEY_USE_WRITE_INTERVAL => false,
+            _ => {
+                key_uses.insert(key_id.to_string(), now);
+                true
+            }
+        }
+    }
+
+    /// Note that API key `key_id` was just used, for the control plane's
+    /// stale-key reports.  Written to Redis at most once per
+    /// [`KEY_USE_WRITE_INTERVAL`] per key, off the caller's task.
+    #[cfg_attr(not(feature = "redis"), allow(unused_variables))]
+    pub fn record_key_use(&self, key_id: &str) {
+        #[cfg(feature = "redis")]
+        self.write_key_use(key_id);
+    }
+
+    #[cfg(feature = "redis")]
+    fn write_key_use(&self, key_id: &str) {
+        let Some(ref manager) = self.manager else {
+            return;
+        };
+        if !self.key_use_due(key_id, Instant::now()) {
+            return;
+        }
+        let key_id = key_id.to_string();
+        let mut manager = manager.clone();
+        let task = self.tasks.track();
+
+        tokio::spawn(async move {
+            let _task = task;
+            let result: Result<(), redis::RedisError> = redis::cmd("HSET")
+                .arg(KEY_LAST_USED_KEY)
+                .arg(&key_id)
+                .arg(Self::now_ms())
+                .query_async(&mut manager)
+                .await;
+            if let Err(e) = result {
+                tracing::warn!("Failed to record use of API key {}: {:?}", key_id, e);
+            }
+        });
+    }
+
+    /// Finish pending rejection and key-use writes, then stop taking
+    /// records and write out the buffer, giving up at `deadline`.  Returns
+    /// whether everything was written, or failed to be, in time; the
+    /// flusher is stopped either way.  Clones share the buffer, so this
+    /// shuts all of them down.
+    pub async fn shutdown(&self, deadline: tokio::time::Instant) -> bool {
+        let mut flushed = tokio::time::timeout_at(deadline, self.tasks.wait_idle())
+            .await
+            .is_ok();
+        let Some(buffer) = self.buffer.get() else {
+            return flushed;
+        };
+        buffer.queue.close();
+        let flusher = buffer
+            .flusher
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        if let Some(mut flusher) = flusher {
+            if tokio::time::timeout_at(deadline, &mut flusher)
+                .await
+                .is_err()
+            {
+                tracing::warn!(
+                    "Telemetry shutdown deadline passed with {} records unwritten",
+                    buffer.queue.len()
+                );
+                flusher.abort();
+                flushed = false;
+            }
+        }
+        flushed
+    }
+
+    fn now_ms() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64
+    }
+
+    /// Buffer `fields` for the next batched write to the telemetry stream.
+    #[cfg(not(feature = "redis"))]
+    async fn push(&self, _fields: Entry) {}
+
+    /// Buffer `fields` for the next batched write to the telemetry stream.
+    #[cfg(feature = "redis")]
+    async fn push(&self, fields: Entry) {
+        let Some(ref manager) = self.manager else {
+            return;
+        };
+        let buffer = self.buffer.get_or_init(|| {
+            let queue = Arc::new(TelemetryQueue::new(
+                self.batching.capacity,
+                self.batching.overflow,
+            ));
+            let flusher = tokio::spawn(Self::flush_loop(
+                queue.clone(),
+                manager.clone(),
+                self.health.clone(),
+                self.stream_key.clone(),
+                self.shards,
+                self.batching,
+            ));
+            Buffer::new(queue, Some(flusher))
+        });
+        buffer.queue.push(fields).await;
+    }
 }
 
-#[cfg(test)]
+#[cfg(feature = "redis")]
+impl Telemetry {
+    /// Write batches from `queue` until it is closed and drained.  After a
+    /// failed write the next one waits out a growing backoff, giving the
+    /// connection manager time to reconnect; records keep buffering.
+    async fn flush_loop(
+        queue: Arc<TelemetryQueue<Entry>>,
+        mut manager: redis::aio::ConnectionManager,
+        health: Arc<Mutex<TelemetryHealth>>,
+        stream_key: String,
+        shards: u32,
+        batching: TelemetryBatching,
+    ) {
+        let mut batch = Vec::with_capacity(batching.max_batch);
+        while Self::next_batch(&queue, &mut batch, &batching).await {
+            crate::telemetry_otlp::record_telemetry_queue_depth(queue.len() as u64);
+            let result: Result<(), redis::RedisError> = Self::pipeline(&stream_key, shards, &batch)
+                .query_async(&mut manager)
+                .await;
+            let backoff = Self::record_write(&health, batch.len(), result.err());
+            batch.clear();
+            if let Some(backoff) = backoff {
+                tokio::time::sleep(backoff).await;
+            }
+        }
+    }
+
+    /// Update `health` after writing `count` records, logging once per
+    /// outage rather than per batch.  Returns how long to wait before the
+    /// next write, if it failed.
+    fn record_write(
+        health: &Mutex<TelemetryHealth>,
+        count: usize,
+        error: Option<redis::RedisError>,
+    ) -> Option<Duration> {
+        let mut health = Self::lock_health(health);
+        let Some(e) = error else {
+            if health.consecutive_failures > 0 {
+                tracing::info!(
+                    "Telemetry writes to Redis resumed after {} failed attempts; {} records lost so far",
+                    health.consecutive_failures,
+                    health.lost
+                );
+            }
+            health.connected = true;
+            health.consecutive_failures = 0;
+            crate::telemetry_otlp::record_telemetry_connected(true);
+            return None;
+        };
+        if health.consecutive_failures == 0 {
+            tracing::warn!(
+                "Failed to write telemetry to Redis, records are lost until it recovers: {}",
+                e
+            );
+        } else {
+            tracing::debug!("Telemetry write to Redis failed again: {}", e);
+        }
+        health.connected = false;
+        health.last_error = Some(e.to_string());
+        health.consecutive_failures = health.consecutive_failures.saturating_add(1);
+        health.lost += count as u64;
+        crate::telemetry_otlp::record_telemetry_connected(false);
+        crate::telemetry_otlp::record_telemetry_lost(count as u64);
+        let doublings = (health.consecutive_failures - 1).min(16);
+        Some((MIN_WRITE_BACKOFF * 2u32.pow(doublings)).min(MAX_WRITE_BACKOFF))
+    }
+
+    /// Fill `batch` with the next batch of records, waiting for the first
+    /// one.  Returns false once the buffer is closed and drained.
+    async fn next_batch(
+        queue: &TelemetryQueue<Entry>,
+        batch: &mut Vec<Entry>,
+        batching: &TelemetryBatching,
+    ) -> bool {
+        if queue.recv_many(batch, batching.max_batch).await == 0 {
+            return false;
+        }
+        let deadline = tokio::time::Instant::now() + batching.flush_interval;
+        while batch.len() < batching.max_batch {
+            let wanted = batching.max_batch - batch.len();
+            match tokio::time::timeout_at(deadline, queue.recv_many(batch, wanted)).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+        true
+    }
+
+    fn pipeline(stream_key: &str, shards: u32, batch: &[Entry]) -> redis::Pipeline {
+        let mut pipe = redis::pipe();
+        for fields in batch {
+            let key = fields
+                .iter()
+                .find(|(field, _)| *field == "key")
+                .map_or("", |(_, key)| key.as_str());
+            let stream = shard_stream_key(stream_key, shard_for_key(key, shards), shards);
+            let cmd = pipe.cmd("XADD").arg(stream).arg("*");
+            for (field, value) in fields {
+                cmd.arg(*field).arg(value);
+            }
+            pipe.ignore();
+        }
+        pipe
+    }
+}
+
+#[cfg(all(test, feature = "redis"))]
 mod tests {
     use super::*;
 
@@ -163,6 +758,22 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_telemetry_record_rejection_invalid_redis() {
+        let telemetry = Telemetry::new("invalid-url").await.unwrap();
+        telemetry.record_rejection("test-key", "gpt-4", "Rate limit exceeded");
+    }
+
+    #[tokio::test]
+    async fn test_telemetry_record_with_metadata_invalid_redis() {
+        let telemetry = Telemetry::new("invalid-url").await.unwrap();
+        let metadata = HashMap::from([("feature".to_string(), "search".to_string())]);
+        let result = telemetry
+            .record_with_metadata("test-key", "gpt-4", 100, 50, 250, &metadata)
+            .await;
+        assert!(result.is_ok());
+    }
+
     #[tokio::test]
     async fn test_telemetry_record_multiple_calls() {
         let telemetry = Telemetry::new("redis://localhost:6379").await.unwrap();
@@ -193,6 +804,15 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_telemetry_record_error_invalid_redis() {
+        let telemetry = Telemetry::new("invalid-url").await.unwrap();
+        let result = telemetry
+            .record_error("test-key", "gpt-4", "openai", "API error (503)", 30_000)
+            .await;
+        assert!(result.is_ok());
+    }
+
     #[tokio::test]
     async fn test_telemetry_with_empty_stream_key() {
         let telemetry = Telemetry::new("redis://localhost:6379")
@@ -340,4 +960,184 @@ mod tests {
             assert!(result.is_ok());
         }
     }
+
+    fn entry(key: &str) -> Entry {
+        vec![("key", key.to_string())]
+    }
+
+    #[tokio::test]
+    async fn test_full_buffer_applies_overflow_policy() {
+        let telemetry =
+            Telemetry::new_lazy("redis://127.0.0.1:1").with_batching(TelemetryBatching {
+                capacity: 1,
+                overflow: OverflowPolicy::DropOldest,
+                ..Default::default()
+            });
+        // Stand in for the flusher so nothing drains the buffer.
+        let queue = Arc::new(TelemetryQueue::new(1, OverflowPolicy::DropOldest));
+        assert!(telemetry
+            .buffer
+            .set(Buffer::new(queue.clone(), None))
+            .is_ok());
+
+        telemetry.push(entry("a")).await;
+        telemetry.push(entry("b")).await;
+        telemetry.clone().push(entry("c")).await;
+        assert_eq!(telemetry.dropped(), 2);
+        assert_eq!(telemetry.queue_depth(), 1);
+
+        let mut batch = Vec::new();
+        queue.recv_many(&mut batch, 10).await;
+        assert_eq!(batch, vec![entry("c")]);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_the_buffer_before_the_deadline() {
+        // Stand-in flushers: one that drains the buffer, one that hangs.
+        let telemetry = Telemetry::new_lazy("redis://127.0.0.1:1");
+        let queue = Arc::new(TelemetryQueue::new(16, OverflowPolicy::Block));
+        let flusher = tokio::spawn({
+            let queue = queue.clone();
+            async move {
+                let mut batch = Vec::new();
+                while queue.recv_many(&mut batch, 16).await > 0 {}
+            }
+        });
+        assert!(telemetry
+            .buffer
+            .set(Buffer::new(queue.clone(), Some(flusher)))
+            .is_ok());
+        telemetry.push(entry("a")).await;
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        assert!(telemetry.clone().shutdown(deadline).await);
+        assert_eq!(telemetry.queue_depth(), 0);
+        // Nothing is taken once shut down.
+        telemetry.push(entry("b")).await;
+        assert_eq!(telemetry.queue_depth(), 0);
+
+        let telemetry = Telemetry::new_lazy("redis://127.0.0.1:1");
+        let queue = Arc::new(TelemetryQueue::new(16, OverflowPolicy::Block));
+        let flusher = tokio::spawn(std::future::pending::<()>());
+        let stuck = flusher.abort_handle();
+        assert!(telemetry
+            .buffer
+            .set(Buffer::new(queue, Some(flusher)))
+            .is_ok());
+        telemetry.push(entry("a")).await;
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(50);
+        assert!(!telemetry.shutdown(deadline).await);
+        tokio::task::yield_now().await;
+        assert!(stuck.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_batches_fill_up_to_max_batch() {
+        let batching = TelemetryBatching {
+            capacity: 16,
+            max_batch: 3,
+            flush_interval: Duration::from_millis(10),
+            ..Default::default()
+        };
+        let queue = TelemetryQueue::new(16, OverflowPolicy::Block);
+        for key in ["a", "b", "c", "d"] {
+            queue.push(entry(key)).await;
+        }
+
+        let mut batch = Vec::new();
+        assert!(Telemetry::next_batch(&queue, &mut batch, &batching).await);
+        assert_eq!(batch.len(), 3);
+        batch.clear();
+        // A partial batch is written once the flush interval passes.
+        assert!(Telemetry::next_batch(&queue, &mut batch, &batching).await);
+        assert_eq!(batch, vec![entry("d")]);
+        batch.clear();
+
+        queue.close();
+        assert!(!Telemetry::next_batch(&queue, &mut batch, &batching).await);
+    }
+
+    #[test]
+    fn test_batch_is_one_pipeline_of_xadds() {
+        let pipe = Telemetry::pipeline("hyperinfer:telemetry", 1, &[entry("a"), entry("b")]);
+        assert_eq!(pipe.len(), 2);
+        let packed = String::from_utf8_lossy(&pipe.get_packed_pipeline()).into_owned();
+        assert_eq!(packed.matches("XADD").count(), 2);
+    }
+
+    #[test]
+    fn test_sharded_batch_writes_each_record_to_its_keys_shard() {
+        let batch: Vec<Entry> = ["sk-a", "sk-b", "sk-c", "sk-a"].map(entry).to_vec();
+        let pipe = Telemetry::pipeline("hyperinfer:telemetry", 4, &batch);
+        let packed = String::from_utf8_lossy(&pipe.get_packed_pipeline()).into_owned();
+        for key in ["sk-a", "sk-b", "sk-c"] {
+            let stream = format!("hyperinfer:telemetry:{}", shard_for_key(key, 4));
+            assert!(packed.contains(&stream), "{} not in {}", stream, packed);
+        }
+        // Nothing goes to the unsharded stream.
+        assert!(!packed.contains("hyperinfer:telemetry\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_key_use_writes_are_throttled_per_key() {
+        let telemetry = Telemetry::new_lazy("redis://localhost:6379");
+        let start = Instant::now();
+        assert!(telemetry.key_use_due("key-a", start));
+        assert!(!telemetry.key_use_due("key-a", start + Duration::from_secs(59)));
+        assert!(telemetry.key_use_due("key-b", start + Duration::from_secs(59)));
+        // Clones share the record, as the client's telemetry handles do.
+        let clone = telemetry.clone();
+        assert!(!clone.key_use_due("key-b", start + Duration::from_secs(60)));
+        assert!(clone.key_use_due("key-a", start + KEY_USE_WRITE_INTERVAL));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_redis_keeps_reconnecting() {
+        let telemetry = Telemetry::new("redis://127.0.0.1:1").await.unwrap();
+        assert!(telemetry.manager.is_some());
+        assert!(!telemetry.is_connected());
+        assert!(telemetry.health().last_error.is_some());
+
+        let telemetry = Telemetry::new_lazy("not a url");
+        assert!(telemetry.manager.is_none());
+        assert!(telemetry.health().last_error.is_some());
+    }
+
+    #[test]
+    fn test_write_failures_back_off_until_success() {
+        let health = Mutex::new(TelemetryHealth::default());
+        let refused = || {
+            Some(redis::RedisError::from(std::io::Error::from(
+                std::io::ErrorKind::ConnectionRefused,
+            )))
+        };
+
+        assert_eq!(
+            Telemetry::record_write(&health, 10, refused()),
+            Some(MIN_WRITE_BACKOFF)
+        );
+        assert_eq!(
+            Telemetry::record_write(&health, 5, refused()),
+            Some(MIN_WRITE_BACKOFF * 2)
+        );
+        {
+            let health = Telemetry::lock_health(&health);
+            assert!(!health.connected);
+            assert_eq!(health.consecutive_failures, 2);
+            assert_eq!(health.lost, 15);
+        }
+        for _ in 0..20 {
+            Telemetry::record_write(&health, 1, refused());
+        }
+        assert_eq!(
+            Telemetry::record_write(&health, 1, refused()),
+            Some(MAX_WRITE_BACKOFF)
+        );
+
+        assert_eq!(Telemetry::record_write(&health, 10, None), None);
+        let health = Telemetry::lock_health(&health);
+        assert!(health.connected);
+        assert_eq!(health.consecutive_failures, 0);
+        assert_eq!(health.lost, 36);
+        assert!(health.last_error.is_some());
+    }
 }
@@ -1,8 +1,13 @@
+use hyperinfer_core::HyperInferError;
 use opentelemetry::global;
+use opentelemetry::metrics::{Counter, Gauge, Histogram, Meter, UpDownCounter};
+use opentelemetry::KeyValue;
 use opentelemetry_http::HttpClient;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
 use opentelemetry_sdk::propagation::TraceContextPropagator;
 use opentelemetry_sdk::trace::SdkTracerProvider;
 use std::sync::OnceLock;
+use std::time::Duration;
 use tracing::Span;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
@@ -33,6 +38,41 @@ impl HttpClient for ReqwestHttpClient {
 /// and `shutdown_telemetry` share the same instance.
 pub(crate) static TRACER_PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
 
+/// Meter provider set up by `init_metrics_with_headers`, kept for shutdown.
+pub(crate) static METER_PROVIDER: OnceLock<SdkMeterProvider> = OnceLock::new();
+
+/// Instruments recorded by `chat()` / `chat_stream()`; unset until metrics
+/// are initialised, in which case recording is a no-op.
+static GEN_AI_METRICS: OnceLock<GenAiMetrics> = OnceLock::new();
+
+/// Instruments for the usage-record buffer, likewise unset until metrics
+/// are initialised.
+static TELEMETRY_QUEUE_METRICS: OnceLock<TelemetryQueueMetrics> = OnceLock::new();
+
+/// Instruments for model cascades, likewise unset until metrics are
+/// initialised.
+static CASCADE_METRICS: OnceLock<CascadeMetrics> = OnceLock::new();
+
+/// Initialise traces and metrics against a single OTLP/HTTP collector.
+///
+/// `endpoint` is the collector base URL (e.g. `http://localhost:4318`);
+/// spans are exported to `{endpoint}/v1/traces` and metrics to
+/// `{endpoint}/v1/metrics`.
+pub fn init_observability(endpoint: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    init_observability_with_headers(endpoint, vec![])
+}
+
+/// Like [`init_observability`] but injects `headers` into every export
+/// request of both signals.
+pub fn init_observability_with_headers(
+    endpoint: &str,
+    headers: Vec<(String, String)>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let base = endpoint.trim_end_matches('/');
+    init_telemetry_with_headers(&format!("{}/v1/traces", base), headers.clone())?;
+    init_metrics_with_headers(&format!("{}/v1/metrics", base), headers)
+}
+
 /// Initialise the global OpenTelemetry tracer and wire it into the
 /// `tracing` subscriber registry.
 ///
@@ -105,6 +145,49 @@ pub fn init_telemetry_with_headers(
     Ok(())
 }
 
+/// Initialise the global OpenTelemetry meter provider with an OTLP/HTTP
+/// metrics exporter and start recording the GenAI client metrics.
+///
+/// `endpoint` is the full metrics URL (usually ending in `/v1/metrics`).
+/// Idempotent like [`init_telemetry_with_headers`].
+pub fn init_metrics_with_headers(
+    endpoint: &str,
+    headers: Vec<(String, String)>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use opentelemetry::metrics::MeterProvider as _;
+    use opentelemetry_otlp::{WithExportConfig, WithHttpConfig};
+
+    if METER_PROVIDER.get().is_some() {
+        return Ok(());
+    }
+
+    let mut http_builder = opentelemetry_otlp::MetricExporter::builder()
+        .with_http()
+        .with_http_client(ReqwestHttpClient(reqwest::Client::new()))
+        .with_endpoint(endpoint);
+
+    if !headers.is_empty() {
+        let header_map: std::collections::HashMap<String, String> =
+            headers.iter().cloned().collect();
+        http_builder = http_builder.with_headers(header_map);
+    }
+
+    let exporter = http_builder.build()?;
+
+    let provider = METER_PROVIDER.get_or_init(|| {
+        SdkMeterProvider::builder()
+            .with_periodic_exporter(exporter)
+            .build()
+    });
+    global::set_meter_provider(provider.clone());
+    let meter = provider.meter("hyperinfer-client");
+    GEN_AI_METRICS.get_or_init(|| GenAiMetrics::new(&meter));
+    TELEMETRY_QUEUE_METRICS.get_or_init(|| TelemetryQueueMetrics::new(&meter));
+    CASCADE_METRICS.get_or_init(|| CascadeMetrics::new(&meter));
+
+    Ok(())
+}
+
 /// Initialise telemetry pointing at a Langfuse instance.
 ///
 /// Langfuse's OTLP endpoint requires HTTP Basic Authentication where
@@ -126,15 +209,19 @@ pub fn init_langfuse_telemetry(
     init_telemetry_with_headers(&endpoint, vec![("Authorization".to_string(), auth_header)])
 }
 
-/// Flush and shut down the global tracer provider.
+/// Flush and shut down the global tracer and meter providers.
 ///
-/// Should be called before process exit to ensure all buffered spans are
-/// exported.  opentelemetry_sdk 0.31 removed `global::shutdown_tracer_provider()`
-/// so we retain the provider in the OnceLock and shut it down directly.
+/// Should be called before process exit to ensure all buffered spans and
+/// metrics are exported.  opentelemetry_sdk 0.31 removed
+/// `global::shutdown_tracer_provider()` so we retain the providers in their
+/// OnceLocks and shut them down directly.
 pub fn shutdown_telemetry() {
     if let Some(provider) = TRACER_PROVIDER.get() {
         let _ = provider.shutdown();
     }
+    if let Some(provider) = METER_PROVIDER.get() {
+        let _ = provider.shutdown();
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -152,11 +239,241 @@ pub fn set_gen_ai_usage(span: &Span, input_tokens: u32, output_tokens: u32) {
     span.set_attribute("gen_ai.usage.output_tokens", output_tokens as i64);
 }
 
+/// Record the estimated prompt size before and after compression.
+pub fn set_compression_attributes(span: &Span, stats: hyperinfer_core::CompressionStats) {
+    span.set_attribute(
+        "hyperinfer.compression.original_tokens",
+        stats.original_tokens as i64,
+    );
+    span.set_attribute(
+        "hyperinfer.compression.compressed_tokens",
+        stats.compressed_tokens as i64,
+    );
+}
+
 pub fn set_gen_ai_response(span: &Span, response_id: &str, finish_reason: &str) {
     span.set_attribute("gen_ai.response.id", response_id.to_owned());
     span.set_attribute("gen_ai.response.finish_reasons", finish_reason.to_owned());
 }
 
+// ---------------------------------------------------------------------------
+// GenAI Semantic Convention metrics
+// ---------------------------------------------------------------------------
+
+/// Bucket boundaries recommended by the GenAI semantic conventions.
+const TOKEN_USAGE_BOUNDARIES: [f64; 14] = [
+    1.0, 4.0, 16.0, 64.0, 256.0, 1024.0, 4096.0, 16384.0, 65536.0, 262144.0, 1048576.0, 4194304.0,
+    16777216.0, 67108864.0,
+];
+const OPERATION_DURATION_BOUNDARIES: [f64; 14] = [
+    0.01, 0.02, 0.04, 0.08, 0.16, 0.32, 0.64, 1.28, 2.56, 5.12, 10.24, 20.48, 40.96, 81.92,
+];
+
+/// `gen_ai.client.*` instruments.  Both are histograms per the semantic
+/// conventions; their count and sum give request and token totals.
+pub struct GenAiMetrics {
+    token_usage: Histogram<u64>,
+    operation_duration: Histogram<f64>,
+}
+
+impl GenAiMetrics {
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            token_usage: meter
+                .u64_histogram("gen_ai.client.token.usage")
+                .with_unit("{token}")
+                .with_description("Number of input and output tokens used")
+                .with_boundaries(TOKEN_USAGE_BOUNDARIES.to_vec())
+                .build(),
+            operation_duration: meter
+                .f64_histogram("gen_ai.client.operation.duration")
+                .with_unit("s")
+                .with_description("GenAI operation duration")
+                .with_boundaries(OPERATION_DURATION_BOUNDARIES.to_vec())
+                .build(),
+        }
+    }
+
+    pub fn record_token_usage(
+        &self,
+        provider: &str,
+        model: &str,
+        operation: &str,
+        input_tokens: u32,
+        output_tokens: u32,
+    ) {
+        for (token_type, tokens) in [("input", input_tokens), ("output", output_tokens)] {
+            let mut attributes = gen_ai_metric_attributes(provider, model, operation);
+            attributes.push(KeyValue::new("gen_ai.token.type", token_type));
+            self.token_usage.record(u64::from(tokens), &attributes);
+        }
+    }
+
+    /// Record how long an operation took; `error_type` is set for failures.
+    pub fn record_duration(
+        &self,
+        provider: &str,
+        model: &str,
+        operation: &str,
+        duration: Duration,
+        error_type: Option<&str>,
+    ) {
+        let mut attributes = gen_ai_metric_attributes(provider, model, operation);
+        if let Some(error_type) = error_type {
+            attributes.push(KeyValue::new("error.type", error_type.to_owned()));
+        }
+        self.operation_duration
+            .record(duration.as_secs_f64(), &attributes);
+    }
+}
+
+/// Health of the buffer usage records wait in before being written to
+/// Redis: how deep it is and how many records it has had to drop.
+pub struct TelemetryQueueMetrics {
+    depth: Gauge<u64>,
+    dropped: Counter<u64>,
+    connected: Gauge<u64>,
+    lost: Counter<u64>,
+}
+
+impl TelemetryQueueMetrics {
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            depth: meter
+                .u64_gauge("hyperinfer.telemetry.queue.depth")
+                .with_unit("{record}")
+                .with_description("Usage records waiting to be written to Redis")
+                .build(),
+            dropped: meter
+                .u64_counter("hyperinfer.telemetry.dropped")
+                .with_unit("{record}")
+                .with_description("Usage records dropped because the buffer was full")
+                .build(),
+            connected: meter
+                .u64_gauge("hyperinfer.telemetry.redis.connected")
+                .with_description("1 while usage records are being written to Redis, 0 otherwise")
+                .build(),
+            lost: meter
+                .u64_counter("hyperinfer.telemetry.lost")
+                .with_unit("{record}")
+                .with_description("Usage records lost because writing them to Redis failed")
+                .build(),
+        }
+    }
+}
+
+/// How model cascades end and what they save.
+pub struct CascadeMetrics {
+    requests: Counter<u64>,
+    saved: UpDownCounter<f64>,
+}
+
+impl CascadeMetrics {
+    pub fn new(meter: &Meter) -> Self {
+        Self {
+            requests: meter
+                .u64_counter("hyperinfer.cascade.requests")
+                .with_unit("{request}")
+                .with_description("Cascaded requests, by whether the cheap answer was kept")
+                .build(),
+            saved: meter
+                .f64_up_down_counter("hyperinfer.cascade.saved")
+                .with_unit("[USD]/100")
+                .with_description(
+                    "Cents saved over sending cascaded requests straight to the expensive model",
+                )
+                .build(),
+        }
+    }
+}
+
+fn gen_ai_metric_attributes(provider: &str, model: &str, operation: &str) -> Vec<KeyValue> {
+    vec![
+        KeyValue::new("gen_ai.provider.name", provider.to_owned()),
+        KeyValue::new("gen_ai.request.model", model.to_owned()),
+        KeyValue::new("gen_ai.operation.name", operation.to_owned()),
+    ]
+}
+
+/// Low-cardinality `error.type` for `error`: the HTTP status for provider
+/// API errors, otherwise the error kind.
+pub fn gen_ai_error_type(error: &HyperInferError) -> String {
+    match error {
+        HyperInferError::ApiError { status, .. } => status.to_string(),
+        HyperInferError::Http(e) if e.is_timeout() => "timeout".to_string(),
+        _ => error.category().to_string(),
+    }
+}
+
+/// Record token usage on the global GenAI metrics, if initialised.
+pub fn record_gen_ai_token_usage(
+    provider: &str,
+    model: &str,
+    operation: &str,
+    input_tokens: u32,
+    output_tokens: u32,
+) {
+    if let Some(metrics) = GEN_AI_METRICS.get() {
+        metrics.record_token_usage(provider, model, operation, input_tokens, output_tokens);
+    }
+}
+
+/// Record an operation duration on the global GenAI metrics, if initialised.
+pub fn record_gen_ai_duration(
+    provider: &str,
+    model: &str,
+    operation: &str,
+    duration: Duration,
+    error_type: Option<&str>,
+) {
+    if let Some(metrics) = GEN_AI_METRICS.get() {
+        metrics.record_duration(provider, model, operation, duration, error_type);
+    }
+}
+
+/// Record the telemetry buffer's depth, if metrics are initialised.
+pub fn record_telemetry_queue_depth(depth: u64) {
+    if let Some(metrics) = TELEMETRY_QUEUE_METRICS.get() {
+        metrics.depth.record(depth, &[]);
+    }
+}
+
+/// Count records dropped from the telemetry buffer, if metrics are
+/// initialised.
+pub fn record_telemetry_dropped(count: u64) {
+    if let Some(metrics) = TELEMETRY_QUEUE_METRICS.get() {
+        metrics.dropped.add(count, &[]);
+    }
+}
+
+/// Record whether the telemetry writer's last Redis write succeeded, if
+/// metrics are initialised.
+pub fn record_telemetry_connected(connected: bool) {
+    if let Some(metrics) = TELEMETRY_QUEUE_METRICS.get() {
+        metrics.connected.record(u64::from(connected), &[]);
+    }
+}
+
+/// Count records lost in failed Redis writes, if metrics are initialised.
+pub fn record_telemetry_lost(count: u64) {
+    if let Some(metrics) = TELEMETRY_QUEUE_METRICS.get() {
+        metrics.lost.add(count, &[]);
+    }
+}
+
+/// Record how a cascade ended (`accepted` or `escalated`) and what it
+/// saved, if metrics are initialised.
+pub fn record_cascade(cascade: &str, outcome: &str, saved_cents: f64) {
+    if let Some(metrics) = CASCADE_METRICS.get() {
+        let attributes = [
+            KeyValue::new("hyperinfer.cascade", cascade.to_owned()),
+            KeyValue::new("hyperinfer.cascade.outcome", outcome.to_owned()),
+        ];
+        metrics.requests.add(1, &attributes);
+        metrics.saved.add(saved_cents, &attributes);
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -231,6 +548,87 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_gen_ai_metrics_recorded() {
+        use opentelemetry::metrics::MeterProvider as _;
+        use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+        use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader};
+
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        let metrics = GenAiMetrics::new(&provider.meter("test"));
+
+        metrics.record_token_usage("openai", "gpt-4", "chat", 100, 50);
+        metrics.record_duration("openai", "gpt-4", "chat", Duration::from_millis(250), None);
+        metrics.record_duration(
+            "openai",
+            "gpt-4",
+            "chat",
+            Duration::from_millis(50),
+            Some("429"),
+        );
+        provider.force_flush().unwrap();
+
+        let exported = exporter.get_finished_metrics().unwrap();
+        let metrics: Vec<_> = exported
+            .iter()
+            .flat_map(|rm| rm.scope_metrics())
+            .flat_map(|sm| sm.metrics())
+            .collect();
+
+        let usage = metrics
+            .iter()
+            .find(|m| m.name() == "gen_ai.client.token.usage")
+            .unwrap();
+        let AggregatedMetrics::U64(MetricData::Histogram(usage)) = usage.data() else {
+            panic!("token usage should be a u64 histogram");
+        };
+        let mut sums: Vec<_> = usage
+            .data_points()
+            .map(|dp| {
+                let token_type = dp
+                    .attributes()
+                    .find(|kv| kv.key.as_str() == "gen_ai.token.type")
+                    .unwrap()
+                    .value
+                    .to_string();
+                (token_type, dp.sum())
+            })
+            .collect();
+        sums.sort();
+        assert_eq!(
+            sums,
+            vec![("input".to_string(), 100), ("output".to_string(), 50)]
+        );
+
+        let duration = metrics
+            .iter()
+            .find(|m| m.name() == "gen_ai.client.operation.duration")
+            .unwrap();
+        assert_eq!(duration.unit(), "s");
+        let AggregatedMetrics::F64(MetricData::Histogram(duration)) = duration.data() else {
+            panic!("operation duration should be an f64 histogram");
+        };
+        assert_eq!(duration.data_points().count(), 2);
+        let failed = duration
+            .data_points()
+            .find(|dp| dp.attributes().any(|kv| kv.key.as_str() == "error.type"))
+            .unwrap();
+        assert_eq!(failed.count(), 1);
+    }
+
+    #[test]
+    fn test_gen_ai_error_type() {
+        let api = HyperInferError::api_error(429, "slow down".to_string());
+        assert_eq!(gen_ai_error_type(&api), "429");
+        assert_eq!(
+            gen_ai_error_type(&HyperInferError::rate_limit("x")),
+            "rate_limit"
+        );
+    }
+
     #[test]
     fn test_init_telemetry_with_headers_build_error() {
         let endpoint = "http://\0invalid";
@@ -0,0 +1,234 @@
+//! Bounded buffer between request handlers and the telemetry flusher.
+//!
+//! Handlers push records; one flusher task takes them off in batches.  When
+//! Redis slows down the buffer fills, and the [`OverflowPolicy`] decides
+//! what gives: records, or the requests producing them.  Only the flusher
+//! built with the `redis` feature takes records off.
+
+#![cfg_attr(not(feature = "redis"), allow(dead_code))]
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+/// What to do with a record that arrives while the buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Drop the new record.
+    #[default]
+    DropNewest,
+    /// Drop the oldest buffered record to make room, keeping the most
+    /// recent traffic.
+    DropOldest,
+    /// Wait for room, so requests slow down with Redis instead of losing
+    /// records.
+    Block,
+    /// Keep one in `keep_one_in` new records once the buffer is half full,
+    /// thinning traffic out before it has to be dropped wholesale.  Records
+    /// arriving while it is full are dropped.
+    Sample { keep_one_in: u32 },
+}
+
+struct State<T> {
+    entries: VecDeque<T>,
+    closed: bool,
+    /// Records offered while sampling, to pick every n-th.
+    sampled: u64,
+}
+
+pub(crate) struct TelemetryQueue<T> {
+    state: Mutex<State<T>>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    /// Woken when records are pushed or the queue closes.
+    readable: Notify,
+    /// Woken when the flusher takes records off.
+    writable: Notify,
+    dropped: AtomicU64,
+}
+
+impl<T> TelemetryQueue<T> {
+    pub(crate) fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            state: Mutex::new(State {
+                entries: VecDeque::new(),
+                closed: false,
+                sampled: 0,
+            }),
+            capacity: capacity.max(1),
+            policy,
+            readable: Notify::new(),
+            writable: Notify::new(),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Records dropped so far, whether for overflow or by sampling.
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn drop_one(&self) {
+        let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+        crate::telemetry_otlp::record_telemetry_dropped(1);
+        if dropped == 1 || dropped.is_multiple_of(1000) {
+            tracing::warn!("Telemetry buffer full; {} records dropped so far", dropped);
+        }
+    }
+
+    /// Buffer `entry` as the overflow policy allows.  Only waits under
+    /// [`OverflowPolicy::Block`].
+    pub(crate) async fn push(&self, entry: T) {
+        loop {
+            let writable = self.writable.notified();
+            tokio::pin!(writable);
+            // Registered before checking, so a drain in between still wakes us.
+            writable.as_mut().enable();
+            {
+                let mut state = self.lock();
+                if state.closed {
+                    return;
+                }
+                let len = state.entries.len();
+                if let OverflowPolicy::Sample { keep_one_in } = self.policy {
+                    if len >= self.capacity.div_ceil(2) {
+                        state.sampled += 1;
+                        if !state.sampled.is_multiple_of(u64::from(keep_one_in.max(1))) {
+                            drop(state);
+                            self.drop_one();
+                            return;
+                        }
+                    }
+                }
+                if len < self.capacity {
+                    state.entries.push_back(entry);
+                    drop(state);
+                    self.readable.notify_one();
+                    return;
+                }
+                match self.policy {
+                    OverflowPolicy::Block => {}
+                    OverflowPolicy::DropOldest => {
+                        state.entries.pop_front();
+                        state.entries.push_back(entry);
+                        drop(state);
+                        self.drop_one();
+                        return;
+                    }
+                    OverflowPolicy::DropNewest | OverflowPolicy::Sample { .. } => {
+                        drop(state);
+                        self.drop_one();
+                        return;
+                    }
+                }
+            }
+            writable.await;
+        }
+    }
+
+    /// Wait for records and move up to `max` of them into `batch`.  Returns
+    /// how many were moved: 0 only once the queue is closed and empty.
+    pub(crate) async fn recv_many(&self, batch: &mut Vec<T>, max: usize) -> usize {
+        loop {
+            {
+                let mut state = self.lock();
+                if !state.entries.is_empty() {
+                    let n = max.min(state.entries.len());
+                    batch.extend(state.entries.drain(..n));
+                    drop(state);
+                    self.writable.notify_waiters();
+                    return n;
+                }
+                if state.closed {
+                    return 0;
+                }
+            }
+            // Single consumer: a push between the check and here leaves a
+            // permit, so it is not missed.
+            self.readable.notified().await;
+        }
+    }
+
+    /// Stop accepting records; the flusher drains what is left and stops.
+    pub(crate) fn close(&self) {
+        self.lock().closed = true;
+        self.readable.notify_one();
+        self.writable.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    async fn drain(queue: &TelemetryQueue<u32>) -> Vec<u32> {
+        let mut batch = Vec::new();
+        queue.recv_many(&mut batch, usize::MAX).await;
+        batch
+    }
+
+    #[tokio::test]
+    async fn test_drop_newest_and_drop_oldest() {
+        let newest = TelemetryQueue::new(2, OverflowPolicy::DropNewest);
+        let oldest = TelemetryQueue::new(2, OverflowPolicy::DropOldest);
+        for i in 1..=3 {
+            newest.push(i).await;
+            oldest.push(i).await;
+        }
+        assert_eq!(drain(&newest).await, vec![1, 2]);
+        assert_eq!(drain(&oldest).await, vec![2, 3]);
+        assert_eq!(newest.dropped(), 1);
+        assert_eq!(oldest.dropped(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_sample_thins_out_past_half_full() {
+        let queue = TelemetryQueue::new(4, OverflowPolicy::Sample { keep_one_in: 2 });
+        for i in 1..=8 {
+            queue.push(i).await;
+        }
+        // 1 and 2 fill half; then every second record is kept until full.
+        assert_eq!(drain(&queue).await, vec![1, 2, 4, 6]);
+        assert_eq!(queue.dropped(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_room() {
+        let queue = Arc::new(TelemetryQueue::new(1, OverflowPolicy::Block));
+        queue.push(1).await;
+        let pusher = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.push(2).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!pusher.is_finished());
+        assert_eq!(queue.len(), 1);
+
+        assert_eq!(drain(&queue).await, vec![1]);
+        pusher.await.unwrap();
+        assert_eq!(drain(&queue).await, vec![2]);
+        assert_eq!(queue.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_close_drains_then_stops() {
+        let queue = TelemetryQueue::new(4, OverflowPolicy::Block);
+        queue.push(1).await;
+        queue.close();
+        queue.push(2).await;
+        assert_eq!(drain(&queue).await, vec![1]);
+        let mut batch = Vec::new();
+        assert_eq!(queue.recv_many(&mut batch, 10).await, 0);
+    }
+}
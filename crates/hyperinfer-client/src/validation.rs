@@ -0,0 +1,379 @@
+//! Structured output validation.
+//!
+//! When a request asks for `response_format: json_schema`, `chat()` parses
+//! the response and checks it against the schema before returning it.  A
+//! response that does not conform is re-asked, up to [`MAX_REASKS`] times,
+//! with a corrective system message quoting what was wrong.  The number of
+//! calls made is reported as `validation_attempts` in the response metadata;
+//! if the last attempt still fails it is returned anyway, with
+//! `validation_error` set, so the caller decides what to do with it.
+//!
+//! Usage is summed across attempts, so re-asks are rate limited and recorded
+//! like any other tokens.  Streams are not validated: their content has
+//! already been delivered by the time it could be checked.
+
+use hyperinfer_core::{
+    ChatMessage, ChatRequest, ChatResponse, HyperInferError, MessageRole, ResponseFormat,
+};
+use hyperinfer_providers::LlmProvider;
+
+/// Corrective re-asks after the first attempt.
+pub const MAX_REASKS: u32 = 2;
+
+/// Response metadata key holding the number of provider calls made.
+pub const ATTEMPTS_METADATA_KEY: &str = "validation_attempts";
+
+/// Response metadata key holding the validation error of a response that
+/// never conformed.
+pub const ERROR_METADATA_KEY: &str = "validation_error";
+
+/// Schema errors listed in a corrective message.
+const MAX_REPORTED_ERRORS: usize = 5;
+
+/// Characters of the previous output quoted in a corrective message.
+const MAX_QUOTED_CHARS: usize = 2000;
+
+/// A compiled `json_schema` response format.
+pub struct OutputValidator {
+    name: String,
+    validator: jsonschema::Validator,
+}
+
+impl OutputValidator {
+    /// Validator for `format`, or `None` when there is no schema to check.
+    pub fn for_format(format: Option<&ResponseFormat>) -> Result<Option<Self>, HyperInferError> {
+        let Some(ResponseFormat::JsonSchema { json_schema }) = format else {
+            return Ok(None);
+        };
+        let validator = jsonschema::validator_for(&json_schema.schema).map_err(|e| {
+            HyperInferError::Config(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid JSON schema '{}': {}", json_schema.name, e),
+            ))
+        })?;
+        Ok(Some(Self {
+            name: json_schema.name.clone(),
+            validator,
+        }))
+    }
+
+    /// Check `content`, describing what is wrong with it on failure.
+    pub fn check(&self, content: &str) -> Result<(), String> {
+        let value: serde_json::Value = serde_json::from_str(content.trim())
+            .map_err(|e| format!("Response is not valid JSON: {}", e))?;
+        let errors: Vec<String> = self
+            .validator
+            .iter_errors(&value)
+            .take(MAX_REPORTED_ERRORS)
+            .map(|e| match e.instance_path().as_str() {
+                "" => e.to_string(),
+                path => format!("{}: {}", path, e),
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Response does not match schema '{}': {}",
+                self.name,
+                errors.join("; ")
+            ))
+        }
+    }
+}
+
+fn content(response: &ChatResponse) -> &str {
+    response
+        .choices
+        .first()
+        .map(|c| c.message.content.as_str())
+        .unwrap_or_default()
+}
+
+/// `request` with a system message asking the model to fix `output`.  The
+/// bad output is quoted rather than replayed as an assistant turn, so the
+/// conversation still ends with the user's message.
+pub fn corrective_request(request: &ChatRequest, output: &str, error: &str) -> ChatRequest {
+    let quoted: String = output.chars().take(MAX_QUOTED_CHARS).collect();
+    let mut retry = request.clone();
+    retry.messages.push(ChatMessage {
+        role: MessageRole::System,
+        content: format!(
+            "Your previous response was rejected. {}\n\nPrevious response:\n{}\n\n\
+             Reply again with only a JSON value that satisfies the schema.",
+            error, quoted
+        ),
+    });
+    retry
+}
+
+/// Validate `response` and re-ask `provider` until it conforms or
+/// `max_reasks` corrective calls have been made.  A failed re-ask ends the
+/// loop with the last response rather than discarding it.
+pub async fn reask_until_valid(
+    validator: &OutputValidator,
+    provider: &dyn LlmProvider,
+    request: &ChatRequest,
+    api_key: &str,
+    mut response: ChatResponse,
+    max_reasks: u32,
+) -> ChatResponse {
+    let mut usage = response.usage.clone();
+    let mut attempts = 1;
+    let mut error = validator.check(content(&response)).err();
+    while let Some(err) = error.as_deref() {
+        if attempts > max_reasks {
+            break;
+        }
+        tracing::warn!(
+            "Response for {} failed validation (attempt {}): {}",
+            request.model,
+            attempts,
+            err
+        );
+        let retry = corrective_request(request, content(&response), err);
+        match provider.chat(&retry, api_key).await {
+            Ok(next) => response = next,
+            Err(e) => {
+                tracing::warn!("Validation re-ask for {} failed: {}", request.model, e);
+                break;
+            }
+        }
+        attempts += 1;
+        usage.input_tokens += response.usage.input_tokens;
+        usage.output_tokens += response.usage.output_tokens;
+        usage.thinking_tokens += response.usage.thinking_tokens;
+        error = validator.check(content(&response)).err();
+    }
+    response.usage = usage;
+    response
+        .metadata
+        .insert(ATTEMPTS_METADATA_KEY.to_string(), attempts.to_string());
+    if let Some(err) = error {
+        response
+            .metadata
+            .insert(ERROR_METADATA_KEY.to_string(), err);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use futures::Stream;
+    use hyperinfer_core::{ChatChunk, Choice, JsonSchemaFormat, Usage};
+    use std::collections::VecDeque;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+
+    /// Answers with the scripted replies in order and records each request.
+    #[derive(Clone, Default)]
+    struct Scripted {
+        replies: Arc<Mutex<VecDeque<&'static str>>>,
+        requests: Arc<Mutex<Vec<ChatRequest>>>,
+    }
+
+    impl Scripted {
+        fn new(replies: &[&'static str]) -> Self {
+            Self {
+                replies: Arc::new(Mutex::new(replies.iter().copied().collect())),
+                ..Default::default()
+            }
+        }
+    }
+
+    #[async_trait]
+    impl LlmProvider for Scripted {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        async fn chat(
+            &self,
+            request: &ChatRequest,
+            _api_key: &str,
+        ) -> Result<ChatResponse, HyperInferError> {
+            self.requests.lock().unwrap().push(request.clone());
+            let reply =
+                self.replies.lock().unwrap().pop_front().ok_or_else(|| {
+                    HyperInferError::api_error(503, "no more replies".to_string())
+                })?;
+            Ok(response(reply))
+        }
+
+        fn stream(
+            &self,
+            _request: &ChatRequest,
+            _api_key: &str,
+        ) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send + 'static>>
+        {
+            Box::pin(futures::stream::empty())
+        }
+    }
+
+    fn response(content: &str) -> ChatResponse {
+        ChatResponse {
+            choices: vec![Choice {
+                index: 0,
+                message: ChatMessage {
+                    role: MessageRole::Assistant,
+                    content: content.to_string(),
+                },
+                finish_reason: Some("stop".to_string()),
+                thinking: None,
+            }],
+            usage: Usage {
+                input_tokens: 10,
+                output_tokens: 5,
+                thinking_tokens: 0,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn format() -> ResponseFormat {
+        ResponseFormat::JsonSchema {
+            json_schema: JsonSchemaFormat {
+                name: "person".to_string(),
+                description: None,
+                schema: serde_json::json!({
+                    "type": "object",
+                    "properties": {"name": {"type": "string"}, "age": {"type": "integer"}},
+                    "required": ["name", "age"]
+                }),
+                strict: None,
+            },
+        }
+    }
+
+    fn validator() -> OutputValidator {
+        OutputValidator::for_format(Some(&format()))
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_only_json_schema_is_validated() {
+        assert!(OutputValidator::for_format(None).unwrap().is_none());
+        assert!(
+            OutputValidator::for_format(Some(&ResponseFormat::JsonObject))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_invalid_schema_is_config_error() {
+        let format = ResponseFormat::JsonSchema {
+            json_schema: JsonSchemaFormat {
+                name: "bad".to_string(),
+                description: None,
+                schema: serde_json::json!({"type": 12}),
+                strict: None,
+            },
+        };
+        assert!(matches!(
+            OutputValidator::for_format(Some(&format)),
+            Err(HyperInferError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_check() {
+        let v = validator();
+        assert!(v.check(r#"{"name": "Ada", "age": 36}"#).is_ok());
+        assert!(v
+            .check("Sure! Here it is")
+            .unwrap_err()
+            .contains("not valid JSON"));
+        let err = v.check(r#"{"name": "Ada", "age": "old"}"#).unwrap_err();
+        assert!(err.contains("schema 'person'"));
+        assert!(err.contains("/age"));
+    }
+
+    #[tokio::test]
+    async fn test_valid_response_is_not_reasked() {
+        let provider = Scripted::new(&[]);
+        let response = reask_until_valid(
+            &validator(),
+            &provider,
+            &ChatRequest::default(),
+            "key",
+            response(r#"{"name": "Ada", "age": 36}"#),
+            MAX_REASKS,
+        )
+        .await;
+        assert_eq!(response.metadata[ATTEMPTS_METADATA_KEY], "1");
+        assert!(!response.metadata.contains_key(ERROR_METADATA_KEY));
+        assert!(provider.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reask_corrects_response() {
+        let provider = Scripted::new(&[r#"{"name": "Ada", "age": 36}"#]);
+        let request = ChatRequest {
+            model: "gpt-4o".to_string(),
+            messages: vec![ChatMessage {
+                role: MessageRole::User,
+                content: "Who wrote the first program?".to_string(),
+            }],
+            response_format: Some(format()),
+            ..Default::default()
+        };
+        let response = reask_until_valid(
+            &validator(),
+            &provider,
+            &request,
+            "key",
+            response(r#"{"name": "Ada"}"#),
+            MAX_REASKS,
+        )
+        .await;
+        assert_eq!(response.metadata[ATTEMPTS_METADATA_KEY], "2");
+        assert!(!response.metadata.contains_key(ERROR_METADATA_KEY));
+        assert_eq!(response.usage.input_tokens, 20);
+        assert_eq!(response.usage.output_tokens, 10);
+
+        let requests = provider.requests.lock().unwrap();
+        let correction = requests[0].messages.last().unwrap();
+        assert_eq!(correction.role, MessageRole::System);
+        assert!(correction.content.contains(r#"{"name": "Ada"}"#));
+        assert!(correction.content.contains("age"));
+    }
+
+    #[tokio::test]
+    async fn test_reasks_are_bounded() {
+        let provider = Scripted::new(&["nope", "still nope", "never"]);
+        let response = reask_until_valid(
+            &validator(),
+            &provider,
+            &ChatRequest::default(),
+            "key",
+            response("no"),
+            MAX_REASKS,
+        )
+        .await;
+        assert_eq!(response.metadata[ATTEMPTS_METADATA_KEY], "3");
+        assert!(response.metadata[ERROR_METADATA_KEY].contains("not valid JSON"));
+        assert_eq!(response.choices[0].message.content, "still nope");
+        assert_eq!(provider.requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_reask_keeps_last_response() {
+        let provider = Scripted::new(&[]);
+        let response = reask_until_valid(
+            &validator(),
+            &provider,
+            &ChatRequest::default(),
+            "key",
+            response("no"),
+            MAX_REASKS,
+        )
+        .await;
+        assert_eq!(response.metadata[ATTEMPTS_METADATA_KEY], "1");
+        assert!(response.metadata.contains_key(ERROR_METADATA_KEY));
+        assert_eq!(response.choices[0].message.content, "no");
+    }
+}
@@ -0,0 +1,712 @@
+//! Contract tests for the provider wire formats.
+//!
+//! Every code path that talks to a provider — `HttpCaller`, the registry
+//! providers and `HyperInferClient` itself, chat and streaming — is pointed
+//! at a wiremock server through its base URL override.  The mocks only match requests with the headers
+//! and body shape the real API requires, so a request that drifts from the
+//! contract fails to match and the call errors.
+
+use futures::{Stream, StreamExt};
+use hyperinfer_client::{
+    EgressConfig, HttpCaller, HyperInferClient, OversizedResponse, ProviderTransport,
+    TransportConfig,
+};
+use hyperinfer_core::types::{ChatMessage, MessageRole};
+use hyperinfer_core::{
+    ChatChunk, ChatRequest, Config, HyperInferError, Provider, ProviderErrorKind, ProviderRegions,
+    RegionSelection, RegionalEndpoint,
+};
+use hyperinfer_providers::anthropic::AnthropicProvider;
+use hyperinfer_providers::openai::OpenAiProvider;
+use hyperinfer_providers::LlmProvider;
+use serde_json::json;
+use std::collections::HashMap;
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const API_KEY: &str = "sk-contract";
+
+fn request(model: &str) -> ChatRequest {
+    ChatRequest {
+        model: model.to_string(),
+        messages: vec![
+            ChatMessage {
+                role: MessageRole::System,
+                content: "Be brief.".to_string(),
+            },
+            ChatMessage {
+                role: MessageRole::User,
+                content: "hi".to_string(),
+            },
+        ],
+        max_tokens: Some(64),
+        stop: Some(vec!["END".to_string()]),
+        ..Default::default()
+    }
+}
+
+fn caller(server: &MockServer) -> HttpCaller {
+    let base_urls = HashMap::from([
+        ("openai".to_string(), server.uri()),
+        ("anthropic".to_string(), server.uri()),
+    ]);
+    let headers = HashMap::from([(
+        "openai".to_string(),
+        HashMap::from([("OpenAI-Organization".to_string(), "org-1".to_string())]),
+    )]);
+    HttpCaller::new()
+        .unwrap()
+        .with_provider_headers(&headers)
+        .unwrap()
+        .with_base_urls(&base_urls)
+}
+
+/// A client config sending every provider's traffic to `server`.
+fn client_config(server: &MockServer) -> Config {
+    Config {
+        api_keys: HashMap::from([
+            ("openai".to_string(), API_KEY.to_string()),
+            ("anthropic".to_string(), API_KEY.to_string()),
+        ]),
+        provider_base_urls: HashMap::from([
+            ("openai".to_string(), server.uri()),
+            ("anthropic".to_string(), server.uri()),
+        ]),
+        ..Default::default()
+    }
+}
+
+fn sse_body(events: &[serde_json::Value]) -> String {
+    events
+        .iter()
+        .map(|event| format!("data: {}\n\n", event))
+        .collect()
+}
+
+fn sse(body: String) -> ResponseTemplate {
+    ResponseTemplate::new(200)
+        .insert_header("content-type", "text/event-stream")
+        .set_body_string(body)
+}
+
+async fn collect(
+    stream: std::pin::Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send>>,
+) -> Vec<Result<ChatChunk, HyperInferError>> {
+    stream.collect().await
+}
+
+fn openai_completion() -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "id": "chatcmpl-1",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": "hello"},
+            "finish_reason": "stop"
+        }],
+        "usage": {"prompt_tokens": 9, "completion_tokens": 2, "total_tokens": 11}
+    }))
+}
+
+fn openai_chunks() -> ResponseTemplate {
+    let events = sse_body(&[
+        json!({"id": "chatcmpl-1", "model": "gpt-4", "choices": [{"delta": {"content": "hel"}, "finish_reason": null}]}),
+        json!({"id": "chatcmpl-1", "model": "gpt-4", "choices": [{"delta": {"content": "lo"}, "finish_reason": "stop"}]}),
+        json!({"id": "chatcmpl-1", "model": "gpt-4", "choices": [], "usage": {"prompt_tokens": 9, "completion_tokens": 2}}),
+    ]);
+    sse(events + "data: [DONE]\n\n")
+}
+
+fn anthropic_message() -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "id": "msg_1",
+        "type": "message",
+        "role": "assistant",
+        "content": [{"type": "text", "text": "hello"}],
+        "stop_reason": "end_turn",
+        "usage": {"input_tokens": 9, "output_tokens": 2}
+    }))
+}
+
+fn anthropic_events() -> ResponseTemplate {
+    sse(sse_body(&[
+        json!({"type": "message_start", "message": {"id": "msg_1", "usage": {"input_tokens": 9}}}),
+        json!({"type": "content_block_delta", "delta": {"type": "text_delta", "text": "hel"}}),
+        json!({"type": "content_block_delta", "delta": {"type": "text_delta", "text": "lo"}}),
+        json!({"type": "message_delta", "delta": {"type": "message_delta", "stop_reason": "end_turn"}, "usage": {"output_tokens": 2}}),
+        json!({"type": "message_stop"}),
+    ]))
+}
+
+/// Streaming bodies also match the non-streaming mocks' partial bodies, so
+/// streaming mocks are tried first.
+fn priority(stream: bool) -> u8 {
+    if stream {
+        1
+    } else {
+        5
+    }
+}
+
+/// Mount an OpenAI chat completions mock requiring the contract headers
+/// and body; `stream` selects the streaming body shape.  The `caller`
+/// also sends its configured organization header.
+async fn mount_openai(
+    server: &MockServer,
+    stream: bool,
+    organization: bool,
+    response: ResponseTemplate,
+) {
+    let mut body = json!({
+        "model": "gpt-4",
+        "messages": [
+            {"role": "system", "content": "Be brief."},
+            {"role": "user", "content": "hi"}
+        ],
+        "max_tokens": 64,
+        "stop": ["END"]
+    });
+    if stream {
+        body["stream"] = json!(true);
+    }
+    let mut mock = Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(header("authorization", "Bearer sk-contract"))
+        .and(header("content-type", "application/json"))
+        .and(body_partial_json(body));
+    if organization {
+        mock = mock.and(header("openai-organization", "org-1"));
+    }
+    mock.respond_with(response)
+        .with_priority(priority(stream))
+        .expect(1)
+        .mount(server)
+        .await;
+}
+
+/// Mount an Anthropic messages mock requiring the contract headers and
+/// body: the system prompt lifted out of `messages`, `stop_sequences`.
+async fn mount_anthropic(server: &MockServer, stream: bool, response: ResponseTemplate) {
+    let mut body = json!({
+        "model": "claude-3-5-sonnet",
+        "system": "Be brief.",
+        "messages": [{"role": "user", "content": "hi"}],
+        "max_tokens": 64,
+        "stop_sequences": ["END"]
+    });
+    if stream {
+        body["stream"] = json!(true);
+    }
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .and(header("x-api-key", "sk-contract"))
+        .and(header("anthropic-version", "2023-06-01"))
+        .and(header("content-type", "application/json"))
+        .and(body_partial_json(body))
+        .respond_with(response)
+        .with_priority(priority(stream))
+        .expect(1)
+        .mount(server)
+        .await;
+}
+
+fn assert_streamed_hello(chunks: &[Result<ChatChunk, HyperInferError>]) {
+    let text: String = chunks
+        .iter()
+        .map(|chunk| chunk.as_ref().unwrap().delta.clone())
+        .collect();
+    assert_eq!(text, "hello");
+    let usage = chunks
+        .iter()
+        .filter_map(|chunk| chunk.as_ref().unwrap().usage.clone())
+        .next_back()
+        .expect("final usage chunk");
+    assert_eq!((usage.input_tokens, usage.output_tokens), (9, 2));
+}
+
+#[tokio::test]
+async fn test_http_caller_openai_chat() {
+    let server = MockServer::start().await;
+    mount_openai(&server, false, true, openai_completion()).await;
+
+    let response = caller(&server)
+        .call_chat(&Provider::OpenAI, "gpt-4", API_KEY, &request("gpt-4"))
+        .await
+        .unwrap();
+    assert_eq!(response.id, "chatcmpl-1");
+    assert_eq!(response.choices[0].message.content, "hello");
+    assert_eq!(response.usage.input_tokens, 9);
+    assert_eq!(response.usage.output_tokens, 2);
+}
+
+#[tokio::test]
+async fn test_http_caller_openai_stream() {
+    let server = MockServer::start().await;
+    mount_openai(&server, true, true, openai_chunks()).await;
+
+    let chunks = collect(caller(&server).call_stream(
+        &Provider::OpenAI,
+        "gpt-4",
+        API_KEY,
+        &request("gpt-4"),
+    ))
+    .await;
+    assert_streamed_hello(&chunks);
+}
+
+#[tokio::test]
+async fn test_http_caller_anthropic_chat() {
+    let server = MockServer::start().await;
+    mount_anthropic(&server, false, anthropic_message()).await;
+
+    let response = caller(&server)
+        .call_chat(
+            &Provider::Anthropic,
+            "claude-3-5-sonnet",
+            API_KEY,
+            &request("claude-3-5-sonnet"),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.id, "msg_1");
+    assert_eq!(response.choices[0].message.content, "hello");
+    assert_eq!(response.usage.input_tokens, 9);
+}
+
+#[tokio::test]
+async fn test_http_caller_anthropic_stream() {
+    let server = MockServer::start().await;
+    mount_anthropic(&server, true, anthropic_events()).await;
+
+    let chunks = collect(caller(&server).call_stream(
+        &Provider::Anthropic,
+        "claude-3-5-sonnet",
+        API_KEY,
+        &request("claude-3-5-sonnet"),
+    ))
+    .await;
+    assert_streamed_hello(&chunks);
+}
+
+#[tokio::test]
+async fn test_registry_openai_provider() {
+    let server = MockServer::start().await;
+    mount_openai(&server, false, false, openai_completion()).await;
+    mount_openai(&server, true, false, openai_chunks()).await;
+
+    let provider = OpenAiProvider::new().unwrap().with_base_url(&server.uri());
+    let response = provider.chat(&request("gpt-4"), API_KEY).await.unwrap();
+    assert_eq!(response.choices[0].message.content, "hello");
+    let chunks = collect(provider.stream(&request("gpt-4"), API_KEY)).await;
+    assert_streamed_hello(&chunks);
+}
+
+#[tokio::test]
+async fn test_registry_anthropic_provider() {
+    let server = MockServer::start().await;
+    mount_anthropic(&server, false, anthropic_message()).await;
+    mount_anthropic(&server, true, anthropic_events()).await;
+
+    let provider = AnthropicProvider::new()
+        .unwrap()
+        .with_base_url(&server.uri());
+    let request = request("claude-3-5-sonnet");
+    let response = provider.chat(&request, API_KEY).await.unwrap();
+    assert_eq!(response.choices[0].message.content, "hello");
+    let chunks = collect(provider.stream(&request, API_KEY)).await;
+    assert_streamed_hello(&chunks);
+}
+
+#[tokio::test]
+async fn test_error_statuses_map_to_api_errors() {
+    let server = MockServer::start().await;
+    Mock::given(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(429).set_body_string("slow down"))
+        .mount(&server)
+        .await;
+    Mock::given(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(529).set_body_string("overloaded"))
+        .mount(&server)
+        .await;
+    let caller = caller(&server);
+    let openai_provider = OpenAiProvider::new().unwrap().with_base_url(&server.uri());
+    let anthropic_provider = AnthropicProvider::new()
+        .unwrap()
+        .with_base_url(&server.uri());
+    let openai = request("gpt-4");
+    let anthropic = request("claude-3-5-sonnet");
+
+    let mut errors = vec![
+        caller
+            .call_chat(&Provider::OpenAI, "gpt-4", API_KEY, &openai)
+            .await
+            .unwrap_err(),
+        caller
+            .call_chat(
+                &Provider::Anthropic,
+                "claude-3-5-sonnet",
+                API_KEY,
+                &anthropic,
+            )
+            .await
+            .unwrap_err(),
+        openai_provider.chat(&openai, API_KEY).await.unwrap_err(),
+        anthropic_provider
+            .chat(&anthropic, API_KEY)
+            .await
+            .unwrap_err(),
+    ];
+    for stream in [
+        caller.call_stream(&Provider::OpenAI, "gpt-4", API_KEY, &openai),
+        caller.call_stream(
+            &Provider::Anthropic,
+            "claude-3-5-sonnet",
+            API_KEY,
+            &anthropic,
+        ),
+        openai_provider.stream(&openai, API_KEY),
+        anthropic_provider.stream(&anthropic, API_KEY),
+    ] {
+        let chunks = collect(stream).await;
+        assert_eq!(chunks.len(), 1);
+        errors.push(chunks.into_iter().next().unwrap().unwrap_err());
+    }
+
+    let statuses: Vec<(u16, ProviderErrorKind, String)> = errors
+        .into_iter()
+        .map(|error| match error {
+            HyperInferError::ApiError {
+                status,
+                message,
+                kind,
+            } => (status, kind, message),
+            other => panic!("expected an API error, got {:?}", other),
+        })
+        .collect();
+    let openai_error = (429, ProviderErrorKind::RateLimit, "slow down".to_string());
+    let anthropic_error = (529, ProviderErrorKind::Overloaded, "overloaded".to_string());
+    assert_eq!(
+        statuses,
+        vec![
+            openai_error.clone(),
+            anthropic_error.clone(),
+            openai_error.clone(),
+            anthropic_error.clone(),
+            openai_error.clone(),
+            anthropic_error.clone(),
+            openai_error,
+            anthropic_error,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_error_bodies_are_classified() {
+    let server = MockServer::start().await;
+    Mock::given(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "error": {
+                "message": "This model's maximum context length is 8192 tokens.",
+                "type": "invalid_request_error",
+                "code": "context_length_exceeded"
+            }
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "type": "error",
+            "error": {"type": "authentication_error", "message": "invalid x-api-key"}
+        })))
+        .mount(&server)
+        .await;
+    let caller = caller(&server);
+
+    for (provider, model, kind) in [
+        (Provider::OpenAI, "gpt-4", ProviderErrorKind::ContextLength),
+        (
+            Provider::Anthropic,
+            "claude-3-5-sonnet",
+            ProviderErrorKind::Auth,
+        ),
+    ] {
+        let err = caller
+            .call_chat(&provider, model, API_KEY, &request(model))
+            .await
+            .unwrap_err();
+        assert_eq!(err.provider_error_kind(), Some(kind), "{:?}", err);
+    }
+}
+
+#[tokio::test]
+async fn test_stream_error_events_are_surfaced() {
+    let server = MockServer::start().await;
+    Mock::given(path("/v1/chat/completions"))
+        .respond_with(sse(sse_body(&[json!({
+            "error": {"message": "context too long", "type": "invalid_request_error"}
+        })])))
+        .mount(&server)
+        .await;
+    Mock::given(path("/v1/messages"))
+        .respond_with(sse(sse_body(&[json!({
+            "type": "error",
+            "error": {"type": "overloaded_error", "message": "Overloaded"}
+        })])))
+        .mount(&server)
+        .await;
+    let caller = caller(&server);
+
+    for (provider, model, message) in [
+        (Provider::OpenAI, "gpt-4", "context too long"),
+        (Provider::Anthropic, "claude-3-5-sonnet", "Overloaded"),
+    ] {
+        let chunks = collect(caller.call_stream(&provider, model, API_KEY, &request(model))).await;
+        assert!(
+            matches!(&chunks[..], [Err(HyperInferError::StreamParse { message: m, .. })] if m == message),
+            "{:?}",
+            chunks
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_oversized_responses_are_rejected() {
+    let server = MockServer::start().await;
+    mount_openai(&server, false, false, openai_completion()).await;
+    mount_anthropic(&server, true, anthropic_events()).await;
+    let caller = caller(&server).with_response_limit(64, OversizedResponse::Reject);
+
+    let err = caller
+        .call_chat(&Provider::OpenAI, "gpt-4", API_KEY, &request("gpt-4"))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        HyperInferError::ResponseTooLarge { limit: 64 }
+    ));
+
+    let model = "claude-3-5-sonnet";
+    let chunks =
+        collect(caller.call_stream(&Provider::Anthropic, model, API_KEY, &request(model))).await;
+    assert!(
+        matches!(
+            chunks.last(),
+            Some(Err(HyperInferError::ResponseTooLarge { limit: 64 }))
+        ),
+        "{:?}",
+        chunks
+    );
+}
+
+#[tokio::test]
+async fn test_oversized_streams_are_truncated() {
+    let server = MockServer::start().await;
+    mount_openai(&server, true, false, openai_chunks()).await;
+    let first = sse_body(&[
+        json!({"id": "chatcmpl-1", "model": "gpt-4", "choices": [{"delta": {"content": "hel"}, "finish_reason": null}]}),
+    ]);
+    // Room for the first event and part of the second.
+    let caller = caller(&server).with_response_limit(first.len() + 10, OversizedResponse::Truncate);
+
+    let chunks =
+        collect(caller.call_stream(&Provider::OpenAI, "gpt-4", API_KEY, &request("gpt-4"))).await;
+    let chunks: Vec<ChatChunk> = chunks.into_iter().map(Result::unwrap).collect();
+    assert_eq!(chunks.len(), 2, "{:?}", chunks);
+    assert_eq!(chunks[0].delta, "hel");
+    assert_eq!(chunks[1].id, "chatcmpl-1");
+    assert_eq!(chunks[1].finish_reason.as_deref(), Some("length"));
+}
+
+#[tokio::test]
+async fn test_oversized_error_bodies_are_cut_short() {
+    let server = MockServer::start().await;
+    Mock::given(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(500).set_body_string("x".repeat(1024)))
+        .mount(&server)
+        .await;
+    let caller = caller(&server).with_response_limit(16, OversizedResponse::Reject);
+
+    let err = caller
+        .call_chat(&Provider::OpenAI, "gpt-4", API_KEY, &request("gpt-4"))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&err, HyperInferError::ApiError { status: 500, message, .. } if message.len() == 16),
+        "{:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn test_regions_fail_over_to_healthy_endpoint() {
+    let down = MockServer::start().await;
+    Mock::given(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(503).set_body_string("unavailable"))
+        .expect(2)
+        .mount(&down)
+        .await;
+    let up = MockServer::start().await;
+    mount_openai(&up, false, false, openai_completion()).await;
+    mount_openai(&up, true, false, openai_chunks()).await;
+
+    let endpoint = |region: &str, server: &MockServer| RegionalEndpoint {
+        region: region.to_string(),
+        base_url: server.uri(),
+        tags: Vec::new(),
+    };
+    let regions = HashMap::from([(
+        "openai".to_string(),
+        ProviderRegions {
+            endpoints: vec![endpoint("eastus", &down), endpoint("westus", &up)],
+            selection: RegionSelection::Priority,
+            failure_threshold: 3,
+            cooldown_secs: 60,
+        },
+    )]);
+    let caller = HttpCaller::new().unwrap().with_regions(&regions);
+
+    let response = caller
+        .call_chat(&Provider::OpenAI, "gpt-4", API_KEY, &request("gpt-4"))
+        .await
+        .unwrap();
+    assert_eq!(response.choices[0].message.content, "hello");
+    let chunks =
+        collect(caller.call_stream(&Provider::OpenAI, "gpt-4", API_KEY, &request("gpt-4"))).await;
+    assert_streamed_hello(&chunks);
+}
+
+#[tokio::test]
+async fn test_client_warms_up_the_pool_chat_uses() {
+    let server = MockServer::start().await;
+    mount_openai(&server, false, false, openai_completion()).await;
+    let transport = TransportConfig {
+        pool_max_idle_per_host: 4,
+        ..Default::default()
+    };
+    let client = HyperInferClient::standalone(client_config(&server))
+        .unwrap()
+        .with_transport_config(&transport)
+        .unwrap();
+
+    assert_eq!(client.warm_up().await, 2);
+    let response = client.chat("team-key", request("gpt-4")).await.unwrap();
+    assert_eq!(response.choices[0].message.content, "hello");
+    let received = server.received_requests().await.unwrap();
+    let probes = received
+        .iter()
+        .filter(|request| request.method.as_str() == "HEAD")
+        .count();
+    assert_eq!(probes, 2);
+}
+
+#[tokio::test]
+async fn test_client_sends_provider_traffic_through_the_proxy() {
+    // The mock plays the proxy: plain-HTTP requests reach it in absolute
+    // form, so the provider host below never has to resolve.
+    let proxy = MockServer::start().await;
+    mount_openai(&proxy, false, false, openai_completion()).await;
+    let mut config = client_config(&proxy);
+    config.provider_base_urls.insert(
+        "openai".to_string(),
+        "http://openai.upstream.invalid".to_string(),
+    );
+    let transport = TransportConfig {
+        egress: EgressConfig {
+            proxy_url: Some(proxy.uri()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let client = HyperInferClient::standalone(config)
+        .unwrap()
+        .with_transport_config(&transport)
+        .unwrap();
+
+    let response = client.chat("team-key", request("gpt-4")).await.unwrap();
+    assert_eq!(response.choices[0].message.content, "hello");
+    let received = proxy.received_requests().await.unwrap();
+    assert_eq!(received[0].url.host_str(), Some("openai.upstream.invalid"));
+}
+
+#[tokio::test]
+async fn test_client_chat_enforces_the_response_limit() {
+    let server = MockServer::start().await;
+    mount_openai(&server, false, false, openai_completion()).await;
+    mount_anthropic(&server, true, anthropic_events()).await;
+    let transport = TransportConfig {
+        max_response_bytes: 64,
+        ..Default::default()
+    };
+    let client = HyperInferClient::standalone(client_config(&server))
+        .unwrap()
+        .with_transport_config(&transport)
+        .unwrap();
+
+    let err = client.chat("team-key", request("gpt-4")).await.unwrap_err();
+    assert!(
+        matches!(err, HyperInferError::ResponseTooLarge { limit: 64 }),
+        "{:?}",
+        err
+    );
+    let model = "claude-3-5-sonnet";
+    let chunks: Vec<_> = client
+        .chat_stream("team-key", request(model))
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert!(
+        matches!(
+            chunks.last(),
+            Some(Err(HyperInferError::ResponseTooLarge { limit: 64 }))
+        ),
+        "{:?}",
+        chunks
+    );
+}
+
+#[cfg(feature = "fault-injection")]
+#[tokio::test]
+async fn test_client_chat_sees_injected_faults() {
+    use hyperinfer_client::fault_injection::{FaultConfig, FaultInjector};
+
+    let server = MockServer::start().await;
+    mount_anthropic(&server, true, anthropic_events()).await;
+    let faults = FaultInjector::new(HashMap::from([(
+        "openai".to_string(),
+        FaultConfig {
+            server_error_probability: 1.0,
+            ..Default::default()
+        },
+    )]))
+    .unwrap();
+    let transport = TransportConfig {
+        faults: Some(faults),
+        ..Default::default()
+    };
+    let client = HyperInferClient::standalone(client_config(&server))
+        .unwrap()
+        .with_transport_config(&transport)
+        .unwrap();
+
+    let err = client.chat("team-key", request("gpt-4")).await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            HyperInferError::ApiError {
+                status: 500 | 502 | 503,
+                ..
+            }
+        ),
+        "{:?}",
+        err
+    );
+    // Providers without faults configured are called as usual.
+    let model = "claude-3-5-sonnet";
+    let chunks: Vec<_> = client
+        .chat_stream("team-key", request(model))
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert_streamed_hello(&chunks);
+}
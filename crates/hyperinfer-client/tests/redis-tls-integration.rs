@@ -0,0 +1,172 @@
+//! Every Redis component over `rediss://` with a private CA, mutual TLS
+//! and an ACL user, against a Redis that only accepts TLS.
+
+use hyperinfer_client::Telemetry;
+use hyperinfer_core::redis::ConfigManager;
+use hyperinfer_core::{Config, RateLimiter, RedisHandle, RedisOptions, TelemetryConsumer};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use testcontainers::{
+    core::IntoContainerPort, runners::AsyncRunner, CopyTargetOptions, GenericImage, ImageExt,
+};
+use testcontainers_modules::redis::REDIS_PORT;
+use tokio::sync::RwLock;
+
+/// The control plane's test certificates: a CA, a server certificate for
+/// localhost and 127.0.0.1, and a client certificate.
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../hyperinfer-server/tests/fixtures/tls"
+    ))
+    .join(name)
+}
+
+async fn setup_tls_redis() -> (RedisOptions, testcontainers::ContainerAsync<GenericImage>) {
+    let copy = |name: &str| {
+        (
+            CopyTargetOptions::new(format!("/tls/{}", name)).with_mode(0o644),
+            fixture(name),
+        )
+    };
+    let (ca, ca_src) = copy("ca.pem");
+    let (cert, cert_src) = copy("server.pem");
+    let (key, key_src) = copy("server.key");
+    let redis = GenericImage::new("redis", "7.2")
+        .with_exposed_port(REDIS_PORT.tcp())
+        .with_wait_for(testcontainers::core::WaitFor::message_on_stdout(
+            "Ready to accept connections",
+        ))
+        .with_copy_to(ca, ca_src)
+        .with_copy_to(cert, cert_src)
+        .with_copy_to(key, key_src)
+        .with_cmd([
+            "redis-server",
+            "--port",
+            "0",
+            "--tls-port",
+            "6379",
+            "--tls-cert-file",
+            "/tls/server.pem",
+            "--tls-key-file",
+            "/tls/server.key",
+            "--tls-ca-cert-file",
+            "/tls/ca.pem",
+            "--user",
+            "default",
+            "off",
+            "--user",
+            "app",
+            "on",
+            ">secret",
+            "~*",
+            "&*",
+            "+@all",
+        ])
+        .start()
+        .await
+        .expect("Failed to start Redis container");
+    let port = redis.get_host_port_ipv4(REDIS_PORT).await.unwrap();
+    let options = RedisOptions::new(format!("rediss://127.0.0.1:{}", port))
+        .with_credentials(Some("app".to_string()), "secret".to_string())
+        .with_ca_cert(fixture("ca.pem"))
+        .with_client_cert(fixture("client.pem"), fixture("client.key"));
+    (options, redis)
+}
+
+#[tokio::test]
+async fn test_components_over_tls_with_acl_user() {
+    let (options, _container) = setup_tls_redis().await;
+    let redis = RedisHandle::connect_with(&options).await.unwrap();
+
+    let manager = ConfigManager::with_redis(&redis);
+    let version = manager
+        .publish_config_update(&Config::default())
+        .await
+        .unwrap();
+    assert!(version > 0);
+    assert!(manager.fetch_config().await.is_ok());
+
+    let limiter = RateLimiter::with_redis(&redis);
+    limiter.preload_scripts().await;
+    let (allowed, _) = limiter.check_rpm("tls-key", 10).await.unwrap();
+    assert!(allowed);
+
+    let telemetry = Telemetry::with_redis(&redis);
+    telemetry.record("tls-key", "gpt-4", 12).await.unwrap();
+    let consumer = TelemetryConsumer::with_redis(&redis);
+    let mut records = Vec::new();
+    for _ in 0..50 {
+        records = consumer.read_single_batch().await.unwrap();
+        if !records.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].model, "gpt-4");
+    assert!(telemetry.health().connected);
+}
+
+#[tokio::test]
+async fn test_tls_redis_rejects_wrong_credentials_and_plain_connections() {
+    let (options, _container) = setup_tls_redis().await;
+
+    let wrong_password = options
+        .clone()
+        .with_credentials(Some("app".to_string()), "wrong".to_string());
+    assert!(RedisHandle::connect_with(&wrong_password).await.is_err());
+
+    let untrusted = RedisOptions {
+        ca_cert_path: None,
+        ..options.clone()
+    };
+    assert!(RedisHandle::connect_with(&untrusted).await.is_err());
+
+    let plain = options.url.replacen("rediss://", "redis://", 1);
+    assert!(RedisHandle::connect(&plain).await.is_err());
+}
+
+#[tokio::test]
+async fn test_config_subscription_resubscribes_with_credentials() {
+    let (options, _container) = setup_tls_redis().await;
+    let redis = RedisHandle::connect_with(&options).await.unwrap();
+    let manager = ConfigManager::with_redis(&redis);
+    let config = Arc::new(RwLock::new(Config::default()));
+    let _subscription = manager
+        .subscribe_to_config_updates(config.clone())
+        .await
+        .unwrap();
+
+    async fn published(manager: &ConfigManager, config: &RwLock<Config>) -> u64 {
+        // The subscriber may still be (re)connecting, so keep publishing
+        // until an update lands.
+        for _ in 0..50 {
+            let version = manager
+                .publish_config_update(&Config::default())
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            if config.read().await.version == version {
+                return version;
+            }
+        }
+        panic!("config update was not received");
+    }
+    let first = published(&manager, &config).await;
+
+    // Drop the subscriber's connection; it must reconnect as the ACL user
+    // over TLS, not with a bare address.
+    let mut conn = redis.connection();
+    let killed: u64 = redis::cmd("CLIENT")
+        .arg("KILL")
+        .arg("TYPE")
+        .arg("pubsub")
+        .query_async(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(killed, 1);
+
+    assert!(published(&manager, &config).await > first);
+}
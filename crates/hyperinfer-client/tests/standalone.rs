@@ -0,0 +1,472 @@
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use hyperinfer_client::{HyperInferClient, ProviderTransport};
+use hyperinfer_core::types::{ChatMessage, Choice, MessageRole, Quota, Usage};
+use hyperinfer_core::{ChatChunk, ChatRequest, ChatResponse, Config, HyperInferError, Provider};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Answers every call with a canned response.
+struct CannedTransport;
+
+#[async_trait]
+impl ProviderTransport for CannedTransport {
+    async fn call_chat(
+        &self,
+        _provider: &Provider,
+        model: &str,
+        _api_key: &str,
+        _request: &ChatRequest,
+    ) -> Result<ChatResponse, HyperInferError> {
+        Ok(ChatResponse {
+            id: "canned-1".to_string(),
+            model: model.to_string(),
+            choices: vec![Choice {
+                index: 0,
+                message: ChatMessage {
+                    role: MessageRole::Assistant,
+                    content: "hello without redis".to_string(),
+                },
+                finish_reason: Some("stop".to_string()),
+                thinking: None,
+            }],
+            usage: Usage {
+                input_tokens: 3,
+                output_tokens: 4,
+                thinking_tokens: 0,
+            },
+            metadata: HashMap::new(),
+            dry_run: None,
+        })
+    }
+
+    fn call_stream(
+        &self,
+        _provider: &Provider,
+        model: &str,
+        _api_key: &str,
+        _request: &ChatRequest,
+    ) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send + 'static>> {
+        Box::pin(futures::stream::iter(vec![Ok(ChatChunk {
+            model: model.to_string(),
+            delta: "hello".to_string(),
+            finish_reason: Some("stop".to_string()),
+            ..Default::default()
+        })]))
+    }
+}
+
+fn config() -> Config {
+    Config {
+        api_keys: HashMap::from([("openai".to_string(), "sk-fake".to_string())]),
+        ..Default::default()
+    }
+}
+
+fn request() -> ChatRequest {
+    ChatRequest {
+        model: "gpt-4".to_string(),
+        messages: vec![ChatMessage {
+            role: MessageRole::User,
+            content: "hi".to_string(),
+        }],
+        ..Default::default()
+    }
+}
+
+/// `config` with the virtual key `vk-team-1`, belonging to `team-1`.
+fn with_team_key(mut config: Config) -> Config {
+    use hyperinfer_client::KeyPolicies;
+    use hyperinfer_core::VirtualKey;
+
+    let key_hash = KeyPolicies::hash_key("vk-team-1");
+    config.virtual_keys.insert(
+        key_hash.clone(),
+        VirtualKey {
+            id: "vk-1".to_string(),
+            key_hash,
+            team_id: "team-1".to_string(),
+            user_id: None,
+            name: None,
+            tags: Vec::new(),
+            allowed_models: Vec::new(),
+            budget_cents: None,
+            is_active: true,
+            expires_at: None,
+        },
+    );
+    config
+}
+
+#[tokio::test]
+async fn test_standalone_client_routes_without_redis() {
+    let client = HyperInferClient::standalone(config())
+        .unwrap()
+        .with_transport(Arc::new(CannedTransport));
+
+    let response = client.chat("team-key", request()).await.unwrap();
+    assert_eq!(response.choices[0].message.content, "hello without redis");
+
+    let chunks: Vec<_> = client
+        .chat_stream("team-key", request())
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert_eq!(chunks[0].as_ref().unwrap().delta, "hello");
+}
+
+#[tokio::test]
+async fn test_standalone_client_enforces_quotas_in_process() {
+    let mut config = config();
+    config.quotas.insert(
+        "team-key".to_string(),
+        Quota {
+            max_requests_per_minute: Some(1),
+            max_tokens_per_minute: None,
+            budget_cents: None,
+            rpm_window: Default::default(),
+        },
+    );
+    let client = HyperInferClient::standalone(config)
+        .unwrap()
+        .with_transport(Arc::new(CannedTransport));
+
+    client.chat("team-key", request()).await.unwrap();
+    let err = client.chat("team-key", request()).await.unwrap_err();
+    assert!(matches!(err, HyperInferError::RateLimit { .. }), "{err:?}");
+}
+
+#[test]
+fn test_resolve_and_limits_read_the_local_config() {
+    let mut config = with_team_key(config());
+    config
+        .model_aliases
+        .insert("fast".to_string(), "gpt-4o-mini".to_string());
+    config.quotas.insert(
+        "vk-team-1".to_string(),
+        Quota {
+            max_requests_per_minute: Some(60),
+            max_tokens_per_minute: None,
+            budget_cents: None,
+            rpm_window: Default::default(),
+        },
+    );
+    let client = HyperInferClient::standalone(config).unwrap();
+
+    let route = client.resolve("fast").unwrap();
+    assert_eq!(route.model, "gpt-4o-mini");
+    assert_eq!(route.provider, Provider::OpenAI);
+    assert!(client.resolve("no-such-model").is_err());
+
+    let limits = client.limits("vk-team-1").unwrap();
+    assert_eq!(limits.team_id.as_deref(), Some("team-1"));
+    assert_eq!(limits.quota.unwrap().max_requests_per_minute, Some(60));
+    assert!(!limits.revoked);
+    assert!(client.limits("team-key").unwrap().quota.is_none());
+}
+
+/// Records the events it hears about.
+#[derive(Default)]
+struct RecordingHooks(std::sync::Mutex<Vec<String>>);
+
+#[async_trait]
+impl hyperinfer_client::ChatHooks for RecordingHooks {
+    async fn on_request(&self, event: &hyperinfer_client::RequestEvent) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("request {}", event.request.model));
+    }
+
+    async fn on_response(&self, event: &hyperinfer_client::ResponseEvent) {
+        self.0.lock().unwrap().push(format!(
+            "response {} {}",
+            event.response.usage.output_tokens, event.request_id
+        ));
+    }
+
+    async fn on_error(&self, event: &hyperinfer_client::ErrorEvent) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(format!("error {}", event.request_id));
+    }
+}
+
+#[tokio::test]
+async fn test_hooks_hear_about_every_chat_call() {
+    let hooks = Arc::new(RecordingHooks::default());
+    let client = HyperInferClient::standalone(config())
+        .unwrap()
+        .with_transport(Arc::new(CannedTransport))
+        .with_hooks(hooks.clone());
+
+    client.chat("team-key", request()).await.unwrap();
+    let unknown = ChatRequest {
+        model: "no-such-model".to_string(),
+        ..request()
+    };
+    client.chat("team-key", unknown).await.unwrap_err();
+
+    let events = hooks.0.lock().unwrap().clone();
+    assert_eq!(events.len(), 4, "{events:?}");
+    assert_eq!(events[0], "request gpt-4");
+    assert!(events[1].starts_with("response 4 "));
+    assert_eq!(events[2], "request no-such-model");
+    assert!(events[3].starts_with("error "));
+    // Each call gets its own id.
+    assert_ne!(events[1].rsplit(' ').next(), events[3].rsplit(' ').next());
+}
+
+#[tokio::test]
+async fn test_shutdown_waits_for_open_streams_and_refuses_new_requests() {
+    let client = HyperInferClient::standalone(config())
+        .unwrap()
+        .with_transport(Arc::new(CannedTransport));
+    let stream = client.chat_stream("team-key", request()).await.unwrap();
+
+    // The open stream is still running when the deadline passes.
+    let report = client.shutdown(Duration::from_millis(50)).await;
+    assert_eq!(report.abandoned_requests, 1);
+    assert!(client.is_shutting_down());
+    let err = client.chat("team-key", request()).await.unwrap_err();
+    assert!(matches!(err, HyperInferError::ShuttingDown), "{err:?}");
+
+    let chunks: Vec<_> = stream.collect().await;
+    assert_eq!(chunks.len(), 1);
+    let report = client.shutdown(Duration::from_secs(5)).await;
+    assert!(report.is_clean(), "{report:?}");
+}
+
+#[tokio::test]
+async fn test_team_features_switch_off_streaming() {
+    let mut config = config();
+    config.team_features.insert(
+        "team-1".to_string(),
+        HashMap::from([("enable_streaming".to_string(), false)]),
+    );
+    let client = HyperInferClient::standalone(with_team_key(config))
+        .unwrap()
+        .with_transport(Arc::new(CannedTransport));
+
+    let err = client
+        .chat_stream("vk-team-1", request())
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(err, HyperInferError::Forbidden(_)), "{err:?}");
+    // Other teams, and plain chat for this one, are unaffected.
+    assert!(client.chat_stream("team-key", request()).await.is_ok());
+    assert!(client.chat("vk-team-1", request()).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_routing_override_pins_the_provider_unless_switched_off() {
+    use hyperinfer_core::RoutingOverride;
+
+    let mut config = config();
+    config.team_features.insert(
+        "team-1".to_string(),
+        HashMap::from([("enable_routing_overrides".to_string(), false)]),
+    );
+    let client = HyperInferClient::standalone(with_team_key(config))
+        .unwrap()
+        .with_transport(Arc::new(CannedTransport));
+    let pinned = ChatRequest {
+        routing: Some(RoutingOverride {
+            provider_override: Some(Provider::Anthropic),
+            ..Default::default()
+        }),
+        ..request()
+    };
+
+    // gpt-4 would go to OpenAI, which has a key; the override sends it to
+    // Anthropic, which has none.
+    let err = client.chat("team-key", pinned.clone()).await.unwrap_err();
+    assert!(err.to_string().contains("Anthropic"), "{err}");
+    let err = client.chat("vk-team-1", pinned).await.unwrap_err();
+    assert!(matches!(err, HyperInferError::Forbidden(_)), "{err:?}");
+}
+
+#[tokio::test]
+async fn test_team_param_clamps_lower_the_output_budget() {
+    use hyperinfer_core::ParamClamps;
+
+    let mut config = config();
+    config.team_param_clamps.insert(
+        "team-1".to_string(),
+        ParamClamps {
+            max_temperature: Some(1.0),
+            max_tokens: Some(256),
+        },
+    );
+    let client = HyperInferClient::standalone(with_team_key(config))
+        .unwrap()
+        .with_transport(Arc::new(CannedTransport));
+    let request = ChatRequest {
+        temperature: Some(1.5),
+        max_tokens: Some(4096),
+        ..request()
+    };
+
+    let report = client.dry_run("vk-team-1", &request).await.unwrap();
+    assert_eq!(report.max_tokens, Some(256));
+    let report = client.dry_run("team-key", &request).await.unwrap();
+    assert_eq!(report.max_tokens, Some(4096));
+    assert!(client.chat("vk-team-1", request).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_team_output_transforms_clean_up_responses() {
+    use hyperinfer_core::OutputTransform;
+
+    let mut config = config();
+    config.team_output_transforms.insert(
+        "team-1".to_string(),
+        vec![
+            OutputTransform::StripStopSequences { sequences: vec![] },
+            OutputTransform::TrimWhitespace,
+        ],
+    );
+    let client = HyperInferClient::standalone(with_team_key(config))
+        .unwrap()
+        .with_transport(Arc::new(CannedTransport));
+    let request = ChatRequest {
+        stop: Some(vec!["without".to_string()]),
+        ..request()
+    };
+
+    let response = client.chat("vk-team-1", request.clone()).await.unwrap();
+    assert_eq!(response.choices[0].message.content, "hello");
+    let response = client.chat("team-key", request).await.unwrap();
+    assert_eq!(response.choices[0].message.content, "hello without redis");
+}
+
+#[tokio::test]
+async fn test_run_eval_scores_every_item() {
+    use hyperinfer_core::{EvalCriterion, EvalDataset, EvalItem};
+
+    let item = |text: &str| EvalItem {
+        messages: request().messages,
+        criteria: vec![EvalCriterion::Contains {
+            text: text.to_string(),
+        }],
+    };
+    let dataset = EvalDataset {
+        id: "dataset-1".to_string(),
+        team_id: "team-1".to_string(),
+        name: "smoke".to_string(),
+        items: vec![item("redis"), item("paris")],
+        created_at: chrono::Utc::now(),
+    };
+    let client = HyperInferClient::standalone(config())
+        .unwrap()
+        .with_transport(Arc::new(CannedTransport));
+
+    let run = client.run_eval("team-key", &dataset, "gpt-4").await;
+    assert_eq!(run.dataset_id, "dataset-1");
+    assert_eq!(run.model, "gpt-4");
+    let passed: Vec<bool> = run.results.iter().map(|r| r.passed).collect();
+    assert_eq!(passed, [true, false]);
+    assert_eq!(run.results[0].output_tokens, 4);
+}
+
+#[tokio::test]
+async fn test_experiment_sends_requests_to_its_arms() {
+    use hyperinfer_core::{BanditArm, BanditExperiment};
+
+    let arm = |model: &str, quality| BanditArm {
+        model: model.to_string(),
+        quality: Some(quality),
+        cost_cents: None,
+    };
+    let mut config = config();
+    config.experiments.insert(
+        "fast".to_string(),
+        BanditExperiment {
+            arms: vec![arm("gpt-4o-mini", 0.9), arm("gpt-4o", 0.5)],
+            epsilon: 0.0,
+            cost_weight: 0.0,
+            teams: Vec::new(),
+            dataset_id: None,
+        },
+    );
+    let client = HyperInferClient::standalone(config)
+        .unwrap()
+        .with_transport(Arc::new(CannedTransport));
+    let request = ChatRequest {
+        model: "fast".to_string(),
+        ..request()
+    };
+
+    let response = client.chat("team-key", request).await.unwrap();
+    assert_eq!(response.model, "gpt-4o-mini");
+}
+
+#[tokio::test]
+async fn test_cascade_escalates_when_the_cheap_answer_fails_verification() {
+    use hyperinfer_core::{cascade, Cascade, CascadeVerifier};
+
+    let cascade_to = |verifier| Cascade {
+        cheap_model: "gpt-4o-mini".to_string(),
+        expensive_model: "gpt-4o".to_string(),
+        verifiers: vec![verifier],
+    };
+    let mut config = config();
+    config
+        .cascades
+        .insert("json".to_string(), cascade_to(CascadeVerifier::ValidJson));
+    config.cascades.insert(
+        "greeting".to_string(),
+        cascade_to(CascadeVerifier::Matches {
+            pattern: "^hello".to_string(),
+        }),
+    );
+    let client = HyperInferClient::standalone(config)
+        .unwrap()
+        .with_transport(Arc::new(CannedTransport));
+    let to = |model: &str| ChatRequest {
+        model: model.to_string(),
+        ..request()
+    };
+
+    let response = client.chat("team-key", to("json")).await.unwrap();
+    assert_eq!(response.model, "gpt-4o");
+    assert_eq!(response.metadata[cascade::STEP_METADATA_KEY], "escalated");
+
+    let response = client.chat("team-key", to("greeting")).await.unwrap();
+    assert_eq!(response.model, "gpt-4o-mini");
+    assert_eq!(response.metadata[cascade::STEP_METADATA_KEY], "cheap");
+    let saved: f64 = response.metadata[cascade::SAVED_METADATA_KEY]
+        .parse()
+        .unwrap();
+    assert!(saved > 0.0);
+}
+
+#[tokio::test]
+async fn test_dry_run_counts_the_requests_tokens_against_tpm() {
+    let client = HyperInferClient::standalone(config()).unwrap();
+    let mut small = request();
+    small.max_tokens = Some(1_000);
+    let report = client.dry_run("team-key", &small).await.unwrap();
+    assert!(report.rate_limit_allowed);
+
+    // Far more input than the default 100,000 tokens a minute, while a
+    // single request's worth of headroom is still left.
+    let large = ChatRequest {
+        messages: vec![ChatMessage {
+            role: MessageRole::User,
+            content: "lorem ipsum ".repeat(100_000),
+        }],
+        ..small
+    };
+    let report = client.dry_run("team-key", &large).await.unwrap();
+    let cost = report.cost.expect("gpt-4 is priced");
+    let tokens = u64::from(cost.input_tokens) + u64::from(cost.max_output_tokens);
+    assert!(tokens > report.rate_limit.tokens_remaining);
+    assert!(report.rate_limit.requests_remaining > 0);
+    assert!(!report.rate_limit_allowed);
+}
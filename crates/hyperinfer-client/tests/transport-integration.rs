@@ -0,0 +1,504 @@
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use hyperinfer_client::{HyperInferClient, ProviderTransport};
+use hyperinfer_core::types::{ChatMessage, Choice, MessageRole, Usage};
+use hyperinfer_core::{ChatChunk, ChatRequest, ChatResponse, Config, HyperInferError, Provider};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use testcontainers::{core::IntoContainerPort, runners::AsyncRunner, GenericImage};
+use testcontainers_modules::redis::REDIS_PORT;
+
+async fn setup_redis() -> (String, testcontainers::ContainerAsync<GenericImage>) {
+    let redis = GenericImage::new("redis", "7.2")
+        .with_exposed_port(REDIS_PORT.tcp())
+        .with_wait_for(testcontainers::core::WaitFor::message_on_stdout(
+            "Ready to accept connections",
+        ))
+        .start()
+        .await
+        .expect("Failed to start Redis container");
+    let port = redis.get_host_port_ipv4(REDIS_PORT).await.unwrap();
+    let redis_url = format!("redis://127.0.0.1:{}", port);
+    (redis_url, redis)
+}
+
+/// Records every call and answers with a canned response.
+#[derive(Default)]
+struct FakeTransport {
+    calls: Mutex<Vec<(Provider, String, String)>>,
+}
+
+#[async_trait]
+impl ProviderTransport for FakeTransport {
+    async fn call_chat(
+        &self,
+        provider: &Provider,
+        model: &str,
+        api_key: &str,
+        _request: &ChatRequest,
+    ) -> Result<ChatResponse, HyperInferError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push((provider.clone(), model.to_string(), api_key.to_string()));
+        Ok(ChatResponse {
+            id: "fake-1".to_string(),
+            model: model.to_string(),
+            choices: vec![Choice {
+                index: 0,
+                message: ChatMessage {
+                    role: MessageRole::Assistant,
+                    content: "hello from fake".to_string(),
+                },
+                finish_reason: Some("stop".to_string()),
+                thinking: None,
+            }],
+            usage: Usage {
+                input_tokens: 3,
+                output_tokens: 4,
+                thinking_tokens: 0,
+            },
+            metadata: HashMap::new(),
+            dry_run: None,
+        })
+    }
+
+    fn call_stream(
+        &self,
+        _provider: &Provider,
+        model: &str,
+        _api_key: &str,
+        _request: &ChatRequest,
+    ) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send + 'static>> {
+        let chunks = vec![
+            Ok(ChatChunk {
+                model: model.to_string(),
+                delta: "hello".to_string(),
+                ..Default::default()
+            }),
+            Ok(ChatChunk {
+                model: model.to_string(),
+                finish_reason: Some("stop".to_string()),
+                ..Default::default()
+            }),
+        ];
+        Box::pin(futures::stream::iter(chunks))
+    }
+}
+
+fn test_config() -> Config {
+    let mut api_keys = HashMap::new();
+    api_keys.insert("openai".to_string(), "sk-fake".to_string());
+    Config {
+        api_keys,
+        routing_rules: vec![],
+        quotas: HashMap::new(),
+        model_aliases: HashMap::new(),
+        default_provider: None,
+        ..Default::default()
+    }
+}
+
+fn test_request() -> ChatRequest {
+    ChatRequest {
+        model: "gpt-4".to_string(),
+        messages: vec![ChatMessage {
+            role: MessageRole::User,
+            content: "hi".to_string(),
+        }],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_chat_uses_injected_transport() {
+    let (redis_url, _container) = setup_redis().await;
+    let transport = Arc::new(FakeTransport::default());
+    let client = HyperInferClient::new(&redis_url, test_config())
+        .await
+        .unwrap()
+        .with_transport(transport.clone());
+
+    let response = client.chat("team-key", test_request()).await.unwrap();
+    assert_eq!(response.id, "fake-1");
+    assert_eq!(response.choices[0].message.content, "hello from fake");
+
+    let calls = transport.calls.lock().unwrap();
+    assert_eq!(
+        *calls,
+        vec![(Provider::OpenAI, "gpt-4".to_string(), "sk-fake".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn test_chat_stream_uses_injected_transport() {
+    let (redis_url, _container) = setup_redis().await;
+    let client = HyperInferClient::new(&redis_url, test_config())
+        .await
+        .unwrap()
+        .with_transport(Arc::new(FakeTransport::default()));
+
+    let chunks: Vec<_> = client
+        .chat_stream("team-key", test_request())
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(chunks[0].as_ref().unwrap().delta, "hello");
+    assert_eq!(
+        chunks[1].as_ref().unwrap().finish_reason.as_deref(),
+        Some("stop")
+    );
+}
+
+#[tokio::test]
+async fn test_recorded_session_replays_through_client() {
+    use hyperinfer_client::{RecordingTransport, ReplayTransport};
+
+    let (redis_url, _container) = setup_redis().await;
+    let recorder = Arc::new(RecordingTransport::new(Arc::new(FakeTransport::default())));
+    let client = HyperInferClient::new(&redis_url, test_config())
+        .await
+        .unwrap()
+        .with_transport(recorder.clone());
+    let recorded = client.chat("team-key", test_request()).await.unwrap();
+    let _: Vec<_> = client
+        .chat_stream("team-key", test_request())
+        .await
+        .unwrap()
+        .collect()
+        .await;
+
+    let path = std::env::temp_dir().join(format!("cassette-{}.json", uuid::Uuid::new_v4()));
+    recorder.save(&path).unwrap();
+    let cassette = std::fs::read_to_string(&path).unwrap();
+    assert!(!cassette.contains("sk-fake"));
+
+    let client = HyperInferClient::new(&redis_url, test_config())
+        .await
+        .unwrap()
+        .with_transport(Arc::new(ReplayTransport::from_file(&path).unwrap()));
+    std::fs::remove_file(&path).unwrap();
+    let replayed = client.chat("team-key", test_request()).await.unwrap();
+    assert_eq!(replayed.choices, recorded.choices);
+    let chunks: Vec<_> = client
+        .chat_stream("team-key", test_request())
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert_eq!(chunks[0].as_ref().unwrap().delta, "hello");
+}
+
+#[tokio::test]
+async fn test_revoked_key_is_rejected_until_restored() {
+    use hyperinfer_client::KeyPolicies;
+    use hyperinfer_core::{PolicyAction, PolicyUpdate};
+
+    let (redis_url, _container) = setup_redis().await;
+    let transport = Arc::new(FakeTransport::default());
+    let client = HyperInferClient::new(&redis_url, test_config())
+        .await
+        .unwrap()
+        .with_transport(transport.clone());
+
+    let update = |action| PolicyUpdate {
+        key: KeyPolicies::hash_key("team-key"),
+        action,
+        reason: Some("Team budget exhausted".to_string()),
+        rpm_limit: None,
+    };
+
+    client.key_policies().apply(update(PolicyAction::Revoke));
+    let err = client.chat("team-key", test_request()).await.unwrap_err();
+    assert!(matches!(err, HyperInferError::KeySuspended(_)));
+    assert!(transport.calls.lock().unwrap().is_empty());
+
+    client.key_policies().apply(update(PolicyAction::Restore));
+    assert!(client.chat("team-key", test_request()).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_estimate_cost() {
+    let (redis_url, _container) = setup_redis().await;
+    let client = HyperInferClient::new(&redis_url, test_config())
+        .await
+        .unwrap();
+
+    let mut request = test_request();
+    request.max_tokens = Some(1_000);
+    let estimate = client.estimate_cost(&request).await.unwrap();
+    assert_eq!(estimate.model, "gpt-4");
+    assert_eq!(estimate.max_output_tokens, 1_000);
+    assert!(estimate.input_tokens > 0);
+    // gpt-4 output is $60 / 1M tokens: 1000 tokens = 6 cents.
+    assert!((estimate.max_output_cost_cents - 6.0).abs() < 1e-9);
+
+    request.model = "llama-3-70b".to_string();
+    assert!(client.estimate_cost(&request).await.is_err());
+}
+
+#[tokio::test]
+async fn test_dry_run_skips_provider_and_rate_limits() {
+    let (redis_url, _container) = setup_redis().await;
+    let transport = Arc::new(FakeTransport::default());
+    let client = HyperInferClient::new(&redis_url, test_config())
+        .await
+        .unwrap()
+        .with_transport(transport.clone());
+
+    let mut request = test_request();
+    request.max_tokens = Some(1_000);
+    request.dry_run = true;
+    let response = client.chat("team-key", request.clone()).await.unwrap();
+    let report = response.dry_run.expect("dry-run report");
+    assert_eq!(report.model, "gpt-4");
+    assert_eq!(report.provider, "openai");
+    assert_eq!(report.max_tokens, Some(1_000));
+    assert!(report.rate_limit_allowed);
+    let cost = report.cost.expect("gpt-4 is priced");
+    assert!((cost.max_output_cost_cents - 6.0).abs() < 1e-9);
+    assert!(response.choices.is_empty());
+    assert!(transport.calls.lock().unwrap().is_empty());
+
+    // Dry runs leave the key's limits untouched.
+    let again = client.dry_run("team-key", &request).await.unwrap();
+    assert_eq!(
+        again.rate_limit.requests_used,
+        report.rate_limit.requests_used
+    );
+
+    request.model = "llama-3-70b".to_string();
+    assert!(client.chat("team-key", request.clone()).await.is_err());
+    request.model = "gpt-4".to_string();
+    assert!(client.chat_stream("team-key", request).await.is_err());
+}
+
+#[tokio::test]
+async fn test_virtual_key_restricts_models() {
+    use hyperinfer_client::KeyPolicies;
+    use hyperinfer_core::VirtualKey;
+
+    let (redis_url, _container) = setup_redis().await;
+    let transport = Arc::new(FakeTransport::default());
+    let mut config = test_config();
+    let key_hash = KeyPolicies::hash_key("vk-restricted");
+    config.virtual_keys.insert(
+        key_hash.clone(),
+        VirtualKey {
+            id: "vk-1".to_string(),
+            key_hash,
+            team_id: "team-1".to_string(),
+            user_id: None,
+            name: Some("restricted".to_string()),
+            tags: vec!["batch".to_string()],
+            allowed_models: vec!["gpt-3.5-turbo".to_string()],
+            budget_cents: None,
+            is_active: true,
+            expires_at: None,
+        },
+    );
+    let client = HyperInferClient::new(&redis_url, config)
+        .await
+        .unwrap()
+        .with_transport(transport.clone());
+
+    let identity = client.resolve_key("vk-restricted").await.unwrap().unwrap();
+    assert_eq!(identity.team_id, "team-1");
+    assert!(client.resolve_key("raw-key").await.unwrap().is_none());
+
+    let err = client
+        .chat("vk-restricted", test_request())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, HyperInferError::Forbidden(_)));
+    assert!(transport.calls.lock().unwrap().is_empty());
+
+    // Keys the control plane does not know about are served as before.
+    assert!(client.chat("raw-key", test_request()).await.is_ok());
+}
+
+/// OpenAI answers after a long delay, Anthropic immediately.
+struct SlowOpenAiTransport;
+
+#[async_trait]
+impl ProviderTransport for SlowOpenAiTransport {
+    async fn call_chat(
+        &self,
+        provider: &Provider,
+        model: &str,
+        api_key: &str,
+        request: &ChatRequest,
+    ) -> Result<ChatResponse, HyperInferError> {
+        if *provider == Provider::OpenAI {
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        }
+        FakeTransport::default()
+            .call_chat(provider, model, api_key, request)
+            .await
+    }
+
+    fn call_stream(
+        &self,
+        provider: &Provider,
+        model: &str,
+        api_key: &str,
+        request: &ChatRequest,
+    ) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send + 'static>> {
+        let stream = FakeTransport::default().call_stream(provider, model, api_key, request);
+        if *provider == Provider::OpenAI {
+            Box::pin(
+                futures::stream::once(tokio::time::sleep(std::time::Duration::from_secs(5)))
+                    .flat_map(move |_| futures::stream::empty())
+                    .chain(stream),
+            )
+        } else {
+            stream
+        }
+    }
+}
+
+fn hedging_config() -> Config {
+    let mut config = test_config();
+    config
+        .api_keys
+        .insert("anthropic".to_string(), "sk-ant-fake".to_string());
+    config.routing_rules = vec![hyperinfer_core::RoutingRule {
+        name: "gpt-4".to_string(),
+        priority: 1,
+        fallback_models: vec!["anthropic/claude-3-haiku".to_string()],
+    }];
+    config.hedging = Some(hyperinfer_core::HedgingConfig {
+        after_ms: 50,
+        max_output_tokens: None,
+    });
+    config
+}
+
+#[tokio::test]
+async fn test_slow_primary_is_hedged() {
+    let (redis_url, _container) = setup_redis().await;
+    let client = HyperInferClient::new(&redis_url, hedging_config())
+        .await
+        .unwrap()
+        .with_transport(Arc::new(SlowOpenAiTransport));
+
+    let started = std::time::Instant::now();
+    let response = client.chat("team-key", test_request()).await.unwrap();
+    assert_eq!(response.model, "claude-3-haiku");
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+    let chunks: Vec<_> = client
+        .chat_stream("team-key", test_request())
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert_eq!(chunks[0].as_ref().unwrap().model, "claude-3-haiku");
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+}
+
+/// gpt-4 rejects any conversation of `max_messages` or more as too long.
+struct ShortContextTransport {
+    max_messages: usize,
+    fake: FakeTransport,
+}
+
+#[async_trait]
+impl ProviderTransport for ShortContextTransport {
+    async fn call_chat(
+        &self,
+        provider: &Provider,
+        model: &str,
+        api_key: &str,
+        request: &ChatRequest,
+    ) -> Result<ChatResponse, HyperInferError> {
+        if model == "gpt-4" && request.messages.len() >= self.max_messages {
+            return Err(HyperInferError::api_error(
+                400,
+                r#"{"error":{"message":"maximum context length exceeded","type":"invalid_request_error","code":"context_length_exceeded"}}"#,
+            ));
+        }
+        self.fake.call_chat(provider, model, api_key, request).await
+    }
+
+    fn call_stream(
+        &self,
+        provider: &Provider,
+        model: &str,
+        api_key: &str,
+        request: &ChatRequest,
+    ) -> Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send + 'static>> {
+        self.fake.call_stream(provider, model, api_key, request)
+    }
+}
+
+fn long_request(context_overflow: Option<hyperinfer_core::ContextOverflow>) -> ChatRequest {
+    let mut messages = Vec::new();
+    for i in 0..4 {
+        for role in [MessageRole::User, MessageRole::Assistant] {
+            messages.push(ChatMessage {
+                role,
+                content: format!("turn {} {}", i, "x".repeat(200)),
+            });
+        }
+    }
+    messages.push(ChatMessage {
+        role: MessageRole::User,
+        content: "and now?".to_string(),
+    });
+    ChatRequest {
+        model: "gpt-4".to_string(),
+        messages,
+        context_overflow,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_context_overflow_recovery() {
+    let (redis_url, _container) = setup_redis().await;
+    let mut config = test_config();
+    config.routing_rules = vec![hyperinfer_core::RoutingRule {
+        name: "gpt-4".to_string(),
+        priority: 1,
+        fallback_models: vec!["gpt-4o".to_string()],
+    }];
+    let transport = Arc::new(ShortContextTransport {
+        max_messages: 9,
+        fake: FakeTransport::default(),
+    });
+    let client = HyperInferClient::new(&redis_url, config)
+        .await
+        .unwrap()
+        .with_transport(transport.clone());
+
+    let err = client
+        .chat("team-key", long_request(None))
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.provider_error_kind(),
+        Some(hyperinfer_core::ProviderErrorKind::ContextLength)
+    );
+
+    let trimmed = client
+        .chat(
+            "team-key",
+            long_request(Some(hyperinfer_core::ContextOverflow::Trim)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(trimmed.model, "gpt-4");
+
+    let upgraded = client
+        .chat(
+            "team-key",
+            long_request(Some(hyperinfer_core::ContextOverflow::LongerContextModel)),
+        )
+        .await
+        .unwrap();
+    assert_eq!(upgraded.model, "gpt-4o");
+}
@@ -0,0 +1,246 @@
+//! Wildcard and rewrite aliases.
+//!
+//! Besides exact model names, alias keys may be globs (`gpt-4*`,
+//! `claude-3-?-sonnet`) or, prefixed with `re:`, regexes whose captures can be
+//! used in the target (`re:^gpt-4-(\d{4})$` → `openai/gpt-4o-$1`).  Regexes
+//! always match the whole model name.
+//!
+//! Exact aliases win over patterns.  Among patterns, globs are tried before
+//! regexes; globs with more literal characters and longer regexes are tried
+//! first, and ties fall back to the key itself, so an alias table resolves
+//! the same way on every instance.
+
+use regex::Regex;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use tracing::warn;
+
+/// Prefix marking an alias key as a regex.
+pub const REGEX_PREFIX: &str = "re:";
+
+/// Whether `key` is a glob or regex rather than an exact model name.
+pub fn is_pattern(key: &str) -> bool {
+    key.starts_with(REGEX_PREFIX) || key.contains(['*', '?'])
+}
+
+enum Matcher {
+    Glob(String),
+    Regex(Regex),
+}
+
+struct AliasPattern {
+    key: String,
+    matcher: Matcher,
+    target: String,
+}
+
+/// The pattern aliases of an alias table, in resolution order.
+#[derive(Default)]
+pub struct AliasPatterns {
+    patterns: Vec<AliasPattern>,
+}
+
+impl AliasPatterns {
+    /// Compile the pattern keys of `aliases`; exact keys are ignored and
+    /// invalid regexes are skipped with a warning.
+    pub fn new<'a>(aliases: impl IntoIterator<Item = (&'a String, &'a String)>) -> Self {
+        let mut patterns: Vec<AliasPattern> = aliases
+            .into_iter()
+            .filter(|(key, _)| is_pattern(key))
+            .filter_map(|(key, target)| {
+                let matcher = match key.strip_prefix(REGEX_PREFIX) {
+                    Some(pattern) => match Regex::new(&format!("^(?:{})$", pattern)) {
+                        Ok(regex) => Matcher::Regex(regex),
+                        Err(err) => {
+                            warn!("Invalid alias pattern '{}': {}", key, err);
+                            return None;
+                        }
+                    },
+                    None => Matcher::Glob(key.clone()),
+                };
+                Some(AliasPattern {
+                    key: key.clone(),
+                    matcher,
+                    target: target.clone(),
+                })
+            })
+            .collect();
+        patterns.sort_by(|a, b| {
+            a.specificity()
+                .cmp(&b.specificity())
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        Self { patterns }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    /// Target of the most specific pattern matching `model`, with regex
+    /// captures substituted.
+    pub fn resolve(&self, model: &str) -> Option<String> {
+        self.matching(model).map(|(_, target)| target)
+    }
+
+    /// Key and target of the most specific pattern matching `model`.
+    pub fn matching(&self, model: &str) -> Option<(&str, String)> {
+        self.patterns.iter().find_map(|p| {
+            let target = match &p.matcher {
+                Matcher::Glob(glob) => glob_matches(glob, model).then(|| p.target.clone()),
+                Matcher::Regex(regex) => regex.captures(model).map(|caps| {
+                    let mut target = String::new();
+                    caps.expand(&p.target, &mut target);
+                    target
+                }),
+            }?;
+            Some((p.key.as_str(), target))
+        })
+    }
+}
+
+impl AliasPattern {
+    /// Sort key: globs before regexes, more specific first.
+    fn specificity(&self) -> (u8, Reverse<usize>) {
+        match &self.matcher {
+            Matcher::Glob(glob) => (
+                0,
+                Reverse(glob.chars().filter(|c| !matches!(c, '*' | '?')).count()),
+            ),
+            Matcher::Regex(regex) => (1, Reverse(regex.as_str().len())),
+        }
+    }
+}
+
+/// Match `text` against a glob where `*` is any run of characters and `?`
+/// any single character.
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it currently absorbs up to.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Split `aliases` into its exact entries and compiled patterns.
+pub fn split_aliases(aliases: HashMap<String, String>) -> (HashMap<String, String>, AliasPatterns) {
+    let patterns = AliasPatterns::new(&aliases);
+    let exact = aliases
+        .into_iter()
+        .filter(|(key, _)| !is_pattern(key))
+        .collect();
+    (exact, patterns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(entries: &[(&str, &str)]) -> AliasPatterns {
+        let aliases: HashMap<String, String> = entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        AliasPatterns::new(&aliases)
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("gpt-4*", "gpt-4"));
+        assert!(glob_matches("gpt-4*", "gpt-4-turbo"));
+        assert!(!glob_matches("gpt-4*", "gpt-3.5-turbo"));
+        assert!(glob_matches("claude-3-?-*", "claude-3-5-sonnet"));
+        assert!(!glob_matches("claude-3-?-*", "claude-3-opus"));
+        assert!(glob_matches("*-mini", "gpt-4o-mini"));
+        assert!(glob_matches("*", ""));
+        assert!(!glob_matches("gpt", "gpt-4"));
+    }
+
+    #[test]
+    fn test_is_pattern() {
+        assert!(is_pattern("gpt-4*"));
+        assert!(is_pattern("re:^gpt-(.*)$"));
+        assert!(!is_pattern("gpt-4"));
+    }
+
+    #[test]
+    fn test_more_literal_glob_wins() {
+        let p = patterns(&[
+            ("gpt-*", "openai/gpt-4o-mini"),
+            ("gpt-4*", "openai/gpt-4o"),
+            ("gpt-4-32k*", "openai/gpt-4o-32k"),
+        ]);
+        assert_eq!(
+            p.resolve("gpt-4-32k-0613").as_deref(),
+            Some("openai/gpt-4o-32k")
+        );
+        assert_eq!(p.resolve("gpt-4-turbo").as_deref(), Some("openai/gpt-4o"));
+        assert_eq!(
+            p.resolve("gpt-3.5-turbo").as_deref(),
+            Some("openai/gpt-4o-mini")
+        );
+        assert_eq!(p.resolve("claude-3"), None);
+    }
+
+    #[test]
+    fn test_glob_ties_break_on_key() {
+        let p = patterns(&[("gpt*", "openai/b"), ("*-4o", "openai/a")]);
+        assert_eq!(p.resolve("gpt-4o").as_deref(), Some("openai/a"));
+    }
+
+    #[test]
+    fn test_regex_rewrites_captures() {
+        let p = patterns(&[(r"re:gpt-4-(\d{4})", "openai/gpt-4o-$1")]);
+        assert_eq!(
+            p.resolve("gpt-4-0613").as_deref(),
+            Some("openai/gpt-4o-0613")
+        );
+        // Regexes must match the whole name.
+        assert_eq!(p.resolve("gpt-4-0613-preview"), None);
+    }
+
+    #[test]
+    fn test_glob_tried_before_regex() {
+        let p = patterns(&[("re:gpt-4.*", "openai/regex"), ("gpt-4*", "openai/glob")]);
+        assert_eq!(p.resolve("gpt-4-turbo").as_deref(), Some("openai/glob"));
+    }
+
+    #[test]
+    fn test_invalid_regex_skipped() {
+        let p = patterns(&[("re:gpt-(", "openai/x"), ("gpt-*", "openai/y")]);
+        assert_eq!(p.len(), 1);
+        assert_eq!(p.resolve("gpt-4").as_deref(), Some("openai/y"));
+    }
+
+    #[test]
+    fn test_split_aliases() {
+        let aliases = HashMap::from([
+            ("fast".to_string(), "openai/gpt-4o-mini".to_string()),
+            ("gpt-4*".to_string(), "openai/gpt-4o".to_string()),
+        ]);
+        let (exact, patterns) = split_aliases(aliases);
+        assert_eq!(exact.len(), 1);
+        assert!(exact.contains_key("fast"));
+        assert_eq!(patterns.len(), 1);
+    }
+}
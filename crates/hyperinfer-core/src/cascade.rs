@@ -0,0 +1,188 @@
+//! Model cascades.
+//!
+//! A [`Cascade`] takes over a model name: requests for it go to a cheap
+//! model first, and only when the answer fails the cascade's verifiers are
+//! they sent again to the expensive model.  Each attempt is recorded in
+//! usage with [`CASCADE_METADATA_KEY`] and [`STEP_METADATA_KEY`], so spend
+//! can be broken down by step.
+
+use crate::evals::EvalCriterion;
+use crate::types::{ChatMessage, ChatRequest, MessageRole};
+use serde::{Deserialize, Serialize};
+
+/// Usage metadata key naming the cascade an attempt belongs to.
+pub const CASCADE_METADATA_KEY: &str = "hyperinfer.cascade";
+/// Usage and response metadata key naming the step of an attempt: `cheap`,
+/// `judge` or `escalated`.
+pub const STEP_METADATA_KEY: &str = "hyperinfer.cascade_step";
+/// Response metadata key holding what the cascade saved over sending the
+/// request straight to the expensive model, in cents; negative when it
+/// escalated.
+pub const SAVED_METADATA_KEY: &str = "hyperinfer.cascade_saved_cents";
+
+/// A check the cheap model's answer must pass.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CascadeVerifier {
+    /// The answer matches the regular expression `pattern`.
+    Matches { pattern: String },
+    /// The answer parses as JSON.
+    ValidJson,
+    /// `model` is asked, with `prompt` as its instructions, whether the
+    /// answer is good enough, and must reply `PASS`.
+    Judge { model: String, prompt: String },
+}
+
+impl CascadeVerifier {
+    /// Check `answer` without calling a model; `None` for a judge.
+    pub fn check(&self, answer: &str) -> Option<bool> {
+        match self {
+            CascadeVerifier::Matches { pattern } => Some(
+                EvalCriterion::Matches {
+                    pattern: pattern.clone(),
+                }
+                .check(answer),
+            ),
+            CascadeVerifier::ValidJson => Some(EvalCriterion::ValidJson.check(answer)),
+            CascadeVerifier::Judge { .. } => None,
+        }
+    }
+
+    /// The request asking a judge about `answer` to `request`; `None` for
+    /// the other verifiers.
+    pub fn judge_request(&self, request: &ChatRequest, answer: &str) -> Option<ChatRequest> {
+        let CascadeVerifier::Judge { model, prompt } = self else {
+            return None;
+        };
+        let question = request
+            .messages
+            .iter()
+            .rev()
+            .find(|message| message.role == MessageRole::User)
+            .map_or("", |message| message.content.as_str());
+        Some(ChatRequest {
+            model: model.clone(),
+            messages: vec![
+                ChatMessage {
+                    role: MessageRole::System,
+                    content: format!(
+                        "{}\n\nReply PASS if the response is acceptable, otherwise FAIL.",
+                        prompt
+                    ),
+                },
+                ChatMessage {
+                    role: MessageRole::User,
+                    content: format!("Request:\n{}\n\nResponse:\n{}", question, answer),
+                },
+            ],
+            max_tokens: Some(8),
+            metadata: request.metadata.clone(),
+            ..Default::default()
+        })
+    }
+
+    /// Whether a judge's reply accepts the answer.
+    pub fn judge_passed(reply: &str) -> bool {
+        reply.trim_start().to_uppercase().starts_with("PASS")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Cascade {
+    /// Tried first.  Resolved like any requested model, so aliases and
+    /// `provider/model` targets work.
+    pub cheap_model: String,
+    /// Used when the cheap model's answer fails a verifier.
+    pub expensive_model: String,
+    /// All must pass for the cheap answer to be kept.
+    pub verifiers: Vec<CascadeVerifier>,
+}
+
+impl Cascade {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.cheap_model.trim().is_empty() || self.expensive_model.trim().is_empty() {
+            return Err("cheap_model and expensive_model must not be empty".to_string());
+        }
+        if self.cheap_model == self.expensive_model {
+            return Err("cheap_model and expensive_model must differ".to_string());
+        }
+        if self.verifiers.is_empty() {
+            return Err("A cascade needs at least one verifier".to_string());
+        }
+        for verifier in &self.verifiers {
+            match verifier {
+                CascadeVerifier::Matches { pattern } => EvalCriterion::Matches {
+                    pattern: pattern.clone(),
+                }
+                .validate()?,
+                CascadeVerifier::Judge { model, .. } if model.trim().is_empty() => {
+                    return Err("A judge needs a model".to_string())
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_verifiers_and_judge_replies() {
+        assert_eq!(CascadeVerifier::ValidJson.check("{\"a\": 1}"), Some(true));
+        assert_eq!(
+            CascadeVerifier::Matches {
+                pattern: r"^\d+$".to_string()
+            }
+            .check("forty-two"),
+            Some(false)
+        );
+        let judge = CascadeVerifier::Judge {
+            model: "gpt-4o-mini".to_string(),
+            prompt: "Is the answer correct?".to_string(),
+        };
+        assert_eq!(judge.check("anything"), None);
+        assert!(CascadeVerifier::judge_passed(" pass - looks right"));
+        assert!(!CascadeVerifier::judge_passed("FAIL"));
+
+        let request = ChatRequest {
+            messages: vec![ChatMessage {
+                role: MessageRole::User,
+                content: "2+2?".to_string(),
+            }],
+            ..Default::default()
+        };
+        let judged = judge.judge_request(&request, "4").unwrap();
+        assert_eq!(judged.model, "gpt-4o-mini");
+        assert!(judged.messages[1].content.contains("2+2?"));
+        assert!(judged.messages[1].content.ends_with("4"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let cascade = Cascade {
+            cheap_model: "gpt-4o-mini".to_string(),
+            expensive_model: "gpt-4o".to_string(),
+            verifiers: vec![CascadeVerifier::ValidJson],
+        };
+        assert!(cascade.validate().is_ok());
+        assert!(Cascade {
+            verifiers: vec![CascadeVerifier::Matches {
+                pattern: "(".to_string()
+            }],
+            ..cascade.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(Cascade {
+            expensive_model: "gpt-4o-mini".to_string(),
+            ..cascade
+        }
+        .validate()
+        .is_err());
+    }
+}
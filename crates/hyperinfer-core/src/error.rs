@@ -2,6 +2,7 @@
 //!
 //! Defines the standard error type used throughout the system.
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// The main error type for HyperInfer
@@ -10,30 +11,213 @@ pub enum HyperInferError {
     #[error("Configuration error: {0}")]
     Config(#[from] std::io::Error),
 
-    #[error("Rate limiting error: {0}")]
-    RateLimit(String),
+    #[error("Rate limiting error: {message}")]
+    RateLimit {
+        message: String,
+        /// Milliseconds until the request would be allowed, when known.
+        retry_after_ms: Option<u64>,
+    },
 
     #[error("HTTP request failed: {0}")]
     Http(#[from] reqwest::Error),
 
     #[error("API error ({status}): {message}")]
-    ApiError { status: u16, message: String },
+    ApiError {
+        status: u16,
+        message: String,
+        kind: ProviderErrorKind,
+    },
 
     #[error("SSE parse error: {message}")]
     StreamParse { message: String, raw: String },
 
+    #[cfg(feature = "postgres")]
     #[error("Database error")]
     Database(#[from] sqlx::Error),
 
+    #[cfg(feature = "redis")]
     #[error("Redis error")]
     Redis(#[from] redis::RedisError),
 
     #[error("Streaming not supported by provider: {0}")]
     UnsupportedStreaming(String),
+
+    #[error("API key suspended: {0}")]
+    KeySuspended(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Provider response exceeded the {limit}-byte limit")]
+    ResponseTooLarge { limit: usize },
+
+    #[error("Client is shutting down")]
+    ShuttingDown,
+}
+
+impl HyperInferError {
+    /// An error response from a provider (or the control plane), classified
+    /// from its status and, when `message` is the provider's JSON error
+    /// body, the error type in it.
+    pub fn api_error(status: u16, message: impl Into<String>) -> Self {
+        let message = message.into();
+        Self::ApiError {
+            status,
+            kind: ProviderErrorKind::classify(status, &message),
+            message,
+        }
+    }
+
+    /// Short name of the variant, for metrics and logs.  Matched here so
+    /// callers need not know which features are enabled.
+    pub fn category(&self) -> &'static str {
+        match self {
+            Self::Config(_) => "config",
+            Self::RateLimit { .. } => "rate_limit",
+            Self::Http(_) => "http",
+            Self::ApiError { .. } => "api_error",
+            Self::StreamParse { .. } => "stream_parse",
+            #[cfg(feature = "postgres")]
+            Self::Database(_) => "database",
+            #[cfg(feature = "redis")]
+            Self::Redis(_) => "redis",
+            Self::UnsupportedStreaming(_) => "unsupported_streaming",
+            Self::KeySuspended(_) => "key_suspended",
+            Self::Forbidden(_) => "forbidden",
+            Self::ResponseTooLarge { .. } => "response_too_large",
+            Self::ShuttingDown => "shutting_down",
+        }
+    }
+
+    /// The normalized category of an API error.
+    pub fn provider_error_kind(&self) -> Option<ProviderErrorKind> {
+        match self {
+            Self::ApiError { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
+
+    /// A rate-limit error without a known retry time.
+    pub fn rate_limit(message: impl Into<String>) -> Self {
+        Self::RateLimit {
+            message: message.into(),
+            retry_after_ms: None,
+        }
+    }
+
+    /// How long to wait before retrying, for rate-limit errors that know.
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        match self {
+            Self::RateLimit {
+                retry_after_ms: Some(ms),
+                ..
+            } => Some(std::time::Duration::from_millis(*ms)),
+            _ => None,
+        }
+    }
+}
+
+/// What went wrong on the provider side, the same for every provider.
+///
+/// OpenAI and Anthropic both name the failure in `error.type` (OpenAI also
+/// in `error.code`), but with different vocabularies; this is what retry
+/// and fallback decisions are made on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderErrorKind {
+    /// Malformed request, unknown model or unsupported parameter.
+    InvalidRequest,
+    /// Missing, invalid or under-privileged API key, or no quota left.
+    Auth,
+    RateLimit,
+    /// The provider is shedding load (OpenAI 503, Anthropic 529).
+    Overloaded,
+    /// Any other provider-side failure.
+    Server,
+    /// The prompt or output was refused by the provider's content filter.
+    ContentFilter,
+    /// The prompt plus `max_tokens` does not fit the model's context window.
+    ContextLength,
+    #[default]
+    Other,
+}
+
+impl ProviderErrorKind {
+    /// Classify an error response from its status and body.  Error types in
+    /// the body take precedence; the status decides when there are none.
+    pub fn classify(status: u16, body: &str) -> Self {
+        #[derive(Deserialize)]
+        struct Envelope {
+            error: Detail,
+        }
+        #[derive(Deserialize)]
+        struct Detail {
+            #[serde(default, rename = "type")]
+            error_type: Option<String>,
+            #[serde(default)]
+            code: Option<serde_json::Value>,
+            #[serde(default)]
+            message: String,
+        }
+
+        if let Ok(Envelope { error }) = serde_json::from_str::<Envelope>(body) {
+            let code = error.code.as_ref().and_then(|c| c.as_str());
+            let kind = code
+                .and_then(Self::from_error_type)
+                .or_else(|| error.error_type.as_deref().and_then(Self::from_error_type));
+            // Anthropic reports an overlong prompt as a plain invalid request.
+            if kind == Some(Self::InvalidRequest) && error.message.starts_with("prompt is too long")
+            {
+                return Self::ContextLength;
+            }
+            if let Some(kind) = kind {
+                return kind;
+            }
+        }
+        Self::from_status(status)
+    }
+
+    fn from_error_type(error_type: &str) -> Option<Self> {
+        let kind = match error_type {
+            "context_length_exceeded" | "string_above_max_length" => Self::ContextLength,
+            "content_filter" | "content_policy_violation" => Self::ContentFilter,
+            "rate_limit_error" | "rate_limit_exceeded" | "requests" | "tokens" => Self::RateLimit,
+            "overloaded_error" | "engine_overloaded" => Self::Overloaded,
+            "authentication_error"
+            | "permission_error"
+            | "invalid_api_key"
+            | "insufficient_quota" => Self::Auth,
+            "api_error" | "server_error" => Self::Server,
+            "invalid_request_error"
+            | "not_found_error"
+            | "request_too_large"
+            | "model_not_found" => Self::InvalidRequest,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// Classify an error response by its status alone.
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            401 | 403 => Self::Auth,
+            429 => Self::RateLimit,
+            503 | 529 => Self::Overloaded,
+            400..=499 => Self::InvalidRequest,
+            500..=599 => Self::Server,
+            _ => Self::Other,
+        }
+    }
+
+    /// Whether the same request might succeed if sent again later.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::RateLimit | Self::Overloaded | Self::Server)
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum DbError {
+    #[cfg(feature = "postgres")]
     #[error("Database error: {0}")]
     Sqlx(#[from] sqlx::Error),
     #[error("Invalid UUID: {0}")]
@@ -46,6 +230,7 @@ pub enum DbError {
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
+    #[cfg(feature = "redis")]
     #[error("Redis error: {0}")]
     Redis(#[from] redis::RedisError),
     #[error("Serialization error: {0}")]
@@ -53,3 +238,59 @@ pub enum ConfigError {
     #[error("Configuration error: {0}")]
     Other(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_openai_errors() {
+        let body = r#"{"error":{"message":"This model's maximum context length is 8192 tokens.","type":"invalid_request_error","code":"context_length_exceeded"}}"#;
+        assert_eq!(
+            ProviderErrorKind::classify(400, body),
+            ProviderErrorKind::ContextLength
+        );
+        let body = r#"{"error":{"message":"Incorrect API key provided","type":"invalid_request_error","code":"invalid_api_key"}}"#;
+        assert_eq!(
+            ProviderErrorKind::classify(401, body),
+            ProviderErrorKind::Auth
+        );
+        let body = r#"{"error":{"message":"Rate limit reached","type":"tokens","code":"rate_limit_exceeded"}}"#;
+        assert_eq!(
+            ProviderErrorKind::classify(429, body),
+            ProviderErrorKind::RateLimit
+        );
+    }
+
+    #[test]
+    fn test_classify_anthropic_errors() {
+        let body = r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        assert_eq!(
+            ProviderErrorKind::classify(529, body),
+            ProviderErrorKind::Overloaded
+        );
+        let body = r#"{"type":"error","error":{"type":"invalid_request_error","message":"prompt is too long: 210000 tokens > 200000 maximum"}}"#;
+        assert_eq!(
+            ProviderErrorKind::classify(400, body),
+            ProviderErrorKind::ContextLength
+        );
+    }
+
+    #[test]
+    fn test_classify_falls_back_to_status() {
+        assert_eq!(
+            ProviderErrorKind::classify(502, "<html>Bad Gateway</html>"),
+            ProviderErrorKind::Server
+        );
+        assert_eq!(
+            ProviderErrorKind::classify(429, "slow down"),
+            ProviderErrorKind::RateLimit
+        );
+        assert!(ProviderErrorKind::from_status(503).is_retryable());
+        assert!(!ProviderErrorKind::from_status(400).is_retryable());
+        assert_eq!(
+            HyperInferError::api_error(403, "nope").provider_error_kind(),
+            Some(ProviderErrorKind::Auth)
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Evaluation datasets and scoring.
+//!
+//! An eval dataset is a list of prompts, each with the criteria a good
+//! answer meets.  Clients run a dataset against a model through the data
+//! plane, score every answer with [`EvalItem::score`] and submit the
+//! results to the control plane, which keeps them per run so runs against
+//! different models can be compared item by item.
+
+use crate::types::ChatMessage;
+use serde::{Deserialize, Serialize};
+
+/// Something an answer must satisfy.  Text comparisons ignore leading and
+/// trailing whitespace.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EvalCriterion {
+    /// The answer contains `text`, ignoring case.
+    Contains { text: String },
+    /// The answer does not contain `text`, ignoring case.
+    NotContains { text: String },
+    /// The answer is exactly `text`.
+    Equals { text: String },
+    /// The answer matches the regular expression `pattern`.
+    Matches { pattern: String },
+    /// The answer is at most `max_chars` characters long.
+    MaxChars { max_chars: usize },
+    /// The answer parses as JSON.
+    ValidJson,
+}
+
+impl EvalCriterion {
+    /// Reject criteria that can never be checked, such as a pattern that
+    /// does not compile.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            EvalCriterion::Matches { pattern } => regex::Regex::new(pattern)
+                .map(|_| ())
+                .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check(&self, answer: &str) -> bool {
+        let answer = answer.trim();
+        match self {
+            EvalCriterion::Contains { text } => {
+                answer.to_lowercase().contains(&text.to_lowercase())
+            }
+            EvalCriterion::NotContains { text } => {
+                !answer.to_lowercase().contains(&text.to_lowercase())
+            }
+            EvalCriterion::Equals { text } => answer == text.trim(),
+            EvalCriterion::Matches { pattern } => {
+                regex::Regex::new(pattern).is_ok_and(|re| re.is_match(answer))
+            }
+            EvalCriterion::MaxChars { max_chars } => answer.chars().count() <= *max_chars,
+            EvalCriterion::ValidJson => serde_json::from_str::<serde_json::Value>(answer).is_ok(),
+        }
+    }
+}
+
+/// One prompt of a dataset and what its answer is judged on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EvalItem {
+    pub messages: Vec<ChatMessage>,
+    pub criteria: Vec<EvalCriterion>,
+}
+
+impl EvalItem {
+    /// Fraction of the criteria `answer` meets, from 0 to 1.
+    pub fn score(&self, answer: &str) -> f64 {
+        if self.criteria.is_empty() {
+            return 1.0;
+        }
+        let met = self.criteria.iter().filter(|c| c.check(answer)).count();
+        met as f64 / self.criteria.len() as f64
+    }
+}
+
+/// The outcome of one dataset item in a run, as submitted by the client
+/// that ran it.  Items whose call failed carry `error` and score 0.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EvalItemResult {
+    /// Position of the item in the dataset.
+    pub item_index: u32,
+    #[serde(default)]
+    pub answer: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+    pub score: f64,
+    /// Whether every criterion was met.
+    pub passed: bool,
+    #[serde(default)]
+    pub latency_ms: u64,
+    #[serde(default)]
+    pub input_tokens: u32,
+    #[serde(default)]
+    pub output_tokens: u32,
+}
+
+impl EvalItemResult {
+    /// Score `answer` against `item`.
+    pub fn scored(item_index: u32, item: &EvalItem, answer: String, latency_ms: u64) -> Self {
+        let score = item.score(&answer);
+        Self {
+            item_index,
+            passed: score >= 1.0,
+            score,
+            answer: Some(answer),
+            error: None,
+            latency_ms,
+            input_tokens: 0,
+            output_tokens: 0,
+        }
+    }
+
+    /// An item whose call failed.
+    pub fn failed(item_index: u32, error: String, latency_ms: u64) -> Self {
+        Self {
+            item_index,
+            answer: None,
+            error: Some(error),
+            score: 0.0,
+            passed: false,
+            latency_ms,
+            input_tokens: 0,
+            output_tokens: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_criteria() {
+        let answer = "  The capital is Paris.\n";
+        assert!(EvalCriterion::Contains {
+            text: "paris".to_string()
+        }
+        .check(answer));
+        assert!(!EvalCriterion::NotContains {
+            text: "PARIS".to_string()
+        }
+        .check(answer));
+        assert!(EvalCriterion::Equals {
+            text: "The capital is Paris.".to_string()
+        }
+        .check(answer));
+        assert!(EvalCriterion::Matches {
+            pattern: r"^The \w+ is".to_string()
+        }
+        .check(answer));
+        assert!(!EvalCriterion::MaxChars { max_chars: 5 }.check(answer));
+        assert!(!EvalCriterion::ValidJson.check(answer));
+        assert!(EvalCriterion::ValidJson.check(r#"{"city": "Paris"}"#));
+    }
+
+    #[test]
+    fn test_score_is_the_fraction_of_criteria_met() {
+        let item = EvalItem {
+            messages: Vec::new(),
+            criteria: vec![
+                EvalCriterion::Contains {
+                    text: "paris".to_string(),
+                },
+                EvalCriterion::MaxChars { max_chars: 3 },
+            ],
+        };
+        let result = EvalItemResult::scored(0, &item, "Paris".to_string(), 10);
+        assert_eq!(result.score, 0.5);
+        assert!(!result.passed);
+        assert!(EvalCriterion::Matches {
+            pattern: "(".to_string()
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! Routing experiments.
+//!
+//! A [`BanditExperiment`] takes over a model name: requests for it are
+//! split between candidate models ("arms") by an epsilon-greedy policy.
+//! Most requests go to the arm with the best reward, its quality less
+//! `cost_weight` times its cost per request; a share `epsilon` goes to an
+//! arm picked at random, so every arm keeps being tried.  The signals are
+//! fed back by the control plane from eval runs and usage telemetry, and
+//! arms without a quality signal yet are tried before any is exploited.
+//!
+//! Each request's assignment is recorded in its usage metadata under
+//! [`EXPERIMENT_METADATA_KEY`] and [`ARM_METADATA_KEY`], so usage can be
+//! broken down by arm.
+
+use serde::{Deserialize, Serialize};
+
+/// Usage metadata key naming the experiment a request was assigned in.
+pub const EXPERIMENT_METADATA_KEY: &str = "hyperinfer.experiment";
+/// Usage metadata key naming the arm a request was assigned to.
+pub const ARM_METADATA_KEY: &str = "hyperinfer.experiment_arm";
+
+fn default_epsilon() -> f64 {
+    0.1
+}
+
+/// A candidate model and the signals gathered about it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BanditArm {
+    /// Model requests are sent to, resolved like any requested model, so
+    /// aliases and `provider/model` targets work.
+    pub model: String,
+    /// Answer quality from 0 to 1, such as the arm's mean eval score.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<f64>,
+    /// Mean cost of a request, in cents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_cents: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BanditExperiment {
+    pub arms: Vec<BanditArm>,
+    /// Share of requests sent to a random arm, from 0 to 1.
+    #[serde(default = "default_epsilon")]
+    pub epsilon: f64,
+    /// Quality given up per cent of cost per request; 0 ignores cost.
+    #[serde(default)]
+    pub cost_weight: f64,
+    /// Teams whose requests take part; all teams when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub teams: Vec<String>,
+    /// Eval dataset whose runs give the arms' quality.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dataset_id: Option<String>,
+}
+
+impl BanditExperiment {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.arms.is_empty() {
+            return Err("An experiment needs at least one arm".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.epsilon) {
+            return Err("epsilon must be between 0 and 1".to_string());
+        }
+        if self.cost_weight.is_nan() || self.cost_weight < 0.0 {
+            return Err("cost_weight must not be negative".to_string());
+        }
+        for (i, arm) in self.arms.iter().enumerate() {
+            if arm.model.trim().is_empty() {
+                return Err(format!("Arm {} has no model", i));
+            }
+            if self.arms[..i].iter().any(|other| other.model == arm.model) {
+                return Err(format!("Arm '{}' is listed more than once", arm.model));
+            }
+            if arm.quality.is_some_and(|q| !(0.0..=1.0).contains(&q)) {
+                return Err(format!("Arm '{}' has a quality outside 0 to 1", arm.model));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether requests of `team_id` take part.
+    pub fn applies_to(&self, team_id: Option<&str>) -> bool {
+        self.teams.is_empty() || team_id.is_some_and(|id| self.teams.iter().any(|t| t == id))
+    }
+
+    /// Quality less the cost penalty; `None` until the arm has a quality.
+    pub fn reward(&self, arm: &BanditArm) -> Option<f64> {
+        Some(arm.quality? - self.cost_weight * arm.cost_cents.unwrap_or(0.0))
+    }
+
+    /// Pick an arm.  `explore` is a uniform draw from `[0, 1)` deciding
+    /// whether to explore, and `pick` a uniform draw choosing among the
+    /// arms when exploring or among the arms not yet scored.
+    pub fn choose(&self, explore: f64, pick: usize) -> Option<&BanditArm> {
+        if self.arms.is_empty() {
+            return None;
+        }
+        if explore < self.epsilon {
+            return self.arms.get(pick % self.arms.len());
+        }
+        let unscored: Vec<&BanditArm> = self
+            .arms
+            .iter()
+            .filter(|arm| arm.quality.is_none())
+            .collect();
+        if !unscored.is_empty() {
+            return Some(unscored[pick % unscored.len()]);
+        }
+        self.arms.iter().max_by(|a, b| {
+            let (a, b) = (self.reward(a), self.reward(b));
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arm(model: &str, quality: Option<f64>, cost_cents: Option<f64>) -> BanditArm {
+        BanditArm {
+            model: model.to_string(),
+            quality,
+            cost_cents,
+        }
+    }
+
+    fn experiment(arms: Vec<BanditArm>) -> BanditExperiment {
+        BanditExperiment {
+            arms,
+            epsilon: 0.1,
+            cost_weight: 0.5,
+            teams: Vec::new(),
+            dataset_id: None,
+        }
+    }
+
+    #[test]
+    fn test_choose_exploits_the_best_reward_and_explores_at_epsilon() {
+        let experiment = experiment(vec![
+            arm("big", Some(0.9), Some(1.0)),
+            arm("small", Some(0.8), Some(0.1)),
+        ]);
+        // big: 0.9 - 0.5, small: 0.8 - 0.05
+        assert_eq!(experiment.choose(0.5, 0).unwrap().model, "small");
+        assert_eq!(experiment.choose(0.05, 0).unwrap().model, "big");
+
+        let free = BanditExperiment {
+            cost_weight: 0.0,
+            ..experiment
+        };
+        assert_eq!(free.choose(0.5, 1).unwrap().model, "big");
+    }
+
+    #[test]
+    fn test_unscored_arms_are_tried_first() {
+        let experiment = experiment(vec![arm("known", Some(1.0), None), arm("new", None, None)]);
+        assert_eq!(experiment.choose(0.9, 0).unwrap().model, "new");
+        assert!(experiment.validate().is_ok());
+        assert!(BanditExperiment {
+            epsilon: 1.5,
+            ..experiment.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(!BanditExperiment {
+            teams: vec!["team-1".to_string()],
+            ..experiment
+        }
+        .applies_to(Some("team-2")));
+    }
+}
@@ -2,20 +2,66 @@
 //!
 //! This crate contains shared data structures, traits, and error definitions
 //! used across the entire HyperInfer monorepo.
+//!
+//! The Redis and Postgres integrations sit behind the default `redis` and
+//! `postgres` features.  With `default-features = false` the crate compiles
+//! to wasm32, so edge workers can validate requests, make routing decisions
+//! and estimate cost before calling the gateway.
 
+pub mod aliases;
+pub mod cascade;
 pub mod error;
+pub mod evals;
+pub mod experiments;
+pub mod pricing;
 pub mod rate_limiting;
+pub mod rbac;
+#[cfg(feature = "redis")]
 pub mod redis;
+pub mod router;
+#[cfg(feature = "redis")]
 pub mod telemetry_consumer;
+#[cfg(feature = "redis")]
+pub mod telemetry_sink;
+pub mod tokenizer;
 pub mod traits;
 pub mod types;
 
-pub use error::{ConfigError, DbError, HyperInferError};
-pub use rate_limiting::{RateLimiter, USAGE_REQUESTS_KEY_PREFIX, USAGE_TOKENS_KEY_PREFIX};
-pub use redis::PolicyUpdate;
+pub use cascade::{Cascade, CascadeVerifier};
+pub use error::{ConfigError, DbError, HyperInferError, ProviderErrorKind};
+pub use evals::{EvalCriterion, EvalItem, EvalItemResult};
+pub use experiments::{BanditArm, BanditExperiment};
+pub use pricing::ConfiguredPrice;
+pub use rate_limiting::{
+    RateLimiter, SharedTokenBucket, TokenBucket, USAGE_REQUESTS_KEY_PREFIX, USAGE_TOKENS_KEY_PREFIX,
+};
+pub use rbac::{Action, Role};
+#[cfg(feature = "redis")]
+pub use redis::{
+    ConfigTarget, HeartbeatSource, InstanceHeartbeat, PolicyAction, PolicyUpdate,
+    RateLimitRejection, RedisHandle, RedisOptions,
+};
+pub use router::{AliasScope, ResolvedRoute, RouteExplanation, RouteStep, Router};
+#[cfg(feature = "redis")]
 pub use telemetry_consumer::TelemetryConsumer;
-pub use traits::{ApiKey, ConfigStore, Database, ModelAlias, Quota, Team, UsageLog, User};
+#[cfg(feature = "redis")]
+pub use telemetry_sink::{PostgresSink, TelemetrySink};
+#[cfg(feature = "redis")]
+pub use traits::ConfigStore;
+pub use traits::{
+    Alert, AlertRule, ApiKey, ApiKeyMetadata, BillingPeriod, BudgetPolicy, Bundle, BundleApiKey,
+    BundleModelAlias, BundleQuota, BundleTeam, BundleUser, Conversation, ConversationMessage,
+    Database, EvalDataset, EvalRun, ImportCounts, ImportSummary, KeyUsageBucket, ModelAlias,
+    ModelUsage, NewAlertRule, NewConversation, NewEvalDataset, NewEvalRun, NewModelPrice,
+    NewOrganization, NewQuotaTemplate, NewReportSubscription, NewWebhook, Organization, Quota,
+    QuotaTemplate, ReportSubscription, RoleChange, TagUsage, Team, UsageLog, User, Webhook,
+    WebhookDelivery,
+};
 pub use types::{
-    ChatChunk, ChatMessage, ChatRequest, ChatResponse, Choice, Config, MessageRole, Provider,
-    RoutingRule, Usage, UsageRecord,
+    ChatChunk, ChatMessage, ChatRequest, ChatResponse, Choice, CompressionOptions,
+    CompressionStats, Config, ContextConfig, ContextOverflow, ContextStrategy, DryRunReport,
+    HedgingConfig, JsonSchemaFormat, KeyLimits, MaintenanceWindow, MessageRole, OutputTransform,
+    ParamClamps, Provider, ProviderRegions, ProviderStatus, RateLimitUsage, ReasoningEffort,
+    RegionSelection, RegionalEndpoint, ResponseFormat, RoutingOverride, RoutingRule, RpmWindow,
+    SingleFlightConfig, TeamFeature, ThinkingOptions, Usage, UsageEvent, UsageRecord, VirtualKey,
 };
@@ -0,0 +1,304 @@
+//! Model pricing
+//!
+//! Built-in list prices used to turn token usage into spend, overridable per
+//! model through the control plane (see [`ConfiguredPrice`]).
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// List price of a model in USD per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ModelPrice {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+/// Built-in prices matched by model-name prefix.  More specific prefixes must
+/// come before the prefixes they extend.
+const DEFAULT_PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4.1-nano", 0.10, 0.40),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("gpt-4-turbo", 10.00, 30.00),
+    ("gpt-4", 30.00, 60.00),
+    ("gpt-3.5-turbo", 0.50, 1.50),
+    ("o1-mini", 1.10, 4.40),
+    ("o1", 15.00, 60.00),
+    ("o3-mini", 1.10, 4.40),
+    ("o3", 2.00, 8.00),
+    ("claude-opus-4-5", 5.00, 25.00),
+    ("claude-opus-4", 15.00, 75.00),
+    ("claude-sonnet-4", 3.00, 15.00),
+    ("claude-haiku-4-5", 1.00, 5.00),
+    ("claude-3-7-sonnet", 3.00, 15.00),
+    ("claude-3-5-sonnet", 3.00, 15.00),
+    ("claude-3-5-haiku", 0.80, 4.00),
+    ("claude-3-opus", 15.00, 75.00),
+    ("claude-3-sonnet", 3.00, 15.00),
+    ("claude-3-haiku", 0.25, 1.25),
+];
+
+/// Built-in price table entry, keyed by model-name prefix.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PriceEntry {
+    pub model: String,
+    #[serde(flatten)]
+    pub price: ModelPrice,
+}
+
+/// The built-in price table, most specific prefixes first.
+pub fn default_prices() -> Vec<PriceEntry> {
+    DEFAULT_PRICES
+        .iter()
+        .map(|(model, input, output)| PriceEntry {
+            model: model.to_string(),
+            price: ModelPrice {
+                input_per_mtok: *input,
+                output_per_mtok: *output,
+            },
+        })
+        .collect()
+}
+
+/// Built-in list price for `model`, if known.
+pub fn default_price(model: &str) -> Option<ModelPrice> {
+    DEFAULT_PRICES
+        .iter()
+        .find(|(prefix, _, _)| model.starts_with(prefix))
+        .map(|(_, input, output)| ModelPrice {
+            input_per_mtok: *input,
+            output_per_mtok: *output,
+        })
+}
+
+impl ModelPrice {
+    /// Cost in cents (fractional) of the given token counts.
+    pub fn cost_cents(&self, input_tokens: u64, output_tokens: u64) -> f64 {
+        (input_tokens as f64 * self.input_per_mtok + output_tokens as f64 * self.output_per_mtok)
+            / 10_000.0
+    }
+}
+
+/// A price managed through the control plane (`model_prices` table) and
+/// synced to the data plane in `Config::model_prices`.  `model` matches by
+/// prefix like the built-in table; the entry applies from `effective_from`
+/// until a later entry for the same model takes over.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConfiguredPrice {
+    pub id: String,
+    pub model: String,
+    pub provider: String,
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+    pub effective_from: DateTime<Utc>,
+}
+
+impl ConfiguredPrice {
+    pub fn price(&self) -> ModelPrice {
+        ModelPrice {
+            input_per_mtok: self.input_per_mtok,
+            output_per_mtok: self.output_per_mtok,
+        }
+    }
+}
+
+/// Price of `model` at `at`: the most specific configured entry already in
+/// effect, falling back to the built-in table.
+pub fn resolve_price(
+    configured: &[ConfiguredPrice],
+    model: &str,
+    at: DateTime<Utc>,
+) -> Option<ModelPrice> {
+    configured
+        .iter()
+        .filter(|p| model.starts_with(&p.model) && p.effective_from <= at)
+        .max_by_key(|p| (p.model.len(), p.effective_from))
+        .map(ConfiguredPrice::price)
+        .or_else(|| default_price(model))
+}
+
+/// The price table in effect at `at`: configured entries first, then the
+/// built-in entries they do not override.
+pub fn effective_prices(configured: &[ConfiguredPrice], at: DateTime<Utc>) -> Vec<PriceEntry> {
+    let mut entries: Vec<PriceEntry> = Vec::new();
+    for p in configured.iter().filter(|p| p.effective_from <= at) {
+        if entries.iter().any(|e| e.model == p.model) {
+            continue;
+        }
+        if let Some(price) = resolve_price(configured, &p.model, at) {
+            entries.push(PriceEntry {
+                model: p.model.clone(),
+                price,
+            });
+        }
+    }
+    let overridden: Vec<String> = entries.iter().map(|e| e.model.clone()).collect();
+    entries.extend(
+        default_prices()
+            .into_iter()
+            .filter(|d| !overridden.contains(&d.model)),
+    );
+    entries
+}
+
+/// Up-front cost estimate for a request: the expected input cost plus the
+/// most the output can cost if the model uses its whole output budget.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CostEstimate {
+    pub model: String,
+    pub input_tokens: u32,
+    pub max_output_tokens: u32,
+    pub input_cost_cents: f64,
+    pub max_output_cost_cents: f64,
+    pub max_total_cost_cents: f64,
+}
+
+impl CostEstimate {
+    pub fn new(model: &str, price: &ModelPrice, input_tokens: u32, max_output_tokens: u32) -> Self {
+        let input_cost_cents = price.cost_cents(u64::from(input_tokens), 0);
+        let max_output_cost_cents = price.cost_cents(0, u64::from(max_output_tokens));
+        Self {
+            model: model.to_string(),
+            input_tokens,
+            max_output_tokens,
+            input_cost_cents,
+            max_output_cost_cents,
+            max_total_cost_cents: input_cost_cents + max_output_cost_cents,
+        }
+    }
+}
+
+/// Cost in cents of a call to `model` at `at`, using configured prices;
+/// unknown models cost nothing.
+pub fn configured_cost_cents(
+    configured: &[ConfiguredPrice],
+    model: &str,
+    at: DateTime<Utc>,
+    input_tokens: u64,
+    output_tokens: u64,
+) -> f64 {
+    resolve_price(configured, model, at).map_or(0.0, |p| p.cost_cents(input_tokens, output_tokens))
+}
+
+/// Cost in cents of a call to `model`; unknown models cost nothing.
+pub fn cost_cents(model: &str, input_tokens: u64, output_tokens: u64) -> f64 {
+    default_price(model).map_or(0.0, |p| p.cost_cents(input_tokens, output_tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_price_prefix_match() {
+        let mini = default_price("gpt-4o-mini-2024-07-18").unwrap();
+        assert_eq!(mini.input_per_mtok, 0.15);
+
+        let full = default_price("gpt-4o-2024-08-06").unwrap();
+        assert_eq!(full.input_per_mtok, 2.50);
+
+        let haiku = default_price("claude-3-haiku-20240307").unwrap();
+        assert_eq!(haiku.output_per_mtok, 1.25);
+    }
+
+    #[test]
+    fn test_default_price_unknown_model() {
+        assert!(default_price("llama-3-70b").is_none());
+        assert_eq!(cost_cents("llama-3-70b", 1_000_000, 1_000_000), 0.0);
+    }
+
+    #[test]
+    fn test_default_prices_matches_lookup() {
+        let prices = default_prices();
+        assert!(!prices.is_empty());
+        for entry in &prices {
+            assert_eq!(default_price(&entry.model), Some(entry.price));
+        }
+        let json = serde_json::to_value(&prices[0]).unwrap();
+        assert!(json.get("input_per_mtok").is_some());
+    }
+
+    #[test]
+    fn test_cost_estimate() {
+        let price = default_price("gpt-4o").unwrap();
+        let estimate = CostEstimate::new("gpt-4o", &price, 1_000, 2_000);
+        // 1000 * $2.50/M = 0.25 cents; 2000 * $10/M = 2 cents
+        assert!((estimate.input_cost_cents - 0.25).abs() < 1e-9);
+        assert!((estimate.max_output_cost_cents - 2.0).abs() < 1e-9);
+        assert!((estimate.max_total_cost_cents - 2.25).abs() < 1e-9);
+    }
+
+    fn configured(model: &str, input: f64, effective_from: DateTime<Utc>) -> ConfiguredPrice {
+        ConfiguredPrice {
+            id: format!("{}-{}", model, effective_from.timestamp()),
+            model: model.to_string(),
+            provider: "openai".to_string(),
+            input_per_mtok: input,
+            output_per_mtok: input * 4.0,
+            effective_from,
+        }
+    }
+
+    #[test]
+    fn test_resolve_price_prefers_configured() {
+        let now = Utc::now();
+        let prices = vec![configured("gpt-4o", 2.0, now - chrono::Duration::days(1))];
+        assert_eq!(
+            resolve_price(&prices, "gpt-4o-2024-08-06", now).map(|p| p.input_per_mtok),
+            Some(2.0)
+        );
+        // The more specific built-in prefix does not beat a configured entry,
+        // but unrelated models still fall back to the built-in table.
+        assert_eq!(
+            resolve_price(&prices, "gpt-3.5-turbo", now).map(|p| p.input_per_mtok),
+            Some(0.50)
+        );
+    }
+
+    #[test]
+    fn test_resolve_price_respects_effective_from() {
+        let now = Utc::now();
+        let prices = vec![
+            configured("gpt-4o", 2.0, now - chrono::Duration::days(30)),
+            configured("gpt-4o", 1.5, now - chrono::Duration::days(1)),
+            configured("gpt-4o", 1.0, now + chrono::Duration::days(7)),
+        ];
+        assert_eq!(
+            resolve_price(&prices, "gpt-4o", now).map(|p| p.input_per_mtok),
+            Some(1.5)
+        );
+        assert_eq!(
+            resolve_price(&prices, "gpt-4o", now - chrono::Duration::days(10))
+                .map(|p| p.input_per_mtok),
+            Some(2.0)
+        );
+    }
+
+    #[test]
+    fn test_effective_prices_overrides_defaults() {
+        let now = Utc::now();
+        let prices = vec![
+            configured("gpt-4o", 2.0, now - chrono::Duration::days(1)),
+            configured("my-finetune", 9.0, now - chrono::Duration::days(1)),
+        ];
+        let table = effective_prices(&prices, now);
+        assert_eq!(table[0].model, "gpt-4o");
+        assert_eq!(table[0].price.input_per_mtok, 2.0);
+        assert_eq!(table.iter().filter(|e| e.model == "gpt-4o").count(), 1);
+        assert!(table.iter().any(|e| e.model == "my-finetune"));
+        assert!(table.iter().any(|e| e.model == "claude-3-haiku"));
+    }
+
+    #[test]
+    fn test_cost_cents() {
+        // 1M input @ $2.50 + 1M output @ $10.00 = $12.50
+        assert!((cost_cents("gpt-4o", 1_000_000, 1_000_000) - 1250.0).abs() < 1e-9);
+        // 1000 input tokens of claude-3-haiku = $0.00025 = 0.025 cents
+        assert!((cost_cents("claude-3-haiku", 1_000, 0) - 0.025).abs() < 1e-9);
+    }
+}
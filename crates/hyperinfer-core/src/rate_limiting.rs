@@ -1,16 +1,28 @@
 //! Rate limiting utilities for HyperInfer
 //!
 //! Provides distributed quota enforcement using Redis and GCRA algorithm.
+//! Without Redis, limits are enforced per process with in-memory token
+//! buckets.
 
+#[cfg(feature = "redis")]
 use redis::aio::ConnectionManager;
-use redis::Client;
+#[cfg(feature = "redis")]
+use redis::Script;
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub use crate::types::RateLimitUsage;
+use crate::types::RpmWindow;
+pub use crate::types::{SharedTokenBucket, TokenBucket};
 
 pub const USAGE_TOKENS_KEY_PREFIX: &str = "hyperinfer:usage:tokens:";
 pub const USAGE_REQUESTS_KEY_PREFIX: &str = "hyperinfer:usage:requests:";
 
-const GCRA_SCRIPT: &str = r#"
+/// GCRA token-rate check: `KEYS[1]` holds the theoretical arrival time;
+/// `ARGV` is rate, capacity, now (ms) and cost.  Replies `{allowed, wait}`.
+pub const GCRA_SCRIPT: &str = r#"
 local key = KEYS[1]
 local rate = tonumber(ARGV[1])
 local capacity = tonumber(ARGV[2])
@@ -37,7 +49,9 @@ else
 end
 "#;
 
-const RPM_SCRIPT: &str = r#"
+/// Fixed-window request counter: `KEYS[1]` is the counter; `ARGV` is limit
+/// and window (seconds).  Replies `{allowed, remaining, ttl}`.
+pub const RPM_SCRIPT: &str = r#"
 local key = KEYS[1]
 local limit = tonumber(ARGV[1])
 local window = tonumber(ARGV[2])
@@ -54,13 +68,67 @@ end
 return {1, limit - current, 0}
 "#;
 
-#[derive(Debug, Clone)]
-pub struct TokenBucket {
-    pub capacity: u64,
-    pub tokens: u64,
-    pub refill_rate: u64,
-    pub last_refill: Instant,
-}
+/// Sliding-window request log: `KEYS[1]` is a sorted set of request times;
+/// `ARGV` is limit, window (ms), now (ms) and a unique member for this
+/// request.  Unlike the fixed window, at most `limit` requests are ever
+/// admitted in any `window`.  Replies `{allowed, retry_after_ms}`.
+pub const SLIDING_RPM_SCRIPT: &str = r#"
+local key = KEYS[1]
+local limit = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+
+redis.call('ZREMRANGEBYSCORE', key, '-inf', now - window)
+if redis.call('ZCARD', key) < limit then
+    redis.call('ZADD', key, now, ARGV[4])
+    redis.call('PEXPIRE', key, window)
+    return {1, 0}
+end
+
+local oldest = redis.call('ZRANGE', key, 0, 0, 'WITHSCORES')
+if not oldest[2] then
+    return {0, 0}
+end
+return {0, math.max(tonumber(oldest[2]) + window - now, 1)}
+"#;
+
+/// RPM and TPM check in one round trip: `KEYS` are the RPM counter and the
+/// TPM arrival time; `ARGV` is the RPM limit and window (seconds), then the
+/// GCRA rate, capacity, now (ms) and cost.  Nothing is recorded unless both
+/// limits allow the request.  Replies `{allowed, retry_after_ms}`.
+pub const LIMIT_SCRIPT: &str = r#"
+local rpm_key = KEYS[1]
+local tpm_key = KEYS[2]
+local rpm_limit = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+local rate = tonumber(ARGV[3])
+local capacity = tonumber(ARGV[4])
+local now = tonumber(ARGV[5])
+local cost = tonumber(ARGV[6])
+
+local count = tonumber(redis.call('GET', rpm_key) or '0')
+if count >= rpm_limit then
+    local ttl = redis.call('PTTL', rpm_key)
+    if ttl < 0 then
+        ttl = 0
+    end
+    return {0, ttl}
+end
+
+local emission_interval = capacity / rate
+local tat = tonumber(redis.call('GET', tpm_key) or now)
+local new_tat = math.max(tat, now) + cost * emission_interval
+local allow_at = new_tat - capacity
+if allow_at > now then
+    return {0, math.ceil(allow_at - now)}
+end
+
+redis.call('SET', tpm_key, new_tat, 'EX', math.ceil(capacity * 2))
+if redis.call('INCR', rpm_key) == 1 then
+    redis.call('EXPIRE', rpm_key, window)
+end
+return {1, 0}
+"#;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Quota {
@@ -69,74 +137,191 @@ pub struct Quota {
     pub budget_cents: Option<u64>,
 }
 
+/// `(allowed, retry_after_ms)` from a `{allowed, wait}` script reply, where
+/// `wait` is in milliseconds.
+#[cfg(feature = "redis")]
+fn gcra_outcome(reply: &[u64]) -> (bool, u64) {
+    match reply.first() {
+        Some(1) => (true, 0),
+        _ => (false, reply.get(1).copied().unwrap_or(0)),
+    }
+}
+
+fn retry_after_ms(wait: Duration) -> u64 {
+    (wait.as_secs_f64() * 1000.0).ceil() as u64
+}
+
 #[derive(Clone)]
 pub struct RateLimiter {
+    #[cfg(feature = "redis")]
     redis_manager: Option<ConnectionManager>,
+    /// Invoked with EVALSHA, falling back to loading the script when Redis
+    /// answers NOSCRIPT (e.g. after a restart or failover).
+    #[cfg(feature = "redis")]
+    rpm_script: Script,
+    #[cfg(feature = "redis")]
+    gcra_script: Script,
+    #[cfg(feature = "redis")]
+    limit_script: Script,
+    #[cfg(feature = "redis")]
+    sliding_rpm_script: Script,
     default_rpm: u64,
     default_tpm: u64,
+    /// Per-key buckets used when there is no Redis.
+    local_buckets: Arc<Mutex<HashMap<String, SharedTokenBucket>>>,
+    /// Per-key request logs for sliding-window limits when there is no
+    /// Redis.
+    local_windows: Arc<Mutex<HashMap<String, VecDeque<Instant>>>>,
 }
 
 impl RateLimiter {
+    /// A limiter backed by the Redis at `redis_url`, or local to this
+    /// process without one.  Giving a URL to a build without the `redis`
+    /// feature is an error.
     pub async fn new(
         redis_url: Option<&str>,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let redis_manager = match redis_url {
-            Some(url) => {
-                let client = Client::open(url)?;
-                Some(ConnectionManager::new(client).await?)
-            }
-            None => None,
+        let Some(url) = redis_url else {
+            return Ok(Self::local());
+        };
+        #[cfg(feature = "redis")]
+        {
+            let limiter = Self::with_redis(&crate::redis::RedisHandle::connect(url).await?);
+            limiter.preload_scripts().await;
+            Ok(limiter)
+        }
+        #[cfg(not(feature = "redis"))]
+        Err(format!(
+            "Cannot use Redis at {}: built without the redis feature",
+            url
+        )
+        .into())
+    }
+
+    /// A Redis-backed limiter on the shared connection of `redis`.  Scripts
+    /// are loaded by the first call that needs them unless
+    /// [`RateLimiter::preload_scripts`] is called.
+    #[cfg(feature = "redis")]
+    pub fn with_redis(redis: &crate::redis::RedisHandle) -> Self {
+        let mut limiter = Self::local();
+        limiter.redis_manager = Some(redis.connection());
+        limiter
+    }
+
+    /// Load the limit scripts into Redis so the first requests go straight
+    /// to EVALSHA.  Failures are logged; the scripts are then loaded on
+    /// first use.
+    #[cfg(feature = "redis")]
+    pub async fn preload_scripts(&self) {
+        let Some(mut manager) = self.redis_manager.clone() else {
+            return;
         };
-        Ok(Self {
-            redis_manager,
+        for script in [
+            &self.rpm_script,
+            &self.gcra_script,
+            &self.limit_script,
+            &self.sliding_rpm_script,
+        ] {
+            if let Err(e) = script.load_async(&mut manager).await {
+                tracing::warn!("Failed to preload rate limit script: {}", e);
+            }
+        }
+    }
+
+    /// A Redis-backed limiter that connects on first use, so it can be
+    /// created while Redis is unreachable.  Checks fail until Redis is back.
+    /// Scripts are loaded by the first call that needs them.
+    #[cfg(feature = "redis")]
+    pub fn new_lazy(redis_url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut limiter = Self::local();
+        limiter.redis_manager = Some(crate::redis::lazy_connection_manager(
+            crate::redis::RedisOptions::new(redis_url).client()?,
+        )?);
+        Ok(limiter)
+    }
+
+    /// A limiter that enforces limits in this process only.
+    pub fn local() -> Self {
+        Self {
+            #[cfg(feature = "redis")]
+            redis_manager: None,
+            #[cfg(feature = "redis")]
+            rpm_script: Script::new(RPM_SCRIPT),
+            #[cfg(feature = "redis")]
+            gcra_script: Script::new(GCRA_SCRIPT),
+            #[cfg(feature = "redis")]
+            limit_script: Script::new(LIMIT_SCRIPT),
+            #[cfg(feature = "redis")]
+            sliding_rpm_script: Script::new(SLIDING_RPM_SCRIPT),
             default_rpm: 60,
             default_tpm: 100000,
-        })
+            local_buckets: Arc::new(Mutex::new(HashMap::new())),
+            local_windows: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Outcome of taking `amount` from a local bucket, as
+    /// `(allowed, retry_after_ms)`.
+    fn consume_local(bucket: &SharedTokenBucket, amount: u64) -> (bool, u64) {
+        match bucket.consume(amount) {
+            Ok(()) => (true, 0),
+            Err(retry_after) => (false, retry_after.map_or(0, retry_after_ms)),
+        }
     }
 
+    /// The local bucket for `key`, created full (or replaced, when the limit
+    /// changed) with a capacity of `limit` per minute.
+    fn local_bucket(&self, key: String, limit: u64) -> SharedTokenBucket {
+        let mut buckets = self.local_buckets.lock().unwrap_or_else(|e| e.into_inner());
+        match buckets.get(&key) {
+            Some(bucket) if bucket.capacity() == limit => bucket.clone(),
+            _ => {
+                let bucket = SharedTokenBucket::new(TokenBucket::per_minute(limit));
+                buckets.insert(key, bucket.clone());
+                bucket
+            }
+        }
+    }
+
+    /// Count a request costing `amount` tokens against the default RPM and
+    /// TPM limits.  Returns `(allowed, retry_after_ms)`; the retry time is
+    /// 0 when allowed or unknown.
     pub async fn is_allowed(
         &self,
         key: &str,
         amount: u64,
-    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<(bool, u64), Box<dyn std::error::Error + Send + Sync>> {
+        #[cfg(feature = "redis")]
         if let Some(ref manager) = self.redis_manager {
             let mut conn = manager.clone();
 
-            let result: Vec<u64> = redis::cmd("EVAL")
-                .arg(RPM_SCRIPT)
-                .arg(1)
-                .arg(format!("hyperinfer:ratelimit:rpm:{}", key))
-                .arg(self.default_rpm)
-                .arg(60)
-                .query_async(&mut conn)
-                .await?;
-
-            let allowed = result.first().copied().unwrap_or(0);
-            if allowed == 0 {
-                return Ok(false);
-            }
-
-            let tpm_key = format!("hyperinfer:ratelimit:tpm:{}", key);
             let now = std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?
                 .as_millis() as u64;
             let rate = self.default_tpm / 60;
-            let tpm_result: Vec<u64> = redis::cmd("EVAL")
-                .arg(GCRA_SCRIPT)
-                .arg(1)
-                .arg(&tpm_key)
+            let result: Vec<u64> = self
+                .limit_script
+                .key(format!("hyperinfer:ratelimit:rpm:{}", key))
+                .key(format!("hyperinfer:ratelimit:tpm:{}", key))
+                .arg(self.default_rpm)
+                .arg(60)
                 .arg(rate)
                 .arg(self.default_tpm)
                 .arg(now)
                 .arg(amount)
-                .query_async(&mut conn)
+                .invoke_async(&mut conn)
                 .await?;
 
-            Ok(tpm_result.first().copied().unwrap_or(0) == 1)
-        } else {
-            Ok(true)
+            return Ok(gcra_outcome(&result));
         }
+        // Like the Redis script, spend nothing unless both limits allow it.
+        let rpm = self.local_bucket(format!("rpm:{}", key), self.default_rpm);
+        let tpm = self.local_bucket(format!("tpm:{}", key), self.default_tpm);
+        Ok(match rpm.consume_with(1, &tpm, amount) {
+            Ok(()) => (true, 0),
+            Err(retry_after) => (false, retry_after.map_or(0, retry_after_ms)),
+        })
     }
 
     pub async fn check_rpm(
@@ -144,32 +329,131 @@ impl RateLimiter {
         key: &str,
         limit: u64,
     ) -> Result<(bool, u64), Box<dyn std::error::Error + Send + Sync>> {
+        #[cfg(feature = "redis")]
         if let Some(ref manager) = self.redis_manager {
             let mut conn = manager.clone();
 
-            let result: Vec<u64> = redis::cmd("EVAL")
-                .arg(RPM_SCRIPT)
-                .arg(1)
-                .arg(format!("hyperinfer:ratelimit:rpm:{}", key))
+            let result: Vec<u64> = self
+                .rpm_script
+                .key(format!("hyperinfer:ratelimit:rpm:{}", key))
                 .arg(limit)
                 .arg(60)
-                .query_async(&mut conn)
+                .invoke_async(&mut conn)
                 .await?;
 
             let allowed = result.first().copied().unwrap_or(0) == 1;
             let remaining = result.get(1).copied().unwrap_or(0);
-            Ok((allowed, remaining))
-        } else {
-            Ok((true, limit))
+            return Ok((allowed, remaining));
+        }
+        let bucket = self.local_bucket(format!("rpm:{}", key), limit);
+        let allowed = bucket.try_consume(1);
+        Ok((allowed, bucket.available()))
+    }
+
+    /// Count a request against the same fixed window as
+    /// [`RateLimiter::check_rpm`], returning `(allowed, retry_after_ms)`
+    /// like [`RateLimiter::is_allowed`] instead of the remaining count.
+    pub async fn check_rpm_retry(
+        &self,
+        key: &str,
+        limit: u64,
+    ) -> Result<(bool, u64), Box<dyn std::error::Error + Send + Sync>> {
+        #[cfg(feature = "redis")]
+        if let Some(ref manager) = self.redis_manager {
+            let mut conn = manager.clone();
+
+            // The TTL is in seconds, and negative when the key has none.
+            let result: Vec<i64> = self
+                .rpm_script
+                .key(format!("hyperinfer:ratelimit:rpm:{}", key))
+                .arg(limit)
+                .arg(60)
+                .invoke_async(&mut conn)
+                .await?;
+
+            let allowed = result.first().copied().unwrap_or(0) == 1;
+            let ttl = result.get(2).copied().unwrap_or(0).max(0) as u64;
+            return Ok((allowed, if allowed { 0 } else { ttl * 1000 }));
+        }
+        let bucket = self.local_bucket(format!("rpm:{}", key), limit);
+        Ok(Self::consume_local(&bucket, 1))
+    }
+
+    /// Count a request against a per-minute `limit` using the sliding-window
+    /// log, which never admits more than `limit` requests in any 60 seconds.
+    /// Returns `(allowed, retry_after_ms)` like [`RateLimiter::is_allowed`].
+    ///
+    /// Costs one sorted-set entry per admitted request, so prefer
+    /// [`RateLimiter::check_rpm`] unless the limit must be exact.
+    pub async fn check_rpm_sliding(
+        &self,
+        key: &str,
+        limit: u64,
+    ) -> Result<(bool, u64), Box<dyn std::error::Error + Send + Sync>> {
+        const WINDOW: Duration = Duration::from_secs(60);
+        #[cfg(feature = "redis")]
+        if let Some(ref manager) = self.redis_manager {
+            let mut conn = manager.clone();
+
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?
+                .as_millis() as u64;
+
+            let result: Vec<u64> = self
+                .sliding_rpm_script
+                .key(format!("hyperinfer:ratelimit:rpm_sliding:{}", key))
+                .arg(limit)
+                .arg(WINDOW.as_millis() as u64)
+                .arg(now)
+                .arg(uuid::Uuid::new_v4().to_string())
+                .invoke_async(&mut conn)
+                .await?;
+
+            return Ok(gcra_outcome(&result));
+        }
+        let mut windows = self.local_windows.lock().unwrap_or_else(|e| e.into_inner());
+        let log = windows.entry(key.to_string()).or_default();
+        let now = Instant::now();
+        while log
+            .front()
+            .is_some_and(|&t| now.duration_since(t) >= WINDOW)
+        {
+            log.pop_front();
+        }
+        if (log.len() as u64) < limit {
+            log.push_back(now);
+            return Ok((true, 0));
+        }
+        let retry_after = log.front().map_or(0, |&t| {
+            retry_after_ms(WINDOW - now.duration_since(t)).max(1)
+        });
+        Ok((false, retry_after))
+    }
+
+    /// Count a request against `limit` with the given window algorithm.
+    /// Returns `(allowed, retry_after_ms)` like [`RateLimiter::is_allowed`].
+    pub async fn check_rpm_window(
+        &self,
+        key: &str,
+        limit: u64,
+        window: RpmWindow,
+    ) -> Result<(bool, u64), Box<dyn std::error::Error + Send + Sync>> {
+        match window {
+            RpmWindow::Fixed => self.check_rpm_retry(key, limit).await,
+            RpmWindow::Sliding => self.check_rpm_sliding(key, limit).await,
         }
     }
 
+    /// Count `tokens` against a per-minute token `limit`.  Returns
+    /// `(allowed, retry_after_ms)` like [`RateLimiter::is_allowed`].
     pub async fn check_tpm(
         &self,
         key: &str,
         limit: u64,
         tokens: u64,
-    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<(bool, u64), Box<dyn std::error::Error + Send + Sync>> {
+        #[cfg(feature = "redis")]
         if let Some(ref manager) = self.redis_manager {
             let mut conn = manager.clone();
 
@@ -179,28 +463,102 @@ impl RateLimiter {
                 .as_millis() as u64;
             let rate = limit / 60;
 
-            let result: Vec<u64> = redis::cmd("EVAL")
-                .arg(GCRA_SCRIPT)
-                .arg(1)
-                .arg(format!("hyperinfer:ratelimit:tpm:{}", key))
+            let result: Vec<u64> = self
+                .gcra_script
+                .key(format!("hyperinfer:ratelimit:tpm:{}", key))
                 .arg(rate)
                 .arg(limit)
                 .arg(now)
                 .arg(tokens)
+                .invoke_async(&mut conn)
+                .await?;
+
+            return Ok(gcra_outcome(&result));
+        }
+        let bucket = self.local_bucket(format!("tpm:{}", key), limit);
+        Ok(Self::consume_local(&bucket, tokens))
+    }
+
+    /// Where `key` stands against the default limits that
+    /// [`RateLimiter::is_allowed`] enforces.  Read-only.
+    pub async fn get_usage(
+        &self,
+        key: &str,
+    ) -> Result<RateLimitUsage, Box<dyn std::error::Error + Send + Sync>> {
+        let mut usage = RateLimitUsage {
+            key: key.to_string(),
+            rpm_limit: self.default_rpm,
+            requests_used: 0,
+            requests_remaining: self.default_rpm,
+            requests_reset_ms: 0,
+            tpm_limit: self.default_tpm,
+            tokens_debt: 0,
+            tokens_remaining: self.default_tpm,
+            tokens_reset_ms: 0,
+        };
+        #[cfg(feature = "redis")]
+        if let Some(ref manager) = self.redis_manager {
+            let mut conn = manager.clone();
+            let rpm_key = format!("hyperinfer:ratelimit:rpm:{}", key);
+            let (count, pttl, tat): (Option<u64>, i64, Option<f64>) = redis::pipe()
+                .cmd("GET")
+                .arg(&rpm_key)
+                .cmd("PTTL")
+                .arg(&rpm_key)
+                .cmd("GET")
+                .arg(format!("hyperinfer:ratelimit:tpm:{}", key))
                 .query_async(&mut conn)
                 .await?;
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?
+                .as_millis() as f64;
 
-            Ok(result.first().copied().unwrap_or(0) == 1)
-        } else {
-            Ok(true)
+            let count = count.unwrap_or(0);
+            usage.requests_used = count;
+            usage.requests_remaining = self.default_rpm.saturating_sub(count);
+            usage.requests_reset_ms = pttl.max(0) as u64;
+
+            // Mirrors the GCRA script: each token pushes the arrival time
+            // `capacity / rate` ms ahead; up to `capacity` ms of debt is
+            // allowed.
+            let capacity = self.default_tpm as f64;
+            let emission_interval = capacity / (self.default_tpm / 60) as f64;
+            let debt_ms = tat.map_or(0.0, |tat| (tat - now).max(0.0));
+            usage.tokens_debt = (debt_ms / emission_interval).ceil() as u64;
+            usage.tokens_remaining = ((capacity - debt_ms).max(0.0) / emission_interval) as u64;
+            usage.tokens_reset_ms = debt_ms.ceil() as u64;
+            return Ok(usage);
         }
+        let buckets = self.local_buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let local = |name: &str, limit: u64| {
+            buckets
+                .get(&format!("{}:{}", name, key))
+                .filter(|bucket| bucket.capacity() == limit)
+                .map(|bucket| (bucket.available(), bucket.time_to_full()))
+        };
+        if let Some((available, reset)) = local("rpm", self.default_rpm) {
+            usage.requests_used = self.default_rpm - available;
+            usage.requests_remaining = available;
+            usage.requests_reset_ms = retry_after_ms(reset);
+        }
+        if let Some((available, reset)) = local("tpm", self.default_tpm) {
+            usage.tokens_debt = self.default_tpm - available;
+            usage.tokens_remaining = available;
+            usage.tokens_reset_ms = retry_after_ms(reset);
+        }
+        Ok(usage)
     }
 
+    /// Add a request and `tokens_used` to `key`'s usage counters in Redis.
+    /// Nothing is counted without Redis.
+    #[cfg_attr(not(feature = "redis"), allow(unused_variables))]
     pub async fn record_usage(
         &self,
         key: &str,
         tokens_used: u64,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        #[cfg(feature = "redis")]
         if let Some(ref manager) = self.redis_manager {
             let mut conn = manager.clone();
 
@@ -216,11 +574,34 @@ impl RateLimiter {
         }
         Ok(())
     }
+
+    /// Add `tokens` to `key`'s token usage without counting a request, for
+    /// usage charged in pieces while a response streams.  A negative amount
+    /// gives back tokens charged on an estimate that came out high.
+    #[cfg_attr(not(feature = "redis"), allow(unused_variables))]
+    pub async fn adjust_token_usage(
+        &self,
+        key: &str,
+        tokens: i64,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        #[cfg(feature = "redis")]
+        if let Some(ref manager) = self.redis_manager {
+            let mut conn = manager.clone();
+
+            redis::cmd("INCRBY")
+                .arg(format!("{}{}", USAGE_TOKENS_KEY_PREFIX, key))
+                .arg(tokens)
+                .query_async::<()>(&mut conn)
+                .await?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::{Duration, Instant};
 
     #[tokio::test]
     async fn test_rate_limiter_new_without_redis() {
@@ -236,8 +617,8 @@ mod tests {
         let limiter = RateLimiter::new(None).await.unwrap();
         let result = limiter.is_allowed("test-key", 1).await;
         assert!(result.is_ok());
-        // Without Redis, should always allow
-        assert!(result.unwrap());
+        // Without Redis, a fresh local bucket allows the request
+        assert!(result.unwrap().0);
     }
 
     #[tokio::test]
@@ -246,9 +627,9 @@ mod tests {
         let result = limiter.check_rpm("test-key", 100).await;
         assert!(result.is_ok());
         let (allowed, remaining) = result.unwrap();
-        // Without Redis, should always allow
+        // Without Redis, the local bucket starts full
         assert!(allowed);
-        assert_eq!(remaining, 100);
+        assert_eq!(remaining, 99);
     }
 
     #[tokio::test]
@@ -256,8 +637,8 @@ mod tests {
         let limiter = RateLimiter::new(None).await.unwrap();
         let result = limiter.check_tpm("test-key", 1000, 100).await;
         assert!(result.is_ok());
-        // Without Redis, should always allow
-        assert!(result.unwrap());
+        // Without Redis, the local bucket starts full
+        assert!(result.unwrap().0);
     }
 
     #[tokio::test]
@@ -272,13 +653,13 @@ mod tests {
         let bucket = TokenBucket {
             capacity: 100,
             tokens: 100,
-            refill_rate: 10,
+            refill_rate: 10.0,
             last_refill: Instant::now(),
         };
 
         assert_eq!(bucket.capacity, 100);
         assert_eq!(bucket.tokens, 100);
-        assert_eq!(bucket.refill_rate, 10);
+        assert_eq!(bucket.refill_rate, 10.0);
     }
 
     #[test]
@@ -286,7 +667,7 @@ mod tests {
         let bucket = TokenBucket {
             capacity: 50,
             tokens: 25,
-            refill_rate: 5,
+            refill_rate: 5.0,
             last_refill: Instant::now(),
         };
 
@@ -344,15 +725,16 @@ mod tests {
         let limiter = RateLimiter::new(None).await.unwrap();
         let result = limiter.is_allowed("test-key", 0).await;
         assert!(result.is_ok());
-        assert!(result.unwrap());
+        assert!(result.unwrap().0);
     }
 
     #[tokio::test]
     async fn test_rate_limiter_is_allowed_with_large_amount() {
         let limiter = RateLimiter::new(None).await.unwrap();
+        // More than the default TPM can ever hold
         let result = limiter.is_allowed("test-key", 999999).await;
         assert!(result.is_ok());
-        assert!(result.unwrap());
+        assert!(!result.unwrap().0);
     }
 
     #[tokio::test]
@@ -361,11 +743,11 @@ mod tests {
 
         let result1 = limiter.check_rpm("key1", 10).await;
         assert!(result1.is_ok());
-        assert_eq!(result1.unwrap().1, 10);
+        assert_eq!(result1.unwrap().1, 9);
 
         let result2 = limiter.check_rpm("key2", 1000).await;
         assert!(result2.is_ok());
-        assert_eq!(result2.unwrap().1, 1000);
+        assert_eq!(result2.unwrap().1, 999);
     }
 
     #[tokio::test]
@@ -376,4 +758,276 @@ mod tests {
         assert!(limiter.record_usage("key", 200).await.is_ok());
         assert!(limiter.record_usage("key", 300).await.is_ok());
     }
+
+    #[test]
+    fn test_token_bucket_try_consume() {
+        let mut bucket = TokenBucket::new(10, 1.0);
+        assert!(bucket.try_consume(7));
+        assert!(!bucket.try_consume(4));
+        assert!(bucket.try_consume(3));
+        assert_eq!(bucket.tokens, 0);
+        assert!(!bucket.try_consume(11));
+    }
+
+    #[test]
+    fn test_token_bucket_refill() {
+        let mut bucket = TokenBucket::new(100, 10.0);
+        bucket.tokens = 0;
+        bucket.last_refill = Instant::now() - Duration::from_millis(1550);
+        bucket.refill();
+        assert_eq!(bucket.tokens, 15);
+        // The leftover half token is kept for the next refill.
+        assert!(bucket.last_refill.elapsed() >= Duration::from_millis(40));
+
+        bucket.last_refill = Instant::now() - Duration::from_secs(60);
+        bucket.refill();
+        assert_eq!(bucket.tokens, 100);
+    }
+
+    #[test]
+    fn test_token_bucket_per_minute_refills_slowly() {
+        let mut bucket = TokenBucket::per_minute(6);
+        bucket.tokens = 0;
+        bucket.last_refill = Instant::now() - Duration::from_secs(9);
+        bucket.refill();
+        assert_eq!(bucket.tokens, 0);
+        bucket.last_refill = Instant::now() - Duration::from_secs(10);
+        bucket.refill();
+        assert_eq!(bucket.tokens, 1);
+    }
+
+    #[test]
+    fn test_shared_token_bucket_across_threads() {
+        let bucket = SharedTokenBucket::new(TokenBucket::new(100, 0.0));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let bucket = bucket.clone();
+                std::thread::spawn(move || (0..50).filter(|_| bucket.try_consume(1)).count())
+            })
+            .collect();
+        let granted: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(granted, 100);
+        assert_eq!(bucket.available(), 0);
+    }
+
+    #[test]
+    fn test_consume_with_in_either_order() {
+        let a = SharedTokenBucket::new(TokenBucket::new(100, 0.0));
+        let b = SharedTokenBucket::new(TokenBucket::new(100, 0.0));
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let (a, b) = (a.clone(), b.clone());
+                std::thread::spawn(move || {
+                    (0..50)
+                        .filter(|_| {
+                            if i % 2 == 0 {
+                                a.consume_with(1, &b, 1).is_ok()
+                            } else {
+                                b.consume_with(1, &a, 1).is_ok()
+                            }
+                        })
+                        .count()
+                })
+            })
+            .collect();
+        let granted: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(granted, 100);
+        assert_eq!((a.available(), b.available()), (0, 0));
+    }
+
+    #[test]
+    fn test_consume_with_the_same_bucket() {
+        let bucket = SharedTokenBucket::new(TokenBucket::new(10, 0.0));
+        assert!(bucket.consume_with(4, &bucket, 5).is_ok());
+        assert_eq!(bucket.available(), 1);
+        assert!(bucket.consume_with(1, &bucket, 1).is_err());
+        assert_eq!(bucket.available(), 1);
+    }
+
+    #[test]
+    fn test_consume_with_never_satisfiable() {
+        let rpm = SharedTokenBucket::new(TokenBucket::per_minute(60));
+        let tpm = SharedTokenBucket::new(TokenBucket::per_minute(1000));
+        // More tokens than the bucket holds can never be granted, whatever
+        // the other bucket's wait.
+        assert_eq!(rpm.consume_with(1, &tpm, 1001), Err(None));
+        assert_eq!(tpm.consume_with(1001, &rpm, 1), Err(None));
+        assert_eq!((rpm.available(), tpm.available()), (60, 1000));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_enforces_local_rpm() {
+        let limiter = RateLimiter::new(None).await.unwrap();
+        for _ in 0..3 {
+            assert!(limiter.check_rpm("local", 3).await.unwrap().0);
+        }
+        assert_eq!(limiter.check_rpm("local", 3).await.unwrap(), (false, 0));
+        // Other keys have their own bucket.
+        assert!(limiter.check_rpm("other", 3).await.unwrap().0);
+        // A changed limit starts a fresh bucket.
+        assert!(limiter.check_rpm("local", 5).await.unwrap().0);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_enforces_local_tpm() {
+        let limiter = RateLimiter::new(None).await.unwrap();
+        assert_eq!(
+            limiter.check_tpm("local", 1000, 600).await.unwrap(),
+            (true, 0)
+        );
+        // 200 tokens short at 1000/min is about 12 seconds.
+        let (allowed, retry_after_ms) = limiter.check_tpm("local", 1000, 600).await.unwrap();
+        assert!(!allowed);
+        assert!((11_900..=12_000).contains(&retry_after_ms));
+        assert!(limiter.check_tpm("local", 1000, 400).await.unwrap().0);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_local_retry_after_unknown_when_never_allowed() {
+        let limiter = RateLimiter::new(None).await.unwrap();
+        assert_eq!(
+            limiter.check_tpm("local", 1000, 5000).await.unwrap(),
+            (false, 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_usage_unseen_key_has_full_headroom() {
+        let limiter = RateLimiter::local();
+        let usage = limiter.get_usage("fresh").await.unwrap();
+        assert_eq!(usage.key, "fresh");
+        assert_eq!(usage.requests_used, 0);
+        assert_eq!(usage.requests_remaining, 60);
+        assert_eq!(usage.tokens_debt, 0);
+        assert_eq!(usage.tokens_remaining, 100000);
+        assert_eq!(usage.tokens_reset_ms, 0);
+        // Reading usage does not start tracking the key.
+        assert!(limiter.local_buckets.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_usage_reports_local_consumption() {
+        let limiter = RateLimiter::local();
+        for _ in 0..3 {
+            assert!(limiter.is_allowed("busy", 2500).await.unwrap().0);
+        }
+        let usage = limiter.get_usage("busy").await.unwrap();
+        assert_eq!(usage.requests_used, 3);
+        assert_eq!(usage.requests_remaining, 57);
+        assert!(usage.requests_reset_ms > 0 && usage.requests_reset_ms <= 3000);
+        assert_eq!(usage.tokens_debt, 7500);
+        assert_eq!(usage.tokens_remaining, 92500);
+        // 7500 tokens at 100000/min is 4.5 seconds.
+        assert!((4400..=4500).contains(&usage.tokens_reset_ms));
+    }
+
+    #[tokio::test]
+    async fn test_sliding_window_local() {
+        let limiter = RateLimiter::local();
+        for _ in 0..3 {
+            assert_eq!(
+                limiter.check_rpm_sliding("strict", 3).await.unwrap(),
+                (true, 0)
+            );
+        }
+        let (allowed, retry_after_ms) = limiter.check_rpm_sliding("strict", 3).await.unwrap();
+        assert!(!allowed);
+        assert!((59_000..=60_000).contains(&retry_after_ms));
+        assert!(limiter.check_rpm_sliding("other", 3).await.unwrap().0);
+    }
+
+    #[tokio::test]
+    async fn test_sliding_window_local_forgets_old_requests() {
+        let limiter = RateLimiter::local();
+        limiter.local_windows.lock().unwrap().insert(
+            "strict".to_string(),
+            VecDeque::from([
+                Instant::now() - Duration::from_secs(61),
+                Instant::now() - Duration::from_secs(30),
+            ]),
+        );
+        assert!(limiter.check_rpm_sliding("strict", 2).await.unwrap().0);
+        let (allowed, retry_after_ms) = limiter.check_rpm_sliding("strict", 2).await.unwrap();
+        assert!(!allowed);
+        assert!((29_000..=30_000).contains(&retry_after_ms));
+    }
+
+    #[tokio::test]
+    async fn test_tpm_rejection_does_not_spend_rpm() {
+        let limiter = RateLimiter::local();
+        assert!(limiter.is_allowed("k", 100000).await.unwrap().0);
+        let rpm = limiter.local_bucket("rpm:k".to_string(), limiter.default_rpm);
+        assert_eq!(rpm.available(), 59);
+
+        let (allowed, retry_after_ms) = limiter.is_allowed("k", 1000).await.unwrap();
+        assert!(!allowed && retry_after_ms > 0);
+        assert_eq!(rpm.available(), 59);
+    }
+
+    #[tokio::test]
+    async fn test_check_rpm_retry_reports_the_wait() {
+        let limiter = RateLimiter::local();
+        for _ in 0..3 {
+            assert_eq!(
+                limiter.check_rpm_retry("local", 3).await.unwrap(),
+                (true, 0)
+            );
+        }
+        let (allowed, retry_after_ms) = limiter.check_rpm_retry("local", 3).await.unwrap();
+        assert!(!allowed);
+        // One request's worth of a 3 RPM bucket refills in about 20 seconds.
+        assert!(retry_after_ms > 19_000 && retry_after_ms <= 20_000);
+        // It shares its window with check_rpm.
+        assert_eq!(limiter.check_rpm("local", 3).await.unwrap(), (false, 0));
+    }
+
+    #[tokio::test]
+    async fn test_check_rpm_window_dispatch() {
+        let limiter = RateLimiter::local();
+        assert!(
+            limiter
+                .check_rpm_window("k", 1, RpmWindow::Fixed)
+                .await
+                .unwrap()
+                .0
+        );
+        let (allowed, retry_after_ms) = limiter
+            .check_rpm_window("k", 1, RpmWindow::Fixed)
+            .await
+            .unwrap();
+        assert!(!allowed && retry_after_ms > 0);
+        // The sliding log is kept separately from the fixed-window counter.
+        assert!(
+            limiter
+                .check_rpm_window("k", 1, RpmWindow::Sliding)
+                .await
+                .unwrap()
+                .0
+        );
+        let (allowed, retry_after_ms) = limiter
+            .check_rpm_window("k", 1, RpmWindow::Sliding)
+            .await
+            .unwrap();
+        assert!(!allowed && retry_after_ms > 0);
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn test_gcra_outcome() {
+        assert_eq!(gcra_outcome(&[1, 0]), (true, 0));
+        assert_eq!(gcra_outcome(&[0, 1500]), (false, 1500));
+        assert_eq!(gcra_outcome(&[]), (false, 0));
+    }
+
+    #[test]
+    fn test_token_bucket_retry_after() {
+        let mut bucket = TokenBucket::new(10, 2.0);
+        assert_eq!(bucket.retry_after(5), Some(Duration::ZERO));
+        bucket.tokens = 0;
+        let wait = bucket.retry_after(4).unwrap();
+        assert!(wait <= Duration::from_secs(2) && wait > Duration::from_millis(1900));
+        assert_eq!(bucket.retry_after(11), None);
+        bucket.refill_rate = 0.0;
+        assert_eq!(bucket.retry_after(1), None);
+    }
 }
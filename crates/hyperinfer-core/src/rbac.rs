@@ -0,0 +1,173 @@
+//! Role-based access control for the control plane
+//!
+//! Every control-plane user has a [`Role`], and every admin API route
+//! requires an [`Action`].  Roles are ordered: each one allows everything the
+//! role below it does.
+//!
+//! | Role   | Allows                                                  |
+//! |--------|---------------------------------------------------------|
+//! | viewer | reading teams, usage, config and alerts                 |
+//! | member | the above, plus issuing and editing API keys            |
+//! | admin  | the above, plus managing teams, users, quotas, config,  |
+//! |        | and reading the full config and exports                 |
+//! | owner  | everything, including organizations                     |
+//!
+//! Viewers and members are confined to their own team's resources; admins
+//! and owners act across teams.
+//!
+//! Granting a role is separately restricted: only owners may make or unmake
+//! owners, so an admin cannot promote themselves or demote the people above
+//! them.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    #[default]
+    Member,
+    Admin,
+    Owner,
+}
+
+/// Something a control-plane request does, checked against the caller's role.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Read any control-plane resource.
+    Read,
+    /// Read the full config or a bulk export, which carry provider
+    /// headers such as Azure's `api-key`.
+    ReadSecrets,
+    /// Issue API keys and edit their metadata.
+    ManageKeys,
+    /// Create and delete teams and users and set their billing, quotas,
+    /// budget policies and alert rules.
+    ManageTeams,
+    /// Change routing config: providers, model aliases, prices and config
+    /// rollbacks.
+    ManageConfig,
+    /// Change users' roles.
+    ManageRoles,
+    /// Create organizations and move teams between them.
+    ManageOrganizations,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Viewer, Role::Member, Role::Admin, Role::Owner];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Member => "member",
+            Role::Admin => "admin",
+            Role::Owner => "owner",
+        }
+    }
+
+    /// Least role allowed to perform `action`.
+    fn required_for(action: Action) -> Role {
+        match action {
+            Action::Read => Role::Viewer,
+            Action::ManageKeys => Role::Member,
+            Action::ReadSecrets
+            | Action::ManageTeams
+            | Action::ManageConfig
+            | Action::ManageRoles => Role::Admin,
+            Action::ManageOrganizations => Role::Owner,
+        }
+    }
+
+    pub fn allows(self, action: Action) -> bool {
+        self >= Role::required_for(action)
+    }
+
+    /// Whether this role may act on teams other than the user's own.
+    pub fn spans_teams(self) -> bool {
+        self >= Role::Admin
+    }
+
+    /// Whether this role may give `role` to a user, or take it away.
+    pub fn can_grant(self, role: Role) -> bool {
+        match self {
+            Role::Owner => true,
+            Role::Admin => role != Role::Owner,
+            Role::Member | Role::Viewer => false,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown role '{}': expected owner, admin, member or viewer",
+                    s
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roles_allow_their_actions_and_below() {
+        assert!(Role::Viewer.allows(Action::Read));
+        assert!(!Role::Viewer.allows(Action::ManageKeys));
+        assert!(Role::Member.allows(Action::ManageKeys));
+        assert!(!Role::Member.allows(Action::ManageTeams));
+        assert!(!Role::Member.allows(Action::ReadSecrets));
+        assert!(Role::Admin.allows(Action::ReadSecrets));
+        assert!(Role::Admin.allows(Action::ManageConfig));
+        assert!(Role::Admin.allows(Action::ManageRoles));
+        assert!(!Role::Admin.allows(Action::ManageOrganizations));
+        assert!(Role::Owner.allows(Action::ManageOrganizations));
+    }
+
+    #[test]
+    fn test_only_admins_and_owners_span_teams() {
+        assert!(!Role::Viewer.spans_teams());
+        assert!(!Role::Member.spans_teams());
+        assert!(Role::Admin.spans_teams());
+        assert!(Role::Owner.spans_teams());
+    }
+
+    #[test]
+    fn test_only_owners_grant_owner() {
+        assert!(Role::Owner.can_grant(Role::Owner));
+        assert!(Role::Admin.can_grant(Role::Admin));
+        assert!(!Role::Admin.can_grant(Role::Owner));
+        assert!(!Role::Member.can_grant(Role::Viewer));
+    }
+
+    #[test]
+    fn test_role_round_trips_through_str_and_serde() {
+        for role in Role::ALL {
+            assert_eq!(role.as_str().parse::<Role>(), Ok(role));
+            assert_eq!(
+                serde_json::to_string(&role).unwrap(),
+                format!("\"{}\"", role)
+            );
+        }
+        assert!("superuser".parse::<Role>().is_err());
+    }
+}
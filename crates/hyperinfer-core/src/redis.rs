@@ -2,37 +2,313 @@
 //!
 //! Provides functionality for Redis-based configuration and policy updates.
 
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use futures_util::stream::StreamExt;
-use redis::aio::ConnectionManager;
-use redis::Client;
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use redis::{Client, ClientTlsConfig, ConnectionAddr, IntoConnectionInfo, TlsCertificates};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::error::ConfigError;
 use crate::types::Config;
 
 pub const CONFIG_CHANNEL: &str = "hyperinfer:config_updates";
 pub const CONFIG_KEY: &str = "hyperinfer:config";
+/// Counter the version of each published config is taken from.
+pub const CONFIG_VERSION_KEY: &str = "hyperinfer:config:version";
+/// Sorted set of recently published configs, scored by version.
+pub const CONFIG_HISTORY_KEY: &str = "hyperinfer:config:history";
+/// Published configs kept in [`CONFIG_HISTORY_KEY`] for rollback.
+pub const CONFIG_HISTORY_LEN: usize = 20;
+/// Prefix of the per-instance heartbeat keys data planes report under.
+pub const FLEET_KEY_PREFIX: &str = "hyperinfer:fleet:";
+/// How often a data plane reports its config version and load.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// Heartbeats expire after this long, so instances that stop reporting drop
+/// out of the fleet.
+pub const HEARTBEAT_TTL: Duration = Duration::from_secs(45);
+/// Hash of API key id to the unix time in milliseconds the data plane last
+/// saw it used, drained into Postgres by the control plane.
+pub const KEY_LAST_USED_KEY: &str = "hyperinfer:api_keys:last_used";
+pub const POLICY_CHANNEL: &str = "hyperinfer:policy_updates";
+/// Data-plane events that are not usage records, e.g. rate-limit rejections.
+pub const EVENTS_CHANNEL: &str = "hyperinfer:events";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConfigUpdate {
     pub config: Config,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A per-key policy change pushed to data-plane clients.
+///
+/// `key` is the SHA-256 hex digest of the API key, as stored in
+/// `api_keys.key_hash`; the control plane never sees raw keys.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PolicyUpdate {
     pub key: String,
     pub action: PolicyAction,
     pub reason: Option<String>,
+    /// Reduced requests-per-minute limit for [`PolicyAction::Throttle`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpm_limit: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum PolicyAction {
     Revoke,
     Update,
+    /// Soft limit reached; requests continue but clients log a warning.
+    Warn,
+    /// Cap the key at `rpm_limit` requests per minute.
+    Throttle,
+    /// Lift any earlier `Revoke` / `Throttle` for the key.
+    Restore,
+}
+
+/// A request a client turned away because its key was over a rate limit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimitRejection {
+    /// SHA-256 hex digest of the API key.
+    pub key_hash: String,
+    /// Model as requested, before alias resolution.
+    pub model: String,
+    pub reason: String,
+    /// Unix time in milliseconds.
+    pub timestamp: u64,
+}
+
+/// What a data-plane instance last reported: the config version it runs
+/// and how busy it is.  Fields after `reported_at` are missing from older
+/// clients' heartbeats.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InstanceHeartbeat {
+    pub instance_id: String,
+    pub config_version: u64,
+    /// Unix time in milliseconds.
+    pub reported_at: u64,
+    /// Service embedding the client, as it names itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    /// Version of the client library.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_version: Option<String>,
+    /// Requests per second since the previous heartbeat.
+    #[serde(default)]
+    pub qps: f64,
+    /// Requests and streams running when the heartbeat was taken.
+    #[serde(default)]
+    pub in_flight: u64,
+    /// Whether the instance's usage records are reaching Redis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub telemetry_connected: Option<bool>,
+}
+
+/// An instance that [`ConfigManager::spawn_heartbeat`] reports for.
+#[async_trait]
+pub trait HeartbeatSource: Send + Sync + 'static {
+    /// The instance's heartbeat as of now; `reported_at` is filled in by
+    /// the caller.
+    async fn heartbeat(&self) -> InstanceHeartbeat;
+}
+
+/// Holder of the running config that
+/// [`ConfigManager::subscribe_to_config_updates`] applies updates to.
+#[async_trait]
+pub trait ConfigTarget: Send + Sync + 'static {
+    async fn config_version(&self) -> u64;
+
+    /// Replace the config with `update` unless it is older than the one
+    /// held, as [`Config::apply_update`] does.  Returns whether it was
+    /// applied.
+    async fn apply_config(&self, update: Config) -> bool;
+}
+
+#[async_trait]
+impl ConfigTarget for RwLock<Config> {
+    async fn config_version(&self) -> u64 {
+        self.read().await.version
+    }
+
+    async fn apply_config(&self, update: Config) -> bool {
+        self.write().await.apply_update(update)
+    }
+}
+
+/// A connection manager that connects on first use and reconnects with
+/// backoff, for components that must start while Redis is down.
+pub fn lazy_connection_manager(client: Client) -> redis::RedisResult<ConnectionManager> {
+    ConnectionManager::new_lazy_with_config(client, ConnectionManagerConfig::new())
+}
+
+/// How to reach Redis: a `redis://` or `rediss://` (TLS) URL plus the ACL
+/// credentials and certificates a URL cannot carry.  Every component opens
+/// its client through [`RedisOptions::client`], so they all connect the
+/// same way.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RedisOptions {
+    pub url: String,
+    /// ACL user, replacing any in the URL.
+    pub username: Option<String>,
+    /// Password, replacing any in the URL.
+    pub password: Option<String>,
+    /// PEM CA certificates to verify the server with instead of the system
+    /// trust store.  Requires a `rediss://` URL.
+    pub ca_cert_path: Option<PathBuf>,
+    /// PEM client certificate and key for mutual TLS.  Require a
+    /// `rediss://` URL and must be set together.
+    pub client_cert_path: Option<PathBuf>,
+    pub client_key_path: Option<PathBuf>,
+}
+
+impl RedisOptions {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Options from `REDIS_URL` (default `redis://localhost:6379`),
+    /// `REDIS_USERNAME`, `REDIS_PASSWORD`, `REDIS_CA_CERT_PATH`,
+    /// `REDIS_CLIENT_CERT_PATH` and `REDIS_CLIENT_KEY_PATH`.
+    pub fn from_env() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Self {
+            url: var("REDIS_URL").unwrap_or_else(|| "redis://localhost:6379".to_string()),
+            username: var("REDIS_USERNAME"),
+            password: var("REDIS_PASSWORD"),
+            ca_cert_path: var("REDIS_CA_CERT_PATH").map(PathBuf::from),
+            client_cert_path: var("REDIS_CLIENT_CERT_PATH").map(PathBuf::from),
+            client_key_path: var("REDIS_CLIENT_KEY_PATH").map(PathBuf::from),
+        }
+    }
+
+    pub fn with_credentials(mut self, username: Option<String>, password: String) -> Self {
+        self.username = username;
+        self.password = Some(password);
+        self
+    }
+
+    pub fn with_ca_cert(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ca_cert_path = Some(path.into());
+        self
+    }
+
+    pub fn with_client_cert(mut self, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        self.client_cert_path = Some(cert.into());
+        self.client_key_path = Some(key.into());
+        self
+    }
+
+    /// A client for these options.  Certificates are read now; nothing
+    /// connects until the client is used.
+    pub fn client(&self) -> redis::RedisResult<Client> {
+        let mut info = self.url.as_str().into_connection_info()?;
+        if self.username.is_some() || self.password.is_some() {
+            let mut settings = info.redis_settings().clone();
+            if let Some(username) = &self.username {
+                settings = settings.set_username(username);
+            }
+            if let Some(password) = &self.password {
+                settings = settings.set_password(password);
+            }
+            info = info.set_redis_settings(settings);
+        }
+        let tls = matches!(info.addr(), ConnectionAddr::TcpTls { .. });
+        if tls {
+            // Both rustls backends are linked in through other crates, so
+            // rustls cannot pick one by itself.  The error only says one
+            // is installed already.
+            let _ = rustls::crypto::ring::default_provider().install_default();
+        }
+
+        let client_tls = match (&self.client_cert_path, &self.client_key_path) {
+            (Some(cert), Some(key)) => Some(ClientTlsConfig {
+                client_cert: std::fs::read(cert)?,
+                client_key: std::fs::read(key)?,
+            }),
+            (None, None) => None,
+            _ => {
+                return Err(invalid_options(
+                    "a Redis client certificate and key must be set together",
+                ))
+            }
+        };
+        let root_cert = self.ca_cert_path.as_ref().map(std::fs::read).transpose()?;
+        if client_tls.is_none() && root_cert.is_none() {
+            return Client::open(info);
+        }
+        if !tls {
+            return Err(invalid_options(
+                "Redis certificates require a rediss:// URL",
+            ));
+        }
+        Client::build_with_tls(
+            info,
+            TlsCertificates {
+                client_tls,
+                root_cert,
+            },
+        )
+    }
+}
+
+fn invalid_options(message: &'static str) -> redis::RedisError {
+    redis::RedisError::from((redis::ErrorKind::InvalidClientConfig, message))
+}
+
+/// A Redis client and one multiplexed connection to it, shared by the
+/// components of a data-plane client (rate limiter, telemetry, cache,
+/// config sync) so they hold a single connection between them and are all
+/// configured from one place.  Pub/sub subscriptions need connections of
+/// their own and open them from [`RedisHandle::client`].
+#[derive(Clone)]
+pub struct RedisHandle {
+    client: Client,
+    manager: ConnectionManager,
+}
+
+impl RedisHandle {
+    /// Connect to `redis_url` now, failing if Redis is unreachable.
+    pub async fn connect(redis_url: &str) -> redis::RedisResult<Self> {
+        Self::connect_with(&RedisOptions::new(redis_url)).await
+    }
+
+    /// Connect to `redis_url` on first use, reconnecting with backoff, so
+    /// the handle can be created while Redis is unreachable.
+    pub fn lazy(redis_url: &str) -> redis::RedisResult<Self> {
+        Self::lazy_with(&RedisOptions::new(redis_url))
+    }
+
+    /// Like [`RedisHandle::connect`], with TLS and ACL options.
+    pub async fn connect_with(options: &RedisOptions) -> redis::RedisResult<Self> {
+        let client = options.client()?;
+        let manager = ConnectionManager::new(client.clone()).await?;
+        Ok(Self { client, manager })
+    }
+
+    /// Like [`RedisHandle::lazy`], with TLS and ACL options.
+    pub fn lazy_with(options: &RedisOptions) -> redis::RedisResult<Self> {
+        let client = options.client()?;
+        let manager = lazy_connection_manager(client.clone())?;
+        Ok(Self { client, manager })
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The shared connection.  Clones are cheap and multiplex over it.
+    pub fn connection(&self) -> ConnectionManager {
+        self.manager.clone()
+    }
 }
 
 #[derive(Clone)]
@@ -43,7 +319,7 @@ pub struct ConfigManager {
 
 impl ConfigManager {
     pub async fn new(redis_url: &str) -> Result<Self, ConfigError> {
-        let client = Client::open(redis_url)?;
+        let client = RedisOptions::new(redis_url).client()?;
         let manager = ConnectionManager::new(client.clone()).await?;
         Ok(Self {
             client: Arc::new(client),
@@ -51,9 +327,31 @@ impl ConfigManager {
         })
     }
 
-    pub async fn subscribe_to_config_updates(
+    /// A manager that connects on first use instead of up front, so it can
+    /// be created while Redis is unreachable.
+    pub fn new_lazy(redis_url: &str) -> Result<Self, ConfigError> {
+        let client = RedisOptions::new(redis_url).client()?;
+        let manager = lazy_connection_manager(client.clone())?;
+        Ok(Self {
+            client: Arc::new(client),
+            manager,
+        })
+    }
+
+    /// A manager on the shared connection of `redis`.
+    pub fn with_redis(redis: &RedisHandle) -> Self {
+        Self {
+            client: Arc::new(redis.client().clone()),
+            manager: redis.connection(),
+        }
+    }
+
+    /// Apply published configs to `config` until the returned task is
+    /// aborted.  Every reconnect goes through this manager's client, so the
+    /// database, credentials and TLS settings it was built with carry over.
+    pub async fn subscribe_to_config_updates<T: ConfigTarget + ?Sized>(
         &self,
-        config: Arc<RwLock<Config>>,
+        config: Arc<T>,
     ) -> Result<tokio::task::JoinHandle<()>, ConfigError> {
         let client = Arc::clone(&self.client);
 
@@ -89,10 +387,15 @@ impl ConfigManager {
                             }
                         };
 
-                        {
-                            let mut cfg = config.write().await;
-                            *cfg = new_config;
-                            info!("Config updated via Pub/Sub");
+                        let version = new_config.version;
+                        if config.apply_config(new_config).await {
+                            info!("Config updated via Pub/Sub to version {}", version);
+                        } else {
+                            info!(
+                                "Ignoring config version {}, already at {}",
+                                version,
+                                config.config_version().await
+                            );
                         }
                     }
                     Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
@@ -120,6 +423,8 @@ impl ConfigManager {
         Ok(handle)
     }
 
+    /// Pass published policy updates to `callback`, reconnecting like
+    /// [`ConfigManager::subscribe_to_config_updates`].
     pub async fn subscribe_to_policy_updates(
         &self,
         callback: impl Fn(PolicyUpdate) + Send + Sync + 'static,
@@ -132,7 +437,7 @@ impl ConfigManager {
             loop {
                 let result = async {
                     let mut pubsub = client.get_async_pubsub().await?;
-                    pubsub.subscribe("hyperinfer:policy_updates").await?;
+                    pubsub.subscribe(POLICY_CHANNEL).await?;
 
                     info!("Subscribed to Redis policy updates channel");
 
@@ -196,46 +501,182 @@ impl ConfigManager {
                 quotas: std::collections::HashMap::new(),
                 model_aliases: std::collections::HashMap::new(),
                 default_provider: None,
+                ..Default::default()
             }),
         }
     }
 
-    pub async fn publish_config_update(&self, config: &Config) -> Result<(), ConfigError> {
+    /// Stamp `config` with the next version, store it and its history entry,
+    /// and push it to subscribers.  Returns the version.
+    pub async fn publish_config_update(&self, config: &Config) -> Result<u64, ConfigError> {
         let mut conn = self.manager.clone();
 
-        // Store config first so it's available when subscribers receive notification
-        let config_bytes = serde_json::to_vec(config)?;
-
-        redis::cmd("SET")
-            .arg(CONFIG_KEY)
-            .arg(config_bytes)
-            .query_async::<()>(&mut conn)
+        let version: u64 = redis::cmd("INCR")
+            .arg(CONFIG_VERSION_KEY)
+            .query_async(&mut conn)
             .await?;
-
         let update = ConfigUpdate {
-            config: config.clone(),
+            config: Config {
+                version,
+                ..config.clone()
+            },
         };
-
+        let config_bytes = serde_json::to_vec(&update.config)?;
         let payload = serde_json::to_string(&update)?;
 
-        redis::cmd("PUBLISH")
+        // Store config first so it's available when subscribers receive
+        // notification.
+        redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(CONFIG_KEY)
+            .arg(&config_bytes)
+            .ignore()
+            .cmd("ZADD")
+            .arg(CONFIG_HISTORY_KEY)
+            .arg(version)
+            .arg(&config_bytes)
+            .ignore()
+            .cmd("ZREMRANGEBYRANK")
+            .arg(CONFIG_HISTORY_KEY)
+            .arg(0)
+            .arg(-(CONFIG_HISTORY_LEN as i64) - 1)
+            .ignore()
+            .cmd("PUBLISH")
             .arg(CONFIG_CHANNEL)
             .arg(&payload)
+            .ignore()
             .query_async::<()>(&mut conn)
             .await?;
 
-        info!("Published config update to channel: {}", CONFIG_CHANNEL);
+        info!(
+            "Published config version {} to channel: {}",
+            version, CONFIG_CHANNEL
+        );
+
+        Ok(version)
+    }
+
+    /// Published config `version`, if it is still in the history.
+    pub async fn fetch_config_version(&self, version: u64) -> Result<Option<Config>, ConfigError> {
+        let mut conn = self.manager.clone();
+
+        let data: Vec<Vec<u8>> = redis::cmd("ZRANGEBYSCORE")
+            .arg(CONFIG_HISTORY_KEY)
+            .arg(version)
+            .arg(version)
+            .query_async(&mut conn)
+            .await?;
+
+        match data.first() {
+            Some(bytes) => Ok(Some(serde_json::from_slice(bytes)?)),
+            None => Ok(None),
+        }
+    }
 
+    /// Record `heartbeat` under its instance's key, expiring after
+    /// [`HEARTBEAT_TTL`].
+    pub async fn report_heartbeat(&self, heartbeat: &InstanceHeartbeat) -> Result<(), ConfigError> {
+        let mut conn = self.manager.clone();
+
+        let payload = serde_json::to_vec(heartbeat)?;
+        redis::cmd("SET")
+            .arg(format!("{}{}", FLEET_KEY_PREFIX, heartbeat.instance_id))
+            .arg(payload)
+            .arg("PX")
+            .arg(HEARTBEAT_TTL.as_millis() as u64)
+            .query_async::<()>(&mut conn)
+            .await?;
         Ok(())
     }
 
+    /// Heartbeats of every instance that reported within [`HEARTBEAT_TTL`].
+    pub async fn fleet_heartbeats(&self) -> Result<Vec<InstanceHeartbeat>, ConfigError> {
+        let mut conn = self.manager.clone();
+
+        let mut keys: Vec<String> = Vec::new();
+        let mut cursor = 0u64;
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(format!("{}*", FLEET_KEY_PREFIX))
+                .arg("COUNT")
+                .arg(100)
+                .query_async(&mut conn)
+                .await?;
+            keys.extend(batch);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Keys can expire between SCAN and MGET.
+        let values: Vec<Option<Vec<u8>>> =
+            redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+        let mut heartbeats = Vec::with_capacity(values.len());
+        for bytes in values.into_iter().flatten() {
+            heartbeats.push(serde_json::from_slice(&bytes)?);
+        }
+        Ok(heartbeats)
+    }
+
+    /// Report `source`'s heartbeat every [`HEARTBEAT_INTERVAL`] until the
+    /// task is aborted.
+    pub fn spawn_heartbeat<S: HeartbeatSource + ?Sized>(
+        &self,
+        source: Arc<S>,
+    ) -> tokio::task::JoinHandle<()> {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+            loop {
+                interval.tick().await;
+                let heartbeat = InstanceHeartbeat {
+                    reported_at: chrono::Utc::now().timestamp_millis() as u64,
+                    ..source.heartbeat().await
+                };
+                if let Err(e) = manager.report_heartbeat(&heartbeat).await {
+                    warn!(
+                        "Failed to report heartbeat for {}: {}",
+                        heartbeat.instance_id, e
+                    );
+                }
+            }
+        })
+    }
+
+    /// Take every API key use recorded under [`KEY_LAST_USED_KEY`], clearing
+    /// the hash in the same transaction so no use is taken twice or lost.
+    pub async fn take_api_key_uses(&self) -> Result<HashMap<String, DateTime<Utc>>, ConfigError> {
+        let mut conn = self.manager.clone();
+
+        let (uses,): (HashMap<String, i64>,) = redis::pipe()
+            .atomic()
+            .cmd("HGETALL")
+            .arg(KEY_LAST_USED_KEY)
+            .cmd("DEL")
+            .arg(KEY_LAST_USED_KEY)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+        Ok(uses
+            .into_iter()
+            .filter_map(|(id, ms)| Some((id, DateTime::from_timestamp_millis(ms)?)))
+            .collect())
+    }
+
     pub async fn publish_policy_update(&self, update: &PolicyUpdate) -> Result<(), ConfigError> {
         let mut conn = self.manager.clone();
 
         let payload = serde_json::to_string(update)?;
 
         redis::cmd("PUBLISH")
-            .arg("hyperinfer:policy_updates")
+            .arg(POLICY_CHANNEL)
             .arg(&payload)
             .query_async::<()>(&mut conn)
             .await?;
@@ -259,6 +700,7 @@ mod tests {
             quotas: std::collections::HashMap::new(),
             model_aliases: std::collections::HashMap::new(),
             default_provider: Some(Provider::OpenAI),
+            ..Default::default()
         };
 
         let update = ConfigUpdate {
@@ -271,12 +713,67 @@ mod tests {
         assert_eq!(deserialized.config.default_provider, Some(Provider::OpenAI));
     }
 
+    #[tokio::test]
+    async fn test_redis_handle_is_shared() {
+        assert!(RedisHandle::lazy("not a url").is_err());
+
+        // Created while Redis is unreachable; components built from it
+        // share its client and connection.
+        let redis = RedisHandle::lazy("redis://127.0.0.1:1").unwrap();
+        let manager = ConfigManager::with_redis(&redis);
+        assert_eq!(
+            format!("{:?}", manager.client.get_connection_info()),
+            format!("{:?}", redis.client().get_connection_info())
+        );
+    }
+
+    #[test]
+    fn test_redis_options_apply_credentials() {
+        let client = RedisOptions::new("redis://old:pw@127.0.0.1:6380/2")
+            .with_credentials(Some("app".to_string()), "secret".to_string())
+            .client()
+            .unwrap();
+        let info = client.get_connection_info().redis_settings();
+        assert_eq!(info.username(), Some("app"));
+        assert_eq!(info.password(), Some("secret"));
+        assert_eq!(info.db(), 2);
+
+        let url_only = RedisOptions::new("redis://:pw@127.0.0.1").client().unwrap();
+        assert_eq!(
+            url_only.get_connection_info().redis_settings().password(),
+            Some("pw")
+        );
+    }
+
+    #[test]
+    fn test_redis_options_reject_misplaced_certificates() {
+        let ca = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+        let err = RedisOptions::new("redis://127.0.0.1")
+            .with_ca_cert(ca)
+            .client()
+            .unwrap_err();
+        assert_eq!(err.kind(), redis::ErrorKind::InvalidClientConfig);
+
+        let half_pair = RedisOptions {
+            client_cert_path: Some(ca.into()),
+            ..RedisOptions::new("rediss://127.0.0.1")
+        };
+        assert_eq!(
+            half_pair.client().unwrap_err().kind(),
+            redis::ErrorKind::InvalidClientConfig
+        );
+
+        let missing = RedisOptions::new("rediss://127.0.0.1").with_ca_cert("/nonexistent/ca.pem");
+        assert!(missing.client().is_err());
+    }
+
     #[test]
     fn test_policy_update_serialization() {
         let update = PolicyUpdate {
             key: "test-key".to_string(),
             action: PolicyAction::Revoke,
             reason: Some("Testing".to_string()),
+            rpm_limit: None,
         };
 
         let json = serde_json::to_string(&update).unwrap();
@@ -300,12 +797,37 @@ mod tests {
         assert_eq!(json, "\"update\"");
     }
 
+    #[test]
+    fn test_policy_update_throttle_roundtrip() {
+        let update = PolicyUpdate {
+            key: "abc123".to_string(),
+            action: PolicyAction::Throttle,
+            reason: Some("Budget exceeded".to_string()),
+            rpm_limit: Some(5),
+        };
+
+        let json = serde_json::to_string(&update).unwrap();
+        assert!(json.contains("\"throttle\""));
+        let deserialized: PolicyUpdate = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.action, PolicyAction::Throttle);
+        assert_eq!(deserialized.rpm_limit, Some(5));
+    }
+
+    #[test]
+    fn test_policy_update_without_rpm_limit_field() {
+        let json = r#"{"key":"k","action":"revoke","reason":null}"#;
+        let update: PolicyUpdate = serde_json::from_str(json).unwrap();
+        assert_eq!(update.action, PolicyAction::Revoke);
+        assert_eq!(update.rpm_limit, None);
+    }
+
     #[test]
     fn test_policy_update_without_reason() {
         let update = PolicyUpdate {
             key: "key123".to_string(),
             action: PolicyAction::Update,
             reason: None,
+            rpm_limit: None,
         };
 
         let json = serde_json::to_string(&update).unwrap();
@@ -321,6 +843,7 @@ mod tests {
             key: "clone-key".to_string(),
             action: PolicyAction::Revoke,
             reason: Some("Clone test".to_string()),
+            rpm_limit: None,
         };
 
         let cloned = update.clone();
@@ -336,6 +859,7 @@ mod tests {
             quotas: std::collections::HashMap::new(),
             model_aliases: std::collections::HashMap::new(),
             default_provider: None,
+            ..Default::default()
         };
 
         let update = ConfigUpdate { config };
@@ -347,6 +871,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_instance_heartbeat_roundtrip() {
+        let heartbeat = InstanceHeartbeat {
+            instance_id: "dp-1".to_string(),
+            config_version: 12,
+            reported_at: 1_700_000_000_000,
+            service: Some("checkout".to_string()),
+            qps: 2.5,
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&heartbeat).unwrap();
+        let deserialized: InstanceHeartbeat = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, heartbeat);
+
+        // Heartbeats from clients that report only their config version.
+        let old: InstanceHeartbeat =
+            serde_json::from_str(r#"{"instance_id":"dp-0","config_version":3,"reported_at":1}"#)
+                .unwrap();
+        assert_eq!(old.qps, 0.0);
+        assert_eq!(old.service, None);
+    }
+
     #[test]
     fn test_config_channel_constant() {
         assert_eq!(CONFIG_CHANNEL, "hyperinfer:config_updates");
@@ -387,6 +933,7 @@ mod tests {
             quotas: std::collections::HashMap::new(),
             model_aliases: std::collections::HashMap::new(),
             default_provider: None,
+            ..Default::default()
         };
 
         let update = ConfigUpdate { config };
@@ -409,6 +956,7 @@ mod tests {
             quotas: std::collections::HashMap::new(),
             model_aliases: aliases,
             default_provider: None,
+            ..Default::default()
         };
 
         let update = ConfigUpdate { config };
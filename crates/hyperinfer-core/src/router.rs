@@ -0,0 +1,1285 @@
+use crate::aliases::{self, AliasPatterns};
+use crate::types::{Config, Provider, RoutingOverride, TeamFeature};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tracing::warn;
+
+/// Compiled pattern aliases of one team, with the alias table they were
+/// compiled from so config updates invalidate them.
+type TeamPatterns = (HashMap<String, String>, Arc<AliasPatterns>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum AliasScope {
+    Team,
+    Global,
+}
+
+/// One step of a [`Router::explain`] trace.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum RouteStep {
+    /// An alias matched `alias` (the model name, or the matching pattern).
+    Alias {
+        scope: AliasScope,
+        alias: String,
+        target: String,
+    },
+    /// A matching alias had an unusable target and was skipped.
+    InvalidAlias {
+        scope: AliasScope,
+        alias: String,
+        target: String,
+        error: String,
+    },
+    /// No alias matched; the model name is used as is.
+    NoAlias,
+    /// The alias target named the provider.
+    ExplicitProvider { provider: Provider },
+    /// The provider was inferred from the model name.
+    Inferred { model: String, provider: Provider },
+    /// No provider could be inferred; the default provider was used.
+    DefaultProvider { provider: Provider },
+    /// No provider could be inferred and there is no default provider.
+    NoProvider { model: String },
+    /// The resolved provider is drained or in a maintenance window.
+    ProviderUnavailable { provider: Provider, reason: String },
+    /// The resolved provider has no endpoint in the team's data region.
+    OutsideDataRegion {
+        provider: Provider,
+        data_region: String,
+    },
+    /// The request named its provider, skipping aliases.
+    ProviderOverride { provider: Provider },
+    /// The resolved provider has no endpoint with the request's route tag.
+    NoTaggedEndpoint {
+        provider: Provider,
+        route_tag: String,
+    },
+    /// Trying a fallback model listed by the routing rule `rule`.
+    Fallback { rule: String, model: String },
+    /// Fallbacks are switched off for the team (`enable_fallback`) or by
+    /// the request.
+    FallbackDisabled,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ResolvedRoute {
+    pub model: String,
+    pub provider: Provider,
+}
+
+/// How a model name was resolved, or why it could not be.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RouteExplanation {
+    pub model: String,
+    pub team_id: Option<String>,
+    pub steps: Vec<RouteStep>,
+    /// `None` if resolution failed; the last step says why.
+    pub resolved: Option<ResolvedRoute>,
+}
+
+/// Steps recorded during resolution; disabled (`None`) on the hot path.
+struct Trace(Option<Vec<RouteStep>>);
+
+impl Trace {
+    fn push(&mut self, step: impl FnOnce() -> RouteStep) {
+        if let Some(steps) = &mut self.0 {
+            steps.push(step());
+        }
+    }
+}
+
+pub struct Router {
+    /// Sorted by descending priority, so fallbacks are tried in order.
+    rules: Vec<crate::types::RoutingRule>,
+    model_aliases: std::collections::HashMap<String, (String, Option<Provider>)>,
+    alias_patterns: AliasPatterns,
+    /// Team patterns compiled up front by [`Router::with_team_aliases`].
+    precompiled_team_patterns: HashMap<String, TeamPatterns>,
+    /// Team patterns compiled on demand, for configs other than the one the
+    /// router was built from.
+    team_patterns: RwLock<HashMap<String, TeamPatterns>>,
+    default_provider: Option<Provider>,
+}
+
+impl Router {
+    pub fn new(mut rules: Vec<crate::types::RoutingRule>) -> Self {
+        rules.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+        Self {
+            rules,
+            model_aliases: std::collections::HashMap::new(),
+            alias_patterns: AliasPatterns::default(),
+            precompiled_team_patterns: HashMap::new(),
+            team_patterns: RwLock::new(HashMap::new()),
+            default_provider: None,
+        }
+    }
+
+    /// Global aliases.  Keys may be exact model names, globs or `re:`
+    /// regexes; see [`crate::aliases`].
+    pub fn with_aliases(mut self, aliases: std::collections::HashMap<String, String>) -> Self {
+        let (exact, patterns) = aliases::split_aliases(aliases);
+        self.alias_patterns = patterns;
+        self.model_aliases = exact
+            .into_iter()
+            .filter_map(|(alias, target)| match Self::parse_target_model(&target) {
+                Ok((model, provider)) => Some((alias, (model, provider))),
+                Err(err) => {
+                    warn!("Invalid alias '{}': {}", alias, err);
+                    None
+                }
+            })
+            .collect();
+        self
+    }
+
+    /// Compile the pattern aliases of every team in `team_aliases` now
+    /// rather than on the first request that needs them.  Resolving against
+    /// a config with different team aliases still works, compiling those on
+    /// demand.
+    pub fn with_team_aliases(
+        mut self,
+        team_aliases: &HashMap<String, HashMap<String, String>>,
+    ) -> Self {
+        self.precompiled_team_patterns = team_aliases
+            .iter()
+            .filter(|(_, aliases)| aliases.keys().any(|key| aliases::is_pattern(key)))
+            .map(|(team_id, aliases)| {
+                let patterns = Arc::new(AliasPatterns::new(aliases));
+                (team_id.clone(), (aliases.clone(), patterns))
+            })
+            .collect();
+        self
+    }
+
+    pub fn with_default_provider(mut self, provider: Option<Provider>) -> Self {
+        self.default_provider = provider;
+        self
+    }
+
+    fn parse_target_model(target: &str) -> Result<(String, Option<Provider>), String> {
+        if let Some(slash_pos) = target.find('/') {
+            let provider_str = &target[..slash_pos];
+            let model = target[slash_pos + 1..].to_string();
+            let provider = match provider_str.to_lowercase().as_str() {
+                "openai" => Some(Provider::OpenAI),
+                "anthropic" => Some(Provider::Anthropic),
+                unknown => return Err(format!("Unknown provider: '{}'", unknown)),
+            };
+            Ok((model, provider))
+        } else {
+            Ok((target.to_string(), None))
+        }
+    }
+
+    fn infer_provider(model: &str) -> Option<Provider> {
+        if model.starts_with("gpt-") || model.starts_with("o1-") || model.starts_with("o3-") {
+            Some(Provider::OpenAI)
+        } else if model.starts_with("claude-") {
+            Some(Provider::Anthropic)
+        } else {
+            None
+        }
+    }
+
+    /// Resolve `model` to a concrete model and provider.  Aliases defined for
+    /// `team_id` in `config` take precedence over the global aliases the
+    /// router was built with; within each, exact aliases win over patterns.
+    pub fn resolve(
+        &self,
+        team_id: Option<&str>,
+        model: &str,
+        config: &Config,
+    ) -> Option<(String, Provider)> {
+        self.route(team_id, model, config, None, &mut Trace(None))
+    }
+
+    /// Resolve `model` like [`Router::resolve`], with the request's own
+    /// `routing` taking precedence over the configured routing.
+    pub fn resolve_with(
+        &self,
+        team_id: Option<&str>,
+        model: &str,
+        config: &Config,
+        routing: Option<&RoutingOverride>,
+    ) -> Option<(String, Provider)> {
+        self.route(team_id, model, config, routing, &mut Trace(None))
+    }
+
+    /// Resolve `model` like [`Router::resolve`], recording every step taken,
+    /// for debugging misroutes.
+    pub fn explain(&self, team_id: Option<&str>, model: &str, config: &Config) -> RouteExplanation {
+        self.explain_with(team_id, model, config, None)
+    }
+
+    /// [`Router::explain`] for [`Router::resolve_with`].
+    pub fn explain_with(
+        &self,
+        team_id: Option<&str>,
+        model: &str,
+        config: &Config,
+        routing: Option<&RoutingOverride>,
+    ) -> RouteExplanation {
+        let mut trace = Trace(Some(Vec::new()));
+        let resolved = self
+            .route(team_id, model, config, routing, &mut trace)
+            .map(|(model, provider)| ResolvedRoute { model, provider });
+        RouteExplanation {
+            model: model.to_string(),
+            team_id: team_id.map(str::to_string),
+            steps: trace.0.unwrap_or_default(),
+            resolved,
+        }
+    }
+
+    /// Resolve `model`, moving to the fallback models of the routing rules
+    /// named after it (or after the model it resolved to) when its provider
+    /// is drained, under maintenance or outside the team's data region.
+    /// Rules with a higher `priority` are tried first.  Fallbacks are either
+    /// `provider/model` targets or model names resolved like any other, and
+    /// do not fall back further.
+    fn route(
+        &self,
+        team_id: Option<&str>,
+        model: &str,
+        config: &Config,
+        routing: Option<&RoutingOverride>,
+        trace: &mut Trace,
+    ) -> Option<(String, Provider)> {
+        let now = chrono::Utc::now();
+        let pinned = routing.and_then(|routing| routing.provider_override.clone());
+        let (resolved_model, provider) = match &pinned {
+            Some(provider) => {
+                trace.push(|| RouteStep::ProviderOverride {
+                    provider: provider.clone(),
+                });
+                (model.to_string(), provider.clone())
+            }
+            None => self.route_model(team_id, model, config, trace)?,
+        };
+        let route_tag = routing.and_then(|routing| routing.route_tag.as_deref());
+        let Some(step) = Self::unusable(team_id, provider.clone(), config, now, route_tag) else {
+            return Some((resolved_model, provider));
+        };
+        trace.push(|| step);
+
+        if pinned.is_some() || routing.is_some_and(|routing| routing.disable_fallback) {
+            trace.push(|| RouteStep::FallbackDisabled);
+            return None;
+        }
+        self.route_fallback(
+            team_id,
+            model,
+            &resolved_model,
+            config,
+            now,
+            route_tag,
+            &|_| true,
+            trace,
+        )
+    }
+
+    /// Fallback for the request model `model` (resolved to
+    /// `resolved_model`) to hedge with: the first available fallback other
+    /// than the primary route itself.
+    pub fn hedge_target(
+        &self,
+        team_id: Option<&str>,
+        model: &str,
+        primary: &(String, Provider),
+        config: &Config,
+    ) -> Option<(String, Provider)> {
+        self.route_fallback(
+            team_id,
+            model,
+            &primary.0,
+            config,
+            chrono::Utc::now(),
+            None,
+            &|route| route != primary,
+            &mut Trace(None),
+        )
+    }
+
+    /// Fallback for the request model `model` (resolved to `primary`) with a
+    /// larger context window than the primary, for a prompt the primary
+    /// rejected as too long.  Models without a known window never qualify.
+    pub fn longer_context_target(
+        &self,
+        team_id: Option<&str>,
+        model: &str,
+        primary: &(String, Provider),
+        config: &Config,
+    ) -> Option<(String, Provider)> {
+        let window = config.context_window(&primary.0).unwrap_or(0);
+        self.route_fallback(
+            team_id,
+            model,
+            &primary.0,
+            config,
+            chrono::Utc::now(),
+            None,
+            &|route| config.context_window(&route.0).is_some_and(|w| w > window),
+            &mut Trace(None),
+        )
+    }
+
+    /// First fallback of the rules for `model` / `resolved_model` whose
+    /// provider is available at `now`, with an endpoint tagged `route_tag`
+    /// if set, and that `accept` accepts, unless the team has fallbacks
+    /// switched off.
+    #[allow(clippy::too_many_arguments)]
+    fn route_fallback(
+        &self,
+        team_id: Option<&str>,
+        model: &str,
+        resolved_model: &str,
+        config: &Config,
+        now: chrono::DateTime<chrono::Utc>,
+        route_tag: Option<&str>,
+        accept: &dyn Fn(&(String, Provider)) -> bool,
+        trace: &mut Trace,
+    ) -> Option<(String, Provider)> {
+        if !config.feature_enabled(team_id, TeamFeature::EnableFallback) {
+            trace.push(|| RouteStep::FallbackDisabled);
+            return None;
+        }
+        let rules = self
+            .rules
+            .iter()
+            .filter(|rule| rule.name == model || rule.name == resolved_model);
+        for rule in rules {
+            for fallback in &rule.fallback_models {
+                trace.push(|| RouteStep::Fallback {
+                    rule: rule.name.clone(),
+                    model: fallback.clone(),
+                });
+                let resolved = match Self::parse_target_model(fallback) {
+                    Ok((model, Some(provider))) => {
+                        trace.push(|| RouteStep::ExplicitProvider {
+                            provider: provider.clone(),
+                        });
+                        Some((model, provider))
+                    }
+                    _ => self.route_model(team_id, fallback, config, trace),
+                };
+                let Some(route) = resolved else {
+                    continue;
+                };
+                if !accept(&route) {
+                    continue;
+                }
+                match Self::unusable(team_id, route.1.clone(), config, now, route_tag) {
+                    None => return Some(route),
+                    Some(step) => trace.push(|| step),
+                }
+            }
+        }
+        None
+    }
+
+    /// Why `provider` must not take `team_id`'s traffic at `now`, or a
+    /// request pinned to `route_tag`, or `None` if it may.
+    fn unusable(
+        team_id: Option<&str>,
+        provider: Provider,
+        config: &Config,
+        now: chrono::DateTime<chrono::Utc>,
+        route_tag: Option<&str>,
+    ) -> Option<RouteStep> {
+        if let Some(reason) = config.provider_unavailable(&provider, now) {
+            return Some(RouteStep::ProviderUnavailable { provider, reason });
+        }
+        if let Some(data_region) = config.data_region(team_id) {
+            if !config.serves_region(&provider, data_region) {
+                return Some(RouteStep::OutsideDataRegion {
+                    provider,
+                    data_region: data_region.to_string(),
+                });
+            }
+        }
+        let route_tag = route_tag?;
+        (!config.serves_region(&provider, route_tag)).then(|| RouteStep::NoTaggedEndpoint {
+            provider,
+            route_tag: route_tag.to_string(),
+        })
+    }
+
+    fn route_model(
+        &self,
+        team_id: Option<&str>,
+        model: &str,
+        config: &Config,
+        trace: &mut Trace,
+    ) -> Option<(String, Provider)> {
+        let team_aliases = team_id.and_then(|team_id| {
+            config
+                .team_model_aliases
+                .get(team_id)
+                .map(|aliases| (team_id, aliases))
+        });
+        if let Some((team_id, aliases)) = team_aliases {
+            let matched = match aliases.get(model) {
+                Some(target) => Some((model.to_string(), target.clone())),
+                None => self.team_patterns(team_id, aliases).and_then(|patterns| {
+                    patterns
+                        .matching(model)
+                        .map(|(alias, target)| (alias.to_string(), target))
+                }),
+            };
+            if let Some((alias, target)) = matched {
+                if let Some(route) = self.route_alias(AliasScope::Team, &alias, &target, trace) {
+                    return route;
+                }
+            }
+        }
+
+        if let Some((target_model, explicit_provider)) = self.model_aliases.get(model) {
+            trace.push(|| RouteStep::Alias {
+                scope: AliasScope::Global,
+                alias: model.to_string(),
+                target: match explicit_provider {
+                    Some(provider) => format!("{}/{}", provider, target_model),
+                    None => target_model.clone(),
+                },
+            });
+            return self.route_provider(target_model.clone(), explicit_provider.clone(), trace);
+        }
+
+        if let Some((alias, target)) = self.alias_patterns.matching(model) {
+            if let Some(route) = self.route_alias(AliasScope::Global, alias, &target, trace) {
+                return route;
+            }
+        }
+
+        trace.push(|| RouteStep::NoAlias);
+        self.route_provider(model.to_string(), None, trace)
+    }
+
+    /// Route through a matched alias.  `None` if its target is invalid and
+    /// resolution should carry on without it.
+    fn route_alias(
+        &self,
+        scope: AliasScope,
+        alias: &str,
+        target: &str,
+        trace: &mut Trace,
+    ) -> Option<Option<(String, Provider)>> {
+        match Self::parse_target_model(target) {
+            Ok((target_model, explicit_provider)) => {
+                trace.push(|| RouteStep::Alias {
+                    scope,
+                    alias: alias.to_string(),
+                    target: target.to_string(),
+                });
+                Some(self.route_provider(target_model, explicit_provider, trace))
+            }
+            Err(err) => {
+                warn!("Invalid alias '{}': {}", alias, err);
+                trace.push(|| RouteStep::InvalidAlias {
+                    scope,
+                    alias: alias.to_string(),
+                    target: target.to_string(),
+                    error: err,
+                });
+                None
+            }
+        }
+    }
+
+    fn route_provider(
+        &self,
+        model: String,
+        explicit: Option<Provider>,
+        trace: &mut Trace,
+    ) -> Option<(String, Provider)> {
+        if let Some(provider) = explicit {
+            trace.push(|| RouteStep::ExplicitProvider {
+                provider: provider.clone(),
+            });
+            return Some((model, provider));
+        }
+        if let Some(provider) = Self::infer_provider(&model) {
+            trace.push(|| RouteStep::Inferred {
+                model: model.clone(),
+                provider: provider.clone(),
+            });
+            return Some((model, provider));
+        }
+        if let Some(provider) = self.default_provider.clone() {
+            trace.push(|| RouteStep::DefaultProvider {
+                provider: provider.clone(),
+            });
+            return Some((model, provider));
+        }
+        trace.push(|| RouteStep::NoProvider {
+            model: model.clone(),
+        });
+        None
+    }
+
+    /// Compiled pattern aliases of `team_id`, or `None` if its aliases are
+    /// all exact.  Compiled once per version of the team's alias table.
+    fn team_patterns(
+        &self,
+        team_id: &str,
+        aliases: &HashMap<String, String>,
+    ) -> Option<Arc<AliasPatterns>> {
+        if let Some((source, patterns)) = self.precompiled_team_patterns.get(team_id) {
+            if source == aliases {
+                return Some(patterns.clone());
+            }
+        }
+        if !aliases.keys().any(|key| aliases::is_pattern(key)) {
+            return None;
+        }
+        let cached = self
+            .team_patterns
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(team_id)
+            .filter(|(source, _)| source == aliases)
+            .map(|(_, patterns)| patterns.clone());
+        if cached.is_some() {
+            return cached;
+        }
+        let patterns = Arc::new(AliasPatterns::new(aliases));
+        self.team_patterns
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(team_id.to_string(), (aliases.clone(), patterns.clone()));
+        Some(patterns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::RoutingRule;
+    use std::collections::HashMap;
+
+    fn create_test_config() -> Config {
+        Config {
+            api_keys: HashMap::new(),
+            routing_rules: vec![],
+            quotas: HashMap::new(),
+            model_aliases: HashMap::new(),
+            default_provider: None,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_router_new() {
+        let router = Router::new(vec![]);
+        assert_eq!(router.model_aliases.len(), 0);
+        assert_eq!(router.default_provider, None);
+    }
+
+    #[test]
+    fn test_router_with_default_provider() {
+        let router = Router::new(vec![]).with_default_provider(Some(Provider::OpenAI));
+        assert_eq!(router.default_provider, Some(Provider::OpenAI));
+    }
+
+    #[test]
+    fn test_parse_target_model_with_provider() {
+        let result = Router::parse_target_model("openai/gpt-4").unwrap();
+        assert_eq!(result.0, "gpt-4");
+        assert_eq!(result.1, Some(Provider::OpenAI));
+
+        let result = Router::parse_target_model("anthropic/claude-3").unwrap();
+        assert_eq!(result.0, "claude-3");
+        assert_eq!(result.1, Some(Provider::Anthropic));
+    }
+
+    #[test]
+    fn test_parse_target_model_without_provider() {
+        let result = Router::parse_target_model("gpt-4").unwrap();
+        assert_eq!(result.0, "gpt-4");
+        assert_eq!(result.1, None);
+    }
+
+    #[test]
+    fn test_parse_target_model_unknown_provider() {
+        let result = Router::parse_target_model("unknown/model");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Unknown provider"));
+    }
+
+    #[test]
+    fn test_infer_provider_gpt() {
+        assert_eq!(Router::infer_provider("gpt-4"), Some(Provider::OpenAI));
+        assert_eq!(
+            Router::infer_provider("gpt-3.5-turbo"),
+            Some(Provider::OpenAI)
+        );
+    }
+
+    #[test]
+    fn test_infer_provider_o1() {
+        assert_eq!(Router::infer_provider("o1-preview"), Some(Provider::OpenAI));
+        assert_eq!(Router::infer_provider("o1-mini"), Some(Provider::OpenAI));
+    }
+
+    #[test]
+    fn test_infer_provider_o3() {
+        assert_eq!(Router::infer_provider("o3-mini"), Some(Provider::OpenAI));
+    }
+
+    #[test]
+    fn test_infer_provider_claude() {
+        assert_eq!(
+            Router::infer_provider("claude-3-opus"),
+            Some(Provider::Anthropic)
+        );
+        assert_eq!(
+            Router::infer_provider("claude-2"),
+            Some(Provider::Anthropic)
+        );
+    }
+
+    #[test]
+    fn test_infer_provider_unknown() {
+        assert_eq!(Router::infer_provider("unknown-model"), None);
+        assert_eq!(Router::infer_provider("llama-2"), None);
+    }
+
+    #[test]
+    fn test_with_aliases_valid() {
+        let mut aliases = HashMap::new();
+        aliases.insert("my-gpt".to_string(), "openai/gpt-4".to_string());
+        aliases.insert("my-claude".to_string(), "anthropic/claude-3".to_string());
+
+        let router = Router::new(vec![]).with_aliases(aliases);
+        assert_eq!(router.model_aliases.len(), 2);
+    }
+
+    #[test]
+    fn test_with_aliases_invalid_skipped() {
+        let mut aliases = HashMap::new();
+        aliases.insert("valid".to_string(), "openai/gpt-4".to_string());
+        aliases.insert("invalid".to_string(), "unknown/model".to_string());
+
+        let router = Router::new(vec![]).with_aliases(aliases);
+        assert_eq!(router.model_aliases.len(), 1);
+        assert!(router.model_aliases.contains_key("valid"));
+        assert!(!router.model_aliases.contains_key("invalid"));
+    }
+
+    #[test]
+    fn test_resolve_with_alias() {
+        let mut aliases = HashMap::new();
+        aliases.insert("my-model".to_string(), "openai/gpt-4".to_string());
+
+        let router = Router::new(vec![]).with_aliases(aliases);
+        let config = create_test_config();
+
+        let result = router.resolve(None, "my-model", &config);
+        assert!(result.is_some());
+        let (model, provider) = result.unwrap();
+        assert_eq!(model, "gpt-4");
+        assert_eq!(provider, Provider::OpenAI);
+    }
+
+    #[test]
+    fn test_resolve_with_inference() {
+        let router = Router::new(vec![]);
+        let config = create_test_config();
+
+        let result = router.resolve(None, "gpt-4", &config);
+        assert!(result.is_some());
+        let (model, provider) = result.unwrap();
+        assert_eq!(model, "gpt-4");
+        assert_eq!(provider, Provider::OpenAI);
+
+        let result = router.resolve(None, "claude-3", &config);
+        assert!(result.is_some());
+        let (model, provider) = result.unwrap();
+        assert_eq!(model, "claude-3");
+        assert_eq!(provider, Provider::Anthropic);
+    }
+
+    #[test]
+    fn test_resolve_with_default_provider() {
+        let router = Router::new(vec![]).with_default_provider(Some(Provider::OpenAI));
+        let config = create_test_config();
+
+        let result = router.resolve(None, "unknown-model", &config);
+        assert!(result.is_some());
+        let (model, provider) = result.unwrap();
+        assert_eq!(model, "unknown-model");
+        assert_eq!(provider, Provider::OpenAI);
+    }
+
+    #[test]
+    fn test_resolve_no_match() {
+        let router = Router::new(vec![]);
+        let config = create_test_config();
+
+        let result = router.resolve(None, "unknown-model", &config);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_resolve_alias_without_explicit_provider() {
+        let mut aliases = HashMap::new();
+        aliases.insert("my-gpt".to_string(), "gpt-4".to_string());
+
+        let router = Router::new(vec![]).with_aliases(aliases);
+        let config = create_test_config();
+
+        let result = router.resolve(None, "my-gpt", &config);
+        assert!(result.is_some());
+        let (model, provider) = result.unwrap();
+        assert_eq!(model, "gpt-4");
+        assert_eq!(provider, Provider::OpenAI);
+    }
+
+    #[test]
+    fn test_resolve_alias_with_default_provider() {
+        let mut aliases = HashMap::new();
+        aliases.insert("my-model".to_string(), "custom-model".to_string());
+
+        let router = Router::new(vec![])
+            .with_aliases(aliases)
+            .with_default_provider(Some(Provider::Anthropic));
+        let config = create_test_config();
+
+        let result = router.resolve(None, "my-model", &config);
+        assert!(result.is_some());
+        let (model, provider) = result.unwrap();
+        assert_eq!(model, "custom-model");
+        assert_eq!(provider, Provider::Anthropic);
+    }
+
+    #[test]
+    fn test_resolve_priority_explicit_over_inference() {
+        let mut aliases = HashMap::new();
+        // Map a gpt-like name to anthropic explicitly
+        aliases.insert("gpt-custom".to_string(), "anthropic/claude-3".to_string());
+
+        let router = Router::new(vec![]).with_aliases(aliases);
+        let config = create_test_config();
+
+        let result = router.resolve(None, "gpt-custom", &config);
+        assert!(result.is_some());
+        let (model, provider) = result.unwrap();
+        assert_eq!(model, "claude-3");
+        assert_eq!(provider, Provider::Anthropic);
+    }
+
+    fn config_with_team_aliases(team_id: &str, aliases: &[(&str, &str)]) -> Config {
+        let mut config = create_test_config();
+        config.team_model_aliases.insert(
+            team_id.to_string(),
+            aliases
+                .iter()
+                .map(|(alias, target)| (alias.to_string(), target.to_string()))
+                .collect(),
+        );
+        config
+    }
+
+    #[test]
+    fn test_resolve_team_alias_overrides_global() {
+        let mut aliases = HashMap::new();
+        aliases.insert("fast".to_string(), "openai/gpt-4o-mini".to_string());
+        let router = Router::new(vec![]).with_aliases(aliases);
+        let config = config_with_team_aliases("team-a", &[("fast", "anthropic/claude-3-haiku")]);
+
+        let (model, provider) = router.resolve(Some("team-a"), "fast", &config).unwrap();
+        assert_eq!(model, "claude-3-haiku");
+        assert_eq!(provider, Provider::Anthropic);
+
+        let (model, provider) = router.resolve(Some("team-b"), "fast", &config).unwrap();
+        assert_eq!(model, "gpt-4o-mini");
+        assert_eq!(provider, Provider::OpenAI);
+
+        let (model, _) = router.resolve(None, "fast", &config).unwrap();
+        assert_eq!(model, "gpt-4o-mini");
+    }
+
+    #[test]
+    fn test_resolve_team_alias_without_global() {
+        let router = Router::new(vec![]);
+        let config = config_with_team_aliases("team-a", &[("smart", "gpt-4o")]);
+
+        let (model, provider) = router.resolve(Some("team-a"), "smart", &config).unwrap();
+        assert_eq!(model, "gpt-4o");
+        assert_eq!(provider, Provider::OpenAI);
+        assert!(router.resolve(Some("team-b"), "smart", &config).is_none());
+    }
+
+    #[test]
+    fn test_resolve_invalid_team_alias_falls_back_to_global() {
+        let mut aliases = HashMap::new();
+        aliases.insert("fast".to_string(), "openai/gpt-4o-mini".to_string());
+        let router = Router::new(vec![]).with_aliases(aliases);
+        let config = config_with_team_aliases("team-a", &[("fast", "unknown/model")]);
+
+        let (model, provider) = router.resolve(Some("team-a"), "fast", &config).unwrap();
+        assert_eq!(model, "gpt-4o-mini");
+        assert_eq!(provider, Provider::OpenAI);
+    }
+
+    #[test]
+    fn test_resolve_glob_alias() {
+        let mut aliases = HashMap::new();
+        aliases.insert("gpt-4*".to_string(), "openai/gpt-4o".to_string());
+        aliases.insert("gpt-4-legacy".to_string(), "openai/gpt-4-0613".to_string());
+        let router = Router::new(vec![]).with_aliases(aliases);
+        let config = create_test_config();
+
+        let (model, _) = router.resolve(None, "gpt-4-turbo", &config).unwrap();
+        assert_eq!(model, "gpt-4o");
+        // Exact aliases win over patterns.
+        let (model, _) = router.resolve(None, "gpt-4-legacy", &config).unwrap();
+        assert_eq!(model, "gpt-4-0613");
+        let (model, _) = router.resolve(None, "gpt-3.5-turbo", &config).unwrap();
+        assert_eq!(model, "gpt-3.5-turbo");
+    }
+
+    #[test]
+    fn test_resolve_regex_rewrite() {
+        let mut aliases = HashMap::new();
+        aliases.insert(
+            r"re:claude-3-(opus|sonnet)-(\d+)".to_string(),
+            "anthropic/claude-3-5-$1-$2".to_string(),
+        );
+        let router = Router::new(vec![]).with_aliases(aliases);
+        let config = create_test_config();
+
+        let (model, provider) = router
+            .resolve(None, "claude-3-sonnet-20240229", &config)
+            .unwrap();
+        assert_eq!(model, "claude-3-5-sonnet-20240229");
+        assert_eq!(provider, Provider::Anthropic);
+    }
+
+    #[test]
+    fn test_resolve_team_pattern_alias() {
+        let mut aliases = HashMap::new();
+        aliases.insert("gpt-4*".to_string(), "openai/gpt-4o".to_string());
+        let router = Router::new(vec![]).with_aliases(aliases);
+        let mut config =
+            config_with_team_aliases("team-a", &[("gpt-4*", "anthropic/claude-3-5-sonnet")]);
+
+        let (model, provider) = router
+            .resolve(Some("team-a"), "gpt-4-turbo", &config)
+            .unwrap();
+        assert_eq!(model, "claude-3-5-sonnet");
+        assert_eq!(provider, Provider::Anthropic);
+
+        // Updated team aliases replace the compiled patterns.
+        config
+            .team_model_aliases
+            .get_mut("team-a")
+            .unwrap()
+            .insert("gpt-4*".to_string(), "openai/gpt-4o-mini".to_string());
+        let (model, _) = router
+            .resolve(Some("team-a"), "gpt-4-turbo", &config)
+            .unwrap();
+        assert_eq!(model, "gpt-4o-mini");
+    }
+
+    #[test]
+    fn test_precompiled_team_patterns() {
+        let mut config =
+            config_with_team_aliases("team-a", &[("gpt-4*", "anthropic/claude-3-5-sonnet")]);
+        let router = Router::new(vec![]).with_team_aliases(&config.team_model_aliases);
+        assert!(router.precompiled_team_patterns.contains_key("team-a"));
+
+        let (model, _) = router
+            .resolve(Some("team-a"), "gpt-4-turbo", &config)
+            .unwrap();
+        assert_eq!(model, "claude-3-5-sonnet");
+        assert!(router.team_patterns.read().unwrap().is_empty());
+
+        // A config the router was not built from is compiled on demand.
+        config
+            .team_model_aliases
+            .get_mut("team-a")
+            .unwrap()
+            .insert("gpt-4*".to_string(), "openai/gpt-4o-mini".to_string());
+        let (model, _) = router
+            .resolve(Some("team-a"), "gpt-4-turbo", &config)
+            .unwrap();
+        assert_eq!(model, "gpt-4o-mini");
+    }
+
+    #[test]
+    fn test_explain_team_alias() {
+        let router = Router::new(vec![]);
+        let config = config_with_team_aliases("team-a", &[("fast", "anthropic/claude-3-haiku")]);
+
+        let explanation = router.explain(Some("team-a"), "fast", &config);
+        assert_eq!(
+            explanation.steps,
+            vec![
+                RouteStep::Alias {
+                    scope: AliasScope::Team,
+                    alias: "fast".to_string(),
+                    target: "anthropic/claude-3-haiku".to_string(),
+                },
+                RouteStep::ExplicitProvider {
+                    provider: Provider::Anthropic
+                },
+            ]
+        );
+        assert_eq!(
+            explanation.resolved,
+            Some(ResolvedRoute {
+                model: "claude-3-haiku".to_string(),
+                provider: Provider::Anthropic,
+            })
+        );
+    }
+
+    #[test]
+    fn test_explain_invalid_alias_then_inference() {
+        let mut aliases = HashMap::new();
+        aliases.insert("gpt-4*".to_string(), "unknown/model".to_string());
+        let router = Router::new(vec![]).with_aliases(aliases);
+
+        let explanation = router.explain(None, "gpt-4-turbo", &create_test_config());
+        assert!(matches!(
+            &explanation.steps[0],
+            RouteStep::InvalidAlias { scope: AliasScope::Global, alias, .. } if alias == "gpt-4*"
+        ));
+        assert_eq!(explanation.steps[1], RouteStep::NoAlias);
+        assert!(matches!(
+            explanation.steps[2],
+            RouteStep::Inferred {
+                provider: Provider::OpenAI,
+                ..
+            }
+        ));
+        assert_eq!(explanation.resolved.unwrap().model, "gpt-4-turbo");
+    }
+
+    #[test]
+    fn test_explain_failure() {
+        let router = Router::new(vec![]);
+        let explanation = router.explain(None, "llama-3", &create_test_config());
+        assert_eq!(
+            explanation.steps,
+            vec![
+                RouteStep::NoAlias,
+                RouteStep::NoProvider {
+                    model: "llama-3".to_string()
+                },
+            ]
+        );
+        assert_eq!(explanation.resolved, None);
+
+        let json = serde_json::to_value(&explanation).unwrap();
+        assert_eq!(json["steps"][1]["step"], "no_provider");
+    }
+
+    #[test]
+    fn test_explain_default_provider() {
+        let router = Router::new(vec![]).with_default_provider(Some(Provider::Anthropic));
+        let explanation = router.explain(None, "custom", &create_test_config());
+        assert_eq!(
+            explanation.steps.last(),
+            Some(&RouteStep::DefaultProvider {
+                provider: Provider::Anthropic
+            })
+        );
+    }
+
+    fn drained(provider: &str) -> (String, crate::ProviderStatus) {
+        (
+            provider.to_string(),
+            crate::ProviderStatus {
+                enabled: false,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn fallback_rule(name: &str, priority: u32, fallbacks: &[&str]) -> RoutingRule {
+        RoutingRule {
+            name: name.to_string(),
+            priority,
+            fallback_models: fallbacks.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_skips_drained_provider() {
+        let router = Router::new(vec![
+            fallback_rule("gpt-4o", 2, &["gpt-4o-mini"]),
+            fallback_rule("gpt-4o", 1, &["claude-3-5-sonnet"]),
+        ]);
+        let mut config = create_test_config();
+        assert_eq!(
+            router.resolve(None, "gpt-4o", &config),
+            Some(("gpt-4o".to_string(), Provider::OpenAI))
+        );
+
+        config.providers.extend([drained("openai")]);
+        assert_eq!(
+            router.resolve(None, "gpt-4o", &config),
+            Some(("claude-3-5-sonnet".to_string(), Provider::Anthropic))
+        );
+
+        // Fallbacks on a drained provider are skipped too.
+        config.providers.extend([drained("anthropic")]);
+        assert_eq!(router.resolve(None, "gpt-4o", &config), None);
+    }
+
+    #[test]
+    fn test_resolve_maintenance_window_uses_fallback() {
+        let mut aliases = HashMap::new();
+        aliases.insert(
+            "smart".to_string(),
+            "anthropic/claude-3-5-sonnet".to_string(),
+        );
+        let router =
+            Router::new(vec![fallback_rule("smart", 1, &["openai/gpt-4o"])]).with_aliases(aliases);
+        let mut config = create_test_config();
+        let now = chrono::Utc::now();
+        config.providers.insert(
+            "anthropic".to_string(),
+            crate::ProviderStatus {
+                enabled: true,
+                maintenance_windows: vec![crate::MaintenanceWindow {
+                    start: now - chrono::Duration::minutes(5),
+                    end: now + chrono::Duration::hours(1),
+                    reason: None,
+                }],
+            },
+        );
+
+        let explanation = router.explain(None, "smart", &config);
+        assert!(explanation.steps.iter().any(|step| matches!(
+            step,
+            RouteStep::ProviderUnavailable {
+                provider: Provider::Anthropic,
+                ..
+            }
+        )));
+        assert!(explanation.steps.contains(&RouteStep::Fallback {
+            rule: "smart".to_string(),
+            model: "openai/gpt-4o".to_string(),
+        }));
+        assert_eq!(
+            explanation.resolved,
+            Some(ResolvedRoute {
+                model: "gpt-4o".to_string(),
+                provider: Provider::OpenAI,
+            })
+        );
+    }
+
+    #[test]
+    fn test_resolve_without_fallback_for_team() {
+        let router = Router::new(vec![fallback_rule("gpt-4o", 1, &["claude-3-5-sonnet"])]);
+        let mut config = create_test_config();
+        config.providers.extend([drained("openai")]);
+        config.team_features.insert(
+            "cautious".to_string(),
+            HashMap::from([("enable_fallback".to_string(), false)]),
+        );
+
+        assert_eq!(
+            router.resolve(Some("other"), "gpt-4o", &config),
+            Some(("claude-3-5-sonnet".to_string(), Provider::Anthropic))
+        );
+        let explanation = router.explain(Some("cautious"), "gpt-4o", &config);
+        assert_eq!(explanation.steps.last(), Some(&RouteStep::FallbackDisabled));
+        assert_eq!(explanation.resolved, None);
+    }
+
+    #[test]
+    fn test_resolve_with_routing_override() {
+        let router =
+            Router::new(vec![fallback_rule("gpt-4o", 1, &["claude-3-5-sonnet"])]).with_aliases(
+                HashMap::from([("gpt-4o".to_string(), "openai/gpt-4o-2024-08-06".to_string())]),
+            );
+        let mut config = create_test_config();
+        let pinned = RoutingOverride {
+            provider_override: Some(Provider::Anthropic),
+            ..Default::default()
+        };
+        // Taken as named, without the alias.
+        assert_eq!(
+            router.resolve_with(None, "gpt-4o", &config, Some(&pinned)),
+            Some(("gpt-4o".to_string(), Provider::Anthropic))
+        );
+
+        config.providers.extend([drained("openai")]);
+        let no_fallback = RoutingOverride {
+            disable_fallback: true,
+            ..Default::default()
+        };
+        let explanation = router.explain_with(None, "gpt-4o", &config, Some(&no_fallback));
+        assert_eq!(explanation.steps.last(), Some(&RouteStep::FallbackDisabled));
+        assert_eq!(explanation.resolved, None);
+        // A pinned provider does not fall back either.
+        let pinned = RoutingOverride {
+            provider_override: Some(Provider::OpenAI),
+            ..Default::default()
+        };
+        assert_eq!(
+            router.resolve_with(None, "gpt-4o", &config, Some(&pinned)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resolve_with_route_tag() {
+        let router = Router::new(vec![fallback_rule("gpt-4o", 1, &["claude-3-5-sonnet"])]);
+        let mut config = create_test_config();
+        config.provider_regions.insert(
+            "anthropic".to_string(),
+            crate::ProviderRegions {
+                endpoints: vec![crate::RegionalEndpoint {
+                    region: "us-east-5".to_string(),
+                    base_url: "https://canary.example.com".to_string(),
+                    tags: vec!["canary".to_string()],
+                }],
+                selection: Default::default(),
+                failure_threshold: 3,
+                cooldown_secs: 30,
+            },
+        );
+        let canary = RoutingOverride {
+            route_tag: Some("canary".to_string()),
+            ..Default::default()
+        };
+
+        let explanation = router.explain_with(None, "gpt-4o", &config, Some(&canary));
+        assert!(explanation.steps.contains(&RouteStep::NoTaggedEndpoint {
+            provider: Provider::OpenAI,
+            route_tag: "canary".to_string(),
+        }));
+        assert_eq!(
+            explanation.resolved,
+            Some(ResolvedRoute {
+                model: "claude-3-5-sonnet".to_string(),
+                provider: Provider::Anthropic,
+            })
+        );
+        let elsewhere = RoutingOverride {
+            route_tag: Some("us-west-1".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            router.resolve_with(None, "gpt-4o", &config, Some(&elsewhere)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resolve_keeps_team_in_data_region() {
+        let router = Router::new(vec![fallback_rule(
+            "gpt-4o",
+            1,
+            &["claude-3-5-sonnet", "openai/gpt-4o-mini"],
+        )]);
+        let mut config = create_test_config();
+        config.provider_regions.insert(
+            "openai".to_string(),
+            crate::ProviderRegions {
+                endpoints: vec![crate::RegionalEndpoint {
+                    region: "swedencentral".to_string(),
+                    base_url: "https://se.example.com".to_string(),
+                    tags: vec!["eu".to_string()],
+                }],
+                selection: Default::default(),
+                failure_threshold: 3,
+                cooldown_secs: 30,
+            },
+        );
+        config
+            .team_data_regions
+            .insert("team-eu".to_string(), "eu".to_string());
+        config
+            .team_data_regions
+            .insert("team-apac".to_string(), "apac".to_string());
+
+        assert_eq!(
+            router.resolve(Some("team-eu"), "gpt-4o", &config),
+            Some(("gpt-4o".to_string(), Provider::OpenAI))
+        );
+        // Anthropic has no regional endpoints, so its location is unknown.
+        assert_eq!(
+            router.resolve(Some("team-eu"), "claude-3-5-sonnet", &config),
+            None
+        );
+        assert_eq!(
+            router.resolve(None, "claude-3-5-sonnet", &config),
+            Some(("claude-3-5-sonnet".to_string(), Provider::Anthropic))
+        );
+
+        config.providers.extend([drained("openai")]);
+        assert_eq!(router.resolve(Some("team-eu"), "gpt-4o", &config), None);
+        let explanation = router.explain(Some("team-apac"), "gpt-4o", &config);
+        assert!(explanation.steps.contains(&RouteStep::OutsideDataRegion {
+            provider: Provider::Anthropic,
+            data_region: "apac".to_string(),
+        }));
+        assert_eq!(explanation.resolved, None);
+    }
+
+    #[test]
+    fn test_hedge_target_skips_primary_and_unavailable() {
+        let router = Router::new(vec![fallback_rule(
+            "gpt-4o",
+            1,
+            &["gpt-4o", "anthropic/claude-3-5-sonnet", "gpt-4o-mini"],
+        )]);
+        let mut config = create_test_config();
+        let primary = ("gpt-4o".to_string(), Provider::OpenAI);
+        assert_eq!(
+            router.hedge_target(None, "gpt-4o", &primary, &config),
+            Some(("claude-3-5-sonnet".to_string(), Provider::Anthropic))
+        );
+
+        config.providers.extend([drained("anthropic")]);
+        assert_eq!(
+            router.hedge_target(None, "gpt-4o", &primary, &config),
+            Some(("gpt-4o-mini".to_string(), Provider::OpenAI))
+        );
+        let other = ("gpt-3.5-turbo".to_string(), Provider::OpenAI);
+        assert_eq!(
+            router.hedge_target(None, "gpt-3.5-turbo", &other, &config),
+            None
+        );
+    }
+
+    #[test]
+    fn test_longer_context_target() {
+        let router = Router::new(vec![fallback_rule(
+            "gpt-4",
+            1,
+            &["gpt-3.5-turbo", "gpt-4-32k", "gpt-4o"],
+        )]);
+        let config = create_test_config();
+        let primary = ("gpt-4".to_string(), Provider::OpenAI);
+        // gpt-3.5-turbo's 16k beats gpt-4's 8k and comes first.
+        assert_eq!(
+            router.longer_context_target(None, "gpt-4", &primary, &config),
+            Some(("gpt-3.5-turbo".to_string(), Provider::OpenAI))
+        );
+        let larger = ("gpt-4-32k".to_string(), Provider::OpenAI);
+        assert_eq!(
+            router.longer_context_target(None, "gpt-4", &larger, &config),
+            Some(("gpt-4o".to_string(), Provider::OpenAI))
+        );
+        let largest = ("gpt-4o".to_string(), Provider::OpenAI);
+        assert_eq!(
+            router.longer_context_target(None, "gpt-4", &largest, &config),
+            None
+        );
+    }
+}
@@ -7,11 +7,13 @@ use redis::aio::MultiplexedConnection;
 use redis::Client;
 use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
-use crate::types::UsageRecord;
+use crate::redis::{RedisHandle, RedisOptions};
+use crate::telemetry_sink::{self, SinkError, TelemetrySink};
+use crate::types::{CompressionStats, UsageEvent, UsageRecord};
 
-const DEFAULT_TELEMETRY_STREAM: &str = "hyperinfer:telemetry";
+pub const DEFAULT_TELEMETRY_STREAM: &str = "hyperinfer:telemetry";
 const DEFAULT_CONSUMER_GROUP: &str = "telemetry-consumer";
 const XAUTOCLAIM_IDLE_MS: &str = "600000";
 const XREADGROUP_BLOCK_MS: u32 = 5000;
@@ -21,24 +23,60 @@ const MAX_BACKOFF_SECS: u64 = 60;
 
 type StreamEntry = (String, Vec<(String, String)>);
 
+/// The shard of `shards` that records for API key `key` are written to.
+/// Writers and consumers must agree on it, so the hash (FNV-1a) is fixed
+/// rather than `std`'s, which may change between releases.
+pub fn shard_for_key(key: &str, shards: u32) -> u32 {
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    (hash % u64::from(shards.max(1))) as u32
+}
+
+/// The stream holding shard `shard` of `shards`: `stream_key` itself when
+/// the stream is not sharded, else `stream_key:shard`.
+pub fn shard_stream_key(stream_key: &str, shard: u32, shards: u32) -> String {
+    if shards <= 1 {
+        stream_key.to_string()
+    } else {
+        format!("{}:{}", stream_key, shard)
+    }
+}
+
+/// Every stream of a telemetry stream split into `shards`.
+pub fn shard_stream_keys(stream_key: &str, shards: u32) -> Vec<String> {
+    (0..shards.max(1))
+        .map(|shard| shard_stream_key(stream_key, shard, shards))
+        .collect()
+}
+
 pub struct TelemetryConsumer {
     client: Arc<Client>,
     stream_key: String,
+    shards: u32,
     consumer_group: String,
     consumer_name: String,
 }
 
 impl TelemetryConsumer {
     pub async fn new(redis_url: &str) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
-        let client = Client::open(redis_url)?;
-        let consumer_name = format!("consumer-{}", uuid::Uuid::new_v4());
+        Ok(Self::with_client(RedisOptions::new(redis_url).client()?))
+    }
+
+    /// A consumer on the client of `redis`.  It still opens connections of
+    /// its own: blocking reads would stall a shared connection.
+    pub fn with_redis(redis: &RedisHandle) -> Self {
+        Self::with_client(redis.client().clone())
+    }
 
-        Ok(Self {
+    fn with_client(client: Client) -> Self {
+        Self {
             client: Arc::new(client),
             stream_key: DEFAULT_TELEMETRY_STREAM.to_string(),
+            shards: 1,
             consumer_group: DEFAULT_CONSUMER_GROUP.to_string(),
-            consumer_name,
-        })
+            consumer_name: format!("consumer-{}", uuid::Uuid::new_v4()),
+        }
     }
 
     pub fn with_stream_key(mut self, stream_key: &str) -> Self {
@@ -46,6 +84,14 @@ impl TelemetryConsumer {
         self
     }
 
+    /// Consume a stream split into `shards` streams, `stream_key:0` to
+    /// `stream_key:{shards - 1}`, as written by clients configured with the
+    /// same count.  One shard, the default, is the stream itself.
+    pub fn with_shards(mut self, shards: u32) -> Self {
+        self.shards = shards.max(1);
+        self
+    }
+
     pub fn with_consumer_group(mut self, group: &str) -> Self {
         self.consumer_group = group.to_string();
         self
@@ -153,13 +199,20 @@ impl TelemetryConsumer {
         Ok(())
     }
 
-    async fn process_entry<F, Fut>(msg_id: &str, fields: &[(String, String)], handler: &F) -> bool
+    async fn process_entry<F, Fut>(
+        stream_key: &str,
+        msg_id: &str,
+        fields: &[(String, String)],
+        handler: &F,
+    ) -> bool
     where
         F: Fn(UsageRecord) -> Fut + Send + Sync + 'static,
         Fut: std::future::Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>>
             + Send,
     {
-        if let Some(record) = Self::parse_entry(Some(msg_id), fields) {
+        // Entry ids are only unique within a stream, and shards are streams.
+        let entry_id = format!("{}/{}", stream_key, msg_id);
+        if let Some(record) = Self::parse_entry(Some(&entry_id), fields) {
             match handler(record).await {
                 Ok(_) => true,
                 Err(e) => {
@@ -326,7 +379,7 @@ impl TelemetryConsumer {
 
             let mut ack_ids = Vec::with_capacity(claimed.len());
             for (msg_id, fields) in &claimed {
-                if Self::process_entry(msg_id, fields, handler).await {
+                if Self::process_entry(stream_key, msg_id, fields, handler).await {
                     ack_ids.push(msg_id.as_str());
                 }
             }
@@ -376,7 +429,7 @@ impl TelemetryConsumer {
         for (_stream, entries) in results {
             let mut ack_ids = Vec::with_capacity(entries.len());
             for (entry_id, fields) in &entries {
-                if Self::process_entry(entry_id, fields, handler).await {
+                if Self::process_entry(stream_key, entry_id, fields, handler).await {
                     ack_ids.push(entry_id.as_str());
                 }
             }
@@ -388,6 +441,9 @@ impl TelemetryConsumer {
         Ok(())
     }
 
+    /// Consume every stream of the telemetry stream's shards, one task
+    /// per stream, each with its own connection.  The handle finishes once
+    /// all of them have stopped.
     pub async fn start_consuming<F, Fut>(
         &self,
         handler: F,
@@ -396,115 +452,173 @@ impl TelemetryConsumer {
     where
         F: Fn(UsageRecord) -> Fut + Send + Sync + 'static,
         Fut: std::future::Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>>
-            + Send,
+            + Send
+            + 'static,
     {
-        let client = Arc::clone(&self.client);
-        let stream_key = self.stream_key.clone();
-        let consumer_group = self.consumer_group.clone();
-        let consumer_name = self.consumer_name.clone();
+        let handler = Arc::new(handler);
+        let handles: Vec<_> = shard_stream_keys(&self.stream_key, self.shards)
+            .into_iter()
+            .map(|stream_key| {
+                tokio::spawn(Self::consume_stream(
+                    Arc::clone(&self.client),
+                    stream_key,
+                    self.consumer_group.clone(),
+                    self.consumer_name.clone(),
+                    Arc::clone(&handler),
+                    cancellation_token.clone(),
+                ))
+            })
+            .collect();
+        Ok(tokio::spawn(async move {
+            futures_util::future::join_all(handles).await;
+        }))
+    }
 
-        let handle = tokio::spawn(async move {
-            let mut backoff = 1u64;
+    async fn consume_stream<F, Fut>(
+        client: Arc<Client>,
+        stream_key: String,
+        consumer_group: String,
+        consumer_name: String,
+        handler: Arc<F>,
+        cancellation_token: CancellationToken,
+    ) where
+        F: Fn(UsageRecord) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<(), Box<dyn std::error::Error + Send + Sync>>>
+            + Send
+            + 'static,
+    {
+        let mut backoff = 1u64;
 
-            loop {
-                if cancellation_token.is_cancelled() {
-                    info!("Telemetry consumer shutting down");
-                    return;
-                }
+        loop {
+            if cancellation_token.is_cancelled() {
+                info!("Telemetry consumer for {} shutting down", stream_key);
+                return;
+            }
 
-                let conn_result = client.get_multiplexed_async_connection().await;
-                if let Err(e) = &conn_result {
-                    error!(
-                        "Failed to connect to Redis: {}. Reconnecting in {}s",
-                        e, backoff
-                    );
-                    tokio::select! {
-                        _ = cancellation_token.cancelled() => {
-                            info!("Telemetry consumer shutting down");
-                            return;
-                        }
-                        _ = tokio::time::sleep(tokio::time::Duration::from_secs(backoff)) => {
-                            backoff = (backoff * 2).min(MAX_BACKOFF_SECS);
-                        }
+            let conn_result = client.get_multiplexed_async_connection().await;
+            if let Err(e) = &conn_result {
+                error!(
+                    "Failed to connect to Redis: {}. Reconnecting in {}s",
+                    e, backoff
+                );
+                tokio::select! {
+                    _ = cancellation_token.cancelled() => {
+                        info!("Telemetry consumer for {} shutting down", stream_key);
+                        return;
+                    }
+                    _ = tokio::time::sleep(tokio::time::Duration::from_secs(backoff)) => {
+                        backoff = (backoff * 2).min(MAX_BACKOFF_SECS);
                     }
-                    continue;
                 }
+                continue;
+            }
 
-                let mut conn = conn_result.unwrap();
-                if let Err(e) =
-                    Self::ensure_consumer_group(&mut conn, &stream_key, &consumer_group).await
-                {
-                    warn!("Failed to ensure consumer group: {}", e);
-                }
+            let mut conn = conn_result.unwrap();
+            if let Err(e) =
+                Self::ensure_consumer_group(&mut conn, &stream_key, &consumer_group).await
+            {
+                warn!("Failed to ensure consumer group: {}", e);
+            }
 
-                info!(
-                    "Starting telemetry consumption from stream: {} (group: {})",
-                    stream_key, consumer_group
-                );
+            info!(
+                "Starting telemetry consumption from stream: {} (group: {})",
+                stream_key, consumer_group
+            );
 
-                let recover_result = Self::recover_pending_messages(
-                    &mut conn,
-                    &stream_key,
-                    &consumer_group,
-                    &consumer_name,
-                    &handler,
-                )
-                .await
-                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>);
-
-                let mut do_reconnect = false;
-                if let Err(e) = &recover_result {
-                    warn!("Failed to recover pending messages: {}", e);
-                    do_reconnect = true;
-                }
+            let recover_result = Self::recover_pending_messages(
+                &mut conn,
+                &stream_key,
+                &consumer_group,
+                &consumer_name,
+                &*handler,
+            )
+            .await
+            .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>);
 
-                if do_reconnect {
-                    error!("Recovery failed, reconnecting to retry on next cycle");
-                    backoff = 1;
-                    continue;
-                }
+            let mut do_reconnect = false;
+            if let Err(e) = &recover_result {
+                warn!("Failed to recover pending messages: {}", e);
+                do_reconnect = true;
+            }
 
-                loop {
-                    if cancellation_token.is_cancelled() {
-                        info!("Telemetry consumer shutting down");
-                        return;
-                    }
+            if do_reconnect {
+                error!("Recovery failed, reconnecting to retry on next cycle");
+                backoff = 1;
+                continue;
+            }
 
-                    tokio::select! {
-                        result = Self::read_and_process_batch(
-                            &mut conn,
-                            &stream_key,
-                            &consumer_group,
-                            &consumer_name,
-                            &handler,
-                        ) => {
-                            match result {
-                                Ok(_) => {
-                                    backoff = 1;
-                                }
-                                Err(e) => {
-                                    error!(
-                                        "Telemetry consumer error: {}. Reconnecting in {}s",
-                                        e, backoff
-                                    );
-                                    backoff = (backoff * 2).min(MAX_BACKOFF_SECS);
-                                    break;
-                                }
+            loop {
+                if cancellation_token.is_cancelled() {
+                    info!("Telemetry consumer for {} shutting down", stream_key);
+                    return;
+                }
+
+                tokio::select! {
+                    result = Self::read_and_process_batch(
+                        &mut conn,
+                        &stream_key,
+                        &consumer_group,
+                        &consumer_name,
+                        &*handler,
+                    ) => {
+                        match result {
+                            Ok(_) => {
+                                backoff = 1;
+                            }
+                            Err(e) => {
+                                error!(
+                                    "Telemetry consumer error: {}. Reconnecting in {}s",
+                                    e, backoff
+                                );
+                                backoff = (backoff * 2).min(MAX_BACKOFF_SECS);
+                                break;
                             }
-                        }
-                        _ = cancellation_token.cancelled() => {
-                            info!("Telemetry consumer shutting down");
-                            return;
                         }
                     }
+                    _ = cancellation_token.cancelled() => {
+                        info!("Telemetry consumer for {} shutting down", stream_key);
+                        return;
+                    }
                 }
             }
-        });
+        }
+    }
 
-        Ok(handle)
+    /// Consume into `sinks`.  `resolve` maps a record's API key to its team
+    /// and key ids; records for unknown keys are dropped.
+    pub async fn start_with_sinks<R, RFut>(
+        &self,
+        resolve: R,
+        sinks: Vec<Arc<dyn TelemetrySink>>,
+        cancellation_token: CancellationToken,
+    ) -> Result<tokio::task::JoinHandle<()>, Box<dyn std::error::Error + Send + Sync>>
+    where
+        R: Fn(String) -> RFut + Send + Sync + 'static,
+        RFut: std::future::Future<Output = Result<Option<(String, String)>, SinkError>> + Send,
+    {
+        let resolve = Arc::new(resolve);
+        let sinks: Arc<[Arc<dyn TelemetrySink>]> = sinks.into();
+        self.start_consuming(
+            move |mut record: UsageRecord| {
+                let resolve = Arc::clone(&resolve);
+                let sinks = Arc::clone(&sinks);
+                async move {
+                    let key = std::mem::take(&mut record.key);
+                    let Some((team_id, api_key_id)) = resolve(key).await? else {
+                        debug!("API key not found, skipping usage record");
+                        return Ok(());
+                    };
+                    let event = UsageEvent::new(team_id, api_key_id, record);
+                    telemetry_sink::write_all(&sinks, &event).await
+                }
+            },
+            cancellation_token,
+        )
+        .await
     }
 
-    fn parse_entry(msg_id: Option<&str>, fields: &[(String, String)]) -> Option<UsageRecord> {
+    /// Parse a telemetry stream entry; `None` if required fields are missing.
+    pub fn parse_entry(msg_id: Option<&str>, fields: &[(String, String)]) -> Option<UsageRecord> {
         let mut map = std::collections::HashMap::new();
         for (k, v) in fields {
             map.insert(k.clone(), v.clone());
@@ -530,6 +644,21 @@ impl TelemetryConsumer {
             response_time_ms,
             timestamp,
             msg_id: msg_id.map(String::from),
+            provider: map.get("provider").cloned(),
+            error: map.get("error").cloned(),
+            metadata: map
+                .get("metadata")
+                .and_then(|m| serde_json::from_str(m).ok())
+                .unwrap_or_default(),
+            compression: map
+                .get("compression_original_tokens")
+                .zip(map.get("compression_compressed_tokens"))
+                .and_then(|(original, compressed)| {
+                    Some(CompressionStats {
+                        original_tokens: original.parse().ok()?,
+                        compressed_tokens: compressed.parse().ok()?,
+                    })
+                }),
         })
     }
 
@@ -543,14 +672,15 @@ impl TelemetryConsumer {
         &self,
     ) -> Result<Vec<UsageRecord>, Box<dyn std::error::Error + Send + Sync>> {
         let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let stream_keys = shard_stream_keys(&self.stream_key, self.shards);
 
         #[allow(clippy::type_complexity)]
         let results: Vec<(String, Vec<(String, Vec<(String, String)>)>)> = redis::cmd("XREAD")
             .arg("COUNT")
             .arg(100)
             .arg("STREAMS")
-            .arg(&self.stream_key)
-            .arg("0")
+            .arg(&stream_keys)
+            .arg(vec!["0"; stream_keys.len()])
             .query_async(&mut conn)
             .await?;
 
@@ -612,6 +742,91 @@ mod tests {
         assert_eq!(record.msg_id, Some("1234567890-0".to_string()));
     }
 
+    #[test]
+    fn test_parse_entry_with_compression() {
+        let mut fields = vec![
+            ("key".to_string(), "test-key".to_string()),
+            ("model".to_string(), "gpt-4".to_string()),
+            ("input_tokens".to_string(), "400".to_string()),
+            ("output_tokens".to_string(), "50".to_string()),
+            ("response_time_ms".to_string(), "250".to_string()),
+            ("timestamp".to_string(), "1700000000000".to_string()),
+        ];
+        assert_eq!(
+            TelemetryConsumer::parse_entry(None, &fields)
+                .unwrap()
+                .compression,
+            None
+        );
+
+        fields.push((
+            "compression_original_tokens".to_string(),
+            "1000".to_string(),
+        ));
+        fields.push((
+            "compression_compressed_tokens".to_string(),
+            "400".to_string(),
+        ));
+        let record = TelemetryConsumer::parse_entry(None, &fields).unwrap();
+        assert_eq!(
+            record.compression,
+            Some(CompressionStats {
+                original_tokens: 1000,
+                compressed_tokens: 400,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_entry_with_error() {
+        let fields = vec![
+            ("key".to_string(), "test-key".to_string()),
+            ("model".to_string(), "gpt-4".to_string()),
+            ("input_tokens".to_string(), "0".to_string()),
+            ("output_tokens".to_string(), "0".to_string()),
+            ("response_time_ms".to_string(), "30000".to_string()),
+            ("timestamp".to_string(), "1700000000000".to_string()),
+            ("provider".to_string(), "openai".to_string()),
+            (
+                "error".to_string(),
+                "API error (503): overloaded".to_string(),
+            ),
+        ];
+
+        let record = TelemetryConsumer::parse_entry(None, &fields).unwrap();
+        assert_eq!(record.provider.as_deref(), Some("openai"));
+        assert_eq!(record.error.as_deref(), Some("API error (503): overloaded"));
+    }
+
+    #[test]
+    fn test_parse_entry_with_metadata() {
+        let fields = vec![
+            ("key".to_string(), "test-key".to_string()),
+            ("model".to_string(), "gpt-4".to_string()),
+            ("input_tokens".to_string(), "10".to_string()),
+            ("output_tokens".to_string(), "5".to_string()),
+            ("response_time_ms".to_string(), "100".to_string()),
+            ("timestamp".to_string(), "1700000000000".to_string()),
+            (
+                "metadata".to_string(),
+                r#"{"feature":"search","env":"prod"}"#.to_string(),
+            ),
+        ];
+
+        let record = TelemetryConsumer::parse_entry(None, &fields).unwrap();
+        assert_eq!(
+            record.metadata.get("feature").map(String::as_str),
+            Some("search")
+        );
+        assert_eq!(record.metadata.len(), 2);
+
+        // Malformed metadata is dropped rather than losing the usage record.
+        let mut fields = fields;
+        fields[6].1 = "not json".to_string();
+        let record = TelemetryConsumer::parse_entry(None, &fields).unwrap();
+        assert!(record.metadata.is_empty());
+    }
+
     #[test]
     fn test_parse_entry_missing_field() {
         let fields = vec![
@@ -652,12 +867,40 @@ mod tests {
             .await
             .unwrap()
             .with_stream_key("custom:stream")
+            .with_shards(0)
             .with_consumer_group("custom-group");
 
         assert_eq!(consumer.stream_key, "custom:stream");
+        assert_eq!(consumer.shards, 1);
         assert_eq!(consumer.consumer_group, "custom-group");
     }
 
+    #[test]
+    fn test_shard_streams() {
+        assert_eq!(
+            shard_stream_keys("hyperinfer:telemetry", 1),
+            vec!["hyperinfer:telemetry"]
+        );
+        assert_eq!(
+            shard_stream_keys("hyperinfer:telemetry", 3),
+            vec![
+                "hyperinfer:telemetry:0",
+                "hyperinfer:telemetry:1",
+                "hyperinfer:telemetry:2"
+            ]
+        );
+        // Fixed values: clients and consumers built from different releases
+        // must still agree.
+        assert_eq!(shard_for_key("", 7), (0xcbf2_9ce4_8422_2325u64 % 7) as u32);
+        assert_eq!(shard_for_key("sk-test", 1), 0);
+        let keys: Vec<String> = (0..1000).map(|i| format!("sk-{}", i)).collect();
+        let mut counts = [0; 4];
+        for key in &keys {
+            counts[shard_for_key(key, 4) as usize] += 1;
+        }
+        assert!(counts.iter().all(|count| *count > 150), "{:?}", counts);
+    }
+
     #[test]
     fn test_parse_entry_extra_fields() {
         let fields = vec![
@@ -0,0 +1,212 @@
+//! Destinations for consumed telemetry.
+//!
+//! [`TelemetryConsumer::start_with_sinks`](crate::TelemetryConsumer::start_with_sinks)
+//! attributes each usage record to its team and key and writes the
+//! resulting [`UsageEvent`] to every configured [`TelemetrySink`]: Postgres
+//! for budgets and reporting, and, with the `kafka` or `nats` features, an
+//! existing event pipeline.  A record is acknowledged only once every sink
+//! has taken it; otherwise it is redelivered to all of them, so sinks see
+//! each event at least once.
+
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use crate::traits::Database;
+use crate::types::UsageEvent;
+
+pub type SinkError = Box<dyn std::error::Error + Send + Sync>;
+
+#[async_trait]
+pub trait TelemetrySink: Send + Sync {
+    /// Names the sink in logs.
+    fn name(&self) -> &str;
+
+    async fn write(&self, event: &UsageEvent) -> Result<(), SinkError>;
+}
+
+/// Write `event` to every sink concurrently, failing if any of them fails.
+pub async fn write_all(
+    sinks: &[Arc<dyn TelemetrySink>],
+    event: &UsageEvent,
+) -> Result<(), SinkError> {
+    let results = join_all(sinks.iter().map(|sink| sink.write(event))).await;
+    let mut failure = None;
+    for (sink, result) in sinks.iter().zip(results) {
+        if let Err(e) = result {
+            warn!("Telemetry sink {} failed: {}", sink.name(), e);
+            failure.get_or_insert(e);
+        }
+    }
+    failure.map_or(Ok(()), Err)
+}
+
+/// Records usage, and request errors, in the control plane's database,
+/// once per stream entry.
+pub struct PostgresSink<D> {
+    db: D,
+}
+
+impl<D: Database> PostgresSink<D> {
+    pub fn new(db: D) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl<D: Database> TelemetrySink for PostgresSink<D> {
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
+    async fn write(&self, event: &UsageEvent) -> Result<(), SinkError> {
+        if !self.db.record_usage_event(event).await? {
+            debug!(
+                "Skipped usage event {} already recorded",
+                event.entry_id.as_deref().unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Publishes events as JSON to a Kafka topic, keyed by team so that a
+/// team's events stay in order on one partition.
+#[cfg(feature = "kafka")]
+pub struct KafkaSink {
+    producer: rdkafka::producer::FutureProducer,
+    topic: String,
+}
+
+#[cfg(feature = "kafka")]
+impl KafkaSink {
+    /// How long a send may wait for room in the producer's queue.
+    const QUEUE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+    /// A producer for the comma-separated `brokers`.
+    pub fn new(brokers: &str, topic: &str) -> Result<Self, rdkafka::error::KafkaError> {
+        let producer = rdkafka::ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("enable.idempotence", "true")
+            .create()?;
+        Ok(Self {
+            producer,
+            topic: topic.to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "kafka")]
+#[async_trait]
+impl TelemetrySink for KafkaSink {
+    fn name(&self) -> &str {
+        "kafka"
+    }
+
+    async fn write(&self, event: &UsageEvent) -> Result<(), SinkError> {
+        let payload = serde_json::to_vec(event)?;
+        let record = rdkafka::producer::FutureRecord::to(&self.topic)
+            .key(&event.team_id)
+            .payload(&payload);
+        self.producer
+            .send(record, Self::QUEUE_TIMEOUT)
+            .await
+            .map_err(|(e, _)| e)?;
+        Ok(())
+    }
+}
+
+/// Publishes events as JSON on `<subject>.<team_id>`, so subscribers can
+/// take every team (`<subject>.*`) or just one.
+#[cfg(feature = "nats")]
+pub struct NatsSink {
+    client: async_nats::Client,
+    subject: String,
+}
+
+#[cfg(feature = "nats")]
+impl NatsSink {
+    pub async fn connect(url: &str, subject: &str) -> Result<Self, async_nats::ConnectError> {
+        Ok(Self {
+            client: async_nats::connect(url).await?,
+            subject: subject.to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "nats")]
+#[async_trait]
+impl TelemetrySink for NatsSink {
+    fn name(&self) -> &str {
+        "nats"
+    }
+
+    async fn write(&self, event: &UsageEvent) -> Result<(), SinkError> {
+        let subject = format!("{}.{}", self.subject, event.team_id);
+        self.client
+            .publish(subject, serde_json::to_vec(event)?.into())
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct Recording {
+        name: &'static str,
+        fail: bool,
+        events: Mutex<Vec<UsageEvent>>,
+    }
+
+    impl Recording {
+        fn new(name: &'static str, fail: bool) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                fail,
+                events: Mutex::new(Vec::new()),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl TelemetrySink for Recording {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn write(&self, event: &UsageEvent) -> Result<(), SinkError> {
+            self.events.lock().unwrap().push(event.clone());
+            if self.fail {
+                Err(format!("{} is down", self.name).into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_all_reaches_every_sink() {
+        let event = UsageEvent {
+            team_id: "team-1".to_string(),
+            model: "gpt-4o".to_string(),
+            ..Default::default()
+        };
+        let postgres = Recording::new("postgres", false);
+        let kafka = Recording::new("kafka", false);
+        let sinks: Vec<Arc<dyn TelemetrySink>> = vec![postgres.clone(), kafka.clone()];
+        write_all(&sinks, &event).await.unwrap();
+        assert_eq!(*postgres.events.lock().unwrap(), vec![event.clone()]);
+        assert_eq!(*kafka.events.lock().unwrap(), vec![event.clone()]);
+
+        // One failing sink fails the write, after the others have it.
+        let nats = Recording::new("nats", true);
+        let sinks: Vec<Arc<dyn TelemetrySink>> = vec![nats, postgres.clone()];
+        let err = write_all(&sinks, &event).await.unwrap_err();
+        assert_eq!(err.to_string(), "nats is down");
+        assert_eq!(postgres.events.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,117 @@
+//! Token count estimation
+//!
+//! Provider-independent approximation of how many tokens a prompt uses, for
+//! cost estimates and budgeting before a request is sent.  Exact counts come
+//! back from the provider in `Usage`; these estimates err slightly high.
+
+use crate::types::{ChatMessage, ChatRequest};
+
+/// Tokens each message adds on top of its content (role and delimiters).
+pub const MESSAGE_OVERHEAD_TOKENS: u32 = 4;
+
+/// Tokens the provider adds to prime the assistant reply.
+pub const REPLY_PRIMING_TOKENS: u32 = 3;
+
+/// Average characters per token for ASCII text in BPE vocabularies.
+const ASCII_CHARS_PER_TOKEN: u32 = 4;
+
+/// Estimated token count of `text`.
+///
+/// ASCII text averages roughly four characters per token; other scripts
+/// (CJK, emoji, accented text) are counted as one token per character.
+pub fn estimate_text_tokens(text: &str) -> u32 {
+    let mut counter = TextTokenCounter::default();
+    counter.push(text);
+    counter.tokens()
+}
+
+/// Running estimate, by the rules of [`estimate_text_tokens`], of text that
+/// arrives in pieces such as a streamed reply.  Counting the pieces together
+/// avoids rounding each one up on its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextTokenCounter {
+    ascii: u32,
+    other: u32,
+}
+
+impl TextTokenCounter {
+    pub fn push(&mut self, text: &str) {
+        for c in text.chars() {
+            if c.is_ascii() {
+                self.ascii = self.ascii.saturating_add(1);
+            } else {
+                self.other = self.other.saturating_add(1);
+            }
+        }
+    }
+
+    pub fn tokens(&self) -> u32 {
+        self.ascii
+            .div_ceil(ASCII_CHARS_PER_TOKEN)
+            .saturating_add(self.other)
+    }
+}
+
+/// Estimated token count of a single message, including its overhead.
+pub fn estimate_message_tokens(message: &ChatMessage) -> u32 {
+    estimate_text_tokens(&message.content).saturating_add(MESSAGE_OVERHEAD_TOKENS)
+}
+
+/// Estimated input (prompt) tokens of `request`.
+pub fn estimate_request_tokens(request: &ChatRequest) -> u32 {
+    request
+        .messages
+        .iter()
+        .map(estimate_message_tokens)
+        .fold(REPLY_PRIMING_TOKENS, u32::saturating_add)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MessageRole;
+
+    #[test]
+    fn test_text_token_counter_matches_whole_text() {
+        let mut counter = TextTokenCounter::default();
+        for piece in ["Hel", "lo, ", "wor", "ld!", " 日本"] {
+            counter.push(piece);
+        }
+        assert_eq!(counter.tokens(), estimate_text_tokens("Hello, world! 日本"));
+        assert_eq!(TextTokenCounter::default().tokens(), 0);
+    }
+
+    #[test]
+    fn test_estimate_text_tokens_ascii() {
+        assert_eq!(estimate_text_tokens(""), 0);
+        assert_eq!(estimate_text_tokens("abcd"), 1);
+        assert_eq!(estimate_text_tokens("abcde"), 2);
+        assert_eq!(estimate_text_tokens("Hello, world!"), 4);
+    }
+
+    #[test]
+    fn test_estimate_text_tokens_non_ascii() {
+        assert_eq!(estimate_text_tokens("日本語"), 3);
+        assert_eq!(estimate_text_tokens("hi 日本"), 3);
+    }
+
+    #[test]
+    fn test_estimate_request_tokens() {
+        let request = ChatRequest {
+            model: "gpt-4".to_string(),
+            messages: vec![
+                ChatMessage {
+                    role: MessageRole::System,
+                    content: "abcd".to_string(),
+                },
+                ChatMessage {
+                    role: MessageRole::User,
+                    content: "abcdefgh".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+        // 3 priming + (1 + 4) + (2 + 4)
+        assert_eq!(estimate_request_tokens(&request), 14);
+    }
+}
@@ -1,12 +1,22 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 
 use crate::error::ConfigError;
-use crate::redis::PolicyUpdate;
+use crate::redis::{InstanceHeartbeat, PolicyUpdate};
 use crate::types::Config;
 
 #[async_trait]
 pub trait ConfigStore: Clone + Send + Sync + 'static {
     async fn fetch_config(&self) -> Result<Config, ConfigError>;
-    async fn publish_config_update(&self, config: &Config) -> Result<(), ConfigError>;
+    /// Publish `config` as a new version.  Returns the version.
+    async fn publish_config_update(&self, config: &Config) -> Result<u64, ConfigError>;
+    /// A recently published version, if it is still kept.
+    async fn fetch_config_version(&self, version: u64) -> Result<Option<Config>, ConfigError>;
+    /// Latest heartbeat of each live data-plane instance.
+    async fn fleet_heartbeats(&self) -> Result<Vec<InstanceHeartbeat>, ConfigError>;
+    /// Drain the API key uses the data plane recorded since the last call,
+    /// keyed by API key id.
+    async fn take_api_key_uses(&self) -> Result<HashMap<String, DateTime<Utc>>, ConfigError>;
     async fn publish_policy_update(&self, update: &PolicyUpdate) -> Result<(), ConfigError>;
 }
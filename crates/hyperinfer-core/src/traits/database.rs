@@ -1,15 +1,24 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 use crate::error::DbError;
+use crate::evals::{EvalItem, EvalItemResult};
+use crate::pricing::ConfiguredPrice;
+use crate::rbac::Role;
+use crate::types::{
+    ChatMessage, MessageRole, OutputTransform, ParamClamps, UsageEvent, VirtualKey,
+};
 
 #[async_trait]
 pub trait Database: Clone + Send + Sync + 'static {
     async fn get_team(&self, id: &str) -> Result<Option<Team>, DbError>;
     async fn create_team(&self, name: &str, budget_cents: i64) -> Result<Team, DbError>;
     async fn get_user(&self, id: &str) -> Result<Option<User>, DbError>;
-    async fn create_user(&self, team_id: &str, email: &str, role: &str) -> Result<User, DbError>;
+    /// The active (not deleted) user with `email`.
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, DbError>;
+    async fn create_user(&self, team_id: &str, email: &str, role: Role) -> Result<User, DbError>;
     async fn get_api_key(&self, id: &str) -> Result<Option<ApiKey>, DbError>;
     async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, DbError>;
     async fn create_api_key(
@@ -20,6 +29,7 @@ pub trait Database: Clone + Send + Sync + 'static {
         name: Option<String>,
     ) -> Result<ApiKey, DbError>;
     async fn get_model_alias(&self, id: &str) -> Result<Option<ModelAlias>, DbError>;
+    async fn list_model_aliases(&self) -> Result<Vec<ModelAlias>, DbError>;
     async fn create_model_alias(
         &self,
         team_id: &str,
@@ -34,6 +44,7 @@ pub trait Database: Clone + Send + Sync + 'static {
         rpm_limit: i32,
         tpm_limit: i32,
     ) -> Result<Quota, DbError>;
+    #[allow(clippy::too_many_arguments)]
     async fn record_usage(
         &self,
         team_id: &str,
@@ -42,28 +53,417 @@ pub trait Database: Clone + Send + Sync + 'static {
         input_tokens: i32,
         output_tokens: i32,
         response_time_ms: i64,
+        metadata: &HashMap<String, String>,
     ) -> Result<UsageLog, DbError>;
+    async fn record_request_error(
+        &self,
+        team_id: &str,
+        api_key_id: &str,
+        model: &str,
+        provider: Option<String>,
+        error: &str,
+    ) -> Result<(), DbError>;
+    /// Record a consumed usage event: its usage, or for a failed request
+    /// its error.  An event whose `entry_id` was recorded before is skipped,
+    /// so redelivered stream entries are not counted twice; returns whether
+    /// the event was new.
+    async fn record_usage_event(&self, event: &UsageEvent) -> Result<bool, DbError>;
+    async fn get_model_usage_since(
+        &self,
+        team_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<ModelUsage>, DbError>;
+    /// Successful usage of every team since `since`, per model.
+    async fn get_all_model_usage_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<ModelUsage>, DbError>;
+    /// Usage of each of a team's keys in `[start, end)`, split into windows
+    /// of `window_minutes` counted back from `end`.
+    async fn get_key_usage_buckets(
+        &self,
+        team_id: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        window_minutes: i32,
+    ) -> Result<Vec<KeyUsageBucket>, DbError>;
+    async fn count_request_errors_since(
+        &self,
+        team_id: Option<String>,
+        provider: Option<String>,
+        since: DateTime<Utc>,
+    ) -> Result<i64, DbError>;
+    async fn create_alert_rule(&self, rule: &NewAlertRule) -> Result<AlertRule, DbError>;
+    async fn list_alert_rules(&self) -> Result<Vec<AlertRule>, DbError>;
+    async fn get_open_alert(&self, rule_id: &str) -> Result<Option<Alert>, DbError>;
+    async fn create_alert(
+        &self,
+        rule: &AlertRule,
+        message: &str,
+        observed_value: f64,
+    ) -> Result<Alert, DbError>;
+    async fn mark_alert_delivered(&self, alert_id: &str) -> Result<(), DbError>;
+    async fn resolve_alert(&self, alert_id: &str) -> Result<(), DbError>;
+    async fn list_alerts(&self, team_id: Option<String>, limit: i64)
+        -> Result<Vec<Alert>, DbError>;
+    async fn list_api_keys_for_team(&self, team_id: &str) -> Result<Vec<ApiKey>, DbError>;
+    async fn upsert_budget_policy(
+        &self,
+        team_id: &str,
+        action: &str,
+        throttle_rpm: Option<i32>,
+    ) -> Result<BudgetPolicy, DbError>;
+    async fn get_budget_policy(&self, team_id: &str) -> Result<Option<BudgetPolicy>, DbError>;
+    async fn list_budget_policies(&self) -> Result<Vec<BudgetPolicy>, DbError>;
+    async fn set_budget_enforcement(
+        &self,
+        team_id: &str,
+        enforced_action: Option<String>,
+    ) -> Result<(), DbError>;
+    async fn list_teams(&self) -> Result<Vec<Team>, DbError>;
+    async fn update_team_billing(
+        &self,
+        team_id: &str,
+        anchor_day: i32,
+        timezone: &str,
+    ) -> Result<Team, DbError>;
+    async fn get_model_usage_between(
+        &self,
+        team_id: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<ModelUsage>, DbError>;
+    /// Store a closed period.  Returns `None` if it was already recorded.
+    async fn record_billing_period(
+        &self,
+        period: &BillingPeriod,
+    ) -> Result<Option<BillingPeriod>, DbError>;
+    async fn list_billing_periods(
+        &self,
+        team_id: &str,
+        limit: i64,
+    ) -> Result<Vec<BillingPeriod>, DbError>;
+    async fn create_model_price(&self, price: &NewModelPrice) -> Result<ConfiguredPrice, DbError>;
+    async fn get_model_price(&self, id: &str) -> Result<Option<ConfiguredPrice>, DbError>;
+    async fn list_model_prices(&self) -> Result<Vec<ConfiguredPrice>, DbError>;
+    async fn update_model_price(
+        &self,
+        id: &str,
+        price: &NewModelPrice,
+    ) -> Result<ConfiguredPrice, DbError>;
+    async fn delete_model_price(&self, id: &str) -> Result<(), DbError>;
+    /// Replace the virtual-key metadata (tags, allowed models, budget share)
+    /// of an API key.  Returns `DbError::NotFound` if the key does not exist.
+    async fn update_api_key_metadata(
+        &self,
+        id: &str,
+        metadata: &ApiKeyMetadata,
+    ) -> Result<ApiKey, DbError>;
+    /// Deactivate a key.  Returns `DbError::NotFound` if the key does not
+    /// exist or is already inactive.
+    async fn revoke_api_key(&self, id: &str) -> Result<ApiKey, DbError>;
+    async fn list_active_api_keys(&self) -> Result<Vec<ApiKey>, DbError>;
+    /// Move keys' `last_used_at` forward to the given times, keyed by API key
+    /// id.  Never moves it back.  Returns the number of keys updated.
+    async fn record_api_key_uses(
+        &self,
+        uses: &HashMap<String, DateTime<Utc>>,
+    ) -> Result<u64, DbError>;
+    /// Active keys not used since `cutoff`, including keys created before it
+    /// and never used, least recently used first.
+    async fn list_stale_api_keys(&self, cutoff: DateTime<Utc>) -> Result<Vec<ApiKey>, DbError>;
+    /// Usage in `[start, end)` grouped by the value of request tag `tag` and
+    /// model.  Requests without the tag have `tag_value: None`.
+    async fn get_tag_usage_between(
+        &self,
+        team_id: &str,
+        tag: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<TagUsage>, DbError>;
+    /// Usage logs recorded in `[start, end)`, oldest first, optionally for a
+    /// single team.  Pages with `limit` / `offset`.
+    async fn list_usage_logs(
+        &self,
+        team_id: Option<String>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<UsageLog>, DbError>;
+    /// Mark a team deleted and deactivate its API keys.  Deleted teams are
+    /// hidden from reads but keep their usage history until purged.
+    /// Returns `DbError::NotFound` if the team does not exist or is already
+    /// deleted.
+    async fn delete_team(&self, id: &str) -> Result<(), DbError>;
+    /// Mark a user deleted and deactivate their API keys, like
+    /// [`Database::delete_team`].
+    async fn delete_user(&self, id: &str) -> Result<(), DbError>;
+    /// Permanently remove teams and users deleted before `before`, with
+    /// everything that references them.  Returns the number of rows removed.
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> Result<u64, DbError>;
+    async fn create_organization(&self, org: &NewOrganization) -> Result<Organization, DbError>;
+    async fn get_organization(&self, id: &str) -> Result<Option<Organization>, DbError>;
+    async fn list_organizations(&self) -> Result<Vec<Organization>, DbError>;
+    /// Move a team into `organization_id`, or out of any organization with
+    /// `None`.  Returns `DbError::NotFound` if the team does not exist.
+    async fn set_team_organization(
+        &self,
+        team_id: &str,
+        organization_id: Option<String>,
+    ) -> Result<Team, DbError>;
+    /// Pin a team's traffic to `data_region`, or lift the constraint with
+    /// `None`.  Returns `DbError::NotFound` if the team does not exist.
+    async fn set_team_data_region(
+        &self,
+        team_id: &str,
+        data_region: Option<String>,
+    ) -> Result<Team, DbError>;
+    /// Replace a team's feature flags.  Returns `DbError::NotFound` if the
+    /// team does not exist.
+    async fn set_team_features(
+        &self,
+        team_id: &str,
+        features: HashMap<String, bool>,
+    ) -> Result<Team, DbError>;
+    /// Set or, with `None`, remove a team's parameter clamps.  Returns
+    /// `DbError::NotFound` if the team does not exist.
+    async fn set_team_param_clamps(
+        &self,
+        team_id: &str,
+        param_clamps: Option<ParamClamps>,
+    ) -> Result<Team, DbError>;
+    /// Replace a team's output transforms.  Returns `DbError::NotFound` if
+    /// the team does not exist.
+    async fn set_team_output_transforms(
+        &self,
+        team_id: &str,
+        transforms: Vec<OutputTransform>,
+    ) -> Result<Team, DbError>;
+    /// Set a user's role and record the change, attributed to `changed_by`,
+    /// in the same transaction.  Returns `DbError::NotFound` if the user
+    /// does not exist or is deleted.
+    async fn set_user_role(
+        &self,
+        user_id: &str,
+        role: Role,
+        changed_by: &str,
+    ) -> Result<User, DbError>;
+    /// A user's role changes, newest first.
+    async fn list_role_changes(&self, user_id: &str) -> Result<Vec<RoleChange>, DbError>;
+    async fn create_webhook(&self, webhook: &NewWebhook) -> Result<Webhook, DbError>;
+    async fn get_webhook(&self, id: &str) -> Result<Option<Webhook>, DbError>;
+    async fn list_webhooks(&self, team_id: &str) -> Result<Vec<Webhook>, DbError>;
+    /// Remove a webhook along with its delivery log.  Returns
+    /// `DbError::NotFound` if it does not exist.
+    async fn delete_webhook(&self, id: &str) -> Result<(), DbError>;
+    /// Active webhooks subscribed to `event`: those of `team_id`, or of
+    /// every team with `None`.
+    async fn list_webhooks_for_event(
+        &self,
+        team_id: Option<String>,
+        event: &str,
+    ) -> Result<Vec<Webhook>, DbError>;
+    /// Queue `payload` for delivery to a webhook, due immediately.
+    async fn enqueue_webhook_delivery(
+        &self,
+        webhook_id: &str,
+        event: &str,
+        payload: &serde_json::Value,
+    ) -> Result<WebhookDelivery, DbError>;
+    /// Pending deliveries due by `now`, oldest first.
+    async fn list_due_webhook_deliveries(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, DbError>;
+    /// Record one delivery attempt and move the delivery to `status`.
+    /// `next_attempt_at` is set for deliveries that stay pending.
+    async fn record_webhook_attempt(
+        &self,
+        id: &str,
+        status: &str,
+        response_status: Option<i32>,
+        error: Option<String>,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) -> Result<(), DbError>;
+    /// A webhook's deliveries, newest first.
+    async fn list_webhook_deliveries(
+        &self,
+        webhook_id: &str,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, DbError>;
+    async fn create_report_subscription(
+        &self,
+        subscription: &NewReportSubscription,
+    ) -> Result<ReportSubscription, DbError>;
+    /// Report subscriptions of `team_id`, or of every team with `None`.
+    async fn list_report_subscriptions(
+        &self,
+        team_id: Option<String>,
+    ) -> Result<Vec<ReportSubscription>, DbError>;
+    /// Returns `DbError::NotFound` if the subscription does not exist.
+    async fn delete_report_subscription(&self, id: &str) -> Result<(), DbError>;
+    /// Record that the report for the period ending at `period_end` was sent.
+    async fn mark_report_sent(&self, id: &str, period_end: DateTime<Utc>) -> Result<(), DbError>;
+    async fn create_quota_template(
+        &self,
+        template: &NewQuotaTemplate,
+    ) -> Result<QuotaTemplate, DbError>;
+    async fn list_quota_templates(&self) -> Result<Vec<QuotaTemplate>, DbError>;
+    async fn get_quota_template(&self, name: &str) -> Result<Option<QuotaTemplate>, DbError>;
+    /// Returns `DbError::NotFound` if no template is called `name`.
+    async fn update_quota_template(
+        &self,
+        name: &str,
+        template: &NewQuotaTemplate,
+    ) -> Result<QuotaTemplate, DbError>;
+    /// Returns `DbError::NotFound` if no template is called `name`.
+    async fn delete_quota_template(&self, name: &str) -> Result<(), DbError>;
+    /// Set the quota of every team in `team_ids` to the limits of template
+    /// `name`, creating or replacing it.  Teams that do not exist are
+    /// skipped.  Returns `DbError::NotFound` if the template does not
+    /// exist.
+    async fn apply_quota_template(
+        &self,
+        name: &str,
+        team_ids: &[String],
+    ) -> Result<Vec<Quota>, DbError>;
+
+    /// Every active team, user, API key, model alias and quota as a bundle.
+    async fn export_bundle(&self) -> Result<Bundle, DbError>;
+    /// Create or update everything in `bundle` in one transaction, matching
+    /// teams by name, users by email, keys by hash and aliases by team and
+    /// alias.  With `dry_run` the transaction is rolled back.  Returns
+    /// `DbError::NotFound` if a record refers to a team or user that is in
+    /// neither the bundle nor the database.
+    async fn import_bundle(&self, bundle: &Bundle, dry_run: bool)
+        -> Result<ImportSummary, DbError>;
+
+    async fn create_conversation(
+        &self,
+        conversation: &NewConversation,
+    ) -> Result<Conversation, DbError>;
+    async fn get_conversation(&self, id: &str) -> Result<Option<Conversation>, DbError>;
+    /// Add `messages` to the end of a conversation.  Returns
+    /// `DbError::NotFound` if the conversation does not exist.
+    async fn append_conversation_messages(
+        &self,
+        conversation_id: &str,
+        messages: &[ChatMessage],
+    ) -> Result<Vec<ConversationMessage>, DbError>;
+    /// A conversation's messages, oldest first.
+    async fn list_conversation_messages(
+        &self,
+        conversation_id: &str,
+    ) -> Result<Vec<ConversationMessage>, DbError>;
+
+    async fn create_eval_dataset(&self, dataset: &NewEvalDataset) -> Result<EvalDataset, DbError>;
+    async fn get_eval_dataset(&self, id: &str) -> Result<Option<EvalDataset>, DbError>;
+    /// Store a run and its results in one transaction, with its aggregate
+    /// scores computed from the results.
+    async fn create_eval_run(&self, run: &NewEvalRun) -> Result<EvalRun, DbError>;
+    async fn get_eval_run(&self, id: &str) -> Result<Option<EvalRun>, DbError>;
+    /// Runs of a dataset, newest first.
+    async fn list_eval_runs(&self, dataset_id: &str) -> Result<Vec<EvalRun>, DbError>;
+    /// A run's results, by item index.
+    async fn list_eval_results(&self, run_id: &str) -> Result<Vec<EvalItemResult>, DbError>;
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Team {
     pub id: String,
     pub name: String,
     pub budget_cents: i64,
+    /// Day of the month (1-28) on which the team's billing period starts.
+    #[serde(default = "default_billing_anchor_day")]
+    pub billing_anchor_day: i32,
+    /// IANA timezone the anchor day is interpreted in.
+    #[serde(default = "default_billing_timezone")]
+    pub billing_timezone: String,
+    /// Organization whose budget and limits the team also counts against.
+    #[serde(default)]
+    pub organization_id: Option<String>,
+    /// Data region the team's traffic is pinned to; see
+    /// `Config::team_data_regions`.
+    #[serde(default)]
+    pub data_region: Option<String>,
+    /// Feature flags by name; see `Config::team_features`.
+    #[serde(default)]
+    pub features: HashMap<String, bool>,
+    /// Bounds on the team's sampling parameters; see
+    /// `Config::team_param_clamps`.
+    #[serde(default)]
+    pub param_clamps: Option<ParamClamps>,
+    /// Cleanup applied to the team's chat responses; see
+    /// `Config::team_output_transforms`.
+    #[serde(default)]
+    pub output_transforms: Vec<OutputTransform>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// A group of teams sharing one budget and set of rate limits, which apply
+/// on top of each team's own.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Organization {
+    pub id: String,
+    pub name: String,
+    /// Monthly budget in cents shared by all member teams; 0 for none.
+    pub budget_cents: i64,
+    pub rpm_limit: Option<i32>,
+    pub tpm_limit: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An organization submitted through the admin API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NewOrganization {
+    pub name: String,
+    #[serde(default)]
+    pub budget_cents: i64,
+    #[serde(default)]
+    pub rpm_limit: Option<i32>,
+    #[serde(default)]
+    pub tpm_limit: Option<i32>,
+}
+
+fn default_billing_anchor_day() -> i32 {
+    1
+}
+
+fn default_billing_timezone() -> String {
+    "UTC".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct User {
     pub id: String,
     pub team_id: String,
     pub email: String,
-    pub role: String,
+    pub role: Role,
     pub created_at: DateTime<Utc>,
 }
 
+/// Audit record of a change to a user's role.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RoleChange {
+    pub id: String,
+    pub user_id: String,
+    pub old_role: Role,
+    pub new_role: Role,
+    pub changed_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ApiKey {
     pub id: String,
     pub key_hash: String,
@@ -73,9 +473,49 @@ pub struct ApiKey {
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
     pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    #[serde(default)]
+    pub budget_cents: Option<i64>,
+    /// When the key was last used, to within a minute or so; `None` if it
+    /// never has been.
+    #[serde(default)]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiKey> for VirtualKey {
+    fn from(key: ApiKey) -> Self {
+        VirtualKey {
+            id: key.id,
+            key_hash: key.key_hash,
+            team_id: key.team_id,
+            user_id: Some(key.user_id),
+            name: key.name,
+            tags: key.tags,
+            allowed_models: key.allowed_models,
+            budget_cents: key.budget_cents,
+            is_active: key.is_active,
+            expires_at: key.expires_at,
+        }
+    }
+}
+
+/// Virtual-key metadata attached to an API key through the admin API.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiKeyMetadata {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    #[serde(default)]
+    pub budget_cents: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ModelAlias {
     pub id: String,
     pub team_id: String,
@@ -85,7 +525,15 @@ pub struct ModelAlias {
     pub created_at: DateTime<Utc>,
 }
 
+impl ModelAlias {
+    /// Alias target in the `provider/model` form used by `Config`.
+    pub fn target(&self) -> String {
+        format!("{}/{}", self.provider, self.target_model)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Quota {
     pub id: String,
     pub team_id: String,
@@ -94,6 +542,27 @@ pub struct Quota {
     pub updated_at: DateTime<Utc>,
 }
 
+/// A named set of quota limits, such as `starter` or `pro`, that can be
+/// applied to many teams at once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct QuotaTemplate {
+    pub id: String,
+    pub name: String,
+    pub rpm_limit: i32,
+    pub tpm_limit: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NewQuotaTemplate {
+    pub name: String,
+    pub rpm_limit: i32,
+    pub tpm_limit: i32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UsageLog {
     pub id: String,
@@ -104,4 +573,406 @@ pub struct UsageLog {
     pub output_tokens: i32,
     pub response_time_ms: i64,
     pub recorded_at: DateTime<Utc>,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+/// Aggregated successful usage of one model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelUsage {
+    pub model: String,
+    pub requests: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+}
+
+/// Aggregated successful usage of one model for one value of a request tag.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagUsage {
+    pub tag_value: Option<String>,
+    pub model: String,
+    pub requests: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+}
+
+/// Successful requests by one API key to one model within one hour of day
+/// (UTC) of one window, counted back from the end of the queried range:
+/// window 0 is the latest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyUsageBucket {
+    pub api_key_id: String,
+    pub model: String,
+    pub windows_ago: i64,
+    pub hour: i32,
+    pub requests: i64,
+}
+
+/// An alert threshold.  `kind` is one of `budget` (fraction of the team
+/// budget spent this billing period), `error_rate` (fraction of failed requests in
+/// the window), `provider_down` (failed requests to `provider` in the
+/// window) or `anomaly` (z-score of a key's requests in the window against
+/// its history; see the server's anomaly module).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AlertRule {
+    pub id: String,
+    pub team_id: Option<String>,
+    pub kind: String,
+    pub threshold: f64,
+    pub provider: Option<String>,
+    pub window_minutes: i32,
+    pub webhook_url: String,
+    pub webhook_format: String,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NewAlertRule {
+    pub team_id: Option<String>,
+    pub kind: String,
+    pub threshold: f64,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default = "default_window_minutes")]
+    pub window_minutes: i32,
+    pub webhook_url: String,
+    #[serde(default = "default_webhook_format")]
+    pub webhook_format: String,
+}
+
+fn default_window_minutes() -> i32 {
+    15
+}
+
+fn default_webhook_format() -> String {
+    "json".to_string()
+}
+
+/// A fired alert.  It stays open (`resolved_at == None`) until its rule's
+/// condition clears, so each breach notifies once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Alert {
+    pub id: String,
+    pub rule_id: String,
+    pub team_id: Option<String>,
+    pub kind: String,
+    pub message: String,
+    pub observed_value: f64,
+    pub threshold: f64,
+    pub delivered: bool,
+    pub fired_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+/// What happens to a team's keys once it has spent its budget.  `action` is
+/// `warn` (notify only), `throttle` (cap each key at `throttle_rpm`) or
+/// `block` (revoke keys).  `enforced_action` is set while the action is in
+/// effect and cleared when spend drops back under budget, e.g. when a new
+/// billing period starts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BudgetPolicy {
+    pub team_id: String,
+    pub action: String,
+    pub throttle_rpm: Option<i32>,
+    pub enforced_action: Option<String>,
+    pub enforced_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Usage snapshot of a closed billing period.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BillingPeriod {
+    pub id: String,
+    pub team_id: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub requests: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub spend_cents: f64,
+    pub budget_cents: i64,
+    pub closed_at: DateTime<Utc>,
+}
+
+/// A model price submitted through the admin API.  `effective_from`
+/// defaults to now.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NewModelPrice {
+    pub model: String,
+    pub provider: String,
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+    #[serde(default)]
+    pub effective_from: Option<DateTime<Utc>>,
+}
+
+/// A team's subscription to lifecycle events.  Every delivery is signed
+/// with `secret`, which is write-only through the admin API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Webhook {
+    pub id: String,
+    pub team_id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    #[serde(skip_serializing, default)]
+    pub secret: String,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NewWebhook {
+    pub team_id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    pub secret: String,
+}
+
+/// One event queued for a webhook.  `status` is `pending` until the
+/// receiver accepts it (`delivered`) or every retry has failed (`failed`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    /// HTTP status of the last attempt, if the receiver answered.
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// A team's scheduled usage report.  `frequency` is `weekly` or `monthly`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReportSubscription {
+    pub id: String,
+    pub team_id: String,
+    pub frequency: String,
+    pub recipients: Vec<String>,
+    /// End of the last period a report was sent for.
+    pub last_period_end: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NewReportSubscription {
+    pub team_id: String,
+    pub frequency: String,
+    pub recipients: Vec<String>,
+}
+
+/// Chat history kept by the control plane for clients that want it stored
+/// server-side.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Conversation {
+    pub id: String,
+    pub team_id: String,
+    pub title: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// When a message was last appended.
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NewConversation {
+    pub team_id: String,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConversationMessage {
+    pub id: i64,
+    pub conversation_id: String,
+    pub role: MessageRole,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<ConversationMessage> for ChatMessage {
+    fn from(message: ConversationMessage) -> Self {
+        ChatMessage {
+            role: message.role,
+            content: message.content,
+        }
+    }
+}
+
+/// Prompts and the criteria their answers are judged on; see
+/// [`crate::evals`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EvalDataset {
+    pub id: String,
+    pub team_id: String,
+    pub name: String,
+    pub items: Vec<EvalItem>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NewEvalDataset {
+    pub team_id: String,
+    pub name: String,
+    pub items: Vec<EvalItem>,
+}
+
+/// One pass of a dataset against a model, with its aggregate scores.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EvalRun {
+    pub id: String,
+    pub dataset_id: String,
+    pub model: String,
+    pub item_count: i32,
+    pub passed_count: i32,
+    /// Mean item score, from 0 to 1.
+    pub mean_score: f64,
+    pub mean_latency_ms: f64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NewEvalRun {
+    pub dataset_id: String,
+    pub model: String,
+    pub results: Vec<EvalItemResult>,
+}
+
+/// Teams, users, API key metadata, model aliases and quotas in a form that
+/// can move between deployments: records refer to teams by name and users
+/// by email rather than by id.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Bundle {
+    #[serde(default)]
+    pub teams: Vec<BundleTeam>,
+    #[serde(default)]
+    pub users: Vec<BundleUser>,
+    #[serde(default)]
+    pub api_keys: Vec<BundleApiKey>,
+    #[serde(default)]
+    pub model_aliases: Vec<BundleModelAlias>,
+    #[serde(default)]
+    pub quotas: Vec<BundleQuota>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BundleTeam {
+    pub name: String,
+    #[serde(default)]
+    pub budget_cents: i64,
+    #[serde(default = "default_billing_anchor_day")]
+    pub billing_anchor_day: i32,
+    #[serde(default = "default_billing_timezone")]
+    pub billing_timezone: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BundleUser {
+    pub email: String,
+    /// Team name.
+    pub team: String,
+    #[serde(default)]
+    pub role: Role,
+}
+
+/// An API key's metadata.  Only the hash of the key travels, so clients
+/// keep using the keys they already have.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BundleApiKey {
+    pub key_hash: String,
+    /// Team name.
+    pub team: String,
+    /// Owner's email, if the key belongs to a user.
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default = "default_true")]
+    pub is_active: bool,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    #[serde(default)]
+    pub budget_cents: Option<i64>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BundleModelAlias {
+    /// Team name.
+    pub team: String,
+    pub alias: String,
+    pub target_model: String,
+    pub provider: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BundleQuota {
+    /// Team name.
+    pub team: String,
+    pub rpm_limit: i32,
+    pub tpm_limit: i32,
+}
+
+/// Records an import created and updated, by kind.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportSummary {
+    pub dry_run: bool,
+    pub teams: ImportCounts,
+    pub users: ImportCounts,
+    pub api_keys: ImportCounts,
+    pub model_aliases: ImportCounts,
+    pub quotas: ImportCounts,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportCounts {
+    pub created: u64,
+    pub updated: u64,
+}
+
+impl ImportCounts {
+    pub fn record(&mut self, created: bool) {
+        if created {
+            self.created += 1;
+        } else {
+            self.updated += 1;
+        }
+    }
 }
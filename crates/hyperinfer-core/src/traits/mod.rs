@@ -1,5 +1,15 @@
+#[cfg(feature = "redis")]
 mod config_store;
 mod database;
 
+#[cfg(feature = "redis")]
 pub use config_store::ConfigStore;
-pub use database::{ApiKey, Database, ModelAlias, Quota, Team, UsageLog, User};
+pub use database::{
+    Alert, AlertRule, ApiKey, ApiKeyMetadata, BillingPeriod, BudgetPolicy, Bundle, BundleApiKey,
+    BundleModelAlias, BundleQuota, BundleTeam, BundleUser, Conversation, ConversationMessage,
+    Database, EvalDataset, EvalRun, ImportCounts, ImportSummary, KeyUsageBucket, ModelAlias,
+    ModelUsage, NewAlertRule, NewConversation, NewEvalDataset, NewEvalRun, NewModelPrice,
+    NewOrganization, NewQuotaTemplate, NewReportSubscription, NewWebhook, Organization, Quota,
+    QuotaTemplate, ReportSubscription, RoleChange, TagUsage, Team, UsageLog, User, Webhook,
+    WebhookDelivery,
+};
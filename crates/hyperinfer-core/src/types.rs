@@ -2,9 +2,11 @@
 //!
 //! Defines common structures used across the system.
 
+use crate::pricing::{ConfiguredPrice, ModelPrice};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::time::Instant;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 /// A chat request to an LLM provider
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
@@ -21,6 +23,173 @@ pub struct ChatRequest {
     /// Stop sequences: generation halts when any of these strings is produced.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stop: Option<Vec<String>>,
+    /// Caller-defined tags for cost attribution (feature, environment,
+    /// customer id, ...).  Recorded with the request's usage; never sent to
+    /// the provider.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    /// Output format to ask the provider for.  With `json_schema`, the
+    /// client validates the response against the schema and re-asks the
+    /// model when it does not conform.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    /// Compress long messages (e.g. retrieved documents) before the request
+    /// is sent.  Applied by the client; never sent to the provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<CompressionOptions>,
+    /// Reasoning effort for OpenAI o-series models.  Dropped for other
+    /// models.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Extended thinking for Claude models.  Ignored by other providers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingOptions>,
+    /// Validate, route and price the request without calling a provider or
+    /// consuming rate limits.  The response carries a [`DryRunReport`]
+    /// instead of choices.  Never sent to the provider.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+    /// How `chat()` recovers when the provider rejects the prompt as too
+    /// long for the model's context window.  `None` returns the error.
+    /// Applied by the client; never sent to the provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_overflow: Option<ContextOverflow>,
+    /// Routing chosen by the caller over the configured routing, for
+    /// debugging or pinning a deployment.  Requests with one are neither
+    /// hedged nor moved to a longer-context model.  Applied by the client;
+    /// never sent to the provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routing: Option<RoutingOverride>,
+    /// Data region the request must be served from.  Set by the client from
+    /// the caller's team ([`Config::team_data_regions`]); provider
+    /// endpoints outside it are never called.
+    #[serde(skip)]
+    pub data_region: Option<String>,
+}
+
+/// Per-request routing that takes precedence over aliases and routing
+/// rules.  Refused for teams with `enable_routing_overrides` switched off;
+/// the key's model allow-list, drained providers and the team's data region
+/// still apply.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoutingOverride {
+    /// Send the model, as named in the request and without alias
+    /// resolution, to this provider.  The pinned route does not fall back.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider_override: Option<Provider>,
+    /// Fail rather than move to a fallback model when the route is
+    /// unavailable.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disable_fallback: bool,
+    /// Only call provider endpoints whose region or tags include this, e.g.
+    /// a canary deployment.  Providers without such an endpoint are
+    /// treated as unavailable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route_tag: Option<String>,
+}
+
+/// Recovery from a context-length error, retried once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextOverflow {
+    /// Trim the conversation with the configured [`ContextStrategy`]
+    /// (dropping the oldest messages without one) and send it again.
+    Trim,
+    /// Send it to the first fallback model of the request's routing rule
+    /// with a larger context window.
+    LongerContextModel,
+}
+
+/// Extended thinking settings for a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThinkingOptions {
+    /// Tokens the model may spend thinking.  Counted within `max_tokens`,
+    /// so `max_tokens` must be larger.
+    pub budget_tokens: u32,
+}
+
+impl ThinkingOptions {
+    /// Smallest budget Anthropic accepts.
+    pub const MIN_BUDGET_TOKENS: u32 = 1024;
+}
+
+/// How much an OpenAI reasoning model thinks before answering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Minimal,
+    Low,
+    Medium,
+    High,
+}
+
+/// Prompt compression settings for a request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompressionOptions {
+    /// Target fraction of each compressed message's tokens to keep, in
+    /// `(0, 1]`.
+    pub ratio: f64,
+    /// Messages estimated below this many tokens are left alone.
+    #[serde(default = "CompressionOptions::default_min_message_tokens")]
+    pub min_message_tokens: u32,
+}
+
+impl CompressionOptions {
+    pub const DEFAULT_MIN_MESSAGE_TOKENS: u32 = 256;
+
+    fn default_min_message_tokens() -> u32 {
+        Self::DEFAULT_MIN_MESSAGE_TOKENS
+    }
+}
+
+/// Estimated prompt tokens before and after compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressionStats {
+    pub original_tokens: u32,
+    pub compressed_tokens: u32,
+}
+
+impl CompressionStats {
+    pub fn saved_tokens(&self) -> u32 {
+        self.original_tokens.saturating_sub(self.compressed_tokens)
+    }
+}
+
+/// Requested output format, in OpenAI's `response_format` shape.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: JsonSchemaFormat },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonSchemaFormat {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub schema: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+}
+
+impl ResponseFormat {
+    /// System prompt text asking for this format, for providers without a
+    /// native `response_format` parameter.
+    pub fn instruction(&self) -> Option<String> {
+        match self {
+            Self::Text => None,
+            Self::JsonObject => {
+                Some("Respond with a single valid JSON object and nothing else.".to_string())
+            }
+            Self::JsonSchema { json_schema } => Some(format!(
+                "Respond with a single valid JSON object and nothing else. \
+                 It must conform to this JSON schema:\n{}",
+                json_schema.schema
+            )),
+        }
+    }
 }
 
 /// A single streamed token delta from a provider SSE event.
@@ -37,6 +206,10 @@ pub struct ChatChunk {
     pub delta: String,
     /// Set to `"stop"` (or similar) on the last chunk, `None` otherwise.
     pub finish_reason: Option<String>,
+    /// Incremental extended-thinking text.  Thinking chunks carry an empty
+    /// `delta`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<String>,
     /// Token usage — only populated on the final chunk (OpenAI `usage` field
     /// with `stream_options: {include_usage: true}`, or Anthropic `message_delta`).
     pub usage: Option<Usage>,
@@ -56,12 +229,131 @@ impl ChatRequest {
                 "messages cannot be empty",
             )));
         }
+        if let Some(thinking) = &self.thinking {
+            if thinking.budget_tokens < ThinkingOptions::MIN_BUDGET_TOKENS {
+                return Err(crate::HyperInferError::Config(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "thinking budget_tokens must be at least {}, got {}",
+                        ThinkingOptions::MIN_BUDGET_TOKENS,
+                        thinking.budget_tokens
+                    ),
+                )));
+            }
+            if let Some(max_tokens) = self.max_tokens {
+                if max_tokens <= thinking.budget_tokens {
+                    return Err(crate::HyperInferError::Config(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!(
+                            "max_tokens {} must exceed thinking budget_tokens {}",
+                            max_tokens, thinking.budget_tokens
+                        ),
+                    )));
+                }
+            }
+        }
+        if let Some(compression) = &self.compression {
+            if !(compression.ratio > 0.0 && compression.ratio <= 1.0) {
+                return Err(crate::HyperInferError::Config(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "compression ratio must be in (0, 1], got {}",
+                        compression.ratio
+                    ),
+                )));
+            }
+        }
         Ok(())
     }
+
+    /// Reject a `max_tokens` above the known output limit of `model` (the
+    /// resolved upstream model, not an alias).
+    pub fn validate_max_tokens(&self, model: &str) -> Result<(), crate::HyperInferError> {
+        match (self.max_tokens, known_max_output_tokens(model)) {
+            (Some(requested), Some(limit)) if requested > limit => {
+                Err(crate::HyperInferError::Config(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "max_tokens {} exceeds the maximum of {} for model '{}'",
+                        requested, limit, model
+                    ),
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Output budget used when a provider requires `max_tokens` (Anthropic) and
+/// neither the request nor the config sets one.
+pub const DEFAULT_MAX_OUTPUT_TOKENS: u32 = 8192;
+
+/// Known output-token limits, matched by model-name prefix.  More specific
+/// prefixes must come before the prefixes they extend.
+const KNOWN_MAX_OUTPUT_TOKENS: &[(&str, u32)] = &[
+    ("claude-opus-4-5", 64_000),
+    ("claude-opus-4-1", 32_000),
+    ("claude-opus-4", 32_000),
+    ("claude-sonnet-4", 64_000),
+    ("claude-haiku-4-5", 64_000),
+    ("claude-3-7-sonnet", 64_000),
+    ("claude-3-5-sonnet", 8_192),
+    ("claude-3-5-haiku", 8_192),
+    ("claude-3-opus", 4_096),
+    ("claude-3-sonnet", 4_096),
+    ("claude-3-haiku", 4_096),
+];
+
+/// Known context windows (input plus output tokens), matched by model-name
+/// prefix like [`KNOWN_MAX_OUTPUT_TOKENS`].
+const KNOWN_CONTEXT_WINDOWS: &[(&str, u32)] = &[
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 16_385),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4-mini", 200_000),
+    ("claude-", 200_000),
+];
+
+/// Context window of `model` in tokens, if known.
+pub fn known_context_window(model: &str) -> Option<u32> {
+    KNOWN_CONTEXT_WINDOWS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, window)| *window)
+}
+
+/// Whether `model` is an OpenAI o-series reasoning model (`o1`, `o3-mini`,
+/// `o4-mini`, ...).  These take `max_completion_tokens` instead of
+/// `max_tokens` and reject sampling parameters such as `temperature`.
+pub fn is_reasoning_model(model: &str) -> bool {
+    let mut chars = model.chars();
+    chars.next() == Some('o') && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+/// Maximum output tokens `model` can produce, if known.
+pub fn known_max_output_tokens(model: &str) -> Option<u32> {
+    KNOWN_MAX_OUTPUT_TOKENS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, limit)| *limit)
+}
+
+/// Output budget to send for `model` when nothing else specifies one:
+/// [`DEFAULT_MAX_OUTPUT_TOKENS`], capped at the model's known maximum.
+pub fn default_max_output_tokens(model: &str) -> u32 {
+    known_max_output_tokens(model).map_or(DEFAULT_MAX_OUTPUT_TOKENS, |limit| {
+        limit.min(DEFAULT_MAX_OUTPUT_TOKENS)
+    })
 }
 
 /// A single message in a chat conversation
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ChatMessage {
     pub role: MessageRole,
     pub content: String,
@@ -69,6 +361,7 @@ pub struct ChatMessage {
 
 /// The role of a message in a chat
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum MessageRole {
     System,
@@ -81,12 +374,165 @@ pub enum MessageRole {
 pub struct TokenBucket {
     pub capacity: u64,
     pub tokens: u64,
-    pub refill_rate: u64, // tokens per second
+    pub refill_rate: f64, // tokens per second
     pub last_refill: Instant,
 }
 
+impl TokenBucket {
+    /// A full bucket.
+    pub fn new(capacity: u64, refill_rate: f64) -> Self {
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_rate,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// A full bucket that refills `limit` tokens per minute.
+    pub fn per_minute(limit: u64) -> Self {
+        Self::new(limit, limit as f64 / 60.0)
+    }
+
+    /// Add the whole tokens earned since the last refill.  The fraction of
+    /// a token left over stays banked until it completes.
+    pub fn refill(&mut self) {
+        let now = Instant::now();
+        if self.tokens >= self.capacity {
+            self.last_refill = now;
+            return;
+        }
+        let earned =
+            (now.duration_since(self.last_refill).as_secs_f64() * self.refill_rate).floor();
+        if earned < 1.0 {
+            return;
+        }
+        self.tokens = self.tokens.saturating_add(earned as u64).min(self.capacity);
+        if self.tokens == self.capacity {
+            self.last_refill = now;
+        } else {
+            self.last_refill += Duration::from_secs_f64(earned / self.refill_rate);
+        }
+    }
+
+    /// Take `n` tokens if that many are available.
+    pub fn try_consume(&mut self, n: u64) -> bool {
+        self.refill();
+        if n <= self.tokens {
+            self.tokens -= n;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Time until `n` tokens will be available, counting the fraction of a
+    /// token already earned.  `None` when they never will be: `n` exceeds
+    /// the capacity or the bucket does not refill.
+    pub fn retry_after(&self, n: u64) -> Option<Duration> {
+        if n <= self.tokens {
+            return Some(Duration::ZERO);
+        }
+        if n > self.capacity || self.refill_rate <= 0.0 {
+            return None;
+        }
+        let earned = self.last_refill.elapsed().as_secs_f64() * self.refill_rate;
+        let missing = (n - self.tokens) as f64 - earned;
+        Some(Duration::from_secs_f64(
+            (missing / self.refill_rate).max(0.0),
+        ))
+    }
+}
+
+/// A [`TokenBucket`] shared between tasks.
+#[derive(Debug, Clone)]
+pub struct SharedTokenBucket(Arc<Mutex<TokenBucket>>);
+
+impl SharedTokenBucket {
+    pub fn new(bucket: TokenBucket) -> Self {
+        Self(Arc::new(Mutex::new(bucket)))
+    }
+
+    /// See [`TokenBucket::try_consume`].
+    pub fn try_consume(&self, n: u64) -> bool {
+        self.lock().try_consume(n)
+    }
+
+    /// Take `n` tokens, or report how long until they will be available
+    /// (see [`TokenBucket::retry_after`]).
+    pub fn consume(&self, n: u64) -> Result<(), Option<Duration>> {
+        let mut bucket = self.lock();
+        if bucket.try_consume(n) {
+            Ok(())
+        } else {
+            Err(bucket.retry_after(n))
+        }
+    }
+
+    /// Take `n` tokens from this bucket and `m` from `other` only if both
+    /// have them, so a rejection spends neither.  Otherwise reports the
+    /// longer wait of the two, or `None` when the pair can never be
+    /// satisfied because either bucket never will be.
+    ///
+    /// The buckets are locked in address order, so callers passing the
+    /// same pair in opposite orders cannot deadlock, and passing one
+    /// bucket twice takes `n + m` from it.
+    pub fn consume_with(
+        &self,
+        n: u64,
+        other: &SharedTokenBucket,
+        m: u64,
+    ) -> Result<(), Option<Duration>> {
+        if Arc::ptr_eq(&self.0, &other.0) {
+            return self.consume(n.saturating_add(m));
+        }
+        let (mut first, mut second) = if Arc::as_ptr(&self.0) < Arc::as_ptr(&other.0) {
+            let first = self.lock();
+            (first, other.lock())
+        } else {
+            let second = other.lock();
+            (self.lock(), second)
+        };
+        first.refill();
+        second.refill();
+        if n <= first.tokens && m <= second.tokens {
+            first.tokens -= n;
+            second.tokens -= m;
+            return Ok(());
+        }
+        Err(first
+            .retry_after(n)
+            .zip(second.retry_after(m))
+            .map(|(a, b)| a.max(b)))
+    }
+
+    /// Tokens currently available.
+    pub fn available(&self) -> u64 {
+        let mut bucket = self.lock();
+        bucket.refill();
+        bucket.tokens
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.lock().capacity
+    }
+
+    /// Time until the bucket is full again.
+    pub fn time_to_full(&self) -> Duration {
+        let mut bucket = self.lock();
+        bucket.refill();
+        let capacity = bucket.capacity;
+        bucket.retry_after(capacity).unwrap_or(Duration::ZERO)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TokenBucket> {
+        // The bucket is plain counters, valid even if a holder panicked.
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
 /// Configuration structure for the system
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Config {
     #[serde(skip_serializing, default)]
     pub api_keys: HashMap<String, String>,
@@ -95,6 +541,628 @@ pub struct Config {
     pub model_aliases: HashMap<String, String>,
     #[serde(default)]
     pub default_provider: Option<Provider>,
+    /// Extra HTTP headers attached to every request sent to a provider,
+    /// keyed by provider name (e.g. `"openai"` →
+    /// `{"OpenAI-Organization": "org-…", "OpenAI-Project": "proj_…"}`).
+    #[serde(default)]
+    pub provider_headers: HashMap<String, HashMap<String, String>>,
+    /// API base URL per provider name, replacing the public endpoint (e.g.
+    /// `"openai"` → `"https://gateway.internal/openai"`).  Request paths
+    /// such as `/v1/chat/completions` are appended to it.
+    #[serde(default)]
+    pub provider_base_urls: HashMap<String, String>,
+    /// Regional endpoints per provider name, failed over between by health.
+    /// Takes precedence over `provider_base_urls` for the same provider.
+    #[serde(default)]
+    pub provider_regions: HashMap<String, ProviderRegions>,
+    /// Default output budget per model, applied when a request leaves
+    /// `max_tokens` unset.  Keyed by resolved model name.
+    #[serde(default)]
+    pub max_output_tokens: HashMap<String, u32>,
+    /// Model prices managed through the control plane.  Models without an
+    /// entry use the built-in price table.
+    #[serde(default)]
+    pub model_prices: Vec<ConfiguredPrice>,
+    /// Virtual keys issued by the control plane, keyed by the SHA-256 hex
+    /// digest of the raw key.
+    #[serde(default)]
+    pub virtual_keys: HashMap<String, VirtualKey>,
+    /// Aliases that only apply to one team's keys, keyed by team ID, then
+    /// alias.  Targets use the same `provider/model` form as `model_aliases`
+    /// and take precedence over them.
+    #[serde(default)]
+    pub team_model_aliases: HashMap<String, HashMap<String, String>>,
+    /// Drain flags and maintenance windows, keyed by provider name.
+    /// Providers without an entry are available.
+    #[serde(default)]
+    pub providers: HashMap<String, ProviderStatus>,
+    /// Hedged requests; disabled when unset.
+    #[serde(default)]
+    pub hedging: Option<HedgingConfig>,
+    /// Context-window management; requests are sent as they are when unset.
+    #[serde(default)]
+    pub context: Option<ContextConfig>,
+    /// Coalescing of identical concurrent requests; disabled when unset.
+    #[serde(default)]
+    pub single_flight: Option<SingleFlightConfig>,
+    /// Data region each residency-constrained team is pinned to, by team
+    /// id.  Such a team's traffic only goes to endpoints in
+    /// `provider_regions` that are in the region, and fails when no route
+    /// has one.
+    #[serde(default)]
+    pub team_data_regions: HashMap<String, String>,
+    /// Organization of each team that belongs to one, by team id.
+    #[serde(default)]
+    pub team_organizations: HashMap<String, String>,
+    /// Feature flags set for a team, by team id, then [`TeamFeature`]
+    /// name.  Names this version does not know are kept but ignored.
+    #[serde(default)]
+    pub team_features: HashMap<String, HashMap<String, bool>>,
+    /// Flags for teams that do not set them, by [`TeamFeature`] name.
+    /// Switching a feature off here and on for one team at a time rolls it
+    /// out gradually.
+    #[serde(default)]
+    pub default_features: HashMap<String, bool>,
+    /// Bounds on the sampling parameters of a team's requests, by team id.
+    #[serde(default)]
+    pub team_param_clamps: HashMap<String, ParamClamps>,
+    /// Cleanup applied, in order, to the content of a team's chat
+    /// responses, by team id.
+    #[serde(default)]
+    pub team_output_transforms: HashMap<String, Vec<OutputTransform>>,
+    /// Bandit experiments, by the model name whose requests they split
+    /// between their arms.
+    #[serde(default)]
+    pub experiments: HashMap<String, crate::experiments::BanditExperiment>,
+    /// Model cascades, by the model name whose requests try their cheap
+    /// model first.
+    #[serde(default)]
+    pub cascades: HashMap<String, crate::cascade::Cascade>,
+    /// Limits shared by all teams of an organization, by organization id.
+    #[serde(default)]
+    pub organization_quotas: HashMap<String, Quota>,
+    /// Version stamped by the control plane when it publishes the config;
+    /// 0 for configs that were never published.
+    #[serde(default)]
+    pub version: u64,
+    /// Who published this version.
+    #[serde(default)]
+    pub author: Option<String>,
+}
+
+impl Config {
+    /// Replace `self` with `update` unless `update` is older, as happens
+    /// when pub/sub messages arrive out of order.  Unversioned updates
+    /// always apply.  Returns whether the update was applied.
+    pub fn apply_update(&mut self, update: Config) -> bool {
+        if update.version != 0 && update.version <= self.version {
+            return false;
+        }
+        *self = update;
+        true
+    }
+
+    /// Organization id and shared quota that `team_id` counts against, if
+    /// its organization has one.
+    pub fn organization_quota(&self, team_id: &str) -> Option<(&str, &Quota)> {
+        let org_id = self.team_organizations.get(team_id)?;
+        let quota = self.organization_quotas.get(org_id)?;
+        Some((org_id, quota))
+    }
+
+    /// Whether `feature` is on for `team_id`: the team's own flag, then
+    /// `default_features`, then the feature's built-in default.  Keys the
+    /// control plane does not know get the defaults.
+    pub fn feature_enabled(&self, team_id: Option<&str>, feature: TeamFeature) -> bool {
+        team_id
+            .and_then(|team_id| self.team_features.get(team_id))
+            .and_then(|features| features.get(feature.as_str()))
+            .or_else(|| self.default_features.get(feature.as_str()))
+            .copied()
+            .unwrap_or(true)
+    }
+
+    /// Parameter bounds of `team_id`, if it has any.
+    pub fn param_clamps(&self, team_id: Option<&str>) -> Option<&ParamClamps> {
+        self.team_param_clamps.get(team_id?)
+    }
+
+    /// Output transforms of `team_id`; empty if it has none.
+    pub fn output_transforms(&self, team_id: Option<&str>) -> &[OutputTransform] {
+        team_id
+            .and_then(|team_id| self.team_output_transforms.get(team_id))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// The experiment `model` is under for requests of `team_id`, if any.
+    pub fn experiment(
+        &self,
+        model: &str,
+        team_id: Option<&str>,
+    ) -> Option<&crate::experiments::BanditExperiment> {
+        self.experiments
+            .get(model)
+            .filter(|experiment| experiment.applies_to(team_id))
+    }
+
+    /// Data region `team_id`'s traffic is pinned to, if any.
+    pub fn data_region(&self, team_id: Option<&str>) -> Option<&str> {
+        self.team_data_regions.get(team_id?).map(String::as_str)
+    }
+
+    /// Whether `provider` has an endpoint in `data_region`.  Providers
+    /// without `provider_regions` have none: their endpoint's location is
+    /// unknown.
+    pub fn serves_region(&self, provider: &Provider, data_region: &str) -> bool {
+        self.provider_regions
+            .get(&provider.to_string())
+            .is_some_and(|regions| regions.serves(data_region))
+    }
+
+    /// Context window of `model` in tokens: configured first, then the
+    /// built-in table.
+    pub fn context_window(&self, model: &str) -> Option<u32> {
+        match &self.context {
+            Some(context) => context.context_window(model),
+            None => known_context_window(model),
+        }
+    }
+
+    /// Configured default `max_tokens` for `model`, capped at the model's
+    /// known maximum.
+    pub fn default_max_tokens(&self, model: &str) -> Option<u32> {
+        let configured = *self.max_output_tokens.get(model)?;
+        Some(known_max_output_tokens(model).map_or(configured, |limit| configured.min(limit)))
+    }
+
+    /// Current price of `model`: configured prices first, then the built-in
+    /// table.
+    pub fn price_for(&self, model: &str) -> Option<ModelPrice> {
+        crate::pricing::resolve_price(&self.model_prices, model, chrono::Utc::now())
+    }
+
+    /// What sending `request` to the resolved `model` would cost at most:
+    /// input tokens are estimated locally and the output is priced at
+    /// `max_tokens`, or the configured or known model maximum.  `None` when
+    /// no price is known for the model.
+    pub fn estimate_cost(
+        &self,
+        model: &str,
+        request: &ChatRequest,
+    ) -> Option<crate::pricing::CostEstimate> {
+        let price = self.price_for(model)?;
+        Some(crate::pricing::CostEstimate::new(
+            model,
+            &price,
+            crate::tokenizer::estimate_request_tokens(request),
+            self.max_output_tokens(model, request),
+        ))
+    }
+
+    /// Most output tokens `request` could produce on `model`: its own
+    /// `max_tokens`, else the configured default, else the model's known
+    /// limit, else [`DEFAULT_MAX_OUTPUT_TOKENS`].
+    pub fn max_output_tokens(&self, model: &str, request: &ChatRequest) -> u32 {
+        request
+            .max_tokens
+            .or_else(|| self.default_max_tokens(model))
+            .or_else(|| known_max_output_tokens(model))
+            .unwrap_or(DEFAULT_MAX_OUTPUT_TOKENS)
+    }
+
+    /// Virtual key whose hash is `key_hash`, if the control plane issued one.
+    pub fn virtual_key(&self, key_hash: &str) -> Option<&VirtualKey> {
+        self.virtual_keys.get(key_hash)
+    }
+
+    /// Target of `alias` for a key of `team_id`: the team's own alias if it
+    /// has one, otherwise the global alias.  Only exact aliases are looked
+    /// up; wildcard and regex aliases are matched by the client router.
+    pub fn alias_target(&self, team_id: Option<&str>, alias: &str) -> Option<&str> {
+        team_id
+            .and_then(|team_id| self.team_model_aliases.get(team_id))
+            .and_then(|aliases| aliases.get(alias))
+            .or_else(|| self.model_aliases.get(alias))
+            .map(String::as_str)
+    }
+
+    /// Why `provider` must not receive traffic at `now`, or `None` if it is
+    /// available.
+    pub fn provider_unavailable(
+        &self,
+        provider: &Provider,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Option<String> {
+        self.providers
+            .get(&provider.to_string())?
+            .unavailable_reason(now)
+    }
+}
+
+/// Hedged requests: when the primary provider has not answered (for
+/// streams: sent its first chunk) within `after_ms`, the request is also sent
+/// to the first available fallback of its routing rules, and whichever
+/// succeeds first is returned while the other call is cancelled.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HedgingConfig {
+    pub after_ms: u64,
+    /// Only hedge requests whose `max_tokens` is at most this, bounding what
+    /// a duplicate call can cost.  Requests without `max_tokens` are not
+    /// hedged when set.
+    #[serde(default)]
+    pub max_output_tokens: Option<u32>,
+}
+
+impl HedgingConfig {
+    /// Whether a request with output budget `max_tokens` may be hedged.
+    pub fn applies_to(&self, max_tokens: Option<u32>) -> bool {
+        match self.max_output_tokens {
+            Some(cap) => max_tokens.is_some_and(|max_tokens| max_tokens <= cap),
+            None => true,
+        }
+    }
+}
+
+/// Several endpoints serving one provider's API, e.g. regional deployments
+/// or gateways.  Each must speak the provider's own wire format.
+///
+/// A call goes to the preferred healthy endpoint and moves on to the next
+/// when the endpoint fails to connect or answers with a retryable error.
+/// After `failure_threshold` such failures in a row an endpoint is left out
+/// for `cooldown_secs`; when every endpoint is out, the one due back first
+/// is tried anyway.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderRegions {
+    pub endpoints: Vec<RegionalEndpoint>,
+    #[serde(default)]
+    pub selection: RegionSelection,
+    #[serde(default = "ProviderRegions::default_failure_threshold")]
+    pub failure_threshold: u32,
+    #[serde(default = "ProviderRegions::default_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+impl ProviderRegions {
+    pub const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+    pub const DEFAULT_COOLDOWN_SECS: u64 = 30;
+
+    fn default_failure_threshold() -> u32 {
+        Self::DEFAULT_FAILURE_THRESHOLD
+    }
+
+    fn default_cooldown_secs() -> u64 {
+        Self::DEFAULT_COOLDOWN_SECS
+    }
+
+    /// Whether any endpoint is in `data_region`.
+    pub fn serves(&self, data_region: &str) -> bool {
+        self.endpoints.iter().any(|e| e.in_region(data_region))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegionalEndpoint {
+    /// Name used in logs, e.g. `"eastus"`.
+    pub region: String,
+    /// API base URL; request paths are appended as for
+    /// `Config::provider_base_urls`.
+    pub base_url: String,
+    /// Data regions the endpoint is in besides `region` itself, e.g.
+    /// `["eu"]` for `"westeurope"`.  Matched against
+    /// `Config::team_data_regions`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl RegionalEndpoint {
+    /// Whether the endpoint is in `data_region`, by name or tag.
+    pub fn in_region(&self, data_region: &str) -> bool {
+        self.region == data_region || self.tags.iter().any(|tag| tag == data_region)
+    }
+}
+
+/// Which healthy endpoint a call goes to first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegionSelection {
+    /// In the order listed.
+    #[default]
+    Priority,
+    /// The one with the lowest recent response time.  Endpoints not yet
+    /// measured are tried first.
+    LowestLatency,
+}
+
+/// Single-flight coalescing: while a `chat()` call is in flight, identical
+/// requests (same model, messages and parameters) under the same key wait
+/// for its response instead of calling the provider again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SingleFlightConfig {
+    /// Whether requests are coalesced for teams without an override and for
+    /// keys the control plane does not know.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Per-team overrides of `enabled`, keyed by team ID.
+    #[serde(default)]
+    pub teams: HashMap<String, bool>,
+}
+
+impl Default for SingleFlightConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            teams: HashMap::new(),
+        }
+    }
+}
+
+impl SingleFlightConfig {
+    /// Whether requests from `team_id` are coalesced.
+    pub fn enabled_for(&self, team_id: Option<&str>) -> bool {
+        team_id
+            .and_then(|team_id| self.teams.get(team_id).copied())
+            .unwrap_or(self.enabled)
+    }
+}
+
+/// Gateway behaviors that can be switched per team through
+/// [`Config::team_features`].  All are on unless switched off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum TeamFeature {
+    /// Answering repeated requests from the response cache.
+    EnableCache,
+    /// `chat_stream()`; off, streaming requests are refused.
+    EnableStreaming,
+    /// Moving to a routing rule's fallback models, for unavailable
+    /// providers, hedging and context-overflow retries.
+    EnableFallback,
+    /// Honoring [`ChatRequest::routing`]; off, requests carrying it are
+    /// refused.
+    EnableRoutingOverrides,
+}
+
+impl TeamFeature {
+    pub const ALL: [TeamFeature; 4] = [
+        TeamFeature::EnableCache,
+        TeamFeature::EnableStreaming,
+        TeamFeature::EnableFallback,
+        TeamFeature::EnableRoutingOverrides,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TeamFeature::EnableCache => "enable_cache",
+            TeamFeature::EnableStreaming => "enable_streaming",
+            TeamFeature::EnableFallback => "enable_fallback",
+            TeamFeature::EnableRoutingOverrides => "enable_routing_overrides",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|feature| feature.as_str() == name)
+    }
+}
+
+/// Upper bounds on a team's sampling parameters, which the client lowers
+/// requests to before sending them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ParamClamps {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_temperature: Option<f64>,
+    /// Also the output budget of requests that leave `max_tokens` unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+}
+
+impl ParamClamps {
+    /// Usage metadata key listing the parameters a request had lowered,
+    /// comma-separated.
+    pub const METADATA_KEY: &'static str = "hyperinfer.clamped";
+
+    pub fn is_empty(&self) -> bool {
+        self.max_temperature.is_none() && self.max_tokens.is_none()
+    }
+
+    /// Lower `request`'s parameters to the bounds.  Returns the names of
+    /// those it lowered; filling in an unset `max_tokens` does not count.
+    pub fn apply(&self, request: &mut ChatRequest) -> Vec<&'static str> {
+        let mut clamped = Vec::new();
+        if let (Some(temperature), Some(max)) = (request.temperature, self.max_temperature) {
+            if temperature > max {
+                request.temperature = Some(max);
+                clamped.push("temperature");
+            }
+        }
+        if let Some(max) = self.max_tokens {
+            match request.max_tokens {
+                Some(max_tokens) if max_tokens > max => {
+                    request.max_tokens = Some(max);
+                    clamped.push("max_tokens");
+                }
+                Some(_) => {}
+                None => request.max_tokens = Some(max),
+            }
+        }
+        clamped
+    }
+}
+
+/// A cleanup step applied to the content of a team's chat responses, so
+/// apps do not each strip the same provider quirks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputTransform {
+    /// Remove leading and trailing whitespace.
+    TrimWhitespace,
+    /// Cut the content at the first of `sequences` or of the request's own
+    /// `stop` sequences, for providers that echo them back.
+    StripStopSequences {
+        #[serde(default)]
+        sequences: Vec<String>,
+    },
+    /// Drop a first line starting with one of `prefixes` (case-insensitive),
+    /// e.g. "Sure! Here is".
+    StripPreamble { prefixes: Vec<String> },
+    /// Unwrap content that is a single fenced Markdown code block.
+    StripCodeFences,
+    /// Cut the content to at most `max_chars` characters.
+    MaxLength { max_chars: usize },
+}
+
+impl OutputTransform {
+    /// Apply `transforms` in order to the content of every choice in
+    /// `response`.  `stop` is the request's own stop sequences.
+    pub fn apply_all(transforms: &[OutputTransform], stop: &[String], response: &mut ChatResponse) {
+        for choice in &mut response.choices {
+            for transform in transforms {
+                transform.apply(stop, &mut choice.message.content);
+            }
+        }
+    }
+
+    fn apply(&self, stop: &[String], content: &mut String) {
+        match self {
+            OutputTransform::TrimWhitespace => {
+                let trimmed = content.trim();
+                if trimmed.len() != content.len() {
+                    *content = trimmed.to_string();
+                }
+            }
+            OutputTransform::StripStopSequences { sequences } => {
+                let cut = sequences
+                    .iter()
+                    .chain(stop)
+                    .filter(|sequence| !sequence.is_empty())
+                    .filter_map(|sequence| content.find(sequence.as_str()))
+                    .min();
+                if let Some(cut) = cut {
+                    content.truncate(cut);
+                }
+            }
+            OutputTransform::StripPreamble { prefixes } => {
+                let start = content.len() - content.trim_start().len();
+                let line_end = content[start..]
+                    .find('\n')
+                    .map_or(content.len(), |end| start + end + 1);
+                let first_line = content[start..line_end].to_lowercase();
+                if prefixes
+                    .iter()
+                    .any(|prefix| first_line.starts_with(&prefix.to_lowercase()))
+                {
+                    content.replace_range(..line_end, "");
+                }
+            }
+            OutputTransform::StripCodeFences => {
+                let trimmed = content.trim();
+                let Some(body) = trimmed
+                    .strip_prefix("```")
+                    .and_then(|rest| rest.strip_suffix("```"))
+                else {
+                    return;
+                };
+                // The opening fence's line may name a language.
+                let Some((_, body)) = body.split_once('\n') else {
+                    return;
+                };
+                if !body.contains("```") {
+                    *content = body.trim_end().to_string();
+                }
+            }
+            OutputTransform::MaxLength { max_chars } => {
+                if let Some((cut, _)) = content.char_indices().nth(*max_chars) {
+                    content.truncate(cut);
+                }
+            }
+        }
+    }
+}
+
+/// Trimming of conversations that would overflow the model's context window.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContextConfig {
+    #[serde(default)]
+    pub strategy: ContextStrategy,
+    /// Context window in tokens per resolved model, overriding the built-in
+    /// table.  Models with neither are never trimmed.
+    #[serde(default)]
+    pub context_windows: HashMap<String, u32>,
+}
+
+impl ContextConfig {
+    /// Context window of `model`: configured first, then the built-in table.
+    pub fn context_window(&self, model: &str) -> Option<u32> {
+        self.context_windows
+            .get(model)
+            .copied()
+            .or_else(|| known_context_window(model))
+    }
+}
+
+/// How a conversation is shortened to fit.  System messages and the latest
+/// message are always kept.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContextStrategy {
+    /// Drop the oldest messages until the rest fits.
+    #[default]
+    DropOldest,
+    /// Keep at most `max_messages` non-system messages, then drop the
+    /// oldest of those if they still do not fit.
+    SlidingWindow { max_messages: usize },
+    /// Replace the messages that would be dropped with a summary written by
+    /// `model`, typically a cheap one.
+    Summarize { model: String },
+}
+
+/// Operational status of a provider, managed through the control plane.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ProviderStatus {
+    /// `false` drains the provider: routing skips it until re-enabled.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+}
+
+impl Default for ProviderStatus {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            maintenance_windows: Vec::new(),
+        }
+    }
+}
+
+/// Scheduled period, `[start, end)`, during which a provider is skipped.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MaintenanceWindow {
+    pub start: chrono::DateTime<chrono::Utc>,
+    pub end: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl ProviderStatus {
+    pub fn unavailable_reason(&self, now: chrono::DateTime<chrono::Utc>) -> Option<String> {
+        if !self.enabled {
+            return Some("provider is drained".to_string());
+        }
+        self.maintenance_windows
+            .iter()
+            .find(|w| w.start <= now && now < w.end)
+            .map(|w| match &w.reason {
+                Some(reason) => format!("maintenance until {}: {}", w.end.to_rfc3339(), reason),
+                None => format!("maintenance until {}", w.end.to_rfc3339()),
+            })
+    }
 }
 
 /// A routing rule for LLM providers
@@ -111,10 +1179,85 @@ pub struct Quota {
     pub max_requests_per_minute: Option<u64>,
     pub max_tokens_per_minute: Option<u64>,
     pub budget_cents: Option<u64>, // monthly budget in cents (USD)
+    /// How `max_requests_per_minute` is counted.
+    #[serde(default)]
+    pub rpm_window: RpmWindow,
+}
+
+/// Algorithm used to count requests against a per-minute limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RpmWindow {
+    /// A counter reset every minute.  Cheap, but a burst straddling the
+    /// reset can admit up to twice the limit.
+    #[default]
+    Fixed,
+    /// A log of request times over the trailing minute.  Exact, at the cost
+    /// of one Redis sorted-set entry per request.
+    Sliding,
+}
+
+/// A user-facing API key issued by the control plane.
+///
+/// Callers pass the raw key to `chat()`; the data plane looks it up by hash
+/// so limits and usage are attributed to the key's team and user rather than
+/// to an opaque string.  Provider credentials stay in `Config::api_keys`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct VirtualKey {
+    pub id: String,
+    pub key_hash: String,
+    pub team_id: String,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Resolved model names the key may call.  Empty allows every model.
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+    /// Portion of the team budget (in cents) assigned to this key.
+    #[serde(default)]
+    pub budget_cents: Option<i64>,
+    #[serde(default = "default_true")]
+    pub is_active: bool,
+    #[serde(default)]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl VirtualKey {
+    /// Whether the key may call the resolved `model`.
+    pub fn allows_model(&self, model: &str) -> bool {
+        self.allowed_models.is_empty() || self.allowed_models.iter().any(|m| m == model)
+    }
+
+    /// Reject the key if it was disabled or has expired as of `now`.
+    pub fn check_usable(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), crate::HyperInferError> {
+        if !self.is_active {
+            return Err(crate::HyperInferError::Forbidden(
+                "API key is disabled".to_string(),
+            ));
+        }
+        if self.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err(crate::HyperInferError::Forbidden(
+                "API key has expired".to_string(),
+            ));
+        }
+        Ok(())
+    }
 }
 
 /// Provider enumeration for LLM services
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum Provider {
     OpenAI,
@@ -140,12 +1283,21 @@ pub struct Usage {
     pub input_tokens: u32,
     #[serde(default)]
     pub output_tokens: u32,
+    /// Part of `output_tokens` spent on extended thinking.  Anthropic bills
+    /// thinking as output without breaking it out, so this is estimated from
+    /// the thinking text.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub thinking_tokens: u32,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
 }
 
 /// A usage record for telemetry (stored in Redis Stream and PostgreSQL)
 ///
 /// All timestamps are in milliseconds since Unix epoch.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct UsageRecord {
     pub key: String,
     pub model: String,
@@ -153,8 +1305,65 @@ pub struct UsageRecord {
     pub output_tokens: u32,
     pub response_time_ms: u64,
     pub timestamp: u64,
+    /// The stream entry the record was consumed from, as `stream/entry-id`;
+    /// unique across the telemetry stream's shards.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub msg_id: Option<String>,
+    /// Provider that served (or failed) the request, e.g. `"openai"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Set when the request failed; holds the error message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Tags copied from `ChatRequest::metadata`.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    /// Set when the prompt was compressed before it was sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<CompressionStats>,
+}
+
+/// A [`UsageRecord`] attributed to its team and API key, as handed to
+/// telemetry sinks.  The client's key itself is left out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct UsageEvent {
+    pub team_id: String,
+    pub api_key_id: String,
+    /// The record's `msg_id`; sinks use it to drop redeliveries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry_id: Option<String>,
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub response_time_ms: u64,
+    pub timestamp: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<CompressionStats>,
+}
+
+impl UsageEvent {
+    pub fn new(team_id: String, api_key_id: String, record: UsageRecord) -> Self {
+        Self {
+            team_id,
+            api_key_id,
+            entry_id: record.msg_id,
+            model: record.model,
+            provider: record.provider,
+            input_tokens: record.input_tokens,
+            output_tokens: record.output_tokens,
+            response_time_ms: record.response_time_ms,
+            timestamp: record.timestamp,
+            error: record.error,
+            metadata: record.metadata,
+            compression: record.compression,
+        }
+    }
 }
 
 /// A choice in a chat response
@@ -163,6 +1372,10 @@ pub struct Choice {
     pub index: u32,
     pub message: ChatMessage,
     pub finish_reason: Option<String>,
+    /// Extended thinking that preceded the answer, kept out of
+    /// `message.content`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<String>,
 }
 
 /// A chat response from an LLM provider
@@ -176,6 +1389,80 @@ pub struct ChatResponse {
     pub choices: Vec<Choice>,
     #[serde(default)]
     pub usage: Usage,
+    /// Processing details added by the client, e.g. `validation_attempts`
+    /// when the response was validated against a JSON schema.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    /// What the client would have done, for `dry_run` requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dry_run: Option<DryRunReport>,
+}
+
+/// Outcome of a `dry_run` request: where it would have gone and what it
+/// would have cost.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DryRunReport {
+    /// Model and provider the request would have been sent to, after
+    /// aliases and routing rules.
+    pub model: String,
+    pub provider: String,
+    /// Model and provider a hedge request would have gone to, when hedging
+    /// applies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hedge_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hedge_provider: Option<String>,
+    /// `max_tokens` the request would have been sent with.
+    pub max_tokens: Option<u32>,
+    /// Whether the key's rate limits have room now for the request and its
+    /// estimated input plus maximum output tokens.
+    pub rate_limit_allowed: bool,
+    pub rate_limit: RateLimitUsage,
+    /// `None` when no price is known for the model.
+    pub cost: Option<crate::pricing::CostEstimate>,
+}
+
+/// The limits configured for a key, as the client's current config and
+/// control-plane policies have them.  Nothing here counts usage; see
+/// [`RateLimitUsage`] for that.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeyLimits {
+    /// Team of the key's virtual key, if the control plane issued one.
+    pub team_id: Option<String>,
+    /// Quota configured for the key itself.
+    pub quota: Option<Quota>,
+    /// Organization whose shared quota also applies, and that quota.
+    pub organization_id: Option<String>,
+    pub organization_quota: Option<Quota>,
+    /// Models the key may call; empty means any.
+    pub allowed_models: Vec<String>,
+    /// Budget of the key's virtual key, in cents.
+    pub budget_cents: Option<i64>,
+    /// Requests per minute the control plane has throttled the key to.
+    pub throttled_rpm: Option<u64>,
+    /// Whether the control plane has revoked the key.
+    pub revoked: bool,
+}
+
+/// Snapshot of a key's standing against its rate limits, from
+/// `RateLimiter::get_usage`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RateLimitUsage {
+    pub key: String,
+    pub rpm_limit: u64,
+    /// Requests counted in the current window.
+    pub requests_used: u64,
+    pub requests_remaining: u64,
+    /// Milliseconds until the request window resets.
+    pub requests_reset_ms: u64,
+    pub tpm_limit: u64,
+    /// Tokens spent that have not yet been paid back by the refill rate.
+    pub tokens_debt: u64,
+    /// Largest request, in tokens, that would be allowed now.
+    pub tokens_remaining: u64,
+    /// Milliseconds until the token debt is paid back.
+    pub tokens_reset_ms: u64,
 }
 
 #[cfg(test)]
@@ -194,6 +1481,15 @@ mod tests {
             max_tokens: None,
             stream: None,
             stop: None,
+            metadata: HashMap::new(),
+            response_format: None,
+            compression: None,
+            reasoning_effort: None,
+            thinking: None,
+            dry_run: false,
+            context_overflow: None,
+            routing: None,
+            data_region: None,
         };
 
         assert!(request.validate().is_err());
@@ -208,6 +1504,15 @@ mod tests {
             max_tokens: None,
             stream: None,
             stop: None,
+            metadata: HashMap::new(),
+            response_format: None,
+            compression: None,
+            reasoning_effort: None,
+            thinking: None,
+            dry_run: false,
+            context_overflow: None,
+            routing: None,
+            data_region: None,
         };
 
         assert!(request.validate().is_err());
@@ -225,11 +1530,43 @@ mod tests {
             max_tokens: Some(100),
             stream: None,
             stop: None,
+            metadata: HashMap::new(),
+            response_format: None,
+            compression: None,
+            reasoning_effort: None,
+            thinking: None,
+            dry_run: false,
+            context_overflow: None,
+            routing: None,
+            data_region: None,
         };
 
         assert!(request.validate().is_ok());
     }
 
+    #[test]
+    fn test_chat_request_validate_compression_ratio() {
+        let mut request = ChatRequest {
+            model: "gpt-4".to_string(),
+            messages: vec![ChatMessage {
+                role: MessageRole::User,
+                content: "Hello".to_string(),
+            }],
+            compression: serde_json::from_str(r#"{"ratio":0.5}"#).unwrap(),
+            ..Default::default()
+        };
+        assert!(request.validate().is_ok());
+        assert_eq!(
+            request.compression.as_ref().unwrap().min_message_tokens,
+            CompressionOptions::DEFAULT_MIN_MESSAGE_TOKENS
+        );
+
+        for ratio in [0.0, 1.5, f64::NAN] {
+            request.compression.as_mut().unwrap().ratio = ratio;
+            assert!(request.validate().is_err(), "ratio {} accepted", ratio);
+        }
+    }
+
     #[test]
     fn test_provider_display() {
         assert_eq!(Provider::OpenAI.to_string(), "openai");
@@ -274,6 +1611,30 @@ mod tests {
         assert_eq!(request.max_tokens, None);
     }
 
+    #[test]
+    fn test_response_format_serialization() {
+        let json = r#"{"type":"json_schema","json_schema":{"name":"answer","schema":{"type":"object"},"strict":true}}"#;
+        let format: ResponseFormat = serde_json::from_str(json).unwrap();
+        let ResponseFormat::JsonSchema { json_schema } = &format else {
+            panic!("expected json_schema, got {:?}", format);
+        };
+        assert_eq!(json_schema.name, "answer");
+        assert_eq!(json_schema.strict, Some(true));
+        assert_eq!(serde_json::to_string(&format).unwrap(), json);
+        assert!(format
+            .instruction()
+            .unwrap()
+            .contains(r#"{"type":"object"}"#));
+
+        let format: ResponseFormat = serde_json::from_str(r#"{"type":"json_object"}"#).unwrap();
+        assert_eq!(format, ResponseFormat::JsonObject);
+        assert_eq!(ResponseFormat::Text.instruction(), None);
+
+        // Omitted from requests that do not set it.
+        let request = serde_json::to_value(ChatRequest::default()).unwrap();
+        assert!(request.get("response_format").is_none());
+    }
+
     #[test]
     fn test_usage_default() {
         let usage = Usage::default();
@@ -313,6 +1674,7 @@ mod tests {
             quotas: HashMap::new(),
             model_aliases: HashMap::new(),
             default_provider: Some(Provider::OpenAI),
+            ..Default::default()
         };
 
         config
@@ -325,17 +1687,431 @@ mod tests {
         assert!(json.contains("routing_rules"));
     }
 
+    #[test]
+    fn test_config_provider_headers_default_and_roundtrip() {
+        let json = r#"{"routing_rules":[],"quotas":{},"model_aliases":{}}"#;
+        let config: Config = serde_json::from_str(json).unwrap();
+        assert!(config.provider_headers.is_empty());
+
+        let mut headers = HashMap::new();
+        headers.insert("OpenAI-Organization".to_string(), "org-123".to_string());
+        let mut config = Config::default();
+        config
+            .provider_headers
+            .insert("openai".to_string(), headers);
+
+        let json = serde_json::to_string(&config).unwrap();
+        let back: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            back.provider_headers["openai"]["OpenAI-Organization"],
+            "org-123"
+        );
+    }
+
+    #[test]
+    fn test_hedging_config_applies_to() {
+        let uncapped = HedgingConfig {
+            after_ms: 500,
+            max_output_tokens: None,
+        };
+        assert!(uncapped.applies_to(None));
+        assert!(uncapped.applies_to(Some(100_000)));
+
+        let capped = HedgingConfig {
+            after_ms: 500,
+            max_output_tokens: Some(1024),
+        };
+        assert!(capped.applies_to(Some(1024)));
+        assert!(!capped.applies_to(Some(1025)));
+        assert!(!capped.applies_to(None));
+    }
+
+    #[test]
+    fn test_single_flight_enabled_for() {
+        let config: SingleFlightConfig =
+            serde_json::from_str(r#"{"teams": {"t1": false}}"#).unwrap();
+        assert!(config.enabled_for(None));
+        assert!(config.enabled_for(Some("t2")));
+        assert!(!config.enabled_for(Some("t1")));
+
+        let opt_in = SingleFlightConfig {
+            enabled: false,
+            teams: HashMap::from([("t1".to_string(), true)]),
+        };
+        assert!(!opt_in.enabled_for(None));
+        assert!(opt_in.enabled_for(Some("t1")));
+    }
+
+    #[test]
+    fn test_param_clamps_lower_parameters_above_the_bounds() {
+        let clamps = ParamClamps {
+            max_temperature: Some(1.0),
+            max_tokens: Some(4096),
+        };
+        let mut request = ChatRequest {
+            temperature: Some(1.8),
+            max_tokens: Some(16_000),
+            ..Default::default()
+        };
+        assert_eq!(clamps.apply(&mut request), ["temperature", "max_tokens"]);
+        assert_eq!(request.temperature, Some(1.0));
+        assert_eq!(request.max_tokens, Some(4096));
+
+        let mut request = ChatRequest {
+            temperature: Some(0.2),
+            ..Default::default()
+        };
+        assert!(clamps.apply(&mut request).is_empty());
+        assert_eq!(request.temperature, Some(0.2));
+        assert_eq!(request.max_tokens, Some(4096));
+    }
+
+    #[test]
+    fn test_output_transforms_apply_in_order() {
+        let transforms = [
+            OutputTransform::StripPreamble {
+                prefixes: vec!["Sure".to_string()],
+            },
+            OutputTransform::StripStopSequences { sequences: vec![] },
+            OutputTransform::StripCodeFences,
+            OutputTransform::TrimWhitespace,
+            OutputTransform::MaxLength { max_chars: 8 },
+        ];
+        let mut response = ChatResponse {
+            choices: vec![Choice {
+                index: 0,
+                message: ChatMessage {
+                    role: MessageRole::Assistant,
+                    content:
+                        "  sure! Here is the JSON:\n```json\n{\"a\": \"bcdef\"}\n```\nEND extra"
+                            .to_string(),
+                },
+                finish_reason: None,
+                thinking: None,
+            }],
+            ..Default::default()
+        };
+        OutputTransform::apply_all(&transforms, &["END".to_string()], &mut response);
+        assert_eq!(response.choices[0].message.content, "{\"a\": \"b");
+
+        // Fenced blocks inside prose are left alone.
+        let mut content = "See:\n```\nx\n```\ndone".to_string();
+        OutputTransform::StripCodeFences.apply(&[], &mut content);
+        assert_eq!(content, "See:\n```\nx\n```\ndone");
+    }
+
+    #[test]
+    fn test_feature_enabled() {
+        let mut config = Config::default();
+        assert!(config.feature_enabled(None, TeamFeature::EnableCache));
+
+        config.team_features.insert(
+            "t1".to_string(),
+            HashMap::from([
+                ("enable_cache".to_string(), false),
+                ("enable_fallback".to_string(), true),
+            ]),
+        );
+        config
+            .default_features
+            .insert("enable_fallback".to_string(), false);
+        assert!(!config.feature_enabled(Some("t1"), TeamFeature::EnableCache));
+        assert!(config.feature_enabled(Some("t2"), TeamFeature::EnableCache));
+        // Rolled out to t1 only.
+        assert!(config.feature_enabled(Some("t1"), TeamFeature::EnableFallback));
+        assert!(!config.feature_enabled(Some("t2"), TeamFeature::EnableFallback));
+        assert!(!config.feature_enabled(None, TeamFeature::EnableFallback));
+        assert!(config.feature_enabled(Some("t1"), TeamFeature::EnableStreaming));
+
+        for feature in TeamFeature::ALL {
+            assert_eq!(TeamFeature::parse(feature.as_str()), Some(feature));
+            let json = serde_json::to_string(&feature).unwrap();
+            assert_eq!(json, format!("\"{}\"", feature.as_str()));
+        }
+        assert_eq!(TeamFeature::parse("enable_magic"), None);
+    }
+
+    #[test]
+    fn test_config_provider_unavailable() {
+        use chrono::TimeZone;
+
+        let now = chrono::Utc.with_ymd_and_hms(2026, 5, 1, 12, 0, 0).unwrap();
+        let mut config = Config::default();
+        assert_eq!(config.provider_unavailable(&Provider::OpenAI, now), None);
+
+        config.providers.insert(
+            "openai".to_string(),
+            ProviderStatus {
+                enabled: false,
+                ..Default::default()
+            },
+        );
+        config.providers.insert(
+            "anthropic".to_string(),
+            ProviderStatus {
+                enabled: true,
+                maintenance_windows: vec![MaintenanceWindow {
+                    start: now - chrono::Duration::hours(1),
+                    end: now + chrono::Duration::hours(1),
+                    reason: Some("region upgrade".to_string()),
+                }],
+            },
+        );
+        assert_eq!(
+            config
+                .provider_unavailable(&Provider::OpenAI, now)
+                .as_deref(),
+            Some("provider is drained")
+        );
+        assert_eq!(
+            config
+                .provider_unavailable(&Provider::Anthropic, now)
+                .as_deref(),
+            Some("maintenance until 2026-05-01T13:00:00+00:00: region upgrade")
+        );
+        assert_eq!(
+            config.provider_unavailable(&Provider::Anthropic, now + chrono::Duration::hours(1)),
+            None
+        );
+
+        let status: ProviderStatus = serde_json::from_str("{}").unwrap();
+        assert!(status.enabled);
+    }
+
+    #[test]
+    fn test_config_alias_target_prefers_team_alias() {
+        let mut config = Config::default();
+        config
+            .model_aliases
+            .insert("fast".to_string(), "openai/gpt-4o-mini".to_string());
+        config.team_model_aliases.insert(
+            "team-a".to_string(),
+            HashMap::from([("fast".to_string(), "anthropic/claude-3-haiku".to_string())]),
+        );
+
+        assert_eq!(
+            config.alias_target(Some("team-a"), "fast"),
+            Some("anthropic/claude-3-haiku")
+        );
+        assert_eq!(
+            config.alias_target(Some("team-b"), "fast"),
+            Some("openai/gpt-4o-mini")
+        );
+        assert_eq!(
+            config.alias_target(None, "fast"),
+            Some("openai/gpt-4o-mini")
+        );
+        assert_eq!(config.alias_target(Some("team-a"), "slow"), None);
+    }
+
+    #[test]
+    fn test_config_serves_region_by_name_or_tag() {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "routing_rules": [],
+            "quotas": {},
+            "model_aliases": {},
+            "provider_regions": {"openai": {"endpoints": [
+                {"region": "westeurope", "base_url": "https://we.example.com", "tags": ["eu"]},
+                {"region": "eastus", "base_url": "https://eus.example.com"}
+            ]}},
+            "team_data_regions": {"team-eu": "eu"}
+        }))
+        .unwrap();
+
+        assert_eq!(config.data_region(Some("team-eu")), Some("eu"));
+        assert_eq!(config.data_region(Some("team-us")), None);
+        assert_eq!(config.data_region(None), None);
+        assert!(config.serves_region(&Provider::OpenAI, "eu"));
+        assert!(config.serves_region(&Provider::OpenAI, "eastus"));
+        assert!(!config.serves_region(&Provider::OpenAI, "apac"));
+        assert!(!config.serves_region(&Provider::Anthropic, "eu"));
+    }
+
+    #[test]
+    fn test_context_window() {
+        assert_eq!(known_context_window("gpt-4o-mini"), Some(128_000));
+        assert_eq!(known_context_window("gpt-4.1-mini"), Some(1_047_576));
+        assert_eq!(known_context_window("gpt-4-0613"), Some(8_192));
+        assert_eq!(
+            known_context_window("claude-3-5-sonnet-latest"),
+            Some(200_000)
+        );
+        assert_eq!(known_context_window("llama-3"), None);
+
+        let context: ContextConfig = serde_json::from_str(
+            r#"{"strategy":{"type":"sliding_window","max_messages":10},"context_windows":{"llama-3":8192}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            context.strategy,
+            ContextStrategy::SlidingWindow { max_messages: 10 }
+        );
+        assert_eq!(context.context_window("llama-3"), Some(8192));
+        assert_eq!(context.context_window("gpt-4o"), Some(128_000));
+
+        let context: ContextConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(context.strategy, ContextStrategy::DropOldest);
+    }
+
+    #[test]
+    fn test_known_max_output_tokens() {
+        assert_eq!(
+            known_max_output_tokens("claude-3-haiku-20240307"),
+            Some(4_096)
+        );
+        assert_eq!(
+            known_max_output_tokens("claude-3-5-sonnet-20241022"),
+            Some(8_192)
+        );
+        assert_eq!(
+            known_max_output_tokens("claude-opus-4-1-20250805"),
+            Some(32_000)
+        );
+        assert_eq!(
+            known_max_output_tokens("claude-opus-4-5-20251101"),
+            Some(64_000)
+        );
+        assert_eq!(known_max_output_tokens("claude-sonnet-4-5"), Some(64_000));
+        assert_eq!(known_max_output_tokens("gpt-4o"), None);
+    }
+
+    #[test]
+    fn test_is_reasoning_model() {
+        for model in ["o1", "o1-mini", "o3", "o3-mini-2025-01-31", "o4-mini"] {
+            assert!(is_reasoning_model(model), "{}", model);
+        }
+        for model in ["gpt-4o", "omni-moderation-latest", "claude-3-opus", ""] {
+            assert!(!is_reasoning_model(model), "{}", model);
+        }
+    }
+
+    #[test]
+    fn test_default_max_output_tokens() {
+        assert_eq!(default_max_output_tokens("claude-3-opus-20240229"), 4_096);
+        assert_eq!(
+            default_max_output_tokens("claude-sonnet-4-5"),
+            DEFAULT_MAX_OUTPUT_TOKENS
+        );
+        assert_eq!(
+            default_max_output_tokens("claude-future"),
+            DEFAULT_MAX_OUTPUT_TOKENS
+        );
+    }
+
+    #[test]
+    fn test_config_default_max_tokens_capped() {
+        let mut config = Config::default();
+        config
+            .max_output_tokens
+            .insert("claude-3-haiku-20240307".to_string(), 100_000);
+        config
+            .max_output_tokens
+            .insert("claude-sonnet-4-5".to_string(), 32_000);
+
+        assert_eq!(
+            config.default_max_tokens("claude-3-haiku-20240307"),
+            Some(4_096)
+        );
+        assert_eq!(config.default_max_tokens("claude-sonnet-4-5"), Some(32_000));
+        assert_eq!(config.default_max_tokens("gpt-4o"), None);
+    }
+
+    #[test]
+    fn test_config_estimate_cost() {
+        let mut config = Config::default();
+        config
+            .max_output_tokens
+            .insert("claude-sonnet-4-5".to_string(), 32_000);
+        let request = ChatRequest {
+            messages: vec![ChatMessage {
+                role: MessageRole::User,
+                content: "Hello".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        let estimate = config.estimate_cost("claude-sonnet-4-5", &request).unwrap();
+        assert_eq!(estimate.max_output_tokens, 32_000);
+        assert!(estimate.input_tokens > 0);
+        let capped = config
+            .estimate_cost(
+                "claude-sonnet-4-5",
+                &ChatRequest {
+                    max_tokens: Some(100),
+                    ..request.clone()
+                },
+            )
+            .unwrap();
+        assert_eq!(capped.max_output_tokens, 100);
+        assert!(capped.max_total_cost_cents < estimate.max_total_cost_cents);
+        assert!(config.estimate_cost("unpriced-model", &request).is_none());
+    }
+
+    #[test]
+    fn test_validate_max_tokens() {
+        let mut request = ChatRequest {
+            max_tokens: Some(10_000),
+            ..Default::default()
+        };
+        assert!(request
+            .validate_max_tokens("claude-3-5-haiku-20241022")
+            .is_err());
+        assert!(request.validate_max_tokens("claude-sonnet-4-5").is_ok());
+        assert!(request.validate_max_tokens("unknown-model").is_ok());
+
+        request.max_tokens = None;
+        assert!(request
+            .validate_max_tokens("claude-3-haiku-20240307")
+            .is_ok());
+    }
+
+    #[test]
+    fn test_validate_thinking() {
+        let mut request = ChatRequest {
+            model: "claude-sonnet-4-5".to_string(),
+            messages: vec![ChatMessage {
+                role: MessageRole::User,
+                content: "hi".to_string(),
+            }],
+            thinking: Some(ThinkingOptions {
+                budget_tokens: 2048,
+            }),
+            ..Default::default()
+        };
+        assert!(request.validate().is_ok());
+
+        request.max_tokens = Some(2048);
+        assert!(request.validate().is_err());
+        request.max_tokens = Some(4096);
+        assert!(request.validate().is_ok());
+
+        request.thinking = Some(ThinkingOptions { budget_tokens: 512 });
+        assert!(request.validate().is_err());
+    }
+
     #[test]
     fn test_quota_with_all_fields() {
         let quota = Quota {
             max_requests_per_minute: Some(100),
             max_tokens_per_minute: Some(10000),
             budget_cents: Some(5000),
+            rpm_window: RpmWindow::Sliding,
         };
 
         assert_eq!(quota.max_requests_per_minute, Some(100));
         assert_eq!(quota.max_tokens_per_minute, Some(10000));
         assert_eq!(quota.budget_cents, Some(5000));
+        assert_eq!(quota.rpm_window, RpmWindow::Sliding);
+    }
+
+    #[test]
+    fn test_quota_rpm_window_defaults_to_fixed() {
+        let quota: Quota = serde_json::from_str(r#"{"max_requests_per_minute": 10}"#).unwrap();
+        assert_eq!(quota.rpm_window, RpmWindow::Fixed);
+        let quota: Quota =
+            serde_json::from_str(r#"{"max_requests_per_minute": 10, "rpm_window": "sliding"}"#)
+                .unwrap();
+        assert_eq!(quota.rpm_window, RpmWindow::Sliding);
     }
 
     #[test]
@@ -347,6 +2123,7 @@ mod tests {
                 content: "Response".to_string(),
             },
             finish_reason: Some("stop".to_string()),
+            thinking: None,
         };
 
         assert_eq!(choice.index, 0);
@@ -364,6 +2141,7 @@ mod tests {
             response_time_ms: 250,
             timestamp: 1700000000000,
             msg_id: None,
+            ..Default::default()
         };
 
         assert_eq!(record.key, "test-key");
@@ -384,6 +2162,7 @@ mod tests {
             response_time_ms: 250,
             timestamp: 1700000000000,
             msg_id: None,
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&record).unwrap();
@@ -402,6 +2181,7 @@ mod tests {
             response_time_ms: 0,
             timestamp: 0,
             msg_id: None,
+            ..Default::default()
         };
 
         assert_eq!(record.input_tokens, 0);
@@ -420,6 +2200,7 @@ mod tests {
             response_time_ms: u64::MAX,
             timestamp: u64::MAX,
             msg_id: None,
+            ..Default::default()
         };
 
         assert_eq!(record.input_tokens, u32::MAX);
@@ -438,6 +2219,7 @@ mod tests {
             response_time_ms: 250,
             timestamp: 1700000000000,
             msg_id: None,
+            ..Default::default()
         };
 
         assert_eq!(record.key, "");
@@ -454,6 +2236,7 @@ mod tests {
             response_time_ms: 250,
             timestamp: 1700000000000,
             msg_id: None,
+            ..Default::default()
         };
 
         assert_eq!(record.key, "test-key-!@#$%");
@@ -470,6 +2253,7 @@ mod tests {
             response_time_ms: 250,
             timestamp: 1700000000000,
             msg_id: None,
+            ..Default::default()
         };
 
         assert_eq!(record.key, "test-key-🔑");
@@ -491,6 +2275,7 @@ mod tests {
             response_time_ms: 250,
             timestamp: 1700000000000,
             msg_id: None,
+            ..Default::default()
         };
 
         assert_eq!(record.key.len(), 10000);
@@ -507,6 +2292,7 @@ mod tests {
             response_time_ms: 250,
             timestamp: 1700000000000,
             msg_id: None,
+            ..Default::default()
         };
 
         let cloned = record.clone();
@@ -523,6 +2309,7 @@ mod tests {
             response_time_ms: 250,
             timestamp: 1700000000000,
             msg_id: None,
+            ..Default::default()
         };
 
         let debug_str = format!("{:?}", record);
@@ -531,4 +2318,55 @@ mod tests {
         assert!(debug_str.contains("100"));
         assert!(debug_str.contains("50"));
     }
+
+    #[test]
+    fn test_virtual_key_deserialize_defaults() {
+        let key: VirtualKey =
+            serde_json::from_str(r#"{"id": "k1", "key_hash": "abc", "team_id": "t1"}"#).unwrap();
+        assert!(key.is_active);
+        assert!(key.tags.is_empty());
+        assert!(key.allows_model("gpt-4o"));
+        assert!(key.check_usable(chrono::Utc::now()).is_ok());
+    }
+
+    #[test]
+    fn test_virtual_key_restrictions() {
+        let now = chrono::Utc::now();
+        let mut key: VirtualKey =
+            serde_json::from_str(r#"{"id": "k1", "key_hash": "abc", "team_id": "t1"}"#).unwrap();
+        key.allowed_models = vec!["gpt-4o-mini".to_string()];
+        assert!(key.allows_model("gpt-4o-mini"));
+        assert!(!key.allows_model("gpt-4o"));
+
+        key.expires_at = Some(now - chrono::Duration::seconds(1));
+        assert!(matches!(
+            key.check_usable(now),
+            Err(crate::HyperInferError::Forbidden(_))
+        ));
+
+        key.expires_at = None;
+        key.is_active = false;
+        assert!(key.check_usable(now).is_err());
+    }
+
+    #[test]
+    fn test_apply_update_ignores_stale_versions() {
+        let versioned = |version, provider| Config {
+            version,
+            default_provider: Some(provider),
+            ..Default::default()
+        };
+        let mut config = versioned(5, Provider::OpenAI);
+
+        assert!(!config.apply_update(versioned(4, Provider::Anthropic)));
+        assert!(!config.apply_update(versioned(5, Provider::Anthropic)));
+        assert_eq!(config.default_provider, Some(Provider::OpenAI));
+
+        assert!(config.apply_update(versioned(6, Provider::Anthropic)));
+        assert_eq!(config.version, 6);
+        assert_eq!(config.default_provider, Some(Provider::Anthropic));
+
+        assert!(config.apply_update(versioned(0, Provider::OpenAI)));
+        assert_eq!(config.version, 0);
+    }
 }
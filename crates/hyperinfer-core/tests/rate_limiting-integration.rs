@@ -1,3 +1,4 @@
+use hyperinfer_core::rate_limiting::RPM_SCRIPT;
 use hyperinfer_core::{RateLimiter, USAGE_REQUESTS_KEY_PREFIX, USAGE_TOKENS_KEY_PREFIX};
 use std::time::{SystemTime, UNIX_EPOCH};
 use testcontainers::{core::IntoContainerPort, runners::AsyncRunner, GenericImage};
@@ -40,7 +41,7 @@ async fn test_rate_limiter_is_allowed() {
 
     let result = limiter.is_allowed(&key, 10).await;
     assert!(result.is_ok());
-    assert!(result.unwrap(), "First request should be allowed");
+    assert!(result.unwrap().0, "First request should be allowed");
 }
 
 #[tokio::test]
@@ -58,8 +59,9 @@ async fn test_rate_limiter_is_allowed_blocking() {
 
     let mut blocked = false;
     for _i in 0..65 {
-        let result = limiter.is_allowed(&key, 10).await.unwrap();
-        if !result {
+        let (allowed, retry_after_ms) = limiter.is_allowed(&key, 10).await.unwrap();
+        if !allowed {
+            assert!(retry_after_ms > 0, "Expected a retry-after when blocked");
             blocked = true;
             break;
         }
@@ -98,6 +100,34 @@ async fn test_rate_limiter_check_rpm() {
     assert!(blocked, "Expected to be blocked");
 }
 
+#[tokio::test]
+async fn test_rate_limiter_check_rpm_retry() {
+    let (redis_url, _container) = setup_redis().await;
+    let limiter = RateLimiter::new(Some(&redis_url)).await.unwrap();
+
+    let key = format!(
+        "test_key_check_rpm_retry_{}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    );
+    let limit = 3;
+
+    for _i in 0..limit {
+        let result = limiter.check_rpm_retry(&key, limit).await.unwrap();
+        assert_eq!(result, (true, 0), "Expected to be allowed");
+    }
+
+    let (allowed, retry_after_ms) = limiter.check_rpm_retry(&key, limit).await.unwrap();
+    assert!(!allowed, "Expected to be blocked");
+    assert!(
+        retry_after_ms > 0 && retry_after_ms <= 60_000,
+        "Expected the window's remaining time, got {}",
+        retry_after_ms
+    );
+}
+
 #[tokio::test]
 async fn test_rate_limiter_check_tpm() {
     let (redis_url, _container) = setup_redis().await;
@@ -112,11 +142,12 @@ async fn test_rate_limiter_check_tpm() {
     );
     let limit = 10000;
 
-    let result = limiter.check_tpm(&key, limit, 50).await.unwrap();
-    assert!(result, "Should allow 50 tokens when limit is 10000");
+    let (allowed, _) = limiter.check_tpm(&key, limit, 50).await.unwrap();
+    assert!(allowed, "Should allow 50 tokens when limit is 10000");
 
-    let result = limiter.check_tpm(&key, limit, 15000).await.unwrap();
-    assert!(!result, "Should deny when tokens exceed limit");
+    let (allowed, retry_after_ms) = limiter.check_tpm(&key, limit, 15000).await.unwrap();
+    assert!(!allowed, "Should deny when tokens exceed limit");
+    assert!(retry_after_ms > 0, "Should say how long to wait");
 }
 
 #[tokio::test]
@@ -166,3 +197,180 @@ async fn test_rate_limiter_record_usage() {
         "Requests made should reflect number of calls"
     );
 }
+
+#[tokio::test]
+async fn test_rate_limiter_adjust_token_usage() {
+    let (redis_url, _container) = setup_redis().await;
+    let limiter = RateLimiter::new(Some(&redis_url)).await.unwrap();
+    let key = format!(
+        "test_key_adjust_usage_{}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    );
+
+    // Charged on estimates while streaming, then corrected at the end.
+    limiter.adjust_token_usage(&key, 300).await.unwrap();
+    limiter.adjust_token_usage(&key, 300).await.unwrap();
+    limiter.adjust_token_usage(&key, -120).await.unwrap();
+    limiter.record_usage(&key, 0).await.unwrap();
+
+    let client = redis::Client::open(redis_url.as_str()).expect("Failed to create client");
+    let mut conn = client
+        .get_multiplexed_async_connection()
+        .await
+        .expect("Failed to connect");
+    let (tokens_used, requests_made): (u64, u64) = redis::pipe()
+        .cmd("GET")
+        .arg(format!("{}{}", USAGE_TOKENS_KEY_PREFIX, key))
+        .cmd("GET")
+        .arg(format!("{}{}", USAGE_REQUESTS_KEY_PREFIX, key))
+        .query_async(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(tokens_used, 480);
+    assert_eq!(requests_made, 1, "Adjustments do not count requests");
+}
+
+#[tokio::test]
+async fn test_rate_limiter_tpm_rejection_does_not_count_request() {
+    let (redis_url, _container) = setup_redis().await;
+    let limiter = RateLimiter::new(Some(&redis_url)).await.unwrap();
+
+    let key = format!(
+        "test_key_tpm_rejection_{}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    );
+    let (allowed, retry_after_ms) = limiter.is_allowed(&key, 1_000_000).await.unwrap();
+    assert!(!allowed, "Should deny more tokens than the TPM limit");
+    assert!(retry_after_ms > 0);
+
+    let client = redis::Client::open(redis_url.as_str()).expect("Failed to create client");
+    let mut conn = client
+        .get_multiplexed_async_connection()
+        .await
+        .expect("Failed to connect");
+    let count: Option<u64> = redis::cmd("GET")
+        .arg(format!("hyperinfer:ratelimit:rpm:{}", key))
+        .query_async(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(count, None, "A rejected request should not use RPM");
+
+    let (allowed, _) = limiter.is_allowed(&key, 10).await.unwrap();
+    assert!(allowed);
+}
+
+#[tokio::test]
+async fn test_rate_limiter_sliding_window_is_strict() {
+    let (redis_url, _container) = setup_redis().await;
+    let limiter = RateLimiter::new(Some(&redis_url)).await.unwrap();
+
+    let key = format!(
+        "test_key_sliding_{}",
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    );
+    for _ in 0..3 {
+        let (allowed, _) = limiter.check_rpm_sliding(&key, 3).await.unwrap();
+        assert!(allowed);
+    }
+    let (allowed, retry_after_ms) = limiter.check_rpm_sliding(&key, 3).await.unwrap();
+    assert!(!allowed, "Fourth request in the window should be denied");
+    assert!(retry_after_ms > 59_000 && retry_after_ms <= 60_000);
+
+    let client = redis::Client::open(redis_url.as_str()).expect("Failed to create client");
+    let mut conn = client
+        .get_multiplexed_async_connection()
+        .await
+        .expect("Failed to connect");
+    let logged: u64 = redis::cmd("ZCARD")
+        .arg(format!("hyperinfer:ratelimit:rpm_sliding:{}", key))
+        .query_async(&mut conn)
+        .await
+        .unwrap();
+    assert_eq!(logged, 3, "Denied requests should not be logged");
+}
+
+#[tokio::test]
+async fn test_rate_limiter_reloads_flushed_scripts() {
+    let (redis_url, _container) = setup_redis().await;
+    let limiter = RateLimiter::new(Some(&redis_url)).await.unwrap();
+
+    let client = redis::Client::open(redis_url.as_str()).expect("Failed to create client");
+    let mut conn = client
+        .get_multiplexed_async_connection()
+        .await
+        .expect("Failed to connect");
+    let _: () = redis::cmd("SCRIPT")
+        .arg("FLUSH")
+        .query_async(&mut conn)
+        .await
+        .unwrap();
+
+    let (allowed, _) = limiter.is_allowed("test_key_flushed", 10).await.unwrap();
+    assert!(allowed, "Should reload the scripts after NOSCRIPT");
+    let (allowed, _) = limiter.check_rpm("test_key_flushed", 5).await.unwrap();
+    assert!(allowed);
+}
+
+async fn net_input_bytes(conn: &mut redis::aio::MultiplexedConnection) -> u64 {
+    let info: String = redis::cmd("INFO")
+        .arg("stats")
+        .query_async(conn)
+        .await
+        .unwrap();
+    info.lines()
+        .find_map(|l| l.strip_prefix("total_net_input_bytes:"))
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap()
+}
+
+/// The EVALSHA hot path must send far less than shipping the script via
+/// EVAL on every call.
+#[tokio::test]
+async fn test_evalsha_sends_less_than_eval() {
+    const CALLS: u64 = 200;
+
+    let (redis_url, _container) = setup_redis().await;
+    let limiter = RateLimiter::new(Some(&redis_url)).await.unwrap();
+    let client = redis::Client::open(redis_url.as_str()).unwrap();
+    let mut stats_conn = client.get_multiplexed_async_connection().await.unwrap();
+    let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+
+    let mut bytes_per_call = Vec::new();
+    for mode in ["EVAL", "EVALSHA"] {
+        let before = net_input_bytes(&mut stats_conn).await;
+        for i in 0..CALLS {
+            let key = format!("evalsha:{}:{}", mode, i);
+            if mode == "EVAL" {
+                let _: Vec<i64> = redis::cmd("EVAL")
+                    .arg(RPM_SCRIPT)
+                    .arg(1)
+                    .arg(format!("hyperinfer:ratelimit:rpm:{}", key))
+                    .arg(1000)
+                    .arg(60)
+                    .query_async(&mut conn)
+                    .await
+                    .unwrap();
+            } else {
+                limiter.check_rpm(&key, 1000).await.unwrap();
+            }
+        }
+        // The INFO call itself is counted too; it is the same for both.
+        bytes_per_call.push((net_input_bytes(&mut stats_conn).await - before) / CALLS);
+    }
+    let (eval, evalsha) = (bytes_per_call[0], bytes_per_call[1]);
+    assert!(
+        evalsha * 2 < eval,
+        "EVALSHA sent {} bytes per call against EVAL's {}",
+        evalsha,
+        eval
+    );
+}
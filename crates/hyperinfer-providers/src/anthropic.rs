@@ -1,15 +1,20 @@
 use super::provider_trait::LlmProvider;
 use async_trait::async_trait;
 use futures::{Stream, StreamExt};
+use hyperinfer_core::types::{default_max_output_tokens, known_max_output_tokens};
 use hyperinfer_core::{
-    ChatChunk, ChatMessage, ChatRequest, ChatResponse, Choice, HyperInferError, MessageRole, Usage,
+    tokenizer, ChatChunk, ChatMessage, ChatRequest, ChatResponse, Choice, HyperInferError,
+    MessageRole, ResponseFormat, ThinkingOptions, Usage,
 };
 use reqwest::Client;
 use std::pin::Pin;
 
+/// Public API endpoint.
+pub const DEFAULT_BASE_URL: &str = "https://api.anthropic.com";
+
 pub struct AnthropicProvider {
     http_client: Client,
-    base_url: &'static str,
+    base_url: String,
 }
 
 fn build_anthropic_request_body(
@@ -20,11 +25,16 @@ fn build_anthropic_request_body(
     Vec<serde_json::Value>,
     serde_json::Map<String, serde_json::Value>,
 ) {
+    let instruction = request
+        .response_format
+        .as_ref()
+        .and_then(ResponseFormat::instruction);
     let system_messages: Vec<_> = request
         .messages
         .iter()
         .filter(|m| m.role == MessageRole::System)
         .map(|m| m.content.as_str())
+        .chain(instruction.as_deref())
         .collect();
 
     let system = if system_messages.is_empty() {
@@ -54,7 +64,9 @@ fn build_anthropic_request_body(
     body.insert("messages".to_string(), serde_json::json!(messages));
     body.insert(
         "max_tokens".to_string(),
-        serde_json::json!(request.max_tokens.unwrap_or(1024)),
+        serde_json::json!(request
+            .max_tokens
+            .unwrap_or_else(|| default_max_output_tokens(&request.model))),
     );
 
     if stream {
@@ -69,19 +81,97 @@ fn build_anthropic_request_body(
     if let Some(stop) = &request.stop {
         body.insert("stop_sequences".to_string(), serde_json::json!(stop));
     }
+    apply_thinking(&mut body, &request.model, request.thinking.as_ref());
 
     (system, messages, body)
 }
 
+/// Enable extended thinking on a Messages API body.  `temperature` is
+/// dropped since thinking does not support it, and a `max_tokens` that
+/// leaves no room beyond the budget is raised by the budget, capped at the
+/// model's known limit.
+pub fn apply_thinking(
+    body: &mut serde_json::Map<String, serde_json::Value>,
+    model: &str,
+    thinking: Option<&ThinkingOptions>,
+) {
+    let Some(thinking) = thinking else {
+        return;
+    };
+    body.insert(
+        "thinking".to_string(),
+        serde_json::json!({"type": "enabled", "budget_tokens": thinking.budget_tokens}),
+    );
+    if body.remove("temperature").is_some() {
+        tracing::debug!(
+            "Dropping temperature for {}: not supported with thinking",
+            model
+        );
+    }
+    let max_tokens = body
+        .get("max_tokens")
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or_default() as u32;
+    if max_tokens <= thinking.budget_tokens {
+        let raised = thinking.budget_tokens.saturating_add(max_tokens);
+        let raised = known_max_output_tokens(model).map_or(raised, |limit| raised.min(limit));
+        body.insert("max_tokens".to_string(), serde_json::json!(raised));
+    }
+}
+
+/// A block of a Messages API response's `content`.
+#[derive(serde::Deserialize)]
+pub struct ContentBlock {
+    pub text: Option<String>,
+    pub thinking: Option<String>,
+}
+
+/// Split response content into the answer text and the thinking, if any.
+/// Redacted thinking blocks carry nothing readable and are skipped.
+pub fn split_content(blocks: Vec<ContentBlock>) -> (String, Option<String>) {
+    let mut text = Vec::new();
+    let mut thinking = Vec::new();
+    for block in blocks {
+        text.extend(block.text);
+        thinking.extend(block.thinking);
+    }
+    let thinking = (!thinking.is_empty()).then(|| thinking.join("\n"));
+    (text.join("\n"), thinking)
+}
+
+/// Estimated thinking tokens within `output_tokens`.
+pub fn thinking_tokens(thinking: &str, output_tokens: u32) -> u32 {
+    if thinking.is_empty() {
+        return 0;
+    }
+    tokenizer::estimate_text_tokens(thinking).min(output_tokens)
+}
+
 impl AnthropicProvider {
     pub fn new() -> Result<Self, reqwest::Error> {
+        Self::with_default_headers(reqwest::header::HeaderMap::new())
+    }
+
+    /// Build a provider that sends `headers` on every request in addition to
+    /// the authentication headers.
+    pub fn with_default_headers(
+        headers: reqwest::header::HeaderMap,
+    ) -> Result<Self, reqwest::Error> {
         Ok(Self {
             http_client: Client::builder()
                 .timeout(std::time::Duration::from_secs(60))
+                .default_headers(headers)
                 .build()?,
-            base_url: "https://api.anthropic.com",
+            base_url: DEFAULT_BASE_URL.to_string(),
         })
     }
+
+    /// Send requests to `base_url` instead of the public API, e.g. a
+    /// gateway or a test server.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
 }
 
 // Clone is required by LlmProvider supertrait. The HTTP client is cheap to clone.
@@ -89,7 +179,7 @@ impl Clone for AnthropicProvider {
     fn clone(&self) -> Self {
         Self {
             http_client: self.http_client.clone(),
-            base_url: self.base_url,
+            base_url: self.base_url.clone(),
         }
     }
 }
@@ -100,8 +190,8 @@ impl LlmProvider for AnthropicProvider {
         "anthropic"
     }
 
-    fn base_url(&self) -> &'static str {
-        self.base_url
+    fn base_url(&self) -> &str {
+        &self.base_url
     }
 
     async fn chat(
@@ -127,10 +217,7 @@ impl LlmProvider for AnthropicProvider {
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
-            return Err(HyperInferError::ApiError {
-                status: status.as_u16(),
-                message: error_text,
-            });
+            return Err(HyperInferError::api_error(status.as_u16(), error_text));
         }
 
         #[derive(serde::Deserialize)]
@@ -141,11 +228,6 @@ impl LlmProvider for AnthropicProvider {
             stop_reason: Option<String>,
         }
 
-        #[derive(serde::Deserialize)]
-        struct ContentBlock {
-            text: Option<String>,
-        }
-
         #[derive(serde::Deserialize)]
         struct AnthropicUsageDetail {
             input_tokens: u32,
@@ -154,12 +236,11 @@ impl LlmProvider for AnthropicProvider {
 
         let data: AnthropicResponse = response.json().await?;
 
-        let content = data
-            .content
-            .into_iter()
-            .filter_map(|b| b.text)
-            .collect::<Vec<_>>()
-            .join("\n");
+        let (content, thinking) = split_content(data.content);
+        let thinking_tokens = thinking_tokens(
+            thinking.as_deref().unwrap_or_default(),
+            data.usage.output_tokens,
+        );
 
         Ok(ChatResponse {
             id: data.id,
@@ -171,11 +252,15 @@ impl LlmProvider for AnthropicProvider {
                     content,
                 },
                 finish_reason: data.stop_reason,
+                thinking,
             }],
             usage: Usage {
                 input_tokens: data.usage.input_tokens,
                 output_tokens: data.usage.output_tokens,
+                thinking_tokens,
             },
+            metadata: std::collections::HashMap::new(),
+            dry_run: None,
         })
     }
 
@@ -206,10 +291,7 @@ impl LlmProvider for AnthropicProvider {
             if !response.status().is_success() {
                 let status = response.status();
                 let error_text = response.text().await.unwrap_or_default();
-                Err(HyperInferError::ApiError {
-                    status: status.as_u16(),
-                    message: error_text,
-                })?;
+                Err(HyperInferError::api_error(status.as_u16(), error_text))?;
                 return;
             }
 
@@ -217,6 +299,7 @@ impl LlmProvider for AnthropicProvider {
             let mut raw_buf: Vec<u8> = Vec::new();
             let mut stream_id = String::new();
             let mut cached_input_tokens: u32 = 0;
+            let mut thinking_text = String::new();
 
             while let Some(bytes) = byte_stream.next().await {
                 let bytes = bytes?;
@@ -250,6 +333,8 @@ impl LlmProvider for AnthropicProvider {
                         delta_type: String,
                         #[serde(default)]
                         text: String,
+                        #[serde(default)]
+                        thinking: String,
                         stop_reason: Option<String>,
                     }
                     #[derive(serde::Deserialize)]
@@ -287,32 +372,48 @@ impl LlmProvider for AnthropicProvider {
                                 }
                             }
                             "content_block_delta" => {
-                                if let Some(delta) = event.delta {
-                                    if delta.delta_type == "text_delta" {
+                                match event.delta {
+                                    Some(delta) if delta.delta_type == "text_delta" => {
                                         yield ChatChunk {
                                             id: stream_id.clone(),
                                             model: model.clone(),
                                             delta: delta.text,
-                                            finish_reason: None,
-                                            usage: None,
+                                            ..Default::default()
                                         };
                                     }
+                                    Some(delta) if delta.delta_type == "thinking_delta" => {
+                                        thinking_text.push_str(&delta.thinking);
+                                        yield ChatChunk {
+                                            id: stream_id.clone(),
+                                            model: model.clone(),
+                                            thinking: Some(delta.thinking),
+                                            ..Default::default()
+                                        };
+                                    }
+                                    _ => {}
                                 }
                             }
                             "message_delta" => {
                                 let finish_reason = event.delta
                                     .as_ref()
                                     .and_then(|d| d.stop_reason.clone());
-                                let usage = event.usage.map(|u| Usage {
-                                    input_tokens: cached_input_tokens,
-                                    output_tokens: u.output_tokens.unwrap_or(0),
+                                let usage = event.usage.map(|u| {
+                                    let output_tokens = u.output_tokens.unwrap_or(0);
+                                    Usage {
+                                        input_tokens: cached_input_tokens,
+                                        output_tokens,
+                                        thinking_tokens: thinking_tokens(
+                                            &thinking_text,
+                                            output_tokens,
+                                        ),
+                                    }
                                 });
                                 yield ChatChunk {
                                     id: stream_id.clone(),
                                     model: model.clone(),
-                                    delta: String::new(),
                                     finish_reason,
                                     usage,
+                                    ..Default::default()
                                 };
                             }
                             "message_stop" => return,
@@ -348,6 +449,9 @@ mod tests {
     fn test_anthropic_provider_base_url() {
         let provider = AnthropicProvider::new().unwrap();
         assert_eq!(provider.base_url(), "https://api.anthropic.com");
+
+        let provider = provider.with_base_url("http://localhost:8080/");
+        assert_eq!(provider.base_url(), "http://localhost:8080");
     }
 
     #[test]
@@ -355,4 +459,89 @@ mod tests {
         let provider = AnthropicProvider::new().unwrap();
         assert!(provider.supports_streaming());
     }
+
+    #[test]
+    fn test_request_body_max_tokens_defaults_per_model() {
+        let request = ChatRequest {
+            model: "claude-3-haiku-20240307".to_string(),
+            ..Default::default()
+        };
+        let (_, _, body) = build_anthropic_request_body(&request, false);
+        assert_eq!(body["max_tokens"], 4096);
+
+        let request = ChatRequest {
+            model: "claude-sonnet-4-5".to_string(),
+            max_tokens: Some(20_000),
+            ..Default::default()
+        };
+        let (_, _, body) = build_anthropic_request_body(&request, false);
+        assert_eq!(body["max_tokens"], 20_000);
+    }
+
+    #[test]
+    fn test_request_body_appends_format_instruction_to_system() {
+        let request = ChatRequest {
+            model: "claude-3-haiku-20240307".to_string(),
+            messages: vec![ChatMessage {
+                role: MessageRole::System,
+                content: "Be terse.".to_string(),
+            }],
+            response_format: Some(ResponseFormat::JsonObject),
+            ..Default::default()
+        };
+        let (system, _, _) = build_anthropic_request_body(&request, false);
+        let system = system.unwrap();
+        assert!(system.starts_with("Be terse.\n"));
+        assert!(system.contains("valid JSON object"));
+    }
+
+    #[test]
+    fn test_request_body_with_thinking() {
+        let request = ChatRequest {
+            model: "claude-sonnet-4-5".to_string(),
+            temperature: Some(0.3),
+            max_tokens: Some(16_000),
+            thinking: Some(ThinkingOptions {
+                budget_tokens: 10_000,
+            }),
+            ..Default::default()
+        };
+        let (_, _, body) = build_anthropic_request_body(&request, false);
+        assert_eq!(body["thinking"]["type"], "enabled");
+        assert_eq!(body["thinking"]["budget_tokens"], 10_000);
+        assert_eq!(body["max_tokens"], 16_000);
+        assert!(body.get("temperature").is_none());
+
+        // The default output budget would leave nothing after thinking.
+        let request = ChatRequest {
+            model: "claude-3-7-sonnet-20250219".to_string(),
+            thinking: Some(ThinkingOptions {
+                budget_tokens: 10_000,
+            }),
+            ..Default::default()
+        };
+        let (_, _, body) = build_anthropic_request_body(&request, false);
+        assert_eq!(body["max_tokens"], 18_192);
+    }
+
+    #[test]
+    fn test_split_content_separates_thinking() {
+        let blocks: Vec<ContentBlock> = serde_json::from_value(serde_json::json!([
+            {"type": "thinking", "thinking": "Let me add 2 and 2.", "signature": "sig"},
+            {"type": "redacted_thinking", "data": "opaque"},
+            {"type": "text", "text": "4"}
+        ]))
+        .unwrap();
+        let (content, thinking) = split_content(blocks);
+        assert_eq!(content, "4");
+        assert_eq!(thinking.as_deref(), Some("Let me add 2 and 2."));
+        assert!(thinking_tokens("Let me add 2 and 2.", 50) > 0);
+        assert_eq!(thinking_tokens("Let me add 2 and 2.", 1), 1);
+
+        let (_, thinking) = split_content(vec![ContentBlock {
+            text: Some("hi".to_string()),
+            thinking: None,
+        }]);
+        assert_eq!(thinking, None);
+    }
 }
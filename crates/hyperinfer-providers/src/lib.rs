@@ -9,6 +9,28 @@ pub mod openai;
 pub use provider_trait::LlmProvider;
 pub use registry::ProviderRegistry;
 
+use hyperinfer_core::HyperInferError;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::HashMap;
+
+/// Convert configured provider headers into a `HeaderMap`, rejecting names or
+/// values that are not valid HTTP.
+pub fn header_map(headers: &HashMap<String, String>) -> Result<HeaderMap, HyperInferError> {
+    let mut map = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        let invalid = |what: &str| {
+            HyperInferError::Config(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid header {}: '{}'", what, name),
+            ))
+        };
+        let name_parsed = HeaderName::from_bytes(name.as_bytes()).map_err(|_| invalid("name"))?;
+        let value_parsed = HeaderValue::from_str(value).map_err(|_| invalid("value for"))?;
+        map.insert(name_parsed, value_parsed);
+    }
+    Ok(map)
+}
+
 pub fn drain_lines(raw_buf: &mut Vec<u8>, lines: &mut Vec<String>) {
     if raw_buf.is_empty() {
         return;
@@ -67,6 +89,27 @@ pub fn init_default_registry(registry: &ProviderRegistry) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_header_map_valid() {
+        let mut headers = HashMap::new();
+        headers.insert("api-key".to_string(), "azure-secret".to_string());
+        headers.insert("X-Custom-Trace".to_string(), "abc".to_string());
+        let map = header_map(&headers).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["api-key"], "azure-secret");
+        assert_eq!(map["x-custom-trace"], "abc");
+    }
+
+    #[test]
+    fn test_header_map_rejects_invalid_value() {
+        let mut headers = HashMap::new();
+        headers.insert("X-Bad".to_string(), "line\nbreak".to_string());
+        assert!(matches!(
+            header_map(&headers),
+            Err(HyperInferError::Config(_))
+        ));
+    }
+
     fn feed_chunks(chunks: &[&[u8]]) -> (Vec<String>, Vec<u8>) {
         let mut raw_buf: Vec<u8> = Vec::new();
         let mut all_lines: Vec<String> = Vec::new();
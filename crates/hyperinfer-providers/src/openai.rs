@@ -1,26 +1,47 @@
 use super::provider_trait::LlmProvider;
 use async_trait::async_trait;
 use futures::{Stream, StreamExt};
+use hyperinfer_core::types::is_reasoning_model;
 use hyperinfer_core::{
-    ChatChunk, ChatMessage, ChatRequest, ChatResponse, Choice, HyperInferError, MessageRole, Usage,
+    ChatChunk, ChatMessage, ChatRequest, ChatResponse, Choice, HyperInferError, MessageRole,
+    ReasoningEffort, Usage,
 };
 use reqwest::Client;
 use std::pin::Pin;
 
+/// Public API endpoint.
+pub const DEFAULT_BASE_URL: &str = "https://api.openai.com";
+
 pub struct OpenAiProvider {
     http_client: Client,
-    base_url: &'static str,
+    base_url: String,
 }
 
 impl OpenAiProvider {
     pub fn new() -> Result<Self, reqwest::Error> {
+        Self::with_default_headers(reqwest::header::HeaderMap::new())
+    }
+
+    /// Build a provider that sends `headers` on every request in addition to
+    /// the authentication headers.
+    pub fn with_default_headers(
+        headers: reqwest::header::HeaderMap,
+    ) -> Result<Self, reqwest::Error> {
         Ok(Self {
             http_client: Client::builder()
                 .timeout(std::time::Duration::from_secs(60))
+                .default_headers(headers)
                 .build()?,
-            base_url: "https://api.openai.com",
+            base_url: DEFAULT_BASE_URL.to_string(),
         })
     }
+
+    /// Send requests to `base_url` instead of the public API, e.g. a
+    /// gateway or a test server.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
 }
 
 // Clone is required by LlmProvider supertrait. The HTTP client is cheap to clone.
@@ -28,7 +49,48 @@ impl Clone for OpenAiProvider {
     fn clone(&self) -> Self {
         Self {
             http_client: self.http_client.clone(),
-            base_url: self.base_url,
+            base_url: self.base_url.clone(),
+        }
+    }
+}
+
+/// Sampling parameters OpenAI's reasoning models reject with a 400.
+const UNSUPPORTED_REASONING_PARAMS: &[&str] = &[
+    "temperature",
+    "top_p",
+    "presence_penalty",
+    "frequency_penalty",
+    "logprobs",
+    "top_logprobs",
+    "logit_bias",
+];
+
+/// Adjust a chat completions body for `model`.  For o-series models
+/// `max_tokens` becomes `max_completion_tokens`, `reasoning_effort` is set,
+/// and unsupported sampling parameters are dropped; other models never get
+/// `reasoning_effort`.
+pub fn apply_reasoning_params(
+    body: &mut serde_json::Map<String, serde_json::Value>,
+    model: &str,
+    effort: Option<ReasoningEffort>,
+) {
+    if !is_reasoning_model(model) {
+        body.remove("reasoning_effort");
+        return;
+    }
+    if let Some(max_tokens) = body.remove("max_tokens") {
+        if !max_tokens.is_null() {
+            body.insert("max_completion_tokens".to_string(), max_tokens);
+        }
+    }
+    if let Some(effort) = effort {
+        body.insert("reasoning_effort".to_string(), serde_json::json!(effort));
+    }
+    for param in UNSUPPORTED_REASONING_PARAMS {
+        if let Some(value) = body.remove(*param) {
+            if !value.is_null() {
+                tracing::debug!("Dropping {} unsupported by {}", param, model);
+            }
         }
     }
 }
@@ -46,6 +108,10 @@ fn chat_request_to_openai_body(request: &ChatRequest) -> serde_json::Value {
     if let Some(stop) = &request.stop {
         body.insert("stop".to_string(), serde_json::json!(stop));
     }
+    if let Some(format) = &request.response_format {
+        body.insert("response_format".to_string(), serde_json::json!(format));
+    }
+    apply_reasoning_params(&mut body, &request.model, request.reasoning_effort);
     serde_json::Value::Object(body)
 }
 
@@ -55,8 +121,8 @@ impl LlmProvider for OpenAiProvider {
         "openai"
     }
 
-    fn base_url(&self) -> &'static str {
-        self.base_url
+    fn base_url(&self) -> &str {
+        &self.base_url
     }
 
     async fn chat(
@@ -79,10 +145,7 @@ impl LlmProvider for OpenAiProvider {
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
-            return Err(HyperInferError::ApiError {
-                status: status.as_u16(),
-                message: error_text,
-            });
+            return Err(HyperInferError::api_error(status.as_u16(), error_text));
         }
 
         #[derive(serde::Deserialize)]
@@ -139,12 +202,16 @@ impl LlmProvider for OpenAiProvider {
                         content: c.message.content,
                     },
                     finish_reason: c.finish_reason,
+                    thinking: None,
                 })
                 .collect(),
             usage: Usage {
                 input_tokens: data.usage.prompt_tokens,
                 output_tokens: data.usage.completion_tokens,
+                thinking_tokens: 0,
             },
+            metadata: std::collections::HashMap::new(),
+            dry_run: None,
         })
     }
 
@@ -177,6 +244,10 @@ impl LlmProvider for OpenAiProvider {
         if let Some(ref stop) = request.stop {
             body.insert("stop".to_string(), serde_json::json!(stop));
         }
+        if let Some(ref format) = request.response_format {
+            body.insert("response_format".to_string(), serde_json::json!(format));
+        }
+        apply_reasoning_params(&mut body, &request.model, request.reasoning_effort);
         let body = serde_json::Value::Object(body);
         let client = self.http_client.clone();
         let api_key = api_key.to_string();
@@ -193,10 +264,7 @@ impl LlmProvider for OpenAiProvider {
             if !response.status().is_success() {
                 let status = response.status();
                 let error_text = response.text().await.unwrap_or_default();
-                Err(HyperInferError::ApiError {
-                    status: status.as_u16(),
-                    message: error_text,
-                })?;
+                Err(HyperInferError::api_error(status.as_u16(), error_text))?;
                 return;
             }
 
@@ -271,6 +339,7 @@ impl LlmProvider for OpenAiProvider {
                             let usage = event.usage.map(|u| Usage {
                                 input_tokens: u.prompt_tokens,
                                 output_tokens: u.completion_tokens,
+                                thinking_tokens: 0,
                             });
 
                             yield ChatChunk {
@@ -278,6 +347,7 @@ impl LlmProvider for OpenAiProvider {
                                 model: event.model,
                                 delta,
                                 finish_reason,
+                                thinking: None,
                                 usage,
                             };
                         }
@@ -300,6 +370,7 @@ impl LlmProvider for OpenAiProvider {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use hyperinfer_core::{JsonSchemaFormat, ResponseFormat};
 
     #[test]
     fn test_openai_provider_name() {
@@ -311,6 +382,9 @@ mod tests {
     fn test_openai_provider_base_url() {
         let provider = OpenAiProvider::new().unwrap();
         assert_eq!(provider.base_url(), "https://api.openai.com");
+
+        let provider = provider.with_base_url("http://localhost:8080/");
+        assert_eq!(provider.base_url(), "http://localhost:8080");
     }
 
     #[test]
@@ -318,4 +392,59 @@ mod tests {
         let provider = OpenAiProvider::new().unwrap();
         assert!(provider.supports_streaming());
     }
+
+    #[test]
+    fn test_openai_body_includes_response_format() {
+        let request = ChatRequest {
+            model: "gpt-4o".to_string(),
+            response_format: Some(ResponseFormat::JsonSchema {
+                json_schema: JsonSchemaFormat {
+                    name: "answer".to_string(),
+                    description: None,
+                    schema: serde_json::json!({"type": "object"}),
+                    strict: Some(true),
+                },
+            }),
+            ..Default::default()
+        };
+        let body = chat_request_to_openai_body(&request);
+        assert_eq!(body["response_format"]["type"], "json_schema");
+        assert_eq!(body["response_format"]["json_schema"]["name"], "answer");
+        assert_eq!(body["response_format"]["json_schema"]["strict"], true);
+
+        let body = chat_request_to_openai_body(&ChatRequest::default());
+        assert!(body.get("response_format").is_none());
+    }
+
+    #[test]
+    fn test_openai_body_for_reasoning_model() {
+        let request = ChatRequest {
+            model: "o3-mini".to_string(),
+            temperature: Some(0.2),
+            max_tokens: Some(500),
+            reasoning_effort: Some(ReasoningEffort::High),
+            ..Default::default()
+        };
+        let body = chat_request_to_openai_body(&request);
+        assert_eq!(body["max_completion_tokens"], 500);
+        assert_eq!(body["reasoning_effort"], "high");
+        assert!(body.get("max_tokens").is_none());
+        assert!(body.get("temperature").is_none());
+
+        let body = chat_request_to_openai_body(&ChatRequest {
+            model: "gpt-4o".to_string(),
+            ..request
+        });
+        assert_eq!(body["max_tokens"], 500);
+        assert_eq!(body["temperature"], 0.2);
+        assert!(body.get("max_completion_tokens").is_none());
+        assert!(body.get("reasoning_effort").is_none());
+    }
+
+    #[test]
+    fn test_apply_reasoning_params_drops_null_max_tokens() {
+        let mut body = serde_json::json!({"model": "o1", "max_tokens": null, "temperature": null});
+        apply_reasoning_params(body.as_object_mut().unwrap(), "o1", None);
+        assert_eq!(body, serde_json::json!({"model": "o1"}));
+    }
 }
@@ -43,6 +43,15 @@ pub trait LlmProvider: dyn_clone::DynClone + Send + Sync {
             max_tokens: Some(1),
             stream: None,
             stop: None,
+            metadata: std::collections::HashMap::new(),
+            response_format: None,
+            compression: None,
+            reasoning_effort: None,
+            thinking: None,
+            dry_run: false,
+            context_overflow: None,
+            routing: None,
+            data_region: None,
         };
         self.chat(&request, api_key).await?;
         Ok(())
@@ -4,13 +4,50 @@ from typing import TYPE_CHECKING
 
 from hyperinfer.client import Client
 from hyperinfer.config import Config
+from hyperinfer.sync import iter_sync, run_sync
+from hyperinfer.types import (
+    ChatChunk,
+    ChatRequest,
+    ChatResponse,
+    Choice,
+    CostEstimate,
+    ErrorEvent,
+    KeyLimits,
+    Message,
+    Quota,
+    RequestEvent,
+    ResponseEvent,
+    Route,
+    ShutdownReport,
+    Usage,
+)
 
 # This block is seen by IDEs/Linters but ignored at runtime
 if TYPE_CHECKING:
     from hyperinfer._hyperinfer import HyperInferClient
 
 __version__ = "0.1.0"
-__all__ = ["HyperInferClient", "Client", "Config"]
+__all__ = [
+    "HyperInferClient",
+    "Client",
+    "Config",
+    "ChatChunk",
+    "ChatRequest",
+    "ChatResponse",
+    "Choice",
+    "CostEstimate",
+    "ErrorEvent",
+    "KeyLimits",
+    "Message",
+    "Quota",
+    "RequestEvent",
+    "ResponseEvent",
+    "Route",
+    "ShutdownReport",
+    "Usage",
+    "iter_sync",
+    "run_sync",
+]
 
 
 def __getattr__(name: str) -> type["HyperInferClient"]:
@@ -8,8 +8,25 @@ the compiled ``.so`` / ``.pyd`` binary at analysis time.
 
 from __future__ import annotations
 
-from collections.abc import AsyncIterator
-from typing import Any
+from collections.abc import Awaitable, Callable
+from typing import Any, TypeVar
+
+from hyperinfer.types import (
+    ChatChunk,
+    ChatRequest,
+    ChatResponse,
+    CostEstimate,
+    ErrorEvent,
+    KeyLimits,
+    RequestEvent,
+    ResponseEvent,
+    Route,
+    ShutdownReport,
+)
+
+_RequestHook = TypeVar("_RequestHook", bound=Callable[[RequestEvent], Awaitable[None] | None])
+_ResponseHook = TypeVar("_ResponseHook", bound=Callable[[ResponseEvent], Awaitable[None] | None])
+_ErrorHook = TypeVar("_ErrorHook", bound=Callable[[ErrorEvent], Awaitable[None] | None])
 
 def init_langfuse_telemetry(
     public_key: str,
@@ -30,10 +47,23 @@ def init_langfuse_telemetry(
     """
     ...
 
+def init_observability(endpoint: str) -> None:
+    """Initialize OpenTelemetry traces and GenAI metrics against an OTLP collector.
+
+    Args:
+        endpoint: Collector base URL, e.g. ``"http://localhost:4318"``.  Spans
+            go to ``/v1/traces`` and metrics to ``/v1/metrics`` under it.
+
+    Raises:
+        RuntimeError: If telemetry fails to initialize.
+    """
+    ...
+
 def shutdown_telemetry() -> None:
-    """Flush and shut down the global tracer provider.
+    """Flush and shut down the global tracer and meter providers.
 
-    Should be called before process exit to ensure all buffered spans are exported.
+    Should be called before process exit to ensure all buffered spans and
+    metrics are exported.
     """
     ...
 
@@ -49,12 +79,27 @@ class HyperInferClient:
         config: Optional configuration dictionary as produced by
             :meth:`hyperinfer.Config.to_dict`.  When omitted an empty
             configuration is used.
+        transport: Optional provider connection settings, over those read
+            from ``HYPERINFER_HTTP_*``, ``HYPERINFER_PROXY_URL``,
+            ``HYPERINFER_NO_PROXY``, ``HYPERINFER_CA_BUNDLE*``,
+            ``HYPERINFER_MAX_RESPONSE_BYTES`` and
+            ``HYPERINFER_OVERSIZED_RESPONSE`` environment variables.
+            Durations are in seconds; ``None`` turns off the optional ones.  Keys: ``timeout``, ``connect_timeout``,
+            ``pool_max_idle_per_host``, ``pool_idle_timeout``,
+            ``tcp_keepalive``, ``tcp_nodelay``, ``http2_prior_knowledge``,
+            ``http2_keep_alive_interval``, ``http2_keep_alive_timeout``,
+            ``http2_keep_alive_while_idle``, ``proxy_url`` (HTTP(S) or
+            ``socks5h://``), ``no_proxy``, ``ca_bundle_paths`` (PEM files
+            to trust), ``ca_bundle_only``, ``max_response_bytes`` and
+            ``oversized_response`` (``"reject"`` or ``"truncate"``).  Unknown
+            keys raise :class:`ValueError`.
     """
 
     def __init__(
         self,
         redis_url: str,
         config: dict[str, Any] | None = None,
+        transport: dict[str, Any] | None = None,
     ) -> None: ...
     async def init(self) -> None:
         """Initialise the underlying Rust client.
@@ -64,16 +109,21 @@ class HyperInferClient:
         """
         ...
 
+    async def init_with_registry(self, registry_wrapper: ProviderRegistryWrapper) -> None:
+        """Like :meth:`init`, with Python providers from ``registry_wrapper``."""
+        ...
+
     async def chat(
         self,
         key: str,
-        request: dict[str, Any],
-    ) -> dict[str, Any]:
+        request: ChatRequest | dict[str, Any],
+    ) -> ChatResponse:
         """Send a chat request through the data plane.
 
         Args:
             key: Virtual key used for rate-limiting and telemetry attribution.
-            request: Request dict with the following structure::
+            request: A :class:`hyperinfer.types.ChatRequest`, or a dict
+                with the following structure::
 
                 {
                     "model": "gpt-4",
@@ -82,10 +132,14 @@ class HyperInferClient:
                     ],
                     "temperature": 0.7,   # optional
                     "max_tokens": 1024,   # optional
+                    "compression": {"ratio": 0.5},  # optional
+                    "reasoning_effort": "low",  # optional, o-series only
+                    "thinking": {"budget_tokens": 2048},  # optional, Claude only
                 }
 
         Returns:
-            Response dict::
+            A :class:`hyperinfer.types.ChatResponse`, which also reads like
+            the dict::
 
                 {
                     "id": "chatcmpl-...",
@@ -95,11 +149,13 @@ class HyperInferClient:
                             "index": 0,
                             "message": {"role": "assistant", "content": "..."},
                             "finish_reason": "stop",
+                            "thinking": None,  # extended thinking, if any
                         }
                     ],
                     "usage": {
                         "input_tokens": 12,
                         "output_tokens": 34,
+                        "thinking_tokens": 0,  # part of output_tokens
                     },
                 }
 
@@ -113,16 +169,18 @@ class HyperInferClient:
     async def chat_stream(
         self,
         key: str,
-        request: dict[str, Any],
-    ) -> AsyncIterator[dict[str, Any]]:
+        request: ChatRequest | dict[str, Any],
+    ) -> ChunkStream:
         """Stream token chunks through the data plane.
 
-        Returns an async iterator that yields one chunk dict per SSE event::
+        Returns an async iterator that yields one
+        :class:`hyperinfer.types.ChatChunk` per SSE event::
 
             {
                 "id": "chatcmpl-...",
                 "model": "gpt-4",
                 "delta": "Hello",        # incremental text
+                "thinking": None,        # incremental extended thinking
                 "finish_reason": None,   # "stop" on the last chunk
                 "usage": None,           # dict with token counts on last chunk
             }
@@ -136,6 +194,100 @@ class HyperInferClient:
         """
         ...
 
+    async def chat_stream_callback(
+        self,
+        key: str,
+        request: ChatRequest | dict[str, Any],
+        on_token: Callable[[ChatChunk], Awaitable[None] | None],
+        buffer: int = 32,
+    ) -> ChatResponse:
+        """Stream ``request``, calling ``on_token`` with each chunk.
+
+        Up to ``buffer`` chunks wait while ``on_token`` runs; once that many
+        are waiting, the provider stream is paused until it catches up.
+        Plain functions run on a worker thread, so a slow one never stalls
+        the gateway's runtime; coroutine functions are awaited on the
+        caller's event loop.
+
+        Returns:
+            The response the chunks add up to, with the final usage.
+
+        Raises:
+            RuntimeError: If the client has not been initialised or the
+                stream fails.
+            ValueError: If ``buffer`` is 0.
+            Exception: Whatever ``on_token`` raised; the stream is dropped.
+        """
+        ...
+
+    async def resolve(self, model: str) -> Route:
+        """Resolve a model name through aliases and routing rules.
+
+        Nothing is sent; the client's current config is used.
+
+        Returns:
+            ``{"model": "gpt-4o-mini", "provider": "openai"}``
+
+        Raises:
+            ValueError: If no routing rule or alias matches ``model``.
+        """
+        ...
+
+    async def get_limits(self, key: str) -> KeyLimits:
+        """Limits in effect for ``key``, from the local config and policies::
+
+            {
+                "team_id": "team-1",             # None for unknown keys
+                "quota": {"max_requests_per_minute": 60, ...},  # or None
+                "organization_id": None,
+                "organization_quota": None,
+                "allowed_models": [],            # empty means any
+                "budget_cents": None,
+                "throttled_rpm": None,           # set by the control plane
+                "revoked": False,
+            }
+
+        Raises:
+            RuntimeError: If the key's virtual key is disabled or expired.
+        """
+        ...
+
+    async def estimate_cost(self, request: ChatRequest | dict[str, Any]) -> CostEstimate:
+        """Estimate what ``request`` (same shape as :meth:`chat`) would cost::
+
+            {
+                "model": "gpt-4",
+                "input_tokens": 12,
+                "max_output_tokens": 1024,
+                "input_cost_cents": 0.036,
+                "max_output_cost_cents": 6.144,
+                "max_total_cost_cents": 6.18,   # upper bound
+            }
+
+        Raises:
+            ValueError: If the model cannot be routed or has no known price.
+        """
+        ...
+
+    def on_request(self, callback: _RequestHook) -> _RequestHook:
+        """Call ``callback`` with a :class:`RequestEvent` before every chat call.
+
+        Coroutine functions are awaited before the call goes on.  A callback
+        that raises is logged to the ``hyperinfer`` logger and does not fail
+        the call.  Returns ``callback``, so this works as a decorator.
+        """
+        ...
+
+    def on_response(self, callback: _ResponseHook) -> _ResponseHook:
+        """Call ``callback`` with a :class:`ResponseEvent` after every successful
+        chat call, with the response, latency and cost."""
+        ...
+
+    def on_error(self, callback: _ErrorHook) -> _ErrorHook:
+        """Call ``callback`` with an :class:`ErrorEvent` after every failed chat
+        call, or stream that could not be started."""
+        ...
+
     async def set_mirror(self, model: str | None = None, sample_rate: float | None = None) -> None:
         """Configure traffic mirroring for the client.
 
@@ -145,15 +297,67 @@ class HyperInferClient:
         """
         ...
 
-    async def close(self) -> None:
-        """Flush pending telemetry and release resources.
+    async def close(self, timeout: float = 5.0) -> ShutdownReport | None:
+        """Stop taking requests and release the client's connections.
 
-        Optional - safe to call multiple times.
+        Waits up to ``timeout`` seconds for running requests and streams to
+        finish and for buffered usage telemetry to be written, then drops
+        the Rust client, closing its Redis connections.  A closed client
+        cannot be initialised again.
+
+        Returns:
+            What got done before the timeout, or ``None`` if the client was
+            never initialised or is already closed.
         """
         ...
 
+    async def __aenter__(self) -> HyperInferClient:
+        """Initialise the client, unless it already is."""
+        ...
+
+    async def __aexit__(
+        self,
+        exc_type: type[BaseException] | None,
+        exc_value: BaseException | None,
+        traceback: Any,
+    ) -> bool:
+        """Close the client with the default timeout."""
+        ...
+
 class ChunkStream:
     """Async iterator over SSE token chunks.  Returned by :meth:`HyperInferClient.chat_stream`."""
 
     def __aiter__(self) -> ChunkStream: ...
-    async def __anext__(self) -> dict[str, Any]: ...
+    async def __anext__(self) -> ChatChunk: ...
+
+class ProviderRegistryWrapper:
+    """Registry of providers implemented in Python.
+
+    Pass to :meth:`HyperInferClient.init_with_registry` to route requests
+    for the registered provider names to Python callables.
+    """
+
+    def __init__(self) -> None: ...
+    def register_provider(
+        self,
+        name: str,
+        chat_callable: Callable[..., Any],
+        stream_callable: Callable[..., Any] | None = None,
+    ) -> None:
+        """Register Python callables as the provider ``name``.
+
+        Raises:
+            ValueError: If a provider with the same name is already registered.
+        """
+        ...
+
+    def unregister_provider(self, name: str) -> bool:
+        """Remove the provider ``name``; returns whether it was registered."""
+        ...
+
+    def list_providers(self) -> list[str]: ...
+    def contains(self, name: str) -> bool: ...
+
+def create_provider_registry() -> ProviderRegistryWrapper:
+    """Create an empty :class:`ProviderRegistryWrapper`."""
+    ...
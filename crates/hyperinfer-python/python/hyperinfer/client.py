@@ -1,10 +1,24 @@
 """High-level async client for HyperInfer."""
 
 import asyncio
-from collections.abc import AsyncIterator
-from typing import TYPE_CHECKING, Any
+from collections.abc import AsyncIterator, Awaitable, Callable
+from typing import TYPE_CHECKING, Any, TypeVar
 
 from hyperinfer.config import Config
+from hyperinfer.types import (
+    ChatChunk,
+    ChatResponse,
+    CostEstimate,
+    ErrorEvent,
+    KeyLimits,
+    Message,
+    RequestEvent,
+    ResponseEvent,
+    Route,
+    ShutdownReport,
+)
+
+_Hook = TypeVar("_Hook", bound=Callable[..., Awaitable[None] | None])
 
 # This block is seen by IDEs/Linters but ignored at runtime
 if TYPE_CHECKING:
@@ -30,14 +44,26 @@ class Client:
         self,
         redis_url: str = "redis://localhost:6379",
         config: Config | None = None,
+        transport: dict[str, Any] | None = None,
     ):
-        """Initialize the client."""
+        """Initialize the client.
+
+        Args:
+            redis_url: Redis connection URL.
+            config: Routing, quotas and provider keys.
+            transport: Provider connection pool, proxy, CA bundle and
+                response size settings, over those read from the
+                environment; see
+                :class:`~hyperinfer._hyperinfer.HyperInferClient`.
+        """
         self._config_dict = config.to_dict() if config is not None else None
+        self._transport = transport
         self._redis_url = redis_url
         self._inner: Any = None
         self._initialized = False
         self._lifecycle_lock = asyncio.Lock()
         self._init_lock = asyncio.Lock()
+        self._hooks: list[tuple[str, Callable[..., Any]]] = []
 
     async def init(self) -> None:
         """Initialize the client connection."""
@@ -47,7 +73,11 @@ class Client:
             if self._inner is None:
                 from hyperinfer._hyperinfer import HyperInferClient
 
-                self._inner = HyperInferClient(self._redis_url, self._config_dict)
+                self._inner = HyperInferClient(
+                    self._redis_url, self._config_dict, self._transport
+                )
+                for event, callback in self._hooks:
+                    getattr(self._inner, f"on_{event}")(callback)
             await self._inner.init()
             self._initialized = True
 
@@ -55,23 +85,44 @@ class Client:
         self,
         key: str,
         model: str,
-        messages: list[dict[str, str]],
+        messages: list[Message] | list[dict[str, str]],
         temperature: float | None = None,
         max_tokens: int | None = None,
         stop: list[str] | None = None,
-    ) -> dict[str, Any]:
+        compression_ratio: float | None = None,
+        reasoning_effort: str | None = None,
+        thinking_budget: int | None = None,
+        on_token: Callable[[ChatChunk], Awaitable[None] | None] | None = None,
+        stream_buffer: int = 32,
+    ) -> ChatResponse:
         """Send a chat request to the LLM gateway.
 
         Args:
             key: API key for authentication.
             model: Model identifier (e.g., "gpt-4", "claude-3").
-            messages: List of message dicts with "role" and "content" keys.
+            messages: :class:`~hyperinfer.types.Message` objects, or dicts
+                with "role" and "content" keys.
             temperature: Sampling temperature (0.0-2.0).
             max_tokens: Maximum tokens to generate.
             stop: Stop sequences; generation halts when any is produced.
+            compression_ratio: Compress long messages (e.g. retrieved
+                documents) to about this fraction of their tokens, in (0, 1].
+            reasoning_effort: ``"minimal"``, ``"low"``, ``"medium"`` or
+                ``"high"``; only sent to OpenAI o-series models.
+            thinking_budget: Enable extended thinking on Claude models with
+                this many tokens (at least 1024) to think with.
+            on_token: Stream the answer, calling this with each
+                :class:`~hyperinfer.types.ChatChunk` as it arrives.  Plain
+                functions run on a worker thread; coroutine functions are
+                awaited.  If it raises, the stream is dropped and the error
+                is raised from ``chat``.
+            stream_buffer: With ``on_token``, how many chunks may wait while
+                it runs before the provider stream is paused.
 
         Returns:
-            Response dictionary containing model output and usage info.
+            The model output and usage info.  ``response.content`` is the
+            first choice's text; with ``on_token`` it is the whole streamed
+            answer.
         """
         async with self._lifecycle_lock:
             if not self._initialized:
@@ -88,40 +139,63 @@ class Client:
             request["max_tokens"] = max_tokens
         if stop is not None:
             request["stop"] = stop
+        if compression_ratio is not None:
+            request["compression"] = {"ratio": compression_ratio}
+        if reasoning_effort is not None:
+            request["reasoning_effort"] = reasoning_effort
+        if thinking_budget is not None:
+            request["thinking"] = {"budget_tokens": thinking_budget}
 
+        if on_token is not None:
+            return await inner.chat_stream_callback(  # type: ignore[no-any-return]
+                key, request, on_token, stream_buffer
+            )
         return await inner.chat(key, request)  # type: ignore[no-any-return]
 
     async def stream(
         self,
         key: str,
         model: str,
-        messages: list[dict[str, str]],
+        messages: list[Message] | list[dict[str, str]],
         temperature: float | None = None,
         max_tokens: int | None = None,
         stop: list[str] | None = None,
-    ) -> AsyncIterator[dict[str, Any]]:
+        compression_ratio: float | None = None,
+        reasoning_effort: str | None = None,
+        thinking_budget: int | None = None,
+    ) -> AsyncIterator[ChatChunk]:
         """Stream token chunks from the LLM gateway.
 
-        Yields one dict per SSE event with the following keys:
+        Yields one :class:`~hyperinfer.types.ChatChunk` per SSE event with
+        the following fields:
 
         - ``id`` (str): Stream identifier (same across all chunks).
         - ``model`` (str): Model that produced the chunk.
         - ``delta`` (str): Incremental text content for this chunk.
+        - ``thinking`` (str | None): Incremental extended thinking; the
+          ``delta`` of a thinking chunk is empty.
         - ``finish_reason`` (str | None): ``"stop"`` on the last chunk.
-        - ``usage`` (dict | None): Token counts on the final chunk only.
+        - ``usage`` (Usage | None): Token counts on the final chunk only.
 
         Args:
             key: Virtual key for authentication / quota tracking.
             model: Model identifier (e.g., ``"gpt-4"``).
-            messages: Conversation history as role/content dicts.
+            messages: Conversation history as :class:`~hyperinfer.types.Message`
+                objects or role/content dicts.
             temperature: Sampling temperature (0.0–2.0).
             max_tokens: Maximum tokens to generate.
             stop: Stop sequences; generation halts when any is produced.
+            compression_ratio: Compress long messages (e.g. retrieved
+                documents) to about this fraction of their tokens, in (0, 1].
+            reasoning_effort: ``"minimal"``, ``"low"``, ``"medium"`` or
+                ``"high"``; only sent to OpenAI o-series models.
+            thinking_budget: Enable extended thinking on Claude models with
+                this many tokens (at least 1024) to think with.
 
         Example::
 
             async for chunk in client.stream("my-key", "gpt-4", messages):
-                print(chunk["delta"], end="", flush=True)
+                print(chunk.delta, end="", flush=True)
         """
         async with self._lifecycle_lock:
             if not self._initialized:
@@ -135,11 +209,96 @@ class Client:
             request["max_tokens"] = max_tokens
         if stop is not None:
             request["stop"] = stop
+        if compression_ratio is not None:
+            request["compression"] = {"ratio": compression_ratio}
+        if reasoning_effort is not None:
+            request["reasoning_effort"] = reasoning_effort
+        if thinking_budget is not None:
+            request["thinking"] = {"budget_tokens": thinking_budget}
 
         chunk_iter = await inner.chat_stream(key, request)
         async for chunk in chunk_iter:
             yield chunk
 
+    def _add_hook(self, event: str, callback: _Hook) -> _Hook:
+        self._hooks.append((event, callback))
+        if self._inner is not None:
+            getattr(self._inner, f"on_{event}")(callback)
+        return callback
+
+    def on_request(self, callback: Callable[[RequestEvent], Awaitable[None] | None]) -> Any:
+        """Call ``callback`` with a :class:`~hyperinfer.types.RequestEvent`
+        before every chat call.
+
+        The callback may be a plain or a coroutine function; coroutines are
+        awaited before the call goes on, so keep them quick.  A callback that
+        raises is logged to the ``hyperinfer`` logger and does not fail the
+        call.  Returns ``callback``, so this works as a decorator::
+
+            @client.on_response
+            async def track(event):
+                langfuse.generation(model=event.request.model, cost=event.cost_cents)
+        """
+        return self._add_hook("request", callback)
+
+    def on_response(self, callback: Callable[[ResponseEvent], Awaitable[None] | None]) -> Any:
+        """Call ``callback`` with a :class:`~hyperinfer.types.ResponseEvent`
+        after every successful chat call, with its response, latency and cost.
+        """
+        return self._add_hook("response", callback)
+
+    def on_error(self, callback: Callable[[ErrorEvent], Awaitable[None] | None]) -> Any:
+        """Call ``callback`` with an :class:`~hyperinfer.types.ErrorEvent`
+        after every failed chat call, or stream that could not be started.
+        """
+        return self._add_hook("error", callback)
+
+    async def _ready(self) -> Any:
+        async with self._lifecycle_lock:
+            if not self._initialized:
+                await self.init()
+            return self._inner
+
+    async def resolve(self, model: str) -> Route:
+        """Resolve a model name to the model and provider it would be sent to.
+
+        Uses the client's current aliases and routing rules; nothing is sent.
+
+        Raises:
+            ValueError: If the model cannot be routed.
+        """
+        inner = await self._ready()
+        return await inner.resolve(model)  # type: ignore[no-any-return]
+
+    async def get_limits(self, key: str) -> KeyLimits:
+        """Return the quotas, allow-list, budget and policy in effect for ``key``.
+
+        Read from the client's config and control-plane policies, without a
+        round trip.
+        """
+        inner = await self._ready()
+        return await inner.get_limits(key)  # type: ignore[no-any-return]
+
+    async def estimate_cost(
+        self,
+        model: str,
+        messages: list[Message] | list[dict[str, str]],
+        max_tokens: int | None = None,
+    ) -> CostEstimate:
+        """Estimate what a chat request would cost before sending it.
+
+        The output side is priced at ``max_tokens`` (or the model's maximum),
+        so ``max_total_cost_cents`` is an upper bound.
+
+        Raises:
+            ValueError: If the model cannot be routed or has no known price.
+        """
+        inner = await self._ready()
+        request: dict[str, Any] = {"model": model, "messages": messages}
+        if max_tokens is not None:
+            request["max_tokens"] = max_tokens
+        return await inner.estimate_cost(request)  # type: ignore[no-any-return]
+
     async def set_mirror(self, model: str | None = None, sample_rate: float | None = None) -> None:
         """Configure traffic mirroring for the client.
 
@@ -158,13 +317,24 @@ class Client:
         await self.init()
         return self
 
-    async def close(self) -> None:
-        """Close the client connection and cleanup resources."""
+    async def close(self, timeout: float = 5.0) -> ShutdownReport | None:
+        """Close the client connection and cleanup resources.
+
+        Waits up to ``timeout`` seconds for running requests and for usage
+        telemetry to be written, then closes the client's Redis connections.
+        The client can be initialised again afterwards, with a new
+        connection.  Safe to call more than once.
+
+        Returns:
+            What got done before the timeout, or ``None`` if the client was
+            not initialised.
+        """
         async with self._lifecycle_lock:
-            if hasattr(self._inner, "close"):
-                await self._inner.close()
-            self._inner = None
+            inner, self._inner = self._inner, None
             self._initialized = False
+            if inner is None:
+                return None
+            return await inner.close(timeout)  # type: ignore[no-any-return]
 
     async def __aexit__(self, exc_type: Any, exc_val: Any, exc_tb: Any) -> None:
         """Async context manager exit."""
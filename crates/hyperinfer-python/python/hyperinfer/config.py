@@ -13,9 +13,15 @@ class Config:
     def __init__(self) -> None:
         self._api_keys: dict[str, str] = {}
         self._routing_rules: list[dict[str, Any]] = []
-        self._quotas: dict[str, dict[str, int | None]] = {}
+        self._quotas: dict[str, dict[str, int | str | None]] = {}
         self._model_aliases: dict[str, str] = {}
         self._default_provider: str | None = None
+        self._provider_headers: dict[str, dict[str, str]] = {}
+        self._provider_base_urls: dict[str, str] = {}
+        self._max_output_tokens: dict[str, int] = {}
+        self._hedging: dict[str, int | None] | None = None
+        self._context: dict[str, Any] | None = None
+        self._single_flight: dict[str, Any] | None = None
 
     def with_api_key(self, provider: str, key: str) -> "Config":
         """Add an API key for a provider.
@@ -71,6 +77,7 @@ class Config:
         budget_cents: int | None = None,
         max_requests_per_minute: int | None = None,
         max_tokens_per_minute: int | None = None,
+        rpm_window: str = "fixed",
     ) -> "Config":
         """Add a quota configuration.
 
@@ -81,6 +88,9 @@ class Config:
             budget_cents: Monthly budget in cents (USD).
             max_requests_per_minute: Requests per minute limit (alias for rpm).
             max_tokens_per_minute: Tokens per minute limit (alias for tpm).
+            rpm_window: ``"fixed"`` (a counter reset every minute, which can
+                admit up to twice the limit across a reset) or ``"sliding"``
+                (exact, at some Redis cost per request).
 
         Returns:
             Self for method chaining.
@@ -93,6 +103,7 @@ class Config:
                 max_tokens_per_minute if max_tokens_per_minute is not None else tpm
             ),
             "budget_cents": budget_cents,
+            "rpm_window": rpm_window,
         }
         return self
 
@@ -108,16 +119,147 @@ class Config:
         self._default_provider = provider
         return self
 
+    def with_provider_header(self, provider: str, name: str, value: str) -> "Config":
+        """Attach an extra HTTP header to every request sent to a provider.
+
+        Args:
+            provider: Provider name (e.g., "openai", "anthropic").
+            name: Header name (e.g., "OpenAI-Organization").
+            value: Header value.
+
+        Returns:
+            Self for method chaining.
+        """
+        self._provider_headers.setdefault(provider, {})[name] = value
+        return self
+
+    def with_provider_base_url(self, provider: str, url: str) -> "Config":
+        """Send a provider's requests to ``url`` instead of its public API.
+
+        Args:
+            provider: Provider name (e.g., "openai", "anthropic").
+            url: Base URL, e.g. "https://gateway.internal/openai".  Paths
+                such as ``/v1/chat/completions`` are appended to it.
+
+        Returns:
+            Self for method chaining.
+        """
+        self._provider_base_urls[provider] = url
+        return self
+
+    def with_max_output_tokens(self, model: str, max_tokens: int) -> "Config":
+        """Set the default output budget for a model.
+
+        Used when a request does not set ``max_tokens``; capped at the
+        model's known maximum.
+
+        Args:
+            model: Resolved model name (e.g., "claude-sonnet-4-5").
+            max_tokens: Default ``max_tokens`` for requests to this model.
+
+        Returns:
+            Self for method chaining.
+        """
+        self._max_output_tokens[model] = max_tokens
+        return self
+
+    def with_hedging(self, after_ms: int, max_output_tokens: int | None = None) -> "Config":
+        """Enable hedged requests.
+
+        If the primary provider has not answered within ``after_ms``, the
+        request is also sent to the first available fallback of its routing
+        rules and whichever succeeds first is returned.
+
+        Args:
+            after_ms: Delay before the hedge request is sent.
+            max_output_tokens: Only hedge requests whose ``max_tokens`` is at
+                most this, bounding what a duplicate call can cost.
+
+        Returns:
+            Self for method chaining.
+        """
+        self._hedging = {"after_ms": after_ms, "max_output_tokens": max_output_tokens}
+        return self
+
+    def with_context(
+        self,
+        strategy: str = "drop_oldest",
+        *,
+        max_messages: int | None = None,
+        summary_model: str | None = None,
+        context_windows: dict[str, int] | None = None,
+    ) -> "Config":
+        """Trim conversations that would overflow the model's context window.
+
+        System messages and the latest message are always kept.
+
+        Args:
+            strategy: ``"drop_oldest"``, ``"sliding_window"`` (requires
+                ``max_messages``) or ``"summarize"`` (requires
+                ``summary_model``, which replaces dropped messages with a
+                summary).
+            max_messages: Non-system messages kept by ``sliding_window``.
+            summary_model: Model that writes summaries, typically a cheap one.
+            context_windows: Context window per model, overriding the
+                built-in table.
+
+        Returns:
+            Self for method chaining.
+        """
+        strategy_dict: dict[str, Any] = {"type": strategy}
+        if strategy == "sliding_window":
+            if max_messages is None:
+                raise ValueError("sliding_window requires max_messages")
+            strategy_dict["max_messages"] = max_messages
+        elif strategy == "summarize":
+            if summary_model is None:
+                raise ValueError("summarize requires summary_model")
+            strategy_dict["model"] = summary_model
+        elif strategy != "drop_oldest":
+            raise ValueError(f"unknown context strategy: {strategy}")
+        self._context = {"strategy": strategy_dict, "context_windows": context_windows or {}}
+        return self
+
+    def with_single_flight(
+        self, enabled: bool = True, teams: dict[str, bool] | None = None
+    ) -> "Config":
+        """Coalesce identical concurrent requests.
+
+        While a ``chat()`` call is in flight, identical requests under the
+        same key wait for its response instead of calling the provider.
+
+        Args:
+            enabled: Whether requests are coalesced for teams without an
+                override.
+            teams: Per-team overrides of ``enabled``, keyed by team ID.
+
+        Returns:
+            Self for method chaining.
+        """
+        self._single_flight = {"enabled": enabled, "teams": teams or {}}
+        return self
+
     def to_dict(self) -> dict[str, Any]:
         """Convert configuration to dictionary.
 
         Returns:
             Dictionary representation of the configuration.
         """
-        return {
+        result: dict[str, Any] = {
             "api_keys": self._api_keys,
             "routing_rules": self._routing_rules,
             "quotas": self._quotas,
             "model_aliases": self._model_aliases,
             "default_provider": self._default_provider,
+            "provider_headers": self._provider_headers,
+            "max_output_tokens": self._max_output_tokens,
         }
+        if self._provider_base_urls:
+            result["provider_base_urls"] = self._provider_base_urls
+        if self._hedging is not None:
+            result["hedging"] = self._hedging
+        if self._context is not None:
+            result["context"] = self._context
+        if self._single_flight is not None:
+            result["single_flight"] = self._single_flight
+        return result
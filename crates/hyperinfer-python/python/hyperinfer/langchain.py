@@ -0,0 +1,24 @@
+"""LangChain chat model backed by HyperInfer.
+
+Re-exports the ``hyperinfer-langchain`` binding, so LangChain code can adopt
+the gateway with one import change::
+
+    from hyperinfer.langchain import ChatHyperInfer
+
+    llm = await ChatHyperInfer.from_config(config, model="gpt-4", virtual_key="team-key")
+
+Install it with ``pip install hyperinfer[langchain]``.
+"""
+
+try:
+    from hyperinfer_langchain import HyperInferChatModel
+except ModuleNotFoundError:
+    raise ImportError(
+        "hyperinfer.langchain requires hyperinfer-langchain. "
+        "Install it with `pip install hyperinfer[langchain]`."
+    ) from None
+
+#: LangChain-style name for :class:`hyperinfer_langchain.HyperInferChatModel`.
+ChatHyperInfer = HyperInferChatModel
+
+__all__ = ["ChatHyperInfer", "HyperInferChatModel"]
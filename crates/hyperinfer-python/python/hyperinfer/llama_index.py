@@ -0,0 +1,22 @@
+"""LlamaIndex LLM backed by HyperInfer.
+
+Re-exports the ``hyperinfer-llamaindex`` binding, so LlamaIndex code can
+adopt the gateway with one import change::
+
+    from llama_index.core import Settings
+    from hyperinfer.llama_index import HyperInferLLM
+
+    Settings.llm = HyperInferLLM.from_config(config, model="gpt-4", virtual_key="team-key")
+
+Install it with ``pip install hyperinfer[llama-index]``.
+"""
+
+try:
+    from hyperinfer_llamaindex import HyperInferLLM
+except ModuleNotFoundError:
+    raise ImportError(
+        "hyperinfer.llama_index requires hyperinfer-llamaindex. "
+        "Install it with `pip install hyperinfer[llama-index]`."
+    ) from None
+
+__all__ = ["HyperInferLLM"]
@@ -0,0 +1,69 @@
+"""Run the async client from synchronous code.
+
+:func:`run_sync` and :func:`iter_sync` run coroutines and async iterators on
+one event loop in a background thread, so they work whether or not the
+calling thread already has a loop running.  The LangChain and LlamaIndex
+bindings (``hyperinfer-langchain`` and ``hyperinfer-llamaindex``) build their
+blocking methods on them::
+
+    from hyperinfer import Client, iter_sync, run_sync
+
+    client = Client(redis_url, config)
+    response = run_sync(client.chat("key", "gpt-4", messages))
+    for chunk in iter_sync(client.stream("key", "gpt-4", messages)):
+        print(chunk.delta, end="")
+"""
+
+import asyncio
+import threading
+from collections.abc import AsyncIterator, Coroutine, Iterator
+from typing import Any, TypeVar
+
+T = TypeVar("T")
+
+_loop: asyncio.AbstractEventLoop | None = None
+_loop_lock = threading.Lock()
+
+
+def _background_loop() -> asyncio.AbstractEventLoop:
+    global _loop
+    with _loop_lock:
+        if _loop is None:
+            _loop = asyncio.new_event_loop()
+            threading.Thread(
+                target=_loop.run_forever, name="hyperinfer-sync", daemon=True
+            ).start()
+        return _loop
+
+
+def run_sync(coro: Coroutine[Any, Any, T]) -> T:
+    """Run ``coro`` on the background loop and wait for its result."""
+    return asyncio.run_coroutine_threadsafe(coro, _background_loop()).result()
+
+
+def iter_sync(items: AsyncIterator[T]) -> Iterator[T]:
+    """Iterate ``items`` on the background loop, one item at a time.
+
+    Each item is fetched only when the caller asks for it, so a slow caller
+    holds the stream back rather than items piling up.  If the caller stops
+    early, ``items`` is closed on the loop, ending the stream upstream.
+    """
+
+    async def next_item() -> tuple[bool, T | None]:
+        try:
+            return True, await items.__anext__()
+        except StopAsyncIteration:
+            return False, None
+
+    finished = False
+    try:
+        while True:
+            more, item = run_sync(next_item())
+            if not more:
+                finished = True
+                return
+            yield item  # type: ignore[misc]
+    finally:
+        aclose = getattr(items, "aclose", None)
+        if not finished and aclose is not None:
+            run_sync(aclose())
@@ -7,8 +7,15 @@ def init_langfuse_telemetry(
     _init(public_key, secret_key, langfuse_host)
 
 
+def init_observability(endpoint: str) -> None:
+    """Initialize OpenTelemetry traces and GenAI metrics against an OTLP collector."""
+    from hyperinfer._hyperinfer import init_observability as _init
+
+    _init(endpoint)
+
+
 def shutdown_telemetry() -> None:
-    """Flush and shut down the global tracer provider."""
+    """Flush and shut down the global tracer and meter providers."""
     from hyperinfer._hyperinfer import shutdown_telemetry as _shutdown
 
     _shutdown()
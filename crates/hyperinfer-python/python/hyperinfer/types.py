@@ -0,0 +1,328 @@
+"""Typed requests and responses for HyperInfer.
+
+Responses from the native client arrive as these dataclasses.  They also
+support ``response["key"]`` and ``response.get("key")``, so code written
+against the earlier dict responses keeps working.  Requests may be passed
+either as dataclasses or as plain dicts.
+"""
+
+from __future__ import annotations
+
+from dataclasses import asdict, dataclass, field, fields
+from typing import Any, Literal
+
+Role = Literal["system", "user", "assistant"]
+
+
+class _Record:
+    """Dict-style read access and conversion for the dataclasses below."""
+
+    def __getitem__(self, key: str) -> Any:
+        if key not in {f.name for f in fields(self)}:  # type: ignore[arg-type]
+            raise KeyError(key)
+        return getattr(self, key)
+
+    def get(self, key: str, default: Any = None) -> Any:
+        try:
+            return self[key]
+        except KeyError:
+            return default
+
+    def to_dict(self) -> dict[str, Any]:
+        """Convert to plain dicts and lists, dropping unset (``None``) fields."""
+        return {k: v for k, v in asdict(self).items() if v is not None}  # type: ignore[call-overload]
+
+
+@dataclass
+class Message(_Record):
+    role: Role
+    content: str
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> Message:
+        return cls(role=data["role"], content=data["content"])
+
+
+@dataclass
+class ChatRequest(_Record):
+    """A chat request.  Optional fields left as ``None`` are not sent."""
+
+    model: str
+    messages: list[Message]
+    temperature: float | None = None
+    max_tokens: int | None = None
+    stop: list[str] | None = None
+    metadata: dict[str, str] | None = None
+    response_format: dict[str, Any] | None = None
+    compression: dict[str, Any] | None = None
+    reasoning_effort: Literal["minimal", "low", "medium", "high"] | None = None
+    thinking: dict[str, int] | None = None
+    dry_run: bool | None = None
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> ChatRequest:
+        """Build from a request dict; keys without a field here are dropped."""
+        known = {f.name for f in fields(cls)} - {"model", "messages"}
+        return cls(
+            model=data["model"],
+            messages=[Message.from_dict(m) for m in data["messages"]],
+            **{k: v for k, v in data.items() if k in known},
+        )
+
+
+@dataclass
+class Usage(_Record):
+    input_tokens: int
+    output_tokens: int
+    #: Part of ``output_tokens``.
+    thinking_tokens: int = 0
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> Usage:
+        return cls(
+            input_tokens=data["input_tokens"],
+            output_tokens=data["output_tokens"],
+            thinking_tokens=data.get("thinking_tokens", 0),
+        )
+
+
+@dataclass
+class Choice(_Record):
+    index: int
+    message: Message
+    finish_reason: str | None = None
+    thinking: str | None = None
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> Choice:
+        return cls(
+            index=data["index"],
+            message=Message.from_dict(data["message"]),
+            finish_reason=data.get("finish_reason"),
+            thinking=data.get("thinking"),
+        )
+
+
+@dataclass
+class ChatResponse(_Record):
+    id: str
+    model: str
+    choices: list[Choice]
+    usage: Usage
+    metadata: dict[str, str] = field(default_factory=dict)
+    #: Report of where the request would have gone, for dry runs.
+    dry_run: dict[str, Any] | None = None
+
+    @property
+    def content(self) -> str:
+        """Content of the first choice, or ``""`` if there is none."""
+        return self.choices[0].message.content if self.choices else ""
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> ChatResponse:
+        return cls(
+            id=data["id"],
+            model=data["model"],
+            choices=[Choice.from_dict(c) for c in data["choices"]],
+            usage=Usage.from_dict(data["usage"]),
+            metadata=data.get("metadata") or {},
+            dry_run=data.get("dry_run"),
+        )
+
+
+@dataclass
+class ChatChunk(_Record):
+    id: str
+    model: str
+    #: Incremental text; empty on thinking chunks.
+    delta: str
+    thinking: str | None = None
+    finish_reason: str | None = None
+    #: Token counts, on the final chunk only.
+    usage: Usage | None = None
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> ChatChunk:
+        usage = data.get("usage")
+        return cls(
+            id=data["id"],
+            model=data["model"],
+            delta=data["delta"],
+            thinking=data.get("thinking"),
+            finish_reason=data.get("finish_reason"),
+            usage=Usage.from_dict(usage) if usage is not None else None,
+        )
+
+
+@dataclass
+class Route(_Record):
+    """Where a model name resolves to."""
+
+    model: str
+    provider: str
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> Route:
+        return cls(model=data["model"], provider=data["provider"])
+
+
+@dataclass
+class Quota(_Record):
+    max_requests_per_minute: int | None = None
+    max_tokens_per_minute: int | None = None
+    budget_cents: int | None = None
+    rpm_window: Literal["fixed", "sliding"] = "fixed"
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> Quota:
+        return cls(
+            max_requests_per_minute=data.get("max_requests_per_minute"),
+            max_tokens_per_minute=data.get("max_tokens_per_minute"),
+            budget_cents=data.get("budget_cents"),
+            rpm_window=data.get("rpm_window", "fixed"),
+        )
+
+
+@dataclass
+class KeyLimits(_Record):
+    """Limits in effect for a key, from the client's config and policies."""
+
+    team_id: str | None = None
+    quota: Quota | None = None
+    organization_id: str | None = None
+    organization_quota: Quota | None = None
+    #: Models the key may call; empty means any.
+    allowed_models: list[str] = field(default_factory=list)
+    budget_cents: int | None = None
+    #: Requests per minute the control plane has throttled the key to.
+    throttled_rpm: int | None = None
+    revoked: bool = False
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> KeyLimits:
+        quota = data.get("quota")
+        organization_quota = data.get("organization_quota")
+        return cls(
+            team_id=data.get("team_id"),
+            quota=Quota.from_dict(quota) if quota is not None else None,
+            organization_id=data.get("organization_id"),
+            organization_quota=(
+                Quota.from_dict(organization_quota) if organization_quota is not None else None
+            ),
+            allowed_models=data.get("allowed_models") or [],
+            budget_cents=data.get("budget_cents"),
+            throttled_rpm=data.get("throttled_rpm"),
+            revoked=data.get("revoked", False),
+        )
+
+
+@dataclass
+class CostEstimate(_Record):
+    model: str
+    input_tokens: int
+    max_output_tokens: int
+    input_cost_cents: float
+    max_output_cost_cents: float
+    #: Upper bound: the output side is priced at ``max_output_tokens``.
+    max_total_cost_cents: float
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> CostEstimate:
+        return cls(
+            model=data["model"],
+            input_tokens=data["input_tokens"],
+            max_output_tokens=data["max_output_tokens"],
+            input_cost_cents=data["input_cost_cents"],
+            max_output_cost_cents=data["max_output_cost_cents"],
+            max_total_cost_cents=data["max_total_cost_cents"],
+        )
+
+
+@dataclass
+class ShutdownReport(_Record):
+    """What closing a client got done before its timeout."""
+
+    #: Requests, streams included, still running at the deadline.
+    abandoned_requests: int
+    #: Whether buffered usage telemetry was written out in time.
+    telemetry_flushed: bool
+
+    @property
+    def is_clean(self) -> bool:
+        """Whether everything finished in time."""
+        return self.abandoned_requests == 0 and self.telemetry_flushed
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> ShutdownReport:
+        return cls(
+            abandoned_requests=data["abandoned_requests"],
+            telemetry_flushed=data["telemetry_flushed"],
+        )
+
+
+@dataclass
+class RequestEvent(_Record):
+    """A chat call about to be handled, as passed to ``on_request`` hooks."""
+
+    #: Id shared by the events of one call.
+    request_id: str
+    #: SHA-256 hex digest of the caller's key; the key itself is left out.
+    key_hash: str
+    request: ChatRequest
+    stream: bool = False
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> RequestEvent:
+        return cls(
+            request_id=data["request_id"],
+            key_hash=data["key_hash"],
+            request=ChatRequest.from_dict(data["request"]),
+            stream=data.get("stream", False),
+        )
+
+
+@dataclass
+class ResponseEvent(_Record):
+    """A chat call that succeeded, as passed to ``on_response`` hooks."""
+
+    request_id: str
+    key_hash: str
+    request: ChatRequest
+    response: ChatResponse
+    latency_ms: int
+    #: Cost at the configured prices; 0 for unpriced models.
+    cost_cents: float = 0.0
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> ResponseEvent:
+        return cls(
+            request_id=data["request_id"],
+            key_hash=data["key_hash"],
+            request=ChatRequest.from_dict(data["request"]),
+            response=ChatResponse.from_dict(data["response"]),
+            latency_ms=data["latency_ms"],
+            cost_cents=data.get("cost_cents", 0.0),
+        )
+
+
+@dataclass
+class ErrorEvent(_Record):
+    """A chat call that failed, as passed to ``on_error`` hooks."""
+
+    request_id: str
+    key_hash: str
+    request: ChatRequest
+    error: str
+    latency_ms: int
+    stream: bool = False
+
+    @classmethod
+    def from_dict(cls, data: dict[str, Any]) -> ErrorEvent:
+        return cls(
+            request_id=data["request_id"],
+            key_hash=data["key_hash"],
+            request=ChatRequest.from_dict(data["request"]),
+            error=data["error"],
+            latency_ms=data["latency_ms"],
+            stream=data.get("stream", False),
+        )
@@ -1,13 +1,20 @@
-use futures::StreamExt;
-use hyperinfer_client::HyperInferClient as RustClient;
-use hyperinfer_core::types::Quota;
-use hyperinfer_core::{ChatChunk, ChatResponse, Config, HyperInferError, RoutingRule};
+use futures::{Stream, StreamExt};
+use hyperinfer_client::{HyperInferClient as RustClient, TransportConfig};
+use hyperinfer_core::types::{Quota, RpmWindow};
+use hyperinfer_core::{
+    ChatChunk, ChatMessage, ChatResponse, Choice, Config, HyperInferError, MessageRole,
+    RoutingRule, Usage,
+};
 use pyo3::prelude::*;
 use pyo3::types::{PyDict, PyList};
 use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{mpsc, RwLock};
 
+use super::hooks::PyHooks;
 use super::registry_wrapper::ProviderRegistryWrapper;
 
 /// Convert a Python config `dict` (as returned by `Config.to_dict()`) into
@@ -18,7 +25,7 @@ use super::registry_wrapper::ProviderRegistryWrapper;
 /// {
 ///     "api_keys": {"openai": "sk-...", "anthropic": "sk-ant-..."},
 ///     "routing_rules": [{"name": "...", "priority": 1, "fallback_models": [...]}],
-///     "quotas": {"my-key": {"max_requests_per_minute": 60, ...}},
+///     "quotas": {"my-key": {"max_requests_per_minute": 60, "rpm_window": "sliding", ...}},
 ///     "model_aliases": {"my-gpt": "openai/gpt-4"},
 ///     "default_provider": "openai",   # or null
 /// }
@@ -77,12 +84,26 @@ fn parse_quotas(dict: &Bound<'_, PyDict>) -> PyResult<HashMap<String, Quota>> {
                 .and_then(|v| if v.is_none() { None } else { Some(v) })
                 .map(|v| v.extract())
                 .transpose()?;
+            let rpm_window = match q_inner.get_item("rpm_window")? {
+                Some(v) if !v.is_none() => match v.extract::<String>()?.as_str() {
+                    "fixed" => RpmWindow::Fixed,
+                    "sliding" => RpmWindow::Sliding,
+                    other => {
+                        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                            "Invalid rpm_window '{}': expected 'fixed' or 'sliding'",
+                            other
+                        )))
+                    }
+                },
+                _ => RpmWindow::Fixed,
+            };
             quotas.insert(
                 key,
                 Quota {
                     max_requests_per_minute,
                     max_tokens_per_minute,
                     budget_cents,
+                    rpm_window,
                 },
             );
         }
@@ -136,9 +157,99 @@ fn config_from_py(py: Python<'_>, obj: &Bound<'_, PyAny>) -> PyResult<Config> {
             None
         };
 
-    // Suppress unused-variable warning – `py` is required by the signature
-    // for lifetime reasons even when not explicitly called.
-    let _ = py;
+    // --- provider_headers ---
+    let provider_headers: HashMap<String, HashMap<String, String>> =
+        if let Some(val) = dict.get_item("provider_headers")? {
+            val.extract()?
+        } else {
+            HashMap::new()
+        };
+
+    // --- provider_base_urls ---
+    let provider_base_urls: HashMap<String, String> =
+        if let Some(val) = dict.get_item("provider_base_urls")? {
+            val.extract()?
+        } else {
+            HashMap::new()
+        };
+
+    // --- max_output_tokens ---
+    let max_output_tokens: HashMap<String, u32> =
+        if let Some(val) = dict.get_item("max_output_tokens")? {
+            val.extract()?
+        } else {
+            HashMap::new()
+        };
+
+    // --- hedging ---
+    let hedging: Option<hyperinfer_core::HedgingConfig> = match dict.get_item("hedging")? {
+        Some(val) if !val.is_none() => {
+            let hedging = val.cast::<PyDict>()?;
+            let after_ms: u64 = hedging
+                .get_item("after_ms")?
+                .ok_or_else(|| {
+                    pyo3::exceptions::PyValueError::new_err("hedging requires 'after_ms'")
+                })?
+                .extract()?;
+            let max_output_tokens: Option<u32> = match hedging.get_item("max_output_tokens")? {
+                Some(v) if !v.is_none() => Some(v.extract()?),
+                _ => None,
+            };
+            Some(hyperinfer_core::HedgingConfig {
+                after_ms,
+                max_output_tokens,
+            })
+        }
+        _ => None,
+    };
+
+    // --- context ---
+    let context: Option<hyperinfer_core::ContextConfig> = match dict.get_item("context")? {
+        Some(val) if !val.is_none() => {
+            let json: String = py
+                .import("json")?
+                .call_method1("dumps", (val,))?
+                .extract()?;
+            Some(serde_json::from_str(&json).map_err(|e| {
+                pyo3::exceptions::PyValueError::new_err(format!("invalid context config: {}", e))
+            })?)
+        }
+        _ => None,
+    };
+
+    // --- single_flight ---
+    let single_flight: Option<hyperinfer_core::SingleFlightConfig> =
+        match dict.get_item("single_flight")? {
+            Some(val) if !val.is_none() => {
+                let json: String = py
+                    .import("json")?
+                    .call_method1("dumps", (val,))?
+                    .extract()?;
+                Some(serde_json::from_str(&json).map_err(|e| {
+                    pyo3::exceptions::PyValueError::new_err(format!(
+                        "invalid single_flight config: {}",
+                        e
+                    ))
+                })?)
+            }
+            _ => None,
+        };
+
+    let provider_regions = match dict.get_item("provider_regions")? {
+        Some(val) if !val.is_none() => {
+            let json: String = py
+                .import("json")?
+                .call_method1("dumps", (val,))?
+                .extract()?;
+            serde_json::from_str(&json).map_err(|e| {
+                pyo3::exceptions::PyValueError::new_err(format!(
+                    "invalid provider_regions config: {}",
+                    e
+                ))
+            })?
+        }
+        _ => HashMap::new(),
+    };
 
     Ok(Config {
         api_keys,
@@ -146,9 +257,131 @@ fn config_from_py(py: Python<'_>, obj: &Bound<'_, PyAny>) -> PyResult<Config> {
         quotas,
         model_aliases,
         default_provider,
+        provider_headers,
+        provider_base_urls,
+        provider_regions,
+        max_output_tokens,
+        // Prices, virtual keys, team aliases, provider drains,
+        // organizations, feature flags, experiments and cascades are managed
+        // on the control plane and arrive with config sync.
+        model_prices: Vec::new(),
+        virtual_keys: HashMap::new(),
+        team_model_aliases: HashMap::new(),
+        providers: HashMap::new(),
+        team_organizations: HashMap::new(),
+        organization_quotas: HashMap::new(),
+        team_features: HashMap::new(),
+        default_features: HashMap::new(),
+        team_param_clamps: HashMap::new(),
+        team_output_transforms: HashMap::new(),
+        experiments: HashMap::new(),
+        cascades: HashMap::new(),
+        hedging,
+        context,
+        single_flight,
+        // Versions are stamped when the control plane publishes a config.
+        version: 0,
+        author: None,
+        team_data_regions: std::collections::HashMap::new(),
     })
 }
 
+/// Chunks buffered between the provider stream and Python by default.
+const DEFAULT_STREAM_BUFFER: usize = 32;
+
+/// Convert a chunk to a `hyperinfer.types.ChatChunk`.
+fn chunk_to_py(py: Python<'_>, chunk: &ChatChunk) -> PyResult<Py<PyAny>> {
+    let dict = PyDict::new(py);
+    dict.set_item("id", &chunk.id)?;
+    dict.set_item("model", &chunk.model)?;
+    dict.set_item("delta", &chunk.delta)?;
+    dict.set_item("thinking", &chunk.thinking)?;
+    dict.set_item("finish_reason", chunk.finish_reason.as_deref())?;
+    if let Some(u) = &chunk.usage {
+        let usage = PyDict::new(py);
+        usage.set_item("input_tokens", u.input_tokens)?;
+        usage.set_item("output_tokens", u.output_tokens)?;
+        usage.set_item("thinking_tokens", u.thinking_tokens)?;
+        dict.set_item("usage", usage)?;
+    } else {
+        dict.set_item("usage", py.None())?;
+    }
+    super::types::typed(py, "ChatChunk", dict.into_any())
+}
+
+/// Bridge `stream` to a channel holding up to `buffer` chunks.  While the
+/// channel is full the task stops polling the provider stream, so a slow
+/// consumer holds the provider back instead of chunks piling up in memory;
+/// the task waits without blocking a runtime thread.  It ends, dropping the
+/// provider stream, once the receiver is dropped.
+fn spawn_pump(
+    mut stream: Pin<Box<dyn Stream<Item = Result<ChatChunk, HyperInferError>> + Send>>,
+    buffer: usize,
+) -> mpsc::Receiver<Result<ChatChunk, String>> {
+    let (tx, rx) = mpsc::channel::<Result<ChatChunk, String>>(buffer);
+    tokio::spawn(async move {
+        while let Some(item) = stream.next().await {
+            if tx.send(item.map_err(|e| e.to_string())).await.is_err() {
+                // Receiver dropped — consumer stopped iterating.
+                break;
+            }
+        }
+        // tx is dropped here, closing the channel.
+    });
+    rx
+}
+
+/// The chunks of a stream folded into the response they add up to.
+#[derive(Default)]
+struct StreamedResponse {
+    id: String,
+    model: String,
+    content: String,
+    thinking: String,
+    finish_reason: Option<String>,
+    usage: Usage,
+}
+
+impl StreamedResponse {
+    fn add(&mut self, chunk: &ChatChunk) {
+        if self.id.is_empty() {
+            self.id = chunk.id.clone();
+        }
+        if self.model.is_empty() {
+            self.model = chunk.model.clone();
+        }
+        self.content.push_str(&chunk.delta);
+        if let Some(thinking) = &chunk.thinking {
+            self.thinking.push_str(thinking);
+        }
+        if chunk.finish_reason.is_some() {
+            self.finish_reason = chunk.finish_reason.clone();
+        }
+        if let Some(usage) = &chunk.usage {
+            self.usage = usage.clone();
+        }
+    }
+
+    fn finish(self) -> ChatResponse {
+        ChatResponse {
+            id: self.id,
+            model: self.model,
+            choices: vec![Choice {
+                index: 0,
+                message: ChatMessage {
+                    role: MessageRole::Assistant,
+                    content: self.content,
+                },
+                finish_reason: self.finish_reason,
+                thinking: (!self.thinking.is_empty()).then_some(self.thinking),
+            }],
+            usage: self.usage,
+            metadata: HashMap::new(),
+            dry_run: None,
+        }
+    }
+}
+
 /// Python async iterator backed by an `Arc<Mutex<mpsc::Receiver<…>>>`.
 #[pyclass]
 pub struct ChunkStream {
@@ -168,27 +401,7 @@ impl ChunkStream {
             let mut guard = rx.lock().await;
             match guard.recv().await {
                 Some(Ok(chunk)) => {
-                    // Convert the chunk to a Python dict.
-                    Python::try_attach(|py| {
-                        let dict = PyDict::new(py);
-                        dict.set_item("id", &chunk.id)?;
-                        dict.set_item("model", &chunk.model)?;
-                        dict.set_item("delta", &chunk.delta)?;
-                        dict.set_item(
-                            "finish_reason",
-                            chunk.finish_reason.as_deref().map(|s| s.to_string()),
-                        )?;
-                        if let Some(u) = &chunk.usage {
-                            let usage = PyDict::new(py);
-                            usage.set_item("input_tokens", u.input_tokens)?;
-                            usage.set_item("output_tokens", u.output_tokens)?;
-                            dict.set_item("usage", usage)?;
-                        } else {
-                            dict.set_item("usage", py.None())?;
-                        }
-                        Ok(dict.into_any().unbind())
-                    })
-                    .ok_or_else(|| {
+                    Python::try_attach(|py| chunk_to_py(py, &chunk)).ok_or_else(|| {
                         pyo3::exceptions::PyRuntimeError::new_err("Failed to attach to Python")
                     })?
                 }
@@ -200,6 +413,69 @@ impl ChunkStream {
     }
 }
 
+/// Provider connection settings from a `transport` dict, over those
+/// [`TransportConfig::from_env`] reads.  Durations are in seconds; `None`
+/// turns off the optional ones.
+fn transport_from_py(obj: &Bound<'_, PyAny>) -> PyResult<TransportConfig> {
+    let dict = obj.cast::<PyDict>()?;
+    let mut transport = TransportConfig::from_env()
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+    let secs = |value: &Bound<'_, PyAny>| -> PyResult<Duration> {
+        Duration::try_from_secs_f64(value.extract()?).map_err(|_| {
+            pyo3::exceptions::PyValueError::new_err(
+                "transport durations must be non-negative numbers of seconds",
+            )
+        })
+    };
+    let optional_secs = |value: &Bound<'_, PyAny>| -> PyResult<Option<Duration>> {
+        if value.is_none() {
+            Ok(None)
+        } else {
+            secs(value).map(Some)
+        }
+    };
+    for (key, value) in dict.iter() {
+        let key: String = key.extract()?;
+        match key.as_str() {
+            "timeout" => transport.timeout = secs(&value)?,
+            "connect_timeout" => transport.connect_timeout = secs(&value)?,
+            "pool_max_idle_per_host" => transport.pool_max_idle_per_host = value.extract()?,
+            "pool_idle_timeout" => transport.pool_idle_timeout = optional_secs(&value)?,
+            "tcp_keepalive" => transport.tcp_keepalive = optional_secs(&value)?,
+            "tcp_nodelay" => transport.tcp_nodelay = value.extract()?,
+            "http2_prior_knowledge" => transport.http2_prior_knowledge = value.extract()?,
+            "http2_keep_alive_interval" => {
+                transport.http2_keep_alive_interval = optional_secs(&value)?
+            }
+            "http2_keep_alive_timeout" => transport.http2_keep_alive_timeout = secs(&value)?,
+            "http2_keep_alive_while_idle" => {
+                transport.http2_keep_alive_while_idle = value.extract()?
+            }
+            "proxy_url" => transport.egress.proxy_url = value.extract()?,
+            "no_proxy" => transport.egress.no_proxy = value.extract()?,
+            "ca_bundle_paths" => {
+                let paths: Vec<String> = value.extract()?;
+                transport.egress.ca_bundle_paths = paths.into_iter().map(Into::into).collect();
+            }
+            "ca_bundle_only" => transport.egress.ca_bundle_only = value.extract()?,
+            "max_response_bytes" => transport.max_response_bytes = value.extract()?,
+            "oversized_response" => {
+                let policy: String = value.extract()?;
+                transport.oversized_response = policy
+                    .parse()
+                    .map_err(pyo3::exceptions::PyValueError::new_err)?;
+            }
+            other => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "Unknown transport setting: '{}'",
+                    other
+                )))
+            }
+        }
+    }
+    Ok(transport)
+}
+
 #[pyclass]
 pub struct HyperInferClient {
     inner: Arc<RwLock<Option<RustClient>>>,
@@ -207,13 +483,28 @@ pub struct HyperInferClient {
     /// Python config dict stored until `init()` is awaited.
     /// Wrapped in Arc<RwLock> so we can take() and drop the Py object after init.
     config_dict: Arc<RwLock<Option<Py<PyAny>>>>,
+    /// Set by `close()`; the config is gone by then, so the client cannot
+    /// be initialised again.
+    closed: Arc<AtomicBool>,
+    /// Callbacks registered with `on_request`, `on_response` and `on_error`.
+    hooks: Arc<PyHooks>,
+    /// Provider connection settings given to the constructor.
+    transport: Option<TransportConfig>,
 }
 
 async fn create_client(
     redis_url: &str,
     inner: &Arc<RwLock<Option<RustClient>>>,
     config_dict: &Arc<RwLock<Option<Py<PyAny>>>>,
+    closed: &AtomicBool,
+    hooks: &Arc<PyHooks>,
+    transport: Option<&TransportConfig>,
 ) -> Result<RustClient, PyErr> {
+    if closed.load(Ordering::SeqCst) {
+        return Err(pyo3::exceptions::PyRuntimeError::new_err(
+            "Client is closed. Create a new one.",
+        ));
+    }
     let inner_guard = inner.write().await;
     if inner_guard.is_some() {
         return Err(pyo3::exceptions::PyRuntimeError::new_err(
@@ -235,6 +526,7 @@ async fn create_client(
                     quotas: std::collections::HashMap::new(),
                     model_aliases: std::collections::HashMap::new(),
                     default_provider: None,
+                    ..Default::default()
                 })
             }
         }) {
@@ -248,11 +540,50 @@ async fn create_client(
         }
     };
 
-    let client = RustClient::new(redis_url, config)
+    let mut client = RustClient::new(redis_url, config)
         .await
         .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+    if let Some(transport) = transport {
+        client = client
+            .with_transport_config(transport)
+            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+    }
 
-    Ok(client)
+    Ok(client.with_hooks(hooks.clone()))
+}
+
+/// How long `close()` waits for running requests and telemetry by default.
+const DEFAULT_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Shut the client down within `timeout` and drop it, for `close()` and
+/// `__aexit__`.  Returns the `ShutdownReport`, or `None` if there was no
+/// client to close.
+async fn shut_down(
+    inner: &Arc<RwLock<Option<RustClient>>>,
+    config_dict: &Arc<RwLock<Option<Py<PyAny>>>>,
+    closed: &AtomicBool,
+    timeout: Duration,
+) -> PyResult<Py<PyAny>> {
+    closed.store(true, Ordering::SeqCst);
+    // Shut down under the read lock so requests holding it can finish;
+    // shutdown waits for them.
+    let report = match inner.read().await.as_ref() {
+        Some(client) => Some(client.shutdown(timeout).await),
+        None => None,
+    };
+    drop(inner.write().await.take());
+    drop(config_dict.write().await.take());
+
+    Python::try_attach(|py| match report {
+        Some(report) => {
+            let dict = PyDict::new(py);
+            dict.set_item("abandoned_requests", report.abandoned_requests)?;
+            dict.set_item("telemetry_flushed", report.telemetry_flushed)?;
+            super::types::typed(py, "ShutdownReport", dict.into_any())
+        }
+        None => Ok(py.None()),
+    })
+    .ok_or_else(|| pyo3::exceptions::PyRuntimeError::new_err("Failed to attach to Python"))?
 }
 
 async fn store_and_clear(
@@ -268,21 +599,45 @@ async fn store_and_clear(
     config_guard.take();
 }
 
+impl HyperInferClient {
+    fn add_hook(&self, event: &str, callback: Py<PyAny>) -> PyResult<Py<PyAny>> {
+        Python::attach(|py| {
+            if !callback.bind(py).is_callable() {
+                return Err(pyo3::exceptions::PyTypeError::new_err(
+                    "hook callback must be callable",
+                ));
+            }
+            self.hooks.add(event, callback.clone_ref(py))?;
+            Ok(callback)
+        })
+    }
+}
+
 #[pymethods]
 impl HyperInferClient {
     /// Create a new (uninitialised) client.
     ///
     /// `config` is an optional Python dict as returned by
     /// `hyperinfer.Config.to_dict()`.  When omitted an empty configuration
-    /// is used (useful for testing without real API keys).
+    /// is used (useful for testing without real API keys).  `transport`
+    /// optionally tunes the provider connection pool, sets the proxy and CA
+    /// bundles provider traffic goes through and caps response sizes; see
+    /// `transport_from_py`.
     #[new]
-    #[pyo3(signature = (redis_url, config=None))]
-    pub fn new(redis_url: String, config: Option<Py<PyAny>>) -> Self {
-        Self {
+    #[pyo3(signature = (redis_url, config=None, transport=None))]
+    pub fn new(
+        redis_url: String,
+        config: Option<Py<PyAny>>,
+        transport: Option<Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        Ok(Self {
             inner: Arc::new(RwLock::new(None)),
             redis_url,
             config_dict: Arc::new(RwLock::new(config)),
-        }
+            closed: Arc::new(AtomicBool::new(false)),
+            hooks: Arc::default(),
+            transport: transport.as_ref().map(transport_from_py).transpose()?,
+        })
     }
 
     /// Asynchronously initialise the underlying Rust client.
@@ -292,9 +647,20 @@ impl HyperInferClient {
         let redis_url = self.redis_url.clone();
         let inner = self.inner.clone();
         let config_dict = self.config_dict.clone();
+        let closed = self.closed.clone();
+        let hooks = self.hooks.clone();
+        let transport = self.transport.clone();
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let client = create_client(&redis_url, &inner, &config_dict).await?;
+            let client = create_client(
+                &redis_url,
+                &inner,
+                &config_dict,
+                &closed,
+                &hooks,
+                transport.as_ref(),
+            )
+            .await?;
             store_and_clear(client, &inner, &config_dict).await;
             Python::try_attach(|py| Ok(py.None())).ok_or_else(|| {
                 pyo3::exceptions::PyRuntimeError::new_err("Failed to attach to Python")
@@ -315,9 +681,20 @@ impl HyperInferClient {
         let inner = self.inner.clone();
         let config_dict = self.config_dict.clone();
         let registry = registry_wrapper.get_registry();
+        let closed = self.closed.clone();
+        let hooks = self.hooks.clone();
+        let transport = self.transport.clone();
 
         pyo3_async_runtimes::tokio::future_into_py(py, async move {
-            let client = create_client(&redis_url, &inner, &config_dict).await?;
+            let client = create_client(
+                &redis_url,
+                &inner,
+                &config_dict,
+                &closed,
+                &hooks,
+                transport.as_ref(),
+            )
+            .await?;
             client.inject_provider_registry(registry).await;
             store_and_clear(client, &inner, &config_dict).await;
             Python::try_attach(|py| Ok(py.None())).ok_or_else(|| {
@@ -326,6 +703,97 @@ impl HyperInferClient {
         })
     }
 
+    /// Stop taking requests, wait up to `timeout` seconds for running ones
+    /// and for buffered telemetry to be written, then drop the Rust client,
+    /// closing its Redis connections.
+    ///
+    /// Returns a `ShutdownReport`, or `None` if the client was never
+    /// initialised or is already closed.  A closed client cannot be
+    /// initialised again.
+    #[pyo3(signature = (timeout=DEFAULT_CLOSE_TIMEOUT.as_secs_f64()))]
+    pub fn close<'a>(&self, py: Python<'a>, timeout: f64) -> PyResult<Bound<'a, PyAny>> {
+        let timeout = Duration::try_from_secs_f64(timeout).map_err(|_| {
+            pyo3::exceptions::PyValueError::new_err("timeout must be a non-negative number")
+        })?;
+        let inner = self.inner.clone();
+        let config_dict = self.config_dict.clone();
+        let closed = self.closed.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            shut_down(&inner, &config_dict, &closed, timeout).await
+        })
+    }
+
+    /// `async with HyperInferClient(...) as client:` initialises the client
+    /// on entry, unless it already is.
+    fn __aenter__<'a>(slf: Py<Self>, py: Python<'a>) -> PyResult<Bound<'a, PyAny>> {
+        let (redis_url, inner, config_dict, closed, hooks, transport) = {
+            let this = slf.borrow(py);
+            (
+                this.redis_url.clone(),
+                this.inner.clone(),
+                this.config_dict.clone(),
+                this.closed.clone(),
+                this.hooks.clone(),
+                this.transport.clone(),
+            )
+        };
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            if inner.read().await.is_none() {
+                let client = create_client(
+                    &redis_url,
+                    &inner,
+                    &config_dict,
+                    &closed,
+                    &hooks,
+                    transport.as_ref(),
+                )
+                .await?;
+                store_and_clear(client, &inner, &config_dict).await;
+            }
+            Ok(slf)
+        })
+    }
+
+    /// Close the client on leaving an `async with` block, with the default
+    /// timeout.  Exceptions from the block are not suppressed.
+    #[pyo3(signature = (_exc_type=None, _exc_value=None, _traceback=None))]
+    fn __aexit__<'a>(
+        &self,
+        py: Python<'a>,
+        _exc_type: Option<Py<PyAny>>,
+        _exc_value: Option<Py<PyAny>>,
+        _traceback: Option<Py<PyAny>>,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let inner = self.inner.clone();
+        let config_dict = self.config_dict.clone();
+        let closed = self.closed.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            shut_down(&inner, &config_dict, &closed, DEFAULT_CLOSE_TIMEOUT).await?;
+            Ok(false)
+        })
+    }
+
+    /// Call `callback` with a `RequestEvent` before every chat call.
+    /// Returns `callback`, so it can be used as a decorator.
+    pub fn on_request(&self, callback: Py<PyAny>) -> PyResult<Py<PyAny>> {
+        self.add_hook("request", callback)
+    }
+
+    /// Call `callback` with a `ResponseEvent` after every successful chat
+    /// call.  Returns `callback`, so it can be used as a decorator.
+    pub fn on_response(&self, callback: Py<PyAny>) -> PyResult<Py<PyAny>> {
+        self.add_hook("response", callback)
+    }
+
+    /// Call `callback` with an `ErrorEvent` after every failed chat call.
+    /// Returns `callback`, so it can be used as a decorator.
+    pub fn on_error(&self, callback: Py<PyAny>) -> PyResult<Py<PyAny>> {
+        self.add_hook("error", callback)
+    }
+
     /// Configure traffic mirroring. Pass `None` to disable.
     #[pyo3(signature = (model=None, sample_rate=None))]
     pub fn set_mirror<'a>(
@@ -362,6 +830,94 @@ impl HyperInferClient {
         })
     }
 
+    /// Resolve `model` through aliases and routing rules to the model and
+    /// provider a request would be sent to, without sending anything.
+    pub fn resolve<'a>(&self, py: Python<'a>, model: String) -> PyResult<Bound<'a, PyAny>> {
+        let inner = self.inner.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let guard = inner.read().await;
+            let client = guard.as_ref().ok_or_else(|| {
+                pyo3::exceptions::PyRuntimeError::new_err(
+                    "Client not initialized. Call init() first.",
+                )
+            })?;
+
+            let route = client
+                .resolve(&model)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+
+            Python::try_attach(|py| {
+                let dict = PyDict::new(py);
+                dict.set_item("model", &route.model)?;
+                dict.set_item("provider", route.provider.to_string())?;
+                super::types::typed(py, "Route", dict.into_any())
+            })
+            .ok_or_else(|| {
+                pyo3::exceptions::PyRuntimeError::new_err("Failed to attach to Python")
+            })?
+        })
+    }
+
+    /// The limits configured for `key`, from the local config and
+    /// control-plane policies.
+    pub fn get_limits<'a>(&self, py: Python<'a>, key: String) -> PyResult<Bound<'a, PyAny>> {
+        let inner = self.inner.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let guard = inner.read().await;
+            let client = guard.as_ref().ok_or_else(|| {
+                pyo3::exceptions::PyRuntimeError::new_err(
+                    "Client not initialized. Call init() first.",
+                )
+            })?;
+
+            let limits = client
+                .limits(&key)
+                .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
+
+            Python::try_attach(|py| super::types::json_to_py(py, "KeyLimits", &limits)).ok_or_else(
+                || pyo3::exceptions::PyRuntimeError::new_err("Failed to attach to Python"),
+            )?
+        })
+    }
+
+    /// Estimate what `request` would cost, without sending it.
+    pub fn estimate_cost<'a>(
+        &self,
+        py: Python<'a>,
+        request: Py<PyAny>,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        let inner = self.inner.clone();
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let guard = inner.read().await;
+            let client = guard.as_ref().ok_or_else(|| {
+                pyo3::exceptions::PyRuntimeError::new_err(
+                    "Client not initialized. Call init() first.",
+                )
+            })?;
+
+            let request = Python::try_attach(|py| {
+                super::types::request_from_py(py, request)
+                    .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+            })
+            .ok_or_else(|| {
+                pyo3::exceptions::PyRuntimeError::new_err("Failed to attach to Python")
+            })??;
+
+            let estimate = client
+                .estimate_cost(&request)
+                .await
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+
+            Python::try_attach(|py| super::types::json_to_py(py, "CostEstimate", &estimate))
+                .ok_or_else(|| {
+                    pyo3::exceptions::PyRuntimeError::new_err("Failed to attach to Python")
+                })?
+        })
+    }
+
     #[pyo3(name = "chat")]
     pub fn chat<'a>(
         &self,
@@ -435,28 +991,13 @@ impl HyperInferClient {
             })??;
 
             // Obtain the stream from the Rust client.
-            let mut stream = client
+            let stream = client
                 .chat_stream(&key, chat_request)
                 .await
                 .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?;
 
             // Bridge the Rust stream to a Python async iterator via a channel.
-            // Buffer size of 32 keeps memory bounded while the consumer iterates.
-            let (tx, rx) = mpsc::channel::<Result<ChatChunk, String>>(32);
-
-            tokio::spawn(async move {
-                while let Some(item) = stream.next().await {
-                    let send_result = match item {
-                        Ok(chunk) => tx.send(Ok(chunk)).await,
-                        Err(e) => tx.send(Err(e.to_string())).await,
-                    };
-                    if send_result.is_err() {
-                        // Receiver dropped — consumer stopped iterating.
-                        break;
-                    }
-                }
-                // tx is dropped here, closing the channel and signalling StopAsyncIteration.
-            });
+            let rx = spawn_pump(stream, DEFAULT_STREAM_BUFFER);
 
             Python::try_attach(|py| {
                 let iter = ChunkStream {
@@ -469,4 +1010,99 @@ impl HyperInferClient {
             })?
         })
     }
+
+    /// Stream `request`, calling `on_token` with each `ChatChunk`, and
+    /// return the `ChatResponse` the chunks add up to.
+    ///
+    /// Up to `buffer` chunks wait while `on_token` runs; once that many are
+    /// waiting, the provider stream is paused until `on_token` catches up.
+    /// Plain functions are called on a blocking thread, so a slow one never
+    /// stalls the async runtime; coroutine functions are awaited on the
+    /// caller's event loop.  If `on_token` raises, the stream is dropped and
+    /// the error is raised here.
+    #[pyo3(
+        name = "chat_stream_callback",
+        signature = (key, request, on_token, buffer = DEFAULT_STREAM_BUFFER)
+    )]
+    pub fn chat_stream_callback<'a>(
+        &self,
+        py: Python<'a>,
+        key: String,
+        request: Py<PyAny>,
+        on_token: Py<PyAny>,
+        buffer: usize,
+    ) -> PyResult<Bound<'a, PyAny>> {
+        if buffer == 0 {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "buffer must be at least 1",
+            ));
+        }
+        if !on_token.bind(py).is_callable() {
+            return Err(pyo3::exceptions::PyTypeError::new_err(
+                "on_token must be callable",
+            ));
+        }
+        let locals = pyo3_async_runtimes::tokio::get_current_locals(py)?;
+        let inner = self.inner.clone();
+        let on_token = Arc::new(on_token);
+
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let stream = {
+                let guard = inner.read().await;
+                let client = guard.as_ref().ok_or_else(|| {
+                    pyo3::exceptions::PyRuntimeError::new_err(
+                        "Client not initialized. Call init() first.",
+                    )
+                })?;
+
+                let chat_request = Python::try_attach(|py| {
+                    super::types::request_from_py(py, request)
+                        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+                })
+                .ok_or_else(|| {
+                    pyo3::exceptions::PyRuntimeError::new_err("Failed to attach to Python")
+                })??;
+
+                client
+                    .chat_stream(&key, chat_request)
+                    .await
+                    .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))?
+            };
+
+            let mut rx = spawn_pump(stream, buffer);
+            let mut streamed = StreamedResponse::default();
+            while let Some(item) = rx.recv().await {
+                let chunk = item.map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+                streamed.add(&chunk);
+
+                let on_token = on_token.clone();
+                let locals = locals.clone();
+                let pending = tokio::task::spawn_blocking(move || {
+                    Python::try_attach(|py| {
+                        let result = on_token
+                            .call1(py, (chunk_to_py(py, &chunk)?,))?
+                            .into_bound(py);
+                        if result.hasattr("__await__")? {
+                            pyo3_async_runtimes::into_future_with_locals(&locals, result).map(Some)
+                        } else {
+                            Ok(None)
+                        }
+                    })
+                    .ok_or_else(|| {
+                        pyo3::exceptions::PyRuntimeError::new_err("Failed to attach to Python")
+                    })?
+                })
+                .await
+                .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))??;
+                if let Some(pending) = pending {
+                    pending.await?;
+                }
+            }
+
+            let response = streamed.finish();
+            Python::try_attach(|py| super::types::response_to_py(py, response)).ok_or_else(
+                || pyo3::exceptions::PyRuntimeError::new_err("Failed to attach to Python"),
+            )?
+        })
+    }
 }
@@ -0,0 +1,129 @@
+//! Python callbacks for the client's chat hooks.
+//!
+//! Callables registered with `on_request`, `on_response` and `on_error` are
+//! called with the event as a `hyperinfer.types` dataclass.  Coroutine
+//! functions are awaited before the call goes on; plain functions work too.
+//! A callback that raises is logged to the `hyperinfer` logger and does not
+//! fail the call.
+
+use async_trait::async_trait;
+use hyperinfer_client::{ChatHooks, ErrorEvent, RequestEvent, ResponseEvent};
+use pyo3::prelude::*;
+use serde::Serialize;
+use std::sync::Mutex;
+
+#[derive(Default)]
+pub struct PyHooks {
+    on_request: Mutex<Vec<Py<PyAny>>>,
+    on_response: Mutex<Vec<Py<PyAny>>>,
+    on_error: Mutex<Vec<Py<PyAny>>>,
+}
+
+impl PyHooks {
+    pub fn add(&self, event: &str, callback: Py<PyAny>) -> PyResult<()> {
+        let callbacks = match event {
+            "request" => &self.on_request,
+            "response" => &self.on_response,
+            "error" => &self.on_error,
+            other => {
+                return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                    "Unknown hook '{}': expected request, response or error",
+                    other
+                )))
+            }
+        };
+        callbacks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(callback);
+        Ok(())
+    }
+
+    /// Call every callback in `callbacks` with `event` as the dataclass
+    /// `class_name`, awaiting those that return awaitables.
+    async fn call<T: Serialize>(
+        &self,
+        hook: &str,
+        callbacks: &Mutex<Vec<Py<PyAny>>>,
+        class_name: &str,
+        event: &T,
+    ) {
+        let pending = Python::try_attach(|py| {
+            let callbacks: Vec<Py<PyAny>> = callbacks
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter()
+                .map(|callback| callback.clone_ref(py))
+                .collect();
+            if callbacks.is_empty() {
+                return Vec::new();
+            }
+            let payload = match super::types::json_to_py(py, class_name, event) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    log_failure(py, hook, &e);
+                    return Vec::new();
+                }
+            };
+            let mut pending = Vec::new();
+            for callback in callbacks {
+                let result = callback
+                    .call1(py, (payload.clone_ref(py),))
+                    .map(|result| result.into_bound(py))
+                    .and_then(|result| {
+                        if result.hasattr("__await__")? {
+                            pyo3_async_runtimes::tokio::into_future(result).map(Some)
+                        } else {
+                            Ok(None)
+                        }
+                    });
+                match result {
+                    Ok(Some(future)) => pending.push(future),
+                    Ok(None) => {}
+                    Err(e) => log_failure(py, hook, &e),
+                }
+            }
+            pending
+        })
+        .unwrap_or_default();
+
+        for future in pending {
+            if let Err(e) = future.await {
+                Python::try_attach(|py| log_failure(py, hook, &e));
+            }
+        }
+    }
+}
+
+fn log_failure(py: Python<'_>, hook: &str, error: &PyErr) {
+    let logged = py
+        .import("logging")
+        .and_then(|logging| logging.call_method1("getLogger", ("hyperinfer",)))
+        .and_then(|logger| {
+            logger.call_method1(
+                "warning",
+                ("%s callback failed: %s", hook, error.to_string()),
+            )
+        });
+    if logged.is_err() {
+        error.print(py);
+    }
+}
+
+#[async_trait]
+impl ChatHooks for PyHooks {
+    async fn on_request(&self, event: &RequestEvent) {
+        self.call("on_request", &self.on_request, "RequestEvent", event)
+            .await;
+    }
+
+    async fn on_response(&self, event: &ResponseEvent) {
+        self.call("on_response", &self.on_response, "ResponseEvent", event)
+            .await;
+    }
+
+    async fn on_error(&self, event: &ErrorEvent) {
+        self.call("on_error", &self.on_error, "ErrorEvent", event)
+            .await;
+    }
+}
@@ -4,6 +4,7 @@
 //! to Python environments.
 
 mod client;
+mod hooks;
 mod providers;
 mod registry_wrapper;
 mod types;
@@ -24,6 +25,12 @@ fn init_langfuse_telemetry(
         .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
 }
 
+#[pyfunction]
+fn init_observability(endpoint: &str) -> PyResult<()> {
+    hyperinfer_client::init_observability(endpoint)
+        .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(e.to_string()))
+}
+
 #[pyfunction]
 fn shutdown_telemetry() {
     hyperinfer_client::shutdown_telemetry();
@@ -35,6 +42,7 @@ fn _hyperinfer(_py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<ChunkStream>()?;
     m.add_class::<ProviderRegistryWrapper>()?;
     m.add_function(wrap_pyfunction!(init_langfuse_telemetry, m)?)?;
+    m.add_function(wrap_pyfunction!(init_observability, m)?)?;
     m.add_function(wrap_pyfunction!(shutdown_telemetry, m)?)?;
     m.add_function(wrap_pyfunction!(create_provider_registry, m)?)?;
     Ok(())
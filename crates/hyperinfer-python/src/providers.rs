@@ -57,6 +57,12 @@ impl PythonProvider {
             }
             dict.set_item("stop", stop_list)?;
         }
+        if let Some(format) = &request.response_format {
+            let json = serde_json::to_string(format)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(e.to_string()))?;
+            let format = py.import("json")?.call_method1("loads", (json,))?;
+            dict.set_item("response_format", format)?;
+        }
 
         let result = self.chat_callable.call1(py, (dict,))?;
 
@@ -124,6 +130,7 @@ impl PythonProvider {
                 index: idx as u32,
                 message: hyperinfer_core::ChatMessage { role, content },
                 finish_reason,
+                thinking: None,
             });
         }
 
@@ -149,6 +156,7 @@ impl PythonProvider {
                 Some(hyperinfer_core::Usage {
                     input_tokens,
                     output_tokens,
+                    thinking_tokens: 0,
                 })
             } else {
                 None
@@ -162,6 +170,8 @@ impl PythonProvider {
             model,
             choices,
             usage: usage.unwrap_or_default(),
+            metadata: std::collections::HashMap::new(),
+            dry_run: None,
         })
     }
 }
@@ -198,14 +208,15 @@ impl hyperinfer_providers::LlmProvider for PythonProvider {
             Python::attach(|py| provider_clone.chat(py, &request_clone))
         })
         .await
-        .map_err(|e| hyperinfer_core::HyperInferError::ApiError {
-            status: 500,
-            message: format!("Task panic: {}", e),
+        .map_err(|e| {
+            hyperinfer_core::HyperInferError::api_error(500, format!("Task panic: {}", e))
         })?;
 
-        result.map_err(|e| hyperinfer_core::HyperInferError::ApiError {
-            status: 500,
-            message: format!("Python provider error: {}", e),
+        result.map_err(|e| {
+            hyperinfer_core::HyperInferError::api_error(
+                500,
+                format!("Python provider error: {}", e),
+            )
         })
     }
 
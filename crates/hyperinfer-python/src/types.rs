@@ -1,12 +1,39 @@
 #![allow(dead_code)]
 #![allow(deprecated)]
 
-use hyperinfer_core::{ChatMessage, ChatRequest, ChatResponse, MessageRole};
+use hyperinfer_core::{
+    ChatMessage, ChatRequest, ChatResponse, CompressionOptions, MessageRole, ReasoningEffort,
+    ResponseFormat, ThinkingOptions,
+};
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
 use pyo3::IntoPyObjectExt;
 use pyo3::Py;
 
+/// `obj` as a dict: dicts as they are, and the dataclasses of
+/// `hyperinfer.types` through their `to_dict()`.
+fn as_dict<'py>(obj: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyDict>> {
+    if let Ok(dict) = obj.cast::<PyDict>() {
+        return Ok(dict.clone());
+    }
+    if obj.hasattr("to_dict")? {
+        return Ok(obj.call_method0("to_dict")?.cast_into::<PyDict>()?);
+    }
+    Err(pyo3::exceptions::PyTypeError::new_err(format!(
+        "expected a dict or a hyperinfer.types dataclass, got {}",
+        obj.get_type().name()?
+    )))
+}
+
+/// Build the `hyperinfer.types` dataclass `class_name` from `dict`.
+pub fn typed(py: Python<'_>, class_name: &str, dict: Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+    Ok(py
+        .import("hyperinfer.types")?
+        .getattr(class_name)?
+        .call_method1("from_dict", (dict,))?
+        .unbind())
+}
+
 pub fn message_from_py(dict: &Bound<'_, PyDict>) -> PyResult<ChatMessage> {
     let role: String = dict
         .get_item("role")?
@@ -34,7 +61,7 @@ pub fn message_from_py(dict: &Bound<'_, PyDict>) -> PyResult<ChatMessage> {
 }
 
 pub fn request_from_py(_py: Python<'_>, obj: Py<PyAny>) -> PyResult<ChatRequest> {
-    let dict = obj.downcast_bound::<PyDict>(_py)?;
+    let dict = as_dict(obj.bind(_py))?;
 
     let model: String = dict
         .get_item("model")?
@@ -48,8 +75,7 @@ pub fn request_from_py(_py: Python<'_>, obj: Py<PyAny>) -> PyResult<ChatRequest>
 
     let mut messages = Vec::new();
     for item in messages_list.iter() {
-        let msg_dict: Bound<'_, PyDict> = item.downcast_into()?;
-        messages.push(message_from_py(&msg_dict)?);
+        messages.push(message_from_py(&as_dict(&item)?)?);
     }
 
     let temperature: Option<f64> = dict
@@ -64,6 +90,81 @@ pub fn request_from_py(_py: Python<'_>, obj: Py<PyAny>) -> PyResult<ChatRequest>
         .get_item("stop")?
         .map(|v: Bound<'_, PyAny>| v.extract())
         .transpose()?;
+    let metadata: std::collections::HashMap<String, String> = dict
+        .get_item("metadata")?
+        .map(|v: Bound<'_, PyAny>| v.extract())
+        .transpose()?
+        .unwrap_or_default();
+    // The schema is arbitrary JSON, so go through `json` rather than
+    // extracting it field by field.
+    let response_format: Option<ResponseFormat> = match dict.get_item("response_format")? {
+        Some(v) if !v.is_none() => {
+            let json: String = _py.import("json")?.call_method1("dumps", (v,))?.extract()?;
+            Some(serde_json::from_str(&json).map_err(|e| {
+                pyo3::exceptions::PyValueError::new_err(format!("invalid response_format: {}", e))
+            })?)
+        }
+        _ => None,
+    };
+
+    let compression: Option<CompressionOptions> = match dict.get_item("compression")? {
+        Some(v) if !v.is_none() => {
+            let options: Bound<'_, PyDict> = v.downcast_into()?;
+            let ratio: f64 = options
+                .get_item("ratio")?
+                .ok_or_else(|| {
+                    pyo3::exceptions::PyValueError::new_err("compression requires 'ratio'")
+                })?
+                .extract()?;
+            let min_message_tokens: Option<u32> = options
+                .get_item("min_message_tokens")?
+                .map(|v: Bound<'_, PyAny>| v.extract())
+                .transpose()?;
+            Some(CompressionOptions {
+                ratio,
+                min_message_tokens: min_message_tokens
+                    .unwrap_or(CompressionOptions::DEFAULT_MIN_MESSAGE_TOKENS),
+            })
+        }
+        _ => None,
+    };
+
+    let reasoning_effort: Option<ReasoningEffort> = match dict.get_item("reasoning_effort")? {
+        Some(v) if !v.is_none() => {
+            let effort: String = v.extract()?;
+            Some(
+                serde_json::from_value(serde_json::Value::String(effort.clone())).map_err(
+                    |_| {
+                        pyo3::exceptions::PyValueError::new_err(format!(
+                            "invalid reasoning_effort '{}', expected minimal, low, medium or high",
+                            effort
+                        ))
+                    },
+                )?,
+            )
+        }
+        _ => None,
+    };
+
+    let thinking: Option<ThinkingOptions> = match dict.get_item("thinking")? {
+        Some(v) if !v.is_none() => {
+            let options: Bound<'_, PyDict> = v.downcast_into()?;
+            let budget_tokens: u32 = options
+                .get_item("budget_tokens")?
+                .ok_or_else(|| {
+                    pyo3::exceptions::PyValueError::new_err("thinking requires 'budget_tokens'")
+                })?
+                .extract()?;
+            Some(ThinkingOptions { budget_tokens })
+        }
+        _ => None,
+    };
+
+    let dry_run: bool = dict
+        .get_item("dry_run")?
+        .map(|v: Bound<'_, PyAny>| v.extract())
+        .transpose()?
+        .unwrap_or(false);
 
     Ok(ChatRequest {
         model,
@@ -72,9 +173,35 @@ pub fn request_from_py(_py: Python<'_>, obj: Py<PyAny>) -> PyResult<ChatRequest>
         max_tokens,
         stream: None,
         stop,
+        metadata,
+        response_format,
+        compression,
+        reasoning_effort,
+        thinking,
+        dry_run,
+        context_overflow: None,
+        routing: None,
+        data_region: None,
     })
 }
 
+/// Convert any serialisable value to the `hyperinfer.types` dataclass
+/// `class_name`, through `json`.
+pub fn json_to_py<T: serde::Serialize>(
+    py: Python<'_>,
+    class_name: &str,
+    value: &T,
+) -> PyResult<Py<PyAny>> {
+    let json = serde_json::to_string(value).map_err(|e| {
+        pyo3::exceptions::PyValueError::new_err(format!("serialisation failed: {}", e))
+    })?;
+    typed(
+        py,
+        class_name,
+        py.import("json")?.call_method1("loads", (json,))?,
+    )
+}
+
 fn message_role_to_py(py: Python<'_>, role: &MessageRole) -> PyResult<Py<PyAny>> {
     match role {
         MessageRole::System => Ok("system".into_py_any(py)?),
@@ -99,6 +226,7 @@ pub fn response_to_py(py: Python<'_>, response: ChatResponse) -> PyResult<Py<PyA
         choice_dict.set_item("message", msg_dict)?;
 
         choice_dict.set_item("finish_reason", &choice.finish_reason)?;
+        choice_dict.set_item("thinking", &choice.thinking)?;
         choices_list.append(choice_dict)?;
     }
     dict.set_item("choices", choices_list)?;
@@ -106,7 +234,18 @@ pub fn response_to_py(py: Python<'_>, response: ChatResponse) -> PyResult<Py<PyA
     let usage_dict = pyo3::types::PyDict::new(py);
     usage_dict.set_item("input_tokens", response.usage.input_tokens)?;
     usage_dict.set_item("output_tokens", response.usage.output_tokens)?;
+    usage_dict.set_item("thinking_tokens", response.usage.thinking_tokens)?;
     dict.set_item("usage", usage_dict)?;
+    dict.set_item("metadata", &response.metadata)?;
+    if let Some(report) = &response.dry_run {
+        let json = serde_json::to_string(report).map_err(|e| {
+            pyo3::exceptions::PyValueError::new_err(format!("invalid dry-run report: {}", e))
+        })?;
+        dict.set_item(
+            "dry_run",
+            py.import("json")?.call_method1("loads", (json,))?,
+        )?;
+    }
 
-    Ok(dict.into())
+    typed(py, "ChatResponse", dict.into_any())
 }
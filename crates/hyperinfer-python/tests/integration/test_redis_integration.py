@@ -1,7 +1,7 @@
 """End-to-end integration tests for the HyperInfer Python SDK using a real Redis instance via Testcontainers."""
 
 import pytest
-from hyperinfer import Client, Config
+from hyperinfer import Client, Config, HyperInferClient
 from testcontainers.redis import RedisContainer
 
 
@@ -53,6 +53,34 @@ async def test_client_context_manager(redis_url):
     assert not client._initialized
 
 
+@pytest.mark.asyncio
+@pytest.mark.integration
+async def test_native_client_context_manager_closes_on_exit(redis_url):
+    """The native client initialises on entry and shuts down on exit."""
+    config = Config().with_api_key("openai", "dummy-key").with_alias("fast", "gpt-4o-mini")
+
+    async with HyperInferClient(redis_url, config.to_dict()) as client:
+        route = await client.resolve("fast")
+        assert route.model == "gpt-4o-mini"
+
+    # Exiting closed it: a second close has nothing left to do, and a
+    # closed client refuses to start again.
+    assert await client.close() is None
+    with pytest.raises(RuntimeError, match="closed"):
+        await client.init()
+
+
+@pytest.mark.asyncio
+@pytest.mark.integration
+async def test_close_reports_a_clean_shutdown(redis_url):
+    """Closing an idle client flushes telemetry within the timeout."""
+    client = Client(redis_url=redis_url, config=Config().with_api_key("openai", "dummy-key"))
+    await client.init()
+    report = await client.close(timeout=2.0)
+    assert report is not None and report.is_clean
+    assert await client.close() is None
+
+
 @pytest.mark.asyncio
 @pytest.mark.integration
 async def test_chat_without_api_key_fails(redis_url):
@@ -88,3 +116,46 @@ async def test_stream_without_api_key_fails(redis_url):
             )
             # The async generator evaluates lazily, so we need to request the first chunk
             await chunk_gen.__anext__()
+
+
+@pytest.mark.asyncio
+@pytest.mark.integration
+async def test_hooks_hear_about_a_failed_chat(redis_url):
+    """Test that request and error hooks are called, sync or async."""
+    requests, errors = [], []
+    client = Client(redis_url=redis_url, config=Config())
+    client.on_request(requests.append)
+
+    @client.on_error
+    async def record_error(event):
+        errors.append(event)
+
+    async with client:
+        with pytest.raises(Exception):
+            await client.chat(
+                key="test-user", model="gpt-4", messages=[{"role": "user", "content": "Hello"}]
+            )
+
+    assert [event.request.model for event in requests] == ["gpt-4"]
+    assert len(errors) == 1
+    assert errors[0].request_id == requests[0].request_id
+    assert errors[0].error
+
+
+@pytest.mark.asyncio
+@pytest.mark.integration
+async def test_chat_with_on_token_without_api_key_fails(redis_url):
+    """Test that callback streaming fails cleanly before calling on_token."""
+    tokens = []
+
+    async with Client(redis_url=redis_url, config=Config()) as client:
+        with pytest.raises(RuntimeError):
+            await client.chat(
+                key="test-user",
+                model="gpt-4",
+                messages=[{"role": "user", "content": "Hello"}],
+                on_token=tokens.append,
+                stream_buffer=4,
+            )
+
+    assert tokens == []
@@ -0,0 +1,51 @@
+"""Tests for the framework re-exports and the sync bridge they share."""
+
+import asyncio
+
+import pytest
+from hyperinfer import iter_sync, run_sync
+
+
+def test_sync_helpers_work_inside_a_running_loop():
+    async def double(x):
+        return 2 * x
+
+    async def items():
+        for x in range(3):
+            yield x
+
+    async def main():
+        # A blocking call from async code must not deadlock on its own loop.
+        return run_sync(double(21)), list(iter_sync(items()))
+
+    assert asyncio.run(main()) == (42, [0, 1, 2])
+
+
+def test_iter_sync_closes_the_stream_when_stopped_early():
+    closed = []
+
+    async def items():
+        try:
+            for x in range(10):
+                yield x
+        finally:
+            closed.append(True)
+
+    chunks = iter_sync(items())
+    assert next(chunks) == 0
+    chunks.close()
+    assert closed == [True]
+
+
+def test_langchain_module_re_exports_the_binding():
+    binding = pytest.importorskip("hyperinfer_langchain")
+    from hyperinfer.langchain import ChatHyperInfer
+
+    assert ChatHyperInfer is binding.HyperInferChatModel
+
+
+def test_llama_index_module_re_exports_the_binding():
+    binding = pytest.importorskip("hyperinfer_llamaindex")
+    from hyperinfer.llama_index import HyperInferLLM
+
+    assert HyperInferLLM is binding.HyperInferLLM
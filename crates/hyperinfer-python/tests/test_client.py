@@ -1,5 +1,7 @@
 """Tests for Config class."""
 
+import pytest
+
 from hyperinfer.config import Config
 
 
@@ -85,8 +87,26 @@ class TestConfig:
         assert quota["max_requests_per_minute"] == 60
         assert quota["max_tokens_per_minute"] == 100000
         assert quota["budget_cents"] == 1000
+        assert quota["rpm_window"] == "fixed"
         assert result is config
 
+    def test_with_quota_sliding_window(self):
+        """Test selecting the sliding-window RPM algorithm."""
+        config = Config()
+        config.with_quota("default", rpm=60, rpm_window="sliding")
+
+        assert config._quotas["default"]["rpm_window"] == "sliding"
+
+    def test_with_single_flight(self):
+        """Test enabling request coalescing with a team override."""
+        config = Config().with_single_flight(teams={"team-a": False})
+
+        assert config.to_dict()["single_flight"] == {
+            "enabled": True,
+            "teams": {"team-a": False},
+        }
+        assert "single_flight" not in Config().to_dict()
+
     def test_with_quota_partial_params(self):
         """Test adding quota with partial parameters."""
         config = Config()
@@ -115,6 +135,63 @@ class TestConfig:
         assert config._default_provider == "openai"
         assert result is config
 
+    def test_with_provider_header(self):
+        """Test attaching custom headers to a provider."""
+        config = Config()
+        result = config.with_provider_header("openai", "OpenAI-Organization", "org-123")
+        config.with_provider_header("openai", "OpenAI-Project", "proj_abc")
+
+        assert config._provider_headers == {
+            "openai": {"OpenAI-Organization": "org-123", "OpenAI-Project": "proj_abc"}
+        }
+        assert result is config
+
+    def test_with_provider_base_url(self):
+        """Test pointing a provider at another endpoint."""
+        config = Config()
+        result = config.with_provider_base_url("openai", "http://localhost:8080")
+
+        assert config.to_dict()["provider_base_urls"] == {"openai": "http://localhost:8080"}
+        assert result is config
+
+    def test_with_max_output_tokens(self):
+        """Test setting a per-model default output budget."""
+        config = Config()
+        result = config.with_max_output_tokens("claude-sonnet-4-5", 16000)
+
+        assert config._max_output_tokens == {"claude-sonnet-4-5": 16000}
+        assert result is config
+
+    def test_with_hedging(self):
+        """Test enabling hedged requests."""
+        config = Config()
+        result = config.with_hedging(750, max_output_tokens=1024)
+
+        assert config.to_dict()["hedging"] == {"after_ms": 750, "max_output_tokens": 1024}
+        assert result is config
+
+    def test_with_context(self):
+        """Test enabling context-window trimming."""
+        config = Config()
+        result = config.with_context("sliding_window", max_messages=20)
+
+        assert config.to_dict()["context"] == {
+            "strategy": {"type": "sliding_window", "max_messages": 20},
+            "context_windows": {},
+        }
+        assert result is config
+
+        config.with_context(
+            "summarize", summary_model="gpt-4o-mini", context_windows={"my-model": 32000}
+        )
+        assert config.to_dict()["context"] == {
+            "strategy": {"type": "summarize", "model": "gpt-4o-mini"},
+            "context_windows": {"my-model": 32000},
+        }
+
+        with pytest.raises(ValueError):
+            config.with_context("sliding_window")
+
     def test_to_dict_empty(self):
         """Test to_dict with empty config."""
         config = Config()
@@ -126,6 +203,8 @@ class TestConfig:
             "quotas": {},
             "model_aliases": {},
             "default_provider": None,
+            "provider_headers": {},
+            "max_output_tokens": {},
         }
 
     def test_to_dict_with_data(self):
@@ -0,0 +1,124 @@
+"""Tests for the typed request and response dataclasses and the native stubs."""
+
+import ast
+import inspect
+from pathlib import Path
+
+import pytest
+from hyperinfer.types import (
+    ChatChunk,
+    ChatRequest,
+    ChatResponse,
+    KeyLimits,
+    Message,
+    ResponseEvent,
+)
+
+STUBS = Path(__file__).parent.parent / "python" / "hyperinfer" / "_hyperinfer.pyi"
+
+
+def test_response_reads_like_the_dict_it_replaces():
+    response = ChatResponse.from_dict(
+        {
+            "id": "chatcmpl-1",
+            "model": "gpt-4",
+            "choices": [
+                {
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Paris"},
+                    "finish_reason": "stop",
+                    "thinking": None,
+                }
+            ],
+            "usage": {"input_tokens": 12, "output_tokens": 3, "thinking_tokens": 0},
+            "metadata": {},
+        }
+    )
+    assert response.content == "Paris"
+    assert response.choices[0].message.role == "assistant"
+    assert response["choices"][0]["message"]["content"] == "Paris"
+    assert response["usage"]["output_tokens"] == 3
+    assert response.get("missing") is None
+    with pytest.raises(KeyError):
+        response["missing"]
+
+
+def test_request_to_dict_drops_unset_fields():
+    request = ChatRequest(model="gpt-4", messages=[Message("user", "hi")], max_tokens=16)
+    assert request.to_dict() == {
+        "model": "gpt-4",
+        "messages": [{"role": "user", "content": "hi"}],
+        "max_tokens": 16,
+    }
+
+
+
+def test_hook_event_carries_typed_request_and_response():
+    event = ResponseEvent.from_dict(
+        {
+            "request_id": "r-1",
+            "key_hash": "ab12",
+            "request": {
+                "model": "gpt-4",
+                "messages": [{"role": "user", "content": "hi"}],
+                "temperature": None,
+                "max_tokens": 16,
+                "stream": False,
+            },
+            "response": {
+                "id": "chatcmpl-1",
+                "model": "gpt-4",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": "hello"}}],
+                "usage": {"input_tokens": 3, "output_tokens": 4},
+            },
+            "latency_ms": 12,
+            "cost_cents": 0.5,
+        }
+    )
+    assert event.request.messages[0] == Message("user", "hi")
+    assert event.request.max_tokens == 16
+    assert event.response.content == "hello"
+    assert event["cost_cents"] == 0.5
+
+def test_nested_values_become_dataclasses():
+    chunk = ChatChunk.from_dict(
+        {
+            "id": "c",
+            "model": "gpt-4",
+            "delta": "",
+            "finish_reason": "stop",
+            "usage": {"input_tokens": 1, "output_tokens": 2},
+        }
+    )
+    assert chunk.usage is not None and chunk.usage.thinking_tokens == 0
+
+    limits = KeyLimits.from_dict({"quota": {"max_requests_per_minute": 60}, "revoked": False})
+    assert limits.quota is not None and limits.quota.rpm_window == "fixed"
+    assert limits.allowed_models == []
+
+
+def test_stubs_cover_the_native_module():
+    native = pytest.importorskip("hyperinfer._hyperinfer")
+    tree = ast.parse(STUBS.read_text())
+    stubbed = {
+        node.name: {
+            item.name
+            for item in node.body
+            if isinstance(item, (ast.FunctionDef, ast.AsyncFunctionDef))
+        }
+        for node in tree.body
+        if isinstance(node, ast.ClassDef)
+    }
+    functions = {
+        node.name for node in tree.body if isinstance(node, ast.FunctionDef)
+    }
+
+    for name, value in vars(native).items():
+        if name.startswith("_"):
+            continue
+        if inspect.isclass(value):
+            assert name in stubbed, f"class {name} has no stub"
+            methods = {m for m in vars(value) if not m.startswith("_")}
+            assert methods <= stubbed[name], f"{name} stubs miss {methods - stubbed[name]}"
+        elif callable(value):
+            assert name in functions, f"function {name} has no stub"
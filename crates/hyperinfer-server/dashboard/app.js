@@ -0,0 +1,83 @@
+// Polls the overview summary with the token the user entered.  The token
+// lives in sessionStorage, so it is forgotten when the tab closes.
+
+const TOKEN_KEY = "hyperinfer.token";
+const REFRESH_MS = 30000;
+
+const $ = (id) => document.getElementById(id);
+
+const number = new Intl.NumberFormat();
+const dollars = (cents) =>
+  new Intl.NumberFormat(undefined, { style: "currency", currency: "USD" }).format(cents / 100);
+const percent = (fraction) => `${(fraction * 100).toFixed(2)}%`;
+
+function setStatus(message) {
+  $("status").textContent = message;
+}
+
+function render(overview) {
+  $("requests").textContent = number.format(overview.requests + overview.errors);
+  $("spend").textContent = dollars(overview.spend_cents);
+  $("error-rate").textContent = percent(overview.error_rate);
+  $("tokens").textContent =
+    `${number.format(overview.input_tokens)} / ${number.format(overview.output_tokens)}`;
+  $("since").textContent = new Date(overview.since).toISOString().slice(0, 16).replace("T", " ");
+
+  const rows = $("top-models");
+  rows.replaceChildren(
+    ...overview.top_models.map((m) => {
+      const row = document.createElement("tr");
+      for (const cell of [
+        m.model,
+        number.format(m.requests),
+        number.format(m.input_tokens),
+        number.format(m.output_tokens),
+        dollars(m.spend_cents),
+      ]) {
+        const td = document.createElement("td");
+        td.textContent = cell;
+        row.appendChild(td);
+      }
+      return row;
+    }),
+  );
+}
+
+async function refresh() {
+  const token = sessionStorage.getItem(TOKEN_KEY);
+  if (!token) {
+    setStatus("Enter a token to load the summary.");
+    return;
+  }
+  try {
+    const response = await fetch("/v1/summary/overview", {
+      headers: { Authorization: `Bearer ${token}` },
+    });
+    if (response.status === 401) {
+      sessionStorage.removeItem(TOKEN_KEY);
+      setStatus("The token was rejected.");
+      return;
+    }
+    if (!response.ok) {
+      setStatus(`Failed to load the summary: ${response.status} ${await response.text()}`);
+      return;
+    }
+    render(await response.json());
+    setStatus("");
+  } catch (e) {
+    setStatus(`Failed to load the summary: ${e}`);
+  }
+}
+
+$("token-form").addEventListener("submit", (event) => {
+  event.preventDefault();
+  const token = $("token").value.trim();
+  if (token) {
+    sessionStorage.setItem(TOKEN_KEY, token);
+    $("token").value = "";
+    refresh();
+  }
+});
+
+refresh();
+setInterval(refresh, REFRESH_MS);
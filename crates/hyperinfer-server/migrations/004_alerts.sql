@@ -0,0 +1,50 @@
+-- Alerting: failed-request log, alert rules and fired-alert history
+
+CREATE TABLE request_errors (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    api_key_id UUID NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    model VARCHAR(255) NOT NULL,
+    provider VARCHAR(50),
+    error TEXT NOT NULL,
+    recorded_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_request_errors_team_recorded ON request_errors(team_id, recorded_at);
+CREATE INDEX idx_request_errors_provider_recorded ON request_errors(provider, recorded_at);
+
+CREATE TABLE alert_rules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    team_id UUID REFERENCES teams(id) ON DELETE CASCADE,
+    kind VARCHAR(50) NOT NULL,
+    threshold DOUBLE PRECISION NOT NULL,
+    provider VARCHAR(50),
+    window_minutes INTEGER NOT NULL DEFAULT 15,
+    webhook_url TEXT NOT NULL,
+    webhook_format VARCHAR(20) NOT NULL DEFAULT 'json',
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    CONSTRAINT alert_rules_kind_valid CHECK (kind IN ('budget', 'error_rate', 'provider_down')),
+    CONSTRAINT alert_rules_format_valid CHECK (webhook_format IN ('json', 'slack')),
+    CONSTRAINT alert_rules_threshold_positive CHECK (threshold > 0),
+    CONSTRAINT alert_rules_window_positive CHECK (window_minutes > 0),
+    CONSTRAINT alert_rules_team_scoped CHECK (kind = 'provider_down' OR team_id IS NOT NULL),
+    CONSTRAINT alert_rules_provider_set CHECK (kind <> 'provider_down' OR provider IS NOT NULL)
+);
+
+CREATE TABLE alerts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    rule_id UUID NOT NULL REFERENCES alert_rules(id) ON DELETE CASCADE,
+    team_id UUID REFERENCES teams(id) ON DELETE CASCADE,
+    kind VARCHAR(50) NOT NULL,
+    message TEXT NOT NULL,
+    observed_value DOUBLE PRECISION NOT NULL,
+    threshold DOUBLE PRECISION NOT NULL,
+    delivered BOOLEAN NOT NULL DEFAULT false,
+    fired_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_alerts_team_fired ON alerts(team_id, fired_at);
+-- At most one open alert per rule
+CREATE UNIQUE INDEX idx_alerts_open_rule ON alerts(rule_id) WHERE resolved_at IS NULL;
@@ -0,0 +1,14 @@
+-- Budget auto-actions: what to do with a team's keys once its budget is spent
+
+CREATE TABLE budget_policies (
+    team_id UUID PRIMARY KEY REFERENCES teams(id) ON DELETE CASCADE,
+    action VARCHAR(20) NOT NULL DEFAULT 'warn',
+    throttle_rpm INTEGER,
+    enforced_action VARCHAR(20),
+    enforced_at TIMESTAMP WITH TIME ZONE,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CONSTRAINT budget_policies_action_valid CHECK (action IN ('warn', 'throttle', 'block')),
+    CONSTRAINT budget_policies_throttle_rpm CHECK (
+        action <> 'throttle' OR (throttle_rpm IS NOT NULL AND throttle_rpm > 0)
+    )
+);
@@ -0,0 +1,21 @@
+-- Billing periods: per-team anchor day / timezone and closed-period snapshots
+
+ALTER TABLE teams
+    ADD COLUMN billing_anchor_day INTEGER NOT NULL DEFAULT 1,
+    ADD COLUMN billing_timezone VARCHAR(64) NOT NULL DEFAULT 'UTC',
+    ADD CONSTRAINT teams_billing_anchor_day_valid CHECK (billing_anchor_day BETWEEN 1 AND 28);
+
+CREATE TABLE billing_periods (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    period_start TIMESTAMP WITH TIME ZONE NOT NULL,
+    period_end TIMESTAMP WITH TIME ZONE NOT NULL,
+    requests BIGINT NOT NULL DEFAULT 0,
+    input_tokens BIGINT NOT NULL DEFAULT 0,
+    output_tokens BIGINT NOT NULL DEFAULT 0,
+    spend_cents DOUBLE PRECISION NOT NULL DEFAULT 0,
+    budget_cents BIGINT NOT NULL DEFAULT 0,
+    closed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CONSTRAINT billing_periods_unique_start UNIQUE (team_id, period_start),
+    CONSTRAINT billing_periods_ordered CHECK (period_end > period_start)
+);
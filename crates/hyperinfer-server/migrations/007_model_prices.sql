@@ -0,0 +1,15 @@
+-- Model prices managed through the control plane (USD per 1M tokens)
+
+CREATE TABLE model_prices (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    model VARCHAR(255) NOT NULL,
+    provider VARCHAR(50) NOT NULL,
+    input_per_mtok DOUBLE PRECISION NOT NULL,
+    output_per_mtok DOUBLE PRECISION NOT NULL,
+    effective_from TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    CONSTRAINT model_prices_unique UNIQUE (model, provider, effective_from),
+    CONSTRAINT model_prices_non_negative CHECK (input_per_mtok >= 0 AND output_per_mtok >= 0)
+);
+
+CREATE INDEX idx_model_prices_model ON model_prices(model, effective_from);
@@ -0,0 +1,7 @@
+-- Virtual keys: tags, allowed models and budget share on API keys
+
+ALTER TABLE api_keys
+    ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN allowed_models TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN budget_cents BIGINT,
+    ADD CONSTRAINT api_keys_budget_cents_non_negative CHECK (budget_cents IS NULL OR budget_cents >= 0);
@@ -0,0 +1,5 @@
+-- Request metadata tags on usage logs for cost attribution
+
+ALTER TABLE usage_logs ADD COLUMN metadata JSONB NOT NULL DEFAULT '{}';
+
+CREATE INDEX idx_usage_logs_metadata ON usage_logs USING GIN (metadata);
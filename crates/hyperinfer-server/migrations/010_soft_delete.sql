@@ -0,0 +1,13 @@
+-- Soft delete for teams and users, so usage history keeps its foreign keys
+
+ALTER TABLE teams ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
+
+-- Names and emails of deleted rows can be reused.
+ALTER TABLE teams DROP CONSTRAINT teams_name_key;
+CREATE UNIQUE INDEX teams_name_active ON teams(name) WHERE deleted_at IS NULL;
+ALTER TABLE users DROP CONSTRAINT users_email_key;
+CREATE UNIQUE INDEX users_email_active ON users(email) WHERE deleted_at IS NULL;
+
+CREATE INDEX idx_teams_deleted_at ON teams(deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX idx_users_deleted_at ON users(deleted_at) WHERE deleted_at IS NOT NULL;
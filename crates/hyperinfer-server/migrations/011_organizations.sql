@@ -0,0 +1,24 @@
+-- Organizations group teams that share one contract: an org-level budget and
+-- rate limits apply on top of each member team's own.
+
+CREATE TABLE organizations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(255) NOT NULL UNIQUE,
+    budget_cents BIGINT NOT NULL DEFAULT 0,
+    rpm_limit INTEGER,
+    tpm_limit INTEGER,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CONSTRAINT organizations_budget_positive CHECK (budget_cents >= 0),
+    CONSTRAINT organizations_rpm_positive CHECK (rpm_limit IS NULL OR rpm_limit > 0),
+    CONSTRAINT organizations_tpm_positive CHECK (tpm_limit IS NULL OR tpm_limit > 0)
+);
+
+CREATE TRIGGER update_organizations_updated_at
+    BEFORE UPDATE ON organizations
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
+
+ALTER TABLE teams ADD COLUMN organization_id UUID REFERENCES organizations(id) ON DELETE SET NULL;
+
+CREATE INDEX idx_teams_organization_id ON teams(organization_id);
@@ -0,0 +1,20 @@
+-- Roles were free-form strings; restrict them to the roles the control plane
+-- enforces and keep an audit trail of every change.
+
+UPDATE users SET role = 'member'
+    WHERE role IS NULL OR role NOT IN ('owner', 'admin', 'member', 'viewer');
+
+ALTER TABLE users
+    ALTER COLUMN role SET NOT NULL,
+    ADD CONSTRAINT users_role_valid CHECK (role IN ('owner', 'admin', 'member', 'viewer'));
+
+CREATE TABLE role_changes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    old_role VARCHAR(50) NOT NULL,
+    new_role VARCHAR(50) NOT NULL,
+    changed_by VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_role_changes_user_id ON role_changes(user_id, created_at DESC);
@@ -0,0 +1,5 @@
+-- When each API key was last used, for finding stale keys
+
+ALTER TABLE api_keys ADD COLUMN last_used_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_api_keys_last_used ON api_keys (COALESCE(last_used_at, created_at)) WHERE is_active = true;
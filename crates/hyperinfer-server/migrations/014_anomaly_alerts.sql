@@ -0,0 +1,5 @@
+-- Anomaly alert rules: per-key usage compared against the key's own history
+
+ALTER TABLE alert_rules DROP CONSTRAINT alert_rules_kind_valid;
+ALTER TABLE alert_rules ADD CONSTRAINT alert_rules_kind_valid
+    CHECK (kind IN ('budget', 'error_rate', 'provider_down', 'anomaly'));
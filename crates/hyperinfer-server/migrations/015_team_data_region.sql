@@ -0,0 +1,4 @@
+-- Data region a team's traffic is pinned to for residency requirements
+-- (e.g. 'eu'); NULL for teams without one.
+
+ALTER TABLE teams ADD COLUMN data_region VARCHAR(64);
@@ -0,0 +1,13 @@
+-- Scheduled usage reports emailed to a team
+
+CREATE TABLE report_subscriptions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    frequency VARCHAR(20) NOT NULL,
+    recipients TEXT[] NOT NULL,
+    last_period_end TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CONSTRAINT report_subscriptions_frequency_valid CHECK (frequency IN ('weekly', 'monthly'))
+);
+
+CREATE INDEX idx_report_subscriptions_team ON report_subscriptions(team_id);
@@ -0,0 +1,17 @@
+-- Quota templates: named presets applied to many teams at once
+
+CREATE TABLE quota_templates (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(100) NOT NULL UNIQUE,
+    rpm_limit INTEGER NOT NULL,
+    tpm_limit INTEGER NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    CONSTRAINT quota_templates_rpm_positive CHECK (rpm_limit > 0),
+    CONSTRAINT quota_templates_tpm_positive CHECK (tpm_limit > 0)
+);
+
+CREATE TRIGGER update_quota_templates_updated_at
+    BEFORE UPDATE ON quota_templates
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at_column();
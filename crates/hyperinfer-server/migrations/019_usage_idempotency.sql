@@ -0,0 +1,8 @@
+-- Stream entry ids of consumed usage, so redelivered entries are recorded once
+
+ALTER TABLE usage_logs ADD COLUMN stream_entry_id TEXT;
+ALTER TABLE request_errors ADD COLUMN stream_entry_id TEXT;
+
+-- NULLs never conflict, leaving rows recorded without an entry id alone.
+CREATE UNIQUE INDEX idx_usage_logs_stream_entry_id ON usage_logs (stream_entry_id);
+CREATE UNIQUE INDEX idx_request_errors_stream_entry_id ON request_errors (stream_entry_id);
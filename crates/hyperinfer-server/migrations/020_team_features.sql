@@ -0,0 +1,4 @@
+-- Feature flags switching gateway behaviors per team, by flag name
+-- (e.g. {"enable_cache": false}); flags left out take the defaults.
+
+ALTER TABLE teams ADD COLUMN features JSONB NOT NULL DEFAULT '{}';
@@ -0,0 +1,4 @@
+-- Upper bounds on a team's sampling parameters, e.g.
+-- {"max_temperature": 1.0, "max_tokens": 4096}; NULL for none.
+
+ALTER TABLE teams ADD COLUMN param_clamps JSONB;
@@ -0,0 +1,4 @@
+-- Cleanup steps applied, in order, to a team's chat responses, e.g.
+-- [{"type": "trim_whitespace"}, {"type": "max_length", "max_chars": 2000}].
+
+ALTER TABLE teams ADD COLUMN output_transforms JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,38 @@
+-- Evals: datasets of prompts with answer criteria, runs of a dataset
+-- against a model, and each run's per-item results
+
+CREATE TABLE eval_datasets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    team_id UUID NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    items JSONB NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_eval_datasets_team ON eval_datasets(team_id);
+
+CREATE TABLE eval_runs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    dataset_id UUID NOT NULL REFERENCES eval_datasets(id) ON DELETE CASCADE,
+    model VARCHAR(255) NOT NULL,
+    item_count INTEGER NOT NULL,
+    passed_count INTEGER NOT NULL,
+    mean_score DOUBLE PRECISION NOT NULL,
+    mean_latency_ms DOUBLE PRECISION NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_eval_runs_dataset_created ON eval_runs(dataset_id, created_at);
+
+CREATE TABLE eval_results (
+    run_id UUID NOT NULL REFERENCES eval_runs(id) ON DELETE CASCADE,
+    item_index INTEGER NOT NULL,
+    answer TEXT,
+    error TEXT,
+    score DOUBLE PRECISION NOT NULL,
+    passed BOOLEAN NOT NULL,
+    latency_ms BIGINT NOT NULL,
+    input_tokens INTEGER NOT NULL,
+    output_tokens INTEGER NOT NULL,
+    PRIMARY KEY (run_id, item_index)
+);
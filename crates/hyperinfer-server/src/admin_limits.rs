@@ -0,0 +1,348 @@
+//! Rate limiting of the control-plane API.
+//!
+//! Every admin endpoint is limited per caller, so a dashboard stuck in a
+//! polling loop or a runaway script cannot swamp Postgres.  Callers are told
+//! apart by a hash of their bearer token once the admin auth has accepted it
+//! (see [`VerifiedCaller`]), and otherwise by IP address, so a client cannot
+//! mint fresh budgets by sending made-up tokens.  Each caller gets a
+//! separate per-minute budget for each endpoint, counted in the shared
+//! [`RateLimiter`] so the limit holds across replicas.
+//!
+//! Endpoints fall into classes with their own defaults, each overridable
+//! through the environment:
+//!
+//! | Class      | Default/min | Env var                | Endpoints                                            |
+//! |------------|-------------|------------------------|------------------------------------------------------|
+//! | data plane | 6000        | `ADMIN_RPM_DATA_PLANE` | config sync, pricing, key resolution                 |
+//! | report     | 30          | `ADMIN_RPM_REPORT`     | usage reports and exports, stale keys, route explain |
+//! | read       | 300         | `ADMIN_RPM_READ`       | every other `GET`                                    |
+//! | write      | 60          | `ADMIN_RPM_WRITE`      | everything else                                      |
+//!
+//! `ADMIN_RPM_ROUTES` sets limits for individual routes as
+//! `/v1/route=rpm,...`, using the route patterns the server registers.
+//!
+//! If the limiter itself fails the request is let through: losing Redis
+//! should not lock administrators out.
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use hyperinfer_core::{RateLimiter, RpmWindow};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Routes polled by every data-plane instance.
+const DATA_PLANE_ROUTES: [&str; 3] = [
+    "/v1/config/sync",
+    "/v1/pricing",
+    "/v1/virtual_keys/:key_hash",
+];
+
+/// Routes that aggregate over usage logs and are costly to serve.
+const REPORT_ROUTES: [&str; 6] = [
+    "/v1/teams/:id/usage",
+    "/v1/teams/:id/billing_periods",
+    "/v1/usage/export",
+    "/v1/route/explain",
+    "/v1/users/:id/role_changes",
+    "/v1/api_keys/stale",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointClass {
+    DataPlane,
+    Report,
+    Read,
+    Write,
+}
+
+impl EndpointClass {
+    pub fn of(method: &Method, route: &str) -> Self {
+        if method != Method::GET {
+            Self::Write
+        } else if DATA_PLANE_ROUTES.contains(&route) {
+            Self::DataPlane
+        } else if REPORT_ROUTES.contains(&route) {
+            Self::Report
+        } else {
+            Self::Read
+        }
+    }
+}
+
+/// Per-minute request limits for the admin API.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdminRateLimits {
+    pub data_plane_rpm: u64,
+    pub report_rpm: u64,
+    pub read_rpm: u64,
+    pub write_rpm: u64,
+    /// Limits for individual routes, overriding their class's.
+    pub routes: HashMap<String, u64>,
+}
+
+impl Default for AdminRateLimits {
+    fn default() -> Self {
+        Self {
+            data_plane_rpm: 6000,
+            report_rpm: 30,
+            read_rpm: 300,
+            write_rpm: 60,
+            routes: HashMap::new(),
+        }
+    }
+}
+
+fn rpm_from(var: &dyn Fn(&str) -> Option<String>, name: &str, default: u64) -> Result<u64, String> {
+    match var(name) {
+        Some(value) => value
+            .trim()
+            .parse()
+            .ok()
+            .filter(|rpm| *rpm > 0)
+            .ok_or_else(|| format!("Invalid {} '{}': expected a positive integer", name, value)),
+        None => Ok(default),
+    }
+}
+
+/// Parse `/v1/route=rpm,...`.
+pub fn parse_route_limits(spec: &str) -> Result<HashMap<String, u64>, String> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .rsplit_once('=')
+                .and_then(|(route, rpm)| {
+                    let rpm = rpm.trim().parse().ok().filter(|rpm| *rpm > 0)?;
+                    Some((route.trim().to_string(), rpm))
+                })
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid ADMIN_RPM_ROUTES entry '{}': expected /route=rpm",
+                        entry
+                    )
+                })
+        })
+        .collect()
+}
+
+impl AdminRateLimits {
+    /// Limits from the settings `var` looks up by name.
+    pub fn from_vars(var: &dyn Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let defaults = Self::default();
+        Ok(Self {
+            data_plane_rpm: rpm_from(var, "ADMIN_RPM_DATA_PLANE", defaults.data_plane_rpm)?,
+            report_rpm: rpm_from(var, "ADMIN_RPM_REPORT", defaults.report_rpm)?,
+            read_rpm: rpm_from(var, "ADMIN_RPM_READ", defaults.read_rpm)?,
+            write_rpm: rpm_from(var, "ADMIN_RPM_WRITE", defaults.write_rpm)?,
+            routes: parse_route_limits(&var("ADMIN_RPM_ROUTES").unwrap_or_default())?,
+        })
+    }
+
+    /// Requests per minute allowed to one caller on `route`.
+    pub fn limit_for(&self, method: &Method, route: &str) -> u64 {
+        if let Some(rpm) = self.routes.get(route) {
+            return *rpm;
+        }
+        match EndpointClass::of(method, route) {
+            EndpointClass::DataPlane => self.data_plane_rpm,
+            EndpointClass::Report => self.report_rpm,
+            EndpointClass::Read => self.read_rpm,
+            EndpointClass::Write => self.write_rpm,
+        }
+    }
+}
+
+/// State of [`admin_rate_limit_middleware`].
+#[derive(Clone)]
+pub struct AdminRateLimiter {
+    limiter: RateLimiter,
+    limits: Arc<AdminRateLimits>,
+}
+
+impl AdminRateLimiter {
+    pub fn new(limiter: RateLimiter, limits: AdminRateLimits) -> Self {
+        Self {
+            limiter,
+            limits: Arc::new(limits),
+        }
+    }
+}
+
+/// A caller whose bearer token the admin auth accepted, named by a hash of
+/// the token.  The auth middleware adds it to the request's extensions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedCaller(String);
+
+impl VerifiedCaller {
+    pub fn from_token(token: &str) -> Self {
+        Self(hex::encode(Sha256::digest(token.as_bytes()))[..16].to_string())
+    }
+}
+
+/// Who is calling: their verified token's hash, or their IP address.
+fn caller(req: &Request) -> String {
+    if let Some(VerifiedCaller(hash)) = req.extensions().get::<VerifiedCaller>() {
+        return format!("token:{}", hash);
+    }
+    match req.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(addr)) => format!("ip:{}", addr.ip()),
+        None => "ip:unknown".to_string(),
+    }
+}
+
+/// Reject a caller over its per-minute budget for the route with 429 and a
+/// `Retry-After` header.  Must be applied with `Router::layer` so the
+/// matched route is known, and inside the auth middleware on authenticated
+/// routes so it sees the [`VerifiedCaller`].
+pub async fn admin_rate_limit_middleware(
+    State(state): State<AdminRateLimiter>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| req.uri().path().to_string());
+    let limit = state.limits.limit_for(req.method(), &route);
+    let key = format!("admin:{}:{}:{}", caller(&req), req.method(), route);
+
+    match state
+        .limiter
+        .check_rpm_window(&key, limit, RpmWindow::Sliding)
+        .await
+    {
+        Ok((true, _)) => next.run(req).await,
+        Ok((false, retry_after_ms)) => {
+            tracing::warn!(route = %route, limit, "Admin API rate limit exceeded");
+            let mut response =
+                (StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded").into_response();
+            let retry_after_secs = retry_after_ms.div_ceil(1000).max(1);
+            if let Ok(value) = HeaderValue::from_str(&retry_after_secs.to_string()) {
+                response.headers_mut().insert(header::RETRY_AFTER, value);
+            }
+            response
+        }
+        Err(e) => {
+            tracing::warn!("Admin rate limit check failed, allowing request: {}", e);
+            next.run(req).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use axum_test::TestServer;
+
+    /// Stands in for the admin auth, accepting every bearer token.
+    async fn accept_any_token(mut req: Request, next: Next) -> Response {
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        if let Some(token) = token {
+            req.extensions_mut()
+                .insert(VerifiedCaller::from_token(&token));
+        }
+        next.run(req).await
+    }
+
+    fn limited_app(rpm: u64, verify: bool) -> Router {
+        let limits = AdminRateLimits {
+            routes: HashMap::from([("/v1/alerts".to_string(), rpm)]),
+            ..Default::default()
+        };
+        let state = AdminRateLimiter::new(RateLimiter::local(), limits);
+        let app = Router::new()
+            .route("/v1/alerts", get(|| async { "ok" }))
+            .route("/v1/teams", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(
+                state,
+                admin_rate_limit_middleware,
+            ));
+        if verify {
+            app.layer(middleware::from_fn(accept_any_token))
+        } else {
+            app
+        }
+    }
+
+    #[test]
+    fn test_endpoint_classes() {
+        let limits = AdminRateLimits {
+            routes: HashMap::from([("/v1/teams/:id".to_string(), 5)]),
+            ..Default::default()
+        };
+        assert_eq!(limits.limit_for(&Method::GET, "/v1/config/sync"), 6000);
+        assert_eq!(limits.limit_for(&Method::GET, "/v1/usage/export"), 30);
+        assert_eq!(limits.limit_for(&Method::GET, "/v1/alerts"), 300);
+        assert_eq!(limits.limit_for(&Method::POST, "/v1/teams"), 60);
+        assert_eq!(limits.limit_for(&Method::GET, "/v1/teams/:id"), 5);
+    }
+
+    #[test]
+    fn test_parse_route_limits() {
+        let routes = parse_route_limits("/v1/teams=100, /v1/usage/export=2").unwrap();
+        assert_eq!(routes["/v1/teams"], 100);
+        assert_eq!(routes["/v1/usage/export"], 2);
+        assert!(parse_route_limits("/v1/teams=0").is_err());
+        assert!(parse_route_limits("/v1/teams").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_callers_are_limited_separately() {
+        let server = TestServer::new(limited_app(2, true));
+        let get_as = |path: &str, token: &str| {
+            server
+                .get(path)
+                .add_header(header::AUTHORIZATION, format!("Bearer {}", token))
+        };
+
+        assert_eq!(
+            get_as("/v1/alerts", "a").await.status_code(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            get_as("/v1/alerts", "a").await.status_code(),
+            StatusCode::OK
+        );
+        let limited = get_as("/v1/alerts", "a").await;
+        assert_eq!(limited.status_code(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(limited.headers().contains_key(header::RETRY_AFTER));
+
+        // Other callers and other endpoints keep their own budgets.
+        assert_eq!(
+            get_as("/v1/alerts", "b").await.status_code(),
+            StatusCode::OK
+        );
+        assert_eq!(get_as("/v1/teams", "a").await.status_code(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_unverified_tokens_share_the_address_budget() {
+        let server = TestServer::new(limited_app(2, false));
+        for token in ["a", "b"] {
+            let response = server
+                .get("/v1/alerts")
+                .add_header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .await;
+            assert_eq!(response.status_code(), StatusCode::OK);
+        }
+        // A made-up token does not buy a fresh budget.
+        let limited = server
+            .get("/v1/alerts")
+            .add_header(header::AUTHORIZATION, "Bearer c")
+            .await;
+        assert_eq!(limited.status_code(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
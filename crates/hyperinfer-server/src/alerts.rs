@@ -0,0 +1,479 @@
+//! Usage-based alerting.
+//!
+//! An [`AlertEvaluator`] runs in the background and, on every tick, checks
+//! each active alert rule against Postgres:
+//!
+//! * `budget` — fraction of the team budget spent in the current billing
+//!   period (e.g. `0.8` fires at 80 %, `1.0` at 100 %).
+//! * `error_rate` — fraction of the team's requests that failed within the
+//!   rule's window.
+//! * `provider_down` — number of failed requests to `provider` within the
+//!   rule's window.
+//! * `anomaly` — how unusual the team's per-key traffic in the window is
+//!   against each key's own history, as a z-score (see [`crate::anomaly`]).
+//!
+//! A breach opens an alert row and delivers a webhook (plain JSON or a Slack
+//! incoming-webhook payload).  A `provider_down` breach also sends the
+//! `provider.down` event to subscribed team webhooks (see [`crate::webhooks`]),
+//! every team's when the rule has none.  The alert stays open — and is not re-sent —
+//! until the condition clears, at which point it is marked resolved.
+
+use crate::anomaly;
+use crate::billing;
+use crate::leader::Leadership;
+use crate::webhooks::{self, WebhookEvent};
+use chrono::{DateTime, Utc};
+use hyperinfer_core::{Alert, AlertRule, Database, DbError, NewAlertRule};
+use serde_json::json;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+// ── Rule kinds ──────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlertKind {
+    Budget,
+    ErrorRate,
+    ProviderDown,
+    Anomaly,
+}
+
+impl AlertKind {
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "budget" => Some(Self::Budget),
+            "error_rate" => Some(Self::ErrorRate),
+            "provider_down" => Some(Self::ProviderDown),
+            "anomaly" => Some(Self::Anomaly),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookFormat {
+    Json,
+    Slack,
+}
+
+impl WebhookFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "json" => Some(Self::Json),
+            "slack" => Some(Self::Slack),
+            _ => None,
+        }
+    }
+}
+
+/// Check a rule submitted through the admin API before it is stored.
+pub fn validate_rule(rule: &NewAlertRule) -> Result<(), String> {
+    let kind = AlertKind::parse(&rule.kind).ok_or_else(|| {
+        format!(
+            "Unknown alert kind '{}': expected budget, error_rate, provider_down or anomaly",
+            rule.kind
+        )
+    })?;
+    if WebhookFormat::parse(&rule.webhook_format).is_none() {
+        return Err(format!(
+            "Unknown webhook format '{}': expected json or slack",
+            rule.webhook_format
+        ));
+    }
+    if !(rule.threshold.is_finite() && rule.threshold > 0.0) {
+        return Err("threshold must be a positive number".to_string());
+    }
+    if kind == AlertKind::ErrorRate && rule.threshold > 1.0 {
+        return Err("error_rate threshold is a fraction and must be <= 1.0".to_string());
+    }
+    if rule.window_minutes <= 0 {
+        return Err("window_minutes must be positive".to_string());
+    }
+    if kind != AlertKind::ProviderDown && rule.team_id.is_none() {
+        return Err(format!("{} rules require a team_id", rule.kind));
+    }
+    if kind == AlertKind::ProviderDown && rule.provider.is_none() {
+        return Err("provider_down rules require a provider".to_string());
+    }
+    if !(rule.webhook_url.starts_with("https://") || rule.webhook_url.starts_with("http://")) {
+        return Err("webhook_url must be an http(s) URL".to_string());
+    }
+    Ok(())
+}
+
+// ── Webhook payloads ────────────────────────────────────────────────────────
+
+/// Body POSTed to a rule's webhook for `alert`.
+pub fn webhook_payload(format: WebhookFormat, alert: &Alert) -> serde_json::Value {
+    match format {
+        WebhookFormat::Json => json!({
+            "alert_id": alert.id,
+            "rule_id": alert.rule_id,
+            "team_id": alert.team_id,
+            "kind": alert.kind,
+            "message": alert.message,
+            "observed_value": alert.observed_value,
+            "threshold": alert.threshold,
+            "fired_at": alert.fired_at,
+        }),
+        WebhookFormat::Slack => json!({
+            "text": format!(":rotating_light: *HyperInfer alert* ({}): {}", alert.kind, alert.message),
+        }),
+    }
+}
+
+// ── Evaluator ───────────────────────────────────────────────────────────────
+
+/// Result of checking one rule.
+#[derive(Debug, Clone, PartialEq)]
+struct Observation {
+    value: f64,
+    message: String,
+}
+
+pub struct AlertEvaluator<D: Database> {
+    db: D,
+    http: reqwest::Client,
+}
+
+impl<D: Database> AlertEvaluator<D> {
+    pub fn new(db: D) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+        Self { db, http }
+    }
+
+    /// Evaluate every rule once on `interval` until `cancel` fires,
+    /// skipping ticks while this replica is not the scheduler leader.
+    pub fn spawn(
+        self,
+        interval: Duration,
+        leader: Leadership,
+        cancel: CancellationToken,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = ticker.tick() => {
+                        if !leader.is_leader() {
+                            continue;
+                        }
+                        if let Err(e) = self.evaluate_once(Utc::now()).await {
+                            tracing::error!("Alert evaluation failed: {:?}", e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Check all active rules as of `now`.  Returns the number of alerts fired.
+    pub async fn evaluate_once(&self, now: DateTime<Utc>) -> Result<usize, DbError> {
+        let mut fired = 0;
+        for rule in self.db.list_alert_rules().await? {
+            match self.evaluate_rule(&rule, now).await {
+                Ok(true) => fired += 1,
+                Ok(false) => {}
+                Err(e) => tracing::warn!("Failed to evaluate alert rule {}: {:?}", rule.id, e),
+            }
+        }
+        Ok(fired)
+    }
+
+    async fn evaluate_rule(&self, rule: &AlertRule, now: DateTime<Utc>) -> Result<bool, DbError> {
+        let Some(observation) = self.observe(rule, now).await? else {
+            return Ok(false);
+        };
+        let open = self.db.get_open_alert(&rule.id).await?;
+
+        if observation.value < rule.threshold {
+            if let Some(alert) = open {
+                self.db.resolve_alert(&alert.id).await?;
+                tracing::info!("Alert {} resolved", alert.id);
+            }
+            return Ok(false);
+        }
+        if open.is_some() {
+            return Ok(false);
+        }
+
+        let alert = match self
+            .db
+            .create_alert(rule, &observation.message, observation.value)
+            .await
+        {
+            Ok(alert) => alert,
+            // Another replica opened it first.
+            Err(DbError::UniqueViolation(_)) => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        tracing::warn!("Alert fired: {}", alert.message);
+        if AlertKind::parse(&rule.kind) == Some(AlertKind::ProviderDown) {
+            webhooks::emit(
+                &self.db,
+                rule.team_id.as_deref(),
+                WebhookEvent::ProviderDown,
+                json!({
+                    "provider": rule.provider,
+                    "failed_requests": observation.value,
+                    "window_minutes": rule.window_minutes,
+                    "message": alert.message,
+                }),
+            )
+            .await;
+        }
+
+        if self.deliver(rule, &alert).await {
+            self.db.mark_alert_delivered(&alert.id).await?;
+        }
+        Ok(true)
+    }
+
+    async fn observe(
+        &self,
+        rule: &AlertRule,
+        now: DateTime<Utc>,
+    ) -> Result<Option<Observation>, DbError> {
+        let window_start = now - chrono::Duration::minutes(i64::from(rule.window_minutes));
+        let Some(kind) = AlertKind::parse(&rule.kind) else {
+            tracing::warn!(
+                "Skipping alert rule {} with unknown kind '{}'",
+                rule.id,
+                rule.kind
+            );
+            return Ok(None);
+        };
+
+        match kind {
+            AlertKind::Budget => {
+                let Some(team_id) = rule.team_id.as_deref() else {
+                    return Ok(None);
+                };
+                let Some(team) = self.db.get_team(team_id).await? else {
+                    return Ok(None);
+                };
+                if team.budget_cents <= 0 {
+                    return Ok(None);
+                }
+                let spent_cents = billing::current_spend_cents(&self.db, &team, now).await?;
+                let value = spent_cents / team.budget_cents as f64;
+                Ok(Some(Observation {
+                    value,
+                    message: format!(
+                        "Team '{}' has used {:.0}% of its budget this billing period (${:.2} of ${:.2})",
+                        team.name,
+                        value * 100.0,
+                        spent_cents / 100.0,
+                        team.budget_cents as f64 / 100.0
+                    ),
+                }))
+            }
+            AlertKind::ErrorRate => {
+                let Some(team_id) = rule.team_id.as_deref() else {
+                    return Ok(None);
+                };
+                let succeeded: i64 = self
+                    .db
+                    .get_model_usage_since(team_id, window_start)
+                    .await?
+                    .iter()
+                    .map(|u| u.requests)
+                    .sum();
+                let failed = self
+                    .db
+                    .count_request_errors_since(Some(team_id.to_string()), None, window_start)
+                    .await?;
+                let total = succeeded + failed;
+                let value = if total == 0 {
+                    0.0
+                } else {
+                    failed as f64 / total as f64
+                };
+                Ok(Some(Observation {
+                    value,
+                    message: format!(
+                        "Error rate for team {} is {:.1}% ({} of {} requests) over the last {} minutes",
+                        team_id,
+                        value * 100.0,
+                        failed,
+                        total,
+                        rule.window_minutes
+                    ),
+                }))
+            }
+            AlertKind::Anomaly => {
+                let Some(team_id) = rule.team_id.as_deref() else {
+                    return Ok(None);
+                };
+                let baseline_windows = anomaly::baseline_windows(rule.window_minutes);
+                let baseline_start = now
+                    - chrono::Duration::minutes(
+                        i64::from(rule.window_minutes) * (baseline_windows + 1),
+                    );
+                let buckets = self
+                    .db
+                    .get_key_usage_buckets(team_id, baseline_start, now, rule.window_minutes)
+                    .await?;
+                let anomalies = anomaly::detect(&buckets, baseline_windows, rule.threshold);
+                let value = anomalies
+                    .iter()
+                    .map(|a| a.score(rule.threshold))
+                    .fold(0.0, f64::max);
+                Ok(Some(Observation {
+                    value,
+                    message: format!(
+                        "Unusual usage for team {} over the last {} minutes: {}",
+                        team_id,
+                        rule.window_minutes,
+                        anomaly::summarize(&anomalies, rule.threshold)
+                    ),
+                }))
+            }
+            AlertKind::ProviderDown => {
+                let Some(provider) = rule.provider.as_deref() else {
+                    return Ok(None);
+                };
+                let failed = self
+                    .db
+                    .count_request_errors_since(
+                        rule.team_id.clone(),
+                        Some(provider.to_string()),
+                        window_start,
+                    )
+                    .await?;
+                Ok(Some(Observation {
+                    value: failed as f64,
+                    message: format!(
+                        "Provider '{}' failed {} requests over the last {} minutes",
+                        provider, failed, rule.window_minutes
+                    ),
+                }))
+            }
+        }
+    }
+
+    /// POST the alert to the rule's webhook.  Returns whether delivery succeeded.
+    async fn deliver(&self, rule: &AlertRule, alert: &Alert) -> bool {
+        let format = WebhookFormat::parse(&rule.webhook_format).unwrap_or(WebhookFormat::Json);
+        let result = self
+            .http
+            .post(&rule.webhook_url)
+            .json(&webhook_payload(format, alert))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        match result {
+            Ok(_) => true,
+            Err(e) => {
+                tracing::warn!("Failed to deliver alert {} webhook: {}", alert.id, e);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_rule(kind: &str) -> NewAlertRule {
+        NewAlertRule {
+            team_id: Some("team-1".to_string()),
+            kind: kind.to_string(),
+            threshold: 0.8,
+            provider: None,
+            window_minutes: 15,
+            webhook_url: "https://hooks.example.com/alert".to_string(),
+            webhook_format: "json".to_string(),
+        }
+    }
+
+    fn alert() -> Alert {
+        Alert {
+            id: "alert-1".to_string(),
+            rule_id: "rule-1".to_string(),
+            team_id: Some("team-1".to_string()),
+            kind: "budget".to_string(),
+            message: "Team 'Research' has used 85% of its monthly budget".to_string(),
+            observed_value: 0.85,
+            threshold: 0.8,
+            delivered: false,
+            fired_at: Utc::now(),
+            resolved_at: None,
+        }
+    }
+
+    #[test]
+    fn test_alert_kind_parse() {
+        assert_eq!(AlertKind::parse("budget"), Some(AlertKind::Budget));
+        assert_eq!(AlertKind::parse("error_rate"), Some(AlertKind::ErrorRate));
+        assert_eq!(
+            AlertKind::parse("provider_down"),
+            Some(AlertKind::ProviderDown)
+        );
+        assert_eq!(AlertKind::parse("anomaly"), Some(AlertKind::Anomaly));
+        assert_eq!(AlertKind::parse("latency"), None);
+    }
+
+    #[test]
+    fn test_validate_rule_accepts_budget() {
+        assert!(validate_rule(&new_rule("budget")).is_ok());
+    }
+
+    #[test]
+    fn test_validate_rule_rejects_unknown_kind() {
+        assert!(validate_rule(&new_rule("latency")).is_err());
+    }
+
+    #[test]
+    fn test_validate_rule_error_rate_is_fraction() {
+        let mut rule = new_rule("error_rate");
+        rule.threshold = 5.0;
+        assert!(validate_rule(&rule).is_err());
+    }
+
+    #[test]
+    fn test_validate_rule_provider_down_requires_provider() {
+        let mut rule = new_rule("provider_down");
+        rule.team_id = None;
+        rule.threshold = 10.0;
+        assert!(validate_rule(&rule).is_err());
+
+        rule.provider = Some("openai".to_string());
+        assert!(validate_rule(&rule).is_ok());
+    }
+
+    #[test]
+    fn test_validate_rule_team_required_for_budget() {
+        let mut rule = new_rule("budget");
+        rule.team_id = None;
+        assert!(validate_rule(&rule).is_err());
+    }
+
+    #[test]
+    fn test_validate_rule_rejects_non_http_webhook() {
+        let mut rule = new_rule("budget");
+        rule.webhook_url = "file:///etc/passwd".to_string();
+        assert!(validate_rule(&rule).is_err());
+    }
+
+    #[test]
+    fn test_webhook_payload_json() {
+        let payload = webhook_payload(WebhookFormat::Json, &alert());
+        assert_eq!(payload["alert_id"], "alert-1");
+        assert_eq!(payload["kind"], "budget");
+        assert_eq!(payload["threshold"], 0.8);
+    }
+
+    #[test]
+    fn test_webhook_payload_slack() {
+        let payload = webhook_payload(WebhookFormat::Slack, &alert());
+        let text = payload["text"].as_str().unwrap();
+        assert!(text.contains("budget"));
+        assert!(text.contains("85%"));
+    }
+}
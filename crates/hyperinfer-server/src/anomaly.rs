@@ -0,0 +1,294 @@
+//! Anomaly detection on usage patterns.
+//!
+//! `anomaly` alert rules watch each of a team's API keys for traffic unlike
+//! the key's own history, which is often the first sign of a leaked key.
+//! The rule's window is compared against the same-sized windows over the
+//! previous [`BASELINE_DAYS`] days, and three things are flagged:
+//!
+//! * **spike** — the key's requests in the window are `threshold` or more
+//!   standard deviations above its baseline mean (a z-score);
+//! * **new model** — the key called a model it did not use in the baseline;
+//! * **off hours** — the key sent requests in an hour of day (UTC) in which
+//!   it sent none in the baseline.
+//!
+//! Model and hour checks only apply to keys with at least
+//! [`MIN_BASELINE_REQUESTS`] baseline requests, so new keys are not flagged
+//! for having no history, and spikes need at least [`MIN_SPIKE_REQUESTS`]
+//! requests so a handful of calls from a quiet key does not count.
+
+use hyperinfer_core::KeyUsageBucket;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// History each window is compared against.
+pub const BASELINE_DAYS: i64 = 7;
+
+/// Baseline requests a key needs before new models and hours are flagged.
+pub const MIN_BASELINE_REQUESTS: i64 = 100;
+
+/// Requests in the window below which no spike is reported.
+pub const MIN_SPIKE_REQUESTS: i64 = 20;
+
+/// Most anomalies named in one alert message.
+const MAX_REPORTED: usize = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnomalyKind {
+    Spike {
+        requests: i64,
+        baseline_mean: f64,
+        z_score: f64,
+    },
+    NewModel {
+        model: String,
+    },
+    OffHours {
+        hour: i32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    pub api_key_id: String,
+    pub kind: AnomalyKind,
+}
+
+impl Anomaly {
+    /// How far over the rule's threshold this is.  Spikes score their
+    /// z-score; new models and off-hours traffic score the threshold itself,
+    /// as they have no magnitude.
+    pub fn score(&self, threshold: f64) -> f64 {
+        match self.kind {
+            AnomalyKind::Spike { z_score, .. } => z_score,
+            AnomalyKind::NewModel { .. } | AnomalyKind::OffHours { .. } => threshold,
+        }
+    }
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            AnomalyKind::Spike {
+                requests,
+                baseline_mean,
+                z_score,
+            } => write!(
+                f,
+                "key {} made {} requests (usually {:.1}, z-score {:.1})",
+                self.api_key_id, requests, baseline_mean, z_score
+            ),
+            AnomalyKind::NewModel { model } => {
+                write!(
+                    f,
+                    "key {} called model '{}' for the first time",
+                    self.api_key_id, model
+                )
+            }
+            AnomalyKind::OffHours { hour } => write!(
+                f,
+                "key {} was used at {:02}:00 UTC, outside its usual hours",
+                self.api_key_id, hour
+            ),
+        }
+    }
+}
+
+/// Windows of `window_minutes` in the baseline.
+pub fn baseline_windows(window_minutes: i32) -> i64 {
+    (BASELINE_DAYS * 24 * 60 / i64::from(window_minutes.max(1))).max(1)
+}
+
+#[derive(Default)]
+struct KeyHistory {
+    current: i64,
+    current_models: BTreeSet<String>,
+    current_hours: BTreeSet<i32>,
+    /// Requests per baseline window, by windows ago.
+    baseline: BTreeMap<i64, i64>,
+    baseline_models: BTreeSet<String>,
+    baseline_hours: BTreeSet<i32>,
+}
+
+/// Anomalies in window 0 of `buckets` against the following
+/// `baseline_windows` windows, for spikes of at least `threshold` standard
+/// deviations.
+pub fn detect(buckets: &[KeyUsageBucket], baseline_windows: i64, threshold: f64) -> Vec<Anomaly> {
+    let mut keys: BTreeMap<&str, KeyHistory> = BTreeMap::new();
+    for bucket in buckets {
+        let history = keys.entry(&bucket.api_key_id).or_default();
+        if bucket.windows_ago == 0 {
+            history.current += bucket.requests;
+            history.current_models.insert(bucket.model.clone());
+            history.current_hours.insert(bucket.hour);
+        } else if (1..=baseline_windows).contains(&bucket.windows_ago) {
+            *history.baseline.entry(bucket.windows_ago).or_default() += bucket.requests;
+            history.baseline_models.insert(bucket.model.clone());
+            history.baseline_hours.insert(bucket.hour);
+        }
+    }
+
+    let mut anomalies = Vec::new();
+    for (api_key_id, history) in keys {
+        if history.current == 0 {
+            continue;
+        }
+        let anomaly = |kind| Anomaly {
+            api_key_id: api_key_id.to_string(),
+            kind,
+        };
+
+        // Windows without traffic count as zero.
+        let n = baseline_windows as f64;
+        let total: i64 = history.baseline.values().sum();
+        let mean = total as f64 / n;
+        let variance = history
+            .baseline
+            .values()
+            .map(|&count| (count as f64 - mean).powi(2))
+            .sum::<f64>()
+            / n
+            + (n - history.baseline.len() as f64) * mean.powi(2) / n;
+        // Floor the deviation so perfectly steady keys are not flagged for a
+        // single extra request.
+        let z_score = (history.current as f64 - mean) / variance.sqrt().max(1.0);
+        if history.current >= MIN_SPIKE_REQUESTS && z_score >= threshold {
+            anomalies.push(anomaly(AnomalyKind::Spike {
+                requests: history.current,
+                baseline_mean: mean,
+                z_score,
+            }));
+        }
+
+        if total < MIN_BASELINE_REQUESTS {
+            continue;
+        }
+        for model in history.current_models.difference(&history.baseline_models) {
+            anomalies.push(anomaly(AnomalyKind::NewModel {
+                model: model.clone(),
+            }));
+        }
+        for hour in history.current_hours.difference(&history.baseline_hours) {
+            anomalies.push(anomaly(AnomalyKind::OffHours { hour: *hour }));
+        }
+    }
+    anomalies
+}
+
+/// Alert message naming the worst of `anomalies`.
+pub fn summarize(anomalies: &[Anomaly], threshold: f64) -> String {
+    let mut ranked: Vec<&Anomaly> = anomalies.iter().collect();
+    ranked.sort_by(|a, b| b.score(threshold).total_cmp(&a.score(threshold)));
+    let mut message = ranked
+        .iter()
+        .take(MAX_REPORTED)
+        .map(|anomaly| anomaly.to_string())
+        .collect::<Vec<_>>()
+        .join("; ");
+    if ranked.len() > MAX_REPORTED {
+        message.push_str(&format!("; and {} more", ranked.len() - MAX_REPORTED));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(
+        key: &str,
+        model: &str,
+        windows_ago: i64,
+        hour: i32,
+        requests: i64,
+    ) -> KeyUsageBucket {
+        KeyUsageBucket {
+            api_key_id: key.to_string(),
+            model: model.to_string(),
+            windows_ago,
+            hour,
+            requests,
+        }
+    }
+
+    /// A key sending `per_window` requests to gpt-4o during business hours
+    /// in each of `windows` baseline windows.
+    fn steady(key: &str, windows: i64, per_window: i64) -> Vec<KeyUsageBucket> {
+        (1..=windows)
+            .map(|ago| bucket(key, "gpt-4o", ago, 9 + (ago % 8) as i32, per_window))
+            .collect()
+    }
+
+    #[test]
+    fn test_baseline_windows() {
+        assert_eq!(baseline_windows(60), 168);
+        assert_eq!(baseline_windows(15), 672);
+        assert_eq!(baseline_windows(0), 10080);
+    }
+
+    #[test]
+    fn test_spike_is_flagged() {
+        let mut buckets = steady("key-1", 24, 10);
+        buckets.push(bucket("key-1", "gpt-4o", 0, 10, 200));
+        let anomalies = detect(&buckets, 24, 3.0);
+        assert_eq!(anomalies.len(), 1);
+        match &anomalies[0].kind {
+            AnomalyKind::Spike {
+                requests, z_score, ..
+            } => {
+                assert_eq!(*requests, 200);
+                assert!(*z_score > 3.0);
+            }
+            other => panic!("unexpected anomaly {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_normal_traffic_is_not_flagged() {
+        let mut buckets = steady("key-1", 24, 10);
+        buckets.push(bucket("key-1", "gpt-4o", 0, 10, 11));
+        assert!(detect(&buckets, 24, 3.0).is_empty());
+
+        // Small absolute counts are not spikes, however unusual.
+        let buckets = vec![bucket("quiet", "gpt-4o", 0, 10, 5)];
+        assert!(detect(&buckets, 24, 3.0).is_empty());
+    }
+
+    #[test]
+    fn test_new_model_and_off_hours_need_history() {
+        let mut buckets = steady("key-1", 24, 10);
+        buckets.push(bucket("key-1", "o1", 0, 3, 2));
+        let anomalies = detect(&buckets, 24, 3.0);
+        assert!(anomalies.contains(&Anomaly {
+            api_key_id: "key-1".to_string(),
+            kind: AnomalyKind::NewModel {
+                model: "o1".to_string()
+            },
+        }));
+        assert!(anomalies.contains(&Anomaly {
+            api_key_id: "key-1".to_string(),
+            kind: AnomalyKind::OffHours { hour: 3 },
+        }));
+
+        // A key with little history is not flagged for either.
+        let mut buckets = steady("new-key", 3, 10);
+        buckets.push(bucket("new-key", "o1", 0, 3, 2));
+        assert!(detect(&buckets, 24, 3.0).is_empty());
+    }
+
+    #[test]
+    fn test_summarize_ranks_and_truncates() {
+        let spike = |key: &str, z_score| Anomaly {
+            api_key_id: key.to_string(),
+            kind: AnomalyKind::Spike {
+                requests: 100,
+                baseline_mean: 2.0,
+                z_score,
+            },
+        };
+        let mut anomalies: Vec<Anomaly> = (0..6).map(|i| spike(&format!("k{}", i), 4.0)).collect();
+        anomalies.push(spike("worst", 40.0));
+        let message = summarize(&anomalies, 3.0);
+        assert!(message.starts_with("key worst made 100 requests"));
+        assert!(message.ends_with("and 2 more"));
+    }
+}
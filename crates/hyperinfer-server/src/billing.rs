@@ -0,0 +1,401 @@
+//! Billing periods.
+//!
+//! Each team's budget applies to a billing period that starts at local
+//! midnight on `billing_anchor_day` (1-28) in `billing_timezone` and lasts one
+//! calendar month.  Budget enforcement and budget alerts measure spend from
+//! the start of the current period, so both reset on the anchor day.
+//!
+//! A [`BillingPeriodCloser`] runs in the background and snapshots each
+//! team's usage for the period that just ended into `billing_periods`.
+
+use crate::leader::Leadership;
+use chrono::{DateTime, Datelike, LocalResult, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use hyperinfer_core::{
+    pricing, BillingPeriod, ConfiguredPrice, Database, DbError, ModelUsage, Team,
+};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use utoipa::ToSchema;
+
+/// Check billing settings submitted through the admin API.
+pub fn validate_billing(anchor_day: i32, timezone: &str) -> Result<Tz, String> {
+    if !(1..=28).contains(&anchor_day) {
+        return Err("anchor_day must be between 1 and 28".to_string());
+    }
+    timezone
+        .parse::<Tz>()
+        .map_err(|_| format!("Unknown timezone '{}'", timezone))
+}
+
+fn prev_month(year: i32, month: u32) -> (i32, u32) {
+    if month == 1 {
+        (year - 1, 12)
+    } else {
+        (year, month - 1)
+    }
+}
+
+fn next_month(year: i32, month: u32) -> (i32, u32) {
+    if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    }
+}
+
+/// Local midnight of `year-month-day` in `tz`, as UTC.
+fn local_midnight(tz: Tz, year: i32, month: u32, day: u32) -> DateTime<Utc> {
+    let naive = NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .unwrap_or_default();
+    match tz.from_local_datetime(&naive) {
+        LocalResult::Single(t) | LocalResult::Ambiguous(t, _) => t.with_timezone(&Utc),
+        // Midnight skipped by a DST change: the day starts an hour later.
+        LocalResult::None => tz
+            .from_local_datetime(&(naive + chrono::Duration::hours(1)))
+            .earliest()
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&naive)),
+    }
+}
+
+/// `[start, end)` of the billing period containing `now`.
+pub fn period_containing(
+    anchor_day: u32,
+    tz: Tz,
+    now: DateTime<Utc>,
+) -> (DateTime<Utc>, DateTime<Utc>) {
+    let day = anchor_day.clamp(1, 28);
+    let local = now.with_timezone(&tz);
+    let (mut year, mut month) = (local.year(), local.month());
+    if local.day() < day {
+        (year, month) = prev_month(year, month);
+    }
+    let (next_year, next_month) = next_month(year, month);
+    (
+        local_midnight(tz, year, month, day),
+        local_midnight(tz, next_year, next_month, day),
+    )
+}
+
+fn team_cycle(team: &Team) -> (u32, Tz) {
+    let tz = team.billing_timezone.parse::<Tz>().unwrap_or_else(|_| {
+        tracing::warn!(
+            "Team {} has invalid billing timezone '{}', using UTC",
+            team.id,
+            team.billing_timezone
+        );
+        Tz::UTC
+    });
+    (u32::try_from(team.billing_anchor_day).unwrap_or(1), tz)
+}
+
+/// `[start, end)` of `team`'s current billing period.
+pub fn current_period(team: &Team, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let (day, tz) = team_cycle(team);
+    period_containing(day, tz, now)
+}
+
+/// `[start, end)` of the billing period before `team`'s current one.
+pub fn previous_period(team: &Team, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+    let (day, tz) = team_cycle(team);
+    let (start, _) = period_containing(day, tz, now);
+    period_containing(day, tz, start - chrono::Duration::seconds(1))
+}
+
+/// Spend in cents of `usage`, priced with the prices configured through the
+/// control plane as of `at` and the built-in table for everything else.
+pub fn spend_cents(usage: &[ModelUsage], prices: &[ConfiguredPrice], at: DateTime<Utc>) -> f64 {
+    usage
+        .iter()
+        .map(|u| {
+            pricing::configured_cost_cents(
+                prices,
+                &u.model,
+                at,
+                u.input_tokens.max(0) as u64,
+                u.output_tokens.max(0) as u64,
+            )
+        })
+        .sum()
+}
+
+/// Spend in cents of `team` in its current billing period.
+pub async fn current_spend_cents<D: Database>(
+    db: &D,
+    team: &Team,
+    now: DateTime<Utc>,
+) -> Result<f64, DbError> {
+    let (start, _) = current_period(team, now);
+    let prices = db.list_model_prices().await?;
+    Ok(spend_cents(
+        &db.get_model_usage_since(&team.id, start).await?,
+        &prices,
+        now,
+    ))
+}
+
+/// Current-period spend versus budget.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BillingSummary {
+    pub team_id: String,
+    pub period_start: DateTime<Utc>,
+    pub period_end: DateTime<Utc>,
+    pub budget_cents: i64,
+    pub spend_cents: f64,
+    pub remaining_cents: f64,
+    /// `None` when the team has no budget.
+    pub percent_used: Option<f64>,
+    pub requests: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+}
+
+pub async fn current_summary<D: Database>(
+    db: &D,
+    team: &Team,
+    now: DateTime<Utc>,
+) -> Result<BillingSummary, DbError> {
+    let (period_start, period_end) = current_period(team, now);
+    let usage = db.get_model_usage_since(&team.id, period_start).await?;
+    let prices = db.list_model_prices().await?;
+    let spend = spend_cents(&usage, &prices, now);
+    Ok(BillingSummary {
+        team_id: team.id.clone(),
+        period_start,
+        period_end,
+        budget_cents: team.budget_cents,
+        spend_cents: spend,
+        remaining_cents: (team.budget_cents as f64 - spend).max(0.0),
+        percent_used: (team.budget_cents > 0).then(|| spend / team.budget_cents as f64 * 100.0),
+        requests: usage.iter().map(|u| u.requests).sum(),
+        input_tokens: usage.iter().map(|u| u.input_tokens).sum(),
+        output_tokens: usage.iter().map(|u| u.output_tokens).sum(),
+    })
+}
+
+pub struct BillingPeriodCloser<D: Database> {
+    db: D,
+    /// `(team_id, period_start)` pairs already snapshotted by this process.
+    closed: Mutex<HashSet<(String, DateTime<Utc>)>>,
+}
+
+impl<D: Database> BillingPeriodCloser<D> {
+    pub fn new(db: D) -> Self {
+        Self {
+            db,
+            closed: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Close finished periods once on `interval` until `cancel` fires,
+    /// skipping ticks while this replica is not the scheduler leader.
+    pub fn spawn(
+        self,
+        interval: Duration,
+        leader: Leadership,
+        cancel: CancellationToken,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = ticker.tick() => {
+                        if !leader.is_leader() {
+                            continue;
+                        }
+                        if let Err(e) = self.close_once(Utc::now()).await {
+                            tracing::error!("Closing billing periods failed: {:?}", e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Snapshot every team's previous period as of `now`.  Returns the number
+    /// of periods newly recorded.
+    pub async fn close_once(&self, now: DateTime<Utc>) -> Result<usize, DbError> {
+        let mut recorded = 0;
+        let prices = self.db.list_model_prices().await?;
+        for team in self.db.list_teams().await? {
+            let (period_start, period_end) = previous_period(&team, now);
+            if period_end <= team.created_at {
+                continue;
+            }
+            let marker = (team.id.clone(), period_start);
+            if self.is_closed(&marker) {
+                continue;
+            }
+
+            let usage = match self
+                .db
+                .get_model_usage_between(&team.id, period_start, period_end)
+                .await
+            {
+                Ok(usage) => usage,
+                Err(e) => {
+                    tracing::warn!("Failed to load usage for team {}: {:?}", team.id, e);
+                    continue;
+                }
+            };
+            let period = BillingPeriod {
+                id: String::new(),
+                team_id: team.id.clone(),
+                period_start,
+                period_end,
+                requests: usage.iter().map(|u| u.requests).sum(),
+                input_tokens: usage.iter().map(|u| u.input_tokens).sum(),
+                output_tokens: usage.iter().map(|u| u.output_tokens).sum(),
+                spend_cents: spend_cents(&usage, &prices, period_end),
+                budget_cents: team.budget_cents,
+                closed_at: now,
+            };
+            match self.db.record_billing_period(&period).await {
+                Ok(Some(_)) => {
+                    tracing::info!(
+                        "Closed billing period {} - {} for team {}",
+                        period_start,
+                        period_end,
+                        team.id
+                    );
+                    recorded += 1;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to record billing period for team {}: {:?}",
+                        team.id,
+                        e
+                    );
+                    continue;
+                }
+            }
+            self.closed
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(marker);
+        }
+        Ok(recorded)
+    }
+
+    fn is_closed(&self, marker: &(String, DateTime<Utc>)) -> bool {
+        self.closed
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(marker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    fn team(anchor_day: i32, timezone: &str) -> Team {
+        Team {
+            id: "team-1".to_string(),
+            name: "Research".to_string(),
+            budget_cents: 10_000,
+            billing_anchor_day: anchor_day,
+            billing_timezone: timezone.to_string(),
+            created_at: utc(2023, 1, 1, 0, 0),
+            updated_at: utc(2023, 1, 1, 0, 0),
+            organization_id: None,
+            data_region: None,
+            features: Default::default(),
+            param_clamps: None,
+            output_transforms: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_validate_billing() {
+        assert!(validate_billing(1, "UTC").is_ok());
+        assert!(validate_billing(28, "America/New_York").is_ok());
+        assert!(validate_billing(0, "UTC").is_err());
+        assert!(validate_billing(29, "UTC").is_err());
+        assert!(validate_billing(15, "Mars/Olympus_Mons").is_err());
+    }
+
+    #[test]
+    fn test_period_calendar_month_utc() {
+        let (start, end) = period_containing(1, Tz::UTC, utc(2024, 3, 17, 13, 45));
+        assert_eq!(start, utc(2024, 3, 1, 0, 0));
+        assert_eq!(end, utc(2024, 4, 1, 0, 0));
+    }
+
+    #[test]
+    fn test_period_before_anchor_day_uses_previous_month() {
+        let (start, end) = period_containing(15, Tz::UTC, utc(2024, 1, 10, 0, 0));
+        assert_eq!(start, utc(2023, 12, 15, 0, 0));
+        assert_eq!(end, utc(2024, 1, 15, 0, 0));
+    }
+
+    #[test]
+    fn test_period_on_anchor_day_starts_new_period() {
+        let (start, _) = period_containing(15, Tz::UTC, utc(2024, 1, 15, 0, 0));
+        assert_eq!(start, utc(2024, 1, 15, 0, 0));
+    }
+
+    #[test]
+    fn test_period_respects_timezone() {
+        // 2024-03-01 03:00 UTC is still Feb 29 in New York (UTC-5).
+        let tz: Tz = "America/New_York".parse().unwrap();
+        let (start, end) = period_containing(1, tz, utc(2024, 3, 1, 3, 0));
+        assert_eq!(start, utc(2024, 2, 1, 5, 0));
+        assert_eq!(end, utc(2024, 3, 1, 5, 0));
+    }
+
+    #[test]
+    fn test_previous_period() {
+        let (start, end) = previous_period(&team(10, "UTC"), utc(2024, 5, 20, 12, 0));
+        assert_eq!(start, utc(2024, 4, 10, 0, 0));
+        assert_eq!(end, utc(2024, 5, 10, 0, 0));
+    }
+
+    #[test]
+    fn test_invalid_team_timezone_falls_back_to_utc() {
+        let (start, _) = current_period(&team(1, "Not/AZone"), utc(2024, 6, 3, 0, 0));
+        assert_eq!(start, utc(2024, 6, 1, 0, 0));
+    }
+
+    #[test]
+    fn test_spend_cents() {
+        let usage = vec![ModelUsage {
+            model: "gpt-4o".to_string(),
+            requests: 3,
+            input_tokens: 1_000_000,
+            output_tokens: 1_000_000,
+        }];
+        assert!((spend_cents(&usage, &[], utc(2024, 6, 1, 0, 0)) - 1250.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_spend_cents_uses_configured_prices() {
+        let usage = vec![ModelUsage {
+            model: "gpt-4o".to_string(),
+            requests: 1,
+            input_tokens: 1_000_000,
+            output_tokens: 0,
+        }];
+        let prices = vec![ConfiguredPrice {
+            id: "p1".to_string(),
+            model: "gpt-4o".to_string(),
+            provider: "openai".to_string(),
+            input_per_mtok: 5.0,
+            output_per_mtok: 15.0,
+            effective_from: utc(2024, 6, 1, 0, 0),
+        }];
+        assert!((spend_cents(&usage, &prices, utc(2024, 5, 31, 0, 0)) - 250.0).abs() < 1e-9);
+        assert!((spend_cents(&usage, &prices, utc(2024, 6, 2, 0, 0)) - 500.0).abs() < 1e-9);
+    }
+}
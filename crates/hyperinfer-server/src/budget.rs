@@ -0,0 +1,334 @@
+//! Budget auto-actions.
+//!
+//! A [`BudgetEnforcer`] compares each team's spend in its current billing
+//! period against its budget and, once the budget is used up, applies the team's
+//! [`BudgetPolicy`] by publishing a [`PolicyUpdate`] for every active key:
+//!
+//! * `warn` — clients log a soft-limit warning; traffic is not affected.
+//! * `throttle` — clients cap each key at `throttle_rpm` requests per minute.
+//! * `block` — clients reject requests for the key.
+//!
+//! A team in an organization is also over budget once the organization's
+//! budget is used up by the combined spend of all its teams, and then gets
+//! its own policy's action like any other over-budget team.
+//!
+//! Throttle and block updates are re-published on every tick while the
+//! budget stays exhausted, so keys created afterwards and clients that
+//! restarted pick them up.  Applying an action also sends the team's
+//! `budget.exceeded` webhooks.  When spend drops back under budget — normally when
+//! the next billing period starts — a `restore` update lifts the action again.
+
+use crate::billing;
+use crate::leader::Leadership;
+use crate::webhooks::{self, WebhookEvent};
+use chrono::{DateTime, Utc};
+use hyperinfer_core::{
+    BudgetPolicy, ConfigStore, Database, DbError, PolicyAction, PolicyUpdate, Team,
+};
+use serde_json::json;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetAction {
+    Warn,
+    Throttle,
+    Block,
+}
+
+impl BudgetAction {
+    pub fn parse(action: &str) -> Option<Self> {
+        match action {
+            "warn" => Some(Self::Warn),
+            "throttle" => Some(Self::Throttle),
+            "block" => Some(Self::Block),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Warn => "warn",
+            Self::Throttle => "throttle",
+            Self::Block => "block",
+        }
+    }
+
+    fn policy_action(&self) -> PolicyAction {
+        match self {
+            Self::Warn => PolicyAction::Warn,
+            Self::Throttle => PolicyAction::Throttle,
+            Self::Block => PolicyAction::Revoke,
+        }
+    }
+}
+
+/// Check a budget policy submitted through the admin API.
+pub fn validate_policy(action: &str, throttle_rpm: Option<i32>) -> Result<BudgetAction, String> {
+    let parsed = BudgetAction::parse(action).ok_or_else(|| {
+        format!(
+            "Unknown budget action '{}': expected warn, throttle or block",
+            action
+        )
+    })?;
+    if parsed == BudgetAction::Throttle && throttle_rpm.is_none_or(|rpm| rpm <= 0) {
+        return Err("throttle policies require a positive throttle_rpm".to_string());
+    }
+    Ok(parsed)
+}
+
+fn policy_update(
+    key_hash: &str,
+    action: PolicyAction,
+    throttle_rpm: Option<i32>,
+    reason: &str,
+) -> PolicyUpdate {
+    PolicyUpdate {
+        key: key_hash.to_string(),
+        action,
+        reason: Some(reason.to_string()),
+        rpm_limit: match action {
+            PolicyAction::Throttle => throttle_rpm.and_then(|rpm| u64::try_from(rpm).ok()),
+            _ => None,
+        },
+    }
+}
+
+pub struct BudgetEnforcer<D: Database, C: ConfigStore> {
+    db: D,
+    config_store: C,
+}
+
+impl<D: Database, C: ConfigStore> BudgetEnforcer<D, C> {
+    pub fn new(db: D, config_store: C) -> Self {
+        Self { db, config_store }
+    }
+
+    /// Evaluate every policy once on `interval` until `cancel` fires,
+    /// skipping ticks while this replica is not the scheduler leader.
+    pub fn spawn(
+        self,
+        interval: Duration,
+        leader: Leadership,
+        cancel: CancellationToken,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = ticker.tick() => {
+                        if !leader.is_leader() {
+                            continue;
+                        }
+                        if let Err(e) = self.evaluate_once(Utc::now()).await {
+                            tracing::error!("Budget enforcement failed: {:?}", e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Check all budget policies as of `now`.  Returns the number of teams
+    /// whose enforcement state changed.
+    pub async fn evaluate_once(&self, now: DateTime<Utc>) -> Result<usize, DbError> {
+        let mut changed = 0;
+        for policy in self.db.list_budget_policies().await? {
+            match self.evaluate_policy(&policy, now).await {
+                Ok(true) => changed += 1,
+                Ok(false) => {}
+                Err(e) => tracing::warn!(
+                    "Failed to evaluate budget policy for team {}: {:?}",
+                    policy.team_id,
+                    e
+                ),
+            }
+        }
+        Ok(changed)
+    }
+
+    async fn evaluate_policy(
+        &self,
+        policy: &BudgetPolicy,
+        now: DateTime<Utc>,
+    ) -> Result<bool, DbError> {
+        let Some(action) = BudgetAction::parse(&policy.action) else {
+            tracing::warn!(
+                "Skipping budget policy for team {} with unknown action '{}'",
+                policy.team_id,
+                policy.action
+            );
+            return Ok(false);
+        };
+        let (over_budget, reason) = match self.db.get_team(&policy.team_id).await? {
+            Some(team) => {
+                if team.budget_cents > 0
+                    && billing::current_spend_cents(&self.db, &team, now).await?
+                        >= team.budget_cents as f64
+                {
+                    (true, "Team budget exhausted")
+                } else if self.organization_over_budget(&team, now).await? {
+                    (true, "Organization budget exhausted")
+                } else {
+                    (false, "")
+                }
+            }
+            None => (false, ""),
+        };
+        let enforced = policy.enforced_action.as_deref();
+
+        if !over_budget {
+            if enforced.is_none() {
+                return Ok(false);
+            }
+            if !self
+                .publish_all(&policy.team_id, PolicyAction::Restore, None, "Budget reset")
+                .await?
+            {
+                return Ok(false);
+            }
+            self.db
+                .set_budget_enforcement(&policy.team_id, None)
+                .await?;
+            tracing::info!("Lifted budget action for team {}", policy.team_id);
+            return Ok(true);
+        }
+
+        let newly_enforced = enforced != Some(action.as_str());
+        if !newly_enforced && action == BudgetAction::Warn {
+            return Ok(false);
+        }
+        if newly_enforced
+            && enforced.is_some()
+            && !self
+                .publish_all(
+                    &policy.team_id,
+                    PolicyAction::Restore,
+                    None,
+                    "Budget policy changed",
+                )
+                .await?
+        {
+            return Ok(false);
+        }
+
+        let published = self
+            .publish_all(
+                &policy.team_id,
+                action.policy_action(),
+                policy.throttle_rpm,
+                reason,
+            )
+            .await?;
+        if !newly_enforced || !published {
+            return Ok(false);
+        }
+        self.db
+            .set_budget_enforcement(&policy.team_id, Some(action.as_str().to_string()))
+            .await?;
+        webhooks::emit(
+            &self.db,
+            Some(&policy.team_id),
+            WebhookEvent::BudgetExceeded,
+            json!({ "action": action.as_str(), "reason": reason }),
+        )
+        .await;
+        tracing::warn!(
+            "Team {}: {}; applied '{}'",
+            policy.team_id,
+            reason,
+            action.as_str()
+        );
+        Ok(true)
+    }
+
+    /// Whether `team`'s organization has a budget that its teams' combined
+    /// spend, each in its own current billing period, has used up.
+    async fn organization_over_budget(
+        &self,
+        team: &Team,
+        now: DateTime<Utc>,
+    ) -> Result<bool, DbError> {
+        let Some(org_id) = team.organization_id.as_deref() else {
+            return Ok(false);
+        };
+        let budget = match self.db.get_organization(org_id).await? {
+            Some(org) if org.budget_cents > 0 => org.budget_cents as f64,
+            _ => return Ok(false),
+        };
+        let mut spend = 0.0;
+        for member in self.db.list_teams().await? {
+            if member.organization_id.as_deref() == Some(org_id) {
+                spend += billing::current_spend_cents(&self.db, &member, now).await?;
+            }
+        }
+        Ok(spend >= budget)
+    }
+
+    /// Publish `action` for every active key of the team.  Returns whether
+    /// every update was published.
+    async fn publish_all(
+        &self,
+        team_id: &str,
+        action: PolicyAction,
+        throttle_rpm: Option<i32>,
+        reason: &str,
+    ) -> Result<bool, DbError> {
+        let mut all_published = true;
+        for key in self.db.list_api_keys_for_team(team_id).await? {
+            let update = policy_update(&key.key_hash, action, throttle_rpm, reason);
+            if let Err(e) = self.config_store.publish_policy_update(&update).await {
+                tracing::warn!(
+                    "Failed to publish {:?} for api key {}: {:?}",
+                    action,
+                    key.id,
+                    e
+                );
+                all_published = false;
+            }
+        }
+        Ok(all_published)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_action_parse() {
+        assert_eq!(BudgetAction::parse("warn"), Some(BudgetAction::Warn));
+        assert_eq!(
+            BudgetAction::parse("throttle"),
+            Some(BudgetAction::Throttle)
+        );
+        assert_eq!(BudgetAction::parse("block"), Some(BudgetAction::Block));
+        assert_eq!(BudgetAction::parse("suspend"), None);
+        assert_eq!(BudgetAction::Block.as_str(), "block");
+    }
+
+    #[test]
+    fn test_validate_policy() {
+        assert_eq!(validate_policy("warn", None), Ok(BudgetAction::Warn));
+        assert_eq!(validate_policy("block", None), Ok(BudgetAction::Block));
+        assert_eq!(
+            validate_policy("throttle", Some(10)),
+            Ok(BudgetAction::Throttle)
+        );
+        assert!(validate_policy("throttle", None).is_err());
+        assert!(validate_policy("throttle", Some(0)).is_err());
+        assert!(validate_policy("pause", None).is_err());
+    }
+
+    #[test]
+    fn test_policy_update_rpm_only_for_throttle() {
+        let throttle = policy_update("hash", PolicyAction::Throttle, Some(5), "over");
+        assert_eq!(throttle.rpm_limit, Some(5));
+        assert_eq!(throttle.key, "hash");
+
+        let revoke = policy_update("hash", PolicyAction::Revoke, Some(5), "over");
+        assert_eq!(revoke.rpm_limit, None);
+        assert_eq!(revoke.reason.as_deref(), Some("over"));
+    }
+}
@@ -0,0 +1,433 @@
+//! Import and export bundles.
+//!
+//! A [`Bundle`] travels as JSON or as a single CSV file with one row per
+//! record.  The `kind` column says what a row is (`team`, `user`,
+//! `api_key`, `model_alias` or `quota`); the other columns are shared and
+//! left empty where they do not apply:
+//!
+//! | kind          | columns                                                                      |
+//! |---------------|------------------------------------------------------------------------------|
+//! | `team`        | `name`, `budget_cents`, `billing_anchor_day`, `billing_timezone`             |
+//! | `user`        | `email`, `team`, `role`                                                      |
+//! | `api_key`     | `key_hash`, `team`, `user`, `name`, `is_active`, `expires_at`, `tags`, `allowed_models`, `budget_cents` |
+//! | `model_alias` | `team`, `alias`, `target_model`, `provider`                                  |
+//! | `quota`       | `team`, `rpm_limit`, `tpm_limit`                                             |
+//!
+//! `team` and `user` refer to a team name and a user email.  `tags` and
+//! `allowed_models` are `;`-separated.
+
+use crate::billing;
+use chrono::{DateTime, Utc};
+use hyperinfer_core::{
+    Bundle, BundleApiKey, BundleModelAlias, BundleQuota, BundleTeam, BundleUser, Role,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Most records one import may contain.
+pub const MAX_RECORDS: usize = 50_000;
+
+/// One CSV row; see the module docs for which columns each kind uses.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Row {
+    kind: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    team: Option<String>,
+    #[serde(default)]
+    role: Option<String>,
+    #[serde(default)]
+    budget_cents: Option<i64>,
+    #[serde(default)]
+    billing_anchor_day: Option<i32>,
+    #[serde(default)]
+    billing_timezone: Option<String>,
+    #[serde(default)]
+    key_hash: Option<String>,
+    #[serde(default)]
+    user: Option<String>,
+    #[serde(default)]
+    is_active: Option<bool>,
+    #[serde(default)]
+    expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    tags: Option<String>,
+    #[serde(default)]
+    allowed_models: Option<String>,
+    #[serde(default)]
+    alias: Option<String>,
+    #[serde(default)]
+    target_model: Option<String>,
+    #[serde(default)]
+    provider: Option<String>,
+    #[serde(default)]
+    rpm_limit: Option<i32>,
+    #[serde(default)]
+    tpm_limit: Option<i32>,
+}
+
+fn join_list(items: &[String]) -> Option<String> {
+    (!items.is_empty()).then(|| items.join(";"))
+}
+
+fn split_list(value: Option<String>) -> Vec<String> {
+    value
+        .map(|v| {
+            v.split(';')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn to_csv(bundle: &Bundle) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for team in &bundle.teams {
+        writer.serialize(Row {
+            kind: "team".to_string(),
+            name: Some(team.name.clone()),
+            budget_cents: Some(team.budget_cents),
+            billing_anchor_day: Some(team.billing_anchor_day),
+            billing_timezone: Some(team.billing_timezone.clone()),
+            ..Default::default()
+        })?;
+    }
+    for user in &bundle.users {
+        writer.serialize(Row {
+            kind: "user".to_string(),
+            email: Some(user.email.clone()),
+            team: Some(user.team.clone()),
+            role: Some(user.role.as_str().to_string()),
+            ..Default::default()
+        })?;
+    }
+    for key in &bundle.api_keys {
+        writer.serialize(Row {
+            kind: "api_key".to_string(),
+            key_hash: Some(key.key_hash.clone()),
+            team: Some(key.team.clone()),
+            user: key.user.clone(),
+            name: key.name.clone(),
+            is_active: Some(key.is_active),
+            expires_at: key.expires_at,
+            tags: join_list(&key.tags),
+            allowed_models: join_list(&key.allowed_models),
+            budget_cents: key.budget_cents,
+            ..Default::default()
+        })?;
+    }
+    for alias in &bundle.model_aliases {
+        writer.serialize(Row {
+            kind: "model_alias".to_string(),
+            team: Some(alias.team.clone()),
+            alias: Some(alias.alias.clone()),
+            target_model: Some(alias.target_model.clone()),
+            provider: Some(alias.provider.clone()),
+            ..Default::default()
+        })?;
+    }
+    for quota in &bundle.quotas {
+        writer.serialize(Row {
+            kind: "quota".to_string(),
+            team: Some(quota.team.clone()),
+            rpm_limit: Some(quota.rpm_limit),
+            tpm_limit: Some(quota.tpm_limit),
+            ..Default::default()
+        })?;
+    }
+    writer.into_inner().map_err(|e| e.into_error().into())
+}
+
+/// Parse a CSV bundle.  Errors name the offending line.
+pub fn from_csv(data: &[u8]) -> Result<Bundle, String> {
+    let mut reader = csv::Reader::from_reader(data);
+    let mut bundle = Bundle::default();
+    for (i, row) in reader.deserialize::<Row>().enumerate() {
+        // Line 1 is the header.
+        let line = i + 2;
+        let row = row.map_err(|e| format!("line {}: {}", line, e))?;
+        let required = |field: &str, value: Option<String>| {
+            value.ok_or_else(|| format!("line {}: {} rows need a {}", line, row.kind, field))
+        };
+        match row.kind.as_str() {
+            "team" => bundle.teams.push(BundleTeam {
+                name: required("name", row.name.clone())?,
+                budget_cents: row.budget_cents.unwrap_or_default(),
+                billing_anchor_day: row.billing_anchor_day.unwrap_or(1),
+                billing_timezone: row
+                    .billing_timezone
+                    .clone()
+                    .unwrap_or_else(|| "UTC".to_string()),
+            }),
+            "user" => bundle.users.push(BundleUser {
+                email: required("email", row.email.clone())?,
+                team: required("team", row.team.clone())?,
+                role: match &row.role {
+                    Some(role) => role
+                        .parse()
+                        .map_err(|_| format!("line {}: unknown role '{}'", line, role))?,
+                    None => Role::default(),
+                },
+            }),
+            "api_key" => bundle.api_keys.push(BundleApiKey {
+                key_hash: required("key_hash", row.key_hash.clone())?,
+                team: required("team", row.team.clone())?,
+                user: row.user.clone(),
+                name: row.name.clone(),
+                is_active: row.is_active.unwrap_or(true),
+                expires_at: row.expires_at,
+                tags: split_list(row.tags.clone()),
+                allowed_models: split_list(row.allowed_models.clone()),
+                budget_cents: row.budget_cents,
+            }),
+            "model_alias" => bundle.model_aliases.push(BundleModelAlias {
+                team: required("team", row.team.clone())?,
+                alias: required("alias", row.alias.clone())?,
+                target_model: required("target_model", row.target_model.clone())?,
+                provider: required("provider", row.provider.clone())?,
+            }),
+            "quota" => bundle.quotas.push(BundleQuota {
+                team: required("team", row.team.clone())?,
+                rpm_limit: row
+                    .rpm_limit
+                    .ok_or_else(|| format!("line {}: quota rows need a rpm_limit", line))?,
+                tpm_limit: row
+                    .tpm_limit
+                    .ok_or_else(|| format!("line {}: quota rows need a tpm_limit", line))?,
+            }),
+            other => return Err(format!("line {}: unknown kind '{}'", line, other)),
+        }
+    }
+    Ok(bundle)
+}
+
+/// Every problem with `bundle`, checked against what the deployment already
+/// holds in `existing`.  Empty when the bundle can be imported.
+pub fn validate(bundle: &Bundle, existing: &Bundle) -> Vec<String> {
+    let mut errors = Vec::new();
+    let records = bundle.teams.len()
+        + bundle.users.len()
+        + bundle.api_keys.len()
+        + bundle.model_aliases.len()
+        + bundle.quotas.len();
+    if records > MAX_RECORDS {
+        errors.push(format!(
+            "At most {} records can be imported at once",
+            MAX_RECORDS
+        ));
+        return errors;
+    }
+
+    let mut duplicate = |kind: &str, seen: &mut HashSet<String>, key: String| {
+        if !seen.insert(key.clone()) {
+            errors.push(format!("Duplicate {} '{}'", kind, key));
+        }
+    };
+    let mut team_names = HashSet::new();
+    for team in &bundle.teams {
+        duplicate("team", &mut team_names, team.name.clone());
+    }
+    let mut emails = HashSet::new();
+    for user in &bundle.users {
+        duplicate("user", &mut emails, user.email.clone());
+    }
+    let mut key_hashes = HashSet::new();
+    for key in &bundle.api_keys {
+        duplicate("api key", &mut key_hashes, key.key_hash.clone());
+    }
+    let mut aliases = HashSet::new();
+    for alias in &bundle.model_aliases {
+        duplicate(
+            "model alias",
+            &mut aliases,
+            format!("{}/{}", alias.team, alias.alias),
+        );
+    }
+    let mut quota_teams = HashSet::new();
+    for quota in &bundle.quotas {
+        duplicate("quota for team", &mut quota_teams, quota.team.clone());
+    }
+
+    for team in &bundle.teams {
+        if team.name.trim().is_empty() {
+            errors.push("Team names must not be empty".to_string());
+        }
+        if team.budget_cents < 0 {
+            errors.push(format!(
+                "Team '{}': budget_cents must not be negative",
+                team.name
+            ));
+        }
+        if let Err(msg) = billing::validate_billing(team.billing_anchor_day, &team.billing_timezone)
+        {
+            errors.push(format!("Team '{}': {}", team.name, msg));
+        }
+    }
+
+    let known_teams: HashSet<&str> = bundle
+        .teams
+        .iter()
+        .chain(&existing.teams)
+        .map(|t| t.name.as_str())
+        .collect();
+    let known_users: HashSet<&str> = bundle
+        .users
+        .iter()
+        .chain(&existing.users)
+        .map(|u| u.email.as_str())
+        .collect();
+    let mut check_team = |what: String, team: &str| {
+        if !known_teams.contains(team) {
+            errors.push(format!("{}: unknown team '{}'", what, team));
+        }
+    };
+    for user in &bundle.users {
+        check_team(format!("User '{}'", user.email), &user.team);
+    }
+    for key in &bundle.api_keys {
+        check_team(format!("API key '{}'", key.key_hash), &key.team);
+    }
+    for alias in &bundle.model_aliases {
+        check_team(format!("Model alias '{}'", alias.alias), &alias.team);
+    }
+    for quota in &bundle.quotas {
+        check_team("Quota".to_string(), &quota.team);
+    }
+
+    for user in &bundle.users {
+        if !user.email.contains('@') {
+            errors.push(format!("User '{}': invalid email", user.email));
+        }
+    }
+    for key in &bundle.api_keys {
+        if key.key_hash.trim().is_empty() {
+            errors.push("API key hashes must not be empty".to_string());
+        }
+        if let Some(user) = &key.user {
+            if !known_users.contains(user.as_str()) {
+                errors.push(format!(
+                    "API key '{}': unknown user '{}'",
+                    key.key_hash, user
+                ));
+            }
+        }
+        if key.budget_cents.is_some_and(|b| b < 0) {
+            errors.push(format!(
+                "API key '{}': budget_cents must not be negative",
+                key.key_hash
+            ));
+        }
+    }
+    for alias in &bundle.model_aliases {
+        if [&alias.alias, &alias.target_model, &alias.provider]
+            .iter()
+            .any(|f| f.trim().is_empty())
+        {
+            errors.push(format!(
+                "Model alias '{}': alias, target_model and provider must not be empty",
+                alias.alias
+            ));
+        }
+    }
+    for quota in &bundle.quotas {
+        if quota.rpm_limit <= 0 || quota.tpm_limit <= 0 {
+            errors.push(format!(
+                "Quota for team '{}': limits must be positive",
+                quota.team
+            ));
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle() -> Bundle {
+        Bundle {
+            teams: vec![BundleTeam {
+                name: "research".to_string(),
+                budget_cents: 50_000,
+                billing_anchor_day: 1,
+                billing_timezone: "UTC".to_string(),
+            }],
+            users: vec![BundleUser {
+                email: "ada@example.com".to_string(),
+                team: "research".to_string(),
+                role: Role::Admin,
+            }],
+            api_keys: vec![BundleApiKey {
+                key_hash: "abc123".to_string(),
+                team: "research".to_string(),
+                user: Some("ada@example.com".to_string()),
+                name: Some("notebook".to_string()),
+                is_active: true,
+                expires_at: None,
+                tags: vec!["batch".to_string(), "eval".to_string()],
+                allowed_models: vec![],
+                budget_cents: Some(1_000),
+            }],
+            model_aliases: vec![BundleModelAlias {
+                team: "research".to_string(),
+                alias: "fast".to_string(),
+                target_model: "gpt-4o-mini".to_string(),
+                provider: "openai".to_string(),
+            }],
+            quotas: vec![BundleQuota {
+                team: "research".to_string(),
+                rpm_limit: 600,
+                tpm_limit: 1_000_000,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let csv = to_csv(&bundle()).unwrap();
+        let text = String::from_utf8(csv.clone()).unwrap();
+        assert!(text.starts_with("kind,name,email,team,role,"));
+        assert!(text.contains("batch;eval"));
+        assert_eq!(from_csv(&csv).unwrap(), bundle());
+    }
+
+    #[test]
+    fn test_from_csv_defaults_and_errors() {
+        let csv = "kind,name,team,rpm_limit,tpm_limit\nteam,ops,,,\nquota,,ops,10,1000\n";
+        let parsed = from_csv(csv.as_bytes()).unwrap();
+        assert_eq!(parsed.teams[0].billing_timezone, "UTC");
+        assert_eq!(parsed.quotas[0].rpm_limit, 10);
+
+        let err = from_csv(b"kind,name\nteam,\n").unwrap_err();
+        assert_eq!(err, "line 2: team rows need a name");
+        let err = from_csv(b"kind,name\nproject,x\n").unwrap_err();
+        assert!(err.contains("unknown kind 'project'"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&bundle(), &Bundle::default()).is_empty());
+
+        let mut bad = bundle();
+        bad.teams.clear();
+        bad.users.push(bad.users[0].clone());
+        bad.quotas[0].rpm_limit = 0;
+        let errors = validate(&bad, &Bundle::default());
+        assert!(errors.contains(&"Duplicate user 'ada@example.com'".to_string()));
+        assert!(errors.iter().any(|e| e.contains("unknown team 'research'")));
+        assert!(errors.iter().any(|e| e.contains("limits must be positive")));
+
+        // Teams may already exist in the deployment.
+        let existing = Bundle {
+            teams: bundle().teams,
+            ..Default::default()
+        };
+        let errors = validate(&bad, &existing);
+        assert!(!errors.iter().any(|e| e.contains("unknown team")));
+    }
+}
@@ -0,0 +1,486 @@
+//! ClickHouse usage sink.
+//!
+//! At millions of requests a day, aggregating `usage_logs` in Postgres gets
+//! slow.  With the `clickhouse` feature, and `clickhouse` listed in
+//! `USAGE_BACKEND`, usage records are also (or instead) written to a
+//! ClickHouse `MergeTree` table over its HTTP interface.  Rows are buffered
+//! by a [`ClickHouseWriter`] and inserted `CLICKHOUSE_BATCH_SIZE` at a time,
+//! or every `CLICKHOUSE_FLUSH_MS`, whichever comes first; ClickHouse copes
+//! far better with few large inserts than with many small ones.
+//!
+//! The telemetry stream acknowledges a record once it is buffered, so rows
+//! still in the buffer are lost if the process dies; a cancelled writer
+//! flushes what it holds before stopping.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hyperinfer_core::telemetry_sink::{SinkError, TelemetrySink};
+use hyperinfer_core::UsageEvent;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+pub const DEFAULT_TABLE: &str = "usage_logs";
+pub const DEFAULT_BATCH_SIZE: usize = 10_000;
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Attempts at inserting a batch before its rows are dropped.
+const MAX_INSERT_ATTEMPTS: u32 = 5;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClickHouseError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("ClickHouse returned {status}: {body}")]
+    Status {
+        status: reqwest::StatusCode,
+        body: String,
+    },
+    #[error("Failed to encode row: {0}")]
+    Encode(#[from] serde_json::Error),
+    #[error("ClickHouse writer has stopped")]
+    Closed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClickHouseSettings {
+    /// `CLICKHOUSE_URL`, the HTTP interface, e.g. `http://clickhouse:8123`.
+    /// A `?database=` parameter selects the database.
+    pub url: url::Url,
+    /// `CLICKHOUSE_TABLE`.
+    pub table: String,
+    /// `CLICKHOUSE_USER`.
+    pub user: Option<String>,
+    /// `CLICKHOUSE_PASSWORD`.
+    pub password: Option<String>,
+    /// `CLICKHOUSE_BATCH_SIZE`: rows per insert.
+    pub batch_size: usize,
+    /// `CLICKHOUSE_FLUSH_MS`: the longest a row waits in the buffer.
+    pub flush_interval: Duration,
+}
+
+impl ClickHouseSettings {
+    /// Settings from the variables `var` looks up, or `None` without a
+    /// `CLICKHOUSE_URL`.
+    pub fn from_vars(var: &dyn Fn(&str) -> Option<String>) -> Result<Option<Self>, String> {
+        let var = |name: &str| var(name).filter(|value| !value.is_empty());
+        let Some(url) = var("CLICKHOUSE_URL") else {
+            return Ok(None);
+        };
+        let url = url::Url::parse(&url)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| format!("Invalid CLICKHOUSE_URL '{}': expected an http(s) URL", url))?;
+        let table = var("CLICKHOUSE_TABLE").unwrap_or_else(|| DEFAULT_TABLE.to_string());
+        if !is_valid_table(&table) {
+            return Err(format!(
+                "Invalid CLICKHOUSE_TABLE '{}': expected `table` or `database.table`",
+                table
+            ));
+        }
+        let batch_size = match var("CLICKHOUSE_BATCH_SIZE") {
+            Some(size) => size
+                .trim()
+                .parse()
+                .ok()
+                .filter(|size| *size > 0)
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid CLICKHOUSE_BATCH_SIZE '{}': expected a positive integer",
+                        size
+                    )
+                })?,
+            None => DEFAULT_BATCH_SIZE,
+        };
+        let flush_interval = match var("CLICKHOUSE_FLUSH_MS") {
+            Some(ms) => ms
+                .trim()
+                .parse()
+                .ok()
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis)
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid CLICKHOUSE_FLUSH_MS '{}': expected a positive number of milliseconds",
+                        ms
+                    )
+                })?,
+            None => DEFAULT_FLUSH_INTERVAL,
+        };
+        Ok(Some(Self {
+            url,
+            table,
+            user: var("CLICKHOUSE_USER"),
+            password: var("CLICKHOUSE_PASSWORD"),
+            batch_size,
+            flush_interval,
+        }))
+    }
+}
+
+/// Table names are interpolated into queries, so only plain identifiers are
+/// accepted.
+fn is_valid_table(table: &str) -> bool {
+    let parts: Vec<&str> = table.split('.').collect();
+    parts.len() <= 2
+        && parts.iter().all(|part| {
+            !part.is_empty()
+                && !part.starts_with(|c: char| c.is_ascii_digit())
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+}
+
+/// `CREATE TABLE` for the usage table.  Ordered by team and time, the shape
+/// of every usage query; partitioned by month so old data can be dropped
+/// a partition at a time.
+pub fn create_table_sql(table: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (\
+         recorded_at DateTime64(3, 'UTC'), \
+         team_id String, \
+         api_key_id String, \
+         model LowCardinality(String), \
+         provider LowCardinality(String), \
+         input_tokens UInt32, \
+         output_tokens UInt32, \
+         response_time_ms UInt64, \
+         error String, \
+         metadata Map(String, String)\
+         ) ENGINE = MergeTree \
+         PARTITION BY toYYYYMM(recorded_at) \
+         ORDER BY (team_id, recorded_at)",
+        table
+    )
+}
+
+/// One usage record, as a `JSONEachRow` row.  Failed requests carry their
+/// error and no tokens.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UsageRow {
+    pub recorded_at: String,
+    pub team_id: String,
+    pub api_key_id: String,
+    pub model: String,
+    pub provider: String,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub response_time_ms: u64,
+    pub error: String,
+    pub metadata: HashMap<String, String>,
+}
+
+impl UsageRow {
+    pub fn new(event: &UsageEvent) -> Self {
+        let recorded_at = i64::try_from(event.timestamp)
+            .ok()
+            .and_then(DateTime::<Utc>::from_timestamp_millis)
+            .unwrap_or_else(Utc::now);
+        Self {
+            // The column is UTC; ClickHouse does not parse RFC 3339 offsets by
+            // default.
+            recorded_at: recorded_at.format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+            team_id: event.team_id.clone(),
+            api_key_id: event.api_key_id.clone(),
+            model: event.model.clone(),
+            provider: event.provider.clone().unwrap_or_default(),
+            input_tokens: event.input_tokens,
+            output_tokens: event.output_tokens,
+            response_time_ms: event.response_time_ms,
+            error: event.error.clone().unwrap_or_default(),
+            metadata: event.metadata.clone(),
+        }
+    }
+}
+
+/// Newline-delimited JSON, the body of an `INSERT ... FORMAT JSONEachRow`.
+pub fn encode_rows(rows: &[UsageRow]) -> Result<Vec<u8>, serde_json::Error> {
+    let mut body = Vec::new();
+    for row in rows {
+        serde_json::to_writer(&mut body, row)?;
+        body.push(b'\n');
+    }
+    Ok(body)
+}
+
+/// Hands rows to a running [`ClickHouseWriter`].
+#[derive(Debug, Clone)]
+pub struct ClickHouseSink {
+    tx: mpsc::Sender<UsageRow>,
+}
+
+impl ClickHouseSink {
+    /// Buffer `row`, waiting while the buffer is full.
+    pub async fn send(&self, row: UsageRow) -> Result<(), ClickHouseError> {
+        self.tx.send(row).await.map_err(|_| ClickHouseError::Closed)
+    }
+}
+
+#[async_trait]
+impl TelemetrySink for ClickHouseSink {
+    fn name(&self) -> &str {
+        "clickhouse"
+    }
+
+    async fn write(&self, event: &UsageEvent) -> Result<(), SinkError> {
+        Ok(self.send(UsageRow::new(event)).await?)
+    }
+}
+
+pub struct ClickHouseWriter {
+    client: reqwest::Client,
+    settings: ClickHouseSettings,
+}
+
+impl ClickHouseWriter {
+    pub fn new(settings: ClickHouseSettings) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            settings,
+        }
+    }
+
+    /// Create the usage table if it does not exist yet.
+    pub async fn ensure_table(&self) -> Result<(), ClickHouseError> {
+        self.execute(&create_table_sql(&self.settings.table), Vec::new())
+            .await
+    }
+
+    pub async fn insert(&self, rows: &[UsageRow]) -> Result<(), ClickHouseError> {
+        let query = format!("INSERT INTO {} FORMAT JSONEachRow", self.settings.table);
+        self.execute(&query, encode_rows(rows)?).await
+    }
+
+    async fn execute(&self, query: &str, body: Vec<u8>) -> Result<(), ClickHouseError> {
+        let mut url = self.settings.url.clone();
+        url.query_pairs_mut().append_pair("query", query);
+        let mut request = self.client.post(url).body(body);
+        if let Some(user) = &self.settings.user {
+            request = request.header("X-ClickHouse-User", user);
+        }
+        if let Some(password) = &self.settings.password {
+            request = request.header("X-ClickHouse-Key", password);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(ClickHouseError::Status { status, body });
+        }
+        Ok(())
+    }
+
+    /// Insert `batch`, retrying with backoff, then clear it.  A batch that
+    /// still fails is dropped so that one bad row cannot stall the sink.
+    async fn flush(&self, batch: &mut Vec<UsageRow>) {
+        if batch.is_empty() {
+            return;
+        }
+        let mut delay = RETRY_BASE_DELAY;
+        for attempt in 1..=MAX_INSERT_ATTEMPTS {
+            match self.insert(batch).await {
+                Ok(()) => {
+                    tracing::debug!("Inserted {} usage rows into ClickHouse", batch.len());
+                    break;
+                }
+                Err(e) if attempt == MAX_INSERT_ATTEMPTS => {
+                    tracing::error!(
+                        "Dropping {} usage rows after {} failed ClickHouse inserts: {}",
+                        batch.len(),
+                        attempt,
+                        e
+                    );
+                }
+                Err(e) => {
+                    tracing::warn!("ClickHouse insert failed, retrying: {}", e);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                }
+            }
+        }
+        batch.clear();
+    }
+
+    /// Run the writer until `cancel` fires.  The returned sink buffers up to
+    /// two batches; beyond that, senders wait for an insert to finish.
+    pub fn spawn(self, cancel: CancellationToken) -> (ClickHouseSink, JoinHandle<()>) {
+        let batch_size = self.settings.batch_size;
+        let (tx, mut rx) = mpsc::channel(batch_size.saturating_mul(2));
+        let handle = tokio::spawn(async move {
+            let mut batch = Vec::with_capacity(batch_size);
+            let mut ticker = tokio::time::interval(self.settings.flush_interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    row = rx.recv() => match row {
+                        Some(row) => {
+                            batch.push(row);
+                            if batch.len() >= batch_size {
+                                self.flush(&mut batch).await;
+                            }
+                        }
+                        None => break,
+                    },
+                    _ = ticker.tick() => self.flush(&mut batch).await,
+                }
+            }
+            rx.close();
+            while let Ok(row) = rx.try_recv() {
+                batch.push(row);
+                if batch.len() >= batch_size {
+                    self.flush(&mut batch).await;
+                }
+            }
+            self.flush(&mut batch).await;
+        });
+        (ClickHouseSink { tx }, handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Query, State};
+    use axum::routing::post;
+    use axum::Router;
+    use std::sync::{Arc, Mutex};
+
+    fn vars(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let pairs: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| pairs.get(name).cloned()
+    }
+
+    fn event(model: &str) -> UsageEvent {
+        UsageEvent {
+            team_id: "team-1".to_string(),
+            api_key_id: "key-1".to_string(),
+            entry_id: None,
+            model: model.to_string(),
+            input_tokens: 10,
+            output_tokens: 20,
+            response_time_ms: 150,
+            timestamp: 1_767_225_600_123,
+            provider: Some("openai".to_string()),
+            error: None,
+            metadata: HashMap::from([("env".to_string(), "prod".to_string())]),
+            compression: None,
+        }
+    }
+
+    #[test]
+    fn test_settings_from_vars() {
+        assert_eq!(ClickHouseSettings::from_vars(&vars(&[])).unwrap(), None);
+
+        let settings = ClickHouseSettings::from_vars(&vars(&[
+            ("CLICKHOUSE_URL", "http://clickhouse:8123/?database=metrics"),
+            ("CLICKHOUSE_TABLE", "metrics.usage"),
+            ("CLICKHOUSE_BATCH_SIZE", "500"),
+            ("CLICKHOUSE_FLUSH_MS", "250"),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(settings.table, "metrics.usage");
+        assert_eq!(settings.batch_size, 500);
+        assert_eq!(settings.flush_interval, Duration::from_millis(250));
+
+        for (name, value) in [
+            ("CLICKHOUSE_URL", "clickhouse:8123"),
+            ("CLICKHOUSE_TABLE", "usage; DROP TABLE usage"),
+            ("CLICKHOUSE_BATCH_SIZE", "0"),
+            ("CLICKHOUSE_FLUSH_MS", "soon"),
+        ] {
+            let mut pairs = vec![("CLICKHOUSE_URL", "http://clickhouse:8123")];
+            pairs.retain(|(n, _)| *n != name);
+            pairs.push((name, value));
+            let err = ClickHouseSettings::from_vars(&vars(&pairs)).unwrap_err();
+            assert!(err.contains(name), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_usage_row() {
+        let mut failed = event("gpt-4o");
+        failed.error = Some("rate limited".to_string());
+        let rows = vec![UsageRow::new(&event("gpt-4o")), UsageRow::new(&failed)];
+        assert_eq!(rows[0].recorded_at, "2026-01-01 00:00:00.123");
+        assert_eq!(rows[0].error, "");
+        assert_eq!(rows[1].error, "rate limited");
+
+        let body = String::from_utf8(encode_rows(&rows).unwrap()).unwrap();
+        let lines: Vec<serde_json::Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["provider"], "openai");
+        assert_eq!(lines[0]["metadata"]["env"], "prod");
+        assert_eq!(lines[1]["error"], "rate limited");
+    }
+
+    type Requests = Arc<Mutex<Vec<(String, String)>>>;
+
+    async fn fake_clickhouse() -> (url::Url, Requests) {
+        let requests = Requests::default();
+        let app = Router::new()
+            .route(
+                "/",
+                post(
+                    |State(requests): State<Requests>,
+                     Query(params): Query<HashMap<String, String>>,
+                     body: String| async move {
+                        let query = params.get("query").cloned().unwrap_or_default();
+                        requests.lock().unwrap().push((query, body));
+                    },
+                ),
+            )
+            .with_state(requests.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url.parse().unwrap(), requests)
+    }
+
+    #[tokio::test]
+    async fn test_writer_batches_and_flushes_on_cancel() {
+        let (url, requests) = fake_clickhouse().await;
+        let writer = ClickHouseWriter::new(ClickHouseSettings {
+            url,
+            table: DEFAULT_TABLE.to_string(),
+            user: None,
+            password: None,
+            batch_size: 2,
+            flush_interval: Duration::from_secs(3600),
+        });
+        writer.ensure_table().await.unwrap();
+
+        let cancel = CancellationToken::new();
+        let (sink, handle) = writer.spawn(cancel.clone());
+        for model in ["a", "b", "c"] {
+            sink.write(&event(model)).await.unwrap();
+        }
+        cancel.cancel();
+        handle.await.unwrap();
+        let err = sink.write(&event("d")).await.unwrap_err();
+        assert_eq!(err.to_string(), ClickHouseError::Closed.to_string());
+
+        let requests = requests.lock().unwrap();
+        assert!(requests[0]
+            .0
+            .starts_with("CREATE TABLE IF NOT EXISTS usage_logs"));
+        let inserts: Vec<usize> = requests[1..]
+            .iter()
+            .map(|(query, body)| {
+                assert_eq!(query, "INSERT INTO usage_logs FORMAT JSONEachRow");
+                body.lines().count()
+            })
+            .collect();
+        assert_eq!(inserts, vec![2, 1]);
+    }
+}
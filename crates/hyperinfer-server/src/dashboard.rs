@@ -0,0 +1,82 @@
+//! The admin dashboard, a single-page app embedded in the binary and served
+//! under `/dashboard`.
+//!
+//! The pages are public; the app asks for an admin or session token and
+//! sends it with its admin API calls.
+
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+    Router,
+};
+
+/// Embedded files: name, content type, contents.
+const ASSETS: &[(&str, &str, &str)] = &[
+    (
+        "index.html",
+        "text/html; charset=utf-8",
+        include_str!("../dashboard/index.html"),
+    ),
+    (
+        "app.js",
+        "text/javascript; charset=utf-8",
+        include_str!("../dashboard/app.js"),
+    ),
+    (
+        "style.css",
+        "text/css; charset=utf-8",
+        include_str!("../dashboard/style.css"),
+    ),
+];
+
+pub fn router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new()
+        .route(
+            "/dashboard",
+            get(|| async { Redirect::permanent("/dashboard/") }),
+        )
+        .route("/dashboard/", get(|| async { asset("index.html") }))
+        .route(
+            "/dashboard/{*path}",
+            get(|Path(path): Path<String>| async move { asset(&path) }),
+        )
+}
+
+fn asset(path: &str) -> Response {
+    match ASSETS.iter().find(|(name, _, _)| *name == path) {
+        Some((_, content_type, body)) => {
+            ([(header::CONTENT_TYPE, *content_type)], *body).into_response()
+        }
+        None => (StatusCode::NOT_FOUND, "Not found").into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_serves_embedded_assets() {
+        let server = axum_test::TestServer::new(router::<()>());
+
+        let redirect = server.get("/dashboard").await;
+        assert_eq!(redirect.status_code(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(redirect.header(header::LOCATION), "/dashboard/");
+
+        let index = server.get("/dashboard/").await;
+        assert_eq!(index.status_code(), StatusCode::OK);
+        assert_eq!(
+            index.header(header::CONTENT_TYPE),
+            "text/html; charset=utf-8"
+        );
+        assert!(index.text().contains("app.js"));
+
+        let script = server.get("/dashboard/app.js").await;
+        assert!(script.text().contains("/v1/summary/overview"));
+
+        let missing = server.get("/dashboard/secrets.txt").await;
+        assert_eq!(missing.status_code(), StatusCode::NOT_FOUND);
+    }
+}
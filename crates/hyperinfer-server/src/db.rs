@@ -1,19 +1,49 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use hyperinfer_core::{
-    ApiKey, ConfigStore, Database, DbError, ModelAlias, PolicyUpdate, Quota, Team, UsageLog, User,
+    Alert, AlertRule, ApiKey, ApiKeyMetadata, BillingPeriod, BudgetPolicy, Bundle, BundleApiKey,
+    BundleModelAlias, BundleQuota, BundleTeam, BundleUser, ChatMessage, ConfigStore,
+    ConfiguredPrice, Conversation, ConversationMessage, Database, DbError, EvalDataset, EvalItem,
+    EvalItemResult, EvalRun, ImportSummary, KeyUsageBucket, MessageRole, ModelAlias, ModelUsage,
+    NewAlertRule, NewConversation, NewEvalDataset, NewEvalRun, NewModelPrice, NewOrganization,
+    NewQuotaTemplate, NewReportSubscription, NewWebhook, Organization, OutputTransform,
+    ParamClamps, PolicyUpdate, Quota, QuotaTemplate, ReportSubscription, Role, RoleChange,
+    TagUsage, Team, UsageEvent, UsageLog, User, Webhook, WebhookDelivery,
 };
 use serde::Serialize;
+use sqlx::types::Json;
 use sqlx::PgPool;
+use std::collections::HashMap;
 
+/// Postgres-backed [`Database`].
+///
+/// With a read replica attached, the usage scans behind exports, tag
+/// reports, the overview and anomaly detection read from it, so heavy
+/// reporting cannot slow down the primary.  Those tolerate the replica's
+/// lag; everything else stays on the primary, including the per-period
+/// usage that billing snapshots and budgets are computed from.
 #[derive(Clone)]
 pub struct SqlxDb {
     pool: PgPool,
+    replica: Option<PgPool>,
 }
 
 impl SqlxDb {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            replica: None,
+        }
+    }
+
+    pub fn with_replica(mut self, replica: PgPool) -> Self {
+        self.replica = Some(replica);
+        self
+    }
+
+    /// Pool for usage aggregations: the replica when there is one.
+    fn analytics(&self) -> &PgPool {
+        self.replica.as_ref().unwrap_or(&self.pool)
     }
 }
 
@@ -22,7 +52,7 @@ impl Database for SqlxDb {
     async fn get_team(&self, id: &str) -> Result<Option<Team>, DbError> {
         let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
         let result: Option<TeamRow> = sqlx::query_as(
-            "SELECT id, name, budget_cents, created_at, updated_at FROM teams WHERE id = $1",
+            "SELECT id, name, budget_cents, billing_anchor_day, billing_timezone, organization_id, data_region, features, param_clamps, output_transforms, created_at, updated_at FROM teams WHERE id = $1 AND deleted_at IS NULL",
         )
         .bind(uuid)
         .fetch_optional(&self.pool)
@@ -33,7 +63,7 @@ impl Database for SqlxDb {
 
     async fn create_team(&self, name: &str, budget_cents: i64) -> Result<Team, DbError> {
         let result: TeamRow = match sqlx::query_as(
-            "INSERT INTO teams (name, budget_cents) VALUES ($1, $2) RETURNING id, name, budget_cents, created_at, updated_at"
+            "INSERT INTO teams (name, budget_cents) VALUES ($1, $2) RETURNING id, name, budget_cents, billing_anchor_day, billing_timezone, organization_id, data_region, features, param_clamps, output_transforms, created_at, updated_at"
         )
         .bind(name)
         .bind(budget_cents)
@@ -58,7 +88,7 @@ impl Database for SqlxDb {
     async fn get_user(&self, id: &str) -> Result<Option<User>, DbError> {
         let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
         let result: Option<UserRow> =
-            sqlx::query_as("SELECT id, team_id, email, role, created_at FROM users WHERE id = $1")
+            sqlx::query_as("SELECT id, team_id, email, role, created_at FROM users WHERE id = $1 AND deleted_at IS NULL")
                 .bind(uuid)
                 .fetch_optional(&self.pool)
                 .await?;
@@ -66,7 +96,18 @@ impl Database for SqlxDb {
         Ok(result.map(User::from))
     }
 
-    async fn create_user(&self, team_id: &str, email: &str, role: &str) -> Result<User, DbError> {
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, DbError> {
+        let result: Option<UserRow> = sqlx::query_as(
+            "SELECT id, team_id, email, role, created_at FROM users WHERE email = $1 AND deleted_at IS NULL",
+        )
+        .bind(email)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(User::from))
+    }
+
+    async fn create_user(&self, team_id: &str, email: &str, role: Role) -> Result<User, DbError> {
         let team_uuid = uuid::Uuid::parse_str(team_id)
             .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
         let result: UserRow = sqlx::query_as(
@@ -74,7 +115,7 @@ impl Database for SqlxDb {
         )
         .bind(team_uuid)
         .bind(email)
-        .bind(role)
+        .bind(role.as_str())
         .fetch_one(&self.pool)
         .await?;
 
@@ -84,7 +125,7 @@ impl Database for SqlxDb {
     async fn get_api_key(&self, id: &str) -> Result<Option<ApiKey>, DbError> {
         let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
         let result: Option<ApiKeyRow> = sqlx::query_as(
-            "SELECT id, key_hash, user_id, team_id, name, is_active, created_at, expires_at FROM api_keys WHERE id = $1"
+            "SELECT id, key_hash, user_id, team_id, name, is_active, created_at, expires_at, tags, allowed_models, budget_cents, last_used_at FROM api_keys WHERE id = $1"
         )
         .bind(uuid)
         .fetch_optional(&self.pool)
@@ -95,7 +136,7 @@ impl Database for SqlxDb {
 
     async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, DbError> {
         let result: Option<ApiKeyRow> = sqlx::query_as(
-            "SELECT id, key_hash, user_id, team_id, name, is_active, created_at, expires_at FROM api_keys WHERE key_hash = $1 AND is_active = true"
+            "SELECT id, key_hash, user_id, team_id, name, is_active, created_at, expires_at, tags, allowed_models, budget_cents, last_used_at FROM api_keys WHERE key_hash = $1 AND is_active = true"
         )
         .bind(key_hash)
         .fetch_optional(&self.pool)
@@ -116,7 +157,7 @@ impl Database for SqlxDb {
         let team_uuid = uuid::Uuid::parse_str(team_id)
             .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
         let result: ApiKeyRow = sqlx::query_as(
-            "INSERT INTO api_keys (key_hash, user_id, team_id, name) VALUES ($1, $2, $3, $4) RETURNING id, key_hash, user_id, team_id, name, is_active, created_at, expires_at"
+            "INSERT INTO api_keys (key_hash, user_id, team_id, name) VALUES ($1, $2, $3, $4) RETURNING id, key_hash, user_id, team_id, name, is_active, created_at, expires_at, tags, allowed_models, budget_cents, last_used_at"
         )
         .bind(key_hash)
         .bind(user_uuid)
@@ -140,6 +181,16 @@ impl Database for SqlxDb {
         Ok(result.map(ModelAlias::from))
     }
 
+    async fn list_model_aliases(&self) -> Result<Vec<ModelAlias>, DbError> {
+        let rows: Vec<ModelAliasRow> = sqlx::query_as(
+            "SELECT id, team_id, alias, target_model, provider, created_at FROM model_aliases ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(ModelAlias::from).collect())
+    }
+
     async fn create_model_alias(
         &self,
         team_id: &str,
@@ -195,6 +246,7 @@ impl Database for SqlxDb {
         Ok(Quota::from(result))
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn record_usage(
         &self,
         team_id: &str,
@@ -203,6 +255,7 @@ impl Database for SqlxDb {
         input_tokens: i32,
         output_tokens: i32,
         response_time_ms: i64,
+        metadata: &HashMap<String, String>,
     ) -> Result<UsageLog, DbError> {
         let team_uuid = uuid::Uuid::parse_str(team_id)
             .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
@@ -210,7 +263,7 @@ impl Database for SqlxDb {
             .map_err(|_| DbError::InvalidUuid(api_key_id.to_string()))?;
 
         let result: UsageLogRow = sqlx::query_as(
-            "INSERT INTO usage_logs (team_id, api_key_id, model, input_tokens, output_tokens, response_time_ms) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id, team_id, api_key_id, model, input_tokens, output_tokens, response_time_ms, recorded_at"
+            "INSERT INTO usage_logs (team_id, api_key_id, model, input_tokens, output_tokens, response_time_ms, metadata) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id, team_id, api_key_id, model, input_tokens, output_tokens, response_time_ms, recorded_at, metadata"
         )
         .bind(team_uuid)
         .bind(api_key_uuid)
@@ -218,183 +271,2441 @@ impl Database for SqlxDb {
         .bind(input_tokens)
         .bind(output_tokens)
         .bind(response_time_ms)
+        .bind(Json(metadata))
         .fetch_one(&self.pool)
         .await?;
 
         Ok(UsageLog::from(result))
     }
-}
 
-#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
-struct TeamRow {
-    id: uuid::Uuid,
-    name: String,
-    budget_cents: i64,
-    created_at: DateTime<Utc>,
-    updated_at: DateTime<Utc>,
-}
+    async fn record_request_error(
+        &self,
+        team_id: &str,
+        api_key_id: &str,
+        model: &str,
+        provider: Option<String>,
+        error: &str,
+    ) -> Result<(), DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let api_key_uuid = uuid::Uuid::parse_str(api_key_id)
+            .map_err(|_| DbError::InvalidUuid(api_key_id.to_string()))?;
 
-impl From<TeamRow> for Team {
-    fn from(row: TeamRow) -> Self {
-        Team {
-            id: row.id.to_string(),
-            name: row.name,
-            budget_cents: row.budget_cents,
-            created_at: row.created_at,
-            updated_at: row.updated_at,
-        }
+        sqlx::query(
+            "INSERT INTO request_errors (team_id, api_key_id, model, provider, error) VALUES ($1, $2, $3, $4, $5)"
+        )
+        .bind(team_uuid)
+        .bind(api_key_uuid)
+        .bind(model)
+        .bind(provider)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
     }
-}
 
-#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
-struct UserRow {
-    id: uuid::Uuid,
-    team_id: uuid::Uuid,
-    email: String,
-    role: String,
-    created_at: DateTime<Utc>,
-}
+    async fn record_usage_event(&self, event: &UsageEvent) -> Result<bool, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(&event.team_id)
+            .map_err(|_| DbError::InvalidUuid(event.team_id.clone()))?;
+        let api_key_uuid = uuid::Uuid::parse_str(&event.api_key_id)
+            .map_err(|_| DbError::InvalidUuid(event.api_key_id.clone()))?;
 
-impl From<UserRow> for User {
-    fn from(row: UserRow) -> Self {
-        User {
-            id: row.id.to_string(),
-            team_id: row.team_id.to_string(),
-            email: row.email,
-            role: row.role,
-            created_at: row.created_at,
-        }
+        let result = match &event.error {
+            Some(error) => {
+                sqlx::query(
+                    "INSERT INTO request_errors (team_id, api_key_id, model, provider, error, stream_entry_id) VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (stream_entry_id) DO NOTHING"
+                )
+                .bind(team_uuid)
+                .bind(api_key_uuid)
+                .bind(&event.model)
+                .bind(&event.provider)
+                .bind(error)
+                .bind(&event.entry_id)
+                .execute(&self.pool)
+                .await?
+            }
+            None => {
+                let clamp = |name: &str, value: u32| {
+                    i32::try_from(value).unwrap_or_else(|_| {
+                        tracing::warn!("{} overflow: {}", name, value);
+                        i32::MAX
+                    })
+                };
+                sqlx::query(
+                    "INSERT INTO usage_logs (team_id, api_key_id, model, input_tokens, output_tokens, response_time_ms, metadata, stream_entry_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (stream_entry_id) DO NOTHING"
+                )
+                .bind(team_uuid)
+                .bind(api_key_uuid)
+                .bind(&event.model)
+                .bind(clamp("input_tokens", event.input_tokens))
+                .bind(clamp("output_tokens", event.output_tokens))
+                .bind(i64::try_from(event.response_time_ms).unwrap_or(i64::MAX))
+                .bind(Json(&event.metadata))
+                .bind(&event.entry_id)
+                .execute(&self.pool)
+                .await?
+            }
+        };
+
+        Ok(result.rows_affected() > 0)
     }
-}
 
-#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
-struct ApiKeyRow {
-    id: uuid::Uuid,
-    key_hash: String,
-    user_id: uuid::Uuid,
-    team_id: uuid::Uuid,
-    name: Option<String>,
-    is_active: bool,
-    created_at: DateTime<Utc>,
-    expires_at: Option<DateTime<Utc>>,
-}
+    async fn get_model_usage_since(
+        &self,
+        team_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<ModelUsage>, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let rows: Vec<ModelUsageRow> = sqlx::query_as(
+            "SELECT model, COUNT(*) AS requests, COALESCE(SUM(input_tokens), 0)::BIGINT AS input_tokens, COALESCE(SUM(output_tokens), 0)::BIGINT AS output_tokens FROM usage_logs WHERE team_id = $1 AND recorded_at >= $2 GROUP BY model"
+        )
+        .bind(team_uuid)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
 
-impl From<ApiKeyRow> for ApiKey {
-    fn from(row: ApiKeyRow) -> Self {
-        ApiKey {
-            id: row.id.to_string(),
-            key_hash: row.key_hash,
-            user_id: row.user_id.to_string(),
-            team_id: row.team_id.to_string(),
-            name: row.name,
-            is_active: row.is_active,
-            created_at: row.created_at,
-            expires_at: row.expires_at,
-        }
+        Ok(rows.into_iter().map(ModelUsage::from).collect())
     }
-}
 
-#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
-struct ModelAliasRow {
-    id: uuid::Uuid,
-    team_id: uuid::Uuid,
-    alias: String,
-    target_model: String,
-    provider: String,
-    created_at: DateTime<Utc>,
-}
+    async fn get_all_model_usage_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<ModelUsage>, DbError> {
+        let rows: Vec<ModelUsageRow> = sqlx::query_as(
+            "SELECT model, COUNT(*) AS requests, COALESCE(SUM(input_tokens), 0)::BIGINT AS input_tokens, COALESCE(SUM(output_tokens), 0)::BIGINT AS output_tokens FROM usage_logs WHERE recorded_at >= $1 GROUP BY model"
+        )
+        .bind(since)
+        .fetch_all(self.analytics())
+        .await?;
 
-impl From<ModelAliasRow> for ModelAlias {
-    fn from(row: ModelAliasRow) -> Self {
-        ModelAlias {
-            id: row.id.to_string(),
-            team_id: row.team_id.to_string(),
-            alias: row.alias,
-            target_model: row.target_model,
-            provider: row.provider,
-            created_at: row.created_at,
-        }
+        Ok(rows.into_iter().map(ModelUsage::from).collect())
     }
-}
 
-#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
-struct QuotaRow {
-    id: uuid::Uuid,
-    team_id: uuid::Uuid,
-    rpm_limit: i32,
-    tpm_limit: i32,
-    updated_at: DateTime<Utc>,
-}
+    async fn get_key_usage_buckets(
+        &self,
+        team_id: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        window_minutes: i32,
+    ) -> Result<Vec<KeyUsageBucket>, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let rows: Vec<KeyUsageBucketRow> = sqlx::query_as(
+            "SELECT api_key_id, model, FLOOR(EXTRACT(EPOCH FROM ($3 - recorded_at)) / ($4 * 60))::BIGINT AS windows_ago, EXTRACT(HOUR FROM recorded_at AT TIME ZONE 'UTC')::INTEGER AS hour, COUNT(*) AS requests FROM usage_logs WHERE team_id = $1 AND recorded_at >= $2 AND recorded_at < $3 GROUP BY 1, 2, 3, 4"
+        )
+        .bind(team_uuid)
+        .bind(start)
+        .bind(end)
+        .bind(window_minutes)
+        .fetch_all(self.analytics())
+        .await?;
 
-impl From<QuotaRow> for Quota {
-    fn from(row: QuotaRow) -> Self {
-        Quota {
-            id: row.id.to_string(),
-            team_id: row.team_id.to_string(),
-            rpm_limit: row.rpm_limit,
-            tpm_limit: row.tpm_limit,
-            updated_at: row.updated_at,
-        }
+        Ok(rows.into_iter().map(KeyUsageBucket::from).collect())
     }
-}
 
-#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
-struct UsageLogRow {
-    id: uuid::Uuid,
-    team_id: uuid::Uuid,
-    api_key_id: uuid::Uuid,
-    model: String,
-    input_tokens: i32,
-    output_tokens: i32,
-    response_time_ms: i64,
-    recorded_at: DateTime<Utc>,
-}
+    async fn count_request_errors_since(
+        &self,
+        team_id: Option<String>,
+        provider: Option<String>,
+        since: DateTime<Utc>,
+    ) -> Result<i64, DbError> {
+        let team_uuid = team_id
+            .map(|id| uuid::Uuid::parse_str(&id).map_err(|_| DbError::InvalidUuid(id.clone())))
+            .transpose()?;
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM request_errors WHERE ($1::UUID IS NULL OR team_id = $1) AND ($2::VARCHAR IS NULL OR provider = $2) AND recorded_at >= $3"
+        )
+        .bind(team_uuid)
+        .bind(provider)
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await?;
 
-impl From<UsageLogRow> for UsageLog {
-    fn from(row: UsageLogRow) -> Self {
-        UsageLog {
-            id: row.id.to_string(),
-            team_id: row.team_id.to_string(),
-            api_key_id: row.api_key_id.to_string(),
-            model: row.model,
-            input_tokens: row.input_tokens,
-            output_tokens: row.output_tokens,
-            response_time_ms: row.response_time_ms,
-            recorded_at: row.recorded_at,
-        }
+        Ok(count)
     }
-}
 
-#[derive(Clone)]
-pub struct RedisConfigStore {
-    manager: hyperinfer_core::redis::ConfigManager,
-}
+    async fn create_alert_rule(&self, rule: &NewAlertRule) -> Result<AlertRule, DbError> {
+        let team_uuid = rule
+            .team_id
+            .as_deref()
+            .map(|id| uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string())))
+            .transpose()?;
+        let result: AlertRuleRow = sqlx::query_as(
+            "INSERT INTO alert_rules (team_id, kind, threshold, provider, window_minutes, webhook_url, webhook_format) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING id, team_id, kind, threshold, provider, window_minutes, webhook_url, webhook_format, is_active, created_at"
+        )
+        .bind(team_uuid)
+        .bind(&rule.kind)
+        .bind(rule.threshold)
+        .bind(rule.provider.as_deref())
+        .bind(rule.window_minutes)
+        .bind(&rule.webhook_url)
+        .bind(&rule.webhook_format)
+        .fetch_one(&self.pool)
+        .await?;
 
-impl RedisConfigStore {
-    pub async fn new(redis_url: &str) -> Result<Self, hyperinfer_core::ConfigError> {
-        let manager = hyperinfer_core::redis::ConfigManager::new(redis_url).await?;
-        Ok(Self { manager })
+        Ok(AlertRule::from(result))
     }
 
-    pub async fn subscribe_to_config_updates(
+    async fn list_alert_rules(&self) -> Result<Vec<AlertRule>, DbError> {
+        let rows: Vec<AlertRuleRow> = sqlx::query_as(
+            "SELECT id, team_id, kind, threshold, provider, window_minutes, webhook_url, webhook_format, is_active, created_at FROM alert_rules WHERE is_active = true ORDER BY created_at"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(AlertRule::from).collect())
+    }
+
+    async fn get_open_alert(&self, rule_id: &str) -> Result<Option<Alert>, DbError> {
+        let uuid = uuid::Uuid::parse_str(rule_id)
+            .map_err(|_| DbError::InvalidUuid(rule_id.to_string()))?;
+        let result: Option<AlertRow> = sqlx::query_as(
+            "SELECT id, rule_id, team_id, kind, message, observed_value, threshold, delivered, fired_at, resolved_at FROM alerts WHERE rule_id = $1 AND resolved_at IS NULL"
+        )
+        .bind(uuid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(Alert::from))
+    }
+
+    async fn create_alert(
         &self,
-        config: std::sync::Arc<tokio::sync::RwLock<hyperinfer_core::Config>>,
-    ) -> Result<tokio::task::JoinHandle<()>, hyperinfer_core::ConfigError> {
-        self.manager.subscribe_to_config_updates(config).await
+        rule: &AlertRule,
+        message: &str,
+        observed_value: f64,
+    ) -> Result<Alert, DbError> {
+        let rule_uuid =
+            uuid::Uuid::parse_str(&rule.id).map_err(|_| DbError::InvalidUuid(rule.id.clone()))?;
+        let team_uuid = rule
+            .team_id
+            .as_deref()
+            .map(|id| uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string())))
+            .transpose()?;
+        let result: AlertRow = match sqlx::query_as(
+            "INSERT INTO alerts (rule_id, team_id, kind, message, observed_value, threshold) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id, rule_id, team_id, kind, message, observed_value, threshold, delivered, fired_at, resolved_at"
+        )
+        .bind(rule_uuid)
+        .bind(team_uuid)
+        .bind(&rule.kind)
+        .bind(message)
+        .bind(observed_value)
+        .bind(rule.threshold)
+        .fetch_one(&self.pool)
+        .await
+        {
+            Ok(row) => row,
+            Err(e) => {
+                if e.as_database_error().map(|db| db.is_unique_violation()).unwrap_or(false) {
+                    return Err(DbError::UniqueViolation(format!(
+                        "Rule '{}' already has an open alert",
+                        rule.id
+                    )));
+                }
+                return Err(DbError::Sqlx(e));
+            }
+        };
+
+        Ok(Alert::from(result))
     }
-}
 
-#[async_trait]
-impl ConfigStore for RedisConfigStore {
-    async fn fetch_config(&self) -> Result<hyperinfer_core::Config, hyperinfer_core::ConfigError> {
-        self.manager.fetch_config().await
+    async fn mark_alert_delivered(&self, alert_id: &str) -> Result<(), DbError> {
+        let uuid = uuid::Uuid::parse_str(alert_id)
+            .map_err(|_| DbError::InvalidUuid(alert_id.to_string()))?;
+        sqlx::query("UPDATE alerts SET delivered = true WHERE id = $1")
+            .bind(uuid)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
     }
 
-    async fn publish_config_update(
+    async fn resolve_alert(&self, alert_id: &str) -> Result<(), DbError> {
+        let uuid = uuid::Uuid::parse_str(alert_id)
+            .map_err(|_| DbError::InvalidUuid(alert_id.to_string()))?;
+        sqlx::query("UPDATE alerts SET resolved_at = NOW() WHERE id = $1 AND resolved_at IS NULL")
+            .bind(uuid)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_alerts(
         &self,
-        config: &hyperinfer_core::Config,
-    ) -> Result<(), hyperinfer_core::ConfigError> {
-        self.manager.publish_config_update(config).await
+        team_id: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<Alert>, DbError> {
+        let team_uuid = team_id
+            .map(|id| uuid::Uuid::parse_str(&id).map_err(|_| DbError::InvalidUuid(id.clone())))
+            .transpose()?;
+        let rows: Vec<AlertRow> = sqlx::query_as(
+            "SELECT id, rule_id, team_id, kind, message, observed_value, threshold, delivered, fired_at, resolved_at FROM alerts WHERE ($1::UUID IS NULL OR team_id = $1) ORDER BY fired_at DESC LIMIT $2"
+        )
+        .bind(team_uuid)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Alert::from).collect())
+    }
+
+    async fn list_api_keys_for_team(&self, team_id: &str) -> Result<Vec<ApiKey>, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let rows: Vec<ApiKeyRow> = sqlx::query_as(
+            "SELECT id, key_hash, user_id, team_id, name, is_active, created_at, expires_at, tags, allowed_models, budget_cents, last_used_at FROM api_keys WHERE team_id = $1 AND is_active = true"
+        )
+        .bind(team_uuid)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(ApiKey::from).collect())
+    }
+
+    async fn upsert_budget_policy(
+        &self,
+        team_id: &str,
+        action: &str,
+        throttle_rpm: Option<i32>,
+    ) -> Result<BudgetPolicy, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let result: BudgetPolicyRow = sqlx::query_as(
+            "INSERT INTO budget_policies (team_id, action, throttle_rpm) VALUES ($1, $2, $3) ON CONFLICT (team_id) DO UPDATE SET action = EXCLUDED.action, throttle_rpm = EXCLUDED.throttle_rpm, updated_at = NOW() RETURNING team_id, action, throttle_rpm, enforced_action, enforced_at, updated_at"
+        )
+        .bind(team_uuid)
+        .bind(action)
+        .bind(throttle_rpm)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(BudgetPolicy::from(result))
+    }
+
+    async fn get_budget_policy(&self, team_id: &str) -> Result<Option<BudgetPolicy>, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let result: Option<BudgetPolicyRow> = sqlx::query_as(
+            "SELECT team_id, action, throttle_rpm, enforced_action, enforced_at, updated_at FROM budget_policies WHERE team_id = $1"
+        )
+        .bind(team_uuid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(BudgetPolicy::from))
+    }
+
+    async fn list_budget_policies(&self) -> Result<Vec<BudgetPolicy>, DbError> {
+        let rows: Vec<BudgetPolicyRow> = sqlx::query_as(
+            "SELECT team_id, action, throttle_rpm, enforced_action, enforced_at, updated_at FROM budget_policies"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(BudgetPolicy::from).collect())
+    }
+
+    async fn set_budget_enforcement(
+        &self,
+        team_id: &str,
+        enforced_action: Option<String>,
+    ) -> Result<(), DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        sqlx::query(
+            "UPDATE budget_policies SET enforced_action = $2, enforced_at = CASE WHEN $2::VARCHAR IS NULL THEN NULL ELSE NOW() END WHERE team_id = $1"
+        )
+        .bind(team_uuid)
+        .bind(enforced_action)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn list_teams(&self) -> Result<Vec<Team>, DbError> {
+        let rows: Vec<TeamRow> = sqlx::query_as(
+            "SELECT id, name, budget_cents, billing_anchor_day, billing_timezone, organization_id, data_region, features, param_clamps, output_transforms, created_at, updated_at FROM teams WHERE deleted_at IS NULL ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Team::from).collect())
+    }
+
+    async fn update_team_billing(
+        &self,
+        team_id: &str,
+        anchor_day: i32,
+        timezone: &str,
+    ) -> Result<Team, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let result: Option<TeamRow> = sqlx::query_as(
+            "UPDATE teams SET billing_anchor_day = $2, billing_timezone = $3, updated_at = NOW() WHERE id = $1 RETURNING id, name, budget_cents, billing_anchor_day, billing_timezone, organization_id, data_region, features, param_clamps, output_transforms, created_at, updated_at"
+        )
+        .bind(team_uuid)
+        .bind(anchor_day)
+        .bind(timezone)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        result.map(Team::from).ok_or(DbError::NotFound)
+    }
+
+    async fn get_model_usage_between(
+        &self,
+        team_id: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<ModelUsage>, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let rows: Vec<ModelUsageRow> = sqlx::query_as(
+            "SELECT model, COUNT(*) AS requests, COALESCE(SUM(input_tokens), 0)::BIGINT AS input_tokens, COALESCE(SUM(output_tokens), 0)::BIGINT AS output_tokens FROM usage_logs WHERE team_id = $1 AND recorded_at >= $2 AND recorded_at < $3 GROUP BY model"
+        )
+        .bind(team_uuid)
+        .bind(start)
+        .bind(end)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(ModelUsage::from).collect())
+    }
+
+    async fn record_billing_period(
+        &self,
+        period: &BillingPeriod,
+    ) -> Result<Option<BillingPeriod>, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(&period.team_id)
+            .map_err(|_| DbError::InvalidUuid(period.team_id.clone()))?;
+        let result: Option<BillingPeriodRow> = sqlx::query_as(
+            "INSERT INTO billing_periods (team_id, period_start, period_end, requests, input_tokens, output_tokens, spend_cents, budget_cents) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (team_id, period_start) DO NOTHING RETURNING id, team_id, period_start, period_end, requests, input_tokens, output_tokens, spend_cents, budget_cents, closed_at"
+        )
+        .bind(team_uuid)
+        .bind(period.period_start)
+        .bind(period.period_end)
+        .bind(period.requests)
+        .bind(period.input_tokens)
+        .bind(period.output_tokens)
+        .bind(period.spend_cents)
+        .bind(period.budget_cents)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(BillingPeriod::from))
+    }
+
+    async fn list_billing_periods(
+        &self,
+        team_id: &str,
+        limit: i64,
+    ) -> Result<Vec<BillingPeriod>, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let rows: Vec<BillingPeriodRow> = sqlx::query_as(
+            "SELECT id, team_id, period_start, period_end, requests, input_tokens, output_tokens, spend_cents, budget_cents, closed_at FROM billing_periods WHERE team_id = $1 ORDER BY period_start DESC LIMIT $2"
+        )
+        .bind(team_uuid)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(BillingPeriod::from).collect())
+    }
+
+    async fn create_model_price(&self, price: &NewModelPrice) -> Result<ConfiguredPrice, DbError> {
+        let result: ModelPriceRow = match sqlx::query_as(
+            "INSERT INTO model_prices (model, provider, input_per_mtok, output_per_mtok, effective_from) VALUES ($1, $2, $3, $4, COALESCE($5, NOW())) RETURNING id, model, provider, input_per_mtok, output_per_mtok, effective_from"
+        )
+        .bind(&price.model)
+        .bind(&price.provider)
+        .bind(price.input_per_mtok)
+        .bind(price.output_per_mtok)
+        .bind(price.effective_from)
+        .fetch_one(&self.pool)
+        .await
+        {
+            Ok(row) => row,
+            Err(e) => {
+                if e.as_database_error().map(|db| db.is_unique_violation()).unwrap_or(false) {
+                    return Err(DbError::UniqueViolation(format!(
+                        "A price for '{}' ({}) with this effective_from already exists",
+                        price.model, price.provider
+                    )));
+                }
+                return Err(DbError::Sqlx(e));
+            }
+        };
+
+        Ok(ConfiguredPrice::from(result))
+    }
+
+    async fn get_model_price(&self, id: &str) -> Result<Option<ConfiguredPrice>, DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        let result: Option<ModelPriceRow> =
+            sqlx::query_as("SELECT id, model, provider, input_per_mtok, output_per_mtok, effective_from FROM model_prices WHERE id = $1")
+                .bind(uuid)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(result.map(ConfiguredPrice::from))
+    }
+
+    async fn list_model_prices(&self) -> Result<Vec<ConfiguredPrice>, DbError> {
+        let rows: Vec<ModelPriceRow> =
+            sqlx::query_as("SELECT id, model, provider, input_per_mtok, output_per_mtok, effective_from FROM model_prices ORDER BY model, effective_from")
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(rows.into_iter().map(ConfiguredPrice::from).collect())
+    }
+
+    async fn update_model_price(
+        &self,
+        id: &str,
+        price: &NewModelPrice,
+    ) -> Result<ConfiguredPrice, DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        let result: Option<ModelPriceRow> = sqlx::query_as(
+            "UPDATE model_prices SET model = $2, provider = $3, input_per_mtok = $4, output_per_mtok = $5, effective_from = COALESCE($6, effective_from) WHERE id = $1 RETURNING id, model, provider, input_per_mtok, output_per_mtok, effective_from"
+        )
+        .bind(uuid)
+        .bind(&price.model)
+        .bind(&price.provider)
+        .bind(price.input_per_mtok)
+        .bind(price.output_per_mtok)
+        .bind(price.effective_from)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        result.map(ConfiguredPrice::from).ok_or(DbError::NotFound)
+    }
+
+    async fn delete_model_price(&self, id: &str) -> Result<(), DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        let result = sqlx::query("DELETE FROM model_prices WHERE id = $1")
+            .bind(uuid)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn update_api_key_metadata(
+        &self,
+        id: &str,
+        metadata: &ApiKeyMetadata,
+    ) -> Result<ApiKey, DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        let result: Option<ApiKeyRow> = sqlx::query_as(
+            "UPDATE api_keys SET tags = $2, allowed_models = $3, budget_cents = $4 WHERE id = $1 RETURNING id, key_hash, user_id, team_id, name, is_active, created_at, expires_at, tags, allowed_models, budget_cents, last_used_at"
+        )
+        .bind(uuid)
+        .bind(&metadata.tags)
+        .bind(&metadata.allowed_models)
+        .bind(metadata.budget_cents)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        result.map(ApiKey::from).ok_or(DbError::NotFound)
+    }
+
+    async fn revoke_api_key(&self, id: &str) -> Result<ApiKey, DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        let result: Option<ApiKeyRow> = sqlx::query_as(
+            "UPDATE api_keys SET is_active = false WHERE id = $1 AND is_active = true RETURNING id, key_hash, user_id, team_id, name, is_active, created_at, expires_at, tags, allowed_models, budget_cents, last_used_at"
+        )
+        .bind(uuid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        result.map(ApiKey::from).ok_or(DbError::NotFound)
+    }
+
+    async fn list_active_api_keys(&self) -> Result<Vec<ApiKey>, DbError> {
+        let rows: Vec<ApiKeyRow> = sqlx::query_as(
+            "SELECT id, key_hash, user_id, team_id, name, is_active, created_at, expires_at, tags, allowed_models, budget_cents, last_used_at FROM api_keys WHERE is_active = true AND (expires_at IS NULL OR expires_at > NOW())"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(ApiKey::from).collect())
+    }
+
+    async fn record_api_key_uses(
+        &self,
+        uses: &HashMap<String, DateTime<Utc>>,
+    ) -> Result<u64, DbError> {
+        let mut ids = Vec::with_capacity(uses.len());
+        let mut times = Vec::with_capacity(uses.len());
+        for (id, at) in uses {
+            // Ids come from the data plane; skip any that are not ours.
+            if let Ok(uuid) = uuid::Uuid::parse_str(id) {
+                ids.push(uuid);
+                times.push(*at);
+            }
+        }
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let result = sqlx::query(
+            "UPDATE api_keys SET last_used_at = GREATEST(api_keys.last_used_at, u.used_at) FROM UNNEST($1::uuid[], $2::timestamptz[]) AS u(id, used_at) WHERE api_keys.id = u.id AND (api_keys.last_used_at IS NULL OR api_keys.last_used_at < u.used_at)"
+        )
+        .bind(&ids)
+        .bind(&times)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn list_stale_api_keys(&self, cutoff: DateTime<Utc>) -> Result<Vec<ApiKey>, DbError> {
+        let rows: Vec<ApiKeyRow> = sqlx::query_as(
+            "SELECT id, key_hash, user_id, team_id, name, is_active, created_at, expires_at, tags, allowed_models, budget_cents, last_used_at FROM api_keys WHERE is_active = true AND COALESCE(last_used_at, created_at) < $1 ORDER BY COALESCE(last_used_at, created_at)"
+        )
+        .bind(cutoff)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(ApiKey::from).collect())
+    }
+
+    async fn get_tag_usage_between(
+        &self,
+        team_id: &str,
+        tag: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<TagUsage>, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let rows: Vec<TagUsageRow> = sqlx::query_as(
+            "SELECT metadata ->> $4 AS tag_value, model, COUNT(*) AS requests, COALESCE(SUM(input_tokens), 0)::BIGINT AS input_tokens, COALESCE(SUM(output_tokens), 0)::BIGINT AS output_tokens FROM usage_logs WHERE team_id = $1 AND recorded_at >= $2 AND recorded_at < $3 GROUP BY 1, 2"
+        )
+        .bind(team_uuid)
+        .bind(start)
+        .bind(end)
+        .bind(tag)
+        .fetch_all(self.analytics())
+        .await?;
+
+        Ok(rows.into_iter().map(TagUsage::from).collect())
+    }
+
+    async fn list_usage_logs(
+        &self,
+        team_id: Option<String>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<UsageLog>, DbError> {
+        let team_uuid = team_id
+            .map(|id| uuid::Uuid::parse_str(&id).map_err(|_| DbError::InvalidUuid(id.clone())))
+            .transpose()?;
+        let rows: Vec<UsageLogRow> = sqlx::query_as(
+            "SELECT id, team_id, api_key_id, model, input_tokens, output_tokens, response_time_ms, recorded_at, metadata FROM usage_logs WHERE ($1::UUID IS NULL OR team_id = $1) AND recorded_at >= $2 AND recorded_at < $3 ORDER BY recorded_at, id LIMIT $4 OFFSET $5"
+        )
+        .bind(team_uuid)
+        .bind(start)
+        .bind(end)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(self.analytics())
+        .await?;
+
+        Ok(rows.into_iter().map(UsageLog::from).collect())
+    }
+
+    async fn delete_team(&self, id: &str) -> Result<(), DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        let mut tx = self.pool.begin().await?;
+        let result =
+            sqlx::query("UPDATE teams SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL")
+                .bind(uuid)
+                .execute(&mut *tx)
+                .await?;
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        sqlx::query("UPDATE api_keys SET is_active = false WHERE team_id = $1")
+            .bind(uuid)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_user(&self, id: &str) -> Result<(), DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        let mut tx = self.pool.begin().await?;
+        let result =
+            sqlx::query("UPDATE users SET deleted_at = NOW() WHERE id = $1 AND deleted_at IS NULL")
+                .bind(uuid)
+                .execute(&mut *tx)
+                .await?;
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        sqlx::query("UPDATE api_keys SET is_active = false WHERE user_id = $1")
+            .bind(uuid)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn purge_deleted(&self, before: DateTime<Utc>) -> Result<u64, DbError> {
+        // Usage logs, keys and the rest cascade from the team or user row.
+        let mut tx = self.pool.begin().await?;
+        let users = sqlx::query("DELETE FROM users WHERE deleted_at < $1")
+            .bind(before)
+            .execute(&mut *tx)
+            .await?;
+        let teams = sqlx::query("DELETE FROM teams WHERE deleted_at < $1")
+            .bind(before)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(users.rows_affected() + teams.rows_affected())
+    }
+
+    async fn create_organization(&self, org: &NewOrganization) -> Result<Organization, DbError> {
+        let result: OrganizationRow = match sqlx::query_as(
+            "INSERT INTO organizations (name, budget_cents, rpm_limit, tpm_limit) VALUES ($1, $2, $3, $4) RETURNING id, name, budget_cents, rpm_limit, tpm_limit, created_at, updated_at"
+        )
+        .bind(&org.name)
+        .bind(org.budget_cents)
+        .bind(org.rpm_limit)
+        .bind(org.tpm_limit)
+        .fetch_one(&self.pool)
+        .await
+        {
+            Ok(row) => row,
+            Err(e) => {
+                if e.as_database_error().map(|db| db.is_unique_violation()).unwrap_or(false) {
+                    return Err(DbError::UniqueViolation(format!(
+                        "Organization with name '{}' already exists",
+                        org.name
+                    )));
+                }
+                return Err(DbError::Sqlx(e));
+            }
+        };
+
+        Ok(Organization::from(result))
+    }
+
+    async fn get_organization(&self, id: &str) -> Result<Option<Organization>, DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        let result: Option<OrganizationRow> = sqlx::query_as(
+            "SELECT id, name, budget_cents, rpm_limit, tpm_limit, created_at, updated_at FROM organizations WHERE id = $1",
+        )
+        .bind(uuid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(Organization::from))
+    }
+
+    async fn list_organizations(&self) -> Result<Vec<Organization>, DbError> {
+        let rows: Vec<OrganizationRow> = sqlx::query_as(
+            "SELECT id, name, budget_cents, rpm_limit, tpm_limit, created_at, updated_at FROM organizations ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Organization::from).collect())
+    }
+
+    async fn set_team_organization(
+        &self,
+        team_id: &str,
+        organization_id: Option<String>,
+    ) -> Result<Team, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let org_uuid = organization_id
+            .map(|id| uuid::Uuid::parse_str(&id).map_err(|_| DbError::InvalidUuid(id.clone())))
+            .transpose()?;
+        let result: Option<TeamRow> = sqlx::query_as(
+            "UPDATE teams SET organization_id = $2 WHERE id = $1 AND deleted_at IS NULL RETURNING id, name, budget_cents, billing_anchor_day, billing_timezone, organization_id, data_region, features, param_clamps, output_transforms, created_at, updated_at"
+        )
+        .bind(team_uuid)
+        .bind(org_uuid)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            // An unknown organization is as missing as an unknown team.
+            if e.as_database_error()
+                .is_some_and(|db| db.is_foreign_key_violation())
+            {
+                DbError::NotFound
+            } else {
+                DbError::Sqlx(e)
+            }
+        })?;
+
+        result.map(Team::from).ok_or(DbError::NotFound)
+    }
+
+    async fn set_team_data_region(
+        &self,
+        team_id: &str,
+        data_region: Option<String>,
+    ) -> Result<Team, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let result: Option<TeamRow> = sqlx::query_as(
+            "UPDATE teams SET data_region = $2 WHERE id = $1 AND deleted_at IS NULL RETURNING id, name, budget_cents, billing_anchor_day, billing_timezone, organization_id, data_region, features, param_clamps, output_transforms, created_at, updated_at"
+        )
+        .bind(team_uuid)
+        .bind(data_region)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        result.map(Team::from).ok_or(DbError::NotFound)
+    }
+
+    async fn set_team_features(
+        &self,
+        team_id: &str,
+        features: HashMap<String, bool>,
+    ) -> Result<Team, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let result: Option<TeamRow> = sqlx::query_as(
+            "UPDATE teams SET features = $2 WHERE id = $1 AND deleted_at IS NULL RETURNING id, name, budget_cents, billing_anchor_day, billing_timezone, organization_id, data_region, features, param_clamps, output_transforms, created_at, updated_at"
+        )
+        .bind(team_uuid)
+        .bind(Json(features))
+        .fetch_optional(&self.pool)
+        .await?;
+
+        result.map(Team::from).ok_or(DbError::NotFound)
+    }
+
+    async fn set_team_param_clamps(
+        &self,
+        team_id: &str,
+        param_clamps: Option<ParamClamps>,
+    ) -> Result<Team, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let result: Option<TeamRow> = sqlx::query_as(
+            "UPDATE teams SET param_clamps = $2 WHERE id = $1 AND deleted_at IS NULL RETURNING id, name, budget_cents, billing_anchor_day, billing_timezone, organization_id, data_region, features, param_clamps, output_transforms, created_at, updated_at"
+        )
+        .bind(team_uuid)
+        .bind(param_clamps.map(Json))
+        .fetch_optional(&self.pool)
+        .await?;
+
+        result.map(Team::from).ok_or(DbError::NotFound)
+    }
+
+    async fn set_team_output_transforms(
+        &self,
+        team_id: &str,
+        transforms: Vec<OutputTransform>,
+    ) -> Result<Team, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let result: Option<TeamRow> = sqlx::query_as(
+            "UPDATE teams SET output_transforms = $2 WHERE id = $1 AND deleted_at IS NULL RETURNING id, name, budget_cents, billing_anchor_day, billing_timezone, organization_id, data_region, features, param_clamps, output_transforms, created_at, updated_at"
+        )
+        .bind(team_uuid)
+        .bind(Json(transforms))
+        .fetch_optional(&self.pool)
+        .await?;
+
+        result.map(Team::from).ok_or(DbError::NotFound)
+    }
+
+    async fn set_user_role(
+        &self,
+        user_id: &str,
+        role: Role,
+        changed_by: &str,
+    ) -> Result<User, DbError> {
+        let uuid = uuid::Uuid::parse_str(user_id)
+            .map_err(|_| DbError::InvalidUuid(user_id.to_string()))?;
+        let mut tx = self.pool.begin().await?;
+        let old_role: Option<String> = sqlx::query_scalar(
+            "SELECT role FROM users WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        )
+        .bind(uuid)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(old_role) = old_role else {
+            return Err(DbError::NotFound);
+        };
+        let result: UserRow = sqlx::query_as(
+            "UPDATE users SET role = $2 WHERE id = $1 RETURNING id, team_id, email, role, created_at",
+        )
+        .bind(uuid)
+        .bind(role.as_str())
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO role_changes (user_id, old_role, new_role, changed_by) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(uuid)
+        .bind(old_role)
+        .bind(role.as_str())
+        .bind(changed_by)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(User::from(result))
+    }
+
+    async fn list_role_changes(&self, user_id: &str) -> Result<Vec<RoleChange>, DbError> {
+        let uuid = uuid::Uuid::parse_str(user_id)
+            .map_err(|_| DbError::InvalidUuid(user_id.to_string()))?;
+        let rows: Vec<RoleChangeRow> = sqlx::query_as(
+            "SELECT id, user_id, old_role, new_role, changed_by, created_at FROM role_changes WHERE user_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(uuid)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(RoleChange::from).collect())
+    }
+
+    async fn create_webhook(&self, webhook: &NewWebhook) -> Result<Webhook, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(&webhook.team_id)
+            .map_err(|_| DbError::InvalidUuid(webhook.team_id.clone()))?;
+        let result: WebhookRow = sqlx::query_as(
+            "INSERT INTO webhooks (team_id, url, events, secret) VALUES ($1, $2, $3, $4) RETURNING id, team_id, url, events, secret, is_active, created_at",
+        )
+        .bind(team_uuid)
+        .bind(&webhook.url)
+        .bind(&webhook.events)
+        .bind(&webhook.secret)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Webhook::from(result))
+    }
+
+    async fn get_webhook(&self, id: &str) -> Result<Option<Webhook>, DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        let result: Option<WebhookRow> = sqlx::query_as(
+            "SELECT id, team_id, url, events, secret, is_active, created_at FROM webhooks WHERE id = $1",
+        )
+        .bind(uuid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(Webhook::from))
+    }
+
+    async fn list_webhooks(&self, team_id: &str) -> Result<Vec<Webhook>, DbError> {
+        let uuid = uuid::Uuid::parse_str(team_id)
+            .map_err(|_| DbError::InvalidUuid(team_id.to_string()))?;
+        let rows: Vec<WebhookRow> = sqlx::query_as(
+            "SELECT id, team_id, url, events, secret, is_active, created_at FROM webhooks WHERE team_id = $1 ORDER BY created_at",
+        )
+        .bind(uuid)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Webhook::from).collect())
+    }
+
+    async fn delete_webhook(&self, id: &str) -> Result<(), DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = $1")
+            .bind(uuid)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn list_webhooks_for_event(
+        &self,
+        team_id: Option<String>,
+        event: &str,
+    ) -> Result<Vec<Webhook>, DbError> {
+        let team_uuid = team_id
+            .as_deref()
+            .map(|id| uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string())))
+            .transpose()?;
+        let rows: Vec<WebhookRow> = sqlx::query_as(
+            "SELECT id, team_id, url, events, secret, is_active, created_at FROM webhooks WHERE is_active = true AND $2 = ANY(events) AND ($1::uuid IS NULL OR team_id = $1)",
+        )
+        .bind(team_uuid)
+        .bind(event)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(Webhook::from).collect())
+    }
+
+    async fn enqueue_webhook_delivery(
+        &self,
+        webhook_id: &str,
+        event: &str,
+        payload: &serde_json::Value,
+    ) -> Result<WebhookDelivery, DbError> {
+        let uuid = uuid::Uuid::parse_str(webhook_id)
+            .map_err(|_| DbError::InvalidUuid(webhook_id.to_string()))?;
+        let result: WebhookDeliveryRow = sqlx::query_as(
+            "INSERT INTO webhook_deliveries (webhook_id, event, payload) VALUES ($1, $2, $3) RETURNING id, webhook_id, event, payload, status, attempts, response_status, last_error, next_attempt_at, created_at, delivered_at",
+        )
+        .bind(uuid)
+        .bind(event)
+        .bind(Json(payload))
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(WebhookDelivery::from(result))
+    }
+
+    async fn list_due_webhook_deliveries(
+        &self,
+        now: DateTime<Utc>,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, DbError> {
+        let rows: Vec<WebhookDeliveryRow> = sqlx::query_as(
+            "SELECT id, webhook_id, event, payload, status, attempts, response_status, last_error, next_attempt_at, created_at, delivered_at FROM webhook_deliveries WHERE status = 'pending' AND next_attempt_at <= $1 ORDER BY next_attempt_at LIMIT $2",
+        )
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(WebhookDelivery::from).collect())
+    }
+
+    async fn record_webhook_attempt(
+        &self,
+        id: &str,
+        status: &str,
+        response_status: Option<i32>,
+        error: Option<String>,
+        next_attempt_at: Option<DateTime<Utc>>,
+    ) -> Result<(), DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        sqlx::query(
+            "UPDATE webhook_deliveries SET status = $2, attempts = attempts + 1, response_status = $3, last_error = $4, next_attempt_at = $5, delivered_at = CASE WHEN $2 = 'delivered' THEN NOW() END WHERE id = $1",
+        )
+        .bind(uuid)
+        .bind(status)
+        .bind(response_status)
+        .bind(error)
+        .bind(next_attempt_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn list_webhook_deliveries(
+        &self,
+        webhook_id: &str,
+        limit: i64,
+    ) -> Result<Vec<WebhookDelivery>, DbError> {
+        let uuid = uuid::Uuid::parse_str(webhook_id)
+            .map_err(|_| DbError::InvalidUuid(webhook_id.to_string()))?;
+        let rows: Vec<WebhookDeliveryRow> = sqlx::query_as(
+            "SELECT id, webhook_id, event, payload, status, attempts, response_status, last_error, next_attempt_at, created_at, delivered_at FROM webhook_deliveries WHERE webhook_id = $1 ORDER BY created_at DESC LIMIT $2",
+        )
+        .bind(uuid)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(WebhookDelivery::from).collect())
+    }
+
+    async fn create_report_subscription(
+        &self,
+        subscription: &NewReportSubscription,
+    ) -> Result<ReportSubscription, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(&subscription.team_id)
+            .map_err(|_| DbError::InvalidUuid(subscription.team_id.clone()))?;
+        let result: ReportSubscriptionRow = sqlx::query_as(
+            "INSERT INTO report_subscriptions (team_id, frequency, recipients) VALUES ($1, $2, $3) RETURNING id, team_id, frequency, recipients, last_period_end, created_at",
+        )
+        .bind(team_uuid)
+        .bind(&subscription.frequency)
+        .bind(&subscription.recipients)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(ReportSubscription::from(result))
+    }
+
+    async fn list_report_subscriptions(
+        &self,
+        team_id: Option<String>,
+    ) -> Result<Vec<ReportSubscription>, DbError> {
+        let team_uuid = team_id
+            .as_deref()
+            .map(|id| uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string())))
+            .transpose()?;
+        let rows: Vec<ReportSubscriptionRow> = sqlx::query_as(
+            "SELECT id, team_id, frequency, recipients, last_period_end, created_at FROM report_subscriptions WHERE $1::uuid IS NULL OR team_id = $1 ORDER BY created_at",
+        )
+        .bind(team_uuid)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(ReportSubscription::from).collect())
+    }
+
+    async fn delete_report_subscription(&self, id: &str) -> Result<(), DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        let result = sqlx::query("DELETE FROM report_subscriptions WHERE id = $1")
+            .bind(uuid)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn mark_report_sent(&self, id: &str, period_end: DateTime<Utc>) -> Result<(), DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        sqlx::query("UPDATE report_subscriptions SET last_period_end = $2 WHERE id = $1")
+            .bind(uuid)
+            .bind(period_end)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn create_quota_template(
+        &self,
+        template: &NewQuotaTemplate,
+    ) -> Result<QuotaTemplate, DbError> {
+        let result: QuotaTemplateRow = match sqlx::query_as(
+            "INSERT INTO quota_templates (name, rpm_limit, tpm_limit) VALUES ($1, $2, $3) RETURNING id, name, rpm_limit, tpm_limit, created_at, updated_at",
+        )
+        .bind(&template.name)
+        .bind(template.rpm_limit)
+        .bind(template.tpm_limit)
+        .fetch_one(&self.pool)
+        .await
+        {
+            Ok(row) => row,
+            Err(e) => {
+                if e.as_database_error().map(|db| db.is_unique_violation()).unwrap_or(false) {
+                    return Err(DbError::UniqueViolation(format!(
+                        "Quota template '{}' already exists",
+                        template.name
+                    )));
+                }
+                return Err(DbError::Sqlx(e));
+            }
+        };
+
+        Ok(QuotaTemplate::from(result))
+    }
+
+    async fn list_quota_templates(&self) -> Result<Vec<QuotaTemplate>, DbError> {
+        let rows: Vec<QuotaTemplateRow> = sqlx::query_as(
+            "SELECT id, name, rpm_limit, tpm_limit, created_at, updated_at FROM quota_templates ORDER BY name",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(QuotaTemplate::from).collect())
+    }
+
+    async fn get_quota_template(&self, name: &str) -> Result<Option<QuotaTemplate>, DbError> {
+        let result: Option<QuotaTemplateRow> = sqlx::query_as(
+            "SELECT id, name, rpm_limit, tpm_limit, created_at, updated_at FROM quota_templates WHERE name = $1",
+        )
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(QuotaTemplate::from))
+    }
+
+    async fn update_quota_template(
+        &self,
+        name: &str,
+        template: &NewQuotaTemplate,
+    ) -> Result<QuotaTemplate, DbError> {
+        let result: Option<QuotaTemplateRow> = match sqlx::query_as(
+            "UPDATE quota_templates SET name = $2, rpm_limit = $3, tpm_limit = $4 WHERE name = $1 RETURNING id, name, rpm_limit, tpm_limit, created_at, updated_at",
+        )
+        .bind(name)
+        .bind(&template.name)
+        .bind(template.rpm_limit)
+        .bind(template.tpm_limit)
+        .fetch_optional(&self.pool)
+        .await
+        {
+            Ok(row) => row,
+            Err(e) => {
+                if e.as_database_error().map(|db| db.is_unique_violation()).unwrap_or(false) {
+                    return Err(DbError::UniqueViolation(format!(
+                        "Quota template '{}' already exists",
+                        template.name
+                    )));
+                }
+                return Err(DbError::Sqlx(e));
+            }
+        };
+
+        result.map(QuotaTemplate::from).ok_or(DbError::NotFound)
+    }
+
+    async fn delete_quota_template(&self, name: &str) -> Result<(), DbError> {
+        let result = sqlx::query("DELETE FROM quota_templates WHERE name = $1")
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn apply_quota_template(
+        &self,
+        name: &str,
+        team_ids: &[String],
+    ) -> Result<Vec<Quota>, DbError> {
+        let team_uuids = team_ids
+            .iter()
+            .map(|id| uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.clone())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut tx = self.pool.begin().await?;
+        // Locks the template so it cannot change halfway through.
+        let template: Option<QuotaTemplateRow> = sqlx::query_as(
+            "SELECT id, name, rpm_limit, tpm_limit, created_at, updated_at FROM quota_templates WHERE name = $1 FOR SHARE",
+        )
+        .bind(name)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let template = template.ok_or(DbError::NotFound)?;
+
+        let rows: Vec<QuotaRow> = sqlx::query_as(
+            "INSERT INTO quotas (team_id, rpm_limit, tpm_limit) SELECT id, $2, $3 FROM teams WHERE id = ANY($1) AND deleted_at IS NULL ON CONFLICT (team_id) DO UPDATE SET rpm_limit = EXCLUDED.rpm_limit, tpm_limit = EXCLUDED.tpm_limit RETURNING id, team_id, rpm_limit, tpm_limit, updated_at",
+        )
+        .bind(&team_uuids)
+        .bind(template.rpm_limit)
+        .bind(template.tpm_limit)
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(rows.into_iter().map(Quota::from).collect())
+    }
+
+    async fn export_bundle(&self) -> Result<Bundle, DbError> {
+        let teams: Vec<BundleTeamRow> = sqlx::query_as(
+            "SELECT name, budget_cents, billing_anchor_day, billing_timezone FROM teams WHERE deleted_at IS NULL ORDER BY name",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let users: Vec<BundleUserRow> = sqlx::query_as(
+            "SELECT u.email, t.name AS team, u.role FROM users u JOIN teams t ON t.id = u.team_id WHERE u.deleted_at IS NULL AND t.deleted_at IS NULL ORDER BY u.email",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let api_keys: Vec<BundleApiKeyRow> = sqlx::query_as(
+            "SELECT k.key_hash, t.name AS team, u.email AS \"user\", k.name, k.is_active, k.expires_at, k.tags, k.allowed_models, k.budget_cents FROM api_keys k LEFT JOIN users u ON u.id = k.user_id AND u.deleted_at IS NULL JOIN teams t ON t.id = COALESCE(k.team_id, u.team_id) WHERE t.deleted_at IS NULL ORDER BY t.name, k.created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let model_aliases: Vec<BundleModelAliasRow> = sqlx::query_as(
+            "SELECT t.name AS team, a.alias, a.target_model, a.provider FROM model_aliases a JOIN teams t ON t.id = a.team_id WHERE t.deleted_at IS NULL ORDER BY t.name, a.alias",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let quotas: Vec<BundleQuotaRow> = sqlx::query_as(
+            "SELECT t.name AS team, q.rpm_limit, q.tpm_limit FROM quotas q JOIN teams t ON t.id = q.team_id WHERE t.deleted_at IS NULL ORDER BY t.name",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(Bundle {
+            teams: teams.into_iter().map(BundleTeam::from).collect(),
+            users: users.into_iter().map(BundleUser::from).collect(),
+            api_keys: api_keys.into_iter().map(BundleApiKey::from).collect(),
+            model_aliases: model_aliases
+                .into_iter()
+                .map(BundleModelAlias::from)
+                .collect(),
+            quotas: quotas.into_iter().map(BundleQuota::from).collect(),
+        })
+    }
+
+    async fn import_bundle(
+        &self,
+        bundle: &Bundle,
+        dry_run: bool,
+    ) -> Result<ImportSummary, DbError> {
+        let mut summary = ImportSummary {
+            dry_run,
+            ..Default::default()
+        };
+        let mut tx = self.pool.begin().await?;
+
+        let mut team_ids: HashMap<String, uuid::Uuid> = HashMap::new();
+        for team in &bundle.teams {
+            let (id, created): (uuid::Uuid, bool) = sqlx::query_as(
+                "INSERT INTO teams (name, budget_cents, billing_anchor_day, billing_timezone) VALUES ($1, $2, $3, $4) ON CONFLICT (name) WHERE deleted_at IS NULL DO UPDATE SET budget_cents = EXCLUDED.budget_cents, billing_anchor_day = EXCLUDED.billing_anchor_day, billing_timezone = EXCLUDED.billing_timezone RETURNING id, (xmax = 0)",
+            )
+            .bind(&team.name)
+            .bind(team.budget_cents)
+            .bind(team.billing_anchor_day)
+            .bind(&team.billing_timezone)
+            .fetch_one(&mut *tx)
+            .await?;
+            summary.teams.record(created);
+            team_ids.insert(team.name.clone(), id);
+        }
+        // Teams the bundle refers to but does not contain must already exist.
+        let referenced = bundle
+            .users
+            .iter()
+            .map(|u| &u.team)
+            .chain(bundle.api_keys.iter().map(|k| &k.team))
+            .chain(bundle.model_aliases.iter().map(|a| &a.team))
+            .chain(bundle.quotas.iter().map(|q| &q.team));
+        for name in referenced {
+            if team_ids.contains_key(name) {
+                continue;
+            }
+            let id: Option<uuid::Uuid> =
+                sqlx::query_scalar("SELECT id FROM teams WHERE name = $1 AND deleted_at IS NULL")
+                    .bind(name)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            team_ids.insert(name.clone(), id.ok_or(DbError::NotFound)?);
+        }
+
+        let mut user_ids: HashMap<String, uuid::Uuid> = HashMap::new();
+        for user in &bundle.users {
+            let existing: Option<(uuid::Uuid, String)> = sqlx::query_as(
+                "SELECT id, role FROM users WHERE email = $1 AND deleted_at IS NULL FOR UPDATE",
+            )
+            .bind(&user.email)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let id = match existing {
+                Some((id, old_role)) => {
+                    sqlx::query("UPDATE users SET team_id = $2, role = $3 WHERE id = $1")
+                        .bind(id)
+                        .bind(team_ids[&user.team])
+                        .bind(user.role.as_str())
+                        .execute(&mut *tx)
+                        .await?;
+                    // Role changes keep their audit trail.
+                    if old_role != user.role.as_str() {
+                        sqlx::query(
+                            "INSERT INTO role_changes (user_id, old_role, new_role, changed_by) VALUES ($1, $2, $3, 'import')",
+                        )
+                        .bind(id)
+                        .bind(old_role)
+                        .bind(user.role.as_str())
+                        .execute(&mut *tx)
+                        .await?;
+                    }
+                    summary.users.record(false);
+                    id
+                }
+                None => {
+                    let id: uuid::Uuid = sqlx::query_scalar(
+                        "INSERT INTO users (team_id, email, role) VALUES ($1, $2, $3) RETURNING id",
+                    )
+                    .bind(team_ids[&user.team])
+                    .bind(&user.email)
+                    .bind(user.role.as_str())
+                    .fetch_one(&mut *tx)
+                    .await?;
+                    summary.users.record(true);
+                    id
+                }
+            };
+            user_ids.insert(user.email.clone(), id);
+        }
+
+        for key in &bundle.api_keys {
+            let user_id = match &key.user {
+                Some(email) => match user_ids.get(email) {
+                    Some(id) => Some(*id),
+                    None => Some(
+                        sqlx::query_scalar::<_, uuid::Uuid>(
+                            "SELECT id FROM users WHERE email = $1 AND deleted_at IS NULL",
+                        )
+                        .bind(email)
+                        .fetch_optional(&mut *tx)
+                        .await?
+                        .ok_or(DbError::NotFound)?,
+                    ),
+                },
+                None => None,
+            };
+            let created: bool = sqlx::query_scalar(
+                "INSERT INTO api_keys (key_hash, user_id, team_id, name, is_active, expires_at, tags, allowed_models, budget_cents) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) ON CONFLICT (key_hash) DO UPDATE SET user_id = EXCLUDED.user_id, team_id = EXCLUDED.team_id, name = EXCLUDED.name, is_active = EXCLUDED.is_active, expires_at = EXCLUDED.expires_at, tags = EXCLUDED.tags, allowed_models = EXCLUDED.allowed_models, budget_cents = EXCLUDED.budget_cents RETURNING (xmax = 0)",
+            )
+            .bind(&key.key_hash)
+            .bind(user_id)
+            .bind(team_ids[&key.team])
+            .bind(&key.name)
+            .bind(key.is_active)
+            .bind(key.expires_at)
+            .bind(&key.tags)
+            .bind(&key.allowed_models)
+            .bind(key.budget_cents)
+            .fetch_one(&mut *tx)
+            .await?;
+            summary.api_keys.record(created);
+        }
+
+        for alias in &bundle.model_aliases {
+            let created: bool = sqlx::query_scalar(
+                "INSERT INTO model_aliases (team_id, alias, target_model, provider) VALUES ($1, $2, $3, $4) ON CONFLICT (team_id, alias) DO UPDATE SET target_model = EXCLUDED.target_model, provider = EXCLUDED.provider RETURNING (xmax = 0)",
+            )
+            .bind(team_ids[&alias.team])
+            .bind(&alias.alias)
+            .bind(&alias.target_model)
+            .bind(&alias.provider)
+            .fetch_one(&mut *tx)
+            .await?;
+            summary.model_aliases.record(created);
+        }
+
+        for quota in &bundle.quotas {
+            let created: bool = sqlx::query_scalar(
+                "INSERT INTO quotas (team_id, rpm_limit, tpm_limit) VALUES ($1, $2, $3) ON CONFLICT (team_id) DO UPDATE SET rpm_limit = EXCLUDED.rpm_limit, tpm_limit = EXCLUDED.tpm_limit RETURNING (xmax = 0)",
+            )
+            .bind(team_ids[&quota.team])
+            .bind(quota.rpm_limit)
+            .bind(quota.tpm_limit)
+            .fetch_one(&mut *tx)
+            .await?;
+            summary.quotas.record(created);
+        }
+
+        if dry_run {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+        }
+        Ok(summary)
+    }
+
+    async fn create_conversation(
+        &self,
+        conversation: &NewConversation,
+    ) -> Result<Conversation, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(&conversation.team_id)
+            .map_err(|_| DbError::InvalidUuid(conversation.team_id.clone()))?;
+        let result: ConversationRow = sqlx::query_as(
+            "INSERT INTO conversations (team_id, title) VALUES ($1, $2) RETURNING id, team_id, title, created_at, updated_at",
+        )
+        .bind(team_uuid)
+        .bind(&conversation.title)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Conversation::from(result))
+    }
+
+    async fn get_conversation(&self, id: &str) -> Result<Option<Conversation>, DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        let result: Option<ConversationRow> = sqlx::query_as(
+            "SELECT id, team_id, title, created_at, updated_at FROM conversations WHERE id = $1",
+        )
+        .bind(uuid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(Conversation::from))
+    }
+
+    async fn append_conversation_messages(
+        &self,
+        conversation_id: &str,
+        messages: &[ChatMessage],
+    ) -> Result<Vec<ConversationMessage>, DbError> {
+        let uuid = uuid::Uuid::parse_str(conversation_id)
+            .map_err(|_| DbError::InvalidUuid(conversation_id.to_string()))?;
+        let mut tx = self.pool.begin().await?;
+        // Also locks the conversation, so concurrent appends keep their order.
+        let result = sqlx::query("UPDATE conversations SET updated_at = NOW() WHERE id = $1")
+            .bind(uuid)
+            .execute(&mut *tx)
+            .await?;
+        if result.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+        let mut appended = Vec::with_capacity(messages.len());
+        for message in messages {
+            let row: ConversationMessageRow = sqlx::query_as(
+                "INSERT INTO conversation_messages (conversation_id, role, content) VALUES ($1, $2, $3) RETURNING id, conversation_id, role, content, created_at",
+            )
+            .bind(uuid)
+            .bind(message_role_str(&message.role))
+            .bind(&message.content)
+            .fetch_one(&mut *tx)
+            .await?;
+            appended.push(ConversationMessage::from(row));
+        }
+        tx.commit().await?;
+        Ok(appended)
+    }
+
+    async fn list_conversation_messages(
+        &self,
+        conversation_id: &str,
+    ) -> Result<Vec<ConversationMessage>, DbError> {
+        let uuid = uuid::Uuid::parse_str(conversation_id)
+            .map_err(|_| DbError::InvalidUuid(conversation_id.to_string()))?;
+        let rows: Vec<ConversationMessageRow> = sqlx::query_as(
+            "SELECT id, conversation_id, role, content, created_at FROM conversation_messages WHERE conversation_id = $1 ORDER BY id",
+        )
+        .bind(uuid)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(ConversationMessage::from).collect())
+    }
+
+    async fn create_eval_dataset(&self, dataset: &NewEvalDataset) -> Result<EvalDataset, DbError> {
+        let team_uuid = uuid::Uuid::parse_str(&dataset.team_id)
+            .map_err(|_| DbError::InvalidUuid(dataset.team_id.clone()))?;
+        let result: EvalDatasetRow = sqlx::query_as(
+            "INSERT INTO eval_datasets (team_id, name, items) VALUES ($1, $2, $3) RETURNING id, team_id, name, items, created_at",
+        )
+        .bind(team_uuid)
+        .bind(&dataset.name)
+        .bind(Json(&dataset.items))
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(EvalDataset::from(result))
+    }
+
+    async fn get_eval_dataset(&self, id: &str) -> Result<Option<EvalDataset>, DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        let result: Option<EvalDatasetRow> = sqlx::query_as(
+            "SELECT id, team_id, name, items, created_at FROM eval_datasets WHERE id = $1",
+        )
+        .bind(uuid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(EvalDataset::from))
+    }
+
+    async fn create_eval_run(&self, run: &NewEvalRun) -> Result<EvalRun, DbError> {
+        let dataset_uuid = uuid::Uuid::parse_str(&run.dataset_id)
+            .map_err(|_| DbError::InvalidUuid(run.dataset_id.clone()))?;
+        let count = run.results.len().max(1) as f64;
+        let passed = run.results.iter().filter(|r| r.passed).count();
+        let mean_score = run.results.iter().map(|r| r.score).sum::<f64>() / count;
+        let mean_latency = run.results.iter().map(|r| r.latency_ms as f64).sum::<f64>() / count;
+
+        let mut tx = self.pool.begin().await?;
+        let row: EvalRunRow = sqlx::query_as(
+            "INSERT INTO eval_runs (dataset_id, model, item_count, passed_count, mean_score, mean_latency_ms) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id, dataset_id, model, item_count, passed_count, mean_score, mean_latency_ms, created_at",
+        )
+        .bind(dataset_uuid)
+        .bind(&run.model)
+        .bind(run.results.len() as i32)
+        .bind(passed as i32)
+        .bind(mean_score)
+        .bind(mean_latency)
+        .fetch_one(&mut *tx)
+        .await?;
+        for result in &run.results {
+            sqlx::query(
+                "INSERT INTO eval_results (run_id, item_index, answer, error, score, passed, latency_ms, input_tokens, output_tokens) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            )
+            .bind(row.id)
+            .bind(result.item_index as i32)
+            .bind(&result.answer)
+            .bind(&result.error)
+            .bind(result.score)
+            .bind(result.passed)
+            .bind(result.latency_ms as i64)
+            .bind(result.input_tokens as i32)
+            .bind(result.output_tokens as i32)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                if e.as_database_error().map(|db| db.is_unique_violation()).unwrap_or(false) {
+                    DbError::UniqueViolation(format!(
+                        "Item {} appears more than once",
+                        result.item_index
+                    ))
+                } else {
+                    DbError::Sqlx(e)
+                }
+            })?;
+        }
+        tx.commit().await?;
+        Ok(EvalRun::from(row))
+    }
+
+    async fn get_eval_run(&self, id: &str) -> Result<Option<EvalRun>, DbError> {
+        let uuid = uuid::Uuid::parse_str(id).map_err(|_| DbError::InvalidUuid(id.to_string()))?;
+        let result: Option<EvalRunRow> = sqlx::query_as(
+            "SELECT id, dataset_id, model, item_count, passed_count, mean_score, mean_latency_ms, created_at FROM eval_runs WHERE id = $1",
+        )
+        .bind(uuid)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result.map(EvalRun::from))
+    }
+
+    async fn list_eval_runs(&self, dataset_id: &str) -> Result<Vec<EvalRun>, DbError> {
+        let uuid = uuid::Uuid::parse_str(dataset_id)
+            .map_err(|_| DbError::InvalidUuid(dataset_id.to_string()))?;
+        let rows: Vec<EvalRunRow> = sqlx::query_as(
+            "SELECT id, dataset_id, model, item_count, passed_count, mean_score, mean_latency_ms, created_at FROM eval_runs WHERE dataset_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(uuid)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(EvalRun::from).collect())
+    }
+
+    async fn list_eval_results(&self, run_id: &str) -> Result<Vec<EvalItemResult>, DbError> {
+        let uuid =
+            uuid::Uuid::parse_str(run_id).map_err(|_| DbError::InvalidUuid(run_id.to_string()))?;
+        let rows: Vec<EvalResultRow> = sqlx::query_as(
+            "SELECT item_index, answer, error, score, passed, latency_ms, input_tokens, output_tokens FROM eval_results WHERE run_id = $1 ORDER BY item_index",
+        )
+        .bind(uuid)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(EvalItemResult::from).collect())
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+struct TeamRow {
+    id: uuid::Uuid,
+    name: String,
+    budget_cents: i64,
+    billing_anchor_day: i32,
+    billing_timezone: String,
+    organization_id: Option<uuid::Uuid>,
+    data_region: Option<String>,
+    features: Json<HashMap<String, bool>>,
+    param_clamps: Option<Json<ParamClamps>>,
+    output_transforms: Json<Vec<OutputTransform>>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<TeamRow> for Team {
+    fn from(row: TeamRow) -> Self {
+        Team {
+            id: row.id.to_string(),
+            name: row.name,
+            budget_cents: row.budget_cents,
+            billing_anchor_day: row.billing_anchor_day,
+            billing_timezone: row.billing_timezone,
+            organization_id: row.organization_id.map(|id| id.to_string()),
+            data_region: row.data_region,
+            features: row.features.0,
+            param_clamps: row.param_clamps.map(|clamps| clamps.0),
+            output_transforms: row.output_transforms.0,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+struct OrganizationRow {
+    id: uuid::Uuid,
+    name: String,
+    budget_cents: i64,
+    rpm_limit: Option<i32>,
+    tpm_limit: Option<i32>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<OrganizationRow> for Organization {
+    fn from(row: OrganizationRow) -> Self {
+        Organization {
+            id: row.id.to_string(),
+            name: row.name,
+            budget_cents: row.budget_cents,
+            rpm_limit: row.rpm_limit,
+            tpm_limit: row.tpm_limit,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+struct UserRow {
+    id: uuid::Uuid,
+    team_id: uuid::Uuid,
+    email: String,
+    role: String,
+    created_at: DateTime<Utc>,
+}
+
+impl From<UserRow> for User {
+    fn from(row: UserRow) -> Self {
+        User {
+            id: row.id.to_string(),
+            team_id: row.team_id.to_string(),
+            email: row.email,
+            // The users_role_valid constraint keeps this parseable.
+            role: row.role.parse().unwrap_or_default(),
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+struct RoleChangeRow {
+    id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    old_role: String,
+    new_role: String,
+    changed_by: String,
+    created_at: DateTime<Utc>,
+}
+
+impl From<RoleChangeRow> for RoleChange {
+    fn from(row: RoleChangeRow) -> Self {
+        RoleChange {
+            id: row.id.to_string(),
+            user_id: row.user_id.to_string(),
+            old_role: row.old_role.parse().unwrap_or_default(),
+            new_role: row.new_role.parse().unwrap_or_default(),
+            changed_by: row.changed_by,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+struct ApiKeyRow {
+    id: uuid::Uuid,
+    key_hash: String,
+    user_id: uuid::Uuid,
+    team_id: uuid::Uuid,
+    name: Option<String>,
+    is_active: bool,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    tags: Vec<String>,
+    allowed_models: Vec<String>,
+    budget_cents: Option<i64>,
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiKeyRow> for ApiKey {
+    fn from(row: ApiKeyRow) -> Self {
+        ApiKey {
+            id: row.id.to_string(),
+            key_hash: row.key_hash,
+            user_id: row.user_id.to_string(),
+            team_id: row.team_id.to_string(),
+            name: row.name,
+            is_active: row.is_active,
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+            tags: row.tags,
+            allowed_models: row.allowed_models,
+            budget_cents: row.budget_cents,
+            last_used_at: row.last_used_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+struct ModelAliasRow {
+    id: uuid::Uuid,
+    team_id: uuid::Uuid,
+    alias: String,
+    target_model: String,
+    provider: String,
+    created_at: DateTime<Utc>,
+}
+
+impl From<ModelAliasRow> for ModelAlias {
+    fn from(row: ModelAliasRow) -> Self {
+        ModelAlias {
+            id: row.id.to_string(),
+            team_id: row.team_id.to_string(),
+            alias: row.alias,
+            target_model: row.target_model,
+            provider: row.provider,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+struct QuotaRow {
+    id: uuid::Uuid,
+    team_id: uuid::Uuid,
+    rpm_limit: i32,
+    tpm_limit: i32,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<QuotaRow> for Quota {
+    fn from(row: QuotaRow) -> Self {
+        Quota {
+            id: row.id.to_string(),
+            team_id: row.team_id.to_string(),
+            rpm_limit: row.rpm_limit,
+            tpm_limit: row.tpm_limit,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct BundleTeamRow {
+    name: String,
+    budget_cents: i64,
+    billing_anchor_day: i32,
+    billing_timezone: String,
+}
+
+impl From<BundleTeamRow> for BundleTeam {
+    fn from(row: BundleTeamRow) -> Self {
+        BundleTeam {
+            name: row.name,
+            budget_cents: row.budget_cents,
+            billing_anchor_day: row.billing_anchor_day,
+            billing_timezone: row.billing_timezone,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct BundleUserRow {
+    email: String,
+    team: String,
+    role: String,
+}
+
+impl From<BundleUserRow> for BundleUser {
+    fn from(row: BundleUserRow) -> Self {
+        BundleUser {
+            email: row.email,
+            team: row.team,
+            role: row.role.parse().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct BundleApiKeyRow {
+    key_hash: String,
+    team: String,
+    user: Option<String>,
+    name: Option<String>,
+    is_active: bool,
+    expires_at: Option<DateTime<Utc>>,
+    tags: Vec<String>,
+    allowed_models: Vec<String>,
+    budget_cents: Option<i64>,
+}
+
+impl From<BundleApiKeyRow> for BundleApiKey {
+    fn from(row: BundleApiKeyRow) -> Self {
+        BundleApiKey {
+            key_hash: row.key_hash,
+            team: row.team,
+            user: row.user,
+            name: row.name,
+            is_active: row.is_active,
+            expires_at: row.expires_at,
+            tags: row.tags,
+            allowed_models: row.allowed_models,
+            budget_cents: row.budget_cents,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct BundleModelAliasRow {
+    team: String,
+    alias: String,
+    target_model: String,
+    provider: String,
+}
+
+impl From<BundleModelAliasRow> for BundleModelAlias {
+    fn from(row: BundleModelAliasRow) -> Self {
+        BundleModelAlias {
+            team: row.team,
+            alias: row.alias,
+            target_model: row.target_model,
+            provider: row.provider,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct BundleQuotaRow {
+    team: String,
+    rpm_limit: i32,
+    tpm_limit: i32,
+}
+
+impl From<BundleQuotaRow> for BundleQuota {
+    fn from(row: BundleQuotaRow) -> Self {
+        BundleQuota {
+            team: row.team,
+            rpm_limit: row.rpm_limit,
+            tpm_limit: row.tpm_limit,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct QuotaTemplateRow {
+    id: uuid::Uuid,
+    name: String,
+    rpm_limit: i32,
+    tpm_limit: i32,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<QuotaTemplateRow> for QuotaTemplate {
+    fn from(row: QuotaTemplateRow) -> Self {
+        QuotaTemplate {
+            id: row.id.to_string(),
+            name: row.name,
+            rpm_limit: row.rpm_limit,
+            tpm_limit: row.tpm_limit,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+struct UsageLogRow {
+    id: uuid::Uuid,
+    team_id: uuid::Uuid,
+    api_key_id: uuid::Uuid,
+    model: String,
+    input_tokens: i32,
+    output_tokens: i32,
+    response_time_ms: i64,
+    recorded_at: DateTime<Utc>,
+    metadata: Json<HashMap<String, String>>,
+}
+
+impl From<UsageLogRow> for UsageLog {
+    fn from(row: UsageLogRow) -> Self {
+        UsageLog {
+            id: row.id.to_string(),
+            team_id: row.team_id.to_string(),
+            api_key_id: row.api_key_id.to_string(),
+            model: row.model,
+            input_tokens: row.input_tokens,
+            output_tokens: row.output_tokens,
+            response_time_ms: row.response_time_ms,
+            recorded_at: row.recorded_at,
+            metadata: row.metadata.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct TagUsageRow {
+    tag_value: Option<String>,
+    model: String,
+    requests: i64,
+    input_tokens: i64,
+    output_tokens: i64,
+}
+
+impl From<TagUsageRow> for TagUsage {
+    fn from(row: TagUsageRow) -> Self {
+        TagUsage {
+            tag_value: row.tag_value,
+            model: row.model,
+            requests: row.requests,
+            input_tokens: row.input_tokens,
+            output_tokens: row.output_tokens,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct KeyUsageBucketRow {
+    api_key_id: uuid::Uuid,
+    model: String,
+    windows_ago: i64,
+    hour: i32,
+    requests: i64,
+}
+
+impl From<KeyUsageBucketRow> for KeyUsageBucket {
+    fn from(row: KeyUsageBucketRow) -> Self {
+        KeyUsageBucket {
+            api_key_id: row.api_key_id.to_string(),
+            model: row.model,
+            windows_ago: row.windows_ago,
+            hour: row.hour,
+            requests: row.requests,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct ModelUsageRow {
+    model: String,
+    requests: i64,
+    input_tokens: i64,
+    output_tokens: i64,
+}
+
+impl From<ModelUsageRow> for ModelUsage {
+    fn from(row: ModelUsageRow) -> Self {
+        ModelUsage {
+            model: row.model,
+            requests: row.requests,
+            input_tokens: row.input_tokens,
+            output_tokens: row.output_tokens,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+struct AlertRuleRow {
+    id: uuid::Uuid,
+    team_id: Option<uuid::Uuid>,
+    kind: String,
+    threshold: f64,
+    provider: Option<String>,
+    window_minutes: i32,
+    webhook_url: String,
+    webhook_format: String,
+    is_active: bool,
+    created_at: DateTime<Utc>,
+}
+
+impl From<AlertRuleRow> for AlertRule {
+    fn from(row: AlertRuleRow) -> Self {
+        AlertRule {
+            id: row.id.to_string(),
+            team_id: row.team_id.map(|id| id.to_string()),
+            kind: row.kind,
+            threshold: row.threshold,
+            provider: row.provider,
+            window_minutes: row.window_minutes,
+            webhook_url: row.webhook_url,
+            webhook_format: row.webhook_format,
+            is_active: row.is_active,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+struct AlertRow {
+    id: uuid::Uuid,
+    rule_id: uuid::Uuid,
+    team_id: Option<uuid::Uuid>,
+    kind: String,
+    message: String,
+    observed_value: f64,
+    threshold: f64,
+    delivered: bool,
+    fired_at: DateTime<Utc>,
+    resolved_at: Option<DateTime<Utc>>,
+}
+
+impl From<AlertRow> for Alert {
+    fn from(row: AlertRow) -> Self {
+        Alert {
+            id: row.id.to_string(),
+            rule_id: row.rule_id.to_string(),
+            team_id: row.team_id.map(|id| id.to_string()),
+            kind: row.kind,
+            message: row.message,
+            observed_value: row.observed_value,
+            threshold: row.threshold,
+            delivered: row.delivered,
+            fired_at: row.fired_at,
+            resolved_at: row.resolved_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+struct BudgetPolicyRow {
+    team_id: uuid::Uuid,
+    action: String,
+    throttle_rpm: Option<i32>,
+    enforced_action: Option<String>,
+    enforced_at: Option<DateTime<Utc>>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<BudgetPolicyRow> for BudgetPolicy {
+    fn from(row: BudgetPolicyRow) -> Self {
+        BudgetPolicy {
+            team_id: row.team_id.to_string(),
+            action: row.action,
+            throttle_rpm: row.throttle_rpm,
+            enforced_action: row.enforced_action,
+            enforced_at: row.enforced_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+struct BillingPeriodRow {
+    id: uuid::Uuid,
+    team_id: uuid::Uuid,
+    period_start: DateTime<Utc>,
+    period_end: DateTime<Utc>,
+    requests: i64,
+    input_tokens: i64,
+    output_tokens: i64,
+    spend_cents: f64,
+    budget_cents: i64,
+    closed_at: DateTime<Utc>,
+}
+
+impl From<BillingPeriodRow> for BillingPeriod {
+    fn from(row: BillingPeriodRow) -> Self {
+        BillingPeriod {
+            id: row.id.to_string(),
+            team_id: row.team_id.to_string(),
+            period_start: row.period_start,
+            period_end: row.period_end,
+            requests: row.requests,
+            input_tokens: row.input_tokens,
+            output_tokens: row.output_tokens,
+            spend_cents: row.spend_cents,
+            budget_cents: row.budget_cents,
+            closed_at: row.closed_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+struct ModelPriceRow {
+    id: uuid::Uuid,
+    model: String,
+    provider: String,
+    input_per_mtok: f64,
+    output_per_mtok: f64,
+    effective_from: DateTime<Utc>,
+}
+
+impl From<ModelPriceRow> for ConfiguredPrice {
+    fn from(row: ModelPriceRow) -> Self {
+        ConfiguredPrice {
+            id: row.id.to_string(),
+            model: row.model,
+            provider: row.provider,
+            input_per_mtok: row.input_per_mtok,
+            output_per_mtok: row.output_per_mtok,
+            effective_from: row.effective_from,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct WebhookRow {
+    id: uuid::Uuid,
+    team_id: uuid::Uuid,
+    url: String,
+    events: Vec<String>,
+    secret: String,
+    is_active: bool,
+    created_at: DateTime<Utc>,
+}
+
+impl From<WebhookRow> for Webhook {
+    fn from(row: WebhookRow) -> Self {
+        Webhook {
+            id: row.id.to_string(),
+            team_id: row.team_id.to_string(),
+            url: row.url,
+            events: row.events,
+            secret: row.secret,
+            is_active: row.is_active,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct ConversationRow {
+    id: uuid::Uuid,
+    team_id: uuid::Uuid,
+    title: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<ConversationRow> for Conversation {
+    fn from(row: ConversationRow) -> Self {
+        Conversation {
+            id: row.id.to_string(),
+            team_id: row.team_id.to_string(),
+            title: row.title,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct ConversationMessageRow {
+    id: i64,
+    conversation_id: uuid::Uuid,
+    role: String,
+    content: String,
+    created_at: DateTime<Utc>,
+}
+
+impl From<ConversationMessageRow> for ConversationMessage {
+    fn from(row: ConversationMessageRow) -> Self {
+        ConversationMessage {
+            id: row.id,
+            conversation_id: row.conversation_id.to_string(),
+            // The column only admits the three roles.
+            role: match row.role.as_str() {
+                "system" => MessageRole::System,
+                "assistant" => MessageRole::Assistant,
+                _ => MessageRole::User,
+            },
+            content: row.content,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct EvalDatasetRow {
+    id: uuid::Uuid,
+    team_id: uuid::Uuid,
+    name: String,
+    items: Json<Vec<EvalItem>>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<EvalDatasetRow> for EvalDataset {
+    fn from(row: EvalDatasetRow) -> Self {
+        EvalDataset {
+            id: row.id.to_string(),
+            team_id: row.team_id.to_string(),
+            name: row.name,
+            items: row.items.0,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct EvalRunRow {
+    id: uuid::Uuid,
+    dataset_id: uuid::Uuid,
+    model: String,
+    item_count: i32,
+    passed_count: i32,
+    mean_score: f64,
+    mean_latency_ms: f64,
+    created_at: DateTime<Utc>,
+}
+
+impl From<EvalRunRow> for EvalRun {
+    fn from(row: EvalRunRow) -> Self {
+        EvalRun {
+            id: row.id.to_string(),
+            dataset_id: row.dataset_id.to_string(),
+            model: row.model,
+            item_count: row.item_count,
+            passed_count: row.passed_count,
+            mean_score: row.mean_score,
+            mean_latency_ms: row.mean_latency_ms,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct EvalResultRow {
+    item_index: i32,
+    answer: Option<String>,
+    error: Option<String>,
+    score: f64,
+    passed: bool,
+    latency_ms: i64,
+    input_tokens: i32,
+    output_tokens: i32,
+}
+
+impl From<EvalResultRow> for EvalItemResult {
+    fn from(row: EvalResultRow) -> Self {
+        EvalItemResult {
+            item_index: row.item_index as u32,
+            answer: row.answer,
+            error: row.error,
+            score: row.score,
+            passed: row.passed,
+            latency_ms: row.latency_ms as u64,
+            input_tokens: row.input_tokens as u32,
+            output_tokens: row.output_tokens as u32,
+        }
+    }
+}
+
+fn message_role_str(role: &MessageRole) -> &'static str {
+    match role {
+        MessageRole::System => "system",
+        MessageRole::User => "user",
+        MessageRole::Assistant => "assistant",
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct WebhookDeliveryRow {
+    id: uuid::Uuid,
+    webhook_id: uuid::Uuid,
+    event: String,
+    payload: Json<serde_json::Value>,
+    status: String,
+    attempts: i32,
+    response_status: Option<i32>,
+    last_error: Option<String>,
+    next_attempt_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+    delivered_at: Option<DateTime<Utc>>,
+}
+
+impl From<WebhookDeliveryRow> for WebhookDelivery {
+    fn from(row: WebhookDeliveryRow) -> Self {
+        WebhookDelivery {
+            id: row.id.to_string(),
+            webhook_id: row.webhook_id.to_string(),
+            event: row.event,
+            payload: row.payload.0,
+            status: row.status,
+            attempts: row.attempts,
+            response_status: row.response_status,
+            last_error: row.last_error,
+            next_attempt_at: row.next_attempt_at,
+            created_at: row.created_at,
+            delivered_at: row.delivered_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct ReportSubscriptionRow {
+    id: uuid::Uuid,
+    team_id: uuid::Uuid,
+    frequency: String,
+    recipients: Vec<String>,
+    last_period_end: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<ReportSubscriptionRow> for ReportSubscription {
+    fn from(row: ReportSubscriptionRow) -> Self {
+        ReportSubscription {
+            id: row.id.to_string(),
+            team_id: row.team_id.to_string(),
+            frequency: row.frequency,
+            recipients: row.recipients,
+            last_period_end: row.last_period_end,
+            created_at: row.created_at,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RedisConfigStore {
+    manager: hyperinfer_core::redis::ConfigManager,
+}
+
+impl RedisConfigStore {
+    pub async fn new(redis_url: &str) -> Result<Self, hyperinfer_core::ConfigError> {
+        let manager = hyperinfer_core::redis::ConfigManager::new(redis_url).await?;
+        Ok(Self { manager })
+    }
+
+    /// A store on the shared connection of `redis`.
+    pub fn with_redis(redis: &hyperinfer_core::RedisHandle) -> Self {
+        Self {
+            manager: hyperinfer_core::redis::ConfigManager::with_redis(redis),
+        }
+    }
+
+    pub async fn subscribe_to_config_updates(
+        &self,
+        config: std::sync::Arc<tokio::sync::RwLock<hyperinfer_core::Config>>,
+    ) -> Result<tokio::task::JoinHandle<()>, hyperinfer_core::ConfigError> {
+        self.manager.subscribe_to_config_updates(config).await
+    }
+}
+
+#[async_trait]
+impl ConfigStore for RedisConfigStore {
+    async fn fetch_config(&self) -> Result<hyperinfer_core::Config, hyperinfer_core::ConfigError> {
+        self.manager.fetch_config().await
+    }
+
+    async fn publish_config_update(
+        &self,
+        config: &hyperinfer_core::Config,
+    ) -> Result<u64, hyperinfer_core::ConfigError> {
+        self.manager.publish_config_update(config).await
+    }
+
+    async fn fetch_config_version(
+        &self,
+        version: u64,
+    ) -> Result<Option<hyperinfer_core::Config>, hyperinfer_core::ConfigError> {
+        self.manager.fetch_config_version(version).await
+    }
+
+    async fn fleet_heartbeats(
+        &self,
+    ) -> Result<Vec<hyperinfer_core::InstanceHeartbeat>, hyperinfer_core::ConfigError> {
+        self.manager.fleet_heartbeats().await
+    }
+
+    async fn take_api_key_uses(
+        &self,
+    ) -> Result<HashMap<String, DateTime<Utc>>, hyperinfer_core::ConfigError> {
+        self.manager.take_api_key_uses().await
     }
 
     async fn publish_policy_update(
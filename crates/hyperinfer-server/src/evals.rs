@@ -0,0 +1,249 @@
+//! Eval datasets, runs and their comparison.
+//!
+//! Datasets are defined here and run by clients through the data plane
+//! (see `hyperinfer_core::evals`); the control plane checks and stores the
+//! submitted results and lines runs up item by item, so a model swap shows
+//! which prompts it fixed and which it broke.
+
+use hyperinfer_core::{
+    Database, DbError, EvalDataset, EvalItemResult, EvalRun, NewEvalDataset, NewEvalRun,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// Most runs one comparison may cover.
+pub const MAX_COMPARED_RUNS: usize = 10;
+
+pub fn validate_dataset(dataset: &NewEvalDataset) -> Result<(), String> {
+    if dataset.name.trim().is_empty() {
+        return Err("Dataset name must not be empty".to_string());
+    }
+    if dataset.items.is_empty() {
+        return Err("A dataset needs at least one item".to_string());
+    }
+    for (index, item) in dataset.items.iter().enumerate() {
+        if item.messages.is_empty() {
+            return Err(format!("Item {} has no messages", index));
+        }
+        if item.criteria.is_empty() {
+            return Err(format!("Item {} has no criteria", index));
+        }
+        for criterion in &item.criteria {
+            criterion
+                .validate()
+                .map_err(|e| format!("Item {}: {}", index, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Check a submitted run against its dataset: one result per item at
+/// most, each naming an item that exists, with a score from 0 to 1.
+pub fn validate_run(dataset: &EvalDataset, run: &NewEvalRun) -> Result<(), String> {
+    if run.model.trim().is_empty() {
+        return Err("model must not be empty".to_string());
+    }
+    if run.results.is_empty() {
+        return Err("A run needs at least one result".to_string());
+    }
+    let mut seen = vec![false; dataset.items.len()];
+    for result in &run.results {
+        let index = result.item_index as usize;
+        match seen.get_mut(index) {
+            None => {
+                return Err(format!(
+                    "Item {} is out of range; the dataset has {} items",
+                    index,
+                    dataset.items.len()
+                ))
+            }
+            Some(true) => return Err(format!("Item {} appears more than once", index)),
+            Some(seen) => *seen = true,
+        }
+        if !(0.0..=1.0).contains(&result.score) {
+            return Err(format!("Item {} has a score outside 0 to 1", index));
+        }
+    }
+    Ok(())
+}
+
+/// How one run did on an item.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ItemOutcome {
+    pub run_id: String,
+    pub score: f64,
+    pub passed: bool,
+    pub error: Option<String>,
+}
+
+/// An item across the compared runs.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ItemComparison {
+    pub item_index: u32,
+    /// One entry per compared run, in the order the runs were given;
+    /// `None` where a run has no result for the item.
+    pub outcomes: Vec<Option<ItemOutcome>>,
+    /// Whether the runs disagree on passing the item.
+    pub changed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct EvalComparison {
+    pub runs: Vec<EvalRun>,
+    pub items: Vec<ItemComparison>,
+}
+
+/// Line up `runs`, each with its results, item by item.  The runs must
+/// all be of the same dataset.
+pub fn compare(runs: Vec<(EvalRun, Vec<EvalItemResult>)>) -> Result<EvalComparison, String> {
+    if let Some((first, _)) = runs.first() {
+        if let Some((other, _)) = runs
+            .iter()
+            .find(|(run, _)| run.dataset_id != first.dataset_id)
+        {
+            return Err(format!(
+                "Runs {} and {} are of different datasets",
+                first.id, other.id
+            ));
+        }
+    }
+    let mut by_item: HashMap<u32, Vec<Option<ItemOutcome>>> = HashMap::new();
+    for (position, (run, results)) in runs.iter().enumerate() {
+        for result in results {
+            let outcomes = by_item
+                .entry(result.item_index)
+                .or_insert_with(|| vec![None; runs.len()]);
+            outcomes[position] = Some(ItemOutcome {
+                run_id: run.id.clone(),
+                score: result.score,
+                passed: result.passed,
+                error: result.error.clone(),
+            });
+        }
+    }
+    let mut items: Vec<ItemComparison> = by_item
+        .into_iter()
+        .map(|(item_index, outcomes)| {
+            let passed: Vec<bool> = outcomes
+                .iter()
+                .map(|outcome| outcome.as_ref().is_some_and(|o| o.passed))
+                .collect();
+            ItemComparison {
+                item_index,
+                changed: passed.windows(2).any(|pair| pair[0] != pair[1]),
+                outcomes,
+            }
+        })
+        .collect();
+    items.sort_by_key(|item| item.item_index);
+    Ok(EvalComparison {
+        runs: runs.into_iter().map(|(run, _)| run).collect(),
+        items,
+    })
+}
+
+/// The runs with `run_ids`, each with its results.  Returns
+/// `DbError::NotFound` if any run does not exist.
+pub async fn load_runs<D: Database>(
+    db: &D,
+    run_ids: &[String],
+) -> Result<Vec<(EvalRun, Vec<EvalItemResult>)>, DbError> {
+    let mut runs = Vec::with_capacity(run_ids.len());
+    for id in run_ids {
+        let run = db.get_eval_run(id).await?.ok_or(DbError::NotFound)?;
+        let results = db.list_eval_results(id).await?;
+        runs.push((run, results));
+    }
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use hyperinfer_core::{ChatMessage, EvalCriterion, EvalItem, MessageRole};
+
+    fn run(id: &str) -> EvalRun {
+        EvalRun {
+            id: id.to_string(),
+            dataset_id: "dataset".to_string(),
+            model: id.to_string(),
+            item_count: 2,
+            passed_count: 1,
+            mean_score: 0.5,
+            mean_latency_ms: 0.0,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn result(item_index: u32, passed: bool) -> EvalItemResult {
+        EvalItemResult {
+            item_index,
+            answer: Some("answer".to_string()),
+            error: None,
+            score: if passed { 1.0 } else { 0.0 },
+            passed,
+            latency_ms: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+        }
+    }
+
+    #[test]
+    fn test_compare_flags_items_whose_outcome_changed() {
+        let comparison = compare(vec![
+            (run("a"), vec![result(0, true), result(1, false)]),
+            (
+                run("b"),
+                vec![result(0, true), result(1, true), result(2, false)],
+            ),
+        ])
+        .unwrap();
+        assert_eq!(comparison.runs.len(), 2);
+        let changed: Vec<(u32, bool)> = comparison
+            .items
+            .iter()
+            .map(|item| (item.item_index, item.changed))
+            .collect();
+        assert_eq!(changed, [(0, false), (1, true), (2, false)]);
+        assert!(comparison.items[2].outcomes[0].is_none());
+
+        let mut other = run("c");
+        other.dataset_id = "other".to_string();
+        assert!(compare(vec![(run("a"), Vec::new()), (other, Vec::new())]).is_err());
+    }
+
+    #[test]
+    fn test_validate_run_against_dataset() {
+        let dataset = EvalDataset {
+            id: "dataset".to_string(),
+            team_id: "team".to_string(),
+            name: "smoke".to_string(),
+            items: vec![EvalItem {
+                messages: vec![ChatMessage {
+                    role: MessageRole::User,
+                    content: "2+2?".to_string(),
+                }],
+                criteria: vec![EvalCriterion::Contains {
+                    text: "4".to_string(),
+                }],
+            }],
+            created_at: Utc::now(),
+        };
+        let new_run = |results| NewEvalRun {
+            dataset_id: "dataset".to_string(),
+            model: "gpt-4".to_string(),
+            results,
+        };
+        assert!(validate_run(&dataset, &new_run(vec![result(0, true)])).is_ok());
+        assert!(validate_run(&dataset, &new_run(vec![result(1, true)])).is_err());
+        assert!(validate_run(&dataset, &new_run(vec![result(0, true), result(0, true)])).is_err());
+        assert!(validate_dataset(&NewEvalDataset {
+            team_id: "team".to_string(),
+            name: "empty".to_string(),
+            items: Vec::new(),
+        })
+        .is_err());
+    }
+}
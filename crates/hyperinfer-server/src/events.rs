@@ -0,0 +1,352 @@
+//! Live event feed.
+//!
+//! An [`EventHub`] fans live traffic out to `GET /v1/ws/events` subscribers:
+//! usage and failed requests tailed from the telemetry stream, rate-limit
+//! rejections that clients publish on [`EVENTS_CHANNEL`], and config / policy
+//! changes from their pub/sub channels.  Every server instance tails the whole
+//! stream with a plain `XREAD` (not the usage consumer group), so a dashboard
+//! connected to any instance sees all traffic.
+//!
+//! Raw API keys never leave the server: events identify keys by their
+//! SHA-256 hash, and config changes are announced without the config itself,
+//! which holds provider credentials.
+
+use futures::StreamExt;
+use hyperinfer_core::redis::{CONFIG_CHANNEL, EVENTS_CHANNEL, POLICY_CHANNEL};
+use hyperinfer_core::telemetry_consumer::{shard_stream_keys, DEFAULT_TELEMETRY_STREAM};
+use hyperinfer_core::{PolicyUpdate, RateLimitRejection, TelemetryConsumer, UsageRecord};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+/// Events buffered per subscriber before slow subscribers start skipping.
+pub const EVENT_BUFFER: usize = 1024;
+
+const XREAD_BLOCK_MS: u32 = 5000;
+const XREAD_COUNT: u32 = 100;
+const MAX_BACKOFF_SECS: u64 = 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    /// A completed (or, with `error` set, failed) request.
+    Usage {
+        key_hash: String,
+        model: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        provider: Option<String>,
+        input_tokens: u32,
+        output_tokens: u32,
+        response_time_ms: u64,
+        timestamp: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+        #[serde(skip_serializing_if = "HashMap::is_empty")]
+        metadata: HashMap<String, String>,
+    },
+    RateLimited(RateLimitRejection),
+    /// The routing config was republished; fetch `/v1/config/sync` for it.
+    ConfigUpdated,
+    PolicyUpdated(PolicyUpdate),
+}
+
+/// Every [`LiveEvent`] `type` tag.
+pub const EVENT_TYPES: [&str; 4] = ["usage", "rate_limited", "config_updated", "policy_updated"];
+
+/// Parse a comma-separated `types` filter; `None` (or an empty filter) means
+/// every type.
+pub fn parse_event_types(types: Option<&str>) -> Result<Option<Vec<String>>, String> {
+    let Some(types) = types.filter(|t| !t.trim().is_empty()) else {
+        return Ok(None);
+    };
+    types
+        .split(',')
+        .map(str::trim)
+        .map(|t| {
+            if EVENT_TYPES.contains(&t) {
+                Ok(t.to_string())
+            } else {
+                Err(format!(
+                    "Unknown event type '{}': expected one of {}",
+                    t,
+                    EVENT_TYPES.join(", ")
+                ))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+impl LiveEvent {
+    /// The `type` tag, used to filter subscriptions.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Usage { .. } => "usage",
+            Self::RateLimited(_) => "rate_limited",
+            Self::ConfigUpdated => "config_updated",
+            Self::PolicyUpdated(_) => "policy_updated",
+        }
+    }
+}
+
+impl From<UsageRecord> for LiveEvent {
+    fn from(record: UsageRecord) -> Self {
+        Self::Usage {
+            key_hash: hex::encode(Sha256::digest(record.key.as_bytes())),
+            model: record.model,
+            provider: record.provider,
+            input_tokens: record.input_tokens,
+            output_tokens: record.output_tokens,
+            response_time_ms: record.response_time_ms,
+            timestamp: record.timestamp,
+            error: record.error,
+            metadata: record.metadata,
+        }
+    }
+}
+
+/// Broadcast hub for [`LiveEvent`]s.  Cheap to clone; clones share subscribers.
+#[derive(Clone)]
+pub struct EventHub {
+    tx: broadcast::Sender<LiveEvent>,
+}
+
+impl Default for EventHub {
+    fn default() -> Self {
+        Self::new(EVENT_BUFFER)
+    }
+}
+
+impl EventHub {
+    pub fn new(capacity: usize) -> Self {
+        let (tx, _) = broadcast::channel(capacity);
+        Self { tx }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LiveEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Deliver `event` to current subscribers; dropped when there are none.
+    pub fn publish(&self, event: LiveEvent) {
+        let _ = self.tx.send(event);
+    }
+
+    /// Tail the telemetry stream, split into `telemetry_shards` streams, and
+    /// the event pub/sub channels into the hub until `cancel` fires,
+    /// reconnecting with backoff on Redis errors.
+    pub fn spawn_redis_fanout(
+        &self,
+        client: &redis::Client,
+        telemetry_shards: u32,
+        cancel: CancellationToken,
+    ) -> Vec<tokio::task::JoinHandle<()>> {
+        let client = client.clone();
+        vec![
+            self.spawn_with_backoff("telemetry stream", cancel.clone(), {
+                let client = client.clone();
+                move |hub| Self::tail_telemetry(client.clone(), telemetry_shards, hub)
+            }),
+            self.spawn_with_backoff("event channels", cancel, move |hub| {
+                Self::follow_channels(client.clone(), hub)
+            }),
+        ]
+    }
+
+    fn spawn_with_backoff<F, Fut>(
+        &self,
+        source: &'static str,
+        cancel: CancellationToken,
+        run: F,
+    ) -> tokio::task::JoinHandle<()>
+    where
+        F: Fn(EventHub) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = Result<(), redis::RedisError>> + Send,
+    {
+        let hub = self.clone();
+        tokio::spawn(async move {
+            let mut backoff = 1u64;
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    result = run(hub.clone()) => match result {
+                        Ok(()) => backoff = 1,
+                        Err(e) => tracing::warn!(
+                            "Live event feed from {} failed: {}, reconnecting in {}s",
+                            source,
+                            e,
+                            backoff
+                        ),
+                    }
+                }
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = tokio::time::sleep(Duration::from_secs(backoff)) => {}
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF_SECS);
+            }
+        })
+    }
+
+    async fn tail_telemetry(
+        client: redis::Client,
+        shards: u32,
+        hub: EventHub,
+    ) -> Result<(), redis::RedisError> {
+        let mut conn = client.get_multiplexed_async_connection().await?;
+        let streams = shard_stream_keys(DEFAULT_TELEMETRY_STREAM, shards);
+        // Only new entries: history is what the usage APIs are for.
+        let mut last_ids: HashMap<String, String> = streams
+            .iter()
+            .map(|stream| (stream.clone(), "$".to_string()))
+            .collect();
+        loop {
+            let ids: Vec<&String> = streams.iter().map(|stream| &last_ids[stream]).collect();
+            #[allow(clippy::type_complexity)]
+            let results: Vec<(String, Vec<(String, Vec<(String, String)>)>)> = redis::cmd("XREAD")
+                .arg("COUNT")
+                .arg(XREAD_COUNT)
+                .arg("BLOCK")
+                .arg(XREAD_BLOCK_MS)
+                .arg("STREAMS")
+                .arg(&streams)
+                .arg(ids)
+                .query_async(&mut conn)
+                .await?;
+            for (stream, entries) in results {
+                for (entry_id, fields) in entries {
+                    if let Some(record) = TelemetryConsumer::parse_entry(Some(&entry_id), &fields) {
+                        hub.publish(record.into());
+                    }
+                    last_ids.insert(stream.clone(), entry_id);
+                }
+            }
+        }
+    }
+
+    async fn follow_channels(
+        client: redis::Client,
+        hub: EventHub,
+    ) -> Result<(), redis::RedisError> {
+        let mut pubsub = client.get_async_pubsub().await?;
+        pubsub
+            .subscribe(&[EVENTS_CHANNEL, CONFIG_CHANNEL, POLICY_CHANNEL])
+            .await?;
+        let mut messages = pubsub.on_message();
+        while let Some(msg) = messages.next().await {
+            let Ok(payload) = msg.get_payload::<String>() else {
+                continue;
+            };
+            if let Some(event) = channel_event(msg.get_channel_name(), &payload) {
+                hub.publish(event);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Event for a message received on one of the pub/sub channels.
+pub fn channel_event(channel: &str, payload: &str) -> Option<LiveEvent> {
+    match channel {
+        EVENTS_CHANNEL => serde_json::from_str(payload)
+            .map(LiveEvent::RateLimited)
+            .map_err(|e| tracing::debug!("Ignoring malformed data-plane event: {}", e))
+            .ok(),
+        CONFIG_CHANNEL => Some(LiveEvent::ConfigUpdated),
+        POLICY_CHANNEL => serde_json::from_str(payload)
+            .map(LiveEvent::PolicyUpdated)
+            .map_err(|e| tracing::debug!("Ignoring malformed policy update: {}", e))
+            .ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyperinfer_core::PolicyAction;
+
+    #[test]
+    fn test_usage_event_hides_raw_key() {
+        let event = LiveEvent::from(UsageRecord {
+            key: "abc".to_string(),
+            model: "gpt-4".to_string(),
+            input_tokens: 10,
+            output_tokens: 5,
+            response_time_ms: 100,
+            timestamp: 1,
+            msg_id: Some("1-0".to_string()),
+            provider: None,
+            error: None,
+            metadata: HashMap::new(),
+            compression: None,
+        });
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "usage");
+        assert_eq!(
+            json["key_hash"],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(!json.to_string().contains("\"abc\""));
+        assert!(json.get("error").is_none());
+    }
+
+    #[test]
+    fn test_channel_event() {
+        let rejection = RateLimitRejection {
+            key_hash: "hash".to_string(),
+            model: "gpt-4".to_string(),
+            reason: "Rate limit exceeded".to_string(),
+            timestamp: 1,
+        };
+        let event =
+            channel_event(EVENTS_CHANNEL, &serde_json::to_string(&rejection).unwrap()).unwrap();
+        assert_eq!(event, LiveEvent::RateLimited(rejection));
+        assert_eq!(event.kind(), "rate_limited");
+
+        assert_eq!(
+            channel_event(CONFIG_CHANNEL, "{\"config\":{}}"),
+            Some(LiveEvent::ConfigUpdated)
+        );
+
+        let policy = channel_event(
+            POLICY_CHANNEL,
+            r#"{"key":"hash","action":"revoke","reason":null}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            policy,
+            LiveEvent::PolicyUpdated(PolicyUpdate {
+                action: PolicyAction::Revoke,
+                ..
+            })
+        ));
+
+        assert_eq!(channel_event(EVENTS_CHANNEL, "not json"), None);
+        assert_eq!(channel_event("other", "{}"), None);
+    }
+
+    #[test]
+    fn test_parse_event_types() {
+        assert_eq!(parse_event_types(None), Ok(None));
+        assert_eq!(parse_event_types(Some("")), Ok(None));
+        assert_eq!(
+            parse_event_types(Some("usage, rate_limited")),
+            Ok(Some(vec!["usage".to_string(), "rate_limited".to_string()]))
+        );
+        assert!(parse_event_types(Some("usage,billing")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_hub_broadcasts_to_subscribers() {
+        let hub = EventHub::new(8);
+        let mut first = hub.subscribe();
+        let mut second = hub.clone().subscribe();
+        hub.publish(LiveEvent::ConfigUpdated);
+        assert_eq!(first.recv().await.unwrap(), LiveEvent::ConfigUpdated);
+        assert_eq!(second.recv().await.unwrap(), LiveEvent::ConfigUpdated);
+    }
+}
@@ -0,0 +1,116 @@
+//! Feedback for bandit routing experiments.
+//!
+//! Clients split an experiment's traffic by the signals on its arms (see
+//! `hyperinfer_core::experiments`); this fills them in: quality from the
+//! newest eval run of each arm's model on the experiment's dataset, and
+//! cost per request from the usage recorded over [`SIGNAL_WINDOW_DAYS`].
+
+use crate::billing;
+use chrono::{DateTime, Duration, Utc};
+use hyperinfer_core::{BanditExperiment, Database, DbError, EvalRun, ModelUsage};
+
+/// Days of usage the cost signal is taken over.
+pub const SIGNAL_WINDOW_DAYS: i64 = 7;
+
+/// Usage is recorded under the resolved model, without a provider prefix.
+fn usage_model(arm_model: &str) -> &str {
+    arm_model
+        .split_once('/')
+        .map_or(arm_model, |(_, model)| model)
+}
+
+/// `experiment` with each arm's signals replaced by those in `runs`
+/// (newest first) and `usage`.  Arms without a run or without usage keep
+/// their previous signal.
+pub fn apply_signals(
+    experiment: &BanditExperiment,
+    runs: &[EvalRun],
+    usage: &[ModelUsage],
+    prices: &[hyperinfer_core::ConfiguredPrice],
+    now: DateTime<Utc>,
+) -> BanditExperiment {
+    let mut updated = experiment.clone();
+    for arm in &mut updated.arms {
+        if let Some(run) = runs.iter().find(|run| run.model == arm.model) {
+            arm.quality = Some(run.mean_score);
+        }
+        let model = usage_model(&arm.model);
+        if let Some(u) = usage.iter().find(|u| u.model == model && u.requests > 0) {
+            let spend = billing::spend_cents(std::slice::from_ref(u), prices, now);
+            arm.cost_cents = Some(spend / u.requests as f64);
+        }
+    }
+    updated
+}
+
+/// Refresh `experiment`'s signals from the database.
+pub async fn refresh<D: Database>(
+    db: &D,
+    experiment: &BanditExperiment,
+    now: DateTime<Utc>,
+) -> Result<BanditExperiment, DbError> {
+    let runs = match &experiment.dataset_id {
+        Some(dataset_id) => db.list_eval_runs(dataset_id).await?,
+        None => Vec::new(),
+    };
+    let usage = db
+        .get_all_model_usage_since(now - Duration::days(SIGNAL_WINDOW_DAYS))
+        .await?;
+    let prices = db.list_model_prices().await?;
+    Ok(apply_signals(experiment, &runs, &usage, &prices, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyperinfer_core::BanditArm;
+
+    #[test]
+    fn test_apply_signals_takes_the_newest_run_and_cost_per_request() {
+        let experiment = BanditExperiment {
+            arms: vec![
+                BanditArm {
+                    model: "openai/gpt-4o-mini".to_string(),
+                    quality: None,
+                    cost_cents: None,
+                },
+                BanditArm {
+                    model: "claude-3-haiku".to_string(),
+                    quality: Some(0.4),
+                    cost_cents: None,
+                },
+            ],
+            epsilon: 0.1,
+            cost_weight: 0.0,
+            teams: Vec::new(),
+            dataset_id: Some("dataset".to_string()),
+        };
+        let run = |model: &str, mean_score| EvalRun {
+            id: format!("{}-{}", model, mean_score),
+            dataset_id: "dataset".to_string(),
+            model: model.to_string(),
+            item_count: 10,
+            passed_count: 5,
+            mean_score,
+            mean_latency_ms: 0.0,
+            created_at: Utc::now(),
+        };
+        let runs = [
+            run("openai/gpt-4o-mini", 0.8),
+            run("openai/gpt-4o-mini", 0.6),
+        ];
+        let usage = [ModelUsage {
+            model: "gpt-4o-mini".to_string(),
+            requests: 4,
+            input_tokens: 4_000,
+            output_tokens: 4_000,
+        }];
+
+        let updated = apply_signals(&experiment, &runs, &usage, &[], Utc::now());
+        assert_eq!(updated.arms[0].quality, Some(0.8));
+        let cost = updated.arms[0].cost_cents.unwrap();
+        assert!(cost > 0.0);
+        assert_eq!(updated.arms[1].quality, Some(0.4));
+        assert_eq!(updated.arms[1].cost_cents, None);
+    }
+}
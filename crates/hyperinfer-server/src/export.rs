@@ -0,0 +1,258 @@
+//! Usage export.
+//!
+//! Dumps `usage_logs` as CSV for teams that analyse usage in their own
+//! warehouse.  Exports are either streamed on demand (`GET /v1/usage/export`)
+//! or written on a schedule by a [`UsageExporter`], which uploads each
+//! finished UTC day to an object store (`s3://bucket/prefix`,
+//! `file:///var/exports`, ...) as `usage/date=YYYY-MM-DD/usage.csv`, a layout
+//! S3 / BigQuery external tables can partition on.
+
+use crate::leader::Leadership;
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use futures::Stream;
+use hyperinfer_core::{Database, DbError, UsageLog};
+use object_store::{path::Path, ObjectStore, PutPayload, WriteMultipart};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Usage logs fetched from the database per page.
+pub const EXPORT_PAGE_SIZE: i64 = 1000;
+
+pub const CSV_COLUMNS: [&str; 9] = [
+    "id",
+    "recorded_at",
+    "team_id",
+    "api_key_id",
+    "model",
+    "input_tokens",
+    "output_tokens",
+    "response_time_ms",
+    "metadata",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("Database error: {0}")]
+    Db(#[from] DbError),
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Object store error: {0}")]
+    Store(#[from] object_store::Error),
+}
+
+/// Encode `logs` as CSV rows, preceded by the header row if `with_header`.
+/// Metadata tags are written as a JSON object with sorted keys.
+pub fn csv_chunk(logs: &[UsageLog], with_header: bool) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    if with_header {
+        writer.write_record(CSV_COLUMNS)?;
+    }
+    for log in logs {
+        let metadata: BTreeMap<_, _> = log.metadata.iter().collect();
+        let metadata = serde_json::to_string(&metadata).unwrap_or_else(|_| "{}".to_string());
+        writer.write_record([
+            log.id.as_str(),
+            &log.recorded_at.to_rfc3339(),
+            log.team_id.as_str(),
+            log.api_key_id.as_str(),
+            log.model.as_str(),
+            &log.input_tokens.to_string(),
+            &log.output_tokens.to_string(),
+            &log.response_time_ms.to_string(),
+            &metadata,
+        ])?;
+    }
+    writer.into_inner().map_err(|e| e.into_error().into())
+}
+
+/// Stream the usage logs recorded in `[start, end)` as CSV, one chunk per
+/// page so large exports never sit in memory.  Always yields at least the
+/// header row.
+pub fn usage_csv_stream<D: Database>(
+    db: D,
+    team_id: Option<String>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> impl Stream<Item = Result<Vec<u8>, ExportError>> + Send + 'static {
+    async_stream::try_stream! {
+        let mut offset = 0;
+        loop {
+            let logs = db
+                .list_usage_logs(team_id.clone(), start, end, EXPORT_PAGE_SIZE, offset)
+                .await?;
+            yield csv_chunk(&logs, offset == 0)?;
+            if (logs.len() as i64) < EXPORT_PAGE_SIZE {
+                break;
+            }
+            offset += EXPORT_PAGE_SIZE;
+        }
+    }
+}
+
+/// Object path of the export for `day` under `prefix`.
+pub fn export_path(prefix: &Path, day: NaiveDate) -> Path {
+    prefix
+        .child("usage")
+        .child(format!("date={}", day.format("%Y-%m-%d")))
+        .child("usage.csv")
+}
+
+pub struct UsageExporter<D: Database> {
+    db: D,
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+    last_exported: Mutex<Option<NaiveDate>>,
+}
+
+impl<D: Database> UsageExporter<D> {
+    pub fn new(db: D, store: Arc<dyn ObjectStore>, prefix: Path) -> Self {
+        Self {
+            db,
+            store,
+            prefix,
+            last_exported: Mutex::new(None),
+        }
+    }
+
+    /// Exporter writing to the store at `url`, e.g. `s3://bucket/prefix` or
+    /// `file:///var/exports`.  Credentials and region come from the usual
+    /// environment variables (`AWS_ACCESS_KEY_ID`, `AWS_REGION`, ...).
+    pub fn from_url(db: D, url: &str) -> Result<Self, object_store::Error> {
+        let url = url::Url::parse(url).map_err(|e| object_store::Error::Generic {
+            store: "url",
+            source: Box::new(e),
+        })?;
+        let (store, prefix) = object_store::parse_url_opts(
+            &url,
+            std::env::vars().map(|(k, v)| (k.to_ascii_lowercase(), v)),
+        )?;
+        Ok(Self::new(db, Arc::from(store), prefix))
+    }
+
+    /// Export the previous day once on `interval` until `cancel` fires,
+    /// skipping ticks while this replica is not the scheduler leader.
+    pub fn spawn(
+        self,
+        interval: Duration,
+        leader: Leadership,
+        cancel: CancellationToken,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = ticker.tick() => {
+                        if !leader.is_leader() {
+                            continue;
+                        }
+                        if let Err(e) = self.export_once(Utc::now()).await {
+                            tracing::error!("Usage export failed: {:?}", e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Export the UTC day before `now` unless it is already in the store.
+    /// Returns whether a file was written.
+    pub async fn export_once(&self, now: DateTime<Utc>) -> Result<bool, ExportError> {
+        let Some(day) = now.date_naive().checked_sub_days(Days::new(1)) else {
+            return Ok(false);
+        };
+        if *self.last_exported.lock().unwrap_or_else(|e| e.into_inner()) == Some(day) {
+            return Ok(false);
+        }
+        let path = export_path(&self.prefix, day);
+        let written = match self.store.head(&path).await {
+            Ok(_) => false,
+            Err(object_store::Error::NotFound { .. }) => {
+                self.export_day(day, &path).await?;
+                true
+            }
+            Err(e) => return Err(e.into()),
+        };
+        *self.last_exported.lock().unwrap_or_else(|e| e.into_inner()) = Some(day);
+        Ok(written)
+    }
+
+    async fn export_day(&self, day: NaiveDate, path: &Path) -> Result<(), ExportError> {
+        let start = day.and_time(chrono::NaiveTime::MIN).and_utc();
+        let end = start + chrono::Duration::days(1);
+        let mut upload = WriteMultipart::new(self.store.put_multipart(path).await?);
+        let mut chunks = std::pin::pin!(usage_csv_stream(self.db.clone(), None, start, end));
+        while let Some(chunk) = futures::StreamExt::next(&mut chunks).await {
+            match chunk {
+                Ok(chunk) => upload.put(PutPayload::from(chunk).into()),
+                Err(e) => {
+                    upload.abort().await?;
+                    return Err(e);
+                }
+            }
+        }
+        upload.finish().await?;
+        tracing::info!("Exported usage for {} to {}", day, path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::collections::HashMap;
+
+    fn log(id: &str, metadata: &[(&str, &str)]) -> UsageLog {
+        UsageLog {
+            id: id.to_string(),
+            team_id: "team-1".to_string(),
+            api_key_id: "key-1".to_string(),
+            model: "gpt-4".to_string(),
+            input_tokens: 10,
+            output_tokens: 20,
+            response_time_ms: 150,
+            recorded_at: Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap(),
+            metadata: metadata
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn test_csv_chunk_with_header() {
+        let csv = csv_chunk(&[log("u1", &[("feature", "chat"), ("env", "prod")])], true).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "id,recorded_at,team_id,api_key_id,model,input_tokens,output_tokens,response_time_ms,metadata"
+        );
+        assert_eq!(
+            lines[1],
+            r#"u1,2026-03-01T12:00:00+00:00,team-1,key-1,gpt-4,10,20,150,"{""env"":""prod"",""feature"":""chat""}""#
+        );
+    }
+
+    #[test]
+    fn test_csv_chunk_without_header() {
+        let csv = csv_chunk(&[log("u1", &[]), log("u2", &[])], false).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 2);
+        assert!(csv.starts_with("u1,"));
+        assert!(csv.ends_with(",{}\n"));
+    }
+
+    #[test]
+    fn test_export_path() {
+        let prefix = Path::from("exports");
+        let day = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+        assert_eq!(
+            export_path(&prefix, day).as_ref(),
+            "exports/usage/date=2026-03-01/usage.csv"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! API key last-use tracking.
+//!
+//! Data-plane instances note each API key's latest use in Redis, at most
+//! once a minute per key (see `KEY_LAST_USED_KEY`).  An [`ApiKeyUseFlusher`]
+//! drains those notes into `api_keys.last_used_at` so security can list
+//! keys nobody uses any more with `GET /v1/api_keys/stale`.
+
+use crate::leader::Leadership;
+use chrono::{DateTime, Utc};
+use hyperinfer_core::{ConfigError, ConfigStore, Database, DbError};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// How often recorded uses are written to Postgres.
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Age in days past which `GET /v1/api_keys/stale` reports a key by default.
+pub const DEFAULT_STALE_DAYS: i64 = 90;
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeyUsageError {
+    #[error("Failed to read key uses: {0}")]
+    Config(#[from] ConfigError),
+    #[error("Failed to store key uses: {0:?}")]
+    Db(#[from] DbError),
+}
+
+/// Fold `uses` into `pending`, keeping the latest use of each key.
+pub fn merge_uses(
+    pending: &mut HashMap<String, DateTime<Utc>>,
+    uses: HashMap<String, DateTime<Utc>>,
+) {
+    for (id, at) in uses {
+        pending
+            .entry(id)
+            .and_modify(|latest| *latest = (*latest).max(at))
+            .or_insert(at);
+    }
+}
+
+pub struct ApiKeyUseFlusher<D: Database, C: ConfigStore> {
+    db: D,
+    config_store: C,
+    /// Uses taken from Redis but not yet stored, kept for the next tick when
+    /// Postgres is unavailable.
+    pending: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl<D: Database, C: ConfigStore> ApiKeyUseFlusher<D, C> {
+    pub fn new(db: D, config_store: C) -> Self {
+        Self {
+            db,
+            config_store,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Flush once on `interval` until `cancel` fires, skipping ticks while
+    /// this replica is not the scheduler leader.
+    pub fn spawn(
+        self,
+        interval: Duration,
+        leader: Leadership,
+        cancel: CancellationToken,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = ticker.tick() => {
+                        if !leader.is_leader() {
+                            continue;
+                        }
+                        if let Err(e) = self.flush_once().await {
+                            tracing::error!("Flushing API key uses failed: {}", e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Move recorded uses from Redis to Postgres.  Returns the number of
+    /// keys whose `last_used_at` changed.
+    pub async fn flush_once(&self) -> Result<u64, KeyUsageError> {
+        let taken = self.config_store.take_api_key_uses().await?;
+        let uses = {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            merge_uses(&mut pending, taken);
+            std::mem::take(&mut *pending)
+        };
+        if uses.is_empty() {
+            return Ok(0);
+        }
+        match self.db.record_api_key_uses(&uses).await {
+            Ok(updated) => Ok(updated),
+            Err(e) => {
+                let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+                merge_uses(&mut pending, uses);
+                Err(e.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_uses_keeps_latest() {
+        let earlier = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let later = DateTime::from_timestamp(1_700_000_060, 0).unwrap();
+        let mut pending = HashMap::from([("a".to_string(), later), ("b".to_string(), earlier)]);
+        merge_uses(
+            &mut pending,
+            HashMap::from([
+                ("a".to_string(), earlier),
+                ("b".to_string(), later),
+                ("c".to_string(), earlier),
+            ]),
+        );
+        assert_eq!(pending["a"], later);
+        assert_eq!(pending["b"], later);
+        assert_eq!(pending["c"], earlier);
+    }
+}
@@ -0,0 +1,323 @@
+//! Leader election for periodic jobs.
+//!
+//! Every server replica starts the same background jobs — alert evaluation,
+//! budget enforcement, billing period closing, usage export — but each must
+//! run once per tick across the deployment, not once per replica.  A
+//! [`LeaderElection`] holds a lease in a [`LeaseStore`] (Redis in
+//! production) and renews it every third of its TTL; jobs only do work while
+//! their [`Leadership`] says this replica holds the lease.  When the leader
+//! dies its lease expires and another replica takes over on its next
+//! attempt, at most one TTL later.  A leader that shuts down cleanly
+//! releases the lease so the takeover is immediate.
+//!
+//! A replica that cannot reach the store steps down rather than risk two
+//! leaders running the same job.
+
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::Script;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// Redis key holding the scheduler lease.
+pub const LEADER_KEY: &str = "hyperinfer:scheduler:leader";
+
+/// How long a lease lasts without renewal.
+pub const DEFAULT_LEASE_TTL: Duration = Duration::from_secs(30);
+
+/// Take the lease if it is free, or extend it if `ARGV[1]` already holds it.
+const ACQUIRE_SCRIPT: &str = r#"
+local holder = redis.call('GET', KEYS[1])
+if holder == ARGV[1] then
+  redis.call('PEXPIRE', KEYS[1], ARGV[2])
+  return 1
+end
+if holder then
+  return 0
+end
+redis.call('SET', KEYS[1], ARGV[1], 'PX', ARGV[2])
+return 1
+"#;
+
+/// Drop the lease, but only if `ARGV[1]` still holds it.
+const RELEASE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+  return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+/// Whether this replica currently leads.  Cheap to clone and check.
+#[derive(Clone, Default)]
+pub struct Leadership(Arc<AtomicBool>);
+
+impl Leadership {
+    /// Leadership that is always held, for a single replica without
+    /// election.
+    pub fn always() -> Self {
+        Self(Arc::new(AtomicBool::new(true)))
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Set leadership, returning the previous state.
+    fn set(&self, leader: bool) -> bool {
+        self.0.swap(leader, Ordering::AcqRel)
+    }
+}
+
+/// Storage for an expiring, single-holder lease.
+#[async_trait]
+pub trait LeaseStore: Send + Sync + 'static {
+    /// Take the lease on `key` for `holder`, or extend it if `holder`
+    /// already has it.  Returns whether `holder` holds it afterwards.
+    async fn acquire(
+        &self,
+        key: &str,
+        holder: &str,
+        ttl: Duration,
+    ) -> Result<bool, redis::RedisError>;
+
+    /// Give up the lease on `key` if `holder` has it.
+    async fn release(&self, key: &str, holder: &str) -> Result<(), redis::RedisError>;
+}
+
+/// Leases shared by every replica through Redis.
+pub struct RedisLeaseStore {
+    manager: ConnectionManager,
+    acquire: Script,
+    release: Script,
+}
+
+impl RedisLeaseStore {
+    pub async fn new(redis_url: &str) -> Result<Self, redis::RedisError> {
+        let client = redis::Client::open(redis_url)?;
+        let manager = ConnectionManager::new(client).await?;
+        Ok(Self::with_manager(manager))
+    }
+
+    /// A store on the shared connection of `redis`.
+    pub fn with_redis(redis: &hyperinfer_core::RedisHandle) -> Self {
+        Self::with_manager(redis.connection())
+    }
+
+    fn with_manager(manager: ConnectionManager) -> Self {
+        Self {
+            manager,
+            acquire: Script::new(ACQUIRE_SCRIPT),
+            release: Script::new(RELEASE_SCRIPT),
+        }
+    }
+}
+
+#[async_trait]
+impl LeaseStore for RedisLeaseStore {
+    async fn acquire(
+        &self,
+        key: &str,
+        holder: &str,
+        ttl: Duration,
+    ) -> Result<bool, redis::RedisError> {
+        let mut conn = self.manager.clone();
+        let held: i64 = self
+            .acquire
+            .key(key)
+            .arg(holder)
+            .arg(ttl.as_millis() as u64)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(held == 1)
+    }
+
+    async fn release(&self, key: &str, holder: &str) -> Result<(), redis::RedisError> {
+        let mut conn = self.manager.clone();
+        self.release
+            .key(key)
+            .arg(holder)
+            .invoke_async::<i64>(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Leases held in process memory, shared only by elections using the same
+/// store.
+#[derive(Default)]
+pub struct LocalLeaseStore {
+    leases: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+#[async_trait]
+impl LeaseStore for LocalLeaseStore {
+    async fn acquire(
+        &self,
+        key: &str,
+        holder: &str,
+        ttl: Duration,
+    ) -> Result<bool, redis::RedisError> {
+        let now = Instant::now();
+        let mut leases = self.leases.lock().unwrap_or_else(|e| e.into_inner());
+        match leases.get(key) {
+            Some((current, expires)) if current != holder && *expires > now => Ok(false),
+            _ => {
+                leases.insert(key.to_string(), (holder.to_string(), now + ttl));
+                Ok(true)
+            }
+        }
+    }
+
+    async fn release(&self, key: &str, holder: &str) -> Result<(), redis::RedisError> {
+        let mut leases = self.leases.lock().unwrap_or_else(|e| e.into_inner());
+        if leases
+            .get(key)
+            .is_some_and(|(current, _)| current == holder)
+        {
+            leases.remove(key);
+        }
+        Ok(())
+    }
+}
+
+/// Campaigns for the scheduler lease on behalf of this replica.
+pub struct LeaderElection {
+    store: Arc<dyn LeaseStore>,
+    key: String,
+    holder: String,
+    ttl: Duration,
+    leadership: Leadership,
+}
+
+impl LeaderElection {
+    pub fn new(store: Arc<dyn LeaseStore>) -> Self {
+        Self {
+            store,
+            key: LEADER_KEY.to_string(),
+            holder: uuid::Uuid::new_v4().to_string(),
+            ttl: DEFAULT_LEASE_TTL,
+            leadership: Leadership::default(),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Id this replica holds the lease under.
+    pub fn holder(&self) -> &str {
+        &self.holder
+    }
+
+    /// Handle the jobs check before each run.
+    pub fn leadership(&self) -> Leadership {
+        self.leadership.clone()
+    }
+
+    /// Take or renew the lease once.  Returns whether this replica leads.
+    pub async fn campaign_once(&self) -> bool {
+        let leader = match self.store.acquire(&self.key, &self.holder, self.ttl).await {
+            Ok(held) => held,
+            Err(e) => {
+                tracing::warn!("Scheduler lease check failed, stepping down: {}", e);
+                false
+            }
+        };
+        match (self.leadership.set(leader), leader) {
+            (false, true) => tracing::info!("Replica {} is now the scheduler leader", self.holder),
+            (true, false) => tracing::warn!("Replica {} lost scheduler leadership", self.holder),
+            _ => {}
+        }
+        leader
+    }
+
+    /// Step down and release the lease if this replica holds it.
+    pub async fn resign(&self) {
+        if self.leadership.set(false) {
+            if let Err(e) = self.store.release(&self.key, &self.holder).await {
+                tracing::warn!("Failed to release scheduler lease: {}", e);
+            }
+        }
+    }
+
+    /// Campaign every third of the lease TTL until `cancel` fires, then
+    /// resign.
+    pub fn spawn(self, cancel: CancellationToken) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.ttl / 3);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = ticker.tick() => {
+                        self.campaign_once().await;
+                    }
+                }
+            }
+            self.resign().await;
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn election(store: &Arc<LocalLeaseStore>, ttl: Duration) -> LeaderElection {
+        LeaderElection::new(store.clone()).with_ttl(ttl)
+    }
+
+    #[tokio::test]
+    async fn test_only_one_replica_leads() {
+        let store = Arc::new(LocalLeaseStore::default());
+        let a = election(&store, Duration::from_secs(30));
+        let b = election(&store, Duration::from_secs(30));
+
+        assert!(a.campaign_once().await);
+        assert!(!b.campaign_once().await);
+        assert!(a.campaign_once().await);
+        assert!(a.leadership().is_leader());
+        assert!(!b.leadership().is_leader());
+    }
+
+    #[tokio::test]
+    async fn test_expired_lease_is_taken_over() {
+        let store = Arc::new(LocalLeaseStore::default());
+        let a = election(&store, Duration::from_millis(20));
+        let b = election(&store, Duration::from_millis(20));
+
+        assert!(a.campaign_once().await);
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(b.campaign_once().await);
+        assert!(!a.campaign_once().await);
+        assert!(!a.leadership().is_leader());
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_leader_releases_lease() {
+        let store = Arc::new(LocalLeaseStore::default());
+        let a = election(&store, Duration::from_secs(30));
+        let leadership = a.leadership();
+        let cancel = CancellationToken::new();
+        let handle = a.spawn(cancel.clone());
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while !leadership.is_leader() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+
+        cancel.cancel();
+        handle.await.unwrap();
+        assert!(!leadership.is_leader());
+        assert!(
+            election(&store, Duration::from_secs(30))
+                .campaign_once()
+                .await
+        );
+    }
+}
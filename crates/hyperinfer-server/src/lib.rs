@@ -1,4 +1,31 @@
+pub mod admin_limits;
+pub mod alerts;
+pub mod anomaly;
+pub mod billing;
+pub mod budget;
+pub mod bundle;
+#[cfg(feature = "clickhouse")]
+pub mod clickhouse;
+pub mod dashboard;
 pub mod db;
+pub mod evals;
+pub mod events;
+pub mod experiments;
+pub mod export;
+pub mod key_usage;
+pub mod leader;
+pub mod litellm;
+pub mod logging;
+pub mod mailer;
 pub mod mcp;
+pub mod oidc;
+pub mod payload;
+pub mod purge;
+pub mod reports;
+pub mod settings;
+pub mod summary;
+pub mod tls;
+pub mod usage;
+pub mod webhooks;
 
 pub use db::{RedisConfigStore, SqlxDb};
@@ -0,0 +1,534 @@
+//! Conversion of LiteLLM proxy configs.
+//!
+//! [`convert`] reads a LiteLLM `config.yaml` and maps what has a HyperInfer
+//! counterpart:
+//!
+//! | LiteLLM                                   | HyperInfer                              |
+//! |-------------------------------------------|-----------------------------------------|
+//! | `model_list[].model_name` → `litellm_params.model` | `Config::model_aliases`        |
+//! | further deployments of a `model_name`     | a routing rule falling back to them     |
+//! | `litellm_params.api_base`                 | `Config::provider_base_urls`            |
+//! | `litellm_params.max_tokens`               | `Config::max_output_tokens`             |
+//! | `litellm_params.*_cost_per_token`         | model prices                            |
+//! | `router_settings.fallbacks`, `litellm_settings.fallbacks` | routing rules           |
+//! | `router_settings.model_group_alias`       | `Config::model_aliases`                 |
+//! | `litellm_settings.default_team_settings`  | teams with budgets, and their quotas    |
+//!
+//! Everything else, including API keys and providers HyperInfer cannot
+//! call, is left out with a warning rather than failing the conversion, so
+//! an operator sees the whole gap at once.
+
+use hyperinfer_core::{Bundle, BundleQuota, BundleTeam, Config, NewModelPrice, RoutingRule};
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+
+/// Providers whose `provider/model` form HyperInfer routes.
+const PROVIDERS: &[&str] = &["openai", "anthropic"];
+
+/// Budget periods that match HyperInfer's monthly team budgets.
+const MONTHLY_DURATIONS: &[&str] = &["30d", "1mo"];
+
+/// What a LiteLLM config becomes.
+#[derive(Debug, Default)]
+pub struct Conversion {
+    /// Aliases, routing rules, base URLs and output budgets; other fields
+    /// are left at their defaults.
+    pub config: Config,
+    /// Teams and quotas for the control plane's database.
+    pub bundle: Bundle,
+    pub prices: Vec<NewModelPrice>,
+    /// Settings that were not carried over, one per setting.
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct LiteLlmConfig {
+    #[serde(default)]
+    model_list: Vec<ModelEntry>,
+    #[serde(default)]
+    router_settings: RouterSettings,
+    #[serde(default)]
+    litellm_settings: LiteLlmSettings,
+    #[serde(default)]
+    general_settings: BTreeMap<String, Value>,
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelEntry {
+    model_name: String,
+    litellm_params: ModelParams,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelParams {
+    model: String,
+    #[serde(default)]
+    api_base: Option<String>,
+    #[serde(default)]
+    api_key: Option<String>,
+    #[serde(default)]
+    rpm: Option<u64>,
+    #[serde(default)]
+    tpm: Option<u64>,
+    #[serde(default)]
+    max_tokens: Option<u32>,
+    #[serde(default)]
+    input_cost_per_token: Option<f64>,
+    #[serde(default)]
+    output_cost_per_token: Option<f64>,
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RouterSettings {
+    #[serde(default)]
+    routing_strategy: Option<String>,
+    #[serde(default)]
+    fallbacks: Vec<BTreeMap<String, Vec<String>>>,
+    #[serde(default)]
+    model_group_alias: BTreeMap<String, Value>,
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct LiteLlmSettings {
+    #[serde(default)]
+    fallbacks: Vec<BTreeMap<String, Vec<String>>>,
+    #[serde(default)]
+    max_budget: Option<f64>,
+    #[serde(default)]
+    default_team_settings: Vec<TeamSettings>,
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TeamSettings {
+    team_id: String,
+    #[serde(default)]
+    team_alias: Option<String>,
+    #[serde(default)]
+    max_budget: Option<f64>,
+    #[serde(default)]
+    budget_duration: Option<String>,
+    #[serde(default)]
+    rpm_limit: Option<i32>,
+    #[serde(default)]
+    tpm_limit: Option<i32>,
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
+}
+
+/// Provider and model of a LiteLLM model string.  LiteLLM treats
+/// unprefixed models as OpenAI's unless they are Claude models.
+fn split_model(model: &str) -> (String, String) {
+    match model.split_once('/') {
+        Some((provider, name)) => (provider.to_ascii_lowercase(), name.to_string()),
+        None if model.starts_with("claude") => ("anthropic".to_string(), model.to_string()),
+        None => ("openai".to_string(), model.to_string()),
+    }
+}
+
+/// `api_base` as a HyperInfer base URL, which request paths such as
+/// `/v1/chat/completions` are appended to.
+fn base_url(api_base: &str) -> String {
+    let trimmed = api_base.trim_end_matches('/');
+    trimmed.strip_suffix("/v1").unwrap_or(trimmed).to_string()
+}
+
+fn usd_to_cents(usd: f64) -> Option<i64> {
+    (usd.is_finite() && usd >= 0.0).then(|| (usd * 100.0).round() as i64)
+}
+
+fn ignored(warnings: &mut Vec<String>, section: &str, keys: &BTreeMap<String, Value>) {
+    for key in keys.keys() {
+        warnings.push(format!(
+            "{}{} is not supported and was ignored",
+            section, key
+        ));
+    }
+}
+
+/// Convert the LiteLLM config in `yaml`.  Fails only when it is not a
+/// LiteLLM config at all; unsupported settings become warnings.
+pub fn convert(yaml: &[u8]) -> Result<Conversion, String> {
+    let parsed: LiteLlmConfig =
+        serde_yaml::from_slice(yaml).map_err(|e| format!("Invalid LiteLLM config: {}", e))?;
+    let mut out = Conversion::default();
+    let warnings = &mut out.warnings;
+
+    // model_name -> targets of its deployments, in config order.
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for entry in &parsed.model_list {
+        let params = &entry.litellm_params;
+        let (provider, model) = split_model(&params.model);
+        if !PROVIDERS.contains(&provider.as_str()) {
+            warnings.push(format!(
+                "model_list: '{}' uses provider '{}', which HyperInfer cannot call, and was skipped",
+                entry.model_name, provider
+            ));
+            continue;
+        }
+        let target = format!("{}/{}", provider, model);
+        let deployments = groups.entry(entry.model_name.clone()).or_default();
+        if !deployments.contains(&target) {
+            deployments.push(target);
+        }
+
+        if let Some(api_base) = &params.api_base {
+            if api_base.starts_with("os.environ/") {
+                warnings.push(format!(
+                    "model_list: '{}' reads api_base from the environment; set provider_base_urls.{} instead",
+                    entry.model_name, provider
+                ));
+            } else {
+                let url = base_url(api_base);
+                match out.config.provider_base_urls.get(&provider) {
+                    Some(existing) if *existing != url => warnings.push(format!(
+                        "model_list: '{}' uses api_base {}, but {} already uses {}; only one base URL per provider is supported",
+                        entry.model_name, url, provider, existing
+                    )),
+                    Some(_) => {}
+                    None => {
+                        out.config.provider_base_urls.insert(provider.clone(), url);
+                    }
+                }
+            }
+        }
+        if params
+            .api_key
+            .as_deref()
+            .is_some_and(|key| !key.starts_with("os.environ/"))
+        {
+            warnings.push(format!(
+                "model_list: '{}' has an inline api_key, which is not imported; give the data plane the {} key instead",
+                entry.model_name, provider
+            ));
+        }
+        if params.rpm.is_some() || params.tpm.is_some() {
+            warnings.push(format!(
+                "model_list: '{}' has per-deployment rpm/tpm limits; HyperInfer limits teams, so they were ignored",
+                entry.model_name
+            ));
+        }
+        if let Some(max_tokens) = params.max_tokens {
+            out.config
+                .max_output_tokens
+                .insert(model.clone(), max_tokens);
+        }
+        match (params.input_cost_per_token, params.output_cost_per_token) {
+            (Some(input), Some(output)) => {
+                let price = NewModelPrice {
+                    model: model.clone(),
+                    provider: provider.clone(),
+                    input_per_mtok: input * 1_000_000.0,
+                    output_per_mtok: output * 1_000_000.0,
+                    effective_from: None,
+                };
+                if !out
+                    .prices
+                    .iter()
+                    .any(|p| p.model == price.model && p.provider == price.provider)
+                {
+                    out.prices.push(price);
+                }
+            }
+            (None, None) => {}
+            _ => warnings.push(format!(
+                "model_list: '{}' needs both input_cost_per_token and output_cost_per_token to set a price",
+                entry.model_name
+            )),
+        }
+        ignored(
+            warnings,
+            &format!("model_list: '{}' litellm_params.", entry.model_name),
+            &params.other,
+        );
+    }
+
+    // LiteLLM balances load across deployments of one name; HyperInfer
+    // sends to the first and fails over to the rest.
+    let mut fallbacks: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (name, deployments) in &groups {
+        out.config
+            .model_aliases
+            .insert(name.clone(), deployments[0].clone());
+        if deployments.len() > 1 {
+            fallbacks.insert(name.clone(), deployments[1..].to_vec());
+        }
+    }
+    if groups.values().any(|d| d.len() > 1) {
+        warnings.push(
+            "model_list: names with several deployments use the first and fail over to the rest instead of load balancing"
+                .to_string(),
+        );
+    }
+    for rule in parsed
+        .router_settings
+        .fallbacks
+        .iter()
+        .chain(&parsed.litellm_settings.fallbacks)
+    {
+        for (name, models) in rule {
+            let entry = fallbacks.entry(name.clone()).or_default();
+            for model in models {
+                if !entry.contains(model) {
+                    entry.push(model.clone());
+                }
+            }
+        }
+    }
+    out.config.routing_rules = fallbacks
+        .into_iter()
+        .map(|(name, fallback_models)| RoutingRule {
+            name,
+            priority: 0,
+            fallback_models,
+        })
+        .collect();
+
+    let router = &parsed.router_settings;
+    if let Some(strategy) = &router.routing_strategy {
+        if strategy != "simple-shuffle" {
+            warnings.push(format!(
+                "router_settings.routing_strategy '{}' is not supported; deployments are tried in order",
+                strategy
+            ));
+        }
+    }
+    for (alias, group) in &router.model_group_alias {
+        let group = match group {
+            Value::String(group) => Some(group.as_str()),
+            Value::Mapping(map) => map.get("model").and_then(Value::as_str),
+            _ => None,
+        };
+        match group.and_then(|group| out.config.model_aliases.get(group).cloned()) {
+            Some(target) => {
+                out.config.model_aliases.insert(alias.clone(), target);
+            }
+            None => warnings.push(format!(
+                "router_settings.model_group_alias.{} does not name a converted model and was ignored",
+                alias
+            )),
+        }
+    }
+    ignored(warnings, "router_settings.", &router.other);
+
+    let settings = &parsed.litellm_settings;
+    if settings.max_budget.is_some() {
+        warnings.push(
+            "litellm_settings.max_budget is proxy-wide; HyperInfer budgets are per team or organization, so it was ignored"
+                .to_string(),
+        );
+    }
+    for team in &settings.default_team_settings {
+        let name = team
+            .team_alias
+            .clone()
+            .unwrap_or_else(|| team.team_id.clone());
+        let budget_cents = match team.max_budget.map(usd_to_cents) {
+            Some(Some(cents)) => cents,
+            Some(None) => {
+                warnings.push(format!("team '{}': max_budget is not a valid amount", name));
+                0
+            }
+            None => 0,
+        };
+        if let Some(duration) = &team.budget_duration {
+            if !MONTHLY_DURATIONS.contains(&duration.as_str()) {
+                warnings.push(format!(
+                    "team '{}': budget_duration '{}' is not supported; the budget resets monthly",
+                    name, duration
+                ));
+            }
+        }
+        match (team.rpm_limit, team.tpm_limit) {
+            (Some(rpm_limit), Some(tpm_limit)) => out.bundle.quotas.push(BundleQuota {
+                team: name.clone(),
+                rpm_limit,
+                tpm_limit,
+            }),
+            (None, None) => {}
+            _ => warnings.push(format!(
+                "team '{}': a quota needs both rpm_limit and tpm_limit and was skipped",
+                name
+            )),
+        }
+        ignored(warnings, &format!("team '{}': ", name), &team.other);
+        out.bundle.teams.push(BundleTeam {
+            name,
+            budget_cents,
+            billing_anchor_day: 1,
+            billing_timezone: "UTC".to_string(),
+        });
+    }
+    ignored(warnings, "litellm_settings.", &settings.other);
+    ignored(warnings, "general_settings.", &parsed.general_settings);
+    ignored(warnings, "", &parsed.other);
+
+    Ok(out)
+}
+
+/// Merge the settings of `converted` into `config`, replacing routing
+/// rules with the same name and keeping everything not converted.
+pub fn merge(config: &mut Config, converted: &Config) {
+    config.model_aliases.extend(converted.model_aliases.clone());
+    config
+        .provider_base_urls
+        .extend(converted.provider_base_urls.clone());
+    config
+        .max_output_tokens
+        .extend(converted.max_output_tokens.clone());
+    config
+        .routing_rules
+        .retain(|rule| !converted.routing_rules.iter().any(|r| r.name == rule.name));
+    config
+        .routing_rules
+        .extend(converted.routing_rules.iter().cloned());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+model_list:
+  - model_name: gpt-4o
+    litellm_params:
+      model: openai/gpt-4o
+      api_base: https://gateway.internal/openai/v1/
+      api_key: os.environ/OPENAI_API_KEY
+      max_tokens: 4096
+      input_cost_per_token: 0.0000025
+      output_cost_per_token: 0.00001
+  - model_name: gpt-4o
+    litellm_params:
+      model: gpt-4o-2024-08-06
+  - model_name: claude
+    litellm_params:
+      model: anthropic/claude-3-5-sonnet-20241022
+      api_key: sk-ant-inline
+      rpm: 60
+  - model_name: azure-gpt
+    litellm_params:
+      model: azure/gpt-4o
+router_settings:
+  routing_strategy: latency-based-routing
+  num_retries: 2
+  fallbacks:
+    - gpt-4o: [claude]
+  model_group_alias:
+    gpt-4: gpt-4o
+litellm_settings:
+  max_budget: 1000
+  default_team_settings:
+    - team_id: team-1
+      team_alias: research
+      max_budget: 250.5
+      budget_duration: 30d
+      rpm_limit: 100
+      tpm_limit: 50000
+general_settings:
+  master_key: sk-1234
+"#;
+
+    #[test]
+    fn test_convert_maps_models_routing_and_teams() {
+        let out = convert(SAMPLE.as_bytes()).unwrap();
+        let config = &out.config;
+
+        assert_eq!(config.model_aliases["gpt-4o"], "openai/gpt-4o");
+        assert_eq!(config.model_aliases["gpt-4"], "openai/gpt-4o");
+        assert_eq!(
+            config.model_aliases["claude"],
+            "anthropic/claude-3-5-sonnet-20241022"
+        );
+        assert!(!config.model_aliases.contains_key("azure-gpt"));
+        assert_eq!(
+            config.provider_base_urls["openai"],
+            "https://gateway.internal/openai"
+        );
+        assert_eq!(config.max_output_tokens["gpt-4o"], 4096);
+
+        let rule = config
+            .routing_rules
+            .iter()
+            .find(|r| r.name == "gpt-4o")
+            .unwrap();
+        assert_eq!(
+            rule.fallback_models,
+            vec!["openai/gpt-4o-2024-08-06".to_string(), "claude".to_string()]
+        );
+
+        assert_eq!(out.prices.len(), 1);
+        assert!((out.prices[0].input_per_mtok - 2.5).abs() < 1e-9);
+        assert!((out.prices[0].output_per_mtok - 10.0).abs() < 1e-9);
+
+        assert_eq!(out.bundle.teams[0].name, "research");
+        assert_eq!(out.bundle.teams[0].budget_cents, 25050);
+        assert_eq!(out.bundle.quotas[0].rpm_limit, 100);
+        assert_eq!(out.bundle.quotas[0].tpm_limit, 50000);
+    }
+
+    #[test]
+    fn test_convert_warns_about_what_it_drops() {
+        let out = convert(SAMPLE.as_bytes()).unwrap();
+        let warned = |needle: &str| out.warnings.iter().any(|w| w.contains(needle));
+
+        assert!(warned("'azure-gpt' uses provider 'azure'"));
+        assert!(warned("'claude' has an inline api_key"));
+        assert!(warned("'claude' has per-deployment rpm/tpm"));
+        assert!(warned("routing_strategy 'latency-based-routing'"));
+        assert!(warned("router_settings.num_retries"));
+        assert!(warned("litellm_settings.max_budget"));
+        assert!(warned("general_settings.master_key"));
+        assert!(!warned("OPENAI_API_KEY"));
+        assert!(!warned("budget_duration"));
+    }
+
+    #[test]
+    fn test_convert_rejects_non_config() {
+        assert!(convert(b"model_list: 3").is_err());
+        assert!(convert(b"- just\n- a list\n").is_err());
+    }
+
+    #[test]
+    fn test_merge_replaces_rules_by_name() {
+        let mut config = Config {
+            routing_rules: vec![
+                RoutingRule {
+                    name: "gpt-4o".to_string(),
+                    priority: 5,
+                    fallback_models: vec!["old".to_string()],
+                },
+                RoutingRule {
+                    name: "other".to_string(),
+                    priority: 1,
+                    fallback_models: vec![],
+                },
+            ],
+            ..Default::default()
+        };
+        config
+            .model_aliases
+            .insert("keep".to_string(), "openai/gpt-4o-mini".to_string());
+
+        let converted = convert(SAMPLE.as_bytes()).unwrap().config;
+        merge(&mut config, &converted);
+
+        assert_eq!(config.model_aliases["keep"], "openai/gpt-4o-mini");
+        assert_eq!(config.model_aliases["gpt-4o"], "openai/gpt-4o");
+        assert_eq!(config.routing_rules.len(), 2);
+        let rule = config
+            .routing_rules
+            .iter()
+            .find(|r| r.name == "gpt-4o")
+            .unwrap();
+        assert_ne!(rule.fallback_models, vec!["old".to_string()]);
+    }
+}
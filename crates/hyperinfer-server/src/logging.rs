@@ -0,0 +1,243 @@
+//! Request logging.
+//!
+//! Every control-plane request runs in an `http_request` span carrying its
+//! request ID (the caller's `x-request-id`, or a generated UUID echoed back
+//! in the response), method, path and — when the path or query names one —
+//! the team ID.  Completed requests log their status and latency inside that
+//! span; `LOG_FORMAT=json` switches the output to one JSON object per line.
+//!
+//! Routes polled by every data-plane client (config sync, pricing, virtual
+//! key resolution) would otherwise dominate the logs, so successful requests
+//! to them are only logged for a `LOG_SAMPLE_RATE` fraction.  Failures are
+//! always logged.
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{Response, Uri},
+    middleware::{self, Next},
+    Router,
+};
+use std::time::Duration;
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{MakeSpan, OnResponse, TraceLayer},
+};
+use tracing::Span;
+
+/// Default fraction of successful high-volume requests that are logged.
+pub const DEFAULT_SAMPLE_RATE: f64 = 0.1;
+
+/// Path prefixes of the routes data-plane clients poll.
+const HIGH_VOLUME_PREFIXES: [&str; 3] = ["/v1/config/sync", "/v1/pricing", "/v1/virtual_keys/"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        match format {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "Unknown log format '{}': expected text or json",
+                format
+            )),
+        }
+    }
+}
+
+/// Install the global `tracing` subscriber.  Filtering follows `RUST_LOG`
+/// and defaults to `info`.
+pub fn init_logging(format: LogFormat) {
+    use tracing_subscriber::EnvFilter;
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().flatten_event(true).init(),
+    }
+}
+
+/// Whether `path` belongs to a route that is subject to log sampling.
+pub fn is_high_volume(path: &str) -> bool {
+    HIGH_VOLUME_PREFIXES
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+}
+
+/// Team ID named by the request, from team-scoped paths
+/// (`/v1/teams/:id/...`, `/v1/quotas/:team_id`, `/v1/budget_policies/:team_id`)
+/// or a `team_id` query parameter.
+pub fn team_id_from_uri(uri: &Uri) -> Option<String> {
+    let mut segments = uri.path().trim_start_matches('/').split('/');
+    if let (Some("v1"), Some("teams" | "quotas" | "budget_policies"), Some(id)) =
+        (segments.next(), segments.next(), segments.next())
+    {
+        if !id.is_empty() {
+            return Some(id.to_string());
+        }
+    }
+    uri.query()?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, value)| *name == "team_id" && !value.is_empty())
+        .map(|(_, value)| value.to_string())
+}
+
+/// Marks responses whose successful completion should not be logged.
+#[derive(Debug, Clone, Copy)]
+struct SampledOut;
+
+#[derive(Debug, Clone, Copy)]
+pub struct LogSampling {
+    /// Fraction in `[0, 1]` of successful high-volume requests to log.
+    pub rate: f64,
+}
+
+impl LogSampling {
+    fn keep(&self, path: &str) -> bool {
+        !is_high_volume(path) || fastrand::f64() < self.rate
+    }
+}
+
+async fn log_sampling_middleware(
+    State(sampling): State<LogSampling>,
+    req: Request,
+    next: Next,
+) -> Response<Body> {
+    let keep = sampling.keep(req.uri().path());
+    let mut response = next.run(req).await;
+    if !keep {
+        response.extensions_mut().insert(SampledOut);
+    }
+    response
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RequestSpan;
+
+impl<B> MakeSpan<B> for RequestSpan {
+    fn make_span(&mut self, request: &axum::http::Request<B>) -> Span {
+        let request_id = request
+            .headers()
+            .get("x-request-id")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let span = tracing::info_span!(
+            "http_request",
+            request_id = %request_id,
+            method = %request.method(),
+            path = %request.uri().path(),
+            team_id = tracing::field::Empty,
+        );
+        if let Some(team_id) = team_id_from_uri(request.uri()) {
+            span.record("team_id", team_id.as_str());
+        }
+        span
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RequestLog;
+
+impl<B> OnResponse<B> for RequestLog {
+    fn on_response(self, response: &Response<B>, latency: Duration, _span: &Span) {
+        let status = response.status();
+        if status.is_success() && response.extensions().get::<SampledOut>().is_some() {
+            return;
+        }
+        let latency_ms = latency.as_millis() as u64;
+        if status.is_server_error() {
+            tracing::error!(status = status.as_u16(), latency_ms, "request failed");
+        } else if status.is_client_error() {
+            tracing::warn!(status = status.as_u16(), latency_ms, "request rejected");
+        } else {
+            tracing::info!(status = status.as_u16(), latency_ms, "request completed");
+        }
+    }
+}
+
+/// Wrap `router` with request IDs, the per-request span and sampled
+/// response logging.
+pub fn with_request_logging<S>(router: Router<S>, sampling: LogSampling) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router
+        .layer(middleware::from_fn_with_state(
+            sampling,
+            log_sampling_middleware,
+        ))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(RequestSpan)
+                .on_response(RequestLog),
+        )
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, routing::get};
+    use axum_test::TestServer;
+
+    #[test]
+    fn test_log_format_parse() {
+        assert_eq!(LogFormat::parse("json"), Ok(LogFormat::Json));
+        assert_eq!(LogFormat::parse("text"), Ok(LogFormat::Text));
+        assert!(LogFormat::parse("yaml").is_err());
+    }
+
+    #[test]
+    fn test_team_id_from_uri() {
+        let team = |uri: &str| team_id_from_uri(&uri.parse().unwrap());
+        assert_eq!(team("/v1/teams/t1/usage").as_deref(), Some("t1"));
+        assert_eq!(team("/v1/quotas/t2").as_deref(), Some("t2"));
+        assert_eq!(team("/v1/budget_policies/t3").as_deref(), Some("t3"));
+        assert_eq!(
+            team("/v1/usage/export?start=x&team_id=t4").as_deref(),
+            Some("t4")
+        );
+        assert_eq!(team("/v1/teams"), None);
+        assert_eq!(team("/v1/api_keys/k1"), None);
+    }
+
+    #[test]
+    fn test_sampling_only_applies_to_high_volume_routes() {
+        let never = LogSampling { rate: 0.0 };
+        assert!(!never.keep("/v1/config/sync"));
+        assert!(!never.keep("/v1/virtual_keys/abc"));
+        assert!(never.keep("/v1/teams/t1"));
+
+        let always = LogSampling { rate: 1.0 };
+        assert!(always.keep("/v1/pricing"));
+    }
+
+    #[tokio::test]
+    async fn test_request_id_generated_and_propagated() {
+        let app = with_request_logging(
+            Router::new().route("/v1/pricing", get(|| async { StatusCode::OK })),
+            LogSampling { rate: 0.0 },
+        );
+        let server = TestServer::new(app);
+
+        let resp = server.get("/v1/pricing").await;
+        assert_eq!(resp.status_code(), StatusCode::OK);
+        let generated = resp.header("x-request-id");
+        assert_eq!(generated.len(), 36);
+
+        let resp = server
+            .get("/v1/pricing")
+            .add_header("x-request-id", "req-123")
+            .await;
+        assert_eq!(resp.header("x-request-id"), "req-123");
+    }
+}
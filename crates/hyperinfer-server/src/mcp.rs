@@ -35,6 +35,7 @@ use serde_json::Value;
 use std::{collections::HashMap, convert::Infallible, sync::Arc, time::Duration};
 use tokio::sync::{mpsc, RwLock};
 use tracing::warn;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
 // ── JWT ─────────────────────────────────────────────────────────────────────
@@ -209,7 +210,7 @@ impl McpState {
 
 // ── JSON-RPC types ────────────────────────────────────────────────────────────
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
 pub struct JsonRpcRequest {
     pub jsonrpc: String,
     pub id: Option<Option<Value>>,
@@ -287,6 +288,17 @@ pub fn dispatch_method(req: &JsonRpcRequest) -> JsonRpcResponse {
 /// Opens a long-lived SSE connection for the calling agent.  The first event
 /// sent to the client is `endpoint` containing the POST URL the client should
 /// use for all subsequent JSON-RPC messages.
+#[utoipa::path(
+    get,
+    path = "/mcp/sse",
+    tag = "mcp",
+    responses(
+        (status = 200, description = "Event stream of JSON-RPC replies", content_type = "text/event-stream"),
+        (status = 429, description = "Too many open sessions for the caller"),
+        (status = 503, description = "Server-wide session limit reached"),
+    ),
+    security(("mcp" = [])),
+)]
 pub async fn mcp_sse_handler(
     State(state): State<McpState>,
     Extension(claims): Extension<McpClaims>,
@@ -406,7 +418,8 @@ pub async fn mcp_sse_handler(
 }
 
 /// Query parameters for `POST /mcp/message`.
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct MessageQuery {
     pub session_id: String,
 }
@@ -418,6 +431,20 @@ pub struct MessageQuery {
 ///
 /// Returns 202 Accepted on success, 404 if the session does not exist,
 /// 403 if the caller does not own the session.
+#[utoipa::path(
+    post,
+    path = "/mcp/message",
+    tag = "mcp",
+    params(MessageQuery),
+    request_body = JsonRpcRequest,
+    responses(
+        (status = 202, description = "Accepted; the reply arrives on the SSE stream"),
+        (status = 400, description = "Not a JSON-RPC 2.0 message"),
+        (status = 403, description = "Not the session owner"),
+        (status = 404, description = "Session not found"),
+    ),
+    security(("mcp" = [])),
+)]
 pub async fn mcp_message_handler(
     State(state): State<McpState>,
     Extension(claims): Extension<McpClaims>,
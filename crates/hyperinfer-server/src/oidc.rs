@@ -0,0 +1,688 @@
+//! Single sign-on through an OpenID Connect provider.
+//!
+//! With `OIDC_ISSUER_URL` set, people log in to the control plane through
+//! the organization's identity provider instead of sharing the admin token.
+//! `/v1/auth/login` redirects to the provider (authorization code flow), and
+//! `/v1/auth/callback` exchanges the returned code for an ID token, verifies
+//! it, and answers with a control-plane session token: a short-lived HS256
+//! JWT naming the user, sent back as `Authorization: Bearer <token>`.
+//!
+//! ID tokens must be signed with one of the provider's advertised
+//! `id_token_signing_alg_values_supported` algorithms (RS256 when it
+//! advertises none), or with one listed in `OIDC_ID_TOKEN_ALGORITHMS`.
+//! Symmetric (HS*) tokens, keyed by the client secret, are only accepted
+//! when listed there.
+//!
+//! A verified email that belongs to an existing user logs in as that user.
+//! Otherwise the email's domain is looked up in `OIDC_DOMAIN_TEAMS`
+//! (`example.com=<team id>,...`) and a user is created in that team with
+//! `OIDC_DEFAULT_ROLE`; emails from unmapped domains are refused.
+//!
+//! The login `state` parameter is itself a signed, expiring token carrying
+//! the nonce the ID token must echo, so the callback needs no server-side
+//! storage and works on any replica.
+
+use chrono::{DateTime, Utc};
+use hyperinfer_core::{Database, DbError, Role, User};
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use utoipa::ToSchema;
+
+/// Path of the provider's discovery document under its issuer URL.
+const DISCOVERY_PATH: &str = "/.well-known/openid-configuration";
+
+/// Audience of control-plane session tokens.
+const SESSION_AUDIENCE: &str = "hyperinfer-session";
+
+/// Audience of login `state` tokens, so one can never pass for the other.
+const STATE_AUDIENCE: &str = "hyperinfer-oidc-state";
+
+/// How long a user has to finish logging in at the provider.
+const LOGIN_STATE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Default lifetime of a session token.
+pub const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(8 * 60 * 60);
+
+#[derive(Debug, thiserror::Error)]
+pub enum OidcError {
+    #[error("OIDC configuration error: {0}")]
+    Config(String),
+    #[error("OIDC provider request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Invalid token: {0}")]
+    Token(#[from] jsonwebtoken::errors::Error),
+    #[error("Login rejected: {0}")]
+    Rejected(String),
+    #[error("Database error: {0}")]
+    Db(#[from] DbError),
+}
+
+#[derive(Debug, Clone)]
+pub struct OidcSettings {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_url: String,
+    pub session_secret: String,
+    pub session_ttl: Duration,
+    /// Email domain to the team its new users join.
+    pub domain_teams: HashMap<String, String>,
+    /// Role given to users created on first login.
+    pub default_role: Role,
+    /// ID token signing algorithms to accept instead of the provider's
+    /// advertised ones.  The only way to accept HS* tokens.
+    pub id_token_algorithms: Vec<Algorithm>,
+}
+
+/// Parse `RS256,ES256`.
+pub fn parse_algorithms(spec: &str) -> Result<Vec<Algorithm>, OidcError> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|alg| !alg.is_empty())
+        .map(|alg| {
+            alg.parse().map_err(|_| {
+                OidcError::Config(format!("Invalid OIDC_ID_TOKEN_ALGORITHMS entry '{}'", alg))
+            })
+        })
+        .collect()
+}
+
+fn is_symmetric(alg: Algorithm) -> bool {
+    matches!(alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512)
+}
+
+/// Parse `domain=team,domain=team`.
+pub fn parse_domain_teams(spec: &str) -> Result<HashMap<String, String>, OidcError> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once('=') {
+            Some((domain, team)) if !domain.trim().is_empty() && !team.trim().is_empty() => {
+                Ok((domain.trim().to_ascii_lowercase(), team.trim().to_string()))
+            }
+            _ => Err(OidcError::Config(format!(
+                "Invalid OIDC_DOMAIN_TEAMS entry '{}': expected domain=team_id",
+                entry
+            ))),
+        })
+        .collect()
+}
+
+impl OidcSettings {
+    /// Settings from the variables `var` looks up by name, or `None` when
+    /// `OIDC_ISSUER_URL` is unset and single sign-on is off.
+    pub fn from_vars(var: &dyn Fn(&str) -> Option<String>) -> Result<Option<Self>, OidcError> {
+        let env = |name: &str| var(name).filter(|v| !v.is_empty());
+        let required = |name: &str| {
+            env(name).ok_or_else(|| {
+                OidcError::Config(format!("{} must be set when OIDC is enabled", name))
+            })
+        };
+        let Some(issuer_url) = env("OIDC_ISSUER_URL") else {
+            return Ok(None);
+        };
+        let session_ttl =
+            match env("SESSION_TTL_SECS") {
+                Some(secs) => Duration::from_secs(secs.trim().parse().map_err(|_| {
+                    OidcError::Config(format!("Invalid SESSION_TTL_SECS '{}'", secs))
+                })?),
+                None => DEFAULT_SESSION_TTL,
+            };
+        let default_role = match env("OIDC_DEFAULT_ROLE") {
+            Some(role) => role.parse().map_err(OidcError::Config)?,
+            None => Role::Viewer,
+        };
+        Ok(Some(Self {
+            issuer_url,
+            client_id: required("OIDC_CLIENT_ID")?,
+            client_secret: required("OIDC_CLIENT_SECRET")?,
+            redirect_url: required("OIDC_REDIRECT_URL")?,
+            session_secret: required("SESSION_SECRET")?,
+            session_ttl,
+            domain_teams: parse_domain_teams(&env("OIDC_DOMAIN_TEAMS").unwrap_or_default())?,
+            default_role,
+            id_token_algorithms: parse_algorithms(
+                &env("OIDC_ID_TOKEN_ALGORITHMS").unwrap_or_default(),
+            )?,
+        }))
+    }
+
+    /// The team new users with `email` join, by its domain.
+    pub fn team_for_email(&self, email: &str) -> Option<&str> {
+        let (_, domain) = email.rsplit_once('@')?;
+        self.domain_teams
+            .get(&domain.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+}
+
+/// The parts of the provider's discovery document used here.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+    #[serde(default)]
+    pub id_token_signing_alg_values_supported: Vec<String>,
+}
+
+/// Claims read from a verified ID token.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub nonce: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Claims of a control-plane session token.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionClaims {
+    /// The user's id.
+    pub sub: String,
+    pub email: String,
+    pub aud: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LoginState {
+    nonce: String,
+    aud: String,
+    exp: i64,
+}
+
+/// A session token and when it expires.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Session {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub user: User,
+}
+
+/// Signs and verifies session and login-state tokens.
+#[derive(Clone)]
+pub struct SessionKeys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    ttl: Duration,
+}
+
+impl SessionKeys {
+    pub fn new(secret: &str, ttl: Duration) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            ttl,
+        }
+    }
+
+    fn validation(audience: &str) -> Validation {
+        let mut validation = Validation::default();
+        validation.set_audience(&[audience]);
+        validation
+    }
+
+    /// Issue a session token for `user`.
+    pub fn issue(&self, user: User, now: DateTime<Utc>) -> Result<Session, OidcError> {
+        let expires_at = now + chrono::Duration::from_std(self.ttl).unwrap_or_default();
+        let claims = SessionClaims {
+            sub: user.id.clone(),
+            email: user.email.clone(),
+            aud: SESSION_AUDIENCE.to_string(),
+            iat: now.timestamp(),
+            exp: expires_at.timestamp(),
+        };
+        let token = encode(&Header::default(), &claims, &self.encoding)?;
+        Ok(Session {
+            token,
+            expires_at,
+            user,
+        })
+    }
+
+    pub fn verify(&self, token: &str) -> Result<SessionClaims, OidcError> {
+        Ok(
+            decode::<SessionClaims>(token, &self.decoding, &Self::validation(SESSION_AUDIENCE))?
+                .claims,
+        )
+    }
+
+    /// A `state` parameter for a new login, carrying `nonce`.
+    pub fn login_state(&self, nonce: &str, now: DateTime<Utc>) -> Result<String, OidcError> {
+        let state = LoginState {
+            nonce: nonce.to_string(),
+            aud: STATE_AUDIENCE.to_string(),
+            exp: (now + chrono::Duration::from_std(LOGIN_STATE_TTL).unwrap_or_default())
+                .timestamp(),
+        };
+        Ok(encode(&Header::default(), &state, &self.encoding)?)
+    }
+
+    /// The nonce a `state` parameter was issued with.
+    pub fn verify_login_state(&self, state: &str) -> Result<String, OidcError> {
+        Ok(
+            decode::<LoginState>(state, &self.decoding, &Self::validation(STATE_AUDIENCE))?
+                .claims
+                .nonce,
+        )
+    }
+}
+
+/// Single sign-on against one OIDC provider.
+pub struct OidcClient {
+    settings: OidcSettings,
+    metadata: ProviderMetadata,
+    http: reqwest::Client,
+    jwks: RwLock<JwkSet>,
+    sessions: SessionKeys,
+}
+
+impl OidcClient {
+    /// Fetch the provider's discovery document and signing keys.
+    pub async fn discover(settings: OidcSettings) -> Result<Self, OidcError> {
+        let http = Self::http_client()?;
+        let url = format!(
+            "{}{}",
+            settings.issuer_url.trim_end_matches('/'),
+            DISCOVERY_PATH
+        );
+        let metadata: ProviderMetadata = http
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let jwks = Self::fetch_jwks(&http, &metadata.jwks_uri).await?;
+        Ok(Self::from_parts(settings, metadata, http, jwks))
+    }
+
+    /// A client for a provider whose `metadata` is already known.  Its
+    /// signing keys are fetched when first needed.
+    pub fn new(settings: OidcSettings, metadata: ProviderMetadata) -> Result<Self, OidcError> {
+        Ok(Self::from_parts(
+            settings,
+            metadata,
+            Self::http_client()?,
+            JwkSet { keys: Vec::new() },
+        ))
+    }
+
+    fn http_client() -> Result<reqwest::Client, OidcError> {
+        Ok(reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()?)
+    }
+
+    fn from_parts(
+        settings: OidcSettings,
+        metadata: ProviderMetadata,
+        http: reqwest::Client,
+        jwks: JwkSet,
+    ) -> Self {
+        let sessions = SessionKeys::new(&settings.session_secret, settings.session_ttl);
+        Self {
+            settings,
+            metadata,
+            http,
+            jwks: RwLock::new(jwks),
+            sessions,
+        }
+    }
+
+    async fn fetch_jwks(http: &reqwest::Client, uri: &str) -> Result<JwkSet, OidcError> {
+        Ok(http
+            .get(uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    pub fn settings(&self) -> &OidcSettings {
+        &self.settings
+    }
+
+    pub fn sessions(&self) -> &SessionKeys {
+        &self.sessions
+    }
+
+    /// Where to send the browser to start a login.
+    pub fn authorization_url(&self, now: DateTime<Utc>) -> Result<String, OidcError> {
+        let nonce = uuid::Uuid::new_v4().to_string();
+        let state = self.sessions.login_state(&nonce, now)?;
+        let mut url = url::Url::parse(&self.metadata.authorization_endpoint)
+            .map_err(|e| OidcError::Config(format!("Invalid authorization endpoint: {}", e)))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.settings.client_id)
+            .append_pair("redirect_uri", &self.settings.redirect_url)
+            .append_pair("scope", "openid email profile")
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce);
+        Ok(url.into())
+    }
+
+    /// Redeem the authorization `code` from a callback carrying `state`,
+    /// returning the verified ID token's claims.
+    pub async fn exchange_code(&self, code: &str, state: &str) -> Result<IdTokenClaims, OidcError> {
+        let nonce = self.sessions.verify_login_state(state)?;
+        let response: TokenResponse = self
+            .http
+            .post(&self.metadata.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.settings.redirect_url.as_str()),
+                ("client_id", self.settings.client_id.as_str()),
+                ("client_secret", self.settings.client_secret.as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let claims = self.verify_id_token(&response.id_token).await?;
+        if claims.nonce.as_deref() != Some(nonce.as_str()) {
+            return Err(OidcError::Rejected("ID token nonce mismatch".to_string()));
+        }
+        Ok(claims)
+    }
+
+    /// The algorithms ID tokens may be signed with: the configured ones,
+    /// else the provider's advertised asymmetric ones, else RS256.
+    fn id_token_algorithms(&self) -> Vec<Algorithm> {
+        if !self.settings.id_token_algorithms.is_empty() {
+            return self.settings.id_token_algorithms.clone();
+        }
+        let advertised: Vec<Algorithm> = self
+            .metadata
+            .id_token_signing_alg_values_supported
+            .iter()
+            .filter_map(|alg| alg.parse().ok())
+            .filter(|alg| !is_symmetric(*alg))
+            .collect();
+        if advertised.is_empty() {
+            vec![Algorithm::RS256]
+        } else {
+            advertised
+        }
+    }
+
+    async fn verify_id_token(&self, token: &str) -> Result<IdTokenClaims, OidcError> {
+        let header = decode_header(token)?;
+        let algorithms = self.id_token_algorithms();
+        if !algorithms.contains(&header.alg) {
+            return Err(OidcError::Rejected(format!(
+                "ID token signed with {:?}, which is not accepted",
+                header.alg
+            )));
+        }
+        let key = if is_symmetric(header.alg) {
+            // Symmetric ID tokens are signed with the client secret.
+            DecodingKey::from_secret(self.settings.client_secret.as_bytes())
+        } else {
+            self.signing_key(header.kid.as_deref(), header.alg).await?
+        };
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.metadata.issuer]);
+        validation.set_audience(&[&self.settings.client_id]);
+        Ok(decode::<IdTokenClaims>(token, &key, &validation)?.claims)
+    }
+
+    /// The provider key with id `kid`, refetching the key set once in case
+    /// the provider rotated its keys.  A key that names its algorithm only
+    /// verifies tokens signed with `alg`.
+    async fn signing_key(
+        &self,
+        kid: Option<&str>,
+        alg: Algorithm,
+    ) -> Result<DecodingKey, OidcError> {
+        let find = |jwks: &JwkSet| match kid {
+            Some(kid) => jwks.find(kid).cloned(),
+            None => jwks.keys.first().cloned(),
+        };
+        let jwk = match find(&*self.jwks.read().await) {
+            Some(jwk) => jwk,
+            None => {
+                let jwks = Self::fetch_jwks(&self.http, &self.metadata.jwks_uri).await?;
+                let jwk = find(&jwks).ok_or_else(|| {
+                    OidcError::Rejected("ID token signed with an unknown key".to_string())
+                })?;
+                *self.jwks.write().await = jwks;
+                jwk
+            }
+        };
+        Self::check_key_algorithm(&jwk, alg)?;
+        Ok(DecodingKey::from_jwk(&jwk)?)
+    }
+
+    fn check_key_algorithm(jwk: &Jwk, alg: Algorithm) -> Result<(), OidcError> {
+        match jwk.common.key_algorithm {
+            Some(key_alg) if key_alg.to_string().parse::<Algorithm>().ok() != Some(alg) => {
+                Err(OidcError::Rejected(format!(
+                    "ID token signed with {:?} by a {} key",
+                    alg, key_alg
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The user an ID token logs in as: the existing user with its email, or a
+/// new one in the team mapped to its domain.
+pub async fn login_user<D: Database>(
+    db: &D,
+    settings: &OidcSettings,
+    claims: &IdTokenClaims,
+) -> Result<User, OidcError> {
+    let email = claims
+        .email
+        .as_deref()
+        .ok_or_else(|| OidcError::Rejected("ID token has no email".to_string()))?;
+    // Linking by email is only safe for addresses the provider vouches for.
+    if claims.email_verified != Some(true) {
+        return Err(OidcError::Rejected("Email is not verified".to_string()));
+    }
+    if let Some(user) = db.get_user_by_email(email).await? {
+        return Ok(user);
+    }
+    let team_id = settings.team_for_email(email).ok_or_else(|| {
+        OidcError::Rejected(format!("No team is mapped to the domain of {}", email))
+    })?;
+    tracing::info!(
+        target: "audit",
+        email = %email,
+        team_id = %team_id,
+        role = %settings.default_role,
+        "Creating user on first SSO login"
+    );
+    Ok(db
+        .create_user(team_id, email, settings.default_role)
+        .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> SessionKeys {
+        SessionKeys::new("session-secret", DEFAULT_SESSION_TTL)
+    }
+
+    fn user() -> User {
+        User {
+            id: "user-1".to_string(),
+            team_id: "team-1".to_string(),
+            email: "alice@example.com".to_string(),
+            role: Role::Viewer,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let session = keys().issue(user(), Utc::now()).unwrap();
+        let claims = keys().verify(&session.token).unwrap();
+        assert_eq!(claims.sub, "user-1");
+        assert_eq!(claims.email, "alice@example.com");
+        assert!(SessionKeys::new("other", DEFAULT_SESSION_TTL)
+            .verify(&session.token)
+            .is_err());
+    }
+
+    #[test]
+    fn test_expired_session_is_rejected() {
+        let session = keys()
+            .issue(user(), Utc::now() - chrono::Duration::days(1))
+            .unwrap();
+        assert!(keys().verify(&session.token).is_err());
+    }
+
+    #[test]
+    fn test_login_state_is_not_a_session() {
+        let state = keys().login_state("nonce-1", Utc::now()).unwrap();
+        assert_eq!(keys().verify_login_state(&state).unwrap(), "nonce-1");
+        assert!(keys().verify(&state).is_err());
+        let session = keys().issue(user(), Utc::now()).unwrap();
+        assert!(keys().verify_login_state(&session.token).is_err());
+    }
+
+    fn settings(issuer_url: &str) -> OidcSettings {
+        OidcSettings {
+            issuer_url: issuer_url.to_string(),
+            client_id: "client-1".to_string(),
+            client_secret: "client-secret".to_string(),
+            redirect_url: "http://localhost:3000/v1/auth/callback".to_string(),
+            session_secret: "session-secret".to_string(),
+            session_ttl: DEFAULT_SESSION_TTL,
+            domain_teams: HashMap::new(),
+            default_role: Role::Viewer,
+            id_token_algorithms: vec![Algorithm::HS256],
+        }
+    }
+
+    /// Serve a provider that answers every code with an HS256 ID token for
+    /// `nonce`.
+    async fn fake_provider(nonce: &'static str) -> String {
+        use axum::routing::{get, post};
+        use axum::Json;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let discovery = serde_json::json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{}/authorize", issuer),
+            "token_endpoint": format!("{}/token", issuer),
+            "jwks_uri": format!("{}/jwks", issuer),
+        });
+        let token_issuer = issuer.clone();
+        let app = axum::Router::new()
+            .route(DISCOVERY_PATH, get(move || async move { Json(discovery) }))
+            .route(
+                "/jwks",
+                get(|| async { Json(serde_json::json!({"keys": []})) }),
+            )
+            .route(
+                "/token",
+                post(move || async move {
+                    let claims = serde_json::json!({
+                        "iss": token_issuer,
+                        "aud": "client-1",
+                        "sub": "idp-user",
+                        "email": "alice@example.com",
+                        "email_verified": true,
+                        "nonce": nonce,
+                        "exp": (Utc::now() + chrono::Duration::minutes(5)).timestamp(),
+                    });
+                    let id_token = encode(
+                        &Header::default(),
+                        &claims,
+                        &EncodingKey::from_secret(b"client-secret"),
+                    )
+                    .unwrap();
+                    Json(serde_json::json!({"id_token": id_token}))
+                }),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        issuer
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code_verifies_id_token() {
+        let issuer = fake_provider("nonce-1").await;
+        let client = OidcClient::discover(settings(&issuer)).await.unwrap();
+
+        let login = client.authorization_url(Utc::now()).unwrap();
+        assert!(login.starts_with(&format!("{}/authorize?response_type=code", issuer)));
+
+        let state = client
+            .sessions()
+            .login_state("nonce-1", Utc::now())
+            .unwrap();
+        let claims = client.exchange_code("code-1", &state).await.unwrap();
+        assert_eq!(claims.email.as_deref(), Some("alice@example.com"));
+
+        let replayed = client
+            .sessions()
+            .login_state("nonce-2", Utc::now())
+            .unwrap();
+        assert!(matches!(
+            client.exchange_code("code-1", &replayed).await,
+            Err(OidcError::Rejected(_))
+        ));
+        assert!(client.exchange_code("code-1", "forged").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_symmetric_id_tokens_need_configuring() {
+        let issuer = fake_provider("nonce-1").await;
+        let settings = OidcSettings {
+            id_token_algorithms: Vec::new(),
+            ..settings(&issuer)
+        };
+        let client = OidcClient::discover(settings).await.unwrap();
+        let state = client
+            .sessions()
+            .login_state("nonce-1", Utc::now())
+            .unwrap();
+        assert!(matches!(
+            client.exchange_code("code-1", &state).await,
+            Err(OidcError::Rejected(_))
+        ));
+        assert_eq!(
+            parse_algorithms("RS256, ES256").unwrap(),
+            vec![Algorithm::RS256, Algorithm::ES256]
+        );
+        assert!(parse_algorithms("none").is_err());
+    }
+
+    #[test]
+    fn test_domain_teams() {
+        let teams = parse_domain_teams("Example.com=team-1, corp.io = team-2").unwrap();
+        let settings = OidcSettings {
+            domain_teams: teams,
+            ..settings("")
+        };
+        assert_eq!(settings.team_for_email("bob@EXAMPLE.com"), Some("team-1"));
+        assert_eq!(settings.team_for_email("eve@corp.io"), Some("team-2"));
+        assert_eq!(settings.team_for_email("mallory@evil.com"), None);
+        assert!(parse_domain_teams("example.com").is_err());
+    }
+}
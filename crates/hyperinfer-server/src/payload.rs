@@ -0,0 +1,279 @@
+//! Request payload validation.
+//!
+//! Every request body is checked before a handler sees it: bodies larger
+//! than `MAX_BODY_BYTES` are refused with 413 without being read past the
+//! limit, non-empty bodies must be JSON or, for bulk imports, CSV or YAML
+//! (415 otherwise), and JSON nested
+//! deeper than `MAX_JSON_DEPTH` is refused with 400 before `serde` recurses
+//! into it.  Rejections carry a JSON body:
+//!
+//! ```json
+//! {"error": {"type": "payload_too_large", "message": "...", "limit": 1048576}}
+//! ```
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+/// Default largest accepted request body.
+pub const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Default deepest accepted JSON nesting.
+pub const DEFAULT_MAX_JSON_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PayloadLimits {
+    pub max_body_bytes: usize,
+    pub max_json_depth: usize,
+}
+
+impl Default for PayloadLimits {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            max_json_depth: DEFAULT_MAX_JSON_DEPTH,
+        }
+    }
+}
+
+fn limit_from(
+    var: &dyn Fn(&str) -> Option<String>,
+    name: &str,
+    default: usize,
+) -> Result<usize, String> {
+    match var(name) {
+        Some(value) => value
+            .trim()
+            .parse()
+            .ok()
+            .filter(|limit| *limit > 0)
+            .ok_or_else(|| format!("Invalid {} '{}': expected a positive integer", name, value)),
+        None => Ok(default),
+    }
+}
+
+impl PayloadLimits {
+    /// Limits from the settings `var` looks up by name.
+    pub fn from_vars(var: &dyn Fn(&str) -> Option<String>) -> Result<Self, String> {
+        Ok(Self {
+            max_body_bytes: limit_from(var, "MAX_BODY_BYTES", DEFAULT_MAX_BODY_BYTES)?,
+            max_json_depth: limit_from(var, "MAX_JSON_DEPTH", DEFAULT_MAX_JSON_DEPTH)?,
+        })
+    }
+}
+
+fn rejection(status: StatusCode, kind: &str, message: &str, limit: Option<usize>) -> Response {
+    let mut error = serde_json::json!({ "type": kind, "message": message });
+    if let Some(limit) = limit {
+        error["limit"] = limit.into();
+    }
+    (status, Json(serde_json::json!({ "error": error }))).into_response()
+}
+
+/// The request's media type, lowercased and without parameters.
+fn media_type(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|mime| mime.trim().to_ascii_lowercase())
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    media_type(headers).is_some_and(|mime| mime == "application/json" || mime.ends_with("+json"))
+}
+
+pub fn is_csv(headers: &HeaderMap) -> bool {
+    media_type(headers).is_some_and(|mime| mime == "text/csv")
+}
+
+pub fn is_yaml(headers: &HeaderMap) -> bool {
+    media_type(headers).is_some_and(|mime| {
+        matches!(
+            mime.as_str(),
+            "application/yaml" | "application/x-yaml" | "text/yaml"
+        )
+    })
+}
+
+/// Deepest nesting of objects and arrays in `json`, ignoring brackets inside
+/// strings.  Stops counting once `limit` is exceeded.
+pub fn json_depth(json: &[u8], limit: usize) -> usize {
+    let (mut depth, mut deepest) = (0usize, 0usize);
+    let (mut in_string, mut escaped) = (false, false);
+    for &byte in json {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                deepest = deepest.max(depth);
+                if deepest > limit {
+                    break;
+                }
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    deepest
+}
+
+/// Enforce [`PayloadLimits`] on the request body, then pass the buffered
+/// body on to the handler.
+pub async fn payload_validation_middleware(
+    State(limits): State<PayloadLimits>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let declared_len = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if declared_len.is_some_and(|len| len > limits.max_body_bytes) {
+        return rejection(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "payload_too_large",
+            "Request body is too large",
+            Some(limits.max_body_bytes),
+        );
+    }
+    // Leave bodiless requests, websocket upgrades among them, untouched.
+    if declared_len == Some(0) || matches!(*req.method(), Method::GET | Method::HEAD) {
+        return next.run(req).await;
+    }
+
+    let (parts, body) = req.into_parts();
+    let bytes = match to_bytes(body, limits.max_body_bytes).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return rejection(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "payload_too_large",
+                "Request body is too large",
+                Some(limits.max_body_bytes),
+            )
+        }
+    };
+    if !bytes.is_empty() && !is_csv(&parts.headers) && !is_yaml(&parts.headers) {
+        if !is_json(&parts.headers) {
+            return rejection(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+                "Request body must be application/json, text/csv or YAML",
+                None,
+            );
+        }
+        if json_depth(&bytes, limits.max_json_depth) > limits.max_json_depth {
+            return rejection(
+                StatusCode::BAD_REQUEST,
+                "json_too_deep",
+                "Request body is nested too deeply",
+                Some(limits.max_json_depth),
+            );
+        }
+    }
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::post, Router};
+    use axum_test::TestServer;
+
+    fn server(limits: PayloadLimits) -> TestServer {
+        let app = Router::new()
+            .route(
+                "/echo",
+                post(|Json(value): Json<serde_json::Value>| async move { Json(value) }),
+            )
+            .route("/empty", post(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(
+                limits,
+                payload_validation_middleware,
+            ));
+        TestServer::new(app)
+    }
+
+    #[test]
+    fn test_json_depth_ignores_strings() {
+        assert_eq!(json_depth(br#"{"a": [1, {"b": 2}]}"#, 32), 3);
+        assert_eq!(json_depth(br#"{"a": "[[[[{{{{\"]]"}"#, 32), 1);
+        assert_eq!(json_depth(b"42", 32), 0);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_is_rejected() {
+        let server = server(PayloadLimits {
+            max_body_bytes: 16,
+            ..Default::default()
+        });
+        let response = server
+            .post("/echo")
+            .json(&serde_json::json!({"name": "far too long for the limit"}))
+            .await;
+        assert_eq!(response.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body: serde_json::Value = response.json();
+        assert_eq!(body["error"]["type"], "payload_too_large");
+        assert_eq!(body["error"]["limit"], 16);
+    }
+
+    #[tokio::test]
+    async fn test_non_json_body_is_rejected() {
+        let server = server(PayloadLimits::default());
+        let response = server.post("/echo").text("name=team").await;
+        assert_eq!(response.status_code(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        // Bodiless requests need no content type.
+        assert_eq!(server.post("/empty").await.status_code(), StatusCode::OK);
+        // CSV passes through for bulk imports.
+        let response = server
+            .post("/empty")
+            .content_type("text/csv")
+            .bytes("kind,name\nteam,ops\n".into())
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        // So does YAML, for LiteLLM configs.
+        let response = server
+            .post("/empty")
+            .content_type("application/yaml")
+            .bytes("model_list: []\n".into())
+            .await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_deep_json_is_rejected() {
+        let server = server(PayloadLimits {
+            max_json_depth: 4,
+            ..Default::default()
+        });
+        let deep: serde_json::Value = serde_json::from_str("[[[[[1]]]]]").unwrap();
+        let response = server.post("/echo").json(&deep).await;
+        assert_eq!(response.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            response.json::<serde_json::Value>()["error"]["type"],
+            "json_too_deep"
+        );
+
+        let shallow = serde_json::json!({"a": [1, 2]});
+        let response = server.post("/echo").json(&shallow).await;
+        assert_eq!(response.status_code(), StatusCode::OK);
+        assert_eq!(response.json::<serde_json::Value>(), shallow);
+    }
+}
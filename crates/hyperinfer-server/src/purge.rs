@@ -0,0 +1,68 @@
+//! Purging soft-deleted data.
+//!
+//! Deleting a team or user only marks it deleted, so its usage history keeps
+//! working for reports and invoices.  A [`DeletedDataPurger`] removes such
+//! rows for good, along with everything that references them, once they
+//! have been deleted for longer than the retention period.
+
+use crate::leader::Leadership;
+use chrono::{DateTime, Utc};
+use hyperinfer_core::{Database, DbError};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// How long deleted teams and users are kept before purging.
+pub const DEFAULT_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+pub struct DeletedDataPurger<D: Database> {
+    db: D,
+    retention: Duration,
+}
+
+impl<D: Database> DeletedDataPurger<D> {
+    pub fn new(db: D, retention: Duration) -> Self {
+        Self { db, retention }
+    }
+
+    /// Purge once on `interval` until `cancel` fires, skipping ticks while
+    /// this replica is not the scheduler leader.
+    pub fn spawn(
+        self,
+        interval: Duration,
+        leader: Leadership,
+        cancel: CancellationToken,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = ticker.tick() => {
+                        if !leader.is_leader() {
+                            continue;
+                        }
+                        if let Err(e) = self.purge_once(Utc::now()).await {
+                            tracing::error!("Purging deleted data failed: {:?}", e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Remove rows deleted more than the retention period before `now`.
+    /// Returns the number of teams and users removed.
+    pub async fn purge_once(&self, now: DateTime<Utc>) -> Result<u64, DbError> {
+        let cutoff = chrono::Duration::from_std(self.retention)
+            .ok()
+            .and_then(|retention| now.checked_sub_signed(retention));
+        let Some(cutoff) = cutoff else {
+            return Ok(0);
+        };
+        let purged = self.db.purge_deleted(cutoff).await?;
+        if purged > 0 {
+            tracing::info!("Purged {} deleted teams and users", purged);
+        }
+        Ok(purged)
+    }
+}
@@ -0,0 +1,465 @@
+//! Scheduled usage reports.
+//!
+//! Teams subscribe recipients to a weekly or monthly report.  A
+//! [`ReportScheduler`] on the scheduler leader checks subscriptions
+//! periodically and, once a period has closed, emails each subscription the
+//! team's usage and spend for it as an HTML summary with the per-model
+//! figures attached as CSV.  Weeks run Monday to Monday and months from the
+//! first of the month, both in UTC; spend is priced as billing prices it.
+
+use crate::leader::Leadership;
+use crate::mailer::{self, Attachment, Email, MailError, Mailer};
+use crate::summary::{self, ModelSummary};
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Months, Utc};
+use hyperinfer_core::{Database, DbError, NewReportSubscription, ReportSubscription, Team};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Most recipients per subscription.
+pub const MAX_RECIPIENTS: usize = 20;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReportError {
+    #[error("Database error: {0}")]
+    Db(#[from] DbError),
+    #[error("CSV error: {0}")]
+    Csv(String),
+    #[error("Mail error: {0}")]
+    Mail(#[from] MailError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFrequency {
+    Weekly,
+    Monthly,
+}
+
+impl ReportFrequency {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "weekly" => Some(ReportFrequency::Weekly),
+            "monthly" => Some(ReportFrequency::Monthly),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportFrequency::Weekly => "weekly",
+            ReportFrequency::Monthly => "monthly",
+        }
+    }
+}
+
+pub fn validate_subscription(subscription: &NewReportSubscription) -> Result<(), String> {
+    if ReportFrequency::parse(&subscription.frequency).is_none() {
+        return Err(format!(
+            "Unknown frequency '{}': expected weekly or monthly",
+            subscription.frequency
+        ));
+    }
+    if subscription.recipients.is_empty() {
+        return Err("recipients must not be empty".to_string());
+    }
+    if subscription.recipients.len() > MAX_RECIPIENTS {
+        return Err(format!("At most {} recipients are allowed", MAX_RECIPIENTS));
+    }
+    if let Some(invalid) = subscription
+        .recipients
+        .iter()
+        .find(|r| !mailer::is_valid_address(r))
+    {
+        return Err(format!("Invalid recipient address '{}'", invalid));
+    }
+    Ok(())
+}
+
+/// The last full period of `frequency` before `now`, as `[start, end)`.
+pub fn last_period(
+    frequency: ReportFrequency,
+    now: DateTime<Utc>,
+) -> (DateTime<Utc>, DateTime<Utc>) {
+    let today = summary::start_of_day(now);
+    match frequency {
+        ReportFrequency::Weekly => {
+            let end = today - ChronoDuration::days(i64::from(now.weekday().num_days_from_monday()));
+            (end - ChronoDuration::days(7), end)
+        }
+        ReportFrequency::Monthly => {
+            let end = today - ChronoDuration::days(i64::from(now.day0()));
+            let start = end
+                .checked_sub_months(Months::new(1))
+                .expect("the previous month is representable");
+            (start, end)
+        }
+    }
+}
+
+/// One team's usage over a report period.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeamReport {
+    pub team_id: String,
+    pub team_name: String,
+    pub frequency: ReportFrequency,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Every model used in the period, most requests first.
+    pub models: Vec<ModelSummary>,
+}
+
+impl TeamReport {
+    pub fn requests(&self) -> i64 {
+        self.models.iter().map(|m| m.requests).sum()
+    }
+
+    pub fn input_tokens(&self) -> i64 {
+        self.models.iter().map(|m| m.input_tokens).sum()
+    }
+
+    pub fn output_tokens(&self) -> i64 {
+        self.models.iter().map(|m| m.output_tokens).sum()
+    }
+
+    pub fn spend_cents(&self) -> f64 {
+        self.models.iter().map(|m| m.spend_cents).sum()
+    }
+
+    /// `2026-03-02 – 2026-03-08`: the period's first and last days.
+    fn period_label(&self) -> String {
+        format!(
+            "{} – {}",
+            self.start.format("%Y-%m-%d"),
+            (self.end - ChronoDuration::days(1)).format("%Y-%m-%d")
+        )
+    }
+
+    pub fn subject(&self) -> String {
+        format!(
+            "HyperInfer {} usage report for {}: {}",
+            self.frequency.as_str(),
+            self.team_name,
+            self.period_label()
+        )
+    }
+}
+
+pub async fn build_report<D: Database>(
+    db: &D,
+    team: &Team,
+    frequency: ReportFrequency,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<TeamReport, DbError> {
+    let usage = db.get_model_usage_between(&team.id, start, end).await?;
+    let prices = db.list_model_prices().await?;
+    Ok(TeamReport {
+        team_id: team.id.clone(),
+        team_name: team.name.clone(),
+        frequency,
+        start,
+        end,
+        // Priced as of the end of the period, as a closed billing period is.
+        models: summary::model_summaries(&usage, &prices, end),
+    })
+}
+
+/// The per-model figures, one row per model.
+pub fn render_csv(report: &TeamReport) -> Result<Vec<u8>, ReportError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for model in &report.models {
+        writer
+            .serialize(model)
+            .map_err(|e| ReportError::Csv(e.to_string()))?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| ReportError::Csv(e.to_string()))
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn dollars(cents: f64) -> String {
+    format!("${:.2}", cents / 100.0)
+}
+
+pub fn render_html(report: &TeamReport) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html><body style=\"font-family: sans-serif\">\n");
+    html.push_str(&format!(
+        "<h2>{}</h2>\n",
+        escape_html(&format!(
+            "{} usage report for {}",
+            match report.frequency {
+                ReportFrequency::Weekly => "Weekly",
+                ReportFrequency::Monthly => "Monthly",
+            },
+            report.team_name
+        ))
+    ));
+    html.push_str(&format!(
+        "<p>{} (UTC)</p>\n",
+        escape_html(&report.period_label())
+    ));
+    html.push_str(&format!(
+        "<p><strong>{}</strong> requests, <strong>{}</strong> input and <strong>{}</strong> output tokens, <strong>{}</strong> spend.</p>\n",
+        report.requests(),
+        report.input_tokens(),
+        report.output_tokens(),
+        dollars(report.spend_cents())
+    ));
+    if report.models.is_empty() {
+        html.push_str("<p>No requests in this period.</p>\n");
+    } else {
+        html.push_str("<table cellpadding=\"4\" style=\"border-collapse: collapse\">\n");
+        html.push_str("<tr><th align=\"left\">Model</th><th align=\"right\">Requests</th><th align=\"right\">Input tokens</th><th align=\"right\">Output tokens</th><th align=\"right\">Spend</th></tr>\n");
+        for model in &report.models {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td align=\"right\">{}</td><td align=\"right\">{}</td><td align=\"right\">{}</td><td align=\"right\">{}</td></tr>\n",
+                escape_html(&model.model),
+                model.requests,
+                model.input_tokens,
+                model.output_tokens,
+                dollars(model.spend_cents)
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body></html>\n");
+    html
+}
+
+pub fn report_email(report: &TeamReport, from: &str, to: &[String]) -> Result<Email, ReportError> {
+    Ok(Email {
+        from: from.to_string(),
+        to: to.to_vec(),
+        subject: report.subject(),
+        html: render_html(report),
+        attachments: vec![Attachment {
+            filename: format!(
+                "usage-{}-{}.csv",
+                report.frequency.as_str(),
+                report.start.format("%Y-%m-%d")
+            ),
+            content_type: "text/csv".to_string(),
+            data: render_csv(report)?,
+        }],
+    })
+}
+
+/// Emails reports for closed periods.
+pub struct ReportScheduler<D> {
+    db: D,
+    mailer: Arc<dyn Mailer>,
+    from: String,
+}
+
+impl<D: Database> ReportScheduler<D> {
+    pub fn new(db: D, mailer: Arc<dyn Mailer>, from: String) -> Self {
+        Self { db, mailer, from }
+    }
+
+    /// Send due reports on `interval` until `cancel` fires, skipping ticks
+    /// while this replica is not the scheduler leader.
+    pub fn spawn(
+        self,
+        interval: Duration,
+        leader: Leadership,
+        cancel: CancellationToken,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = ticker.tick() => {
+                        if !leader.is_leader() {
+                            continue;
+                        }
+                        if let Err(e) = self.run_once(Utc::now()).await {
+                            tracing::error!("Report run failed: {:?}", e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Send every report whose period closed before `now` and has not been
+    /// sent yet.  Returns the number sent; a report that fails to send is
+    /// retried on the next run.
+    pub async fn run_once(&self, now: DateTime<Utc>) -> Result<usize, DbError> {
+        let mut sent = 0;
+        for subscription in self.db.list_report_subscriptions(None).await? {
+            match self.send_due(&subscription, now).await {
+                Ok(true) => sent += 1,
+                Ok(false) => {}
+                Err(e) => tracing::warn!(
+                    "Failed to send report for subscription {}: {}",
+                    subscription.id,
+                    e
+                ),
+            }
+        }
+        Ok(sent)
+    }
+
+    async fn send_due(
+        &self,
+        subscription: &ReportSubscription,
+        now: DateTime<Utc>,
+    ) -> Result<bool, ReportError> {
+        let Some(frequency) = ReportFrequency::parse(&subscription.frequency) else {
+            return Ok(false);
+        };
+        let (start, end) = last_period(frequency, now);
+        if subscription.last_period_end.is_some_and(|sent| sent >= end) {
+            return Ok(false);
+        }
+        let Some(team) = self.db.get_team(&subscription.team_id).await? else {
+            return Ok(false);
+        };
+        let report = build_report(&self.db, &team, frequency, start, end).await?;
+        let email = report_email(&report, &self.from, &subscription.recipients)?;
+        self.mailer.send(&email).await?;
+        self.db.mark_report_sent(&subscription.id, end).await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn report() -> TeamReport {
+        TeamReport {
+            team_id: "team-id".to_string(),
+            team_name: "R&D <core>".to_string(),
+            frequency: ReportFrequency::Weekly,
+            start: Utc.with_ymd_and_hms(2026, 3, 2, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2026, 3, 9, 0, 0, 0).unwrap(),
+            models: vec![
+                ModelSummary {
+                    model: "gpt-4o".to_string(),
+                    requests: 3,
+                    input_tokens: 3_000,
+                    output_tokens: 1_500,
+                    spend_cents: 2.25,
+                },
+                ModelSummary {
+                    model: "claude-3-haiku".to_string(),
+                    requests: 1,
+                    input_tokens: 100,
+                    output_tokens: 50,
+                    spend_cents: 0.5,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_last_period_weekly() {
+        // Wednesday 2026-03-11.
+        let now = Utc.with_ymd_and_hms(2026, 3, 11, 15, 0, 0).unwrap();
+        assert_eq!(
+            last_period(ReportFrequency::Weekly, now),
+            (
+                Utc.with_ymd_and_hms(2026, 3, 2, 0, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2026, 3, 9, 0, 0, 0).unwrap()
+            )
+        );
+        // On Monday the week that just ended is reported.
+        let monday = Utc.with_ymd_and_hms(2026, 3, 9, 0, 30, 0).unwrap();
+        assert_eq!(
+            last_period(ReportFrequency::Weekly, monday).1,
+            monday - ChronoDuration::minutes(30)
+        );
+    }
+
+    #[test]
+    fn test_last_period_monthly() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 31, 23, 0, 0).unwrap();
+        assert_eq!(
+            last_period(ReportFrequency::Monthly, now),
+            (
+                Utc.with_ymd_and_hms(2026, 2, 1, 0, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2026, 3, 1, 0, 0, 0).unwrap()
+            )
+        );
+        let january = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            last_period(ReportFrequency::Monthly, january).0,
+            Utc.with_ymd_and_hms(2025, 12, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_validate_subscription() {
+        let mut subscription = NewReportSubscription {
+            team_id: "team-id".to_string(),
+            frequency: "weekly".to_string(),
+            recipients: vec!["ops@example.com".to_string()],
+        };
+        assert!(validate_subscription(&subscription).is_ok());
+
+        subscription.frequency = "daily".to_string();
+        assert!(validate_subscription(&subscription).is_err());
+
+        subscription.frequency = "monthly".to_string();
+        subscription.recipients = vec!["ops@example.com\r\nBcc: x@example.com".to_string()];
+        assert!(validate_subscription(&subscription).is_err());
+
+        subscription.recipients.clear();
+        assert!(validate_subscription(&subscription).is_err());
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let csv = String::from_utf8(render_csv(&report()).unwrap()).unwrap();
+        assert_eq!(
+            csv,
+            "model,requests,input_tokens,output_tokens,spend_cents\n\
+             gpt-4o,3,3000,1500,2.25\n\
+             claude-3-haiku,1,100,50,0.5\n"
+        );
+    }
+
+    #[test]
+    fn test_render_html_escapes_and_totals() {
+        let html = render_html(&report());
+        assert!(html.contains("Weekly usage report for R&amp;D &lt;core&gt;"));
+        assert!(html.contains("2026-03-02 – 2026-03-08"));
+        assert!(html.contains("<strong>4</strong> requests"));
+        assert!(html.contains("<strong>$0.03</strong> spend"));
+        assert!(html.contains("<td>claude-3-haiku</td>"));
+    }
+
+    #[test]
+    fn test_report_email() {
+        let email = report_email(
+            &report(),
+            "reports@example.com",
+            &["ops@example.com".to_string()],
+        )
+        .unwrap();
+        assert_eq!(
+            email.subject,
+            "HyperInfer weekly usage report for R&D <core>: 2026-03-02 – 2026-03-08"
+        );
+        assert_eq!(email.attachments[0].filename, "usage-weekly-2026-03-02.csv");
+    }
+}
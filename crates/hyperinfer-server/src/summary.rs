@@ -0,0 +1,169 @@
+//! Deployment-wide summaries for the dashboard.
+//!
+//! Figures cover every team since the start of the current UTC day and are
+//! priced the same way as billing.
+
+use crate::billing;
+use chrono::{DateTime, Utc};
+use hyperinfer_core::{ConfiguredPrice, Database, DbError, ModelUsage};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Most models listed in [`Overview::top_models`].
+pub const TOP_MODELS: usize = 5;
+
+/// Usage and spend of one model.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ModelSummary {
+    pub model: String,
+    pub requests: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub spend_cents: f64,
+}
+
+/// Today's traffic across the deployment.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Overview {
+    /// Start of the current UTC day; every figure covers the time since.
+    pub since: DateTime<Utc>,
+    /// Successful requests.
+    pub requests: i64,
+    /// Failed requests.
+    pub errors: i64,
+    /// Failed requests as a fraction of all requests; 0 when there were none.
+    pub error_rate: f64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub spend_cents: f64,
+    /// Busiest models, most requests first.
+    pub top_models: Vec<ModelSummary>,
+}
+
+/// Start of the UTC day containing `now`.
+pub fn start_of_day(now: DateTime<Utc>) -> DateTime<Utc> {
+    now.date_naive()
+        .and_hms_opt(0, 0, 0)
+        .expect("midnight is a valid time")
+        .and_utc()
+}
+
+pub async fn overview<D: Database>(db: &D, now: DateTime<Utc>) -> Result<Overview, DbError> {
+    let since = start_of_day(now);
+    let usage = db.get_all_model_usage_since(since).await?;
+    let errors = db.count_request_errors_since(None, None, since).await?;
+    let prices = db.list_model_prices().await?;
+    Ok(summarize(since, &usage, errors, &prices, now))
+}
+
+/// Per-model usage priced at `at`, most requests first.
+pub fn model_summaries(
+    usage: &[ModelUsage],
+    prices: &[ConfiguredPrice],
+    at: DateTime<Utc>,
+) -> Vec<ModelSummary> {
+    let mut models: Vec<ModelSummary> = usage
+        .iter()
+        .map(|u| ModelSummary {
+            model: u.model.clone(),
+            requests: u.requests,
+            input_tokens: u.input_tokens,
+            output_tokens: u.output_tokens,
+            spend_cents: billing::spend_cents(std::slice::from_ref(u), prices, at),
+        })
+        .collect();
+    models.sort_by(|a, b| {
+        b.requests
+            .cmp(&a.requests)
+            .then_with(|| a.model.cmp(&b.model))
+    });
+    models
+}
+
+fn summarize(
+    since: DateTime<Utc>,
+    usage: &[ModelUsage],
+    errors: i64,
+    prices: &[ConfiguredPrice],
+    now: DateTime<Utc>,
+) -> Overview {
+    let models = model_summaries(usage, prices, now);
+    let requests = models.iter().map(|m| m.requests).sum::<i64>();
+    let total = requests + errors;
+    Overview {
+        since,
+        requests,
+        errors,
+        error_rate: if total > 0 {
+            errors as f64 / total as f64
+        } else {
+            0.0
+        },
+        input_tokens: models.iter().map(|m| m.input_tokens).sum(),
+        output_tokens: models.iter().map(|m| m.output_tokens).sum(),
+        spend_cents: models.iter().map(|m| m.spend_cents).sum(),
+        top_models: models.into_iter().take(TOP_MODELS).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn usage(model: &str, requests: i64) -> ModelUsage {
+        ModelUsage {
+            model: model.to_string(),
+            requests,
+            input_tokens: requests * 1_000,
+            output_tokens: requests * 500,
+        }
+    }
+
+    #[test]
+    fn test_start_of_day() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 14, 15, 9, 26).unwrap();
+        assert_eq!(
+            start_of_day(now),
+            Utc.with_ymd_and_hms(2026, 3, 14, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_summarize_totals_and_top_models() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 14, 12, 0, 0).unwrap();
+        let since = start_of_day(now);
+        let rows: Vec<ModelUsage> = (1..=7)
+            .map(|i| usage(&format!("gpt-4o-{}", i), i))
+            .collect();
+
+        let overview = summarize(since, &rows, 4, &[], now);
+        assert_eq!(overview.requests, 28);
+        assert_eq!(overview.errors, 4);
+        assert!((overview.error_rate - 4.0 / 32.0).abs() < 1e-9);
+        assert_eq!(overview.input_tokens, 28_000);
+        assert_eq!(overview.output_tokens, 14_000);
+        // gpt-4o lists at $2.50 / $10.00 per million tokens.
+        assert!(
+            (overview.spend_cents - (28_000.0 * 2.5 + 14_000.0 * 10.0) / 10_000.0).abs() < 1e-9
+        );
+        let top: Vec<&str> = overview
+            .top_models
+            .iter()
+            .map(|m| m.model.as_str())
+            .collect();
+        assert_eq!(
+            top,
+            ["gpt-4o-7", "gpt-4o-6", "gpt-4o-5", "gpt-4o-4", "gpt-4o-3"]
+        );
+    }
+
+    #[test]
+    fn test_summarize_without_traffic() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 14, 0, 0, 1).unwrap();
+        let overview = summarize(start_of_day(now), &[], 0, &[], now);
+        assert_eq!(overview.requests, 0);
+        assert_eq!(overview.error_rate, 0.0);
+        assert!(overview.top_models.is_empty());
+    }
+}
@@ -0,0 +1,313 @@
+//! TLS termination for deployments without a fronting load balancer.
+//!
+//! With `TLS_CERT_PATH` and `TLS_KEY_PATH` set (PEM certificate chain and
+//! PKCS#8, PKCS#1 or SEC1 private key), the server accepts HTTPS directly
+//! through a [`TlsListener`].  Setting `TLS_CLIENT_CA_PATH` as well turns on
+//! mutual TLS: clients must present a certificate issued by one of the CAs
+//! in that file, or may present one when `TLS_CLIENT_AUTH=optional`.
+//!
+//! Handshakes run off the accept loop, with a timeout, so one slow or
+//! malicious client cannot hold up others.
+
+use axum::serve::Listener;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+/// Longest a client may take to finish the handshake.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Completed handshakes waiting to be served.
+const ACCEPT_BACKLOG: usize = 128;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("TLS configuration error: {0}")]
+    Config(String),
+    #[error("Failed to read {path}: {message}")]
+    Pem { path: String, message: String },
+    #[error("TLS error: {0}")]
+    Rustls(#[from] rustls::Error),
+}
+
+/// Whether clients must present a certificate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientAuth {
+    Required,
+    Optional,
+}
+
+impl ClientAuth {
+    pub fn parse(value: &str) -> Result<Self, TlsError> {
+        match value {
+            "required" => Ok(Self::Required),
+            "optional" => Ok(Self::Optional),
+            _ => Err(TlsError::Config(format!(
+                "Unknown TLS_CLIENT_AUTH '{}': expected required or optional",
+                value
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsSettings {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// CAs whose client certificates are accepted; `None` disables mutual
+    /// TLS.
+    pub client_ca_path: Option<PathBuf>,
+    pub client_auth: ClientAuth,
+}
+
+impl TlsSettings {
+    /// Settings from the variables `var` looks up by name, or `None` to
+    /// serve plain HTTP.
+    pub fn from_vars(var: &dyn Fn(&str) -> Option<String>) -> Result<Option<Self>, TlsError> {
+        let path = |name: &str| var(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+        let (cert_path, key_path) = match (path("TLS_CERT_PATH"), path("TLS_KEY_PATH")) {
+            (Some(cert), Some(key)) => (cert, key),
+            (None, None) => return Ok(None),
+            _ => {
+                return Err(TlsError::Config(
+                    "TLS_CERT_PATH and TLS_KEY_PATH must be set together".to_string(),
+                ))
+            }
+        };
+        let client_auth = match var("TLS_CLIENT_AUTH") {
+            Some(value) => ClientAuth::parse(&value)?,
+            None => ClientAuth::Required,
+        };
+        Ok(Some(Self {
+            cert_path,
+            key_path,
+            client_ca_path: path("TLS_CLIENT_CA_PATH"),
+            client_auth,
+        }))
+    }
+
+    /// Load the certificates and key into a rustls server config.
+    pub fn server_config(&self) -> Result<Arc<ServerConfig>, TlsError> {
+        let pem_error = |path: &PathBuf, e: rustls::pki_types::pem::Error| TlsError::Pem {
+            path: path.display().to_string(),
+            message: e.to_string(),
+        };
+        let certs = CertificateDer::pem_file_iter(&self.cert_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| pem_error(&self.cert_path, e))?;
+        let key = PrivateKeyDer::from_pem_file(&self.key_path)
+            .map_err(|e| pem_error(&self.key_path, e))?;
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let builder = match &self.client_ca_path {
+            None => builder.with_no_client_auth(),
+            Some(ca_path) => {
+                let mut roots = RootCertStore::empty();
+                for ca in
+                    CertificateDer::pem_file_iter(ca_path).map_err(|e| pem_error(ca_path, e))?
+                {
+                    roots.add(ca.map_err(|e| pem_error(ca_path, e))?)?;
+                }
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+                let verifier = match self.client_auth {
+                    ClientAuth::Required => verifier,
+                    ClientAuth::Optional => verifier.allow_unauthenticated(),
+                };
+                let verifier = verifier
+                    .build()
+                    .map_err(|e| TlsError::Config(format!("Invalid client CA: {}", e)))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+        };
+        let mut config = builder.with_single_cert(certs, key)?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(Arc::new(config))
+    }
+}
+
+/// A listener yielding connections that completed a TLS handshake.
+pub struct TlsListener {
+    ready: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+    accept_loop: tokio::task::JoinHandle<()>,
+}
+
+impl TlsListener {
+    pub fn new(listener: TcpListener, config: Arc<ServerConfig>) -> std::io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let acceptor = TlsAcceptor::from(config);
+        let (tx, ready) = mpsc::channel(ACCEPT_BACKLOG);
+        let accept_loop = tokio::spawn(async move {
+            loop {
+                let (stream, addr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        tracing::warn!("Failed to accept connection: {}", e);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
+                };
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(tls)) => {
+                            let _ = tx.send((tls, addr)).await;
+                        }
+                        Ok(Err(e)) => tracing::debug!("TLS handshake with {} failed: {}", addr, e),
+                        Err(_) => tracing::debug!("TLS handshake with {} timed out", addr),
+                    }
+                });
+            }
+        });
+        Ok(Self {
+            ready,
+            local_addr,
+            accept_loop,
+        })
+    }
+}
+
+impl Drop for TlsListener {
+    fn drop(&mut self) {
+        self.accept_loop.abort();
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.ready.recv().await {
+            Some(accepted) => accepted,
+            // The accept loop never ends on its own.
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Router};
+    use rustls::ClientConfig;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tls");
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(FIXTURES).join(name)
+    }
+
+    fn settings(client_ca: bool) -> TlsSettings {
+        TlsSettings {
+            cert_path: fixture("server.pem"),
+            key_path: fixture("server.key"),
+            client_ca_path: client_ca.then(|| fixture("ca.pem")),
+            client_auth: ClientAuth::Required,
+        }
+    }
+
+    async fn serve(settings: TlsSettings) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let listener = TlsListener::new(listener, settings.server_config().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/health", get(|| async { "ok" }));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        addr
+    }
+
+    /// GET /health over TLS, returning the raw response or the error.
+    async fn get_health(addr: SocketAddr, client_cert: bool) -> std::io::Result<String> {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_file(fixture("ca.pem")).unwrap())
+            .unwrap();
+        let builder =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_root_certificates(roots);
+        let config = if client_cert {
+            let certs = CertificateDer::pem_file_iter(fixture("client.pem"))
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            let key = PrivateKeyDer::from_pem_file(fixture("client.key")).unwrap();
+            builder.with_client_auth_cert(certs, key).unwrap()
+        } else {
+            builder.with_no_client_auth()
+        };
+
+        let stream = TcpStream::connect(addr).await?;
+        let mut tls = TlsConnector::from(Arc::new(config))
+            .connect("localhost".try_into().unwrap(), stream)
+            .await?;
+        tls.write_all(b"GET /health HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        tls.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    #[tokio::test]
+    async fn test_serves_https() {
+        let addr = serve(settings(false)).await;
+        let response = get_health(addr, false).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn test_mutual_tls_requires_client_certificate() {
+        let addr = serve(settings(true)).await;
+        let response = get_health(addr, true).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+
+        let rejected = get_health(addr, false).await;
+        assert!(
+            rejected.as_ref().map_or(true, |r| !r.contains("200 OK")),
+            "{:?}",
+            rejected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_optional_client_auth_admits_anonymous_clients() {
+        let addr = serve(TlsSettings {
+            client_auth: ClientAuth::Optional,
+            ..settings(true)
+        })
+        .await;
+        let response = get_health(addr, false).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    }
+
+    #[test]
+    fn test_missing_key_file_is_reported() {
+        let err = TlsSettings {
+            key_path: fixture("missing.key"),
+            ..settings(false)
+        }
+        .server_config()
+        .unwrap_err();
+        assert!(matches!(err, TlsError::Pem { .. }), "{}", err);
+    }
+}
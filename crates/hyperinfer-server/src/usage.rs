@@ -0,0 +1,120 @@
+//! Usage analytics.
+//!
+//! Breaks a team's usage in a time window down by model or by the value of a
+//! request metadata tag (`group_by=tag:<name>`), priced the same way as
+//! billing so the groups add up to the team's spend.
+
+use crate::billing;
+use chrono::{DateTime, Utc};
+use hyperinfer_core::{Database, DbError, ModelUsage};
+use serde::Serialize;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GroupBy {
+    Model,
+    /// Value of the named `ChatRequest::metadata` tag.
+    Tag(String),
+}
+
+impl GroupBy {
+    /// Parse `model` or `tag:<name>`.
+    pub fn parse(group_by: &str) -> Result<Self, String> {
+        match group_by.split_once(':') {
+            None if group_by == "model" => Ok(Self::Model),
+            Some(("tag", name)) if !name.trim().is_empty() => Ok(Self::Tag(name.to_string())),
+            _ => Err(format!(
+                "Unknown group_by '{}': expected 'model' or 'tag:<name>'",
+                group_by
+            )),
+        }
+    }
+}
+
+/// Usage and spend of one group.  `group` is `None` for requests that did
+/// not carry the tag being grouped by.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct UsageGroup {
+    pub group: Option<String>,
+    pub requests: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub spend_cents: f64,
+}
+
+/// Usage of `team_id` in `[start, end)` grouped by `group_by`, highest
+/// spend first.
+pub async fn usage_report<D: Database>(
+    db: &D,
+    team_id: &str,
+    group_by: &GroupBy,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<UsageGroup>, DbError> {
+    let prices = db.list_model_prices().await?;
+    let rows: Vec<(Option<String>, ModelUsage)> = match group_by {
+        GroupBy::Model => db
+            .get_model_usage_between(team_id, start, end)
+            .await?
+            .into_iter()
+            .map(|u| (Some(u.model.clone()), u))
+            .collect(),
+        GroupBy::Tag(tag) => db
+            .get_tag_usage_between(team_id, tag, start, end)
+            .await?
+            .into_iter()
+            .map(|u| {
+                (
+                    u.tag_value,
+                    ModelUsage {
+                        model: u.model,
+                        requests: u.requests,
+                        input_tokens: u.input_tokens,
+                        output_tokens: u.output_tokens,
+                    },
+                )
+            })
+            .collect(),
+    };
+
+    let mut groups: HashMap<Option<String>, UsageGroup> = HashMap::new();
+    for (group, usage) in rows {
+        let spend = billing::spend_cents(std::slice::from_ref(&usage), &prices, end);
+        let entry = groups.entry(group.clone()).or_insert_with(|| UsageGroup {
+            group,
+            requests: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+            spend_cents: 0.0,
+        });
+        entry.requests += usage.requests;
+        entry.input_tokens += usage.input_tokens;
+        entry.output_tokens += usage.output_tokens;
+        entry.spend_cents += spend;
+    }
+    let mut groups: Vec<UsageGroup> = groups.into_values().collect();
+    groups.sort_by(|a, b| {
+        b.spend_cents
+            .total_cmp(&a.spend_cents)
+            .then_with(|| a.group.cmp(&b.group))
+    });
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_by_parse() {
+        assert_eq!(GroupBy::parse("model"), Ok(GroupBy::Model));
+        assert_eq!(
+            GroupBy::parse("tag:feature"),
+            Ok(GroupBy::Tag("feature".to_string()))
+        );
+        assert!(GroupBy::parse("tag:").is_err());
+        assert!(GroupBy::parse("team").is_err());
+        assert!(GroupBy::parse("label:feature").is_err());
+    }
+}
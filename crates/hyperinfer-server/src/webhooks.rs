@@ -0,0 +1,467 @@
+//! Webhooks for lifecycle events.
+//!
+//! Teams subscribe URLs to events such as `key.created` or
+//! `budget.exceeded`.  [`emit`] queues one delivery per subscribed webhook in
+//! the `webhook_deliveries` table, which doubles as the delivery log.  A
+//! [`WebhookDispatcher`] on the scheduler leader POSTs due deliveries and
+//! retries failures with exponential backoff; after [`MAX_ATTEMPTS`] a
+//! delivery is marked failed and left in the log.
+//!
+//! Every request carries:
+//!
+//! * `X-HyperInfer-Event` — the event type.
+//! * `X-HyperInfer-Delivery` — the delivery id, the same across retries.
+//! * `X-HyperInfer-Signature` — `t=<unix seconds>,v1=<hex>`, where `<hex>` is
+//!   the HMAC-SHA256 of `<t>.<body>` keyed with the webhook's secret.
+//!   Receivers should recompute it and reject stale timestamps.
+
+use crate::leader::Leadership;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use hyperinfer_core::{Database, DbError, NewWebhook, Webhook, WebhookDelivery};
+use ring::hmac;
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+pub const EVENT_HEADER: &str = "X-HyperInfer-Event";
+pub const DELIVERY_HEADER: &str = "X-HyperInfer-Delivery";
+pub const SIGNATURE_HEADER: &str = "X-HyperInfer-Signature";
+
+/// Attempts per delivery before it is marked failed.
+pub const MAX_ATTEMPTS: i32 = 8;
+/// Shortest secret accepted through the admin API.
+pub const MIN_SECRET_LEN: usize = 16;
+
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(30);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+/// Deliveries sent per dispatcher tick.
+const BATCH_SIZE: i64 = 100;
+/// Deliveries in flight at once, so one slow receiver cannot hold up the
+/// rest of a tick.
+const MAX_IN_FLIGHT: usize = 16;
+
+// ── Events ──────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookEvent {
+    KeyCreated,
+    KeyRevoked,
+    QuotaUpdated,
+    BudgetExceeded,
+    ProviderDown,
+}
+
+/// Every event type a webhook can subscribe to.
+pub const EVENT_TYPES: [&str; 5] = [
+    "key.created",
+    "key.revoked",
+    "quota.updated",
+    "budget.exceeded",
+    "provider.down",
+];
+
+impl WebhookEvent {
+    pub fn parse(event: &str) -> Option<Self> {
+        match event {
+            "key.created" => Some(Self::KeyCreated),
+            "key.revoked" => Some(Self::KeyRevoked),
+            "quota.updated" => Some(Self::QuotaUpdated),
+            "budget.exceeded" => Some(Self::BudgetExceeded),
+            "provider.down" => Some(Self::ProviderDown),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::KeyCreated => "key.created",
+            Self::KeyRevoked => "key.revoked",
+            Self::QuotaUpdated => "quota.updated",
+            Self::BudgetExceeded => "budget.exceeded",
+            Self::ProviderDown => "provider.down",
+        }
+    }
+}
+
+/// Check a webhook submitted through the admin API before it is stored.
+pub fn validate_webhook(webhook: &NewWebhook) -> Result<(), String> {
+    if !(webhook.url.starts_with("https://") || webhook.url.starts_with("http://")) {
+        return Err("url must be an http(s) URL".to_string());
+    }
+    if webhook.events.is_empty() {
+        return Err("events must not be empty".to_string());
+    }
+    if let Some(unknown) = webhook
+        .events
+        .iter()
+        .find(|e| WebhookEvent::parse(e).is_none())
+    {
+        return Err(format!(
+            "Unknown event '{}': expected one of {}",
+            unknown,
+            EVENT_TYPES.join(", ")
+        ));
+    }
+    if webhook.secret.len() < MIN_SECRET_LEN {
+        return Err(format!(
+            "secret must be at least {} characters",
+            MIN_SECRET_LEN
+        ));
+    }
+    Ok(())
+}
+
+// ── Queueing ────────────────────────────────────────────────────────────────
+
+/// Body delivered to `webhook` for `event`.
+pub fn payload(
+    webhook: &Webhook,
+    event: WebhookEvent,
+    data: &serde_json::Value,
+    occurred_at: DateTime<Utc>,
+) -> serde_json::Value {
+    json!({
+        "event": event.as_str(),
+        "team_id": webhook.team_id,
+        "occurred_at": occurred_at,
+        "data": data,
+    })
+}
+
+/// Queue `event` for every active webhook subscribed to it: those of
+/// `team_id`, or of every team with `None`.  Failures are logged rather
+/// than returned, so a webhook problem never fails the change behind it.
+pub async fn emit<D: Database>(
+    db: &D,
+    team_id: Option<&str>,
+    event: WebhookEvent,
+    data: serde_json::Value,
+) {
+    let webhooks = match db
+        .list_webhooks_for_event(team_id.map(str::to_string), event.as_str())
+        .await
+    {
+        Ok(webhooks) => webhooks,
+        Err(e) => {
+            tracing::warn!("Failed to look up {} webhooks: {:?}", event.as_str(), e);
+            return;
+        }
+    };
+    let now = Utc::now();
+    for webhook in &webhooks {
+        if let Err(e) = db
+            .enqueue_webhook_delivery(
+                &webhook.id,
+                event.as_str(),
+                &payload(webhook, event, &data, now),
+            )
+            .await
+        {
+            tracing::warn!(
+                "Failed to queue {} for webhook {}: {:?}",
+                event.as_str(),
+                webhook.id,
+                e
+            );
+        }
+    }
+}
+
+// ── Delivery ────────────────────────────────────────────────────────────────
+
+fn hmac_sha256_hex(secret: &[u8], message: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+    hex::encode(hmac::sign(&key, message).as_ref())
+}
+
+/// `X-HyperInfer-Signature` value for `body` sent at `timestamp`.
+pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let signed = format!("{}.{}", timestamp, body);
+    format!(
+        "t={},v1={}",
+        timestamp,
+        hmac_sha256_hex(secret.as_bytes(), signed.as_bytes())
+    )
+}
+
+/// Wait before the next attempt once `attempts` have failed: 30 seconds,
+/// doubling per attempt, capped at an hour.
+pub fn retry_delay(attempts: i32) -> Duration {
+    let doublings = u32::try_from(attempts.saturating_sub(1)).unwrap_or(0);
+    FIRST_RETRY_DELAY
+        .checked_mul(2u32.saturating_pow(doublings))
+        .map_or(MAX_RETRY_DELAY, |delay| delay.min(MAX_RETRY_DELAY))
+}
+
+pub struct WebhookDispatcher<D: Database> {
+    db: D,
+    http: reqwest::Client,
+}
+
+impl<D: Database> WebhookDispatcher<D> {
+    pub fn new(db: D) -> Self {
+        // Redirects are not followed: a receiver could otherwise bounce the
+        // signed payload to an address that was never registered.
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap_or_default();
+        Self { db, http }
+    }
+
+    /// Send due deliveries on `interval` until `cancel` fires, skipping
+    /// ticks while this replica is not the scheduler leader.
+    pub fn spawn(
+        self,
+        interval: Duration,
+        leader: Leadership,
+        cancel: CancellationToken,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = ticker.tick() => {
+                        if !leader.is_leader() {
+                            continue;
+                        }
+                        if let Err(e) = self.dispatch_once(Utc::now()).await {
+                            tracing::error!("Webhook dispatch failed: {:?}", e);
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    /// Attempt every delivery due by `now`, up to [`MAX_IN_FLIGHT`] at a
+    /// time.  Returns the number delivered.
+    pub async fn dispatch_once(&self, now: DateTime<Utc>) -> Result<usize, DbError> {
+        let deliveries = self.db.list_due_webhook_deliveries(now, BATCH_SIZE).await?;
+        let mut webhooks: HashMap<String, Option<Webhook>> = HashMap::new();
+        for delivery in &deliveries {
+            if !webhooks.contains_key(&delivery.webhook_id) {
+                let webhook = self.db.get_webhook(&delivery.webhook_id).await?;
+                webhooks.insert(delivery.webhook_id.clone(), webhook);
+            }
+        }
+        let webhooks = &webhooks;
+        let delivered = stream::iter(deliveries)
+            .map(|delivery| async move {
+                let webhook = webhooks[&delivery.webhook_id].as_ref();
+                match self.attempt(webhook, &delivery, now).await {
+                    Ok(delivered) => usize::from(delivered),
+                    Err(e) => {
+                        tracing::warn!(
+                            "Failed to record webhook delivery {}: {:?}",
+                            delivery.id,
+                            e
+                        );
+                        0
+                    }
+                }
+            })
+            .buffer_unordered(MAX_IN_FLIGHT)
+            .fold(0, |total, delivered| async move { total + delivered })
+            .await;
+        Ok(delivered)
+    }
+
+    async fn attempt(
+        &self,
+        webhook: Option<&Webhook>,
+        delivery: &WebhookDelivery,
+        now: DateTime<Utc>,
+    ) -> Result<bool, DbError> {
+        let Some(webhook) = webhook.filter(|w| w.is_active) else {
+            self.db
+                .record_webhook_attempt(
+                    &delivery.id,
+                    "failed",
+                    None,
+                    Some("Webhook is disabled".to_string()),
+                    None,
+                )
+                .await?;
+            return Ok(false);
+        };
+
+        let body = delivery.payload.to_string();
+        let result = self
+            .http
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, &delivery.event)
+            .header(DELIVERY_HEADER, &delivery.id)
+            .header(
+                SIGNATURE_HEADER,
+                signature(&webhook.secret, now.timestamp(), &body),
+            )
+            .body(body)
+            .send()
+            .await;
+        let (response_status, error) = match result {
+            Ok(response) if response.status().is_success() => {
+                self.db
+                    .record_webhook_attempt(
+                        &delivery.id,
+                        "delivered",
+                        Some(i32::from(response.status().as_u16())),
+                        None,
+                        None,
+                    )
+                    .await?;
+                return Ok(true);
+            }
+            Ok(response) => (
+                Some(i32::from(response.status().as_u16())),
+                format!("Receiver answered {}", response.status()),
+            ),
+            Err(e) => (None, e.to_string()),
+        };
+
+        let attempts = delivery.attempts + 1;
+        let next_attempt_at = (attempts < MAX_ATTEMPTS)
+            .then(|| chrono::Duration::from_std(retry_delay(attempts)).ok())
+            .flatten()
+            .map(|delay| now + delay);
+        tracing::warn!(
+            "Webhook delivery {} to {} failed (attempt {} of {}): {}",
+            delivery.id,
+            webhook.url,
+            attempts,
+            MAX_ATTEMPTS,
+            error
+        );
+        self.db
+            .record_webhook_attempt(
+                &delivery.id,
+                if next_attempt_at.is_some() {
+                    "pending"
+                } else {
+                    "failed"
+                },
+                response_status,
+                Some(error),
+                next_attempt_at,
+            )
+            .await?;
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_webhook() -> NewWebhook {
+        NewWebhook {
+            team_id: "team-1".to_string(),
+            url: "https://hooks.example.com/hyperinfer".to_string(),
+            events: vec!["key.created".to_string(), "budget.exceeded".to_string()],
+            secret: "0123456789abcdef".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_event_round_trip() {
+        for event in EVENT_TYPES {
+            assert_eq!(WebhookEvent::parse(event).unwrap().as_str(), event);
+        }
+        assert_eq!(WebhookEvent::parse("key.rotated"), None);
+    }
+
+    #[test]
+    fn test_validate_webhook() {
+        assert!(validate_webhook(&new_webhook()).is_ok());
+
+        let mut webhook = new_webhook();
+        webhook.url = "ftp://hooks.example.com".to_string();
+        assert!(validate_webhook(&webhook).is_err());
+
+        let mut webhook = new_webhook();
+        webhook.events.clear();
+        assert!(validate_webhook(&webhook).is_err());
+
+        let mut webhook = new_webhook();
+        webhook.events.push("key.rotated".to_string());
+        assert!(validate_webhook(&webhook)
+            .unwrap_err()
+            .contains("key.rotated"));
+
+        let mut webhook = new_webhook();
+        webhook.secret = "short".to_string();
+        assert!(validate_webhook(&webhook).is_err());
+    }
+
+    #[test]
+    fn test_hmac_sha256_matches_rfc_4231() {
+        assert_eq!(
+            hmac_sha256_hex(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_signature_covers_timestamp_and_body() {
+        let signed = signature(
+            "0123456789abcdef",
+            1_700_000_000,
+            r#"{"event":"key.created"}"#,
+        );
+        assert!(signed.starts_with("t=1700000000,v1="));
+        assert_eq!(
+            signed,
+            format!(
+                "t=1700000000,v1={}",
+                hmac_sha256_hex(
+                    b"0123456789abcdef",
+                    br#"1700000000.{"event":"key.created"}"#
+                )
+            )
+        );
+        assert_ne!(
+            signed,
+            signature(
+                "0123456789abcdef",
+                1_700_000_001,
+                r#"{"event":"key.created"}"#
+            )
+        );
+    }
+
+    #[test]
+    fn test_retry_delay_doubles_up_to_cap() {
+        assert_eq!(retry_delay(1), Duration::from_secs(30));
+        assert_eq!(retry_delay(2), Duration::from_secs(60));
+        assert_eq!(retry_delay(4), Duration::from_secs(240));
+        assert_eq!(retry_delay(MAX_ATTEMPTS), Duration::from_secs(3600));
+        assert_eq!(retry_delay(i32::MAX), Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn test_payload_wraps_event_data() {
+        let webhook = Webhook {
+            id: "hook-1".to_string(),
+            team_id: "team-1".to_string(),
+            url: "https://hooks.example.com/hyperinfer".to_string(),
+            events: vec!["quota.updated".to_string()],
+            secret: "0123456789abcdef".to_string(),
+            is_active: true,
+            created_at: Utc::now(),
+        };
+        let body = payload(
+            &webhook,
+            WebhookEvent::QuotaUpdated,
+            &json!({"rpm_limit": 60}),
+            Utc::now(),
+        );
+        assert_eq!(body["event"], "quota.updated");
+        assert_eq!(body["team_id"], "team-1");
+        assert_eq!(body["data"]["rpm_limit"], 60);
+    }
+}
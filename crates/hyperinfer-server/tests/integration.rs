@@ -1,6 +1,7 @@
-use hyperinfer_core::Database;
+use hyperinfer_core::{Database, Role, UsageEvent};
 use hyperinfer_server::SqlxDb;
 use sqlx::postgres::PgPoolOptions;
+use std::collections::HashMap;
 use testcontainers::ImageExt;
 use testcontainers::{runners::AsyncRunner, ContainerAsync};
 use testcontainers_modules::postgres::Postgres;
@@ -42,6 +43,93 @@ async fn setup_test_db() -> (SqlxDb, ContainerAsync<Postgres>) {
     .await
     .expect("Failed to run migration 003");
 
+    sqlx::raw_sql(include_str!("../migrations/004_alerts.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 004");
+
+    sqlx::raw_sql(include_str!("../migrations/005_budget_policies.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 005");
+
+    sqlx::raw_sql(include_str!("../migrations/006_billing_periods.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 006");
+    sqlx::raw_sql(include_str!("../migrations/007_model_prices.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 007");
+    sqlx::raw_sql(include_str!("../migrations/008_virtual_keys.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 008");
+    sqlx::raw_sql(include_str!("../migrations/009_usage_metadata.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 009");
+    sqlx::raw_sql(include_str!("../migrations/010_soft_delete.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 010");
+    sqlx::raw_sql(include_str!("../migrations/011_organizations.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 011");
+    sqlx::raw_sql(include_str!("../migrations/012_roles.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 012");
+    sqlx::raw_sql(include_str!("../migrations/013_api_key_last_used.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 013");
+    sqlx::raw_sql(include_str!("../migrations/014_anomaly_alerts.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 014");
+    sqlx::raw_sql(include_str!("../migrations/015_team_data_region.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 015");
+    sqlx::raw_sql(include_str!("../migrations/016_webhooks.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 016");
+    sqlx::raw_sql(include_str!("../migrations/017_report_subscriptions.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 017");
+    sqlx::raw_sql(include_str!("../migrations/018_quota_templates.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 018");
+    sqlx::raw_sql(include_str!("../migrations/019_usage_idempotency.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 019");
+    sqlx::raw_sql(include_str!("../migrations/020_team_features.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 020");
+    sqlx::raw_sql(include_str!("../migrations/021_team_param_clamps.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 021");
+    sqlx::raw_sql(include_str!("../migrations/022_team_output_transforms.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 022");
+    sqlx::raw_sql(include_str!("../migrations/023_conversations.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 023");
+    sqlx::raw_sql(include_str!("../migrations/024_evals.sql"))
+        .execute(&pool)
+        .await
+        .expect("Failed to run migration 024");
+
     (SqlxDb::new(pool), postgres)
 }
 
@@ -75,11 +163,11 @@ async fn test_database_create_and_get_user() {
         .expect("Failed to create team");
 
     let user = db
-        .create_user(&team.id, "test@example.com", "admin")
+        .create_user(&team.id, "test@example.com", Role::Admin)
         .await
         .expect("Failed to create user");
     assert_eq!(user.email, "test@example.com");
-    assert_eq!(user.role, "admin");
+    assert_eq!(user.role, Role::Admin);
 
     let fetched = db
         .get_user(&user.id)
@@ -100,7 +188,7 @@ async fn test_database_create_and_get_api_key() {
         .expect("Failed to create team");
 
     let user = db
-        .create_user(&team.id, "test@example.com", "admin")
+        .create_user(&team.id, "test@example.com", Role::Admin)
         .await
         .expect("Failed to create user");
 
@@ -262,12 +350,12 @@ async fn test_duplicate_user_email() {
         .await
         .expect("Failed to create team");
 
-    db.create_user(&team.id, "unique@example.com", "admin")
+    db.create_user(&team.id, "unique@example.com", Role::Admin)
         .await
         .expect("Failed to create first user");
 
     let result = db
-        .create_user(&team.id, "unique@example.com", "member")
+        .create_user(&team.id, "unique@example.com", Role::Member)
         .await;
     assert!(result.is_err(), "Should fail on duplicate user email");
 }
@@ -282,7 +370,7 @@ async fn test_duplicate_api_key_hash() {
         .expect("Failed to create team");
 
     let user = db
-        .create_user(&team.id, "test@example.com", "admin")
+        .create_user(&team.id, "test@example.com", Role::Admin)
         .await
         .expect("Failed to create user");
 
@@ -351,7 +439,7 @@ async fn test_create_user_invalid_team_fk() {
         .create_user(
             "00000000-0000-0000-0000-000000000000",
             "test@example.com",
-            "admin",
+            Role::Admin,
         )
         .await;
     assert!(result.is_err(), "Should fail on invalid team foreign key");
@@ -387,7 +475,7 @@ async fn test_create_api_key_invalid_team_fk() {
         .expect("Failed to create team");
 
     let user = db
-        .create_user(&team.id, "test@example.com", "admin")
+        .create_user(&team.id, "test@example.com", Role::Admin)
         .await
         .expect("Failed to create user");
 
@@ -439,7 +527,7 @@ async fn test_record_usage_success() {
         .expect("Failed to create team");
 
     let user = db
-        .create_user(&team.id, "test@example.com", "admin")
+        .create_user(&team.id, "test@example.com", Role::Admin)
         .await
         .expect("Failed to create user");
 
@@ -449,7 +537,15 @@ async fn test_record_usage_success() {
         .expect("Failed to create API key");
 
     let usage_log = db
-        .record_usage(&team.id, &api_key.id, "gpt-4", 100, 50, 250)
+        .record_usage(
+            &team.id,
+            &api_key.id,
+            "gpt-4",
+            100,
+            50,
+            250,
+            &HashMap::new(),
+        )
         .await
         .expect("Failed to record usage");
 
@@ -471,7 +567,7 @@ async fn test_record_usage_zero_tokens() {
         .expect("Failed to create team");
 
     let user = db
-        .create_user(&team.id, "test@example.com", "admin")
+        .create_user(&team.id, "test@example.com", Role::Admin)
         .await
         .expect("Failed to create user");
 
@@ -481,7 +577,7 @@ async fn test_record_usage_zero_tokens() {
         .expect("Failed to create API key");
 
     let usage_log = db
-        .record_usage(&team.id, &api_key.id, "gpt-4", 0, 0, 0)
+        .record_usage(&team.id, &api_key.id, "gpt-4", 0, 0, 0, &HashMap::new())
         .await
         .expect("Failed to record usage");
 
@@ -500,7 +596,7 @@ async fn test_record_usage_large_values() {
         .expect("Failed to create team");
 
     let user = db
-        .create_user(&team.id, "test@example.com", "admin")
+        .create_user(&team.id, "test@example.com", Role::Admin)
         .await
         .expect("Failed to create user");
 
@@ -510,7 +606,15 @@ async fn test_record_usage_large_values() {
         .expect("Failed to create API key");
 
     let usage_log = db
-        .record_usage(&team.id, &api_key.id, "gpt-4", i32::MAX, i32::MAX, i64::MAX)
+        .record_usage(
+            &team.id,
+            &api_key.id,
+            "gpt-4",
+            i32::MAX,
+            i32::MAX,
+            i64::MAX,
+            &HashMap::new(),
+        )
         .await
         .expect("Failed to record usage");
 
@@ -529,7 +633,7 @@ async fn test_record_usage_multiple_logs() {
         .expect("Failed to create team");
 
     let user = db
-        .create_user(&team.id, "test@example.com", "admin")
+        .create_user(&team.id, "test@example.com", Role::Admin)
         .await
         .expect("Failed to create user");
 
@@ -540,12 +644,28 @@ async fn test_record_usage_multiple_logs() {
 
     // Record multiple usage logs
     let log1 = db
-        .record_usage(&team.id, &api_key.id, "gpt-4", 100, 50, 250)
+        .record_usage(
+            &team.id,
+            &api_key.id,
+            "gpt-4",
+            100,
+            50,
+            250,
+            &HashMap::new(),
+        )
         .await
         .expect("Failed to record first usage");
 
     let log2 = db
-        .record_usage(&team.id, &api_key.id, "gpt-3.5-turbo", 200, 100, 150)
+        .record_usage(
+            &team.id,
+            &api_key.id,
+            "gpt-3.5-turbo",
+            200,
+            100,
+            150,
+            &HashMap::new(),
+        )
         .await
         .expect("Failed to record second usage");
 
@@ -566,7 +686,7 @@ async fn test_record_usage_invalid_team_id() {
         .expect("Failed to create team");
 
     let user = db
-        .create_user(&team.id, "test@example.com", "admin")
+        .create_user(&team.id, "test@example.com", Role::Admin)
         .await
         .expect("Failed to create user");
 
@@ -583,6 +703,7 @@ async fn test_record_usage_invalid_team_id() {
             100,
             50,
             250,
+            &HashMap::new(),
         )
         .await;
 
@@ -606,6 +727,7 @@ async fn test_record_usage_invalid_api_key_id() {
             100,
             50,
             250,
+            &HashMap::new(),
         )
         .await;
 
@@ -620,7 +742,15 @@ async fn test_record_usage_invalid_uuid_format() {
     let (db, _container) = setup_test_db().await;
 
     let result = db
-        .record_usage("not-a-uuid", "also-not-a-uuid", "gpt-4", 100, 50, 250)
+        .record_usage(
+            "not-a-uuid",
+            "also-not-a-uuid",
+            "gpt-4",
+            100,
+            50,
+            250,
+            &HashMap::new(),
+        )
         .await;
 
     assert!(result.is_err(), "Should fail with invalid UUID format");
@@ -636,7 +766,7 @@ async fn test_record_usage_different_models() {
         .expect("Failed to create team");
 
     let user = db
-        .create_user(&team.id, "test@example.com", "admin")
+        .create_user(&team.id, "test@example.com", Role::Admin)
         .await
         .expect("Failed to create user");
 
@@ -649,7 +779,7 @@ async fn test_record_usage_different_models() {
 
     for model in models {
         let log = db
-            .record_usage(&team.id, &api_key.id, model, 100, 50, 200)
+            .record_usage(&team.id, &api_key.id, model, 100, 50, 200, &HashMap::new())
             .await
             .expect("Failed to record usage");
         assert_eq!(log.model, model);
@@ -666,7 +796,7 @@ async fn test_get_api_key_by_hash() {
         .expect("Failed to create team");
 
     let user = db
-        .create_user(&team.id, "test@example.com", "admin")
+        .create_user(&team.id, "test@example.com", Role::Admin)
         .await
         .expect("Failed to create user");
 
@@ -696,7 +826,7 @@ async fn test_get_api_key_by_hash_inactive() {
         .expect("Failed to create team");
 
     let user = db
-        .create_user(&team.id, "test@example.com", "admin")
+        .create_user(&team.id, "test@example.com", Role::Admin)
         .await
         .expect("Failed to create user");
 
@@ -726,3 +856,852 @@ async fn test_get_api_key_by_hash_not_found() {
 
     assert!(result.is_none(), "Should return None for nonexistent hash");
 }
+
+#[tokio::test]
+async fn test_model_usage_and_request_errors_since() {
+    let (db, _container) = setup_test_db().await;
+
+    let team = db
+        .create_team("Test Team", 10000)
+        .await
+        .expect("Failed to create team");
+    let user = db
+        .create_user(&team.id, "test@example.com", Role::Admin)
+        .await
+        .expect("Failed to create user");
+    let api_key = db
+        .create_api_key("test_hash", &user.id, &team.id, None)
+        .await
+        .expect("Failed to create API key");
+
+    let since = chrono::Utc::now() - chrono::Duration::minutes(1);
+    db.record_usage(
+        &team.id,
+        &api_key.id,
+        "gpt-4",
+        100,
+        50,
+        200,
+        &HashMap::new(),
+    )
+    .await
+    .expect("Failed to record usage");
+    db.record_usage(&team.id, &api_key.id, "gpt-4", 10, 5, 200, &HashMap::new())
+        .await
+        .expect("Failed to record usage");
+    db.record_request_error(
+        &team.id,
+        &api_key.id,
+        "gpt-4",
+        Some("openai".to_string()),
+        "HTTP error: 503",
+    )
+    .await
+    .expect("Failed to record request error");
+
+    let usage = db
+        .get_model_usage_since(&team.id, since)
+        .await
+        .expect("Failed to get model usage");
+    assert_eq!(usage.len(), 1);
+    assert_eq!(usage[0].requests, 2);
+    assert_eq!(usage[0].input_tokens, 110);
+    assert_eq!(usage[0].output_tokens, 55);
+
+    let team_errors = db
+        .count_request_errors_since(Some(team.id.clone()), None, since)
+        .await
+        .expect("Failed to count errors");
+    assert_eq!(team_errors, 1);
+
+    let anthropic_errors = db
+        .count_request_errors_since(None, Some("anthropic".to_string()), since)
+        .await
+        .expect("Failed to count errors");
+    assert_eq!(anthropic_errors, 0);
+}
+
+#[tokio::test]
+async fn test_redelivered_usage_events_are_recorded_once() {
+    let (db, _container) = setup_test_db().await;
+
+    let team = db
+        .create_team("Test Team", 10000)
+        .await
+        .expect("Failed to create team");
+    let user = db
+        .create_user(&team.id, "test@example.com", Role::Admin)
+        .await
+        .expect("Failed to create user");
+    let api_key = db
+        .create_api_key("test_hash", &user.id, &team.id, None)
+        .await
+        .expect("Failed to create API key");
+
+    let since = chrono::Utc::now() - chrono::Duration::minutes(1);
+    let usage = UsageEvent {
+        team_id: team.id.clone(),
+        api_key_id: api_key.id.clone(),
+        entry_id: Some("hyperinfer:telemetry/1-0".to_string()),
+        model: "gpt-4".to_string(),
+        input_tokens: 100,
+        output_tokens: 50,
+        ..Default::default()
+    };
+    let error = UsageEvent {
+        entry_id: Some("hyperinfer:telemetry/2-0".to_string()),
+        provider: Some("openai".to_string()),
+        error: Some("HTTP error: 503".to_string()),
+        ..usage.clone()
+    };
+    for event in [&usage, &error] {
+        assert!(db.record_usage_event(event).await.unwrap());
+        assert!(!db.record_usage_event(event).await.unwrap());
+    }
+    // The same entry id on another shard is another entry.
+    let other_shard = UsageEvent {
+        entry_id: Some("hyperinfer:telemetry:1/1-0".to_string()),
+        ..usage.clone()
+    };
+    assert!(db.record_usage_event(&other_shard).await.unwrap());
+
+    let usage = db
+        .get_model_usage_since(&team.id, since)
+        .await
+        .expect("Failed to get model usage");
+    assert_eq!(usage[0].requests, 2);
+    assert_eq!(usage[0].input_tokens, 200);
+    let errors = db
+        .count_request_errors_since(Some(team.id.clone()), None, since)
+        .await
+        .expect("Failed to count errors");
+    assert_eq!(errors, 1);
+}
+
+#[tokio::test]
+async fn test_alert_lifecycle() {
+    let (db, _container) = setup_test_db().await;
+
+    let team = db
+        .create_team("Test Team", 10000)
+        .await
+        .expect("Failed to create team");
+    let rule = db
+        .create_alert_rule(&hyperinfer_core::NewAlertRule {
+            team_id: Some(team.id.clone()),
+            kind: "budget".to_string(),
+            threshold: 0.8,
+            provider: None,
+            window_minutes: 15,
+            webhook_url: "https://example.com/hook".to_string(),
+            webhook_format: "json".to_string(),
+        })
+        .await
+        .expect("Failed to create alert rule");
+    assert!(rule.is_active);
+    assert_eq!(db.list_alert_rules().await.unwrap().len(), 1);
+
+    let alert = db
+        .create_alert(&rule, "80% of budget used", 0.85)
+        .await
+        .expect("Failed to create alert");
+    assert!(!alert.delivered);
+
+    // Only one open alert per rule.
+    let duplicate = db.create_alert(&rule, "still over", 0.9).await;
+    assert!(matches!(
+        duplicate,
+        Err(hyperinfer_core::DbError::UniqueViolation(_))
+    ));
+
+    db.mark_alert_delivered(&alert.id)
+        .await
+        .expect("Failed to mark delivered");
+    let open = db
+        .get_open_alert(&rule.id)
+        .await
+        .expect("Failed to get open alert")
+        .expect("Alert should be open");
+    assert!(open.delivered);
+
+    db.resolve_alert(&alert.id)
+        .await
+        .expect("Failed to resolve alert");
+    assert!(db.get_open_alert(&rule.id).await.unwrap().is_none());
+
+    let history = db
+        .list_alerts(Some(team.id.clone()), 10)
+        .await
+        .expect("Failed to list alerts");
+    assert_eq!(history.len(), 1);
+    assert!(history[0].resolved_at.is_some());
+}
+
+#[tokio::test]
+async fn test_budget_policy_upsert_and_enforcement() {
+    let (db, _container) = setup_test_db().await;
+
+    let team = db
+        .create_team("Test Team", 10000)
+        .await
+        .expect("Failed to create team");
+    let user = db
+        .create_user(&team.id, "test@example.com", Role::Admin)
+        .await
+        .expect("Failed to create user");
+    db.create_api_key("test_hash", &user.id, &team.id, None)
+        .await
+        .expect("Failed to create API key");
+
+    let keys = db
+        .list_api_keys_for_team(&team.id)
+        .await
+        .expect("Failed to list keys");
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0].key_hash, "test_hash");
+
+    db.upsert_budget_policy(&team.id, "warn", None)
+        .await
+        .expect("Failed to create budget policy");
+    let policy = db
+        .upsert_budget_policy(&team.id, "throttle", Some(5))
+        .await
+        .expect("Failed to update budget policy");
+    assert_eq!(policy.action, "throttle");
+    assert_eq!(policy.throttle_rpm, Some(5));
+    assert_eq!(db.list_budget_policies().await.unwrap().len(), 1);
+
+    db.set_budget_enforcement(&team.id, Some("throttle".to_string()))
+        .await
+        .expect("Failed to set enforcement");
+    let enforced = db
+        .get_budget_policy(&team.id)
+        .await
+        .expect("Failed to get budget policy")
+        .expect("Budget policy not found");
+    assert_eq!(enforced.enforced_action.as_deref(), Some("throttle"));
+    assert!(enforced.enforced_at.is_some());
+
+    db.set_budget_enforcement(&team.id, None)
+        .await
+        .expect("Failed to clear enforcement");
+    let lifted = db.get_budget_policy(&team.id).await.unwrap().unwrap();
+    assert!(lifted.enforced_action.is_none());
+    assert!(lifted.enforced_at.is_none());
+}
+
+#[tokio::test]
+async fn test_team_billing_settings_and_periods() {
+    let (db, _container) = setup_test_db().await;
+
+    let team = db
+        .create_team("Test Team", 10000)
+        .await
+        .expect("Failed to create team");
+    assert_eq!(team.billing_anchor_day, 1);
+    assert_eq!(team.billing_timezone, "UTC");
+
+    let updated = db
+        .update_team_billing(&team.id, 15, "Europe/Berlin")
+        .await
+        .expect("Failed to update billing settings");
+    assert_eq!(updated.billing_anchor_day, 15);
+    assert_eq!(updated.billing_timezone, "Europe/Berlin");
+    assert_eq!(db.list_teams().await.unwrap().len(), 1);
+
+    let start = chrono::Utc::now() - chrono::Duration::days(30);
+    let end = chrono::Utc::now() - chrono::Duration::days(1);
+    let period = hyperinfer_core::BillingPeriod {
+        id: String::new(),
+        team_id: team.id.clone(),
+        period_start: start,
+        period_end: end,
+        requests: 3,
+        input_tokens: 300,
+        output_tokens: 150,
+        spend_cents: 12.5,
+        budget_cents: 10000,
+        closed_at: chrono::Utc::now(),
+    };
+    let recorded = db
+        .record_billing_period(&period)
+        .await
+        .expect("Failed to record billing period")
+        .expect("Period should be newly recorded");
+    assert_eq!(recorded.requests, 3);
+
+    let duplicate = db
+        .record_billing_period(&period)
+        .await
+        .expect("Failed to record billing period");
+    assert!(duplicate.is_none());
+
+    let periods = db
+        .list_billing_periods(&team.id, 12)
+        .await
+        .expect("Failed to list billing periods");
+    assert_eq!(periods.len(), 1);
+    assert_eq!(periods[0].spend_cents, 12.5);
+
+    let usage = db
+        .get_model_usage_between(&team.id, start, end)
+        .await
+        .expect("Failed to get usage");
+    assert!(usage.is_empty());
+}
+
+#[tokio::test]
+async fn test_model_price_crud() {
+    let (db, _container) = setup_test_db().await;
+
+    let new_price = hyperinfer_core::NewModelPrice {
+        model: "gpt-4o".to_string(),
+        provider: "openai".to_string(),
+        input_per_mtok: 2.5,
+        output_per_mtok: 10.0,
+        effective_from: None,
+    };
+    let created = db
+        .create_model_price(&new_price)
+        .await
+        .expect("Failed to create model price");
+    assert_eq!(created.model, "gpt-4o");
+
+    let fetched = db
+        .get_model_price(&created.id)
+        .await
+        .expect("Failed to get model price")
+        .expect("Model price not found");
+    assert_eq!(fetched.input_per_mtok, 2.5);
+
+    let updated = db
+        .update_model_price(
+            &created.id,
+            &hyperinfer_core::NewModelPrice {
+                input_per_mtok: 2.0,
+                ..new_price.clone()
+            },
+        )
+        .await
+        .expect("Failed to update model price");
+    assert_eq!(updated.input_per_mtok, 2.0);
+    assert_eq!(updated.effective_from, created.effective_from);
+    assert_eq!(db.list_model_prices().await.unwrap().len(), 1);
+
+    db.delete_model_price(&created.id)
+        .await
+        .expect("Failed to delete model price");
+    assert!(db.list_model_prices().await.unwrap().is_empty());
+    assert!(matches!(
+        db.delete_model_price(&created.id).await,
+        Err(hyperinfer_core::DbError::NotFound)
+    ));
+}
+
+#[tokio::test]
+async fn test_api_key_virtual_key_metadata() {
+    let (db, _container) = setup_test_db().await;
+
+    let team = db
+        .create_team("Test Team", 10000)
+        .await
+        .expect("Failed to create team");
+    let user = db
+        .create_user(&team.id, "vk@example.com", Role::Member)
+        .await
+        .expect("Failed to create user");
+    let key = db
+        .create_api_key("vk-hash", &user.id, &team.id, None)
+        .await
+        .expect("Failed to create API key");
+    assert!(key.tags.is_empty());
+    assert!(key.allowed_models.is_empty());
+    assert!(key.budget_cents.is_none());
+
+    let metadata = hyperinfer_core::ApiKeyMetadata {
+        tags: vec!["prod".to_string(), "search".to_string()],
+        allowed_models: vec!["gpt-4o-mini".to_string()],
+        budget_cents: Some(2500),
+    };
+    let updated = db
+        .update_api_key_metadata(&key.id, &metadata)
+        .await
+        .expect("Failed to update API key metadata");
+    assert_eq!(updated.tags, metadata.tags);
+    assert_eq!(updated.allowed_models, metadata.allowed_models);
+    assert_eq!(updated.budget_cents, Some(2500));
+
+    let active = db
+        .list_active_api_keys()
+        .await
+        .expect("Failed to list active API keys");
+    assert_eq!(active.len(), 1);
+    let virtual_key = hyperinfer_core::VirtualKey::from(active[0].clone());
+    assert_eq!(virtual_key.team_id, team.id);
+    assert!(virtual_key.allows_model("gpt-4o-mini"));
+    assert!(!virtual_key.allows_model("gpt-4o"));
+
+    assert!(matches!(
+        db.update_api_key_metadata("00000000-0000-0000-0000-000000000000", &metadata)
+            .await,
+        Err(hyperinfer_core::DbError::NotFound)
+    ));
+}
+
+#[tokio::test]
+async fn test_usage_metadata_group_by_tag() {
+    let (db, _container) = setup_test_db().await;
+
+    let team = db
+        .create_team("Test Team", 10000)
+        .await
+        .expect("Failed to create team");
+    let user = db
+        .create_user(&team.id, "tags@example.com", Role::Member)
+        .await
+        .expect("Failed to create user");
+    let api_key = db
+        .create_api_key("tags-hash", &user.id, &team.id, None)
+        .await
+        .expect("Failed to create API key");
+
+    let search = HashMap::from([
+        ("feature".to_string(), "search".to_string()),
+        ("env".to_string(), "prod".to_string()),
+    ]);
+    let log = db
+        .record_usage(&team.id, &api_key.id, "gpt-4", 100, 50, 200, &search)
+        .await
+        .expect("Failed to record usage");
+    assert_eq!(log.metadata, search);
+    db.record_usage(&team.id, &api_key.id, "gpt-4", 10, 5, 200, &search)
+        .await
+        .expect("Failed to record usage");
+    db.record_usage(&team.id, &api_key.id, "gpt-4", 1, 1, 200, &HashMap::new())
+        .await
+        .expect("Failed to record usage");
+
+    let start = chrono::Utc::now() - chrono::Duration::hours(1);
+    let end = chrono::Utc::now() + chrono::Duration::hours(1);
+    let mut usage = db
+        .get_tag_usage_between(&team.id, "feature", start, end)
+        .await
+        .expect("Failed to get tag usage");
+    usage.sort_by(|a, b| a.tag_value.cmp(&b.tag_value));
+    assert_eq!(usage.len(), 2);
+    assert_eq!(usage[0].tag_value, None);
+    assert_eq!(usage[0].requests, 1);
+    assert_eq!(usage[1].tag_value.as_deref(), Some("search"));
+    assert_eq!(usage[1].requests, 2);
+    assert_eq!(usage[1].input_tokens, 110);
+}
+
+#[tokio::test]
+async fn test_list_usage_logs_pages_in_order() {
+    let (db, _container) = setup_test_db().await;
+
+    let team = db
+        .create_team("Export Team", 10000)
+        .await
+        .expect("Failed to create team");
+    let other = db
+        .create_team("Other Team", 10000)
+        .await
+        .expect("Failed to create team");
+    let user = db
+        .create_user(&team.id, "export@example.com", Role::Member)
+        .await
+        .expect("Failed to create user");
+    let api_key = db
+        .create_api_key("export-hash", &user.id, &team.id, None)
+        .await
+        .expect("Failed to create API key");
+
+    for input_tokens in [1, 2, 3] {
+        db.record_usage(
+            &team.id,
+            &api_key.id,
+            "gpt-4",
+            input_tokens,
+            0,
+            100,
+            &HashMap::new(),
+        )
+        .await
+        .expect("Failed to record usage");
+    }
+    db.record_usage(&other.id, &api_key.id, "gpt-4", 9, 0, 100, &HashMap::new())
+        .await
+        .expect("Failed to record usage");
+
+    let start = chrono::Utc::now() - chrono::Duration::hours(1);
+    let end = chrono::Utc::now() + chrono::Duration::hours(1);
+    let first = db
+        .list_usage_logs(Some(team.id.clone()), start, end, 2, 0)
+        .await
+        .expect("Failed to list usage logs");
+    let second = db
+        .list_usage_logs(Some(team.id.clone()), start, end, 2, 2)
+        .await
+        .expect("Failed to list usage logs");
+    let tokens: Vec<_> = first
+        .iter()
+        .chain(&second)
+        .map(|log| log.input_tokens)
+        .collect();
+    assert_eq!(tokens, vec![1, 2, 3]);
+
+    let all = db
+        .list_usage_logs(None, start, end, 100, 0)
+        .await
+        .expect("Failed to list usage logs");
+    assert_eq!(all.len(), 4);
+}
+
+#[tokio::test]
+async fn test_list_model_aliases_across_teams() {
+    let (db, _container) = setup_test_db().await;
+
+    let team_a = db.create_team("Team A", 10000).await.unwrap();
+    let team_b = db.create_team("Team B", 10000).await.unwrap();
+    db.create_model_alias(&team_a.id, "fast", "gpt-4o-mini", "openai")
+        .await
+        .unwrap();
+    db.create_model_alias(&team_b.id, "fast", "claude-3-haiku", "anthropic")
+        .await
+        .unwrap();
+
+    let aliases = db.list_model_aliases().await.unwrap();
+    assert_eq!(aliases.len(), 2);
+    let target_for = |team_id: &str| {
+        aliases
+            .iter()
+            .find(|a| a.team_id == team_id)
+            .map(|a| a.target())
+    };
+    assert_eq!(
+        target_for(&team_a.id).as_deref(),
+        Some("openai/gpt-4o-mini")
+    );
+    assert_eq!(
+        target_for(&team_b.id).as_deref(),
+        Some("anthropic/claude-3-haiku")
+    );
+}
+
+#[tokio::test]
+async fn test_soft_delete_and_purge() {
+    let (db, _container) = setup_test_db().await;
+
+    let team = db
+        .create_team("Deleted Team", 10000)
+        .await
+        .expect("Failed to create team");
+    let user = db
+        .create_user(&team.id, "gone@example.com", Role::Member)
+        .await
+        .expect("Failed to create user");
+    let key = db
+        .create_api_key("soft-delete-hash", &user.id, &team.id, None)
+        .await
+        .expect("Failed to create API key");
+    db.record_usage(&team.id, &key.id, "gpt-4", 10, 5, 100, &HashMap::new())
+        .await
+        .expect("Failed to record usage");
+
+    db.delete_team(&team.id)
+        .await
+        .expect("Failed to delete team");
+    assert!(db.get_team(&team.id).await.unwrap().is_none());
+    assert!(!db
+        .list_teams()
+        .await
+        .unwrap()
+        .iter()
+        .any(|t| t.id == team.id));
+    assert!(db
+        .get_api_key_by_hash("soft-delete-hash")
+        .await
+        .unwrap()
+        .is_none());
+    assert!(matches!(
+        db.delete_team(&team.id).await,
+        Err(hyperinfer_core::DbError::NotFound)
+    ));
+
+    // Usage history survives until the purge.
+    let now = chrono::Utc::now();
+    let usage = db
+        .get_model_usage_since(&team.id, now - chrono::Duration::hours(1))
+        .await
+        .unwrap();
+    assert_eq!(usage.len(), 1);
+
+    // The name can be reused while the old team is kept.
+    db.create_team("Deleted Team", 500)
+        .await
+        .expect("Failed to reuse team name");
+
+    assert_eq!(
+        db.purge_deleted(now - chrono::Duration::days(1))
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        db.purge_deleted(now + chrono::Duration::seconds(1))
+            .await
+            .unwrap(),
+        1
+    );
+    let usage = db
+        .get_model_usage_since(&team.id, now - chrono::Duration::hours(1))
+        .await
+        .unwrap();
+    assert!(usage.is_empty());
+}
+
+#[tokio::test]
+async fn test_set_user_role_records_change() {
+    let (db, _container) = setup_test_db().await;
+
+    let team = db
+        .create_team("Role Team", 10000)
+        .await
+        .expect("Failed to create team");
+    let user = db
+        .create_user(&team.id, "promoted@example.com", Role::Viewer)
+        .await
+        .expect("Failed to create user");
+
+    let updated = db
+        .set_user_role(&user.id, Role::Admin, "owner@example.com")
+        .await
+        .expect("Failed to set role");
+    assert_eq!(updated.role, Role::Admin);
+    assert_eq!(
+        db.get_user(&user.id).await.unwrap().unwrap().role,
+        Role::Admin
+    );
+
+    let changes = db.list_role_changes(&user.id).await.unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].old_role, Role::Viewer);
+    assert_eq!(changes[0].new_role, Role::Admin);
+    assert_eq!(changes[0].changed_by, "owner@example.com");
+
+    db.delete_user(&user.id).await.unwrap();
+    assert!(matches!(
+        db.set_user_role(&user.id, Role::Member, "owner@example.com")
+            .await,
+        Err(hyperinfer_core::DbError::NotFound)
+    ));
+}
+
+#[tokio::test]
+async fn test_api_key_uses_and_stale_keys() {
+    let (db, _container) = setup_test_db().await;
+
+    let team = db
+        .create_team("Stale Team", 10000)
+        .await
+        .expect("Failed to create team");
+    let user = db
+        .create_user(&team.id, "keys@example.com", Role::Member)
+        .await
+        .expect("Failed to create user");
+    let used = db
+        .create_api_key("used_hash", &user.id, &team.id, None)
+        .await
+        .unwrap();
+    let unused = db
+        .create_api_key("unused_hash", &user.id, &team.id, None)
+        .await
+        .unwrap();
+    assert!(used.last_used_at.is_none());
+
+    let now = chrono::Utc::now();
+    let uses =
+        std::collections::HashMap::from([(used.id.clone(), now), ("not-a-uuid".to_string(), now)]);
+    assert_eq!(db.record_api_key_uses(&uses).await.unwrap(), 1);
+    // An older use does not move last_used_at back.
+    let older =
+        std::collections::HashMap::from([(used.id.clone(), now - chrono::Duration::days(1))]);
+    assert_eq!(db.record_api_key_uses(&older).await.unwrap(), 0);
+    let fetched = db.get_api_key(&used.id).await.unwrap().unwrap();
+    assert_eq!(
+        fetched.last_used_at.map(|t| t.timestamp_millis()),
+        Some(now.timestamp_millis())
+    );
+
+    let stale = db
+        .list_stale_api_keys(now + chrono::Duration::seconds(1))
+        .await
+        .unwrap();
+    assert_eq!(stale.len(), 2);
+    assert_eq!(stale[0].id, unused.id);
+    let stale = db
+        .list_stale_api_keys(now - chrono::Duration::seconds(1))
+        .await
+        .unwrap();
+    assert!(stale.is_empty());
+}
+
+#[tokio::test]
+async fn test_key_usage_buckets() {
+    let (db, _container) = setup_test_db().await;
+
+    let team = db
+        .create_team("Bucket Team", 10000)
+        .await
+        .expect("Failed to create team");
+    let user = db
+        .create_user(&team.id, "buckets@example.com", Role::Member)
+        .await
+        .expect("Failed to create user");
+    let api_key = db
+        .create_api_key("bucket_hash", &user.id, &team.id, None)
+        .await
+        .unwrap();
+    for model in ["gpt-4", "gpt-4", "claude-3"] {
+        db.record_usage(&team.id, &api_key.id, model, 10, 5, 100, &HashMap::new())
+            .await
+            .expect("Failed to record usage");
+    }
+
+    let now = chrono::Utc::now();
+    let end = now + chrono::Duration::minutes(1);
+    let buckets = db
+        .get_key_usage_buckets(&team.id, now - chrono::Duration::hours(1), end, 15)
+        .await
+        .unwrap();
+    assert!(buckets
+        .iter()
+        .all(|b| b.windows_ago == 0 && b.api_key_id == api_key.id));
+    // Rows are also split by hour of day, so add them up per model.
+    let mut requests: HashMap<String, i64> = HashMap::new();
+    for bucket in &buckets {
+        *requests.entry(bucket.model.clone()).or_default() += bucket.requests;
+    }
+    assert_eq!(requests["gpt-4"], 2);
+    assert_eq!(requests["claude-3"], 1);
+}
+
+#[tokio::test]
+async fn test_conversation_messages_keep_their_order() {
+    use hyperinfer_core::{ChatMessage, MessageRole, NewConversation};
+
+    let (db, _container) = setup_test_db().await;
+    let team = db
+        .create_team("Chat Team", 10000)
+        .await
+        .expect("Failed to create team");
+    let conversation = db
+        .create_conversation(&NewConversation {
+            team_id: team.id.clone(),
+            title: Some("Support".to_string()),
+        })
+        .await
+        .expect("Failed to create conversation");
+
+    let message = |role, content: &str| ChatMessage {
+        role,
+        content: content.to_string(),
+    };
+    db.append_conversation_messages(
+        &conversation.id,
+        &[
+            message(MessageRole::User, "hi"),
+            message(MessageRole::Assistant, "hello"),
+        ],
+    )
+    .await
+    .unwrap();
+    db.append_conversation_messages(&conversation.id, &[message(MessageRole::User, "bye")])
+        .await
+        .unwrap();
+
+    let messages = db
+        .list_conversation_messages(&conversation.id)
+        .await
+        .unwrap();
+    let contents: Vec<_> = messages.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, ["hi", "hello", "bye"]);
+    assert_eq!(messages[1].role, MessageRole::Assistant);
+    let fetched = db
+        .get_conversation(&conversation.id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(fetched.updated_at >= conversation.updated_at);
+
+    assert!(matches!(
+        db.append_conversation_messages(
+            &uuid::Uuid::new_v4().to_string(),
+            &[message(MessageRole::User, "lost")]
+        )
+        .await,
+        Err(hyperinfer_core::DbError::NotFound)
+    ));
+}
+
+#[tokio::test]
+async fn test_eval_run_aggregates_its_results() {
+    use hyperinfer_core::{
+        ChatMessage, EvalCriterion, EvalItem, EvalItemResult, MessageRole, NewEvalDataset,
+        NewEvalRun,
+    };
+
+    let (db, _container) = setup_test_db().await;
+    let team = db
+        .create_team("Eval Team", 10000)
+        .await
+        .expect("Failed to create team");
+    let item = EvalItem {
+        messages: vec![ChatMessage {
+            role: MessageRole::User,
+            content: "2+2?".to_string(),
+        }],
+        criteria: vec![EvalCriterion::Contains {
+            text: "4".to_string(),
+        }],
+    };
+    let dataset = db
+        .create_eval_dataset(&NewEvalDataset {
+            team_id: team.id.clone(),
+            name: "arithmetic".to_string(),
+            items: vec![item.clone(), item.clone()],
+        })
+        .await
+        .expect("Failed to create dataset");
+    assert_eq!(
+        db.get_eval_dataset(&dataset.id)
+            .await
+            .unwrap()
+            .unwrap()
+            .items,
+        vec![item.clone(), item.clone()]
+    );
+
+    let run = db
+        .create_eval_run(&NewEvalRun {
+            dataset_id: dataset.id.clone(),
+            model: "gpt-4".to_string(),
+            results: vec![
+                EvalItemResult::failed(1, "timeout".to_string(), 300),
+                EvalItemResult::scored(0, &item, "4".to_string(), 100),
+            ],
+        })
+        .await
+        .expect("Failed to create run");
+    assert_eq!(run.item_count, 2);
+    assert_eq!(run.passed_count, 1);
+    assert_eq!(run.mean_score, 0.5);
+    assert_eq!(run.mean_latency_ms, 200.0);
+
+    let results = db.list_eval_results(&run.id).await.unwrap();
+    let indexes: Vec<u32> = results.iter().map(|r| r.item_index).collect();
+    assert_eq!(indexes, [0, 1]);
+    let runs = db.list_eval_runs(&dataset.id).await.unwrap();
+    assert_eq!(runs, vec![run]);
+}